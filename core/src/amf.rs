@@ -0,0 +1,505 @@
+//! Action Message Format (AMF0) encoding and decoding
+//!
+//! AMF0 is the serialization format used by Flash Remoting, where requests and responses are
+//! wrapped in a packet of headers and messages. Only AMF0 is supported: values flagged as AMF3
+//! (via the `avmplus-object` marker) are rejected with `AmfError::Amf3`.
+
+use std::error::Error as StdError;
+use std::fmt::{self, Display, Formatter};
+
+const NUMBER: u8 = 0x00;
+const BOOLEAN: u8 = 0x01;
+const STRING: u8 = 0x02;
+const OBJECT: u8 = 0x03;
+const NULL: u8 = 0x05;
+const UNDEFINED: u8 = 0x06;
+const REFERENCE: u8 = 0x07;
+const ECMA_ARRAY: u8 = 0x08;
+const OBJECT_END: u8 = 0x09;
+const STRICT_ARRAY: u8 = 0x0A;
+const DATE: u8 = 0x0B;
+const LONG_STRING: u8 = 0x0C;
+const UNSUPPORTED: u8 = 0x0D;
+const XML_DOCUMENT: u8 = 0x0F;
+const TYPED_OBJECT: u8 = 0x10;
+const AVMPLUS_OBJECT: u8 = 0x11;
+
+/// The MIME type of Flash Remoting requests and responses.
+pub const CONTENT_TYPE: &str = "application/x-amf";
+
+/// A value that can be serialized as AMF0.
+///
+/// References are resolved while reading, so a decoded value is always a plain tree.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AmfValue {
+    Number(f64),
+    Bool(bool),
+    String(String),
+    Object(Vec<(String, AmfValue)>),
+    Null,
+    Undefined,
+    EcmaArray(Vec<(String, AmfValue)>),
+    StrictArray(Vec<AmfValue>),
+
+    /// A date, in milliseconds since the Unix epoch.
+    Date(f64),
+    Xml(String),
+    TypedObject(String, Vec<(String, AmfValue)>),
+}
+
+/// An error encountered while reading AMF data.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AmfError {
+    /// The data ended in the middle of a value.
+    UnexpectedEof,
+
+    /// A value used a type marker which can't be decoded.
+    UnsupportedMarker(u8),
+
+    /// A value was flagged as AMF3, which is not supported.
+    Amf3,
+
+    /// A reference pointed to an object which has not been read (yet).
+    InvalidReference(u16),
+
+    /// A string was not valid UTF-8.
+    InvalidUtf8,
+}
+
+impl Display for AmfError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            AmfError::UnexpectedEof => write!(f, "Unexpected end of AMF data"),
+            AmfError::UnsupportedMarker(marker) => {
+                write!(f, "Unsupported AMF0 type marker 0x{:02X}", marker)
+            }
+            AmfError::Amf3 => write!(f, "AMF3 data is not supported"),
+            AmfError::InvalidReference(index) => write!(f, "Invalid AMF0 reference {}", index),
+            AmfError::InvalidUtf8 => write!(f, "Invalid UTF-8 string in AMF data"),
+        }
+    }
+}
+
+impl StdError for AmfError {}
+
+/// A header of a remoting packet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Header {
+    pub name: String,
+    pub must_understand: bool,
+    pub value: AmfValue,
+}
+
+/// A message (body) of a remoting packet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    /// The method being called, or for a response, the response URI of the call followed by
+    /// `/onResult` or `/onStatus`.
+    pub target_uri: String,
+
+    /// The URI that the response to this message will be sent to.
+    pub response_uri: String,
+
+    pub value: AmfValue,
+}
+
+/// A Flash Remoting packet, as sent to and received from a gateway.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Packet {
+    pub version: u16,
+    pub headers: Vec<Header>,
+    pub messages: Vec<Message>,
+}
+
+impl Packet {
+    /// Parse a remoting packet.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, AmfError> {
+        let mut reader = Reader::new(data);
+        let version = reader.read_u16()?;
+
+        let header_count = reader.read_u16()?;
+        let mut headers = Vec::with_capacity(header_count.into());
+        for _ in 0..header_count {
+            let name = reader.read_utf8()?;
+            let must_understand = reader.read_u8()? != 0;
+            let _length = reader.read_u32()?;
+
+            // Each header and message has its own reference table.
+            reader.references.clear();
+            let value = reader.read_value()?;
+            headers.push(Header {
+                name,
+                must_understand,
+                value,
+            });
+        }
+
+        let message_count = reader.read_u16()?;
+        let mut messages = Vec::with_capacity(message_count.into());
+        for _ in 0..message_count {
+            let target_uri = reader.read_utf8()?;
+            let response_uri = reader.read_utf8()?;
+            let _length = reader.read_u32()?;
+
+            reader.references.clear();
+            let value = reader.read_value()?;
+            messages.push(Message {
+                target_uri,
+                response_uri,
+                value,
+            });
+        }
+
+        Ok(Self {
+            version,
+            headers,
+            messages,
+        })
+    }
+
+    /// Serialize this packet.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![];
+        write_u16(&mut out, self.version);
+
+        write_u16(&mut out, self.headers.len() as u16);
+        for header in &self.headers {
+            write_utf8(&mut out, &header.name);
+            out.push(header.must_understand as u8);
+            write_sized_value(&mut out, &header.value);
+        }
+
+        write_u16(&mut out, self.messages.len() as u16);
+        for message in &self.messages {
+            write_utf8(&mut out, &message.target_uri);
+            write_utf8(&mut out, &message.response_uri);
+            write_sized_value(&mut out, &message.value);
+        }
+
+        out
+    }
+}
+
+impl AmfValue {
+    /// Parse a single AMF0 value.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, AmfError> {
+        Reader::new(data).read_value()
+    }
+
+    /// Serialize this value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![];
+        write_value(&mut out, self);
+        out
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+
+    /// Complex values read so far, which may be referred to by later values.
+    ///
+    /// A value is `None` while it is still being read.
+    references: Vec<Option<AmfValue>>,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            references: vec![],
+        }
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], AmfError> {
+        if self.data.len() < len {
+            return Err(AmfError::UnexpectedEof);
+        }
+
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, AmfError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, AmfError> {
+        let bytes = self.read_bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, AmfError> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_f64(&mut self) -> Result<f64, AmfError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.read_bytes(8)?);
+        Ok(f64::from_be_bytes(bytes))
+    }
+
+    fn read_string(&mut self, len: usize) -> Result<String, AmfError> {
+        let bytes = self.read_bytes(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| AmfError::InvalidUtf8)
+    }
+
+    fn read_utf8(&mut self) -> Result<String, AmfError> {
+        let len = self.read_u16()?;
+        self.read_string(len.into())
+    }
+
+    fn read_long_utf8(&mut self) -> Result<String, AmfError> {
+        let len = self.read_u32()?;
+        self.read_string(len as usize)
+    }
+
+    /// Read the properties of an object, up to and including the object end marker.
+    fn read_properties(&mut self) -> Result<Vec<(String, AmfValue)>, AmfError> {
+        let mut properties = vec![];
+        loop {
+            let name = self.read_utf8()?;
+            if name.is_empty() && self.data.first() == Some(&OBJECT_END) {
+                self.read_u8()?;
+                return Ok(properties);
+            }
+
+            let value = self.read_value()?;
+            properties.push((name, value));
+        }
+    }
+
+    /// Read a complex value, making it available to later references.
+    fn read_complex(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<AmfValue, AmfError>,
+    ) -> Result<AmfValue, AmfError> {
+        let index = self.references.len();
+        self.references.push(None);
+
+        let value = read(self)?;
+        self.references[index] = Some(value.clone());
+        Ok(value)
+    }
+
+    fn read_value(&mut self) -> Result<AmfValue, AmfError> {
+        match self.read_u8()? {
+            NUMBER => Ok(AmfValue::Number(self.read_f64()?)),
+            BOOLEAN => Ok(AmfValue::Bool(self.read_u8()? != 0)),
+            STRING => Ok(AmfValue::String(self.read_utf8()?)),
+            OBJECT => self.read_complex(|r| Ok(AmfValue::Object(r.read_properties()?))),
+            NULL => Ok(AmfValue::Null),
+            UNDEFINED | UNSUPPORTED => Ok(AmfValue::Undefined),
+            REFERENCE => {
+                let index = self.read_u16()?;
+                match self.references.get(usize::from(index)) {
+                    Some(Some(value)) => Ok(value.clone()),
+                    _ => Err(AmfError::InvalidReference(index)),
+                }
+            }
+            ECMA_ARRAY => self.read_complex(|r| {
+                // The count is only a hint; the array is terminated like an object.
+                let _count = r.read_u32()?;
+                Ok(AmfValue::EcmaArray(r.read_properties()?))
+            }),
+            STRICT_ARRAY => self.read_complex(|r| {
+                let count = r.read_u32()?;
+                let mut values = vec![];
+                for _ in 0..count {
+                    values.push(r.read_value()?);
+                }
+                Ok(AmfValue::StrictArray(values))
+            }),
+            DATE => {
+                let time = self.read_f64()?;
+                let _time_zone = self.read_u16()?;
+                Ok(AmfValue::Date(time))
+            }
+            LONG_STRING => Ok(AmfValue::String(self.read_long_utf8()?)),
+            XML_DOCUMENT => Ok(AmfValue::Xml(self.read_long_utf8()?)),
+            TYPED_OBJECT => self.read_complex(|r| {
+                let class_name = r.read_utf8()?;
+                Ok(AmfValue::TypedObject(class_name, r.read_properties()?))
+            }),
+            AVMPLUS_OBJECT => Err(AmfError::Amf3),
+            marker => Err(AmfError::UnsupportedMarker(marker)),
+        }
+    }
+}
+
+fn write_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn write_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn write_utf8(out: &mut Vec<u8>, value: &str) {
+    write_u16(out, value.len() as u16);
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn write_long_utf8(out: &mut Vec<u8>, value: &str) {
+    write_u32(out, value.len() as u32);
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn write_properties(out: &mut Vec<u8>, properties: &[(String, AmfValue)]) {
+    for (name, value) in properties {
+        write_utf8(out, name);
+        write_value(out, value);
+    }
+    write_utf8(out, "");
+    out.push(OBJECT_END);
+}
+
+/// Write a value preceded by its length in bytes, as done for headers and messages.
+fn write_sized_value(out: &mut Vec<u8>, value: &AmfValue) {
+    let bytes = value.to_bytes();
+    write_u32(out, bytes.len() as u32);
+    out.extend_from_slice(&bytes);
+}
+
+fn write_value(out: &mut Vec<u8>, value: &AmfValue) {
+    match value {
+        AmfValue::Number(number) => {
+            out.push(NUMBER);
+            out.extend_from_slice(&number.to_be_bytes());
+        }
+        AmfValue::Bool(value) => {
+            out.push(BOOLEAN);
+            out.push(*value as u8);
+        }
+        AmfValue::String(string) if string.len() > usize::from(u16::max_value()) => {
+            out.push(LONG_STRING);
+            write_long_utf8(out, string);
+        }
+        AmfValue::String(string) => {
+            out.push(STRING);
+            write_utf8(out, string);
+        }
+        AmfValue::Object(properties) => {
+            out.push(OBJECT);
+            write_properties(out, properties);
+        }
+        AmfValue::Null => out.push(NULL),
+        AmfValue::Undefined => out.push(UNDEFINED),
+        AmfValue::EcmaArray(properties) => {
+            out.push(ECMA_ARRAY);
+            write_u32(out, properties.len() as u32);
+            write_properties(out, properties);
+        }
+        AmfValue::StrictArray(values) => {
+            out.push(STRICT_ARRAY);
+            write_u32(out, values.len() as u32);
+            for value in values {
+                write_value(out, value);
+            }
+        }
+        AmfValue::Date(time) => {
+            out.push(DATE);
+            out.extend_from_slice(&time.to_be_bytes());
+            write_u16(out, 0);
+        }
+        AmfValue::Xml(xml) => {
+            out.push(XML_DOCUMENT);
+            write_long_utf8(out, xml);
+        }
+        AmfValue::TypedObject(class_name, properties) => {
+            out.push(TYPED_OBJECT);
+            write_utf8(out, class_name);
+            write_properties(out, properties);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packet_round_trip() {
+        let packet = Packet {
+            version: 0,
+            headers: vec![Header {
+                name: "Credentials".to_string(),
+                must_understand: false,
+                value: AmfValue::Object(vec![
+                    ("userid".to_string(), AmfValue::String("user".to_string())),
+                    ("password".to_string(), AmfValue::Null),
+                ]),
+            }],
+            messages: vec![Message {
+                target_uri: "Service.method".to_string(),
+                response_uri: "/1".to_string(),
+                value: AmfValue::StrictArray(vec![
+                    AmfValue::Number(1.5),
+                    AmfValue::Bool(true),
+                    AmfValue::Undefined,
+                    AmfValue::EcmaArray(vec![("0".to_string(), AmfValue::Date(0.0))]),
+                    AmfValue::TypedObject("Point".to_string(), vec![]),
+                    AmfValue::Xml("<a/>".to_string()),
+                    AmfValue::String("x".repeat(70000)),
+                ]),
+            }],
+        };
+
+        assert_eq!(Packet::from_bytes(&packet.to_bytes()), Ok(packet));
+    }
+
+    #[test]
+    fn references_are_resolved() {
+        // [{a: 1}, <reference 1>]
+        let data = [
+            STRICT_ARRAY,
+            0,
+            0,
+            0,
+            2, // Array of two values
+            OBJECT,
+            0,
+            1,
+            b'a',
+            NUMBER,
+            0x3F,
+            0xF0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            OBJECT_END,
+            REFERENCE,
+            0,
+            1,
+        ];
+        let object = AmfValue::Object(vec![("a".to_string(), AmfValue::Number(1.0))]);
+
+        assert_eq!(
+            AmfValue::from_bytes(&data),
+            Ok(AmfValue::StrictArray(vec![object.clone(), object]))
+        );
+    }
+
+    #[test]
+    fn invalid_data_is_rejected() {
+        assert_eq!(
+            AmfValue::from_bytes(&[AVMPLUS_OBJECT, 0x01]),
+            Err(AmfError::Amf3)
+        );
+        assert_eq!(
+            AmfValue::from_bytes(&[OBJECT, 0, 1, b'a', REFERENCE, 0, 0]),
+            Err(AmfError::InvalidReference(0))
+        );
+        assert_eq!(
+            AmfValue::from_bytes(&[STRING, 0, 5, b'a']),
+            Err(AmfError::UnexpectedEof)
+        );
+    }
+}
@@ -2,36 +2,42 @@ use crate::avm1::function::{Avm1Function, FunctionObject};
 use crate::avm1::globals::create_globals;
 use crate::avm1::object::search_prototype;
 use crate::avm1::return_value::ReturnValue;
+use crate::backend::log::TraceContext;
 use crate::backend::navigator::{NavigationMethod, RequestOptions};
 use crate::context::UpdateContext;
 use crate::prelude::*;
-use gc_arena::{GcCell, MutationContext};
+use gc_arena::{Gc, GcCell, MutationContext};
 use rand::Rng;
+use smallvec::SmallVec;
 use std::collections::HashMap;
 use std::convert::TryInto;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 use url::form_urlencoded;
 
 use swf::avm1::read::Reader;
 use swf::avm1::types::{Action, Function};
 
 use crate::display_object::{DisplayObject, MovieClip};
-use crate::tag_utils::SwfSlice;
+use crate::tag_utils::{SwfMovie, SwfSlice};
 
 #[cfg(test)]
 #[macro_use]
 mod test_utils;
 
-#[macro_use]
 pub mod listeners;
 
 mod activation;
+mod arguments_object;
+mod bitmap_data_object;
 mod fscommand;
 pub mod function;
 pub mod globals;
+mod isolated_global_object;
 pub mod object;
-mod property;
+pub mod property;
 mod return_value;
-mod scope;
+pub mod scope;
 pub mod script_object;
 mod sound_object;
 mod stage_object;
@@ -47,13 +53,17 @@ mod tests;
 
 use crate::avm1::listeners::SystemListener;
 pub use activation::Activation;
+pub use bitmap_data_object::BitmapDataObject;
 pub use globals::SystemPrototypes;
+pub use isolated_global_object::IsolatedGlobalObject;
 pub use object::{Object, ObjectPtr, TObject};
 use scope::Scope;
 pub use script_object::ScriptObject;
 pub use sound_object::SoundObject;
 pub use stage_object::StageObject;
+pub use super_object::SuperObject;
 pub use value::Value;
+use value::{f64_to_wrapping_i32, f64_to_wrapping_u32};
 
 macro_rules! avm_debug {
     ($($arg:tt)*) => (
@@ -73,6 +83,12 @@ pub struct Avm1<'gc> {
     /// The global object.
     globals: Object<'gc>,
 
+    /// Whether movies other than the root movie each get a `_global` of their own.
+    isolate_global_per_movie: bool,
+
+    /// The `_global` of each isolated movie, keyed by the address of the movie.
+    movie_globals: HashMap<usize, (Weak<SwfMovie>, Object<'gc>)>,
+
     /// System builtins that we use internally to construct new objects.
     prototypes: globals::SystemPrototypes<'gc>,
 
@@ -88,6 +104,28 @@ pub struct Avm1<'gc> {
     /// The operand stack (shared across functions).
     stack: Vec<Value<'gc>>,
 
+    /// The number of values the operand stack may hold before the executing
+    /// action block is aborted.
+    max_stack_size: usize,
+
+    /// The number of activations that may be nested before the running script is aborted.
+    max_recursion_depth: usize,
+
+    /// Whether scripts may currently run.
+    script_state: ScriptState,
+
+    /// How long an action block may run before it is suspended.
+    max_execution_duration: Duration,
+
+    /// The activation records of a suspended script, set aside until it is resumed.
+    suspended_stack_frames: Vec<GcCell<'gc, Activation<'gc>>>,
+
+    /// The operand stack of a suspended script.
+    suspended_stack: Vec<Value<'gc>>,
+
+    /// How many times an action has popped a value off the empty operand stack.
+    stack_underflows: u32,
+
     /// The register slots (also shared across functions).
     /// `ActionDefineFunction2` defined functions do not use these slots.
     registers: [Value<'gc>; 4],
@@ -97,12 +135,17 @@ unsafe impl<'gc> gc_arena::Collect for Avm1<'gc> {
     #[inline]
     fn trace(&self, cc: gc_arena::CollectionContext) {
         self.globals.trace(cc);
+        for (_, globals) in self.movie_globals.values() {
+            globals.trace(cc);
+        }
         self.constant_pool.trace(cc);
         self.system_listeners.trace(cc);
         self.prototypes.trace(cc);
         self.display_properties.trace(cc);
         self.stack_frames.trace(cc);
         self.stack.trace(cc);
+        self.suspended_stack_frames.trace(cc);
+        self.suspended_stack.trace(cc);
 
         for register in &self.registers {
             register.trace(cc);
@@ -112,6 +155,39 @@ unsafe impl<'gc> gc_arena::Collect for Avm1<'gc> {
 
 type Error = Box<dyn std::error::Error>;
 
+/// The default limit on the size of the operand stack.
+///
+/// Flash Player is only limited by available memory, but well-formed content
+/// never comes close to this; only malformed or obfuscated SWFs do.
+pub const DEFAULT_MAX_STACK_SIZE: usize = 1 << 17;
+
+/// The default limit on how deeply functions and `call`ed frames may nest.
+///
+/// This matches the default `ScriptLimits` recursion depth of Flash Player.
+pub const DEFAULT_MAX_RECURSION_DEPTH: usize = 256;
+
+/// The default time a script may run for before the user is asked whether to abort it.
+///
+/// This matches the default `ScriptLimits` timeout of Flash Player.
+pub const DEFAULT_MAX_EXECUTION_DURATION: Duration = Duration::from_secs(15);
+
+/// The number of actions run between checks of the execution time.
+const TIMEOUT_CHECK_INTERVAL: u32 = 1000;
+
+/// Whether the AVM may run scripts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScriptState {
+    /// Scripts run normally.
+    Running,
+
+    /// A script ran for longer than the maximum execution duration, and is waiting to be
+    /// resumed or aborted.
+    Suspended,
+
+    /// A long-running script was aborted, and no further scripts will run.
+    Aborted,
+}
+
 impl<'gc> Avm1<'gc> {
     pub fn new(gc_context: MutationContext<'gc, '_>, player_version: u8) -> Self {
         let (prototypes, globals, system_listeners) = create_globals(gc_context);
@@ -120,11 +196,20 @@ impl<'gc> Avm1<'gc> {
             player_version,
             constant_pool: GcCell::allocate(gc_context, vec![]),
             globals,
+            isolate_global_per_movie: false,
+            movie_globals: HashMap::new(),
             prototypes,
             system_listeners,
             display_properties: stage_object::DisplayPropertyMap::new(gc_context),
             stack_frames: vec![],
             stack: vec![],
+            max_stack_size: DEFAULT_MAX_STACK_SIZE,
+            max_recursion_depth: DEFAULT_MAX_RECURSION_DEPTH,
+            script_state: ScriptState::Running,
+            max_execution_duration: DEFAULT_MAX_EXECUTION_DURATION,
+            suspended_stack_frames: vec![],
+            suspended_stack: vec![],
+            stack_underflows: 0,
             registers: [
                 Value::Undefined,
                 Value::Undefined,
@@ -139,6 +224,18 @@ impl<'gc> Avm1<'gc> {
         self.current_stack_frame().unwrap().read().base_clip()
     }
 
+    /// Describes the clip running the current code, for messages sent to the log backend.
+    fn trace_context(&self) -> TraceContext {
+        let base_clip = self.base_clip();
+        TraceContext {
+            target_path: base_clip.path(),
+            frame: base_clip.as_movie_clip().map(|clip| clip.current_frame()),
+            movie_url: base_clip
+                .movie()
+                .and_then(|movie| movie.url().map(str::to_string)),
+        }
+    }
+
     /// The current target clip for the executing code.
     /// This is the movie clip that contains the bytecode.
     /// Timeline actions like `GotoFrame` use this because
@@ -239,9 +336,10 @@ impl<'gc> Avm1<'gc> {
         code: SwfSlice,
         action_context: &mut UpdateContext<'_, 'gc, '_>,
     ) {
+        let globals = self.global_object_for_clip(active_clip, action_context);
         let global_scope = GcCell::allocate(
             action_context.gc_context,
-            Scope::from_global_object(self.globals),
+            Scope::from_global_object(globals),
         );
         let clip_obj = active_clip.object().as_object().unwrap();
         let child_scope = GcCell::allocate(
@@ -270,9 +368,10 @@ impl<'gc> Avm1<'gc> {
         code: SwfSlice,
         action_context: &mut UpdateContext<'_, 'gc, '_>,
     ) {
+        let globals = self.global_object_for_clip(active_clip, action_context);
         let global_scope = GcCell::allocate(
             action_context.gc_context,
-            Scope::from_global_object(self.globals),
+            Scope::from_global_object(globals),
         );
         let clip_obj = active_clip.object().as_object().unwrap();
         let child_scope = GcCell::allocate(
@@ -307,9 +406,10 @@ impl<'gc> Avm1<'gc> {
     ) {
         // Grab the property with the given name.
         // Requires a dummy stack frame.
+        let globals = self.global_object_for_clip(active_clip, context);
         self.stack_frames.push(GcCell::allocate(
             context.gc_context,
-            Activation::from_nothing(swf_version, self.globals, context.gc_context, active_clip),
+            Activation::from_nothing(swf_version, globals, context.gc_context, active_clip),
         ));
         let search_result = search_prototype(Some(obj), name, self, context, obj)
             .and_then(|r| Ok((r.0.resolve(self, context)?, r.1)));
@@ -343,6 +443,73 @@ impl<'gc> Avm1<'gc> {
         !self.stack_frames.is_empty()
     }
 
+    /// Get the limit on the size of the operand stack.
+    pub fn max_stack_size(&self) -> usize {
+        self.max_stack_size
+    }
+
+    /// Set the limit on the size of the operand stack.
+    ///
+    /// An action block that grows the stack beyond this size is aborted.
+    pub fn set_max_stack_size(&mut self, max_stack_size: usize) {
+        self.max_stack_size = max_stack_size;
+    }
+
+    /// Get the limit on how deeply activations may nest.
+    pub fn max_recursion_depth(&self) -> usize {
+        self.max_recursion_depth
+    }
+
+    /// Set the limit on how deeply activations may nest.
+    ///
+    /// Exceeding it aborts every running activation, like Flash Player does.
+    pub fn set_max_recursion_depth(&mut self, max_recursion_depth: usize) {
+        self.max_recursion_depth = max_recursion_depth;
+    }
+
+    /// Get how long an action block may run before it is suspended.
+    pub fn max_execution_duration(&self) -> Duration {
+        self.max_execution_duration
+    }
+
+    /// Set how long an action block may run before it is suspended.
+    pub fn set_max_execution_duration(&mut self, max_execution_duration: Duration) {
+        self.max_execution_duration = max_execution_duration;
+    }
+
+    pub fn script_state(&self) -> ScriptState {
+        self.script_state
+    }
+
+    /// Continue running a suspended script.
+    ///
+    /// Its activations are put back on the stack, ready to run with `run_stack_with_timeout`.
+    pub fn resume_script(&mut self) {
+        if self.script_state == ScriptState::Suspended {
+            self.stack_frames = std::mem::take(&mut self.suspended_stack_frames);
+            self.stack = std::mem::take(&mut self.suspended_stack);
+            self.script_state = ScriptState::Running;
+        }
+    }
+
+    /// Discard a suspended script, and stop running scripts altogether.
+    pub fn abort_script(&mut self) {
+        if self.script_state == ScriptState::Suspended {
+            self.suspended_stack_frames.clear();
+            self.suspended_stack.clear();
+            self.script_state = ScriptState::Aborted;
+        }
+    }
+
+    /// The constant pool used by action blocks that don't define their own.
+    pub fn constant_pool(&self) -> GcCell<'gc, Vec<String>> {
+        self.constant_pool
+    }
+
+    pub fn set_constant_pool(&mut self, constant_pool: GcCell<'gc, Vec<String>>) {
+        self.constant_pool = constant_pool;
+    }
+
     /// Get the currently executing SWF version.
     pub fn current_swf_version(&self) -> u8 {
         self.current_stack_frame()
@@ -365,17 +532,20 @@ impl<'gc> Avm1<'gc> {
         args: &[Value<'gc>],
     ) {
         // Push a dummy stack frame.
+        let globals = self.global_object_for_clip(active_clip, context);
         self.stack_frames.push(GcCell::allocate(
             context.gc_context,
-            Activation::from_nothing(swf_version, self.globals, context.gc_context, active_clip),
+            Activation::from_nothing(swf_version, globals, context.gc_context, active_clip),
         ));
-        let listeners = self.system_listeners.get(listener);
-        let mut handlers = listeners.prepare_handlers(self, context, method);
+        let broadcaster = self.system_listeners.get(listener);
+        let handlers =
+            globals::as_broadcaster::prepare_handlers(self, context, broadcaster, method);
         self.stack_frames.pop();
 
         // Each callback exec pushes its own stack frame.
         // The functions are now ready to execute with `run_stack_till_empty`.
-        for (listener, handler) in handlers.drain(..) {
+        // The last frame pushed runs first, so push them from the last listener.
+        for (listener, handler) in handlers.unwrap_or_default().into_iter().rev() {
             let _ = handler.call(self, context, listener, None, &args);
         }
     }
@@ -463,6 +633,12 @@ impl<'gc> Avm1<'gc> {
         &mut self,
         context: &mut UpdateContext<'_, 'gc, '_>,
     ) -> Result<(), Error> {
+        if self.script_state == ScriptState::Aborted {
+            self.stack_frames.clear();
+            self.stack.clear();
+            return Ok(());
+        }
+
         while !self.stack_frames.is_empty() {
             self.with_current_reader_mut(context, |this, r, context| {
                 this.do_next_action(context, r)
@@ -480,6 +656,51 @@ impl<'gc> Avm1<'gc> {
         Ok(())
     }
 
+    /// Execute the AVM stack until it is exhausted, or until it has run for longer than the
+    /// maximum execution duration.
+    ///
+    /// In the latter case, the remaining activations are set aside and the script is suspended
+    /// until `resume_script` or `abort_script` is called.
+    pub fn run_stack_with_timeout(
+        &mut self,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+    ) -> Result<(), Error> {
+        if self.script_state != ScriptState::Running {
+            return Ok(());
+        }
+
+        let start_time = context.navigator.time_since_launch();
+        let mut actions_run = 0u32;
+        while !self.stack_frames.is_empty() {
+            self.with_current_reader_mut(context, |this, r, context| {
+                this.do_next_action(context, r)
+            })?;
+
+            actions_run = actions_run.wrapping_add(1);
+            if actions_run % TIMEOUT_CHECK_INTERVAL == 0 && !self.stack_frames.is_empty() {
+                let elapsed = context
+                    .navigator
+                    .time_since_launch()
+                    .checked_sub(start_time)
+                    .unwrap_or_default();
+                if elapsed >= self.max_execution_duration {
+                    log::warn!("AVM1 script ran for {:?}; suspending it", elapsed);
+                    self.suspended_stack_frames = std::mem::take(&mut self.stack_frames);
+                    self.suspended_stack = std::mem::take(&mut self.stack);
+                    self.script_state = ScriptState::Suspended;
+                    return Ok(());
+                }
+            }
+        }
+
+        if !self.stack.is_empty() {
+            log::warn!("Operand stack is not empty after execution");
+            self.stack.clear();
+        }
+
+        Ok(())
+    }
+
     /// Execute the AVM stack until a given activation returns.
     pub fn run_current_frame(
         &mut self,
@@ -522,7 +743,27 @@ impl<'gc> Avm1<'gc> {
         if reader.pos() >= (data.end - data.start) {
             //Executing beyond the end of a function constitutes an implicit return.
             self.retire_stack_frame(context, Value::Undefined)?;
-        } else if let Some(action) = reader.read_action()? {
+        } else if self.stack.len() > self.max_stack_size {
+            // Malformed code can push values without ever popping them.
+            log::warn!(
+                "AVM1 operand stack exceeded {} values; aborting action block",
+                self.max_stack_size
+            );
+            self.stack.clear();
+            self.retire_stack_frame(context, Value::Undefined)?;
+        } else if self.stack_frames.len() > self.max_recursion_depth {
+            // Callers waiting on any of these activations stop once it is gone.
+            log::warn!(
+                "{} levels of recursion were exceeded; aborting script",
+                self.max_recursion_depth
+            );
+            self.stack_frames.clear();
+            self.stack.clear();
+        } else if let Some(action) = reader.read_action().unwrap_or_else(|e| {
+            // We can't tell where the next action starts, so end the block.
+            log::warn!("AVM1 parse error: {}; aborting action block", e);
+            None
+        }) {
             avm_debug!("Action: {:?}", action);
 
             let result = match action {
@@ -650,7 +891,7 @@ impl<'gc> Avm1<'gc> {
                 return result;
             }
         } else {
-            //The explicit end opcode was encountered so return here
+            //The explicit end opcode was encountered (or the action was malformed) so return here
             self.retire_stack_frame(context, Value::Undefined)?;
         }
 
@@ -997,18 +1238,69 @@ impl<'gc> Avm1<'gc> {
         self.stack.push(value);
     }
 
+    /// Pops a value off the operand stack.
+    ///
+    /// Obfuscated code deliberately pops more values than it pushed, which yields `undefined`
+    /// as in Flash Player. Such code tends to underflow constantly, so only every time the
+    /// number of underflows doubles is logged.
     #[allow(clippy::let_and_return)]
     fn pop(&mut self) -> Value<'gc> {
-        let value = self.stack.pop().unwrap_or_else(|| {
-            log::warn!("Avm1::pop: Stack underflow");
-            Value::Undefined
-        });
+        let value = match self.stack.pop() {
+            Some(value) => value,
+            None => {
+                self.stack_underflows = self.stack_underflows.saturating_add(1);
+                if self.stack_underflows.is_power_of_two() {
+                    log::warn!(
+                        "AVM1 stack underflow ({} so far); using undefined",
+                        self.stack_underflows
+                    );
+                }
+                Value::Undefined
+            }
+        };
 
         avm_debug!("Stack pop {}: {:?}", self.stack.len(), value);
 
         value
     }
 
+    /// Pops the argument count of a call action, and then that many arguments.
+    ///
+    /// The arguments have already been evaluated when they were pushed, whether or not there
+    /// is anything to call. A count that isn't a positive number pops no arguments, and a count
+    /// larger than the stack only pops what is on it.
+    ///
+    /// Most calls have few arguments, which are kept inline rather than allocated.
+    fn pop_args(
+        &mut self,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+    ) -> Result<SmallVec<[Value<'gc>; 8]>, Error> {
+        let num_args = self.pop().as_number(self, context)?;
+        let num_args = if num_args > 0.0 {
+            (num_args as usize).min(self.stack.len())
+        } else {
+            0
+        };
+        let start = self.stack.len() - num_args;
+        Ok(self.stack.drain(start..).rev().collect())
+    }
+
+    /// Pop an operand of the arithmetic actions shared with SWF 4.
+    ///
+    /// SWF 4 movies have no objects, and convert strings that aren't numbers to 0 rather than
+    /// `NaN`.
+    fn pop_arithmetic_operand(
+        &mut self,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+    ) -> Result<f64, Error> {
+        let value = self.pop();
+        if self.current_swf_version() < 5 {
+            Ok(value.into_number_v1())
+        } else {
+            value.as_number(self, context)
+        }
+    }
+
     /// Retrieve a given register value.
     ///
     /// If a given register does not exist, this function yields
@@ -1060,8 +1352,48 @@ impl<'gc> Avm1<'gc> {
         _context: &mut UpdateContext,
         action: swf::avm1::types::Action,
     ) -> Result<(), Error> {
-        log::error!("Unknown AVM1 opcode: {:?}", action);
-        Err("Unknown op".into())
+        // Flash Player skips actions it doesn't know, which obfuscators use to hide code.
+        log::warn!("Unknown AVM1 opcode: {:?}", action);
+        Ok(())
+    }
+
+    /// Pops a number operand, converting other values as `Number()` does.
+    fn pop_number(&mut self, context: &mut UpdateContext<'_, 'gc, '_>) -> Result<f64, Error> {
+        self.pop().as_number(self, context)
+    }
+
+    /// Pops an operand that must be an object, or returns `None` and warns if it isn't one.
+    ///
+    /// Actions that need an object do nothing when given another value, as in Flash Player.
+    fn pop_object(&mut self, action_name: &str) -> Option<Object<'gc>> {
+        match self.pop() {
+            Value::Object(object) => Some(object),
+            value => {
+                log::warn!("{}: Expected an object, found {:?}", action_name, value);
+                None
+            }
+        }
+    }
+
+    /// Moves the reader by a jump offset of `Jump` or `If`.
+    ///
+    /// Obfuscated code jumps into the middle of other actions, which is allowed, but a jump
+    /// outside of the current action block ends the block instead of running other data.
+    fn jump(&mut self, reader: &mut Reader<'_>, jump_offset: i16) {
+        let data = self.current_stack_frame().unwrap().read().data();
+        let len = (data.end - data.start) as isize;
+        let pos = reader.pos() as isize;
+        let target = pos + isize::from(jump_offset);
+        if target >= 0 && target <= len {
+            reader.seek(jump_offset.into());
+        } else {
+            log::warn!(
+                "AVM1 jump to {} is outside of the action block (length {}); ending the block",
+                target,
+                len
+            );
+            reader.seek(len - pos);
+        }
     }
 
     fn action_add(&mut self, _context: &mut UpdateContext) -> Result<(), Error> {
@@ -1103,7 +1435,7 @@ impl<'gc> Avm1<'gc> {
 
     fn action_ascii_to_char(&mut self, _context: &mut UpdateContext) -> Result<(), Error> {
         // TODO(Herschel): Results on incorrect operands?
-        let val = (self.pop().as_f64()? as u8) as char;
+        let val = (self.pop().into_number_v1() as u8) as char;
         self.push(val.to_string());
         Ok(())
     }
@@ -1141,84 +1473,104 @@ impl<'gc> Avm1<'gc> {
         Ok(())
     }
 
-    fn action_bit_and(&mut self, _context: &mut UpdateContext) -> Result<(), Error> {
-        let a = self.pop().as_u32()?;
-        let b = self.pop().as_u32()?;
+    fn action_bit_and(&mut self, context: &mut UpdateContext<'_, 'gc, '_>) -> Result<(), Error> {
+        let a = f64_to_wrapping_u32(self.pop_number(context)?);
+        let b = f64_to_wrapping_u32(self.pop_number(context)?);
         let result = a & b;
         self.push(result);
         Ok(())
     }
 
-    fn action_bit_lshift(&mut self, _context: &mut UpdateContext) -> Result<(), Error> {
-        let a = self.pop().as_i32()? & 0b11111; // Only 5 bits used for shift count
-        let b = self.pop().as_i32()?;
+    fn action_bit_lshift(&mut self, context: &mut UpdateContext<'_, 'gc, '_>) -> Result<(), Error> {
+        let a = f64_to_wrapping_i32(self.pop_number(context)?) & 0b11111; // Only 5 bits used for shift count
+        let b = f64_to_wrapping_i32(self.pop_number(context)?);
         let result = b << a;
         self.push(result);
         Ok(())
     }
 
-    fn action_bit_or(&mut self, _context: &mut UpdateContext) -> Result<(), Error> {
-        let a = self.pop().as_u32()?;
-        let b = self.pop().as_u32()?;
+    fn action_bit_or(&mut self, context: &mut UpdateContext<'_, 'gc, '_>) -> Result<(), Error> {
+        let a = f64_to_wrapping_u32(self.pop_number(context)?);
+        let b = f64_to_wrapping_u32(self.pop_number(context)?);
         let result = a | b;
         self.push(result);
         Ok(())
     }
 
-    fn action_bit_rshift(&mut self, _context: &mut UpdateContext) -> Result<(), Error> {
-        let a = self.pop().as_i32()? & 0b11111; // Only 5 bits used for shift count
-        let b = self.pop().as_i32()?;
+    fn action_bit_rshift(&mut self, context: &mut UpdateContext<'_, 'gc, '_>) -> Result<(), Error> {
+        let a = f64_to_wrapping_i32(self.pop_number(context)?) & 0b11111; // Only 5 bits used for shift count
+        let b = f64_to_wrapping_i32(self.pop_number(context)?);
         let result = b >> a;
         self.push(result);
         Ok(())
     }
 
-    fn action_bit_urshift(&mut self, _context: &mut UpdateContext) -> Result<(), Error> {
-        let a = self.pop().as_u32()? & 0b11111; // Only 5 bits used for shift count
-        let b = self.pop().as_u32()?;
+    fn action_bit_urshift(
+        &mut self,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+    ) -> Result<(), Error> {
+        let a = f64_to_wrapping_u32(self.pop_number(context)?) & 0b11111; // Only 5 bits used for shift count
+        let b = f64_to_wrapping_u32(self.pop_number(context)?);
         let result = b >> a;
         self.push(result);
         Ok(())
     }
 
-    fn action_bit_xor(&mut self, _context: &mut UpdateContext) -> Result<(), Error> {
-        let a = self.pop().as_u32()?;
-        let b = self.pop().as_u32()?;
+    fn action_bit_xor(&mut self, context: &mut UpdateContext<'_, 'gc, '_>) -> Result<(), Error> {
+        let a = f64_to_wrapping_u32(self.pop_number(context)?);
+        let b = f64_to_wrapping_u32(self.pop_number(context)?);
         let result = b ^ a;
         self.push(result);
         Ok(())
     }
 
     fn action_call(&mut self, context: &mut UpdateContext<'_, 'gc, '_>) -> Result<(), Error> {
-        // Runs any actions on the given frame.
+        // Runs the actions on the given frame, like a subroutine, without moving the playhead.
         let frame = self.pop();
-        let clip = self.target_clip_or_root();
-        if let Some(clip) = clip.as_movie_clip() {
-            // Use frame # if parameter is a number, otherwise cast to string and check for frame labels.
-            let frame = if let Ok(frame) = frame.as_u32() {
-                if frame >= 1 && frame <= u32::from(clip.total_frames()) {
-                    Some(frame as u16)
-                } else {
-                    None
-                }
-            } else {
-                let frame_label = frame.coerce_to_string(self, context)?;
-                clip.frame_label_to_number(&frame_label)
-            };
+        let start = self.target_clip_or_root();
+
+        // A string may name a clip as well, as in `/clip:label`.
+        let (clip, frame) = match frame {
+            Value::Number(n) => (start.as_movie_clip(), Some(f64_to_wrapping_u32(n))),
+            frame => {
+                let frame = frame.coerce_to_string(self, context)?;
+                let (clip, frame) = match frame.rfind(':') {
+                    Some(i) => {
+                        let path = Value::String(frame[..i].to_string());
+                        let clip = self.resolve_target_display_object(context, start, path)?;
+                        (clip, &frame[i + 1..])
+                    }
+                    None => (Some(start), &frame[..]),
+                };
+                let clip = clip.and_then(|clip| clip.as_movie_clip());
+                // Frame numbers may be given as strings, otherwise this is a label.
+                let frame = match frame.parse::<u32>() {
+                    Ok(frame) => Some(frame),
+                    Err(_) => clip
+                        .and_then(|clip| clip.frame_label_to_number(frame))
+                        .map(u32::from),
+                };
+                (clip, frame)
+            }
+        };
 
+        if let Some(clip) = clip {
+            let frame =
+                frame.filter(|frame| *frame >= 1 && *frame <= u32::from(clip.total_frames()));
             if let Some(frame) = frame {
+                // The actions run on the timeline of the clip the frame belongs to.
                 // We must run the actions in the order that the tags appear,
                 // so we want to push the stack frames in reverse order.
-                for action in clip.actions_on_frame(context, frame).rev() {
+                for action in clip.actions_on_frame(context, frame as u16).rev() {
                     self.insert_stack_frame_for_action(
-                        self.target_clip_or_root(),
-                        self.current_swf_version(),
+                        clip.into(),
+                        clip.swf_version(),
                         action,
                         context,
                     );
                 }
             } else {
-                log::warn!("Call: Invalid frame {:?}", frame);
+                log::warn!("Call: Invalid frame");
             }
         } else {
             log::warn!("Call: Expected MovieClip");
@@ -1231,19 +1583,16 @@ impl<'gc> Avm1<'gc> {
         context: &mut UpdateContext<'_, 'gc, '_>,
     ) -> Result<(), Error> {
         let fn_name = self.pop();
-        let mut args = Vec::new();
-        let num_args = self.pop().as_i64()?; // TODO(Herschel): max arg count?
-        for _ in 0..num_args {
-            args.push(self.pop());
-        }
+        let args = self.pop_args(context)?;
 
+        let fn_name = fn_name.coerce_to_string(self, context)?;
         let target_fn = self
             .stack_frames
             .last()
             .unwrap()
             .clone()
             .read()
-            .resolve(fn_name.as_string()?, self, context)?
+            .resolve(&fn_name, self, context)?
             .resolve(self, context)?;
         let this = self.target_clip_or_root().object().as_object()?;
         target_fn.call(self, context, this, None, &args)?.push(self);
@@ -1258,11 +1607,7 @@ impl<'gc> Avm1<'gc> {
         let method_name = self.pop();
         let object_val = self.pop();
         let object = value_object::ValueObject::boxed(self, context, object_val);
-        let num_args = self.pop().as_i64()?; // TODO(Herschel): max arg count?
-        let mut args = Vec::new();
-        for _ in 0..num_args {
-            args.push(self.pop());
-        }
+        let args = self.pop_args(context)?;
 
         match method_name {
             Value::Undefined | Value::Null => {
@@ -1277,28 +1622,29 @@ impl<'gc> Avm1<'gc> {
                     self.push(Value::Undefined);
                 }
             }
-            Value::String(name) => {
+            _ => {
+                let name = method_name.coerce_to_string(self, context)?;
                 if name.is_empty() {
                     object.call(self, context, object, None, &args)?.push(self);
                 } else {
                     object.call_method(&name, &args, self, context)?.push(self);
                 }
             }
-            _ => {
-                return Err(format!(
-                    "Invalid method name, expected string but found {:?}",
-                    method_name
-                )
-                .into())
-            }
         }
 
         Ok(())
     }
 
     fn action_cast_op(&mut self, context: &mut UpdateContext<'_, 'gc, '_>) -> Result<(), Error> {
-        let obj = self.pop().as_object()?;
-        let constr = self.pop().as_object()?;
+        let obj = self.pop_object("CastOp");
+        let constr = self.pop_object("CastOp");
+        let (obj, constr) = match (obj, constr) {
+            (Some(obj), Some(constr)) => (obj, constr),
+            _ => {
+                self.push(Value::Null);
+                return Ok(());
+            }
+        };
 
         let prototype = constr
             .get("prototype", self, context)?
@@ -1370,7 +1716,7 @@ impl<'gc> Avm1<'gc> {
             ScriptObject::object(context.gc_context, Some(self.prototypes.object)).into();
         let func_obj = FunctionObject::function(
             context.gc_context,
-            func,
+            Gc::allocate(context.gc_context, func),
             Some(self.prototypes.function),
             Some(prototype),
         );
@@ -1416,7 +1762,7 @@ impl<'gc> Avm1<'gc> {
             ScriptObject::object(context.gc_context, Some(self.prototypes.object)).into();
         let func_obj = FunctionObject::function(
             context.gc_context,
-            func,
+            Gc::allocate(context.gc_context, func),
             Some(self.prototypes.function),
             Some(prototype),
         );
@@ -1501,8 +1847,8 @@ impl<'gc> Avm1<'gc> {
 
     fn action_divide(&mut self, context: &mut UpdateContext<'_, 'gc, '_>) -> Result<(), Error> {
         // AS1 divide
-        let a = self.pop().as_number(self, context)?;
-        let b = self.pop().as_number(self, context)?;
+        let a = self.pop_arithmetic_operand(context)?;
+        let b = self.pop_arithmetic_operand(context)?;
 
         // TODO(Herschel): SWF19: "If A is zero, the result NaN, Infinity, or -Infinity is pushed to the in SWF 5 and later.
         // In SWF 4, the result is the string #ERROR#.""
@@ -1518,25 +1864,11 @@ impl<'gc> Avm1<'gc> {
     }
 
     fn action_enumerate(&mut self, context: &mut UpdateContext<'_, 'gc, '_>) -> Result<(), Error> {
+        // The legacy `Enumerate` action takes the name (or path) of the variable to enumerate.
         let name_value = self.pop();
-        let name = name_value.as_string()?;
-        self.push(Value::Null); // Sentinel that indicates end of enumeration
-        let object = self
-            .current_stack_frame()
-            .unwrap()
-            .read()
-            .resolve(name, self, context)?
-            .resolve(self, context)?;
-
-        match object {
-            Value::Object(ob) => {
-                for k in ob.get_keys(self).into_iter().rev() {
-                    self.push(k);
-                }
-            }
-            _ => log::error!("Cannot enumerate properties of {}", name_value.as_string()?),
-        };
-
+        let name = name_value.coerce_to_string(self, context)?;
+        let object = self.get_variable(context, &name)?.resolve(self, context)?;
+        self.enumerate_value(object);
         Ok(())
     }
 
@@ -1545,7 +1877,17 @@ impl<'gc> Avm1<'gc> {
         _context: &mut UpdateContext<'_, 'gc, '_>,
     ) -> Result<(), Error> {
         let value = self.pop();
+        self.enumerate_value(value);
+        Ok(())
+    }
 
+    /// Pushes the enumerable property names of a value onto the stack for a `for..in` loop,
+    /// as used by the `Enumerate` and `Enumerate2` actions.
+    ///
+    /// A `null` sentinel is pushed first to mark the end of the enumeration, followed by the
+    /// property names so that they are popped in the order returned by `get_keys`.
+    /// The names are snapshotted, so modifying the object during the loop doesn't affect it.
+    fn enumerate_value(&mut self, value: Value<'gc>) {
         self.push(Value::Null); // Sentinel that indicates end of enumeration
 
         if let Value::Object(object) = value {
@@ -1555,8 +1897,6 @@ impl<'gc> Avm1<'gc> {
         } else {
             log::warn!("Cannot enumerate {:?}", value);
         }
-
-        Ok(())
     }
 
     #[allow(clippy::float_cmp)]
@@ -1580,8 +1920,12 @@ impl<'gc> Avm1<'gc> {
     }
 
     fn action_extends(&mut self, context: &mut UpdateContext<'_, 'gc, '_>) -> Result<(), Error> {
-        let superclass = self.pop().as_object()?;
-        let subclass = self.pop().as_object()?;
+        let superclass = self.pop_object("Extends");
+        let subclass = self.pop_object("Extends");
+        let (superclass, subclass) = match (superclass, subclass) {
+            (Some(superclass), Some(subclass)) => (superclass, subclass),
+            _ => return Ok(()),
+        };
 
         //TODO: What happens if we try to extend an object which has no `prototype`?
         //e.g. `class Whatever extends Object.prototype` or `class Whatever extends 5`
@@ -1641,7 +1985,12 @@ impl<'gc> Avm1<'gc> {
     }
 
     fn action_get_time(&mut self, context: &mut UpdateContext) -> Result<(), Error> {
-        let time = context.navigator.time_since_launch().as_millis() as u32;
+        let time = context
+            .navigator
+            .time_since_launch()
+            .checked_sub(context.suspended_time)
+            .unwrap_or_default()
+            .as_millis() as u32;
         self.push(time);
         Ok(())
     }
@@ -1651,16 +2000,75 @@ impl<'gc> Avm1<'gc> {
         self.base_clip().root().object()
     }
 
-    /// Obtain the value of `_global`.
-    pub fn global_object(&self, _context: &mut UpdateContext<'_, 'gc, '_>) -> Value<'gc> {
-        Value::Object(self.globals)
+    /// Obtain the value of `_global` for the currently running code.
+    pub fn global_object(&mut self, context: &mut UpdateContext<'_, 'gc, '_>) -> Value<'gc> {
+        Value::Object(self.global_object_for_clip(self.base_clip(), context))
     }
 
-    /// Obtain a reference to `_global`.
+    /// Obtain a reference to the `_global` shared by all movies.
     pub fn global_object_cell(&self) -> Object<'gc> {
         self.globals
     }
 
+    /// Obtain the `_global` seen by code in the given clip.
+    ///
+    /// All movies share one `_global`, as in Flash Player, unless movies are isolated. Then
+    /// every movie other than the root movie gets its own.
+    pub fn global_object_for_clip(
+        &mut self,
+        clip: DisplayObject<'gc>,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+    ) -> Object<'gc> {
+        if !self.isolate_global_per_movie {
+            return self.globals;
+        }
+        let movie = match clip.movie() {
+            Some(movie) if !Arc::ptr_eq(&movie, context.swf) => movie,
+            _ => return self.globals,
+        };
+
+        let key = &*movie as *const SwfMovie as usize;
+        if let Some((weak, globals)) = self.movie_globals.get(&key) {
+            if weak
+                .upgrade()
+                .map_or(false, |other| Arc::ptr_eq(&other, &movie))
+            {
+                return *globals;
+            }
+        }
+
+        // Forget the globals of movies that are gone, whose addresses may be reused.
+        self.movie_globals
+            .retain(|_, (weak, _)| weak.strong_count() > 0);
+        let globals = IsolatedGlobalObject::new(context.gc_context, self.globals).into();
+        self.movie_globals
+            .insert(key, (Arc::downgrade(&movie), globals));
+        globals
+    }
+
+    /// Returns whether the given object is a `_global`, either the shared one or the one of an
+    /// isolated movie.
+    pub fn is_global_object(&self, object: Object<'gc>) -> bool {
+        Object::ptr_eq(object, self.globals)
+            || self
+                .movie_globals
+                .values()
+                .any(|(_, globals)| Object::ptr_eq(object, *globals))
+    }
+
+    /// Whether movies other than the root movie each get a `_global` of their own.
+    pub fn isolate_global_per_movie(&self) -> bool {
+        self.isolate_global_per_movie
+    }
+
+    /// Set whether movies other than the root movie each get a `_global` of their own.
+    ///
+    /// An isolated movie's `_global` reads the properties of the shared `_global`, but the
+    /// properties that the movie sets aren't seen by other movies.
+    pub fn set_isolate_global_per_movie(&mut self, isolate_global_per_movie: bool) {
+        self.isolate_global_per_movie = isolate_global_per_movie;
+    }
+
     /// Obtain system built-in prototypes for this instance.
     pub fn prototypes(&self) -> &globals::SystemPrototypes<'gc> {
         &self.prototypes
@@ -1687,16 +2095,17 @@ impl<'gc> Avm1<'gc> {
         if target.starts_with("_level") && target.len() > 6 {
             let url = url.to_string();
             let level_id = target[6..].parse::<u32>()?;
-            let fetch = context.navigator.fetch(url, RequestOptions::get());
             let level = self.resolve_level(level_id, context);
 
-            let process = context.load_manager.load_movie_into_clip(
+            context.load_manager.load_movie_into_clip(
                 context.player.clone().unwrap(),
                 level,
-                fetch,
+                context.navigator,
+                context.cancellation_token,
+                url,
+                RequestOptions::get(),
                 None,
             );
-            context.navigator.spawn_future(process);
 
             return Ok(());
         }
@@ -1760,7 +2169,10 @@ impl<'gc> Avm1<'gc> {
                     fetch,
                 );
 
-                context.navigator.spawn_future(process);
+                context
+                    .navigator
+                    .spawn_future(process, context.cancellation_token.clone())
+                    .detach();
             }
 
             return Ok(());
@@ -1771,14 +2183,15 @@ impl<'gc> Avm1<'gc> {
                     url,
                     NavigationMethod::from_send_vars_method(swf_method),
                 );
-                let fetch = context.navigator.fetch(url, opts);
-                let process = context.load_manager.load_movie_into_clip(
+                context.load_manager.load_movie_into_clip(
                     context.player.clone().unwrap(),
                     clip_target,
-                    fetch,
+                    context.navigator,
+                    context.cancellation_token,
+                    url,
+                    opts,
                     None,
                 );
-                context.navigator.spawn_future(process);
             }
 
             return Ok(());
@@ -1871,7 +2284,7 @@ impl<'gc> Avm1<'gc> {
     ) -> Result<(), Error> {
         let val = self.pop();
         if val.as_bool(self.current_swf_version()) {
-            reader.seek(jump_offset.into());
+            self.jump(reader, jump_offset);
         }
         Ok(())
     }
@@ -1883,7 +2296,7 @@ impl<'gc> Avm1<'gc> {
     }
 
     fn action_init_array(&mut self, context: &mut UpdateContext<'_, 'gc, '_>) -> Result<(), Error> {
-        let num_elements = self.pop().as_i64()?;
+        let num_elements = self.pop_number(context)? as i64;
         let array = ScriptObject::array(context.gc_context, Some(self.prototypes.array));
 
         for i in 0..num_elements {
@@ -1898,7 +2311,7 @@ impl<'gc> Avm1<'gc> {
         &mut self,
         context: &mut UpdateContext<'_, 'gc, '_>,
     ) -> Result<(), Error> {
-        let num_props = self.pop().as_i64()?;
+        let num_props = self.pop_number(context)? as i64;
         let object = ScriptObject::object(context.gc_context, Some(self.prototypes.object));
         for _ in 0..num_props {
             let value = self.pop();
@@ -1915,15 +2328,20 @@ impl<'gc> Avm1<'gc> {
         &mut self,
         context: &mut UpdateContext<'_, 'gc, '_>,
     ) -> Result<(), Error> {
-        let constr = self.pop().as_object()?;
-        let count = self.pop().as_i64()?; //TODO: Is this coercion actually performed by Flash?
+        let constr = self.pop_object("ImplementsOp");
+        let count = self.pop_number(context)? as i64; //TODO: Is this coercion actually performed by Flash?
         let mut interfaces = vec![];
 
-        //TODO: If one of the interfaces is not an object, do we leave the
-        //whole stack dirty, or...?
+        // All of the interfaces are popped, even if some of them aren't objects.
         for _ in 0..count {
-            interfaces.push(self.pop().as_object()?);
+            if let Some(interface) = self.pop_object("ImplementsOp") {
+                interfaces.push(interface);
+            }
         }
+        let constr = match constr {
+            Some(constr) => constr,
+            None => return Ok(()),
+        };
 
         let mut prototype = constr
             .get("prototype", self, context)?
@@ -1939,8 +2357,16 @@ impl<'gc> Avm1<'gc> {
         &mut self,
         context: &mut UpdateContext<'_, 'gc, '_>,
     ) -> Result<(), Error> {
-        let constr = self.pop().as_object()?;
-        let obj = self.pop().as_object()?;
+        let constr = self.pop_object("InstanceOf");
+        let obj = self.pop();
+        let (constr, obj) = match (constr, obj) {
+            (Some(constr), Value::Object(obj)) => (constr, obj),
+            // Primitives are never instances of anything.
+            _ => {
+                self.push(false);
+                return Ok(());
+            }
+        };
 
         let prototype = constr
             .get("prototype", self, context)?
@@ -1958,8 +2384,7 @@ impl<'gc> Avm1<'gc> {
         jump_offset: i16,
         reader: &mut Reader<'_>,
     ) -> Result<(), Error> {
-        // TODO(Herschel): Handle out-of-bounds.
-        reader.seek(jump_offset.into());
+        self.jump(reader, jump_offset);
         Ok(())
     }
 
@@ -1997,7 +2422,7 @@ impl<'gc> Avm1<'gc> {
     fn action_mb_ascii_to_char(&mut self, _context: &mut UpdateContext) -> Result<(), Error> {
         // TODO(Herschel): Results on incorrect operands?
         use std::convert::TryFrom;
-        let val = char::try_from(self.pop().as_f64()? as u32)?;
+        let val = char::try_from(self.pop().into_number_v1() as u32).unwrap_or('\0');
         self.push(val.to_string());
         Ok(())
     }
@@ -2012,10 +2437,11 @@ impl<'gc> Avm1<'gc> {
 
     fn action_mb_string_extract(&mut self, _context: &mut UpdateContext) -> Result<(), Error> {
         // TODO(Herschel): Result with incorrect operands?
-        let len = self.pop().as_f64()? as usize;
-        let start = self.pop().as_f64()? as usize;
+        let len = self.pop().into_number_v1() as usize;
+        let start = self.pop().into_number_v1() as usize;
         let s = self.pop().into_string(self.current_swf_version());
-        let result = s[len..len + start].to_string(); // TODO(Herschel): Flash uses UTF-16 internally.
+        // TODO(Herschel): Flash uses UTF-16 internally.
+        let result = s.chars().skip(start).take(len).collect::<String>();
         self.push(result);
         Ok(())
     }
@@ -2028,8 +2454,8 @@ impl<'gc> Avm1<'gc> {
     }
 
     fn action_multiply(&mut self, context: &mut UpdateContext<'_, 'gc, '_>) -> Result<(), Error> {
-        let a = self.pop().as_number(self, context)?;
-        let b = self.pop().as_number(self, context)?;
+        let a = self.pop_arithmetic_operand(context)?;
+        let b = self.pop_arithmetic_operand(context)?;
         self.push(a * b);
         Ok(())
     }
@@ -2065,21 +2491,35 @@ impl<'gc> Avm1<'gc> {
     fn action_new_method(&mut self, context: &mut UpdateContext<'_, 'gc, '_>) -> Result<(), Error> {
         let method_name = self.pop();
         let object_val = self.pop();
-        let num_args = self.pop().as_i64()?;
-        let mut args = Vec::new();
-        for _ in 0..num_args {
-            args.push(self.pop());
-        }
+        let args = self.pop_args(context)?;
 
         let object = value_object::ValueObject::boxed(self, context, object_val);
-        let constructor = object
-            .get(&method_name.as_string()?, self, context)?
+        let method_name = method_name.coerce_to_string(self, context)?;
+        let constructor = match object
+            .get(&method_name, self, context)?
             .resolve(self, context)?
-            .as_object()?;
-        let prototype = constructor
+        {
+            Value::Object(constructor) => constructor,
+            _ => {
+                avm_debug!("NewMethod: Object is not a function: {}", method_name);
+                self.push(Value::Undefined);
+                return Ok(());
+            }
+        };
+        let prototype = match constructor
             .get("prototype", self, context)?
             .resolve(self, context)?
-            .as_object()?;
+        {
+            Value::Object(prototype) => prototype,
+            _ => {
+                log::warn!(
+                    "NewMethod: Constructor has invalid prototype: {}",
+                    method_name
+                );
+                self.push(Value::Undefined);
+                return Ok(());
+            }
+        };
 
         let this = prototype.new(self, context, prototype, &args)?;
 
@@ -2100,11 +2540,7 @@ impl<'gc> Avm1<'gc> {
 
     fn action_new_object(&mut self, context: &mut UpdateContext<'_, 'gc, '_>) -> Result<(), Error> {
         let fn_name = self.pop();
-        let num_args = self.pop().as_i64()?;
-        let mut args = Vec::new();
-        for _ in 0..num_args {
-            args.push(self.pop());
-        }
+        let args = self.pop_args(context)?;
 
         let mut ret = Value::Undefined;
 
@@ -2139,7 +2575,7 @@ impl<'gc> Avm1<'gc> {
                     log::warn!("NewObject: Constructor has invalid prototype: {}", fn_name);
                 }
             } else {
-                log::warn!("NewObject: Object is not a function: {}", fn_name);
+                avm_debug!("NewObject: Object is not a function: {}", fn_name);
             }
         } else {
             log::warn!("NewObject: Expected String for object name: {:?}", fn_name);
@@ -2238,7 +2674,7 @@ impl<'gc> Avm1<'gc> {
     }
 
     fn action_push_duplicate(&mut self, _context: &mut UpdateContext) -> Result<(), Error> {
-        let val = self.stack.last().ok_or("Stack underflow")?.clone();
+        let val = self.stack.last().cloned().unwrap_or(Value::Undefined);
         self.push(val);
         Ok(())
     }
@@ -2304,7 +2740,7 @@ impl<'gc> Avm1<'gc> {
         context: &mut UpdateContext<'_, 'gc, '_>,
     ) -> Result<(), Error> {
         let value = self.pop();
-        let prop_index = self.pop().as_u32()? as usize;
+        let prop_index = self.pop().into_number_v1() as usize;
         let path = self.pop();
         if let Some(target) = self.target_clip() {
             if let Some(clip) = self.resolve_target_display_object(context, target, path)? {
@@ -2364,7 +2800,15 @@ impl<'gc> Avm1<'gc> {
         } else {
             log::warn!("SetTarget failed: {} not found", target);
             // TODO: Emulate AVM1 trace error message.
-            log::info!(target: "avm_trace", "Target not found: Target=\"{}\" Base=\"{}\"", target, base_clip.path());
+            let trace_context = self.trace_context();
+            context.log.avm_trace_with_context(
+                &format!(
+                    "Target not found: Target=\"{}\" Base=\"{}\"",
+                    target,
+                    base_clip.path()
+                ),
+                &trace_context,
+            );
 
             // When SetTarget has an invalid target, subsequent GetVariables act
             // as if they are targeting root, but subsequent Play/Stop/etc.
@@ -2526,8 +2970,8 @@ impl<'gc> Avm1<'gc> {
     fn action_string_extract(&mut self, _context: &mut UpdateContext) -> Result<(), Error> {
         // SWFv4 substring
         // TODO(Herschel): Result with incorrect operands?
-        let len = self.pop().as_f64()? as usize;
-        let start = self.pop().as_f64()? as usize;
+        let len = self.pop().into_number_v1() as usize;
+        let start = self.pop().into_number_v1() as usize;
         let s = self.pop().into_string(self.current_swf_version());
         // This is specifically a non-UTF8 aware substring.
         // SWFv4 only used ANSI strings.
@@ -2587,8 +3031,8 @@ impl<'gc> Avm1<'gc> {
     }
 
     fn action_subtract(&mut self, context: &mut UpdateContext<'_, 'gc, '_>) -> Result<(), Error> {
-        let a = self.pop().as_number(self, context)?;
-        let b = self.pop().as_number(self, context)?;
+        let a = self.pop_arithmetic_operand(context)?;
+        let b = self.pop_arithmetic_operand(context)?;
         self.push(b - a);
         Ok(())
     }
@@ -2635,7 +3079,8 @@ impl<'gc> Avm1<'gc> {
         } else {
             val.coerce_to_string(self, context)?
         };
-        log::info!(target: "avm_trace", "{}", out);
+        let trace_context = self.trace_context();
+        context.log.avm_trace_with_context(&out, &trace_context);
         Ok(())
     }
 
@@ -2669,7 +3114,7 @@ impl<'gc> Avm1<'gc> {
         r: &mut Reader<'_>,
     ) -> Result<(), Error> {
         // TODO(Herschel): Always true for now.
-        let _frame_num = self.pop().as_f64()? as u16;
+        let _frame_num = self.pop();
         let loaded = true;
         if !loaded {
             // Note that the offset is given in # of actions, NOT in bytes.
@@ -2684,7 +3129,11 @@ impl<'gc> Avm1<'gc> {
         context: &mut UpdateContext<'_, 'gc, '_>,
         actions: &[u8],
     ) -> Result<(), Error> {
-        let object = self.pop().as_object()?;
+        // The block is skipped if there is no object to look up variables on.
+        let object = match self.pop_object("With") {
+            Some(object) => object,
+            None => return Ok(()),
+        };
         let block = self
             .current_stack_frame()
             .unwrap()
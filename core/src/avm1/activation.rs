@@ -76,6 +76,9 @@ pub struct Activation<'gc> {
     /// The arguments this function was called by.
     arguments: Option<Object<'gc>>,
 
+    /// The function being executed, if any.
+    callee: Option<Object<'gc>>,
+
     /// The return value of the activation.
     return_value: Option<Value<'gc>>,
 
@@ -117,6 +120,7 @@ unsafe impl<'gc> gc_arena::Collect for Activation<'gc> {
         self.constant_pool.trace(cc);
         self.this.trace(cc);
         self.arguments.trace(cc);
+        self.callee.trace(cc);
         self.return_value.trace(cc);
         self.local_registers.trace(cc);
         self.base_clip.trace(cc);
@@ -144,6 +148,7 @@ impl<'gc> Activation<'gc> {
             target_clip: Some(base_clip),
             this,
             arguments,
+            callee: None,
             return_value: None,
             is_function: false,
             local_registers: None,
@@ -151,6 +156,7 @@ impl<'gc> Activation<'gc> {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn from_function(
         swf_version: u8,
         code: SwfSlice,
@@ -159,6 +165,7 @@ impl<'gc> Activation<'gc> {
         base_clip: DisplayObject<'gc>,
         this: Object<'gc>,
         arguments: Option<Object<'gc>>,
+        callee: Option<Object<'gc>>,
     ) -> Activation<'gc> {
         Activation {
             swf_version,
@@ -170,6 +177,7 @@ impl<'gc> Activation<'gc> {
             target_clip: Some(base_clip),
             this,
             arguments,
+            callee,
             return_value: None,
             is_function: true,
             local_registers: None,
@@ -207,6 +215,7 @@ impl<'gc> Activation<'gc> {
             target_clip: Some(base_clip),
             this: globals,
             arguments: None,
+            callee: None,
             return_value: None,
             is_function: false,
             local_registers: None,
@@ -226,6 +235,7 @@ impl<'gc> Activation<'gc> {
             target_clip: self.target_clip,
             this: self.this,
             arguments: self.arguments,
+            callee: self.callee,
             return_value: None,
             is_function: false,
             local_registers: self.local_registers,
@@ -304,6 +314,11 @@ impl<'gc> Activation<'gc> {
         self.target_clip = value;
     }
 
+    /// Returns the function being executed, if any.
+    pub fn callee(&self) -> Option<Object<'gc>> {
+        self.callee
+    }
+
     /// Indicates whether or not the end of this scope should return a value.
     pub fn can_return(&self) -> bool {
         self.is_function
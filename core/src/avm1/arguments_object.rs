@@ -0,0 +1,353 @@
+//! Object impl for the `arguments` of a function call
+
+use crate::avm1::function::Executable;
+use crate::avm1::object::{ObjectPtr, TObject};
+use crate::avm1::property::Attribute::{self, *};
+use crate::avm1::return_value::ReturnValue;
+use crate::avm1::{Avm1, Error, Object, ScriptObject, UpdateContext, Value};
+use enumset::EnumSet;
+use gc_arena::{Collect, GcCell, MutationContext};
+use std::fmt;
+
+/// The `arguments` object of an executing function.
+///
+/// In functions defined with `DefineFunction`, each argument passed to a named parameter is
+/// shared with that parameter: assigning to one is visible through the other.
+#[derive(Clone, Copy, Collect)]
+#[collect(no_drop)]
+pub struct ArgumentsObject<'gc>(GcCell<'gc, ArgumentsObjectData<'gc>>);
+
+#[derive(Clone, Collect)]
+#[collect(no_drop)]
+pub struct ArgumentsObjectData<'gc> {
+    /// Base implementation of ScriptObject.
+    base: ScriptObject<'gc>,
+
+    /// The local variables of the function call, which hold the named parameters.
+    locals: Object<'gc>,
+
+    /// The names of the parameters that share their value with the argument at the same index.
+    aliased_parameters: Vec<String>,
+}
+
+impl<'gc> ArgumentsObject<'gc> {
+    /// Construct the `arguments` of a function call.
+    ///
+    /// `callee` is the function being called, and `caller` is the function that called it, if
+    /// any. The first `aliased_parameters.len()` arguments are shared with the local variables
+    /// of the same name in `locals`.
+    pub fn new(
+        gc_context: MutationContext<'gc, '_>,
+        proto: Option<Object<'gc>>,
+        args: &[Value<'gc>],
+        callee: Option<Object<'gc>>,
+        caller: Option<Object<'gc>>,
+        locals: Object<'gc>,
+        aliased_parameters: Vec<String>,
+    ) -> Self {
+        let base = ScriptObject::object(gc_context, proto);
+        for (i, arg) in args.iter().enumerate() {
+            base.define_value(gc_context, &i.to_string(), arg.clone(), DontDelete.into());
+        }
+
+        base.define_value(
+            gc_context,
+            "length",
+            args.len().into(),
+            DontDelete | DontEnum,
+        );
+        base.define_value(
+            gc_context,
+            "callee",
+            callee.map(Value::Object).unwrap_or(Value::Undefined),
+            DontDelete | DontEnum,
+        );
+        base.define_value(
+            gc_context,
+            "caller",
+            caller.map(Value::Object).unwrap_or(Value::Null),
+            DontDelete | DontEnum,
+        );
+
+        ArgumentsObject(GcCell::allocate(
+            gc_context,
+            ArgumentsObjectData {
+                base,
+                locals,
+                aliased_parameters,
+            },
+        ))
+    }
+
+    /// Returns the local variables and the name of the parameter that share their value with
+    /// the given property, if any.
+    fn aliased_parameter(&self, name: &str) -> Option<(Object<'gc>, String)> {
+        let index = name.parse::<usize>().ok()?;
+        if index.to_string() != name {
+            return None;
+        }
+
+        let data = self.0.read();
+        data.aliased_parameters
+            .get(index)
+            .map(|param| (data.locals, param.clone()))
+    }
+}
+
+impl fmt::Debug for ArgumentsObject<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let this = self.0.read();
+        f.debug_struct("ArgumentsObject")
+            .field("base", &this.base)
+            .field("aliased_parameters", &this.aliased_parameters)
+            .finish()
+    }
+}
+
+impl<'gc> TObject<'gc> for ArgumentsObject<'gc> {
+    fn get_local(
+        &self,
+        name: &str,
+        avm: &mut Avm1<'gc>,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+        this: Object<'gc>,
+    ) -> Result<ReturnValue<'gc>, Error> {
+        if let Some((locals, param)) = self.aliased_parameter(name) {
+            if self.has_own_property(avm, context, name) {
+                return locals.get(&param, avm, context);
+            }
+        }
+
+        self.0.read().base.get_local(name, avm, context, this)
+    }
+
+    fn set(
+        &self,
+        name: &str,
+        value: Value<'gc>,
+        avm: &mut Avm1<'gc>,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+    ) -> Result<(), Error> {
+        if let Some((locals, param)) = self.aliased_parameter(name) {
+            if self.has_own_property(avm, context, name) {
+                return locals.set(&param, value, avm, context);
+            }
+        }
+
+        self.0.read().base.set(name, value, avm, context)
+    }
+
+    fn call(
+        &self,
+        avm: &mut Avm1<'gc>,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+        this: Object<'gc>,
+        base_proto: Option<Object<'gc>>,
+        args: &[Value<'gc>],
+    ) -> Result<ReturnValue<'gc>, Error> {
+        self.0
+            .read()
+            .base
+            .call(avm, context, this, base_proto, args)
+    }
+
+    fn call_setter(
+        &self,
+        name: &str,
+        value: Value<'gc>,
+        avm: &mut Avm1<'gc>,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+        this: Object<'gc>,
+    ) -> Result<ReturnValue<'gc>, Error> {
+        self.0
+            .read()
+            .base
+            .call_setter(name, value, avm, context, this)
+    }
+
+    #[allow(clippy::new_ret_no_self)]
+    fn new(
+        &self,
+        avm: &mut Avm1<'gc>,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+        this: Object<'gc>,
+        args: &[Value<'gc>],
+    ) -> Result<Object<'gc>, Error> {
+        self.0.read().base.new(avm, context, this, args)
+    }
+
+    fn delete(
+        &self,
+        avm: &mut Avm1<'gc>,
+        gc_context: MutationContext<'gc, '_>,
+        name: &str,
+    ) -> bool {
+        self.0.read().base.delete(avm, gc_context, name)
+    }
+
+    fn add_property(
+        &self,
+        gc_context: MutationContext<'gc, '_>,
+        name: &str,
+        get: Executable<'gc>,
+        set: Option<Executable<'gc>>,
+        attributes: EnumSet<Attribute>,
+    ) {
+        self.0
+            .read()
+            .base
+            .add_property(gc_context, name, get, set, attributes)
+    }
+
+    fn add_property_with_case(
+        &self,
+        avm: &mut Avm1<'gc>,
+        gc_context: MutationContext<'gc, '_>,
+        name: &str,
+        get: Executable<'gc>,
+        set: Option<Executable<'gc>>,
+        attributes: EnumSet<Attribute>,
+    ) {
+        self.0
+            .read()
+            .base
+            .add_property_with_case(avm, gc_context, name, get, set, attributes)
+    }
+
+    fn define_value(
+        &self,
+        gc_context: MutationContext<'gc, '_>,
+        name: &str,
+        value: Value<'gc>,
+        attributes: EnumSet<Attribute>,
+    ) {
+        self.0
+            .read()
+            .base
+            .define_value(gc_context, name, value, attributes)
+    }
+
+    fn set_attributes(
+        &mut self,
+        gc_context: MutationContext<'gc, '_>,
+        name: Option<&str>,
+        set_attributes: EnumSet<Attribute>,
+        clear_attributes: EnumSet<Attribute>,
+    ) {
+        self.0.write(gc_context).base.set_attributes(
+            gc_context,
+            name,
+            set_attributes,
+            clear_attributes,
+        )
+    }
+
+    fn proto(&self) -> Option<Object<'gc>> {
+        self.0.read().base.proto()
+    }
+
+    fn set_proto(&self, gc_context: MutationContext<'gc, '_>, prototype: Option<Object<'gc>>) {
+        self.0
+            .write(gc_context)
+            .base
+            .set_proto(gc_context, prototype);
+    }
+
+    fn has_property(
+        &self,
+        avm: &mut Avm1<'gc>,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+        name: &str,
+    ) -> bool {
+        self.0.read().base.has_property(avm, context, name)
+    }
+
+    fn has_own_property(
+        &self,
+        avm: &mut Avm1<'gc>,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+        name: &str,
+    ) -> bool {
+        self.0.read().base.has_own_property(avm, context, name)
+    }
+
+    fn has_own_virtual(
+        &self,
+        avm: &mut Avm1<'gc>,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+        name: &str,
+    ) -> bool {
+        self.0.read().base.has_own_virtual(avm, context, name)
+    }
+
+    fn is_property_overwritable(&self, avm: &mut Avm1<'gc>, name: &str) -> bool {
+        self.0.read().base.is_property_overwritable(avm, name)
+    }
+
+    fn is_property_enumerable(&self, avm: &mut Avm1<'gc>, name: &str) -> bool {
+        self.0.read().base.is_property_enumerable(avm, name)
+    }
+
+    fn get_keys(&self, avm: &mut Avm1<'gc>) -> Vec<String> {
+        self.0.read().base.get_keys(avm)
+    }
+
+    fn as_string(&self) -> String {
+        self.0.read().base.as_string()
+    }
+
+    fn type_of(&self) -> &'static str {
+        self.0.read().base.type_of()
+    }
+
+    fn interfaces(&self) -> Vec<Object<'gc>> {
+        self.0.read().base.interfaces()
+    }
+
+    fn set_interfaces(&mut self, context: MutationContext<'gc, '_>, iface_list: Vec<Object<'gc>>) {
+        self.0
+            .write(context)
+            .base
+            .set_interfaces(context, iface_list)
+    }
+
+    fn as_script_object(&self) -> Option<ScriptObject<'gc>> {
+        Some(self.0.read().base)
+    }
+
+    fn as_ptr(&self) -> *const ObjectPtr {
+        self.0.read().base.as_ptr() as *const ObjectPtr
+    }
+
+    fn length(&self) -> usize {
+        self.0.read().base.length()
+    }
+
+    fn array(&self) -> Vec<Value<'gc>> {
+        self.0.read().base.array()
+    }
+
+    fn set_length(&self, gc_context: MutationContext<'gc, '_>, length: usize) {
+        self.0.read().base.set_length(gc_context, length)
+    }
+
+    fn array_element(&self, index: usize) -> Value<'gc> {
+        self.0.read().base.array_element(index)
+    }
+
+    fn set_array_element(
+        &self,
+        index: usize,
+        value: Value<'gc>,
+        gc_context: MutationContext<'gc, '_>,
+    ) -> usize {
+        self.0
+            .read()
+            .base
+            .set_array_element(index, value, gc_context)
+    }
+
+    fn delete_array_element(&self, index: usize, gc_context: MutationContext<'gc, '_>) {
+        self.0.read().base.delete_array_element(index, gc_context)
+    }
+}
@@ -1,6 +1,7 @@
 //! Code relating to executable functions + calling conventions.
 
 use crate::avm1::activation::Activation;
+use crate::avm1::arguments_object::ArgumentsObject;
 use crate::avm1::property::{Attribute, Attribute::*};
 use crate::avm1::return_value::ReturnValue;
 use crate::avm1::scope::Scope;
@@ -10,7 +11,7 @@ use crate::avm1::{Avm1, Error, Object, ObjectPtr, ScriptObject, TObject, UpdateC
 use crate::display_object::{DisplayObject, TDisplayObject};
 use crate::tag_utils::SwfSlice;
 use enumset::EnumSet;
-use gc_arena::{Collect, CollectionContext, GcCell, MutationContext};
+use gc_arena::{Collect, CollectionContext, Gc, GcCell, MutationContext};
 use std::fmt;
 use swf::avm1::types::FunctionParam;
 
@@ -48,6 +49,10 @@ pub struct Avm1Function<'gc> {
     /// The name of the function, if not anonymous.
     name: Option<String>,
 
+    /// Whether this function was defined by `DefineFunction2`, rather than
+    /// `DefineFunction`.
+    is_function2: bool,
+
     /// The number of registers to allocate for this function's private register
     /// set. Any register beyond this ID will be served from the global one.
     register_count: u8,
@@ -101,6 +106,7 @@ impl<'gc> Avm1Function<'gc> {
             swf_version,
             data: actions,
             name,
+            is_function2: false,
             register_count: 0,
             preload_parent: false,
             preload_root: false,
@@ -145,12 +151,13 @@ impl<'gc> Avm1Function<'gc> {
             swf_version,
             data: actions,
             name,
+            is_function2: true,
             register_count: swf_function.register_count,
             preload_parent: swf_function.preload_parent,
             preload_root: swf_function.preload_root,
             suppress_super: swf_function.suppress_super,
             preload_super: swf_function.preload_super,
-            suppress_arguments: swf_function.suppress_super,
+            suppress_arguments: swf_function.suppress_arguments,
             preload_arguments: swf_function.preload_arguments,
             suppress_this: swf_function.suppress_this,
             preload_this: swf_function.preload_this,
@@ -174,9 +181,48 @@ impl<'gc> Avm1Function<'gc> {
         self.scope
     }
 
+    pub fn is_function2(&self) -> bool {
+        self.is_function2
+    }
+
     pub fn register_count(&self) -> u8 {
         self.register_count
     }
+
+    pub fn constant_pool(&self) -> GcCell<'gc, Vec<String>> {
+        self.constant_pool
+    }
+
+    pub fn base_clip(&self) -> DisplayObject<'gc> {
+        self.base_clip
+    }
+
+    /// Describe this function as the `DefineFunction2` action that would
+    /// define it, without its body.
+    pub fn swf_function(&self) -> swf::avm1::types::Function<'_> {
+        swf::avm1::types::Function {
+            name: self.name.as_deref().unwrap_or(""),
+            register_count: self.register_count,
+            params: self
+                .params
+                .iter()
+                .map(|(register_index, name)| FunctionParam {
+                    name,
+                    register_index: *register_index,
+                })
+                .collect(),
+            preload_parent: self.preload_parent,
+            preload_root: self.preload_root,
+            suppress_super: self.suppress_super,
+            preload_super: self.preload_super,
+            suppress_arguments: self.suppress_arguments,
+            preload_arguments: self.preload_arguments,
+            suppress_this: self.suppress_this,
+            preload_this: self.preload_this,
+            preload_global: self.preload_global,
+            actions: &[],
+        }
+    }
 }
 
 /// Represents a function that can be defined in the Ruffle runtime or by the
@@ -188,7 +234,10 @@ pub enum Executable<'gc> {
 
     /// ActionScript data defined by a previous `DefineFunction` or
     /// `DefineFunction2` action.
-    Action(Avm1Function<'gc>),
+    ///
+    /// The function is shared rather than owned, so that calling it doesn't
+    /// copy its name and parameters.
+    Action(Gc<'gc, Avm1Function<'gc>>),
 }
 
 unsafe impl<'gc> Collect for Executable<'gc> {
@@ -219,6 +268,9 @@ impl<'gc> Executable<'gc> {
     /// returns. If on-stack execution is possible, then this function returns
     /// a return value you must push onto the stack. Otherwise, you must
     /// create a new stack frame and execute the action data yourself.
+    ///
+    /// `callee` is the function object being called, if any. It is exposed to
+    /// ActionScript as `arguments.callee`.
     pub fn exec(
         &self,
         avm: &mut Avm1<'gc>,
@@ -226,6 +278,7 @@ impl<'gc> Executable<'gc> {
         this: Object<'gc>,
         base_proto: Option<Object<'gc>>,
         args: &[Value<'gc>],
+        callee: Option<Object<'gc>>,
     ) -> Result<ReturnValue<'gc>, Error> {
         match self {
             Executable::Native(nf) => nf(avm, ac, this, args),
@@ -234,26 +287,36 @@ impl<'gc> Executable<'gc> {
                     ac.gc_context,
                     Scope::new_local_scope(af.scope(), ac.gc_context),
                 );
-                let arguments = ScriptObject::object(ac.gc_context, Some(avm.prototypes().object));
-                if !af.suppress_arguments {
-                    for i in 0..args.len() {
-                        arguments.define_value(
+                let argcell: Option<Object<'gc>> = if !af.suppress_arguments {
+                    // Only `DefineFunction` functions share their arguments with their
+                    // named parameters.
+                    let aliased_parameters = if af.is_function2 {
+                        vec![]
+                    } else {
+                        af.params
+                            .iter()
+                            .take(args.len())
+                            .map(|(_register, name)| name.clone())
+                            .collect()
+                    };
+                    let caller = avm
+                        .current_stack_frame()
+                        .and_then(|frame| frame.read().callee());
+                    Some(
+                        ArgumentsObject::new(
                             ac.gc_context,
-                            &format!("{}", i),
-                            args.get(i).unwrap().clone(),
-                            DontDelete.into(),
+                            Some(avm.prototypes().object),
+                            args,
+                            callee,
+                            caller,
+                            *child_scope.read().locals(),
+                            aliased_parameters,
                         )
-                    }
-
-                    arguments.define_value(
-                        ac.gc_context,
-                        "length",
-                        args.len().into(),
-                        DontDelete | DontEnum,
-                    );
-                }
-
-                let argcell = arguments.into();
+                        .into(),
+                    )
+                } else {
+                    None
+                };
                 let super_object: Option<Object<'gc>> = if !af.suppress_super {
                     Some(
                         SuperObject::from_this_and_base_proto(
@@ -285,7 +348,8 @@ impl<'gc> Executable<'gc> {
                         af.constant_pool,
                         af.base_clip,
                         this,
-                        Some(argcell),
+                        argcell,
+                        callee,
                     ),
                 );
                 let mut frame = frame_cell.write(ac.gc_context);
@@ -304,7 +368,9 @@ impl<'gc> Executable<'gc> {
                 if af.preload_arguments {
                     //TODO: What happens if you specify both suppress and
                     //preload for arguments?
-                    frame.set_local_register(preload_r, argcell, ac.gc_context);
+                    if let Some(argcell) = argcell {
+                        frame.set_local_register(preload_r, argcell, ac.gc_context);
+                    }
                     preload_r += 1;
                 }
 
@@ -339,7 +405,8 @@ impl<'gc> Executable<'gc> {
                 }
 
                 if af.preload_global {
-                    frame.set_local_register(preload_r, avm.global_object(ac), ac.gc_context);
+                    let globals = avm.global_object_for_clip(af.base_clip, ac);
+                    frame.set_local_register(preload_r, globals, ac.gc_context);
                 }
 
                 //TODO: What happens if the argument registers clash with the
@@ -369,8 +436,8 @@ impl<'gc> From<NativeFunction<'gc>> for Executable<'gc> {
     }
 }
 
-impl<'gc> From<Avm1Function<'gc>> for Executable<'gc> {
-    fn from(af: Avm1Function<'gc>) -> Self {
+impl<'gc> From<Gc<'gc, Avm1Function<'gc>>> for Executable<'gc> {
+    fn from(af: Gc<'gc, Avm1Function<'gc>>) -> Self {
         Executable::Action(af)
     }
 }
@@ -480,7 +547,7 @@ impl<'gc> TObject<'gc> for FunctionObject<'gc> {
         args: &[Value<'gc>],
     ) -> Result<ReturnValue<'gc>, Error> {
         if let Some(exec) = self.as_executable() {
-            exec.exec(avm, context, this, base_proto, args)
+            exec.exec(avm, context, this, base_proto, args, Some((*self).into()))
         } else {
             Ok(Value::Undefined.into())
         }
@@ -10,21 +10,30 @@ use rand::Rng;
 use std::f64;
 
 mod array;
+pub(crate) mod as_broadcaster;
+mod bitmap_data;
 pub(crate) mod boolean;
 pub(crate) mod button;
 mod color;
+mod context_menu;
+mod context_menu_item;
 pub(crate) mod display_object;
+pub(crate) mod external_interface;
 mod function;
 mod key;
 mod math;
 pub(crate) mod mouse;
 pub(crate) mod movie_clip;
 mod movie_clip_loader;
+pub(crate) mod net_connection;
 pub(crate) mod number;
 mod object;
+mod selection;
+pub(crate) mod shared_object;
 mod sound;
 mod stage;
 pub(crate) mod string;
+mod system;
 pub(crate) mod text_field;
 mod text_format;
 mod xml;
@@ -71,6 +80,18 @@ pub fn random<'gc>(
     }
 }
 
+/// Redraw the stage as soon as the current event has been handled, without waiting for the next
+/// frame. The timeline does not advance.
+pub fn update_after_event<'gc>(
+    _avm: &mut Avm1<'gc>,
+    action_context: &mut UpdateContext<'_, 'gc, '_>,
+    _this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    *action_context.needs_render = true;
+    Ok(Value::Undefined.into())
+}
+
 pub fn is_nan<'gc>(
     avm: &mut Avm1<'gc>,
     action_context: &mut UpdateContext<'_, 'gc, '_>,
@@ -115,6 +136,7 @@ pub fn get_nan<'gc>(
 /// user-modifiable.
 #[derive(Clone)]
 pub struct SystemPrototypes<'gc> {
+    pub bitmap_data: Object<'gc>,
     pub button: Object<'gc>,
     pub object: Object<'gc>,
     pub function: Object<'gc>,
@@ -127,6 +149,7 @@ pub struct SystemPrototypes<'gc> {
     pub string: Object<'gc>,
     pub number: Object<'gc>,
     pub boolean: Object<'gc>,
+    pub shared_object: Object<'gc>,
 }
 
 unsafe impl<'gc> gc_arena::Collect for SystemPrototypes<'gc> {
@@ -144,6 +167,8 @@ unsafe impl<'gc> gc_arena::Collect for SystemPrototypes<'gc> {
         self.string.trace(cc);
         self.number.trace(cc);
         self.boolean.trace(cc);
+        self.shared_object.trace(cc);
+        self.bitmap_data.trace(cc);
     }
 }
 
@@ -164,6 +189,12 @@ pub fn create_globals<'gc>(
     let movie_clip_loader_proto: Object<'gc> =
         movie_clip_loader::create_proto(gc_context, object_proto, function_proto);
 
+    let net_connection_proto: Object<'gc> =
+        net_connection::create_proto(gc_context, object_proto, function_proto);
+
+    let shared_object_proto: Object<'gc> =
+        shared_object::create_proto(gc_context, object_proto, function_proto);
+
     let sound_proto: Object<'gc> = sound::create_proto(gc_context, object_proto, function_proto);
 
     let text_field_proto: Object<'gc> =
@@ -174,6 +205,10 @@ pub fn create_globals<'gc>(
     let array_proto: Object<'gc> = array::create_proto(gc_context, object_proto, function_proto);
 
     let color_proto: Object<'gc> = color::create_proto(gc_context, object_proto, function_proto);
+    let context_menu_proto: Object<'gc> =
+        context_menu::create_proto(gc_context, object_proto, function_proto);
+    let context_menu_item_proto: Object<'gc> =
+        context_menu_item::create_proto(gc_context, object_proto, function_proto);
     let xmlnode_proto: Object<'gc> =
         xml::create_xmlnode_proto(gc_context, object_proto, function_proto);
 
@@ -183,6 +218,8 @@ pub fn create_globals<'gc>(
     let number_proto: Object<'gc> = number::create_proto(gc_context, object_proto, function_proto);
     let boolean_proto: Object<'gc> =
         boolean::create_proto(gc_context, object_proto, function_proto);
+    let bitmap_data_proto: Object<'gc> =
+        bitmap_data::create_proto(gc_context, object_proto, function_proto);
 
     //TODO: These need to be constructors and should also set `.prototype` on each one
     let object = object::create_object_object(gc_context, object_proto, function_proto);
@@ -199,6 +236,18 @@ pub fn create_globals<'gc>(
         Some(function_proto),
         Some(color_proto),
     );
+    let context_menu = FunctionObject::function(
+        gc_context,
+        Executable::Native(context_menu::constructor),
+        Some(function_proto),
+        Some(context_menu_proto),
+    );
+    let context_menu_item = FunctionObject::function(
+        gc_context,
+        Executable::Native(context_menu_item::constructor),
+        Some(function_proto),
+        Some(context_menu_item_proto),
+    );
     let function = FunctionObject::function(
         gc_context,
         Executable::Native(function::constructor),
@@ -217,6 +266,17 @@ pub fn create_globals<'gc>(
         Some(function_proto),
         Some(movie_clip_loader_proto),
     );
+    let net_connection = FunctionObject::function(
+        gc_context,
+        Executable::Native(net_connection::constructor),
+        Some(function_proto),
+        Some(net_connection_proto),
+    );
+    let shared_object = shared_object::create_shared_object_object(
+        gc_context,
+        Some(shared_object_proto),
+        Some(function_proto),
+    );
     let sound = FunctionObject::function(
         gc_context,
         Executable::Native(sound::constructor),
@@ -252,13 +312,52 @@ pub fn create_globals<'gc>(
     let number = number::create_number_object(gc_context, Some(number_proto), Some(function_proto));
     let boolean =
         boolean::create_boolean_object(gc_context, Some(boolean_proto), Some(function_proto));
+    let bitmap_data = bitmap_data::create_bitmap_data_object(
+        gc_context,
+        Some(bitmap_data_proto),
+        Some(function_proto),
+    );
 
-    let listeners = SystemListeners::new(gc_context, Some(array_proto));
+    let mouse = mouse::create_mouse_object(
+        gc_context,
+        Some(object_proto),
+        Some(array_proto),
+        Some(function_proto),
+    );
+    let key = key::create_key_object(
+        gc_context,
+        Some(object_proto),
+        Some(array_proto),
+        Some(function_proto),
+    );
+    let listeners = SystemListeners { key, mouse };
 
     let mut globals = ScriptObject::bare_object(gc_context);
     globals.define_value(gc_context, "Array", array.into(), EnumSet::empty());
+    globals.define_value(
+        gc_context,
+        "AsBroadcaster",
+        Value::Object(as_broadcaster::create(
+            gc_context,
+            Some(object_proto),
+            Some(function_proto),
+        )),
+        EnumSet::empty(),
+    );
     globals.define_value(gc_context, "Button", button.into(), EnumSet::empty());
     globals.define_value(gc_context, "Color", color.into(), EnumSet::empty());
+    globals.define_value(
+        gc_context,
+        "ContextMenu",
+        context_menu.into(),
+        EnumSet::empty(),
+    );
+    globals.define_value(
+        gc_context,
+        "ContextMenuItem",
+        context_menu_item.into(),
+        EnumSet::empty(),
+    );
     globals.define_value(gc_context, "Object", object.into(), EnumSet::empty());
     globals.define_value(gc_context, "Function", function.into(), EnumSet::empty());
     globals.define_value(gc_context, "MovieClip", movie_clip.into(), EnumSet::empty());
@@ -268,6 +367,18 @@ pub fn create_globals<'gc>(
         movie_clip_loader.into(),
         EnumSet::empty(),
     );
+    globals.define_value(
+        gc_context,
+        "NetConnection",
+        net_connection.into(),
+        EnumSet::empty(),
+    );
+    globals.define_value(
+        gc_context,
+        "SharedObject",
+        shared_object.into(),
+        EnumSet::empty(),
+    );
     globals.define_value(gc_context, "Sound", sound.into(), EnumSet::empty());
     globals.define_value(gc_context, "TextField", text_field.into(), EnumSet::empty());
     globals.define_value(
@@ -282,6 +393,28 @@ pub fn create_globals<'gc>(
     globals.define_value(gc_context, "Number", number.into(), EnumSet::empty());
     globals.define_value(gc_context, "Boolean", boolean.into(), EnumSet::empty());
 
+    // Classes added in Flash 8 live in packages under `flash`.
+    let flash_display = ScriptObject::object(gc_context, Some(object_proto));
+    flash_display.define_value(
+        gc_context,
+        "BitmapData",
+        bitmap_data.into(),
+        EnumSet::empty(),
+    );
+    let flash = ScriptObject::object(gc_context, Some(object_proto));
+    flash.define_value(
+        gc_context,
+        "display",
+        Value::Object(flash_display.into()),
+        EnumSet::empty(),
+    );
+    globals.define_value(
+        gc_context,
+        "flash",
+        Value::Object(flash.into()),
+        EnumSet::empty(),
+    );
+
     globals.define_value(
         gc_context,
         "Math",
@@ -292,23 +425,25 @@ pub fn create_globals<'gc>(
         )),
         EnumSet::empty(),
     );
+    globals.define_value(gc_context, "Mouse", mouse.into(), EnumSet::empty());
     globals.define_value(
         gc_context,
-        "Mouse",
-        Value::Object(mouse::create_mouse_object(
+        "ExternalInterface",
+        Value::Object(external_interface::create(
             gc_context,
             Some(object_proto),
             Some(function_proto),
-            &listeners.mouse,
         )),
         EnumSet::empty(),
     );
+    globals.define_value(gc_context, "Key", key.into(), EnumSet::empty());
     globals.define_value(
         gc_context,
-        "Key",
-        Value::Object(key::create_key_object(
+        "Selection",
+        Value::Object(selection::create_selection_object(
             gc_context,
             Some(object_proto),
+            Some(array_proto),
             Some(function_proto),
         )),
         EnumSet::empty(),
@@ -324,6 +459,16 @@ pub fn create_globals<'gc>(
         )),
         EnumSet::empty(),
     );
+    globals.define_value(
+        gc_context,
+        "System",
+        Value::Object(system::create(
+            gc_context,
+            Some(object_proto),
+            Some(function_proto),
+        )),
+        EnumSet::empty(),
+    );
     globals.force_set_function(
         "isNaN",
         is_nan,
@@ -345,6 +490,13 @@ pub fn create_globals<'gc>(
         EnumSet::empty(),
         Some(function_proto),
     );
+    globals.force_set_function(
+        "updateAfterEvent",
+        update_after_event,
+        gc_context,
+        EnumSet::empty(),
+        Some(function_proto),
+    );
     globals.force_set_function(
         "ASSetPropFlags",
         object::as_set_prop_flags,
@@ -369,6 +521,7 @@ pub fn create_globals<'gc>(
 
     (
         SystemPrototypes {
+            bitmap_data: bitmap_data_proto,
             button: button_proto,
             object: object_proto,
             function: function_proto,
@@ -381,6 +534,7 @@ pub fn create_globals<'gc>(
             string: string_proto,
             number: number_proto,
             boolean: boolean_proto,
+            shared_object: shared_object_proto,
         },
         globals.into(),
         listeners,
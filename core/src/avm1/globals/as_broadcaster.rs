@@ -0,0 +1,270 @@
+//! `AsBroadcaster` impl
+//!
+//! Every built-in event source (`Key`, `Mouse`, `Stage`, `Selection`,
+//! `MovieClipLoader` and text fields) is initialized as a broadcaster, so
+//! they all share the same listener semantics:
+//!
+//! * Listeners live in the object's `_listeners` array, which is read at
+//!   dispatch time. Scripts may inspect, reorder or replace it.
+//! * Adding a listener that is already registered moves it to the end.
+//! * A broadcast dispatches to a snapshot of `_listeners`, in index order;
+//!   listeners added or removed by a handler only affect later broadcasts.
+
+use crate::avm1::function::{Executable, FunctionObject, NativeFunction};
+use crate::avm1::property::Attribute;
+use crate::avm1::return_value::ReturnValue;
+use crate::avm1::{Avm1, Error, Object, ScriptObject, TObject, UpdateContext, Value};
+use gc_arena::MutationContext;
+
+pub fn add_listener<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let new_listener = args.get(0).cloned().unwrap_or(Value::Undefined);
+    let listeners = this
+        .get("_listeners", avm, context)?
+        .resolve(avm, context)?;
+
+    if let Value::Object(listeners) = listeners {
+        remove_from(listeners, &new_listener, avm, context)?;
+        let length = length_of(listeners, avm, context)?;
+        listeners.set(&length.to_string(), new_listener, avm, context)?;
+    }
+
+    Ok(true.into())
+}
+
+pub fn remove_listener<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let old_listener = args.get(0).cloned().unwrap_or(Value::Undefined);
+    let listeners = this
+        .get("_listeners", avm, context)?
+        .resolve(avm, context)?;
+
+    if let Value::Object(listeners) = listeners {
+        return Ok(remove_from(listeners, &old_listener, avm, context)?.into());
+    }
+
+    Ok(false.into())
+}
+
+pub fn broadcast_message<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let event_name = args
+        .get(0)
+        .cloned()
+        .unwrap_or(Value::Undefined)
+        .coerce_to_string(avm, context)?;
+    let call_args = args.get(1..).unwrap_or_default();
+
+    broadcast(avm, context, this, &event_name, call_args)?;
+
+    Ok(Value::Undefined.into())
+}
+
+pub fn initialize<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    if let Some(Value::Object(broadcaster)) = args.get(0) {
+        // Like Flash, the methods are copied from `AsBroadcaster` itself, so
+        // scripts that patch them there affect every later broadcaster.
+        for &(name, _) in broadcaster_methods().iter() {
+            let method = this.get(name, avm, context)?.resolve(avm, context)?;
+            broadcaster.define_value(context.gc_context, name, method, Attribute::DontEnum.into());
+        }
+
+        let listeners = ScriptObject::array(context.gc_context, Some(avm.prototypes().array));
+        broadcaster.define_value(
+            context.gc_context,
+            "_listeners",
+            Value::Object(listeners.into()),
+            Attribute::DontEnum.into(),
+        );
+    }
+
+    Ok(Value::Undefined.into())
+}
+
+/// Turns `broadcaster` into an event source with an empty `_listeners` array.
+///
+/// This is `AsBroadcaster.initialize` for objects created by the player.
+pub fn initialize_internal<'gc>(
+    gc_context: MutationContext<'gc, '_>,
+    broadcaster: Object<'gc>,
+    array_proto: Option<Object<'gc>>,
+    fn_proto: Option<Object<'gc>>,
+) -> Object<'gc> {
+    for &(name, method) in broadcaster_methods().iter() {
+        broadcaster.define_value(
+            gc_context,
+            name,
+            FunctionObject::function(gc_context, Executable::Native(method), fn_proto, None).into(),
+            Attribute::DontEnum.into(),
+        );
+    }
+
+    let listeners: Object<'gc> = ScriptObject::array(gc_context, array_proto).into();
+    broadcaster.define_value(
+        gc_context,
+        "_listeners",
+        listeners.into(),
+        Attribute::DontEnum.into(),
+    );
+
+    listeners
+}
+
+/// Calls `method` on every listener of `broadcaster`, in `_listeners` order.
+pub fn broadcast<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    broadcaster: Object<'gc>,
+    method: &str,
+    args: &[Value<'gc>],
+) -> Result<(), Error> {
+    for (listener, handler) in prepare_handlers(avm, context, broadcaster, method)? {
+        handler
+            .call(avm, context, listener, None, args)?
+            .resolve(avm, context)?;
+    }
+
+    Ok(())
+}
+
+/// Snapshots the listeners of `broadcaster` along with their `method` handlers.
+///
+/// Dispatching to the snapshot rather than the live array keeps the order
+/// stable when handlers add or remove listeners.
+pub fn prepare_handlers<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    broadcaster: Object<'gc>,
+    method: &str,
+) -> Result<Vec<(Object<'gc>, Value<'gc>)>, Error> {
+    let listeners = broadcaster
+        .get("_listeners", avm, context)?
+        .resolve(avm, context)?;
+
+    let mut handlers = vec![];
+    if let Value::Object(listeners) = listeners {
+        let listeners = listener_values(listeners, avm, context)?;
+        handlers.reserve(listeners.len());
+        for listener in listeners {
+            if let Value::Object(listener) = listener {
+                let handler = listener.get(method, avm, context)?.resolve(avm, context)?;
+                handlers.push((listener, handler));
+            }
+        }
+    }
+
+    Ok(handlers)
+}
+
+pub fn create<'gc>(
+    gc_context: MutationContext<'gc, '_>,
+    proto: Option<Object<'gc>>,
+    fn_proto: Option<Object<'gc>>,
+) -> Object<'gc> {
+    let mut object = ScriptObject::object(gc_context, proto);
+
+    object.force_set_function(
+        "initialize",
+        initialize,
+        gc_context,
+        Attribute::DontEnum | Attribute::DontDelete,
+        fn_proto,
+    );
+
+    for &(name, method) in broadcaster_methods().iter() {
+        object.force_set_function(
+            name,
+            method,
+            gc_context,
+            Attribute::DontEnum | Attribute::DontDelete,
+            fn_proto,
+        );
+    }
+
+    object.into()
+}
+
+fn broadcaster_methods<'gc>() -> [(&'static str, NativeFunction<'gc>); 3] {
+    [
+        ("addListener", add_listener),
+        ("removeListener", remove_listener),
+        ("broadcastMessage", broadcast_message),
+    ]
+}
+
+fn length_of<'gc>(
+    listeners: Object<'gc>,
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+) -> Result<usize, Error> {
+    let length = listeners
+        .get("length", avm, context)?
+        .resolve(avm, context)?
+        .as_number(avm, context)?;
+    if length.is_finite() && length > 0.0 {
+        Ok(length as usize)
+    } else {
+        Ok(0)
+    }
+}
+
+/// Reads every element of a `_listeners` array through normal property access,
+/// so arrays that scripts have rearranged are seen exactly as scripts see them.
+fn listener_values<'gc>(
+    listeners: Object<'gc>,
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+) -> Result<Vec<Value<'gc>>, Error> {
+    let length = length_of(listeners, avm, context)?;
+
+    let mut values = Vec::with_capacity(length);
+    for i in 0..length {
+        values.push(
+            listeners
+                .get(&i.to_string(), avm, context)?
+                .resolve(avm, context)?,
+        );
+    }
+
+    Ok(values)
+}
+
+/// Removes the first occurrence of `listener`, shifting later listeners down.
+fn remove_from<'gc>(
+    listeners: Object<'gc>,
+    listener: &Value<'gc>,
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+) -> Result<bool, Error> {
+    let values = listener_values(listeners, avm, context)?;
+    let position = match values.iter().position(|other| other == listener) {
+        Some(position) => position,
+        None => return Ok(false),
+    };
+
+    let new_length = values.len() - 1;
+    for (i, value) in values.into_iter().enumerate().skip(position + 1) {
+        listeners.set(&(i - 1).to_string(), value, avm, context)?;
+    }
+    listeners.delete(avm, context.gc_context, &new_length.to_string());
+    listeners.set("length", new_length.into(), avm, context)?;
+
+    Ok(true)
+}
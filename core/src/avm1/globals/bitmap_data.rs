@@ -0,0 +1,314 @@
+//! `flash.display.BitmapData` impl
+//! TODO: Drawing methods other than `setPixel`, `dispose`
+
+use crate::avm1::function::{Executable, FunctionObject};
+use crate::avm1::property::Attribute::*;
+use crate::avm1::return_value::ReturnValue;
+use crate::avm1::{Avm1, BitmapDataObject, Error, Object, TObject, UpdateContext, Value};
+use crate::bitmap_data::BitmapData;
+use crate::character::Character;
+use crate::display_object::TDisplayObject;
+use gc_arena::MutationContext;
+use std::convert::TryFrom;
+
+/// Implements `BitmapData`
+pub fn constructor<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let width = arg_i32(0, avm, context, args)?;
+    let height = arg_i32(1, avm, context, args)?;
+    let transparent = args
+        .get(2)
+        .map(|v| v.as_bool(avm.current_swf_version()))
+        .unwrap_or(true);
+    let fill_color = match args.get(3) {
+        Some(fill_color) => fill_color.coerce_to_u32(avm, context)?,
+        None => 0xFFFF_FFFF,
+    };
+
+    if let Some(bitmap_data_object) = this.as_bitmap_data_object() {
+        // An invalid size leaves the object without pixels, as in Flash Player.
+        if let (Ok(width), Ok(height)) = (u32::try_from(width), u32::try_from(height)) {
+            if let Ok(bitmap_data) = BitmapData::try_new(
+                width,
+                height,
+                transparent,
+                fill_color,
+                context.renderer.capabilities(),
+            ) {
+                bitmap_data_object.set_bitmap_data(context.gc_context, bitmap_data);
+            }
+        }
+    }
+
+    Ok(Value::Undefined.into())
+}
+
+/// Implements `BitmapData.loadBitmap`, which copies the pixels of an exported library bitmap
+/// into a new `BitmapData`.
+pub fn load_bitmap<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    _this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let name = args
+        .get(0)
+        .cloned()
+        .unwrap_or(Value::Undefined)
+        .coerce_to_string(avm, context)?;
+
+    let movie = avm.base_clip().movie();
+    let library = movie
+        .clone()
+        .and_then(|movie| context.library.library_for_movie(movie));
+    let (bitmap, movie, jpeg_tables) = match (library, movie) {
+        (Some(library), Some(movie)) => match library.get_character_by_export_name(&name) {
+            Some(Character::Bitmap(bitmap)) => (
+                *bitmap,
+                movie,
+                library.jpeg_tables().map(|tables| tables.to_vec()),
+            ),
+            _ => {
+                log::warn!("BitmapData.loadBitmap: Bitmap '{}' not found", name);
+                return Ok(Value::Undefined.into());
+            }
+        },
+        _ => {
+            log::warn!(
+                "BitmapData.loadBitmap: Cannot load Bitmap '{}' without a library to reference",
+                name
+            );
+            return Ok(Value::Undefined.into());
+        }
+    };
+
+    match bitmap.decode(movie, jpeg_tables.as_deref()) {
+        Ok(bitmap_data) => {
+            let bitmap_data_object =
+                BitmapDataObject::empty(context.gc_context, Some(avm.prototypes.bitmap_data));
+            bitmap_data_object.set_bitmap_data(context.gc_context, bitmap_data);
+            Ok(Value::Object(bitmap_data_object.into()).into())
+        }
+        Err(e) => {
+            log::warn!("BitmapData.loadBitmap: Couldn't decode '{}': {}", name, e);
+            Ok(Value::Undefined.into())
+        }
+    }
+}
+
+pub fn create_bitmap_data_object<'gc>(
+    gc_context: MutationContext<'gc, '_>,
+    bitmap_data_proto: Option<Object<'gc>>,
+    fn_proto: Option<Object<'gc>>,
+) -> Object<'gc> {
+    let bitmap_data = FunctionObject::function(
+        gc_context,
+        Executable::Native(constructor),
+        fn_proto,
+        bitmap_data_proto,
+    );
+    let mut object = bitmap_data.as_script_object().unwrap();
+
+    object.force_set_function(
+        "loadBitmap",
+        load_bitmap,
+        gc_context,
+        DontDelete | ReadOnly | DontEnum,
+        fn_proto,
+    );
+
+    bitmap_data
+}
+
+pub fn create_proto<'gc>(
+    gc_context: MutationContext<'gc, '_>,
+    proto: Object<'gc>,
+    fn_proto: Object<'gc>,
+) -> Object<'gc> {
+    let object = BitmapDataObject::empty(gc_context, Some(proto));
+
+    object.add_property(
+        gc_context,
+        "width",
+        Executable::Native(width),
+        None,
+        DontDelete | ReadOnly | DontEnum,
+    );
+
+    object.add_property(
+        gc_context,
+        "height",
+        Executable::Native(height),
+        None,
+        DontDelete | ReadOnly | DontEnum,
+    );
+
+    object.add_property(
+        gc_context,
+        "transparent",
+        Executable::Native(transparent),
+        None,
+        DontDelete | ReadOnly | DontEnum,
+    );
+
+    object.as_script_object().unwrap().force_set_function(
+        "getPixel",
+        get_pixel,
+        gc_context,
+        DontDelete | ReadOnly | DontEnum,
+        Some(fn_proto),
+    );
+
+    object.as_script_object().unwrap().force_set_function(
+        "getPixel32",
+        get_pixel32,
+        gc_context,
+        DontDelete | ReadOnly | DontEnum,
+        Some(fn_proto),
+    );
+
+    object.as_script_object().unwrap().force_set_function(
+        "setPixel",
+        set_pixel,
+        gc_context,
+        DontDelete | ReadOnly | DontEnum,
+        Some(fn_proto),
+    );
+
+    object.as_script_object().unwrap().force_set_function(
+        "setPixel32",
+        set_pixel32,
+        gc_context,
+        DontDelete | ReadOnly | DontEnum,
+        Some(fn_proto),
+    );
+
+    object.into()
+}
+
+fn width<'gc>(
+    _avm: &mut Avm1<'gc>,
+    _context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    // BitmapData without pixels report a size of -1.
+    match this.as_bitmap_data_object().and_then(|o| o.bitmap_data()) {
+        Some(bitmap_data) => Ok(bitmap_data.borrow().width().into()),
+        None => Ok((-1).into()),
+    }
+}
+
+fn height<'gc>(
+    _avm: &mut Avm1<'gc>,
+    _context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    match this.as_bitmap_data_object().and_then(|o| o.bitmap_data()) {
+        Some(bitmap_data) => Ok(bitmap_data.borrow().height().into()),
+        None => Ok((-1).into()),
+    }
+}
+
+fn transparent<'gc>(
+    _avm: &mut Avm1<'gc>,
+    _context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    match this.as_bitmap_data_object().and_then(|o| o.bitmap_data()) {
+        Some(bitmap_data) => Ok(bitmap_data.borrow().transparent().into()),
+        None => Ok((-1).into()),
+    }
+}
+
+fn get_pixel<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let x = arg_i32(0, avm, context, args)?;
+    let y = arg_i32(1, avm, context, args)?;
+    match this.as_bitmap_data_object().and_then(|o| o.bitmap_data()) {
+        Some(bitmap_data) => Ok((bitmap_data.borrow().get_pixel32(x, y) & 0xFF_FFFF).into()),
+        None => Ok((-1).into()),
+    }
+}
+
+fn get_pixel32<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let x = arg_i32(0, avm, context, args)?;
+    let y = arg_i32(1, avm, context, args)?;
+    match this.as_bitmap_data_object().and_then(|o| o.bitmap_data()) {
+        // AVM1 sees the ARGB value as a signed integer.
+        Some(bitmap_data) => Ok((bitmap_data.borrow().get_pixel32(x, y) as i32).into()),
+        None => Ok((-1).into()),
+    }
+}
+
+fn set_pixel<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let x = arg_i32(0, avm, context, args)?;
+    let y = arg_i32(1, avm, context, args)?;
+    let color = arg_u32(2, avm, context, args)?;
+    if let Some(bitmap_data) = this.as_bitmap_data_object().and_then(|o| o.bitmap_data()) {
+        // Only the color changes; the pixel keeps its alpha.
+        let mut bitmap_data = bitmap_data.borrow_mut();
+        let alpha = bitmap_data.get_pixel32(x, y) & 0xFF00_0000;
+        bitmap_data.set_pixel32(x, y, alpha | (color & 0xFF_FFFF));
+    }
+
+    Ok(Value::Undefined.into())
+}
+
+fn set_pixel32<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let x = arg_i32(0, avm, context, args)?;
+    let y = arg_i32(1, avm, context, args)?;
+    let color = arg_u32(2, avm, context, args)?;
+    if let Some(bitmap_data) = this.as_bitmap_data_object().and_then(|o| o.bitmap_data()) {
+        bitmap_data.borrow_mut().set_pixel32(x, y, color);
+    }
+
+    Ok(Value::Undefined.into())
+}
+
+fn arg_i32<'gc>(
+    index: usize,
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    args: &[Value<'gc>],
+) -> Result<i32, Error> {
+    args.get(index)
+        .unwrap_or(&Value::Undefined)
+        .coerce_to_i32(avm, context)
+}
+
+fn arg_u32<'gc>(
+    index: usize,
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    args: &[Value<'gc>],
+) -> Result<u32, Error> {
+    args.get(index)
+        .unwrap_or(&Value::Undefined)
+        .coerce_to_u32(avm, context)
+}
@@ -1,8 +1,11 @@
 //! Button/SimpleButton prototype
 
+use crate::avm1::function::Executable;
 use crate::avm1::globals::display_object;
+use crate::avm1::property::Attribute::*;
 use crate::avm1::return_value::ReturnValue;
-use crate::avm1::{Avm1, Error, Object, ScriptObject, UpdateContext, Value};
+use crate::avm1::{Avm1, Error, Object, ScriptObject, TObject, UpdateContext, Value};
+use crate::display_object::TDisplayObject;
 use gc_arena::MutationContext;
 
 pub fn create_proto<'gc>(
@@ -26,3 +29,45 @@ pub fn constructor<'gc>(
 ) -> Result<ReturnValue<'gc>, Error> {
     Ok(Value::Undefined.into())
 }
+
+pub fn track_as_menu<'gc>(
+    _avm: &mut Avm1<'gc>,
+    _context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    if let Some(button) = this.as_display_object().and_then(|dobj| dobj.as_button()) {
+        return Ok(button.track_as_menu().into());
+    }
+
+    Ok(Value::Undefined.into())
+}
+
+pub fn set_track_as_menu<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let track_as_menu = args
+        .get(0)
+        .cloned()
+        .unwrap_or(Value::Undefined)
+        .as_bool(avm.current_swf_version());
+
+    if let Some(button) = this.as_display_object().and_then(|dobj| dobj.as_button()) {
+        button.set_track_as_menu(context.gc_context, track_as_menu);
+    }
+
+    Ok(Value::Undefined.into())
+}
+
+pub fn attach_virtual_properties<'gc>(gc_context: MutationContext<'gc, '_>, object: Object<'gc>) {
+    object.add_property(
+        gc_context,
+        "trackAsMenu",
+        Executable::Native(track_as_menu),
+        Some(Executable::Native(set_track_as_menu)),
+        DontDelete | DontEnum,
+    );
+}
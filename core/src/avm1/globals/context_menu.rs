@@ -0,0 +1,133 @@
+//! ContextMenu object
+
+use crate::avm1::globals::context_menu_item;
+use crate::avm1::property::Attribute::*;
+use crate::avm1::return_value::ReturnValue;
+use crate::avm1::{Avm1, Error, Object, ScriptObject, TObject, UpdateContext, Value};
+use gc_arena::MutationContext;
+
+/// The names of the properties of `builtInItems`, each controlling a group of built-in items.
+const BUILT_IN_ITEMS: &[&str] = &[
+    "forward_back",
+    "loop",
+    "play",
+    "print",
+    "quality",
+    "rewind",
+    "save",
+    "zoom",
+];
+
+pub fn constructor<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    // The callback is called with the object under the cursor when the menu is opened.
+    let callback = args.get(0).cloned().unwrap_or(Value::Undefined);
+    this.set("onSelect", callback, avm, context)?;
+
+    let built_in_items = ScriptObject::object(context.gc_context, Some(avm.prototypes().object));
+    for name in BUILT_IN_ITEMS {
+        built_in_items.set(name, true.into(), avm, context)?;
+    }
+    this.set("builtInItems", built_in_items.into(), avm, context)?;
+
+    let custom_items = ScriptObject::array(context.gc_context, Some(avm.prototypes().array));
+    this.set("customItems", custom_items.into(), avm, context)?;
+
+    Ok(Value::Undefined.into())
+}
+
+pub fn create_proto<'gc>(
+    gc_context: MutationContext<'gc, '_>,
+    proto: Object<'gc>,
+    fn_proto: Object<'gc>,
+) -> Object<'gc> {
+    let mut object = ScriptObject::object(gc_context, Some(proto));
+
+    object.force_set_function(
+        "copy",
+        copy,
+        gc_context,
+        DontDelete | ReadOnly | DontEnum,
+        Some(fn_proto),
+    );
+
+    object.force_set_function(
+        "hideBuiltInItems",
+        hide_built_in_items,
+        gc_context,
+        DontDelete | ReadOnly | DontEnum,
+        Some(fn_proto),
+    );
+
+    object.into()
+}
+
+fn copy<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let copy: Object<'gc> = ScriptObject::object(context.gc_context, this.proto()).into();
+    let callback = this.get("onSelect", avm, context)?.resolve(avm, context)?;
+    let _ = constructor(avm, context, copy, &[callback])?;
+
+    let built_in_items = this
+        .get("builtInItems", avm, context)?
+        .resolve(avm, context)?;
+    let copy_built_in_items = copy
+        .get("builtInItems", avm, context)?
+        .resolve(avm, context)?;
+    if let (Value::Object(built_in_items), Value::Object(copy_built_in_items)) =
+        (built_in_items, copy_built_in_items)
+    {
+        for name in BUILT_IN_ITEMS {
+            let value = built_in_items
+                .get(name, avm, context)?
+                .resolve(avm, context)?;
+            copy_built_in_items.set(name, value, avm, context)?;
+        }
+    }
+
+    let custom_items = this
+        .get("customItems", avm, context)?
+        .resolve(avm, context)?;
+    let copy_custom_items = copy
+        .get("customItems", avm, context)?
+        .resolve(avm, context)?;
+    if let (Value::Object(custom_items), Value::Object(copy_custom_items)) =
+        (custom_items, copy_custom_items)
+    {
+        for (index, item) in custom_items.array().into_iter().enumerate() {
+            let item = match item {
+                Value::Object(item) => context_menu_item::copy_item(avm, context, item)?.into(),
+                item => item,
+            };
+            copy_custom_items.set_array_element(index, item, context.gc_context);
+        }
+    }
+
+    Ok(copy.into())
+}
+
+fn hide_built_in_items<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    if let Value::Object(built_in_items) = this
+        .get("builtInItems", avm, context)?
+        .resolve(avm, context)?
+    {
+        for name in BUILT_IN_ITEMS {
+            built_in_items.set(name, false.into(), avm, context)?;
+        }
+    }
+
+    Ok(Value::Undefined.into())
+}
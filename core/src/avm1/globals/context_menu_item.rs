@@ -0,0 +1,83 @@
+//! ContextMenuItem object
+
+use crate::avm1::property::Attribute::*;
+use crate::avm1::return_value::ReturnValue;
+use crate::avm1::{Avm1, Error, Object, ScriptObject, TObject, UpdateContext, Value};
+use gc_arena::MutationContext;
+
+/// The properties that make up a context menu item.
+const ITEM_PROPERTIES: &[&str] = &[
+    "caption",
+    "onSelect",
+    "separatorBefore",
+    "enabled",
+    "visible",
+];
+
+pub fn constructor<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let swf_version = avm.current_swf_version();
+    let caption = args
+        .get(0)
+        .cloned()
+        .unwrap_or(Value::Undefined)
+        .coerce_to_string(avm, context)?;
+    let callback = args.get(1).cloned().unwrap_or(Value::Undefined);
+    let separator_before = args.get(2).map(|v| v.as_bool(swf_version)).unwrap_or(false);
+    let enabled = args.get(3).map(|v| v.as_bool(swf_version)).unwrap_or(true);
+    let visible = args.get(4).map(|v| v.as_bool(swf_version)).unwrap_or(true);
+
+    this.set("caption", caption.into(), avm, context)?;
+    this.set("onSelect", callback, avm, context)?;
+    this.set("separatorBefore", separator_before.into(), avm, context)?;
+    this.set("enabled", enabled.into(), avm, context)?;
+    this.set("visible", visible.into(), avm, context)?;
+
+    Ok(Value::Undefined.into())
+}
+
+pub fn create_proto<'gc>(
+    gc_context: MutationContext<'gc, '_>,
+    proto: Object<'gc>,
+    fn_proto: Object<'gc>,
+) -> Object<'gc> {
+    let mut object = ScriptObject::object(gc_context, Some(proto));
+
+    object.force_set_function(
+        "copy",
+        copy,
+        gc_context,
+        DontDelete | ReadOnly | DontEnum,
+        Some(fn_proto),
+    );
+
+    object.into()
+}
+
+/// Create a new `ContextMenuItem` with the same properties as the given one.
+pub fn copy_item<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    item: Object<'gc>,
+) -> Result<Object<'gc>, Error> {
+    let copy: Object<'gc> = ScriptObject::object(context.gc_context, item.proto()).into();
+    for name in ITEM_PROPERTIES {
+        let value = item.get(name, avm, context)?.resolve(avm, context)?;
+        copy.set(name, value, avm, context)?;
+    }
+
+    Ok(copy)
+}
+
+fn copy<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    Ok(copy_item(avm, context, this)?.into())
+}
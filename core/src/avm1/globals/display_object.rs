@@ -97,6 +97,72 @@ pub fn get_depth<'gc>(
     }
 }
 
+/// Swaps the depth of a display object with either a numeric depth or another display object
+/// sharing the same parent. Any type of display object may occupy the target depth.
+pub fn swap_depths<'gc>(
+    display_object: DisplayObject<'gc>,
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let arg = args.get(0).cloned().unwrap_or(Value::Undefined);
+
+    let parent = if let Some(parent) = display_object.parent().and_then(|o| o.as_movie_clip()) {
+        parent
+    } else {
+        return Ok(Value::Undefined.into());
+    };
+
+    let mut depth = None;
+    if let Value::Number(n) = arg {
+        depth = Some(crate::avm1::value::f64_to_wrapping_i32(n).wrapping_add(AVM_DEPTH_BIAS));
+    } else if let Some(target) = avm.resolve_target_display_object(context, display_object, arg)? {
+        if let Some(target_parent) = target.parent() {
+            if DisplayObject::ptr_eq(target_parent, parent.into()) {
+                depth = Some(target.depth())
+            } else {
+                log::warn!("swapDepths: Objects do not have the same parent");
+            }
+        }
+    } else {
+        log::warn!("swapDepths: Invalid target");
+    };
+
+    if let Some(depth) = depth {
+        if depth < 0 || depth > AVM_MAX_DEPTH {
+            // Depth out of range; no action.
+            return Ok(Value::Undefined.into());
+        }
+
+        if depth != display_object.depth() {
+            parent.swap_child_to_depth(context, display_object, depth);
+        }
+    }
+
+    Ok(Value::Undefined.into())
+}
+
+/// Removes a script-placed display object from its parent.
+/// Used by `removeMovieClip`, `removeTextField` and the `RemoveSprite` action;
+/// callers are responsible for checking that `display_object` is of the correct type.
+pub fn remove_display_object<'gc>(
+    display_object: DisplayObject<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    depth_bias: i32,
+) {
+    let depth = display_object.depth().wrapping_add(depth_bias);
+    // Can only remove positive depths (when offset by the AVM depth bias).
+    // Generally this prevents you from removing non-dynamically created objects,
+    // although you can get around it with swapDepths.
+    // TODO: Figure out the derivation of this range.
+    if depth >= AVM_DEPTH_BIAS && depth < 2_130_706_416 {
+        // Need a parent to remove from.
+        if let Some(mut parent) = display_object.parent().and_then(|o| o.as_movie_clip()) {
+            parent.remove_child_from_avm(context, display_object);
+        }
+    }
+}
+
 pub fn overwrite_root<'gc>(
     _avm: &mut Avm1<'gc>,
     ac: &mut UpdateContext<'_, 'gc, '_>,
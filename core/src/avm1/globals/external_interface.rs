@@ -0,0 +1,209 @@
+//! ExternalInterface object
+//!
+//! Calls between ActionScript and the host of the movie are sent through the
+//! `ExternalInterface` stored in the update context.
+
+use crate::avm1::function::Executable;
+use crate::avm1::property::Attribute;
+use crate::avm1::return_value::ReturnValue;
+use crate::avm1::{Avm1, Error, Object, ScriptObject, TObject, UpdateContext, Value};
+use crate::external::{self, Callback};
+use enumset::EnumSet;
+use gc_arena::MutationContext;
+use std::collections::BTreeMap;
+
+pub fn create<'gc>(
+    gc_context: MutationContext<'gc, '_>,
+    proto: Option<Object<'gc>>,
+    fn_proto: Option<Object<'gc>>,
+) -> Object<'gc> {
+    let mut object = ScriptObject::object(gc_context, proto);
+
+    object.force_set_function(
+        "addCallback",
+        add_callback,
+        gc_context,
+        Attribute::DontEnum | Attribute::DontDelete | Attribute::ReadOnly,
+        fn_proto,
+    );
+
+    object.add_property(
+        gc_context,
+        "available",
+        Executable::Native(available),
+        None,
+        Attribute::DontEnum | Attribute::DontDelete | Attribute::ReadOnly,
+    );
+
+    object.force_set_function(
+        "call",
+        call,
+        gc_context,
+        Attribute::DontEnum | Attribute::DontDelete | Attribute::ReadOnly,
+        fn_proto,
+    );
+
+    object.into()
+}
+
+pub fn available<'gc>(
+    _avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    _this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    Ok(context.external_interface.available().into())
+}
+
+pub fn add_callback<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    _this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    if args.len() < 3 || !context.external_interface.available() {
+        return Ok(false.into());
+    }
+
+    let name = args[0].clone().coerce_to_string(avm, context)?;
+    let this = match &args[1] {
+        Value::Object(this) => *this,
+        _ => avm.global_object_cell(),
+    };
+    let method = match &args[2] {
+        Value::Object(method) if method.as_executable().is_some() => *method,
+        _ => return Ok(false.into()),
+    };
+
+    context
+        .external_interface
+        .add_callback(name, Callback::new(this, method));
+    Ok(true.into())
+}
+
+pub fn call<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    _this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let name = match args.get(0) {
+        Some(name) => name.clone().coerce_to_string(avm, context)?,
+        None => return Ok(Value::Undefined.into()),
+    };
+    if !context.external_interface.available() {
+        return Ok(Value::Null.into());
+    }
+
+    let mut external_args = vec![];
+    for arg in args.iter().skip(1) {
+        external_args.push(value_to_external(
+            avm,
+            context,
+            arg.to_owned(),
+            &mut vec![],
+        )?);
+    }
+
+    match context
+        .external_interface
+        .call_method(&name, &external_args)
+    {
+        Ok(result) => Ok(external_to_value(avm, context.gc_context, &result).into()),
+        Err(e) => {
+            log::warn!("ExternalInterface.call(\"{}\") failed: {}", name, e);
+            Ok(Value::Undefined.into())
+        }
+    }
+}
+
+/// Convert an AVM value into a value to be sent to the host.
+///
+/// Functions are skipped, and objects that contain themselves are sent as `null` the second
+/// time they are encountered.
+pub fn value_to_external<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    value: Value<'gc>,
+    parents: &mut Vec<Object<'gc>>,
+) -> Result<external::Value, Error> {
+    let object = match value {
+        Value::Undefined => return Ok(external::Value::Undefined),
+        Value::Null => return Ok(external::Value::Null),
+        Value::Bool(value) => return Ok(external::Value::Bool(value)),
+        Value::Number(number) => return Ok(external::Value::Number(number)),
+        Value::String(string) => return Ok(external::Value::String(string)),
+        Value::Object(object) => object,
+    };
+
+    if parents.iter().any(|parent| Object::ptr_eq(*parent, object)) {
+        return Ok(external::Value::Null);
+    }
+    parents.push(object);
+
+    let array_proto = avm.prototypes().array;
+    let mut proto = object.proto();
+    let mut is_array = false;
+    while let Some(p) = proto {
+        if Object::ptr_eq(p, array_proto) {
+            is_array = true;
+            break;
+        }
+        proto = p.proto();
+    }
+
+    let external = if is_array {
+        let mut values = vec![];
+        for element in object.array() {
+            values.push(value_to_external(avm, context, element, parents)?);
+        }
+        external::Value::List(values)
+    } else {
+        let mut properties = BTreeMap::new();
+        for name in object.get_keys(avm) {
+            let property = object.get(&name, avm, context)?.resolve(avm, context)?;
+            if let Value::Object(property) = property {
+                if property.as_executable().is_some() {
+                    continue;
+                }
+            }
+            properties.insert(name, value_to_external(avm, context, property, parents)?);
+        }
+        external::Value::Object(properties)
+    };
+
+    parents.pop();
+    Ok(external)
+}
+
+/// Convert a value received from the host into an AVM value.
+pub fn external_to_value<'gc>(
+    avm: &mut Avm1<'gc>,
+    gc_context: MutationContext<'gc, '_>,
+    external: &external::Value,
+) -> Value<'gc> {
+    match external {
+        external::Value::Undefined => Value::Undefined,
+        external::Value::Null => Value::Null,
+        external::Value::Bool(value) => (*value).into(),
+        external::Value::Number(number) => (*number).into(),
+        external::Value::String(string) => string.to_owned().into(),
+        external::Value::Object(properties) => {
+            let object = ScriptObject::object(gc_context, Some(avm.prototypes().object));
+            for (name, property) in properties {
+                let property = external_to_value(avm, gc_context, property);
+                object.define_value(gc_context, name, property, EnumSet::empty());
+            }
+            object.into()
+        }
+        external::Value::List(values) => {
+            let array = ScriptObject::array(gc_context, Some(avm.prototypes().array));
+            array.set_length(gc_context, values.len());
+            for (index, value) in values.iter().enumerate() {
+                let value = external_to_value(avm, gc_context, value);
+                array.set_array_element(index, value, gc_context);
+            }
+            array.into()
+        }
+    }
+}
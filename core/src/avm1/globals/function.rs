@@ -24,7 +24,7 @@ pub fn call<'gc>(
 ) -> Result<ReturnValue<'gc>, Error> {
     let this = match myargs.get(0) {
         Some(Value::Object(this)) => *this,
-        _ => avm.globals,
+        _ => avm.global_object_for_clip(avm.base_clip(), action_context),
     };
     let empty = [];
     let args = match myargs.len() {
@@ -34,7 +34,7 @@ pub fn call<'gc>(
     };
 
     match func.as_executable() {
-        Some(exec) => exec.exec(avm, action_context, this, None, args),
+        Some(exec) => exec.exec(avm, action_context, this, None, args, Some(func)),
         _ => Ok(Value::Undefined.into()),
     }
 }
@@ -48,7 +48,7 @@ pub fn apply<'gc>(
 ) -> Result<ReturnValue<'gc>, Error> {
     let this = match myargs.get(0) {
         Some(Value::Object(this)) => *this,
-        _ => avm.globals,
+        _ => avm.global_object_for_clip(avm.base_clip(), action_context),
     };
     let mut child_args = Vec::new();
     let args_object = myargs.get(1).cloned().unwrap_or(Value::Undefined);
@@ -70,7 +70,7 @@ pub fn apply<'gc>(
     }
 
     match func.as_executable() {
-        Some(exec) => exec.exec(avm, action_context, this, None, &child_args),
+        Some(exec) => exec.exec(avm, action_context, this, None, &child_args, Some(func)),
         _ => Ok(Value::Undefined.into()),
     }
 }
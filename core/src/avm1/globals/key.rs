@@ -1,8 +1,9 @@
+use crate::avm1::globals::as_broadcaster;
 use crate::avm1::property::Attribute;
 use crate::avm1::return_value::ReturnValue;
 use crate::avm1::{Avm1, Error, Object, ScriptObject, TObject, UpdateContext, Value};
 
-use crate::events::KeyCode;
+use crate::events::{key_ascii, KeyCode};
 use gc_arena::MutationContext;
 use std::convert::TryFrom;
 
@@ -17,7 +18,28 @@ pub fn is_down<'gc>(
         .and_then(|v| v.as_number(avm, context).ok())
         .and_then(|k| KeyCode::try_from(k as u8).ok())
     {
-        Ok(context.input.is_key_down(key).into())
+        let is_down = context
+            .keyboard
+            .is_modifier_down(key)
+            .unwrap_or_else(|| context.input.is_key_down(key));
+        Ok(is_down.into())
+    } else {
+        Ok(false.into())
+    }
+}
+
+pub fn is_toggled<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    _this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    if let Some(key) = args
+        .get(0)
+        .and_then(|v| v.as_number(avm, context).ok())
+        .and_then(|k| KeyCode::try_from(k as u8).ok())
+    {
+        Ok(context.keyboard.is_toggled(key).into())
     } else {
         Ok(false.into())
     }
@@ -33,13 +55,29 @@ pub fn get_code<'gc>(
     Ok(code.into())
 }
 
+pub fn get_ascii<'gc>(
+    _avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    _this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let ascii = key_ascii(
+        context.input.get_last_key_code(),
+        context.input.get_last_key_char(),
+    );
+    Ok(ascii.into())
+}
+
 pub fn create_key_object<'gc>(
     gc_context: MutationContext<'gc, '_>,
     proto: Option<Object<'gc>>,
+    array_proto: Option<Object<'gc>>,
     fn_proto: Option<Object<'gc>>,
 ) -> Object<'gc> {
     let mut key = ScriptObject::object(gc_context, proto);
 
+    as_broadcaster::initialize_internal(gc_context, key.into(), array_proto, fn_proto);
+
     key.define_value(
         gc_context,
         "ALT",
@@ -163,6 +201,22 @@ pub fn create_key_object<'gc>(
         fn_proto,
     );
 
+    key.force_set_function(
+        "isToggled",
+        is_toggled,
+        gc_context,
+        Attribute::DontEnum | Attribute::DontDelete | Attribute::ReadOnly,
+        fn_proto,
+    );
+
+    key.force_set_function(
+        "getAscii",
+        get_ascii,
+        gc_context,
+        Attribute::DontEnum | Attribute::DontDelete | Attribute::ReadOnly,
+        fn_proto,
+    );
+
     key.force_set_function(
         "getCode",
         get_code,
@@ -54,10 +54,12 @@ fn pow<'gc>(
     if let Some(y) = args.get(0) {
         if let Some(x) = args.get(1) {
             let x = x.as_number(avm, context)?;
-            if x.is_nan() {
+            let y = y.as_number(avm, context)?;
+            // Unlike `powf`, ECMA-262 gives NaN for 1 to the power of NaN or infinity.
+            if x.is_nan() || (x.is_infinite() && y.abs() == 1.0) {
                 return Ok(NAN.into());
             }
-            return Ok(y.as_number(avm, context)?.powf(x).into());
+            return Ok(y.powf(x).into());
         }
     }
     Ok(NAN.into())
@@ -293,7 +295,9 @@ mod tests {
             [Value::Null] => NAN,
             [-1.0] => f64::asin(-1.0),
             [0.0] => f64::asin(0.0),
-            [1.0] => f64::asin(1.0)
+            [1.0] => f64::asin(1.0),
+            [2.0] => NAN,
+            [-2.0] => NAN
         }
     );
 
@@ -364,6 +368,8 @@ mod tests {
             [-23.2] => -23.0,
             [-23.5] => -23.0,
             [-23.7] => -24.0,
+            [-0.5] => 0.0,
+            [-1.5] => -1.0,
             [std::f64::NAN] => std::f64::NAN,
             [std::f64::INFINITY] => std::f64::INFINITY,
             [std::f64::NEG_INFINITY] => std::f64::NEG_INFINITY
@@ -390,7 +396,9 @@ mod tests {
             [] => NAN,
             [Value::Null] => NAN,
             [0.0] => f64::sqrt(0.0),
-            [5.0] => f64::sqrt(5.0)
+            [5.0] => f64::sqrt(5.0),
+            [-1.0] => NAN,
+            [INFINITY] => INFINITY
         }
     );
 
@@ -412,7 +420,11 @@ mod tests {
             [Value::Undefined] => NAN,
             ["5"] => NAN,
             [1.0, 2.0] => 1.0,
-            [3.0, 2.0, 1.0] => 9.0
+            [3.0, 2.0, 1.0] => 9.0,
+            [1.0, INFINITY] => NAN,
+            [-1.0, NEG_INFINITY] => NAN,
+            [2.0, NEG_INFINITY] => 0.0,
+            [NAN, 0.0] => 1.0
         },
         [5, 6] => {
             [1.0, Value::Null] => 1.0,
@@ -429,8 +441,9 @@ mod tests {
             [] => NAN,
             [Value::Null] => NAN,
             [2.0] => f64::ln(2.0),
-            [0.0] => f64::ln(0.0),
-            [1.0] => f64::ln(1.0)
+            [0.0] => NEG_INFINITY,
+            [1.0] => f64::ln(1.0),
+            [-1.0] => NAN
         }
     );
 
@@ -443,7 +456,11 @@ mod tests {
             [Value::Undefined] => NAN,
             ["5"] => NAN,
             [1.0, 2.0] => 2.0,
-            [3.0, 2.0, 1.0] => 3.0
+            [3.0, 2.0, 1.0] => 3.0,
+            [1.0, 2.0, 3.0] => 2.0,
+            [NAN, 1.0] => NAN,
+            [1.0, NAN] => NAN,
+            [NEG_INFINITY, INFINITY] => INFINITY
         },
         [5, 6] => {
             [1.0, Value::Null] => 1.0,
@@ -464,7 +481,11 @@ mod tests {
             [Value::Undefined] => NAN,
             ["5"] => NAN,
             [1.0, 2.0] => 1.0,
-            [3.0, 2.0, 1.0] => 2.0
+            [3.0, 2.0, 1.0] => 2.0,
+            [1.0, 2.0, 0.0] => 1.0,
+            [NAN, 1.0] => NAN,
+            [1.0, NAN] => NAN,
+            [NEG_INFINITY, INFINITY] => NEG_INFINITY
         },
         [5, 6] => {
             [1.0, Value::Null] => 0.0,
@@ -520,4 +541,30 @@ mod tests {
             );
         });
     }
+
+    #[test]
+    fn test_atan2_quadrants() {
+        with_avm(19, |avm, context, _root| {
+            let math = create(
+                context.gc_context,
+                Some(avm.prototypes().object),
+                Some(avm.prototypes().function),
+            );
+
+            use std::f64::consts::{FRAC_PI_4, PI};
+            for &(y, x, expected) in &[
+                (1.0, 1.0, FRAC_PI_4),
+                (1.0, -1.0, 3.0 * FRAC_PI_4),
+                (-1.0, -1.0, -3.0 * FRAC_PI_4),
+                (-1.0, 1.0, -FRAC_PI_4),
+                (0.0, -1.0, PI),
+                (INFINITY, INFINITY, FRAC_PI_4),
+            ] {
+                assert_eq!(
+                    atan2(avm, context, math, &[y.into(), x.into()]).unwrap(),
+                    expected.into()
+                );
+            }
+        });
+    }
 }
@@ -1,7 +1,7 @@
-use crate::avm1::listeners::Listeners;
+use crate::avm1::globals::as_broadcaster;
 use crate::avm1::property::Attribute;
 use crate::avm1::return_value::ReturnValue;
-use crate::avm1::{Avm1, Error, Object, ScriptObject, TObject, UpdateContext, Value};
+use crate::avm1::{Avm1, Error, Object, ScriptObject, UpdateContext, Value};
 
 use gc_arena::MutationContext;
 
@@ -11,8 +11,8 @@ pub fn show_mouse<'gc>(
     _this: Object<'gc>,
     _args: &[Value<'gc>],
 ) -> Result<ReturnValue<'gc>, Error> {
-    let was_visible = context.input.mouse_visible();
-    context.input.show_mouse();
+    let was_visible = context.ui.mouse_visible();
+    context.ui.set_mouse_visible(true);
     if was_visible {
         Ok(0.into())
     } else {
@@ -26,8 +26,8 @@ pub fn hide_mouse<'gc>(
     _this: Object<'gc>,
     _args: &[Value<'gc>],
 ) -> Result<ReturnValue<'gc>, Error> {
-    let was_visible = context.input.mouse_visible();
-    context.input.hide_mouse();
+    let was_visible = context.ui.mouse_visible();
+    context.ui.set_mouse_visible(false);
     if was_visible {
         Ok(0.into())
     } else {
@@ -38,12 +38,12 @@ pub fn hide_mouse<'gc>(
 pub fn create_mouse_object<'gc>(
     gc_context: MutationContext<'gc, '_>,
     proto: Option<Object<'gc>>,
+    array_proto: Option<Object<'gc>>,
     fn_proto: Option<Object<'gc>>,
-    listener: &Listeners<'gc>,
 ) -> Object<'gc> {
     let mut mouse = ScriptObject::object(gc_context, proto);
 
-    register_listener!(gc_context, mouse, listener, fn_proto, mouse);
+    as_broadcaster::initialize_internal(gc_context, mouse.into(), array_proto, fn_proto);
 
     mouse.force_set_function(
         "show",
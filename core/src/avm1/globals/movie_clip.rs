@@ -5,8 +5,9 @@ use crate::avm1::property::Attribute::*;
 use crate::avm1::return_value::ReturnValue;
 use crate::avm1::{Avm1, Error, Object, ScriptObject, TObject, UpdateContext, Value};
 use crate::backend::navigator::NavigationMethod;
-use crate::display_object::{DisplayObject, EditText, MovieClip, TDisplayObject};
-use crate::prelude::*;
+use crate::display_object::{
+    Bitmap, DisplayObject, EditText, MovieClip, PixelSnapping, TDisplayObject,
+};
 use crate::tag_utils::SwfSlice;
 use gc_arena::MutationContext;
 use swf::Twips;
@@ -98,6 +99,7 @@ pub fn create_proto<'gc>(
         gc_context,
         object,
         Some(fn_proto),
+        "attachBitmap" => attach_bitmap,
         "attachMovie" => attach_movie,
         "createEmptyMovieClip" => create_empty_movie_clip,
         "createTextField" => create_text_field,
@@ -105,6 +107,7 @@ pub fn create_proto<'gc>(
         "getBounds" => get_bounds,
         "getBytesLoaded" => get_bytes_loaded,
         "getBytesTotal" => get_bytes_total,
+        "getInstanceAtDepth" => get_instance_at_depth,
         "getNextHighestDepth" => get_next_highest_depth,
         "getRect" => get_rect,
         "globalToLocal" => global_to_local,
@@ -129,6 +132,51 @@ pub fn create_proto<'gc>(
     object.into()
 }
 
+fn attach_bitmap<'gc>(
+    mut movie_clip: MovieClip<'gc>,
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let bitmap_data = match args
+        .get(0)
+        .and_then(|v| v.as_object().ok())
+        .and_then(|o| o.as_bitmap_data_object())
+        .and_then(|o| o.bitmap_data())
+    {
+        Some(bitmap_data) => bitmap_data,
+        None => {
+            log::warn!("MovieClip.attachBitmap: Can only attach a valid BitmapData");
+            return Ok(Value::Undefined.into());
+        }
+    };
+    let depth = args
+        .get(1)
+        .and_then(|depth| depth.as_i32().ok())
+        .unwrap_or(0)
+        .wrapping_add(AVM_DEPTH_BIAS);
+    let pixel_snapping = match args.get(2) {
+        Some(pixel_snapping) => {
+            PixelSnapping::from_name(&pixel_snapping.clone().coerce_to_string(avm, context)?)
+        }
+        None => PixelSnapping::Auto,
+    };
+    let smoothing = args
+        .get(3)
+        .map(|v| v.as_bool(avm.current_swf_version()))
+        .unwrap_or(false);
+
+    if depth < 0 || depth > AVM_MAX_DEPTH {
+        return Ok(Value::Undefined.into());
+    }
+
+    // Like in Flash Player, the attached bitmap has no name or script object of its own.
+    let bitmap = Bitmap::from_bitmap_data(context, bitmap_data, pixel_snapping, smoothing);
+    movie_clip.add_child_from_avm(context, bitmap.into(), depth);
+
+    Ok(Value::Undefined.into())
+}
+
 fn attach_movie<'gc>(
     mut movie_clip: MovieClip<'gc>,
     avm: &mut Avm1<'gc>,
@@ -227,7 +275,8 @@ fn create_text_field<'gc>(
         .get(1)
         .cloned()
         .unwrap_or(Value::Undefined)
-        .as_number(avm, context)?;
+        .coerce_to_i32(avm, context)?
+        .wrapping_add(AVM_DEPTH_BIAS);
     let x = args
         .get(2)
         .cloned()
@@ -252,7 +301,7 @@ fn create_text_field<'gc>(
     let mut text_field: DisplayObject<'gc> =
         EditText::new(context, movie, x, y, width, height).into();
     text_field.set_name(context.gc_context, &instance_name);
-    movie_clip.add_child_from_avm(context, text_field, depth as Depth);
+    movie_clip.add_child_from_avm(context, text_field, depth);
     text_field.post_instantiation(avm, context, text_field, None);
 
     if avm.current_swf_version() >= 8 {
@@ -316,7 +365,7 @@ pub fn duplicate_movie_clip_with_bias<'gc>(
         parent.add_child_from_avm(context, new_clip, depth);
 
         // Copy display properties from previous clip to new clip.
-        new_clip.set_matrix(context.gc_context, &*movie_clip.matrix());
+        new_clip.set_matrix(context.gc_context, &movie_clip.matrix());
         new_clip.set_color_transform(context.gc_context, &*movie_clip.color_transform());
         // TODO: Any other properties we should copy...?
         // Definitely not ScriptObject properties.
@@ -356,6 +405,35 @@ fn get_bytes_total<'gc>(
     Ok(1.0.into())
 }
 
+fn get_instance_at_depth<'gc>(
+    movie_clip: MovieClip<'gc>,
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    if avm.current_swf_version() >= 7 {
+        let depth = if let Some(depth) = args.get(0) {
+            depth
+                .clone()
+                .coerce_to_i32(avm, context)?
+                .wrapping_add(AVM_DEPTH_BIAS)
+        } else {
+            return Ok(Value::Undefined.into());
+        };
+        // Any display object can occupy a depth, not only movie clips.
+        if let Some(child) = movie_clip.child_by_depth(depth) {
+            // If the child doesn't have a corresponding AVM object (such as a Shape), return the parent.
+            let object = child.object();
+            return Ok(match object {
+                Value::Undefined => movie_clip.object(),
+                object => object,
+            }
+            .into());
+        }
+    }
+    Ok(Value::Undefined.into())
+}
+
 fn get_next_highest_depth<'gc>(
     movie_clip: MovieClip<'gc>,
     avm: &mut Avm1<'gc>,
@@ -476,21 +554,7 @@ pub fn remove_movie_clip_with_bias<'gc>(
     context: &mut UpdateContext<'_, 'gc, '_>,
     depth_bias: i32,
 ) -> Result<ReturnValue<'gc>, Error> {
-    let depth = movie_clip.depth().wrapping_add(depth_bias);
-    // Can only remove positive depths (when offset by the AVM depth bias).
-    // Generally this prevents you from removing non-dynamically created clips,
-    // although you can get around it with swapDepths.
-    // TODO: Figure out the derivation of this range.
-    if depth >= AVM_DEPTH_BIAS && depth < 2_130_706_416 {
-        // Need a parent to remove from.
-        let mut parent = if let Some(parent) = movie_clip.parent().and_then(|o| o.as_movie_clip()) {
-            parent
-        } else {
-            return Ok(Value::Undefined.into());
-        };
-
-        parent.remove_child_from_avm(context, movie_clip.into());
-    }
+    display_object::remove_display_object(movie_clip.into(), context, depth_bias);
     Ok(Value::Undefined.into())
 }
 
@@ -531,43 +595,7 @@ fn swap_depths<'gc>(
     context: &mut UpdateContext<'_, 'gc, '_>,
     args: &[Value<'gc>],
 ) -> Result<ReturnValue<'gc>, Error> {
-    let arg = args.get(0).cloned().unwrap_or(Value::Undefined);
-
-    let parent = if let Some(parent) = movie_clip.parent().and_then(|o| o.as_movie_clip()) {
-        parent
-    } else {
-        return Ok(Value::Undefined.into());
-    };
-
-    let mut depth = None;
-    if let Value::Number(n) = arg {
-        depth = Some(crate::avm1::value::f64_to_wrapping_i32(n).wrapping_add(AVM_DEPTH_BIAS));
-    } else if let Some(target) =
-        avm.resolve_target_display_object(context, movie_clip.into(), arg)?
-    {
-        if let Some(target_parent) = target.parent() {
-            if DisplayObject::ptr_eq(target_parent, parent.into()) {
-                depth = Some(target.depth())
-            } else {
-                log::warn!("MovieClip.swapDepths: Objects do not have the same parent");
-            }
-        }
-    } else {
-        log::warn!("MovieClip.swapDepths: Invalid target");
-    };
-
-    if let Some(depth) = depth {
-        if depth < 0 || depth > AVM_MAX_DEPTH {
-            // Depth out of range; no action.
-            return Ok(Value::Undefined.into());
-        }
-
-        if depth != movie_clip.depth() {
-            parent.swap_child_to_depth(context, movie_clip.into(), depth);
-        }
-    }
-
-    Ok(Value::Undefined.into())
+    display_object::swap_depths(movie_clip.into(), avm, context, args)
 }
 
 fn to_string<'gc>(
@@ -711,16 +739,16 @@ fn load_movie<'gc>(
     let method = args.get(1).cloned().unwrap_or(Value::Undefined);
     let method = NavigationMethod::from_method_str(&method.coerce_to_string(avm, context)?);
     let (url, opts) = avm.locals_into_request_options(context, url, method);
-    let fetch = context.navigator.fetch(url, opts);
-    let process = context.load_manager.load_movie_into_clip(
+    context.load_manager.load_movie_into_clip(
         context.player.clone().unwrap(),
         DisplayObject::MovieClip(target),
-        fetch,
+        context.navigator,
+        context.cancellation_token,
+        url,
+        opts,
         None,
     );
 
-    context.navigator.spawn_future(process);
-
     Ok(Value::Undefined.into())
 }
 
@@ -745,7 +773,10 @@ fn load_variables<'gc>(
         fetch,
     );
 
-    context.navigator.spawn_future(process);
+    context
+        .navigator
+        .spawn_future(process, context.cancellation_token.clone())
+        .detach();
 
     Ok(Value::Undefined.into())
 }
@@ -756,6 +787,7 @@ fn unload_movie<'gc>(
     context: &mut UpdateContext<'_, 'gc, '_>,
     _args: &[Value<'gc>],
 ) -> Result<ReturnValue<'gc>, Error> {
+    context.load_manager.cancel_movie_loads(target.into());
     target.unload(context);
     target.replace_with_movie(context.gc_context, None);
 
@@ -1,5 +1,6 @@
 //! `MovieClipLoader` impl
 
+use crate::avm1::globals::as_broadcaster;
 use crate::avm1::object::TObject;
 use crate::avm1::property::Attribute;
 use crate::avm1::return_value::ReturnValue;
@@ -28,106 +29,6 @@ pub fn constructor<'gc>(
     Ok(Value::Undefined.into())
 }
 
-pub fn add_listener<'gc>(
-    avm: &mut Avm1<'gc>,
-    context: &mut UpdateContext<'_, 'gc, '_>,
-    this: Object<'gc>,
-    args: &[Value<'gc>],
-) -> Result<ReturnValue<'gc>, Error> {
-    let new_listener = args.get(0).cloned().unwrap_or(Value::Undefined);
-    let listeners = this
-        .get("_listeners", avm, context)?
-        .resolve(avm, context)?;
-
-    if let Value::Object(listeners) = listeners {
-        let length = listeners.length();
-        listeners.set_length(context.gc_context, length + 1);
-        listeners.set_array_element(length, new_listener, context.gc_context);
-    }
-
-    Ok(true.into())
-}
-
-pub fn remove_listener<'gc>(
-    avm: &mut Avm1<'gc>,
-    context: &mut UpdateContext<'_, 'gc, '_>,
-    this: Object<'gc>,
-    args: &[Value<'gc>],
-) -> Result<ReturnValue<'gc>, Error> {
-    let old_listener = args.get(0).cloned().unwrap_or(Value::Undefined);
-    let listeners = this
-        .get("_listeners", avm, context)?
-        .resolve(avm, context)?;
-
-    if let Value::Object(listeners) = listeners {
-        let length = listeners.length();
-        let mut position = None;
-
-        for i in 0..length {
-            let other_listener = listeners
-                .get(&format!("{}", i), avm, context)?
-                .resolve(avm, context)?;
-            if old_listener == other_listener {
-                position = Some(i);
-                break;
-            }
-        }
-
-        if let Some(position) = position {
-            if length > 0 {
-                let new_length = length - 1;
-                for i in position..new_length {
-                    listeners.set_array_element(
-                        i,
-                        listeners.array_element(i + 1),
-                        context.gc_context,
-                    );
-                }
-
-                listeners.delete_array_element(new_length, context.gc_context);
-                listeners.delete(avm, context.gc_context, &new_length.to_string());
-
-                listeners.set_length(context.gc_context, new_length);
-            }
-        }
-    }
-
-    Ok(true.into())
-}
-
-pub fn broadcast_message<'gc>(
-    avm: &mut Avm1<'gc>,
-    context: &mut UpdateContext<'_, 'gc, '_>,
-    this: Object<'gc>,
-    args: &[Value<'gc>],
-) -> Result<ReturnValue<'gc>, Error> {
-    let event_name = args
-        .get(0)
-        .cloned()
-        .unwrap_or(Value::Undefined)
-        .coerce_to_string(avm, context)?;
-    let call_args = &args[0..];
-
-    let listeners = this
-        .get("_listeners", avm, context)?
-        .resolve(avm, context)?;
-    if let Value::Object(listeners) = listeners {
-        for i in 0..listeners.length() {
-            let listener = listeners
-                .get(&format!("{}", i), avm, context)?
-                .resolve(avm, context)?;
-
-            if let Value::Object(listener) = listener {
-                listener
-                    .call_method(&event_name, call_args, avm, context)?
-                    .resolve(avm, context)?;
-            }
-        }
-    }
-
-    Ok(Value::Undefined.into())
-}
-
 pub fn load_clip<'gc>(
     avm: &mut Avm1<'gc>,
     context: &mut UpdateContext<'_, 'gc, '_>,
@@ -146,15 +47,15 @@ pub fn load_clip<'gc>(
             .as_display_object()
             .and_then(|dobj| dobj.as_movie_clip())
         {
-            let fetch = context.navigator.fetch(url, RequestOptions::get());
-            let process = context.load_manager.load_movie_into_clip(
+            context.load_manager.load_movie_into_clip(
                 context.player.clone().unwrap(),
                 DisplayObject::MovieClip(movieclip),
-                fetch,
+                context.navigator,
+                context.cancellation_token,
+                url,
+                RequestOptions::get(),
                 Some(this),
             );
-
-            context.navigator.spawn_future(process);
         }
 
         Ok(true.into())
@@ -176,6 +77,7 @@ pub fn unload_clip<'gc>(
             .as_display_object()
             .and_then(|dobj| dobj.as_movie_clip())
         {
+            context.load_manager.cancel_movie_loads(movieclip.into());
             movieclip.unload(context);
             movieclip.replace_with_movie(context.gc_context, None);
 
@@ -199,23 +101,34 @@ pub fn get_progress<'gc>(
             .as_display_object()
             .and_then(|dobj| dobj.as_movie_clip())
         {
+            // While a movie is being loaded into the clip, its progress is that of the load.
+            let (bytes_loaded, bytes_total) = match context
+                .load_manager
+                .movie_progress(DisplayObject::MovieClip(movieclip))
+            {
+                Some((loaded, total)) => {
+                    (loaded.into(), total.map_or(Value::Undefined, Value::from))
+                }
+                None => {
+                    let length = movieclip
+                        .movie()
+                        .map(|mv| (mv.data().len() + 21).into())
+                        .unwrap_or(Value::Undefined);
+                    (length.clone(), length)
+                }
+            };
+
             let ret_obj = ScriptObject::object(context.gc_context, None);
             ret_obj.define_value(
                 context.gc_context,
                 "bytesLoaded",
-                movieclip
-                    .movie()
-                    .map(|mv| (mv.data().len() + 21).into())
-                    .unwrap_or(Value::Undefined),
+                bytes_loaded,
                 EnumSet::empty(),
             );
             ret_obj.define_value(
                 context.gc_context,
                 "bytesTotal",
-                movieclip
-                    .movie()
-                    .map(|mv| (mv.data().len() + 21).into())
-                    .unwrap_or(Value::Undefined),
+                bytes_total,
                 EnumSet::empty(),
             );
 
@@ -235,21 +148,21 @@ pub fn create_proto<'gc>(
 
     mcl_proto.as_script_object().unwrap().force_set_function(
         "addListener",
-        add_listener,
+        as_broadcaster::add_listener,
         gc_context,
         EnumSet::empty(),
         Some(fn_proto),
     );
     mcl_proto.as_script_object().unwrap().force_set_function(
         "removeListener",
-        remove_listener,
+        as_broadcaster::remove_listener,
         gc_context,
         EnumSet::empty(),
         Some(fn_proto),
     );
     mcl_proto.as_script_object().unwrap().force_set_function(
         "broadcastMessage",
-        broadcast_message,
+        as_broadcaster::broadcast_message,
         gc_context,
         EnumSet::empty(),
         Some(fn_proto),
@@ -0,0 +1,376 @@
+//! NetConnection object
+//!
+//! Only Flash Remoting is supported: `connect` records the URL of an HTTP gateway, and each
+//! `call` is sent to it as an AMF0 request.
+
+use crate::amf::{AmfValue, Header, Message, Packet, CONTENT_TYPE};
+use crate::avm1::property::Attribute::*;
+use crate::avm1::return_value::ReturnValue;
+use crate::avm1::{Avm1, Error, Object, ScriptObject, TObject, UpdateContext, Value};
+use crate::backend::navigator::RequestOptions;
+use crate::context::ActionType;
+use enumset::EnumSet;
+use gc_arena::MutationContext;
+
+pub fn constructor<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let headers = ScriptObject::array(context.gc_context, Some(avm.prototypes().array));
+    this.define_value(
+        context.gc_context,
+        "_headers",
+        headers.into(),
+        DontEnum.into(),
+    );
+    this.set("isConnected", false.into(), avm, context)?;
+
+    Ok(Value::Undefined.into())
+}
+
+pub fn create_proto<'gc>(
+    gc_context: MutationContext<'gc, '_>,
+    proto: Object<'gc>,
+    fn_proto: Object<'gc>,
+) -> Object<'gc> {
+    let mut object = ScriptObject::object(gc_context, Some(proto));
+
+    object.force_set_function(
+        "addHeader",
+        add_header,
+        gc_context,
+        DontDelete | ReadOnly | DontEnum,
+        Some(fn_proto),
+    );
+
+    object.force_set_function(
+        "call",
+        call,
+        gc_context,
+        DontDelete | ReadOnly | DontEnum,
+        Some(fn_proto),
+    );
+
+    object.force_set_function(
+        "close",
+        close,
+        gc_context,
+        DontDelete | ReadOnly | DontEnum,
+        Some(fn_proto),
+    );
+
+    object.force_set_function(
+        "connect",
+        connect,
+        gc_context,
+        DontDelete | ReadOnly | DontEnum,
+        Some(fn_proto),
+    );
+
+    object.into()
+}
+
+/// Create the info object passed to `onStatus` handlers.
+pub fn status_object<'gc>(
+    avm: &mut Avm1<'gc>,
+    gc_context: MutationContext<'gc, '_>,
+    level: &str,
+    code: &str,
+    description: Option<String>,
+) -> Object<'gc> {
+    let info = ScriptObject::object(gc_context, Some(avm.prototypes().object));
+    info.define_value(gc_context, "level", level.into(), EnumSet::empty());
+    info.define_value(gc_context, "code", code.into(), EnumSet::empty());
+    if let Some(description) = description {
+        info.define_value(
+            gc_context,
+            "description",
+            description.into(),
+            EnumSet::empty(),
+        );
+    }
+
+    info.into()
+}
+
+/// Convert an AVM value into an AMF value to be sent to a gateway.
+///
+/// Functions are skipped, and objects that contain themselves are sent as `null` the second
+/// time they are encountered.
+pub fn value_to_amf<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    value: Value<'gc>,
+    parents: &mut Vec<Object<'gc>>,
+) -> Result<AmfValue, Error> {
+    let object = match value {
+        Value::Undefined => return Ok(AmfValue::Undefined),
+        Value::Null => return Ok(AmfValue::Null),
+        Value::Bool(value) => return Ok(AmfValue::Bool(value)),
+        Value::Number(number) => return Ok(AmfValue::Number(number)),
+        Value::String(string) => return Ok(AmfValue::String(string)),
+        Value::Object(object) => object,
+    };
+
+    if parents.iter().any(|parent| Object::ptr_eq(*parent, object)) {
+        return Ok(AmfValue::Null);
+    }
+    parents.push(object);
+
+    let array_proto = avm.prototypes().array;
+    let mut proto = object.proto();
+    let mut is_array = false;
+    while let Some(p) = proto {
+        if Object::ptr_eq(p, array_proto) {
+            is_array = true;
+            break;
+        }
+        proto = p.proto();
+    }
+
+    let amf = if is_array {
+        let mut values = vec![];
+        for element in object.array() {
+            values.push(value_to_amf(avm, context, element, parents)?);
+        }
+        AmfValue::StrictArray(values)
+    } else {
+        let mut properties = vec![];
+        for name in object.get_keys(avm) {
+            let property = object.get(&name, avm, context)?.resolve(avm, context)?;
+            if let Value::Object(property) = property {
+                if property.as_executable().is_some() {
+                    continue;
+                }
+            }
+            properties.push((name, value_to_amf(avm, context, property, parents)?));
+        }
+        AmfValue::Object(properties)
+    };
+
+    parents.pop();
+    Ok(amf)
+}
+
+/// Convert an AMF value received from a gateway into an AVM value.
+pub fn amf_to_value<'gc>(
+    avm: &mut Avm1<'gc>,
+    gc_context: MutationContext<'gc, '_>,
+    amf: &AmfValue,
+) -> Value<'gc> {
+    match amf {
+        AmfValue::Number(number) | AmfValue::Date(number) => (*number).into(),
+        AmfValue::Bool(value) => (*value).into(),
+        AmfValue::String(string) | AmfValue::Xml(string) => string.to_owned().into(),
+        AmfValue::Null => Value::Null,
+        AmfValue::Undefined => Value::Undefined,
+        AmfValue::Object(properties) | AmfValue::TypedObject(_, properties) => {
+            let object = ScriptObject::object(gc_context, Some(avm.prototypes().object));
+            for (name, property) in properties {
+                let property = amf_to_value(avm, gc_context, property);
+                object.define_value(gc_context, name, property, EnumSet::empty());
+            }
+            object.into()
+        }
+        AmfValue::EcmaArray(properties) => {
+            let array = ScriptObject::array(gc_context, Some(avm.prototypes().array));
+            for (name, property) in properties {
+                let property = amf_to_value(avm, gc_context, property);
+                match name.parse::<usize>() {
+                    Ok(index) => {
+                        if index >= array.length() {
+                            array.set_length(gc_context, index + 1);
+                        }
+                        array.set_array_element(index, property, gc_context);
+                    }
+                    Err(_) => array.define_value(gc_context, name, property, EnumSet::empty()),
+                }
+            }
+            array.into()
+        }
+        AmfValue::StrictArray(values) => {
+            let array = ScriptObject::array(gc_context, Some(avm.prototypes().array));
+            array.set_length(gc_context, values.len());
+            for (index, value) in values.iter().enumerate() {
+                let value = amf_to_value(avm, gc_context, value);
+                array.set_array_element(index, value, gc_context);
+            }
+            array.into()
+        }
+    }
+}
+
+pub fn connect<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let url = match args.get(0).cloned().unwrap_or(Value::Undefined) {
+        Value::Undefined | Value::Null => None,
+        url => Some(url.coerce_to_string(avm, context)?),
+    };
+
+    let (success, code) = match &url {
+        // A null connection is used to play local media, and is always connected.
+        None => (true, "NetConnection.Connect.Success"),
+        Some(url) if url.starts_with("rtmp") => {
+            log::warn!("NetConnection.connect: RTMP is not supported ({})", url);
+            (false, "NetConnection.Connect.Failed")
+        }
+
+        // Remoting gateways are connectionless, so no status is reported.
+        Some(url) => {
+            this.set("uri", url.to_owned().into(), avm, context)?;
+            this.set("isConnected", false.into(), avm, context)?;
+            return Ok(true.into());
+        }
+    };
+
+    this.set(
+        "uri",
+        url.map(Value::from).unwrap_or(Value::Null),
+        avm,
+        context,
+    )?;
+    this.set("isConnected", success.into(), avm, context)?;
+
+    let level = if success { "status" } else { "error" };
+    let info = status_object(avm, context.gc_context, level, code, None);
+    context.action_queue.queue_actions(
+        avm.target_clip_or_root(),
+        ActionType::Method {
+            object: this,
+            name: "onStatus",
+            args: vec![info.into()],
+        },
+        false,
+    );
+
+    Ok(success.into())
+}
+
+pub fn add_header<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let name = args
+        .get(0)
+        .cloned()
+        .unwrap_or(Value::Undefined)
+        .coerce_to_string(avm, context)?;
+    let must_understand = args.get(1).cloned().unwrap_or(Value::Undefined);
+    let value = args.get(2).cloned().unwrap_or(Value::Undefined);
+
+    if let Value::Object(headers) = this.get("_headers", avm, context)?.resolve(avm, context)? {
+        let header = ScriptObject::object(context.gc_context, Some(avm.prototypes().object));
+        header.set("name", name.into(), avm, context)?;
+        header.set("mustUnderstand", must_understand, avm, context)?;
+        header.set("value", value, avm, context)?;
+
+        let length = headers.length();
+        headers.set_length(context.gc_context, length + 1);
+        headers.set_array_element(length, header.into(), context.gc_context);
+    }
+
+    Ok(Value::Undefined.into())
+}
+
+pub fn call<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let url = match this.get("uri", avm, context)?.resolve(avm, context)? {
+        Value::String(url) => url,
+        _ => {
+            log::warn!("NetConnection.call: Not connected to a remoting gateway");
+            return Ok(Value::Undefined.into());
+        }
+    };
+
+    let method = args
+        .get(0)
+        .cloned()
+        .unwrap_or(Value::Undefined)
+        .coerce_to_string(avm, context)?;
+    let responder = match args.get(1) {
+        Some(Value::Object(responder)) => Some(*responder),
+        _ => None,
+    };
+
+    let mut parameters = vec![];
+    for arg in args.iter().skip(2) {
+        parameters.push(value_to_amf(avm, context, arg.to_owned(), &mut vec![])?);
+    }
+
+    let mut headers = vec![];
+    if let Value::Object(header_objects) =
+        this.get("_headers", avm, context)?.resolve(avm, context)?
+    {
+        let swf_version = avm.current_swf_version();
+        for header in header_objects.array() {
+            if let Value::Object(header) = header {
+                let name = header
+                    .get("name", avm, context)?
+                    .resolve(avm, context)?
+                    .coerce_to_string(avm, context)?;
+                let must_understand = header
+                    .get("mustUnderstand", avm, context)?
+                    .resolve(avm, context)?
+                    .as_bool(swf_version);
+                let value = header.get("value", avm, context)?.resolve(avm, context)?;
+                headers.push(Header {
+                    name,
+                    must_understand,
+                    value: value_to_amf(avm, context, value, &mut vec![])?,
+                });
+            }
+        }
+    }
+
+    let navigator = &mut context.navigator;
+    let process = context.load_manager.load_remoting_result(
+        context.player.clone().unwrap(),
+        this,
+        responder,
+        avm.target_clip_or_root(),
+        |response_uri| {
+            let packet = Packet {
+                version: 0,
+                headers,
+                messages: vec![Message {
+                    target_uri: method,
+                    response_uri: response_uri.to_string(),
+                    value: AmfValue::StrictArray(parameters),
+                }],
+            };
+            let body = (packet.to_bytes(), CONTENT_TYPE.to_string());
+            navigator.fetch(url, RequestOptions::post(Some(body)))
+        },
+    );
+
+    context
+        .navigator
+        .spawn_future(process, context.cancellation_token.clone())
+        .detach();
+
+    Ok(Value::Undefined.into())
+}
+
+pub fn close<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    this.set("uri", Value::Null, avm, context)?;
+    this.set("isConnected", false.into(), avm, context)?;
+
+    Ok(Value::Undefined.into())
+}
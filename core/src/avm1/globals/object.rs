@@ -4,6 +4,7 @@ use crate::avm1::property::Attribute::{self, *};
 use crate::avm1::return_value::ReturnValue;
 use crate::avm1::{Avm1, Error, Object, TObject, UpdateContext, Value};
 use crate::character::Character;
+use crate::display_object::TDisplayObject;
 use enumset::EnumSet;
 use gc_arena::MutationContext;
 
@@ -144,9 +145,14 @@ pub fn register_class<'gc>(
 ) -> Result<ReturnValue<'gc>, Error> {
     if let Some(class_name) = args.get(0).cloned() {
         let class_name = class_name.coerce_to_string(avm, context)?;
+        // Export names are scoped to the movie that defined the calling code.
+        let movie = avm
+            .base_clip()
+            .movie()
+            .unwrap_or_else(|| context.swf.clone());
         if let Some(Character::MovieClip(movie_clip)) = context
             .library
-            .library_for_movie_mut(context.swf.clone())
+            .library_for_movie_mut(movie)
             .get_character_by_export_name(&class_name)
         {
             if let Some(constructor) = args.get(1) {
@@ -0,0 +1,214 @@
+//! Selection object
+//!
+//! Only text fields can be focused.
+use crate::avm1::globals::as_broadcaster;
+use crate::avm1::property::Attribute;
+use crate::avm1::return_value::ReturnValue;
+use crate::avm1::{Avm1, Error, Object, ScriptObject, UpdateContext, Value};
+use crate::display_object::{DisplayObject, EditText, TDisplayObject};
+use gc_arena::MutationContext;
+
+/// Returns the focused text field, if it is still on the stage.
+fn focused_text_field<'gc>(context: &UpdateContext<'_, 'gc, '_>) -> Option<EditText<'gc>> {
+    context
+        .focused_object
+        .filter(|object| !object.removed())
+        .and_then(|object| object.as_edit_text())
+}
+
+/// Moves focus to `new_focus`, notifying `Selection` listeners of the change.
+fn change_focus<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    selection: Object<'gc>,
+    new_focus: Option<DisplayObject<'gc>>,
+) -> Result<(), Error> {
+    let old_focus = focused_text_field(context).map(DisplayObject::from);
+    *context.focused_object = new_focus;
+
+    let unchanged = match (old_focus, new_focus) {
+        (Some(old), Some(new)) => DisplayObject::ptr_eq(old, new),
+        (old, new) => old.is_none() && new.is_none(),
+    };
+    if !unchanged {
+        let to_value =
+            |object: Option<DisplayObject<'gc>>| object.map_or(Value::Null, |o| o.object());
+        as_broadcaster::broadcast(
+            avm,
+            context,
+            selection,
+            "onSetFocus",
+            &[to_value(old_focus), to_value(new_focus)],
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn get_focus<'gc>(
+    _avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    _this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    match focused_text_field(context) {
+        Some(text_field) => Ok(text_field.path().into()),
+        None => Ok(Value::Null.into()),
+    }
+}
+
+pub fn set_focus<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let target = match args.get(0).cloned().unwrap_or(Value::Undefined) {
+        Value::Undefined | Value::Null => {
+            change_focus(avm, context, this, None)?;
+            return Ok(true.into());
+        }
+        target => target,
+    };
+
+    let start = avm.target_clip_or_root();
+    let text_field = avm
+        .resolve_target_display_object(context, start, target)?
+        .and_then(|object| object.as_edit_text())
+        .filter(|text_field| text_field.is_selectable());
+
+    match text_field {
+        Some(text_field) => {
+            // Focusing a text field selects all of its text.
+            let length = text_field.text().chars().count();
+            text_field.set_selection(0, length, context.gc_context);
+            change_focus(avm, context, this, Some(text_field.into()))?;
+            Ok(true.into())
+        }
+        None => Ok(false.into()),
+    }
+}
+
+pub fn get_begin_index<'gc>(
+    _avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    _this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    match focused_text_field(context) {
+        Some(text_field) => Ok((text_field.selection().begin as f64).into()),
+        None => Ok((-1).into()),
+    }
+}
+
+pub fn get_end_index<'gc>(
+    _avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    _this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    match focused_text_field(context) {
+        Some(text_field) => Ok((text_field.selection().end as f64).into()),
+        None => Ok((-1).into()),
+    }
+}
+
+pub fn get_caret_index<'gc>(
+    _avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    _this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    match focused_text_field(context) {
+        Some(text_field) => Ok((text_field.selection().caret as f64).into()),
+        None => Ok((-1).into()),
+    }
+}
+
+pub fn set_selection<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    _this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    if let Some(text_field) = focused_text_field(context) {
+        let begin = args
+            .get(0)
+            .cloned()
+            .unwrap_or(Value::Undefined)
+            .as_number(avm, context)?;
+        let end = args
+            .get(1)
+            .cloned()
+            .unwrap_or(Value::Undefined)
+            .as_number(avm, context)?;
+        text_field.set_selection(
+            begin.max(0.0) as usize,
+            end.max(0.0) as usize,
+            context.gc_context,
+        );
+    }
+
+    Ok(Value::Undefined.into())
+}
+
+pub fn create_selection_object<'gc>(
+    gc_context: MutationContext<'gc, '_>,
+    proto: Option<Object<'gc>>,
+    array_proto: Option<Object<'gc>>,
+    fn_proto: Option<Object<'gc>>,
+) -> Object<'gc> {
+    let mut selection = ScriptObject::object(gc_context, proto);
+
+    as_broadcaster::initialize_internal(gc_context, selection.into(), array_proto, fn_proto);
+
+    selection.force_set_function(
+        "getFocus",
+        get_focus,
+        gc_context,
+        Attribute::DontEnum | Attribute::DontDelete | Attribute::ReadOnly,
+        fn_proto,
+    );
+
+    selection.force_set_function(
+        "setFocus",
+        set_focus,
+        gc_context,
+        Attribute::DontEnum | Attribute::DontDelete | Attribute::ReadOnly,
+        fn_proto,
+    );
+
+    selection.force_set_function(
+        "getBeginIndex",
+        get_begin_index,
+        gc_context,
+        Attribute::DontEnum | Attribute::DontDelete | Attribute::ReadOnly,
+        fn_proto,
+    );
+
+    selection.force_set_function(
+        "getEndIndex",
+        get_end_index,
+        gc_context,
+        Attribute::DontEnum | Attribute::DontDelete | Attribute::ReadOnly,
+        fn_proto,
+    );
+
+    selection.force_set_function(
+        "getCaretIndex",
+        get_caret_index,
+        gc_context,
+        Attribute::DontEnum | Attribute::DontDelete | Attribute::ReadOnly,
+        fn_proto,
+    );
+
+    selection.force_set_function(
+        "setSelection",
+        set_selection,
+        gc_context,
+        Attribute::DontEnum | Attribute::DontDelete | Attribute::ReadOnly,
+        fn_proto,
+    );
+
+    selection.into()
+}
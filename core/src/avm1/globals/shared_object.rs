@@ -0,0 +1,347 @@
+//! SharedObject object
+//!
+//! Only local shared objects are supported. Their data is kept by the `StorageBackend` as AMF0,
+//! under a key made from the location of the movie, like Flash Player stores `.sol` files.
+
+use crate::amf::AmfValue;
+use crate::avm1::function::{Executable, FunctionObject};
+use crate::avm1::globals::net_connection::{amf_to_value, status_object, value_to_amf};
+use crate::avm1::property::Attribute::*;
+use crate::avm1::return_value::ReturnValue;
+use crate::avm1::{Avm1, Error, Object, ScriptObject, TObject, UpdateContext, Value};
+use crate::context::ActionType;
+use crate::display_object::TDisplayObject;
+use gc_arena::MutationContext;
+
+/// Characters that Flash Player doesn't allow in the names of shared objects.
+const INVALID_NAME_CHARACTERS: &[char] = &[
+    '~', '%', '&', '\\', ';', ':', '"', '\'', ',', '<', '>', '?', '#', ' ',
+];
+
+pub fn constructor<'gc>(
+    _avm: &mut Avm1<'gc>,
+    _context: &mut UpdateContext<'_, 'gc, '_>,
+    _this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    Ok(Value::Undefined.into())
+}
+
+pub fn create_shared_object_object<'gc>(
+    gc_context: MutationContext<'gc, '_>,
+    shared_object_proto: Option<Object<'gc>>,
+    fn_proto: Option<Object<'gc>>,
+) -> Object<'gc> {
+    let shared_object = FunctionObject::function(
+        gc_context,
+        Executable::Native(constructor),
+        fn_proto,
+        shared_object_proto,
+    );
+    let mut object = shared_object.as_script_object().unwrap();
+
+    object.force_set_function(
+        "getLocal",
+        get_local,
+        gc_context,
+        DontDelete | ReadOnly | DontEnum,
+        fn_proto,
+    );
+
+    shared_object
+}
+
+pub fn create_proto<'gc>(
+    gc_context: MutationContext<'gc, '_>,
+    proto: Object<'gc>,
+    fn_proto: Object<'gc>,
+) -> Object<'gc> {
+    let mut object = ScriptObject::object(gc_context, Some(proto));
+
+    object.force_set_function(
+        "clear",
+        clear,
+        gc_context,
+        DontDelete | ReadOnly | DontEnum,
+        Some(fn_proto),
+    );
+
+    object.force_set_function(
+        "flush",
+        flush,
+        gc_context,
+        DontDelete | ReadOnly | DontEnum,
+        Some(fn_proto),
+    );
+
+    object.force_set_function(
+        "getSize",
+        get_size,
+        gc_context,
+        DontDelete | ReadOnly | DontEnum,
+        Some(fn_proto),
+    );
+
+    object.into()
+}
+
+/// Build the storage key of a shared object, or `None` if ActionScript may not access it.
+///
+/// Like Flash Player, objects are stored per host and per path: by default, the full path of
+/// the movie, so that different movies can't see each other's objects. `local_path` may give a
+/// shorter path, which must be a parent directory of the movie (or the movie itself), so that
+/// movies in the same directory tree can share objects.
+pub fn storage_key(
+    movie_url: Option<&str>,
+    name: &str,
+    local_path: Option<&str>,
+) -> Option<String> {
+    if name.is_empty() || name.contains(INVALID_NAME_CHARACTERS) {
+        return None;
+    }
+
+    // Local files and movies without a URL are stored under `localhost`, like Flash Player.
+    // Windows paths would parse as URLs with a drive letter scheme.
+    let (host, movie_path) = match movie_url.map(url::Url::parse) {
+        Some(Ok(url)) if url.scheme().len() > 1 => (
+            url.host_str().unwrap_or("localhost").to_string(),
+            url.path().to_string(),
+        ),
+        _ => {
+            let path = movie_url.unwrap_or("").replace('\\', "/");
+            let path = if path.starts_with('/') {
+                path
+            } else {
+                format!("/{}", path)
+            };
+            ("localhost".to_string(), path)
+        }
+    };
+
+    let path = match local_path {
+        Some(local_path) => {
+            let local_path = local_path.trim_end_matches('/');
+            if !local_path.is_empty()
+                && movie_path != local_path
+                && !movie_path.starts_with(&format!("{}/", local_path))
+            {
+                return None;
+            }
+            local_path
+        }
+        None => movie_path.trim_end_matches('/'),
+    };
+
+    Some(format!("{}{}/{}", host, path, name))
+}
+
+pub fn get_local<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    _this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let name = args
+        .get(0)
+        .cloned()
+        .unwrap_or(Value::Undefined)
+        .coerce_to_string(avm, context)?;
+    let local_path = match args.get(1) {
+        None | Some(Value::Undefined) | Some(Value::Null) => None,
+        Some(local_path) => Some(local_path.clone().coerce_to_string(avm, context)?),
+    };
+
+    // Objects belong to the movie that defined the calling code.
+    let movie = avm
+        .base_clip()
+        .movie()
+        .unwrap_or_else(|| context.swf.clone());
+    let key = match storage_key(context.movie_url(&movie), &name, local_path.as_deref()) {
+        Some(key) => key,
+        None => return Ok(Value::Null.into()),
+    };
+
+    // Every call for the same object returns the same instance.
+    if let Some(shared_object) = context.shared_objects.get(&key) {
+        return Ok((*shared_object).into());
+    }
+
+    let data = match context
+        .storage
+        .get(&key)
+        .map(|data| AmfValue::from_bytes(&data))
+    {
+        Some(Ok(amf @ AmfValue::Object(_))) => amf_to_value(avm, context.gc_context, &amf),
+        Some(Ok(_)) | Some(Err(_)) => {
+            log::warn!("SharedObject {}: ignoring unreadable data", key);
+            ScriptObject::object(context.gc_context, Some(avm.prototypes().object)).into()
+        }
+        None => ScriptObject::object(context.gc_context, Some(avm.prototypes().object)).into(),
+    };
+
+    let shared_object =
+        ScriptObject::object(context.gc_context, Some(avm.prototypes().shared_object));
+    shared_object.define_value(context.gc_context, "data", data, DontDelete.into());
+    context.shared_objects.insert(key, shared_object.into());
+
+    Ok(Value::Object(shared_object.into()).into())
+}
+
+/// Finds the storage key of a shared object returned by `getLocal`.
+fn key_of<'gc>(context: &UpdateContext<'_, 'gc, '_>, this: Object<'gc>) -> Option<String> {
+    context
+        .shared_objects
+        .iter()
+        .find(|(_, shared_object)| Object::ptr_eq(**shared_object, this))
+        .map(|(key, _)| key.clone())
+}
+
+/// Serialize the `data` of a shared object, or `None` if it's empty.
+pub fn serialize_data<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+) -> Result<Option<Vec<u8>>, Error> {
+    let data = this.get("data", avm, context)?.resolve(avm, context)?;
+    match value_to_amf(avm, context, data, &mut vec![])? {
+        AmfValue::Object(properties) if properties.is_empty() => Ok(None),
+        amf => Ok(Some(amf.to_bytes())),
+    }
+}
+
+pub fn flush<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let key = match key_of(context, this) {
+        Some(key) => key,
+        None => return Ok(false.into()),
+    };
+    let data =
+        serialize_data(avm, context, this)?.unwrap_or_else(|| AmfValue::Object(vec![]).to_bytes());
+    let persisted = context.storage.put(&key, data);
+
+    // The data is written in the background, and `onStatus` reports how it went.
+    let player = context.player.clone();
+    let process = Box::pin(async move {
+        let result = persisted.await;
+        if let Some(player) = player.and_then(|player| player.upgrade()) {
+            player
+                .lock()
+                .expect("Could not lock player!!")
+                .update(|avm, context| {
+                    let (level, code) = match &result {
+                        Ok(()) => ("status", "SharedObject.Flush.Success"),
+                        Err(e) => {
+                            log::warn!("SharedObject {}: flush failed: {}", key, e);
+                            ("error", "SharedObject.Flush.Failed")
+                        }
+                    };
+                    let shared_object = context.shared_objects.get(&key).copied();
+                    let root = context.levels.get(&0).copied();
+                    if let (Some(shared_object), Some(root)) = (shared_object, root) {
+                        let info = status_object(avm, context.gc_context, level, code, None);
+                        context.action_queue.queue_actions(
+                            root,
+                            ActionType::Method {
+                                object: shared_object,
+                                name: "onStatus",
+                                args: vec![info.into()],
+                            },
+                            false,
+                        );
+                    }
+                });
+        }
+        Ok(())
+    });
+    context
+        .navigator
+        .spawn_future(process, context.cancellation_token.clone())
+        .detach();
+
+    Ok("pending".into())
+}
+
+pub fn clear<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    if let Some(key) = key_of(context, this) {
+        context.storage.remove(&key);
+    }
+    let data = ScriptObject::object(context.gc_context, Some(avm.prototypes().object));
+    this.define_value(context.gc_context, "data", data.into(), DontDelete.into());
+
+    Ok(Value::Undefined.into())
+}
+
+pub fn get_size<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let size = serialize_data(avm, context, this)?.map_or(0, |data| data.len());
+    Ok((size as f64).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn objects_are_stored_under_the_movie_path() {
+        assert_eq!(
+            storage_key(Some("http://example.com/games/a.swf"), "scores", None),
+            Some("example.com/games/a.swf/scores".to_string())
+        );
+        assert_eq!(
+            storage_key(Some("http://example.com/games/b.swf"), "scores", None),
+            Some("example.com/games/b.swf/scores".to_string())
+        );
+        assert_eq!(
+            storage_key(Some("file:///home/user/a.swf"), "scores", None),
+            Some("localhost/home/user/a.swf/scores".to_string())
+        );
+        assert_eq!(
+            storage_key(Some("tests/a.swf"), "sub/scores", None),
+            Some("localhost/tests/a.swf/sub/scores".to_string())
+        );
+        assert_eq!(
+            storage_key(None, "scores", None),
+            Some("localhost/scores".to_string())
+        );
+    }
+
+    #[test]
+    fn local_path_must_contain_the_movie() {
+        let url = Some("http://example.com/games/a.swf");
+        assert_eq!(
+            storage_key(url, "scores", Some("/")),
+            Some("example.com/scores".to_string())
+        );
+        assert_eq!(
+            storage_key(url, "scores", Some("/games/")),
+            Some("example.com/games/scores".to_string())
+        );
+        assert_eq!(
+            storage_key(url, "scores", Some("/games/a.swf")),
+            Some("example.com/games/a.swf/scores".to_string())
+        );
+        assert_eq!(storage_key(url, "scores", Some("/gam")), None);
+        assert_eq!(storage_key(url, "scores", Some("/other")), None);
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        let url = Some("http://example.com/a.swf");
+        assert_eq!(storage_key(url, "", None), None);
+        assert_eq!(storage_key(url, "high scores", None), None);
+        assert_eq!(storage_key(url, "scores?", None), None);
+    }
+}
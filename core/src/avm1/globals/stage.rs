@@ -2,27 +2,24 @@
 //!
 //! TODO: This is a very rough stub with not much implementation.
 use crate::avm1::function::Executable;
+use crate::avm1::globals::as_broadcaster;
 use crate::avm1::property::Attribute;
 use crate::avm1::return_value::ReturnValue;
 use crate::avm1::{Avm1, Error, Object, ScriptObject, TObject, UpdateContext, Value};
+use crate::backend::ui::DisplayState;
+use crate::viewport::StageScaleMode;
 
 use gc_arena::MutationContext;
 
 pub fn create_stage_object<'gc>(
     gc_context: MutationContext<'gc, '_>,
     proto: Option<Object<'gc>>,
-    _array_proto: Option<Object<'gc>>,
+    array_proto: Option<Object<'gc>>,
     fn_proto: Option<Object<'gc>>,
 ) -> Object<'gc> {
     let mut stage = ScriptObject::object(gc_context, proto);
 
-    stage.force_set_function(
-        "addListener",
-        add_listener,
-        gc_context,
-        Attribute::DontEnum | Attribute::DontDelete | Attribute::ReadOnly,
-        fn_proto,
-    );
+    as_broadcaster::initialize_internal(gc_context, stage.into(), array_proto, fn_proto);
 
     stage.add_property(
         gc_context,
@@ -34,18 +31,18 @@ pub fn create_stage_object<'gc>(
 
     stage.add_property(
         gc_context,
-        "height",
-        Executable::Native(height),
-        None,
-        Attribute::DontEnum | Attribute::DontDelete | Attribute::ReadOnly,
+        "displayState",
+        Executable::Native(display_state),
+        Some(Executable::Native(set_display_state)),
+        Attribute::DontEnum | Attribute::DontDelete,
     );
 
-    stage.force_set_function(
-        "removeListener",
-        remove_listener,
+    stage.add_property(
         gc_context,
+        "height",
+        Executable::Native(height),
+        None,
         Attribute::DontEnum | Attribute::DontDelete | Attribute::ReadOnly,
-        fn_proto,
     );
 
     stage.add_property(
@@ -75,16 +72,6 @@ pub fn create_stage_object<'gc>(
     stage.into()
 }
 
-fn add_listener<'gc>(
-    _avm: &mut Avm1<'gc>,
-    _context: &mut UpdateContext<'_, 'gc, '_>,
-    _this: Object<'gc>,
-    _args: &[Value<'gc>],
-) -> Result<ReturnValue<'gc>, Error> {
-    log::warn!("Stage.addListener: unimplemented");
-    Ok(Value::Undefined.into())
-}
-
 fn align<'gc>(
     _avm: &mut Avm1<'gc>,
     _context: &mut UpdateContext<'_, 'gc, '_>,
@@ -105,42 +92,72 @@ fn set_align<'gc>(
     Ok(Value::Undefined.into())
 }
 
-fn height<'gc>(
+fn display_state<'gc>(
     _avm: &mut Avm1<'gc>,
     context: &mut UpdateContext<'_, 'gc, '_>,
     _this: Object<'gc>,
     _args: &[Value<'gc>],
 ) -> Result<ReturnValue<'gc>, Error> {
-    Ok(context.stage_size.1.to_pixels().into())
+    let state = match context.ui.display_state() {
+        DisplayState::Normal => "normal",
+        DisplayState::FullScreen => "fullScreen",
+    };
+    Ok(state.into())
+}
+
+fn set_display_state<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    _this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let state = args
+        .get(0)
+        .cloned()
+        .unwrap_or(Value::Undefined)
+        .coerce_to_string(avm, context)?;
+    let state = match state.as_str() {
+        "normal" => DisplayState::Normal,
+        "fullScreen" => DisplayState::FullScreen,
+        _ => return Ok(Value::Undefined.into()),
+    };
+    if let Err(e) = context.ui.set_display_state(state) {
+        log::warn!("Stage.displayState: {}", e);
+    }
+    Ok(Value::Undefined.into())
 }
 
-fn remove_listener<'gc>(
+fn height<'gc>(
     _avm: &mut Avm1<'gc>,
-    _context: &mut UpdateContext<'_, 'gc, '_>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
     _this: Object<'gc>,
     _args: &[Value<'gc>],
 ) -> Result<ReturnValue<'gc>, Error> {
-    log::warn!("Stage.removeListener: unimplemented");
-    Ok("".into())
+    Ok(context.stage_size.1.to_pixels().into())
 }
 
 fn scale_mode<'gc>(
     _avm: &mut Avm1<'gc>,
-    _context: &mut UpdateContext<'_, 'gc, '_>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
     _this: Object<'gc>,
     _args: &[Value<'gc>],
 ) -> Result<ReturnValue<'gc>, Error> {
-    log::warn!("Stage.scaleMode: unimplemented");
-    Ok("noScale".into())
+    Ok(context.scale_mode.name().into())
 }
 
 fn set_scale_mode<'gc>(
-    _avm: &mut Avm1<'gc>,
-    _context: &mut UpdateContext<'_, 'gc, '_>,
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
     _this: Object<'gc>,
-    _args: &[Value<'gc>],
+    args: &[Value<'gc>],
 ) -> Result<ReturnValue<'gc>, Error> {
-    log::warn!("Stage.scaleMode: unimplemented");
+    let scale_mode = args
+        .get(0)
+        .cloned()
+        .unwrap_or(Value::Undefined)
+        .coerce_to_string(avm, context)?;
+    // Flash Player falls back to `showAll` for unknown modes.
+    *context.scale_mode = StageScaleMode::from_name(&scale_mode).unwrap_or_default();
     Ok(Value::Undefined.into())
 }
 
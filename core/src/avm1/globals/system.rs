@@ -0,0 +1,42 @@
+//! System object
+//!
+//! TODO: `capabilities`, `security` and `useCodepage` are not implemented.
+use crate::avm1::property::Attribute;
+use crate::avm1::return_value::ReturnValue;
+use crate::avm1::{Avm1, Error, Object, ScriptObject, UpdateContext, Value};
+
+use gc_arena::MutationContext;
+
+pub fn set_clipboard<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    _this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let content = args
+        .get(0)
+        .cloned()
+        .unwrap_or(Value::Undefined)
+        .coerce_to_string(avm, context)?;
+    context.ui.set_clipboard_content(content);
+
+    Ok(Value::Undefined.into())
+}
+
+pub fn create<'gc>(
+    gc_context: MutationContext<'gc, '_>,
+    proto: Option<Object<'gc>>,
+    fn_proto: Option<Object<'gc>>,
+) -> Object<'gc> {
+    let mut system = ScriptObject::object(gc_context, proto);
+
+    system.force_set_function(
+        "setClipboard",
+        set_clipboard,
+        gc_context,
+        Attribute::DontEnum | Attribute::DontDelete | Attribute::ReadOnly,
+        fn_proto,
+    );
+
+    system.into()
+}
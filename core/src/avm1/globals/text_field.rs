@@ -1,9 +1,9 @@
 use crate::avm1::function::Executable;
-use crate::avm1::globals::display_object;
+use crate::avm1::globals::{as_broadcaster, display_object};
 use crate::avm1::property::Attribute::*;
 use crate::avm1::return_value::ReturnValue;
 use crate::avm1::{Avm1, Error, Object, ScriptObject, TObject, UpdateContext, Value};
-use crate::display_object::{EditText, TDisplayObject};
+use crate::display_object::{DisplayObject, EditText, TDisplayObject};
 use crate::font::TextFormat;
 use gc_arena::MutationContext;
 
@@ -53,6 +53,115 @@ pub fn set_text<'gc>(
     Ok(Value::Undefined.into())
 }
 
+pub fn get_html_text<'gc>(
+    _avm: &mut Avm1<'gc>,
+    _context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    if let Some(text_field) = this
+        .as_display_object()
+        .and_then(|dobj| dobj.as_edit_text())
+    {
+        return Ok(text_field.html_text().into());
+    }
+    Ok(Value::Undefined.into())
+}
+
+pub fn set_html_text<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    if let Some(text_field) = this
+        .as_display_object()
+        .and_then(|dobj| dobj.as_edit_text())
+    {
+        if let Some(value) = args.get(0) {
+            let html = value.to_owned().coerce_to_string(avm, context)?;
+            text_field.set_html_text(html, context.gc_context);
+        }
+    }
+    Ok(Value::Undefined.into())
+}
+
+pub fn html<'gc>(
+    _avm: &mut Avm1<'gc>,
+    _context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    if let Some(etext) = this
+        .as_display_object()
+        .and_then(|dobj| dobj.as_edit_text())
+    {
+        return Ok(etext.is_html().into());
+    }
+
+    Ok(Value::Undefined.into())
+}
+
+pub fn set_html<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let is_html = args
+        .get(0)
+        .cloned()
+        .unwrap_or(Value::Undefined)
+        .as_bool(avm.current_swf_version());
+
+    if let Some(etext) = this
+        .as_display_object()
+        .and_then(|dobj| dobj.as_edit_text())
+    {
+        etext.set_is_html(is_html, context.gc_context);
+    }
+
+    Ok(Value::Undefined.into())
+}
+
+pub fn condense_white<'gc>(
+    _avm: &mut Avm1<'gc>,
+    _context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    if let Some(etext) = this
+        .as_display_object()
+        .and_then(|dobj| dobj.as_edit_text())
+    {
+        return Ok(etext.condense_white().into());
+    }
+
+    Ok(Value::Undefined.into())
+}
+
+pub fn set_condense_white<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Error> {
+    let condense_white = args
+        .get(0)
+        .cloned()
+        .unwrap_or(Value::Undefined)
+        .as_bool(avm.current_swf_version());
+
+    if let Some(etext) = this
+        .as_display_object()
+        .and_then(|dobj| dobj.as_edit_text())
+    {
+        etext.set_condense_white(condense_white, context.gc_context);
+    }
+
+    Ok(Value::Undefined.into())
+}
+
 macro_rules! with_text_field {
     ( $gc_context: ident, $object:ident, $fn_proto: expr, $($name:expr => $fn:expr),* ) => {{
         $(
@@ -212,10 +321,81 @@ pub fn create_proto<'gc>(
                 text_field.set_new_text_format(tf_parsed, context.gc_context);
             }
 
+            Ok(Value::Undefined.into())
+        },
+        "replaceSel" => |text_field: EditText<'gc>, avm: &mut Avm1<'gc>, context: &mut UpdateContext<'_, 'gc, '_>, args: &[Value<'gc>]| {
+            let text = args
+                .get(0)
+                .cloned()
+                .unwrap_or(Value::Undefined)
+                .coerce_to_string(avm, context)?;
+
+            // Only the focused text field has a selection to replace.
+            let is_focused = context
+                .focused_object
+                .map(|focus| DisplayObject::ptr_eq(focus, text_field.into()))
+                .unwrap_or(false);
+            if is_focused {
+                text_field.replace_selection(&text, context.gc_context);
+            }
+
+            Ok(Value::Undefined.into())
+        },
+        "replaceText" => |text_field: EditText<'gc>, avm: &mut Avm1<'gc>, context: &mut UpdateContext<'_, 'gc, '_>, args: &[Value<'gc>]| {
+            let from = args
+                .get(0)
+                .cloned()
+                .unwrap_or(Value::Undefined)
+                .as_number(avm, context)?;
+            let to = args
+                .get(1)
+                .cloned()
+                .unwrap_or(Value::Undefined)
+                .as_number(avm, context)?;
+            let text = args
+                .get(2)
+                .cloned()
+                .unwrap_or(Value::Undefined)
+                .coerce_to_string(avm, context)?;
+
+            text_field.replace_text(
+                from.max(0.0) as usize,
+                to.max(0.0) as usize,
+                &text,
+                context.gc_context,
+            );
+
+            Ok(Value::Undefined.into())
+        },
+        "removeTextField" => |text_field: EditText<'gc>, _avm: &mut Avm1<'gc>, context: &mut UpdateContext<'_, 'gc, '_>, _args| {
+            display_object::remove_display_object(text_field.into(), context, display_object::AVM_DEPTH_BIAS);
+
             Ok(Value::Undefined.into())
         }
     );
 
+    object.force_set_function(
+        "addListener",
+        as_broadcaster::add_listener,
+        gc_context,
+        DontEnum | DontDelete,
+        Some(fn_proto),
+    );
+    object.force_set_function(
+        "removeListener",
+        as_broadcaster::remove_listener,
+        gc_context,
+        DontEnum | DontDelete,
+        Some(fn_proto),
+    );
+    object.force_set_function(
+        "broadcastMessage",
+        as_broadcaster::broadcast_message,
+        gc_context,
+        DontEnum | DontDelete,
+        Some(fn_proto),
+    );
+
     object.into()
 }
 
@@ -227,6 +407,27 @@ pub fn attach_virtual_properties<'gc>(gc_context: MutationContext<'gc, '_>, obje
         Some(Executable::Native(set_text)),
         DontDelete | ReadOnly | DontEnum,
     );
+    object.add_property(
+        gc_context,
+        "htmlText",
+        Executable::Native(get_html_text),
+        Some(Executable::Native(set_html_text)),
+        DontDelete | ReadOnly | DontEnum,
+    );
+    object.add_property(
+        gc_context,
+        "html",
+        Executable::Native(html),
+        Some(Executable::Native(set_html)),
+        ReadOnly.into(),
+    );
+    object.add_property(
+        gc_context,
+        "condenseWhite",
+        Executable::Native(condense_white),
+        Some(Executable::Native(set_condense_white)),
+        ReadOnly.into(),
+    );
     object.add_property(
         gc_context,
         "textWidth",
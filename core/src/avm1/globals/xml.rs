@@ -808,7 +808,9 @@ pub fn xml_load<'gc>(
             fetch,
         );
 
-        ac.navigator.spawn_future(process);
+        ac.navigator
+            .spawn_future(process, ac.cancellation_token.clone())
+            .detach();
 
         Ok(true.into())
     } else {
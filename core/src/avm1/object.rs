@@ -1,5 +1,6 @@
 //! Object trait to expose objects to AVM
 
+use crate::avm1::arguments_object::ArgumentsObject;
 use crate::avm1::function::{Executable, FunctionObject};
 use crate::avm1::property::Attribute;
 use crate::avm1::return_value::ReturnValue;
@@ -8,7 +9,10 @@ use crate::avm1::value_object::ValueObject;
 use crate::avm1::xml_attributes_object::XMLAttributesObject;
 use crate::avm1::xml_idmap_object::XMLIDMapObject;
 use crate::avm1::xml_object::XMLObject;
-use crate::avm1::{Avm1, Error, ScriptObject, SoundObject, StageObject, UpdateContext, Value};
+use crate::avm1::{
+    Avm1, BitmapDataObject, Error, IsolatedGlobalObject, ScriptObject, SoundObject, StageObject,
+    UpdateContext, Value,
+};
 use crate::display_object::DisplayObject;
 use crate::xml::XMLNode;
 use enumset::EnumSet;
@@ -23,7 +27,9 @@ use std::fmt::Debug;
     #[collect(no_drop)]
     pub enum Object<'gc> {
         ScriptObject(ScriptObject<'gc>),
+        IsolatedGlobalObject(IsolatedGlobalObject<'gc>),
         SoundObject(SoundObject<'gc>),
+        BitmapDataObject(BitmapDataObject<'gc>),
         StageObject(StageObject<'gc>),
         SuperObject(SuperObject<'gc>),
         XMLObject(XMLObject<'gc>),
@@ -31,6 +37,7 @@ use std::fmt::Debug;
         XMLIDMapObject(XMLIDMapObject<'gc>),
         ValueObject(ValueObject<'gc>),
         FunctionObject(FunctionObject<'gc>),
+        ArgumentsObject(ArgumentsObject<'gc>),
     }
 )]
 pub trait TObject<'gc>: 'gc + Collect + Debug + Into<Object<'gc>> + Clone + Copy {
@@ -107,7 +114,7 @@ pub trait TObject<'gc>: 'gc + Collect + Debug + Into<Object<'gc>> + Clone + Copy
 
         if let Value::Object(_) = method {
         } else {
-            log::warn!("Object method {} is not callable", name);
+            log::debug!("Object method {} is not callable", name);
         }
 
         method.call(avm, context, (*self).into(), base_proto, args)
@@ -353,6 +360,11 @@ pub trait TObject<'gc>: 'gc + Collect + Debug + Into<Object<'gc>> + Clone + Copy
         None
     }
 
+    /// Get the underlying `BitmapDataObject`, if it exists.
+    fn as_bitmap_data_object(&self) -> Option<BitmapDataObject<'gc>> {
+        None
+    }
+
     /// Get the underlying super object, if it exists.
     fn as_super_object(&self) -> Option<SuperObject<'gc>> {
         None
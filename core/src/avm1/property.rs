@@ -44,7 +44,7 @@ impl<'gc> Property<'gc> {
         base_proto: Option<Object<'gc>>,
     ) -> Result<ReturnValue<'gc>, Error> {
         match self {
-            Property::Virtual { get, .. } => get.exec(avm, context, this, base_proto, &[]),
+            Property::Virtual { get, .. } => get.exec(avm, context, this, base_proto, &[], None),
             Property::Stored { value, .. } => Ok(value.to_owned().into()),
         }
     }
@@ -65,7 +65,7 @@ impl<'gc> Property<'gc> {
         match self {
             Property::Virtual { set, .. } => {
                 if let Some(function) = set {
-                    function.exec(avm, context, this, base_proto, &[new_value.into()])
+                    function.exec(avm, context, this, base_proto, &[new_value.into()], None)
                 } else {
                     Ok(Value::Undefined.into())
                 }
@@ -149,6 +149,51 @@ impl<'gc> ScriptObject<'gc> {
         self.0.write(gc_context).type_of = type_of;
     }
 
+    /// Returns whether this object stores its elements in a vector, as `Array`
+    /// objects do.
+    pub fn is_array(&self) -> bool {
+        match self.0.read().array {
+            ArrayStorage::Vector(_) => true,
+            ArrayStorage::Properties { .. } => false,
+        }
+    }
+
+    /// List the properties defined on this object itself, including ones that
+    /// are not enumerable, in the order they were defined.
+    ///
+    /// Virtual properties are listed without a value.
+    pub fn own_properties(&self) -> Vec<(String, Option<Value<'gc>>, EnumSet<Attribute>)> {
+        self.0
+            .read()
+            .values
+            .iter()
+            .map(|(name, property)| {
+                let value = match property {
+                    Property::Stored { value, .. } => Some(value.to_owned()),
+                    Property::Virtual { .. } => None,
+                };
+                (name.to_string(), value, property.attributes())
+            })
+            .collect()
+    }
+
+    /// Define a property listed by `own_properties`.
+    ///
+    /// Unlike `define_value`, this never replaces a property whose name only differs in case.
+    pub fn define_own_property(
+        &self,
+        gc_context: MutationContext<'gc, '_>,
+        name: &str,
+        value: Value<'gc>,
+        attributes: EnumSet<Attribute>,
+    ) {
+        self.0.write(gc_context).values.insert(
+            name.to_string(),
+            Property::Stored { value, attributes },
+            true,
+        );
+    }
+
     #[allow(clippy::trivially_copy_pass_by_ref)]
     pub fn sync_native_property(
         &self,
@@ -705,17 +750,26 @@ mod tests {
     use crate::avm1::property::Attribute::*;
     use crate::backend::audio::NullAudioBackend;
     use crate::backend::input::NullInputBackend;
-    use crate::backend::navigator::NullNavigatorBackend;
+    use crate::backend::locale::{FixedOffsetTimeZone, DEFAULT_LOCALE};
+    use crate::backend::log::NullLogBackend;
+    use crate::backend::navigator::{CancellationToken, NullNavigatorBackend};
     use crate::backend::render::NullRenderer;
+    use crate::backend::storage::MemoryStorageBackend;
+    use crate::backend::ui::NullUiBackend;
+    use crate::context_menu::ContextMenuState;
     use crate::display_object::MovieClip;
+    use crate::events::KeyboardState;
+    use crate::external::ExternalInterface;
     use crate::library::Library;
     use crate::loader::LoadManager;
     use crate::prelude::*;
     use crate::tag_utils::{SwfMovie, SwfSlice};
+    use crate::viewport::StageScaleMode;
     use gc_arena::rootless_arena;
     use rand::{rngs::SmallRng, SeedableRng};
     use std::collections::BTreeMap;
     use std::sync::Arc;
+    use std::time::Duration;
 
     fn with_object<F, R>(swf_version: u8, test: F) -> R
     where
@@ -733,6 +787,7 @@ mod tests {
             let mut context = UpdateContext {
                 gc_context,
                 global_time: 0,
+                suspended_time: Duration::default(),
                 player_version: 32,
                 swf: &swf,
                 levels: &mut levels,
@@ -740,6 +795,9 @@ mod tests {
                 action_queue: &mut crate::context::ActionQueue::new(),
                 audio: &mut NullAudioBackend::new(),
                 input: &mut NullInputBackend::new(),
+                ui: &mut NullUiBackend::new(),
+                log: &mut NullLogBackend::new(),
+                storage: &mut MemoryStorageBackend::new(),
                 background_color: &mut Color {
                     r: 0,
                     g: 0,
@@ -752,10 +810,22 @@ mod tests {
                 system_prototypes: avm.prototypes().clone(),
                 mouse_hovered_object: None,
                 mouse_position: &(Twips::new(0), Twips::new(0)),
+                keyboard: &KeyboardState::new(),
                 drag_object: &mut None,
+                focused_object: &mut None,
                 stage_size: (Twips::from_pixels(550.0), Twips::from_pixels(400.0)),
                 player: None,
                 load_manager: &mut LoadManager::new(),
+                context_menu: &mut ContextMenuState::default(),
+                external_interface: &mut ExternalInterface::default(),
+                shared_objects: &mut BTreeMap::new(),
+                sound_buffer_time: &mut 5,
+                needs_render: &mut false,
+                scale_mode: &mut StageScaleMode::default(),
+                time_zone: &FixedOffsetTimeZone::utc(),
+                locale: DEFAULT_LOCALE,
+                cancellation_token: &CancellationToken::new(),
+                spoofed_url: None,
             };
 
             root.post_instantiation(&mut avm, &mut context, root, None);
@@ -11,6 +11,7 @@ use crate::property_map::PropertyMap;
 use enumset::EnumSet;
 use gc_arena::{Collect, GcCell, MutationContext};
 use std::fmt;
+use swf::BlendMode;
 
 /// The type string for MovieClip objects.
 pub const TYPE_OF_MOVIE_CLIP: &str = "movieclip";
@@ -293,9 +294,11 @@ impl<'gc> TObject<'gc> for StageObject<'gc> {
         // Keys from the underlying object are listed first, followed by
         // child display objects in order from highest depth to lowest depth.
         let mut keys = self.base.get_keys(avm);
+        // Children without a corresponding AVM object (such as shapes) are not enumerable.
         keys.extend(
             self.display_object
                 .children()
+                .filter(|child| child.object().as_object().is_ok())
                 .map(|child| child.name().to_string()),
         );
         keys
@@ -415,6 +418,9 @@ unsafe impl<'gc> Collect for DisplayProperty<'gc> {
     }
 }
 
+/// How many display properties can be accessed by index, from `_x` to `_ymouse`.
+const NUM_INDEXED_PROPERTIES: usize = 22;
+
 /// The map from key/index to function pointers for special display object properties.
 #[derive(Collect)]
 #[collect(no_drop)]
@@ -450,6 +456,9 @@ impl<'gc> DisplayPropertyMap<'gc> {
         property_map.add_property("_xmouse", x_mouse, None);
         property_map.add_property("_ymouse", y_mouse, None);
 
+        // Properties added since SWF4 have no index.
+        property_map.add_property("blendMode", blend_mode, Some(set_blend_mode));
+
         GcCell::allocate(gc_context, property_map)
     }
 
@@ -466,7 +475,11 @@ impl<'gc> DisplayPropertyMap<'gc> {
     /// Used by `GetProperty`/`SetProperty`.
     /// SWF19 pp. 85-86
     pub fn get_by_index(&self, index: usize) -> Option<&DisplayProperty<'gc>> {
-        self.0.get_index(index)
+        if index < NUM_INDEXED_PROPERTIES {
+            self.0.get_index(index)
+        } else {
+            None
+        }
     }
 
     fn add_property(
@@ -685,9 +698,9 @@ fn set_rotation<'gc>(
     degrees: Value<'gc>,
 ) -> Result<(), Error> {
     if let Some(mut degrees) = property_coerce_to_number(avm, context, degrees)? {
-        // Normalize into the range of [-180, 180].
+        // Normalize into the range of (-180, 180].
         degrees %= 360.0;
-        if degrees < -180.0 {
+        if degrees <= -180.0 {
             degrees += 360.0
         } else if degrees > 180.0 {
             degrees -= 360.0
@@ -747,11 +760,15 @@ fn drop_target<'gc>(
 
 fn url<'gc>(
     _avm: &mut Avm1<'gc>,
-    _context: &mut UpdateContext<'_, 'gc, '_>,
-    _this: DisplayObject<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: DisplayObject<'gc>,
 ) -> Result<Value<'gc>, Error> {
-    log::warn!("Unimplemented property _url");
-    Ok("".into())
+    // `_url` is the URL of the SWF that defined this object, not necessarily the root movie.
+    Ok(this
+        .movie()
+        .and_then(|movie| context.movie_url(&movie).map(|url| url.to_string()))
+        .unwrap_or_default()
+        .into())
 }
 
 fn high_quality<'gc>(
@@ -794,20 +811,22 @@ fn set_focus_rect<'gc>(
 
 fn sound_buf_time<'gc>(
     _avm: &mut Avm1<'gc>,
-    _context: &mut UpdateContext<'_, 'gc, '_>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
     _this: DisplayObject<'gc>,
 ) -> Result<Value<'gc>, Error> {
-    log::warn!("Unimplemented property _soundbuftime");
-    Ok(5.into())
+    Ok((*context.sound_buffer_time).into())
 }
 
+/// `_soundbuftime` can be set on any clip, but it is global to the player.
 fn set_sound_buf_time<'gc>(
-    _avm: &mut Avm1<'gc>,
-    _context: &mut UpdateContext<'_, 'gc, '_>,
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
     _this: DisplayObject<'gc>,
-    _val: Value<'gc>,
+    val: Value<'gc>,
 ) -> Result<(), Error> {
-    log::warn!("Unimplemented property _soundbuftime");
+    if let Some(seconds) = property_coerce_to_number(avm, context, val)? {
+        *context.sound_buffer_time = seconds.max(0.0).min(f64::from(i32::max_value())) as i32;
+    }
     Ok(())
 }
 
@@ -848,6 +867,65 @@ fn y_mouse<'gc>(
     Ok(local.1.to_pixels().into())
 }
 
+/// The names of the blend modes, in the order of their values in `PlaceObject3` tags,
+/// starting from 1.
+const BLEND_MODES: [(BlendMode, &str); 14] = [
+    (BlendMode::Normal, "normal"),
+    (BlendMode::Layer, "layer"),
+    (BlendMode::Multiply, "multiply"),
+    (BlendMode::Screen, "screen"),
+    (BlendMode::Lighten, "lighten"),
+    (BlendMode::Darken, "darken"),
+    (BlendMode::Difference, "difference"),
+    (BlendMode::Add, "add"),
+    (BlendMode::Subtract, "subtract"),
+    (BlendMode::Invert, "invert"),
+    (BlendMode::Alpha, "alpha"),
+    (BlendMode::Erase, "erase"),
+    (BlendMode::Overlay, "overlay"),
+    (BlendMode::HardLight, "hardlight"),
+];
+
+fn blend_mode<'gc>(
+    _avm: &mut Avm1<'gc>,
+    _context: &mut UpdateContext<'_, 'gc, '_>,
+    this: DisplayObject<'gc>,
+) -> Result<Value<'gc>, Error> {
+    let blend_mode = this.blend_mode();
+    Ok(BLEND_MODES
+        .iter()
+        .find(|(mode, _)| *mode == blend_mode)
+        .map(|(_, name)| Value::from(*name))
+        .unwrap_or(Value::Undefined))
+}
+
+/// Sets the blend mode from either its name or its number.
+/// Unknown blend modes are ignored.
+fn set_blend_mode<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    mut this: DisplayObject<'gc>,
+    val: Value<'gc>,
+) -> Result<(), Error> {
+    let blend_mode = match val {
+        Value::Number(n) if (1.0..=BLEND_MODES.len() as f64).contains(&n) => {
+            Some(BLEND_MODES[n as usize - 1].0)
+        }
+        Value::Number(_) => None,
+        val => {
+            let name = val.coerce_to_string(avm, context)?;
+            BLEND_MODES
+                .iter()
+                .find(|(_, mode_name)| *mode_name == name)
+                .map(|(mode, _)| *mode)
+        }
+    };
+    if let Some(blend_mode) = blend_mode {
+        this.set_blend_mode(context.gc_context, blend_mode);
+    }
+    Ok(())
+}
+
 fn property_coerce_to_number<'gc>(
     avm: &mut Avm1<'gc>,
     context: &mut UpdateContext<'_, 'gc, '_>,
@@ -55,6 +55,16 @@ impl<'gc> SuperObject<'gc> {
         )))
     }
 
+    /// The object present as `this` throughout the superchain.
+    pub fn child(self) -> Object<'gc> {
+        self.0.read().child
+    }
+
+    /// The prototype of the currently-executing method.
+    pub fn base_proto(self) -> Object<'gc> {
+        self.0.read().base_proto
+    }
+
     /// Retrieve the prototype that `super` should be pulling from.
     fn super_proto(self) -> Option<Object<'gc>> {
         self.0.read().base_proto.proto()
@@ -2,18 +2,27 @@ use crate::avm1::activation::Activation;
 use crate::avm1::{Avm1, Object, UpdateContext};
 use crate::backend::audio::NullAudioBackend;
 use crate::backend::input::NullInputBackend;
-use crate::backend::navigator::NullNavigatorBackend;
+use crate::backend::locale::{FixedOffsetTimeZone, DEFAULT_LOCALE};
+use crate::backend::log::NullLogBackend;
+use crate::backend::navigator::{CancellationToken, NullNavigatorBackend};
 use crate::backend::render::NullRenderer;
+use crate::backend::storage::MemoryStorageBackend;
+use crate::backend::ui::NullUiBackend;
 use crate::context::ActionQueue;
+use crate::context_menu::ContextMenuState;
 use crate::display_object::{MovieClip, TDisplayObject};
+use crate::events::KeyboardState;
+use crate::external::ExternalInterface;
 use crate::library::Library;
 use crate::loader::LoadManager;
 use crate::prelude::*;
 use crate::tag_utils::{SwfMovie, SwfSlice};
+use crate::viewport::StageScaleMode;
 use gc_arena::{rootless_arena, GcCell, MutationContext};
 use rand::{rngs::SmallRng, SeedableRng};
 use std::collections::BTreeMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 pub fn with_avm<F, R>(swf_version: u8, test: F) -> R
 where
@@ -34,12 +43,16 @@ where
         let mut context = UpdateContext {
             gc_context,
             global_time: 0,
+            suspended_time: Duration::default(),
             player_version: 32,
             swf: &swf,
             levels: &mut levels,
             rng: &mut SmallRng::from_seed([0u8; 16]),
             audio: &mut NullAudioBackend::new(),
             input: &mut NullInputBackend::new(),
+            ui: &mut NullUiBackend::new(),
+            log: &mut NullLogBackend::new(),
+            storage: &mut MemoryStorageBackend::new(),
             action_queue: &mut ActionQueue::new(),
             background_color: &mut Color {
                 r: 0,
@@ -53,10 +66,22 @@ where
             system_prototypes: avm.prototypes().clone(),
             mouse_hovered_object: None,
             mouse_position: &(Twips::new(0), Twips::new(0)),
+            keyboard: &KeyboardState::new(),
             drag_object: &mut None,
+            focused_object: &mut None,
             stage_size: (Twips::from_pixels(550.0), Twips::from_pixels(400.0)),
             player: None,
             load_manager: &mut LoadManager::new(),
+            context_menu: &mut ContextMenuState::default(),
+            external_interface: &mut ExternalInterface::default(),
+            shared_objects: &mut BTreeMap::new(),
+            sound_buffer_time: &mut 5,
+            needs_render: &mut false,
+            scale_mode: &mut StageScaleMode::default(),
+            time_zone: &FixedOffsetTimeZone::utc(),
+            locale: DEFAULT_LOCALE,
+            cancellation_token: &CancellationToken::new(),
+            spoofed_url: None,
         };
         root.post_instantiation(&mut avm, &mut context, root, None);
 
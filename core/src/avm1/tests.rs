@@ -1,7 +1,15 @@
 use crate::avm1::activation::Activation;
+use crate::avm1::function::{Executable, FunctionObject};
+use crate::avm1::return_value::ReturnValue;
 use crate::avm1::test_utils::with_avm;
-use crate::avm1::TObject;
+use crate::avm1::{Avm1, Object, ScriptObject, TObject, UpdateContext, Value};
+use crate::context_menu::{ContextMenuItem, ContextMenuState};
+use crate::display_object::{DisplayObject, EditText, MovieClip, TDisplayObject};
+use crate::prelude::*;
+use crate::tag_utils::SwfSlice;
 use gc_arena::GcCell;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use swf::avm1::types::{Action, Value as SwfValue};
 
 #[test]
 fn locals_into_form_values() {
@@ -28,3 +36,688 @@ fn locals_into_form_values() {
         assert_eq!(my_local_values.get("value2"), Some(&"2".to_string()));
     });
 }
+
+#[test]
+fn enumerate_pushes_keys_before_null_sentinel() {
+    with_avm(6, |avm, context, _this| {
+        let object = ScriptObject::object(context.gc_context, None);
+        object.set("a", 1.0.into(), avm, context).unwrap();
+        object.set("b", 2.0.into(), avm, context).unwrap();
+
+        avm.enumerate_value(object.into());
+
+        // Properties are enumerated in reverse insertion order.
+        assert_eq!(avm.pop(), Value::String("b".to_string()));
+        assert_eq!(avm.pop(), Value::String("a".to_string()));
+        assert_eq!(avm.pop(), Value::Null);
+    });
+}
+
+#[test]
+fn enumerate_non_object_pushes_only_sentinel() {
+    with_avm(6, |avm, _context, _this| {
+        avm.push(Value::Undefined);
+        avm.enumerate_value(Value::Undefined);
+
+        assert_eq!(avm.pop(), Value::Null);
+        assert_eq!(avm.pop(), Value::Undefined);
+    });
+}
+
+/// Construct an instance of the given global class.
+fn construct<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    class: &str,
+    args: &[Value<'gc>],
+) -> Result<Object<'gc>, Box<dyn std::error::Error>> {
+    let constructor = avm
+        .global_object_cell()
+        .get(class, avm, context)?
+        .resolve(avm, context)?
+        .as_object()?;
+    let prototype = constructor
+        .get("prototype", avm, context)?
+        .resolve(avm, context)?
+        .as_object()?;
+    let object = prototype.new(avm, context, prototype, args)?;
+    let _ = constructor.call(avm, context, object, None, args)?;
+    Ok(object)
+}
+
+/// Records the object the menu was opened on as `selected_on` of the selected item.
+fn record_selection<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<ReturnValue<'gc>, Box<dyn std::error::Error>> {
+    let target = args.get(0).cloned().unwrap_or(Value::Undefined);
+    this.set("selected_on", target, avm, context)?;
+    Ok(Value::Undefined.into())
+}
+
+fn captions(items: &[ContextMenuItem]) -> Vec<&str> {
+    items.iter().map(|item| item.caption.as_str()).collect()
+}
+
+#[test]
+fn menu_is_resolved_from_the_object_under_the_cursor() -> Result<(), Box<dyn std::error::Error>> {
+    with_avm(
+        8,
+        |avm, context, root| -> Result<(), Box<dyn std::error::Error>> {
+            let root_clip = context.levels.get(&0).copied().unwrap();
+            let mut movie_clip = root_clip.as_movie_clip().unwrap();
+            let movie = context.swf.clone();
+
+            let mut text_field: DisplayObject<'_> =
+                EditText::new(context, movie, 0.0, 0.0, 100.0, 100.0).into();
+            movie_clip.add_child_from_avm(context, text_field, 0);
+            text_field.post_instantiation(avm, context, text_field, None);
+            let text_field_object = text_field.object().as_object()?;
+
+            let callback = FunctionObject::function(
+                context.gc_context,
+                Executable::Native(record_selection),
+                Some(avm.prototypes().function),
+                None,
+            );
+            let item = construct(
+                avm,
+                context,
+                "ContextMenuItem",
+                &["Hello".into(), callback.into()],
+            )?;
+            let menu = construct(avm, context, "ContextMenu", &[])?;
+            let _ = menu.call_method("hideBuiltInItems", &[], avm, context)?;
+            menu.get("customItems", avm, context)?
+                .resolve(avm, context)?
+                .as_object()?
+                .set_array_element(0, item.into(), context.gc_context);
+            root.set("menu", menu.into(), avm, context)?;
+
+            // The text field has no menu of its own, so the menu of its parent is used.
+            let point = (Twips::from_pixels(50.0), Twips::from_pixels(50.0));
+            let (state, items) = ContextMenuState::build(avm, context, point);
+            assert_eq!(captions(&items), vec!["Hello"]);
+
+            // Callbacks receive the object under the cursor, not the owner of the menu.
+            state.run_callback(avm, context, 0);
+            let selected_on = item
+                .get("selected_on", avm, context)?
+                .resolve(avm, context)?;
+            assert_eq!(selected_on, Value::Object(text_field_object));
+
+            // A menu on the text field itself takes precedence.
+            let text_field_menu = construct(avm, context, "ContextMenu", &[])?;
+            let _ = text_field_menu.call_method("hideBuiltInItems", &[], avm, context)?;
+            let hidden_item = construct(
+                avm,
+                context,
+                "ContextMenuItem",
+                &[
+                    "Hidden".into(),
+                    Value::Undefined,
+                    false.into(),
+                    true.into(),
+                    false.into(),
+                ],
+            )?;
+            text_field_menu
+                .get("customItems", avm, context)?
+                .resolve(avm, context)?
+                .as_object()?
+                .set_array_element(0, hidden_item.into(), context.gc_context);
+            text_field_object.set("menu", text_field_menu.into(), avm, context)?;
+
+            let (_, items) = ContextMenuState::build(avm, context, point);
+            assert!(items.is_empty());
+
+            // Outside of the text field, the root's menu is used again.
+            let point = (Twips::from_pixels(200.0), Twips::from_pixels(200.0));
+            let (_, items) = ContextMenuState::build(avm, context, point);
+            assert_eq!(captions(&items), vec!["Hello"]);
+
+            Ok(())
+        },
+    )
+}
+
+/// Replace the root clip with an empty clip of two frames, which has playback controls in its
+/// context menu.
+fn use_two_frame_root<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+) -> MovieClip<'gc> {
+    let movie_clip = MovieClip::new_with_data(
+        context.gc_context,
+        0,
+        SwfSlice::empty(context.swf.clone()),
+        2,
+    );
+    let mut root: DisplayObject<'_> = movie_clip.into();
+    root.post_instantiation(avm, context, root, None);
+    context.levels.insert(0, root);
+    movie_clip
+}
+
+#[test]
+fn built_in_items_are_shown_by_default() -> Result<(), Box<dyn std::error::Error>> {
+    with_avm(
+        8,
+        |avm, context, _root| -> Result<(), Box<dyn std::error::Error>> {
+            // A single-frame movie has no playback controls.
+            let point = (Twips::new(0), Twips::new(0));
+            let (_, items) = ContextMenuState::build(avm, context, point);
+            assert!(items.is_empty());
+
+            let root = use_two_frame_root(avm, context).object().as_object()?;
+            let menu = construct(avm, context, "ContextMenu", &[])?;
+            root.set("menu", menu.into(), avm, context)?;
+            menu.get("builtInItems", avm, context)?
+                .resolve(avm, context)?
+                .as_object()?
+                .set("rewind", false.into(), avm, context)?;
+
+            let (_, items) = ContextMenuState::build(avm, context, point);
+            assert_eq!(captions(&items), vec!["Play", "Loop", "Forward", "Back"]);
+            assert!(items[2].separator_before);
+            Ok(())
+        },
+    )
+}
+
+#[test]
+fn loop_item_toggles_looping_of_the_root() {
+    with_avm(8, |avm, context, _root| {
+        let root_clip = use_two_frame_root(avm, context);
+        let point = (Twips::new(0), Twips::new(0));
+
+        let (state, items) = ContextMenuState::build(avm, context, point);
+        assert_eq!(items[1].caption, "Loop");
+        assert!(items[1].checked);
+
+        state.run_callback(avm, context, 1);
+        assert!(!root_clip.is_looping());
+        let (_, items) = ContextMenuState::build(avm, context, point);
+        assert!(!items[1].checked);
+    });
+}
+
+/// Assemble the given actions, followed by the raw bytes in `trailer`.
+fn assemble(actions: &[Action], trailer: &[u8]) -> Vec<u8> {
+    let mut data = vec![];
+    let mut writer = swf::avm1::write::Writer::new(&mut data, 8);
+    for action in actions {
+        writer.write_action(action).unwrap();
+    }
+    data.extend_from_slice(trailer);
+    data
+}
+
+/// Run an action block on the root clip until it finishes.
+fn run_block<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    code: Vec<u8>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let root = context.levels.get(&0).copied().unwrap();
+    let code = SwfSlice::empty(context.swf.clone()).owned_subslice(code);
+    avm.insert_stack_frame_for_action(root, 8, code, context);
+    avm.run_stack_till_empty(context)?;
+
+    // Leave a frame for the test to read variables with.
+    avm.insert_stack_frame(GcCell::allocate(
+        context.gc_context,
+        Activation::from_nothing(8, avm.global_object_cell(), context.gc_context, root),
+    ));
+    Ok(())
+}
+
+fn set_variable<'a>(name: &'a str, value: SwfValue<'a>) -> Vec<Action<'a>> {
+    vec![
+        Action::Push(vec![SwfValue::Str(name), value]),
+        Action::SetVariable,
+    ]
+}
+
+#[test]
+fn out_of_range_constant_pool_index_pushes_undefined() -> Result<(), Box<dyn std::error::Error>> {
+    with_avm(
+        8,
+        |avm, context, root| -> Result<(), Box<dyn std::error::Error>> {
+            let mut actions = vec![Action::ConstantPool(vec!["in_range"])];
+            actions.extend(set_variable("first", SwfValue::ConstantPool(0)));
+            actions.extend(set_variable("second", SwfValue::ConstantPool(5)));
+            actions.extend(set_variable("after", SwfValue::Int(1)));
+            run_block(avm, context, assemble(&actions, &[]))?;
+
+            assert_eq!(
+                root.get("first", avm, context)?.resolve(avm, context)?,
+                "in_range".into()
+            );
+            assert_eq!(
+                root.get("second", avm, context)?.resolve(avm, context)?,
+                Value::Undefined
+            );
+            assert_eq!(
+                root.get("after", avm, context)?.resolve(avm, context)?,
+                1.0.into()
+            );
+            Ok(())
+        },
+    )
+}
+
+#[test]
+fn truncated_push_ends_action_block() -> Result<(), Box<dyn std::error::Error>> {
+    with_avm(
+        8,
+        |avm, context, root| -> Result<(), Box<dyn std::error::Error>> {
+            // A double needs 8 bytes, but the push only has room for 4.
+            let truncated_double = [0x96, 0x05, 0x00, 0x06, 0x00, 0x00, 0xF0, 0x3F];
+            let mut code = assemble(&set_variable("before", SwfValue::Int(1)), &truncated_double);
+            code.extend(assemble(&set_variable("after", SwfValue::Int(1)), &[]));
+            run_block(avm, context, code)?;
+
+            assert_eq!(
+                root.get("before", avm, context)?.resolve(avm, context)?,
+                1.0.into()
+            );
+            assert_eq!(
+                root.get("after", avm, context)?.resolve(avm, context)?,
+                Value::Undefined
+            );
+
+            // Later blocks are unaffected.
+            run_block(
+                avm,
+                context,
+                assemble(&set_variable("next", SwfValue::Int(1)), &[]),
+            )?;
+            assert_eq!(
+                root.get("next", avm, context)?.resolve(avm, context)?,
+                1.0.into()
+            );
+            Ok(())
+        },
+    )
+}
+
+#[test]
+fn stack_overflow_aborts_action_block() -> Result<(), Box<dyn std::error::Error>> {
+    with_avm(
+        8,
+        |avm, context, root| -> Result<(), Box<dyn std::error::Error>> {
+            avm.set_max_stack_size(16);
+
+            let mut actions = vec![Action::Push(vec![SwfValue::Null; 20])];
+            actions.extend(set_variable("after", SwfValue::Int(1)));
+            run_block(avm, context, assemble(&actions, &[]))?;
+
+            assert_eq!(
+                root.get("after", avm, context)?.resolve(avm, context)?,
+                Value::Undefined
+            );
+            assert!(avm.stack.is_empty());
+
+            // Later blocks are unaffected.
+            run_block(
+                avm,
+                context,
+                assemble(&set_variable("next", SwfValue::Int(1)), &[]),
+            )?;
+            assert_eq!(
+                root.get("next", avm, context)?.resolve(avm, context)?,
+                1.0.into()
+            );
+            Ok(())
+        },
+    )
+}
+
+#[test]
+fn deep_recursion_aborts_script() -> Result<(), Box<dyn std::error::Error>> {
+    with_avm(
+        8,
+        |avm, context, root| -> Result<(), Box<dyn std::error::Error>> {
+            avm.set_max_recursion_depth(16);
+
+            // function f() { depth++; f(); f(); }
+            // Unwinding one level at a time would call `f` exponentially many times.
+            let call_f = [
+                Action::Push(vec![SwfValue::Int(0), SwfValue::Str("f")]),
+                Action::CallFunction,
+                Action::Pop,
+            ];
+            let mut body = vec![
+                Action::Push(vec![SwfValue::Str("depth"), SwfValue::Str("depth")]),
+                Action::GetVariable,
+                Action::Increment,
+                Action::SetVariable,
+            ];
+            body.extend_from_slice(&call_f);
+            body.extend_from_slice(&call_f);
+            let body = assemble(&body, &[]);
+
+            let mut actions = set_variable("depth", SwfValue::Int(0));
+            actions.push(Action::DefineFunction {
+                name: "f",
+                params: vec![],
+                actions: &body,
+            });
+            actions.extend_from_slice(&call_f);
+            actions.extend(set_variable("after", SwfValue::Int(1)));
+            run_block(avm, context, assemble(&actions, &[]))?;
+
+            // Two of the 16 activations are the test's own and the action block's.
+            assert_eq!(
+                root.get("depth", avm, context)?.resolve(avm, context)?,
+                14.0.into()
+            );
+            assert_eq!(
+                root.get("after", avm, context)?.resolve(avm, context)?,
+                Value::Undefined
+            );
+            assert!(avm.stack.is_empty());
+
+            // Later blocks are unaffected.
+            run_block(
+                avm,
+                context,
+                assemble(&set_variable("next", SwfValue::Int(1)), &[]),
+            )?;
+            assert_eq!(
+                root.get("next", avm, context)?.resolve(avm, context)?,
+                1.0.into()
+            );
+            Ok(())
+        },
+    )
+}
+
+#[test]
+fn stack_underflow_pops_undefined() -> Result<(), Box<dyn std::error::Error>> {
+    with_avm(
+        8,
+        |avm, context, root| -> Result<(), Box<dyn std::error::Error>> {
+            // Bitwise operators need numbers, and convert the undefined operands to 0.
+            let mut actions = vec![
+                Action::Pop,
+                Action::BitOr,
+                Action::Push(vec![SwfValue::Str("result")]),
+                Action::StackSwap,
+                Action::SetVariable,
+                Action::PushDuplicate,
+                Action::Push(vec![SwfValue::Str("duplicate")]),
+                Action::StackSwap,
+                Action::SetVariable,
+            ];
+            actions.extend(set_variable("after", SwfValue::Int(1)));
+            run_block(avm, context, assemble(&actions, &[]))?;
+
+            assert_eq!(
+                root.get("result", avm, context)?.resolve(avm, context)?,
+                0.0.into()
+            );
+            assert_eq!(
+                root.get("duplicate", avm, context)?.resolve(avm, context)?,
+                Value::Undefined
+            );
+            assert_eq!(
+                root.get("after", avm, context)?.resolve(avm, context)?,
+                1.0.into()
+            );
+            assert!(avm.stack_underflows > 0);
+            Ok(())
+        },
+    )
+}
+
+#[test]
+fn jump_over_junk_bytes() -> Result<(), Box<dyn std::error::Error>> {
+    with_avm(
+        8,
+        |avm, context, root| -> Result<(), Box<dyn std::error::Error>> {
+            // The skipped byte starts a push that would swallow the following actions if
+            // the code were read from start to end.
+            let mut code = assemble(&[Action::Jump { offset: 1 }], &[0x96]);
+            code.extend(assemble(&set_variable("hidden", SwfValue::Int(1)), &[]));
+            run_block(avm, context, code)?;
+
+            assert_eq!(
+                root.get("hidden", avm, context)?.resolve(avm, context)?,
+                1.0.into()
+            );
+            Ok(())
+        },
+    )
+}
+
+#[test]
+fn jumps_outside_of_the_block_end_it() -> Result<(), Box<dyn std::error::Error>> {
+    with_avm(
+        8,
+        |avm, context, root| -> Result<(), Box<dyn std::error::Error>> {
+            for &offset in &[-100, 100, i16::MIN, i16::MAX] {
+                let jumps = vec![
+                    vec![Action::Jump { offset }],
+                    vec![
+                        Action::Push(vec![SwfValue::Bool(true)]),
+                        Action::If { offset },
+                    ],
+                ];
+                for jump in jumps {
+                    let mut actions = set_variable("after", SwfValue::Undefined);
+                    actions.extend(jump);
+                    actions.extend(set_variable("after", SwfValue::Int(1)));
+                    run_block(avm, context, assemble(&actions, &[]))?;
+
+                    assert_eq!(
+                        root.get("after", avm, context)?.resolve(avm, context)?,
+                        Value::Undefined,
+                        "jump by {}",
+                        offset
+                    );
+                }
+            }
+
+            // Later blocks are unaffected.
+            run_block(
+                avm,
+                context,
+                assemble(&set_variable("next", SwfValue::Int(1)), &[]),
+            )?;
+            assert_eq!(
+                root.get("next", avm, context)?.resolve(avm, context)?,
+                1.0.into()
+            );
+            Ok(())
+        },
+    )
+}
+
+#[test]
+fn actions_given_the_wrong_types_are_skipped() -> Result<(), Box<dyn std::error::Error>> {
+    with_avm(
+        8,
+        |avm, context, root| -> Result<(), Box<dyn std::error::Error>> {
+            let inside = assemble(&set_variable("inside", SwfValue::Int(1)), &[]);
+            let mut actions = vec![
+                // Goes nowhere.
+                Action::Push(vec![SwfValue::Null]),
+                Action::GotoFrame2 {
+                    set_playing: false,
+                    scene_offset: 0,
+                },
+                // There is no object to run the block with.
+                Action::Push(vec![SwfValue::Int(5)]),
+                Action::With { actions: &inside },
+                // Primitives have no prototype chain.
+                Action::Push(vec![
+                    SwfValue::Str("instance"),
+                    SwfValue::Int(5),
+                    SwfValue::Int(5),
+                ]),
+                Action::InstanceOf,
+                Action::SetVariable,
+                Action::Push(vec![SwfValue::Int(5), SwfValue::Int(5)]),
+                Action::Extends,
+                Action::Unknown {
+                    opcode: 0xFE,
+                    data: &[1, 2, 3],
+                },
+            ];
+            actions.extend(set_variable("after", SwfValue::Int(1)));
+            run_block(avm, context, assemble(&actions, &[]))?;
+
+            assert_eq!(
+                root.get("inside", avm, context)?.resolve(avm, context)?,
+                Value::Undefined
+            );
+            assert_eq!(
+                root.get("instance", avm, context)?.resolve(avm, context)?,
+                false.into()
+            );
+            assert_eq!(
+                root.get("after", avm, context)?.resolve(avm, context)?,
+                1.0.into()
+            );
+            Ok(())
+        },
+    )
+}
+
+/// A random action that needs no setup, for fuzzing the interpreter.
+fn random_action(rng: &mut SmallRng) -> Action<'static> {
+    const SIMPLE_ACTIONS: &[Action<'static>] = &[
+        Action::Add,
+        Action::Add2,
+        Action::And,
+        Action::AsciiToChar,
+        Action::BitAnd,
+        Action::BitLShift,
+        Action::BitOr,
+        Action::BitRShift,
+        Action::BitURShift,
+        Action::BitXor,
+        Action::Call,
+        Action::CastOp,
+        Action::CharToAscii,
+        Action::Decrement,
+        Action::Delete,
+        Action::Delete2,
+        Action::Divide,
+        Action::Enumerate2,
+        Action::Equals,
+        Action::Equals2,
+        Action::Extends,
+        Action::GetMember,
+        Action::GetProperty,
+        Action::GetVariable,
+        Action::Greater,
+        Action::ImplementsOp,
+        Action::Increment,
+        Action::InitArray,
+        Action::InitObject,
+        Action::InstanceOf,
+        Action::Less,
+        Action::Less2,
+        Action::MBAsciiToChar,
+        Action::MBCharToAscii,
+        Action::MBStringExtract,
+        Action::MBStringLength,
+        Action::Modulo,
+        Action::Multiply,
+        Action::Not,
+        Action::Or,
+        Action::Pop,
+        Action::PushDuplicate,
+        Action::SetMember,
+        Action::SetProperty,
+        Action::SetVariable,
+        Action::StackSwap,
+        Action::StrictEquals,
+        Action::StringAdd,
+        Action::StringEquals,
+        Action::StringExtract,
+        Action::StringGreater,
+        Action::StringLength,
+        Action::StringLess,
+        Action::Subtract,
+        Action::TargetPath,
+        Action::ToInteger,
+        Action::ToNumber,
+        Action::ToString,
+        Action::TypeOf,
+    ];
+    const STRINGS: &[&str] = &["", "a", "_root", "_level0", "Object", "length", "1"];
+    const BLOCK: &[u8] = &[0x96, 0x02, 0x00, 0x05, 0x01, 0x4C, 0x17, 0x00];
+
+    match rng.gen_range(0, 8) {
+        0 | 1 => {
+            let values = (0..rng.gen_range(1, 4))
+                .map(|_| match rng.gen_range(0, 7) {
+                    0 => SwfValue::Undefined,
+                    1 => SwfValue::Null,
+                    2 => SwfValue::Bool(rng.gen()),
+                    3 => SwfValue::Int(rng.gen_range(-4, 20)),
+                    4 => SwfValue::Double(rng.gen()),
+                    5 => SwfValue::Register(rng.gen_range(0, 6)),
+                    _ => SwfValue::Str(STRINGS[rng.gen_range(0, STRINGS.len())]),
+                })
+                .collect();
+            Action::Push(values)
+        }
+        2 => Action::Jump {
+            offset: rng.gen_range(-40, 40),
+        },
+        3 => Action::If {
+            offset: rng.gen_range(-40, 40),
+        },
+        4 => Action::GotoFrame2 {
+            set_playing: rng.gen(),
+            scene_offset: rng.gen_range(0, 3),
+        },
+        5 => Action::With {
+            actions: &BLOCK[..rng.gen_range(0, BLOCK.len() + 1)],
+        },
+        6 => Action::StoreRegister(rng.gen_range(0, 6)),
+        _ => SIMPLE_ACTIONS[rng.gen_range(0, SIMPLE_ACTIONS.len())].clone(),
+    }
+}
+
+#[test]
+fn random_action_blocks_do_not_panic() {
+    with_avm(8, |avm, context, _root| {
+        let mut rng = SmallRng::seed_from_u64(0x5eed);
+        let root = context.levels.get(&0).copied().unwrap();
+        for _ in 0..500 {
+            let actions: Vec<_> = (0..rng.gen_range(1, 40))
+                .map(|_| random_action(&mut rng))
+                .collect();
+            let mut code = assemble(&actions, &[]);
+            // Corrupt some blocks, to make truncated and overlapping actions.
+            if rng.gen() {
+                for _ in 0..rng.gen_range(1, 4) {
+                    let i = rng.gen_range(0, code.len());
+                    code[i] = rng.gen();
+                }
+            }
+
+            let code = SwfSlice::empty(context.swf.clone()).owned_subslice(code);
+            avm.insert_stack_frame_for_action(root, 8, code, context);
+            // Blocks may loop forever, so only run a limited number of actions.
+            for _ in 0..1000 {
+                if avm.stack_frames.is_empty() {
+                    break;
+                }
+                let _ = avm.with_current_reader_mut(context, |this, r, context| {
+                    this.do_next_action(context, r)
+                });
+            }
+            avm.stack_frames.clear();
+            avm.stack.clear();
+        }
+    });
+}
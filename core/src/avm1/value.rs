@@ -328,10 +328,10 @@ impl<'gc> Value<'gc> {
             (Value::Bool(a), Value::Bool(b)) => Ok((a == b).into()),
             (Value::Object(a), Value::Object(b)) => Ok(Object::ptr_eq(*a, *b).into()),
             (Value::Object(a), Value::Null) | (Value::Object(a), Value::Undefined) => {
-                Ok(Object::ptr_eq(*a, avm.global_object_cell()).into())
+                Ok(avm.is_global_object(*a).into())
             }
             (Value::Null, Value::Object(b)) | (Value::Undefined, Value::Object(b)) => {
-                Ok(Object::ptr_eq(*b, avm.global_object_cell()).into())
+                Ok(avm.is_global_object(*b).into())
             }
             (Value::Undefined, Value::Null) => Ok(true.into()),
             (Value::Null, Value::Undefined) => Ok(true.into()),
@@ -594,28 +594,58 @@ impl<'gc> Value<'gc> {
 
 /// Converts an `f64` to a String with (hopefully) the same output as Flash.
 /// For example, NAN returns `"NaN"`, and infinity returns `"Infinity"`.
+///
+/// Flash prints at most 15 significant digits, so `Math.PI` is printed as `3.14159265358979`.
 pub fn f64_to_string(n: f64) -> String {
     if n.is_nan() {
-        "NaN".to_string()
+        return "NaN".to_string();
     } else if n == std::f64::INFINITY {
-        "Infinity".to_string()
+        return "Infinity".to_string();
     } else if n == std::f64::NEG_INFINITY {
-        "-Infinity".to_string()
-    } else if n != 0.0 && (n.abs() >= 1e15 || n.abs() < 1e-5) {
-        // Exponential notation.
-        // Cheating a bit here; Flash always put a sign in front of the exponent, e.g. 1e+15.
-        // Can't do this with rust format params, so shove it in there manually.
-        let mut s = format!("{:e}", n);
-        if let Some(i) = s.find('e') {
-            if s.as_bytes().get(i + 1) != Some(&b'-') {
-                s.insert(i + 1, '+');
-            }
+        return "-Infinity".to_string();
+    } else if n == 0.0 {
+        // Negative zero is printed without a sign.
+        return "0".to_string();
+    }
+
+    // Round to 15 significant digits, e.g. "-3.14159265358979e0".
+    let rounded = format!("{:.14e}", n.abs());
+    let e = rounded.find('e').unwrap_or_else(|| rounded.len());
+    let exponent: i32 = rounded[e + 1..].parse().unwrap_or(0);
+    let digits: String = rounded[..e].chars().filter(|c| *c != '.').collect();
+    let digits = digits.trim_end_matches('0');
+
+    let mut s = String::with_capacity(24);
+    if n < 0.0 {
+        s.push('-');
+    }
+    if exponent >= 15 || exponent < -5 {
+        // Exponential notation. Flash always puts a sign in front of the exponent, e.g. 1e+15.
+        s.push_str(&digits[..1]);
+        if digits.len() > 1 {
+            s.push('.');
+            s.push_str(&digits[1..]);
         }
-        s
-    } else {
+        s.push_str(if exponent < 0 { "e-" } else { "e+" });
+        s.push_str(&exponent.abs().to_string());
+    } else if exponent >= 0 {
         // Normal number.
-        n.to_string()
+        let int_len = exponent as usize + 1;
+        if digits.len() <= int_len {
+            s.push_str(digits);
+            s.extend(std::iter::repeat('0').take(int_len - digits.len()));
+        } else {
+            s.push_str(&digits[..int_len]);
+            s.push('.');
+            s.push_str(&digits[int_len..]);
+        }
+    } else {
+        // Small number with leading zeros.
+        s.push_str("0.");
+        s.extend(std::iter::repeat('0').take((-exponent - 1) as usize));
+        s.push_str(digits);
     }
+    s
 }
 
 /// Converts an `f64` to an `u16` with ECMAScript `ToUInt16` wrapping behavior.
@@ -929,5 +959,11 @@ mod test {
         assert_eq!(f64_to_string(-1e-5), "-0.00001");
         assert_eq!(f64_to_string(0.999e-5), "9.99e-6");
         assert_eq!(f64_to_string(-0.999e-5), "-9.99e-6");
+        assert_eq!(f64_to_string(std::f64::consts::PI), "3.14159265358979");
+        assert_eq!(f64_to_string(std::f64::consts::SQRT_2), "1.4142135623731");
+        assert_eq!(f64_to_string(0.1 + 0.2), "0.3");
+        assert_eq!(f64_to_string(999_999_999_999_999.9), "1e+15");
+        assert_eq!(f64_to_string(1.5e300), "1.5e+300");
+        assert_eq!(f64_to_string(123_456_789.0), "123456789");
     }
 }
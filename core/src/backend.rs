@@ -1,4 +1,8 @@
 pub mod audio;
 pub mod input;
+pub mod locale;
+pub mod log;
 pub mod navigator;
 pub mod render;
+pub mod storage;
+pub mod ui;
@@ -1,4 +1,6 @@
 use generational_arena::{Arena, Index};
+use std::cell::RefCell;
+use std::rc::Rc;
 
 pub mod decoders;
 pub mod swf {
@@ -54,6 +56,19 @@ pub trait AudioBackend {
     /// Should be called whenever a MovieClip timeline stops playing or seeks to a new frame.
     fn stop_stream(&mut self, stream: AudioStreamHandle);
 
+    /// Returns how many milliseconds of a stream's audio are ready to play, ahead of its
+    /// current playback position.
+    ///
+    /// Backends that always have the rest of the stream at hand return infinity, which is the
+    /// default.
+    fn stream_buffered_duration(&self, _stream: AudioStreamHandle) -> f64 {
+        std::f64::INFINITY
+    }
+
+    /// Pauses or resumes a stream while its timeline waits for more audio to be buffered.
+    /// A buffering stream is silent, and its playback position doesn't advance.
+    fn set_stream_buffering(&mut self, _stream: AudioStreamHandle, _is_buffering: bool) {}
+
     /// Good ol' stopAllSounds() :-)
     fn stop_all_sounds(&mut self);
 
@@ -74,7 +89,10 @@ pub trait AudioBackend {
     fn is_loading_complete(&self) -> bool {
         true
     }
-    fn tick(&mut self) {}
+
+    /// Called once per `Player::tick`, before any frames run, with the time elapsed since the
+    /// last tick in milliseconds.
+    fn tick(&mut self, _dt: f64) {}
 
     /// Inform the audio backend of the current stage frame rate.
     ///
@@ -82,59 +100,334 @@ pub trait AudioBackend {
     /// what the stage frame rate is. Otherwise, you are free to avoid
     /// implementing it.
     fn set_frame_rate(&mut self, _frame_rate: f64) {}
+
+    /// Sets the gain applied to the final audio mix, ranging from 0.0 (silent) to 1.0.
+    ///
+    /// This is the host-controlled master volume, and must be applied independently of
+    /// any volume or transform set by the SWF.
+    fn set_global_volume(&mut self, _volume: f32) {}
+}
+
+/// A call made to a `NullAudioBackend`, as recorded in its call log.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AudioCall {
+    StartSound {
+        sound: SoundHandle,
+        instance: SoundInstanceHandle,
+    },
+    StartStream {
+        clip_id: swf::CharacterId,
+        clip_frame: u16,
+        stream: AudioStreamHandle,
+    },
+    StopSound(SoundInstanceHandle),
+    StopStream(AudioStreamHandle),
+    SetStreamBuffering {
+        stream: AudioStreamHandle,
+        is_buffering: bool,
+    },
+    StopAllSounds,
+    StopSoundsWithHandle(SoundHandle),
+    SetFrameRate(f64),
+    SetGlobalVolume(f32),
+}
+
+/// The playback position of a sound stream in a `NullAudioBackend`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NullStreamPosition {
+    pub stream: AudioStreamHandle,
+    pub clip_id: swf::CharacterId,
+
+    /// The frame of the clip that the stream started from.
+    pub clip_frame: u16,
+
+    /// How much of the stream has played, in milliseconds.
+    pub position: f64,
+
+    /// How much of the stream is ready to play after `position`, in milliseconds.
+    pub buffered: f64,
+}
+
+/// The playback position of an event sound in a `NullAudioBackend`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NullSoundPosition {
+    pub instance: SoundInstanceHandle,
+    pub sound: SoundHandle,
+
+    /// How much of the sound has played, in milliseconds.
+    pub position: f64,
 }
 
 /// Audio backend that ignores all audio.
+///
+/// Sounds are not decoded or played, but the backend still keeps track of where each sound
+/// would be on a virtual clock, which only advances when the player ticks. Tests can take a
+/// `NullAudioMonitor` from the backend before handing it to the player, to read the log of
+/// calls made to the backend and the positions of playing sounds, or to simulate underruns,
+/// output latency and slowly arriving stream data.
 pub struct NullAudioBackend {
-    sounds: Arena<()>,
-    streams: Arena<()>,
+    state: Rc<RefCell<NullAudioState>>,
+}
+
+/// A handle to the state of a `NullAudioBackend`, which stays usable after the backend has been
+/// given to a player.
+#[derive(Clone)]
+pub struct NullAudioMonitor {
+    state: Rc<RefCell<NullAudioState>>,
+}
+
+struct NullAudioState {
+    sounds: Arena<NullSound>,
+    sound_instances: Arena<NullSoundInstance>,
+    streams: Arena<NullStream>,
+    calls: Vec<(f64, AudioCall)>,
+
+    /// The virtual time, in milliseconds since the backend was created.
+    time: f64,
+
+    /// How late newly started sounds begin to play, in milliseconds.
+    latency: f64,
+
+    /// How much longer playback is stalled for, in milliseconds.
+    underrun: f64,
+
+    /// How many milliseconds of stream audio arrive per millisecond, or `None` if streams are
+    /// always fully available.
+    stream_data_rate: Option<f64>,
+
+    global_volume: f32,
+}
+
+struct NullSound {
+    num_samples: u32,
+    sample_rate: u16,
+}
+
+struct NullSoundInstance {
+    sound: SoundHandle,
+
+    /// The playback position in milliseconds, which is negative while waiting out the latency.
+    position: f64,
+
+    /// The total play time including loops, in milliseconds.
+    length: f64,
+}
+
+struct NullStream {
+    clip_id: swf::CharacterId,
+    clip_frame: u16,
+    position: f64,
+
+    /// The audio that has arrived but not played yet, in milliseconds.
+    buffered: f64,
+    is_buffering: bool,
 }
 
 impl NullAudioBackend {
     pub fn new() -> NullAudioBackend {
         NullAudioBackend {
-            streams: Arena::new(),
-            sounds: Arena::new(),
+            state: Rc::new(RefCell::new(NullAudioState {
+                sounds: Arena::new(),
+                sound_instances: Arena::new(),
+                streams: Arena::new(),
+                calls: vec![],
+                time: 0.0,
+                latency: 0.0,
+                underrun: 0.0,
+                stream_data_rate: None,
+                global_volume: 1.0,
+            })),
+        }
+    }
+
+    /// Get a monitor that can inspect and control this backend.
+    pub fn monitor(&self) -> NullAudioMonitor {
+        NullAudioMonitor {
+            state: self.state.clone(),
         }
     }
+
+    fn log(&mut self, call: AudioCall) {
+        let mut state = self.state.borrow_mut();
+        let time = state.time;
+        state.calls.push((time, call));
+    }
 }
 
 impl AudioBackend for NullAudioBackend {
-    fn register_sound(&mut self, _sound: &swf::Sound) -> Result<SoundHandle, Error> {
-        Ok(self.sounds.insert(()))
+    fn register_sound(&mut self, sound: &swf::Sound) -> Result<SoundHandle, Error> {
+        Ok(self.state.borrow_mut().sounds.insert(NullSound {
+            num_samples: sound.num_samples,
+            sample_rate: sound.format.sample_rate,
+        }))
     }
 
     fn start_sound(
         &mut self,
-        _sound: SoundHandle,
-        _sound_info: &swf::SoundInfo,
+        sound: SoundHandle,
+        sound_info: &swf::SoundInfo,
     ) -> SoundInstanceHandle {
-        SoundInstanceHandle::from_raw_parts(0, 0)
+        let instance = {
+            let mut state = self.state.borrow_mut();
+            let length = state.sounds.get(sound).map_or(0.0, |data| {
+                let start = sound_info.in_sample.unwrap_or(0);
+                let end = sound_info.out_sample.unwrap_or(data.num_samples);
+                let num_loops = sound_info.num_loops.max(1);
+                samples_to_millis(end.saturating_sub(start), data.sample_rate)
+                    * f64::from(num_loops)
+            });
+            let position = -state.latency;
+            state.sound_instances.insert(NullSoundInstance {
+                sound,
+                position,
+                length,
+            })
+        };
+        self.log(AudioCall::StartSound { sound, instance });
+        instance
     }
 
     fn start_stream(
         &mut self,
-        _clip_id: crate::prelude::CharacterId,
-        _stream_start_frame: u16,
+        clip_id: crate::prelude::CharacterId,
+        clip_frame: u16,
         _clip_data: crate::tag_utils::SwfSlice,
         _handle: &swf::SoundStreamHead,
     ) -> AudioStreamHandle {
-        self.streams.insert(())
+        let stream = {
+            let mut state = self.state.borrow_mut();
+            let position = -state.latency;
+            state.streams.insert(NullStream {
+                clip_id,
+                clip_frame,
+                position,
+                buffered: 0.0,
+                is_buffering: false,
+            })
+        };
+        self.log(AudioCall::StartStream {
+            clip_id,
+            clip_frame,
+            stream,
+        });
+        stream
     }
 
-    fn stop_sound(&mut self, _sound: SoundInstanceHandle) {}
+    fn stop_sound(&mut self, sound: SoundInstanceHandle) {
+        self.state.borrow_mut().sound_instances.remove(sound);
+        self.log(AudioCall::StopSound(sound));
+    }
 
     fn stop_stream(&mut self, stream: AudioStreamHandle) {
-        self.streams.remove(stream);
+        self.state.borrow_mut().streams.remove(stream);
+        self.log(AudioCall::StopStream(stream));
     }
-    fn stop_all_sounds(&mut self) {}
-    fn stop_sounds_with_handle(&mut self, _handle: SoundHandle) {}
-    fn is_sound_playing_with_handle(&mut self, _handle: SoundHandle) -> bool {
-        false
+
+    fn stream_buffered_duration(&self, stream: AudioStreamHandle) -> f64 {
+        let state = self.state.borrow();
+        match (state.stream_data_rate, state.streams.get(stream)) {
+            (Some(_), Some(stream)) => stream.buffered,
+            _ => std::f64::INFINITY,
+        }
     }
 
-    fn get_sound_duration(&self, _sound: SoundHandle) -> Option<u32> {
-        None
+    fn set_stream_buffering(&mut self, stream: AudioStreamHandle, is_buffering: bool) {
+        if let Some(data) = self.state.borrow_mut().streams.get_mut(stream) {
+            data.is_buffering = is_buffering;
+        }
+        self.log(AudioCall::SetStreamBuffering {
+            stream,
+            is_buffering,
+        });
+    }
+
+    fn stop_all_sounds(&mut self) {
+        {
+            let mut state = self.state.borrow_mut();
+            state.sound_instances.clear();
+            state.streams.clear();
+        }
+        self.log(AudioCall::StopAllSounds);
+    }
+
+    fn stop_sounds_with_handle(&mut self, handle: SoundHandle) {
+        {
+            let mut state = self.state.borrow_mut();
+            let instances: Vec<SoundInstanceHandle> = state
+                .sound_instances
+                .iter()
+                .filter(|(_, instance)| instance.sound == handle)
+                .map(|(index, _)| index)
+                .collect();
+            for instance in instances {
+                state.sound_instances.remove(instance);
+            }
+        }
+        self.log(AudioCall::StopSoundsWithHandle(handle));
+    }
+
+    fn is_sound_playing_with_handle(&mut self, handle: SoundHandle) -> bool {
+        self.state
+            .borrow()
+            .sound_instances
+            .iter()
+            .any(|(_, instance)| instance.sound == handle)
+    }
+
+    fn get_sound_duration(&self, sound: SoundHandle) -> Option<u32> {
+        self.state
+            .borrow()
+            .sounds
+            .get(sound)
+            .map(|sound| samples_to_millis(sound.num_samples, sound.sample_rate).round() as u32)
+    }
+
+    fn tick(&mut self, dt: f64) {
+        let mut state = self.state.borrow_mut();
+        state.time += dt;
+
+        // Playback doesn't advance while an underrun lasts.
+        let stalled = dt.min(state.underrun);
+        state.underrun -= stalled;
+        let dt = dt - stalled;
+
+        for (_, instance) in state.sound_instances.iter_mut() {
+            instance.position += dt;
+        }
+        let stream_data_rate = state.stream_data_rate;
+        for (_, stream) in state.streams.iter_mut() {
+            if let Some(rate) = stream_data_rate {
+                // Stream audio keeps arriving while the device is stalled, and a stream can't
+                // play past the audio that has arrived.
+                stream.buffered += (dt + stalled) * rate;
+                if !stream.is_buffering {
+                    let played = dt.min(stream.buffered);
+                    stream.position += played;
+                    stream.buffered -= played;
+                }
+            } else if !stream.is_buffering {
+                stream.position += dt;
+            }
+        }
+
+        let finished: Vec<SoundInstanceHandle> = state
+            .sound_instances
+            .iter()
+            .filter(|(_, instance)| instance.position >= instance.length)
+            .map(|(index, _)| index)
+            .collect();
+        for instance in finished {
+            state.sound_instances.remove(instance);
+        }
+    }
+
+    fn set_frame_rate(&mut self, frame_rate: f64) {
+        self.log(AudioCall::SetFrameRate(frame_rate));
+    }
+
+    fn set_global_volume(&mut self, volume: f32) {
+        self.state.borrow_mut().global_volume = volume;
+        self.log(AudioCall::SetGlobalVolume(volume));
     }
 }
 
@@ -143,3 +436,88 @@ impl Default for NullAudioBackend {
         NullAudioBackend::new()
     }
 }
+
+impl NullAudioMonitor {
+    /// The virtual time, in milliseconds since the backend was created.
+    pub fn time(&self) -> f64 {
+        self.state.borrow().time
+    }
+
+    /// Every call made to the backend so far, with the virtual time it was made at.
+    pub fn calls(&self) -> Vec<(f64, AudioCall)> {
+        self.state.borrow().calls.clone()
+    }
+
+    /// Remove and return the calls made to the backend since the last time this was called.
+    pub fn take_calls(&self) -> Vec<(f64, AudioCall)> {
+        std::mem::take(&mut self.state.borrow_mut().calls)
+    }
+
+    /// The streams that are currently playing, sorted by handle.
+    pub fn stream_positions(&self) -> Vec<NullStreamPosition> {
+        let mut streams: Vec<NullStreamPosition> = self
+            .state
+            .borrow()
+            .streams
+            .iter()
+            .map(|(stream, data)| NullStreamPosition {
+                stream,
+                clip_id: data.clip_id,
+                clip_frame: data.clip_frame,
+                position: data.position.max(0.0),
+                buffered: data.buffered,
+            })
+            .collect();
+        streams.sort_by_key(|stream| stream.stream.into_raw_parts());
+        streams
+    }
+
+    /// The event sounds that are currently playing, sorted by handle.
+    pub fn sound_positions(&self) -> Vec<NullSoundPosition> {
+        let mut sounds: Vec<NullSoundPosition> = self
+            .state
+            .borrow()
+            .sound_instances
+            .iter()
+            .map(|(instance, data)| NullSoundPosition {
+                instance,
+                sound: data.sound,
+                position: data.position.max(0.0),
+            })
+            .collect();
+        sounds.sort_by_key(|sound| sound.instance.into_raw_parts());
+        sounds
+    }
+
+    /// The volume of the final mix, as last set by the player.
+    pub fn global_volume(&self) -> f32 {
+        self.state.borrow().global_volume
+    }
+
+    /// Stall playback for the given number of milliseconds of virtual time, as if the audio
+    /// device ran out of data.
+    pub fn inject_underrun(&self, duration: f64) {
+        self.state.borrow_mut().underrun += duration;
+    }
+
+    /// Delay sounds started from now on by the given number of milliseconds, as if the audio
+    /// device had that much output latency.
+    pub fn set_latency(&self, latency: f64) {
+        self.state.borrow_mut().latency = latency;
+    }
+
+    /// Make stream audio arrive at the given rate, in milliseconds of audio per millisecond, as
+    /// if the movie was still downloading. `None` makes streams fully available again.
+    pub fn set_stream_data_rate(&self, rate: Option<f64>) {
+        self.state.borrow_mut().stream_data_rate = rate;
+    }
+}
+
+/// Convert a number of samples to milliseconds.
+fn samples_to_millis(num_samples: u32, sample_rate: u16) -> f64 {
+    if sample_rate > 0 {
+        f64::from(num_samples) * 1000.0 / f64::from(sample_rate)
+    } else {
+        0.0
+    }
+}
@@ -4,16 +4,14 @@ use downcast_rs::Downcast;
 pub trait InputBackend: Downcast {
     fn is_key_down(&self, key: KeyCode) -> bool;
 
+    /// Returns the Flash key code of the last key pressed.
+    ///
+    /// Backends resolve this with `events::key_code_for_key`, so that letter keys report the
+    /// letter they type under the current keyboard layout.
     fn get_last_key_code(&self) -> KeyCode;
 
-    fn mouse_visible(&self) -> bool;
-
-    fn hide_mouse(&mut self);
-
-    fn show_mouse(&mut self);
-
-    /// Changes the mouse cursor image.
-    fn set_mouse_cursor(&mut self, cursor: MouseCursor);
+    /// Returns the character typed by the last key pressed, if any.
+    fn get_last_key_char(&self) -> Option<char>;
 }
 impl_downcast!(InputBackend);
 
@@ -35,15 +33,9 @@ impl InputBackend for NullInputBackend {
         KeyCode::Unknown
     }
 
-    fn mouse_visible(&self) -> bool {
-        true
+    fn get_last_key_char(&self) -> Option<char> {
+        None
     }
-
-    fn hide_mouse(&mut self) {}
-
-    fn show_mouse(&mut self) {}
-
-    fn set_mouse_cursor(&mut self, _cursor: MouseCursor) {}
 }
 
 impl Default for NullInputBackend {
@@ -51,24 +43,3 @@ impl Default for NullInputBackend {
         NullInputBackend::new()
     }
 }
-
-/// A mouse cursor icon displayed by the Flash Player.
-/// Communicated from the core to the input backend via `InputBackend::set_mouse_cursor`.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum MouseCursor {
-    /// The default arrow icon.
-    /// Equivalent to AS3 `MouseCursor.ARROW`.
-    Arrow,
-
-    /// The hand icon incdicating a button or link.
-    /// Equivalent to AS3 `MouseCursor.BUTTON`.
-    Hand,
-
-    /// The text I-beam.
-    /// Equivalent to AS3 `MouseCursor.IBEAM`.
-    IBeam,
-
-    /// The grabby-dragging hand icon.
-    /// Equivalent to AS3 `MouseCursor.HAND`.
-    Grab,
-}
@@ -0,0 +1,407 @@
+//! Time zone and locale of the player's host.
+//!
+//! Local times shown to movies depend on the host's time zone, which hosts provide through a
+//! `TimeZoneProvider`. The player defaults to UTC, so that movies behave the same on every
+//! machine unless the host opts in to its own zone.
+
+/// The number of milliseconds in a day.
+const MS_PER_DAY: f64 = 86_400_000.0;
+
+/// The number of milliseconds in a minute.
+const MS_PER_MINUTE: f64 = 60_000.0;
+
+/// The locale used by players whose host doesn't set one.
+pub const DEFAULT_LOCALE: &str = "en-US";
+
+/// Tells how far local time is ahead of UTC at a given moment.
+pub trait TimeZoneProvider {
+    /// The offset of local time from UTC in minutes, at the given UTC time in milliseconds
+    /// since the Unix epoch. Zones east of Greenwich have positive offsets.
+    ///
+    /// This includes daylight saving time, if it is in effect at that time.
+    fn utc_offset(&self, utc_time: f64) -> i32;
+
+    /// Converts a local time to UTC, both in milliseconds since the Unix epoch.
+    ///
+    /// Local times that are skipped or repeated by a daylight saving transition are resolved
+    /// using the offset from just before the transition.
+    fn local_to_utc(&self, local_time: f64) -> f64 {
+        let guess = local_time - f64::from(self.utc_offset(local_time)) * MS_PER_MINUTE;
+        local_time - f64::from(self.utc_offset(guess)) * MS_PER_MINUTE
+    }
+
+    /// Converts a UTC time to local time, both in milliseconds since the Unix epoch.
+    fn utc_to_local(&self, utc_time: f64) -> f64 {
+        utc_time + f64::from(self.utc_offset(utc_time)) * MS_PER_MINUTE
+    }
+}
+
+/// A time zone that is always the same number of minutes ahead of UTC.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FixedOffsetTimeZone(pub i32);
+
+impl FixedOffsetTimeZone {
+    pub fn utc() -> Self {
+        FixedOffsetTimeZone(0)
+    }
+}
+
+impl TimeZoneProvider for FixedOffsetTimeZone {
+    fn utc_offset(&self, _utc_time: f64) -> i32 {
+        self.0
+    }
+}
+
+/// The moment in a year that daylight saving time starts or ends, in the form used by POSIX
+/// `TZ` rules such as `M3.2.0/2`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DstTransition {
+    /// The month, from 1 for January to 12 for December.
+    pub month: u32,
+
+    /// Which occurrence of the weekday in the month, from 1 to 4, or 5 for the last one.
+    pub week: u32,
+
+    /// The day of the week, from 0 for Sunday to 6 for Saturday.
+    pub weekday: u32,
+
+    /// The minutes after midnight, in the local time in effect before the transition.
+    pub time: u32,
+}
+
+impl DstTransition {
+    /// The local date of this transition in the given year, in days since the Unix epoch.
+    fn day(self, year: i32) -> i64 {
+        let first_of_month = days_from_civil(year, self.month - 1, 1);
+        let first_weekday = weekday(first_of_month);
+        let mut day =
+            (i64::from(self.weekday) - first_weekday).rem_euclid(7) + i64::from(self.week - 1) * 7;
+        let days_in_month = days_from_civil(year, self.month, 1) - first_of_month;
+        while day >= days_in_month {
+            day -= 7;
+        }
+        first_of_month + day
+    }
+
+    /// The UTC time of this transition in the given year, for a zone that is `offset` minutes
+    /// ahead of UTC before the transition.
+    fn utc_time(self, year: i32, offset: i32) -> f64 {
+        self.day(year) as f64 * MS_PER_DAY + f64::from(self.time) * MS_PER_MINUTE
+            - f64::from(offset) * MS_PER_MINUTE
+    }
+}
+
+/// The yearly rule for daylight saving time in a time zone.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DstRule {
+    /// When daylight saving time starts.
+    pub start: DstTransition,
+
+    /// When daylight saving time ends.
+    pub end: DstTransition,
+
+    /// The minutes that clocks are moved forward while daylight saving time is in effect.
+    pub saving: i32,
+}
+
+/// A time zone with a standard offset from UTC, and daylight saving rules that may have
+/// changed over the years.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleBasedTimeZone {
+    /// The offset of standard time from UTC in minutes.
+    standard_offset: i32,
+
+    /// The daylight saving rule in effect from each year on, sorted by year. `None` means the
+    /// zone observed no daylight saving time.
+    rules: Vec<(i32, Option<DstRule>)>,
+}
+
+impl RuleBasedTimeZone {
+    /// Creates a zone that keeps standard time all year.
+    pub fn new(standard_offset: i32) -> Self {
+        Self {
+            standard_offset,
+            rules: vec![],
+        }
+    }
+
+    /// Uses the given daylight saving rule from the start of `year` until the year of the next
+    /// rule, or `None` for no daylight saving time.
+    pub fn with_rule(mut self, year: i32, rule: Option<DstRule>) -> Self {
+        self.rules.retain(|&(from_year, _)| from_year != year);
+        self.rules.push((year, rule));
+        self.rules.sort_by_key(|&(from_year, _)| from_year);
+        self
+    }
+
+    /// The daylight saving rule in effect in the given year.
+    fn rule(&self, year: i32) -> Option<DstRule> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|&&(from_year, _)| from_year <= year)
+            .and_then(|&(_, rule)| rule)
+    }
+}
+
+impl TimeZoneProvider for RuleBasedTimeZone {
+    fn utc_offset(&self, utc_time: f64) -> i32 {
+        if !utc_time.is_finite() {
+            return self.standard_offset;
+        }
+
+        let standard_time = utc_time + f64::from(self.standard_offset) * MS_PER_MINUTE;
+        let (year, _, _) = civil_from_days((standard_time / MS_PER_DAY).floor() as i64);
+        let rule = match self.rule(year) {
+            Some(rule) => rule,
+            None => return self.standard_offset,
+        };
+
+        let start = rule.start.utc_time(year, self.standard_offset);
+        let end = rule.end.utc_time(year, self.standard_offset + rule.saving);
+        let is_dst = if start < end {
+            start <= utc_time && utc_time < end
+        } else {
+            // In the southern hemisphere, daylight saving time spans the new year.
+            utc_time < end || start <= utc_time
+        };
+
+        if is_dst {
+            self.standard_offset + rule.saving
+        } else {
+            self.standard_offset
+        }
+    }
+}
+
+/// Formats a UTC time as local time in the pattern of Flash Player's `Date.toString`, such as
+/// `Mon Jan 5 10:00:00 GMT+0100 2004`.
+pub fn format_date(utc_time: f64, time_zone: &dyn TimeZoneProvider) -> String {
+    const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    if !utc_time.is_finite() {
+        return "Invalid Date".to_string();
+    }
+
+    let offset = time_zone.utc_offset(utc_time);
+    let local_time = utc_time + f64::from(offset) * MS_PER_MINUTE;
+    let days = (local_time / MS_PER_DAY).floor() as i64;
+    let (year, month, day) = civil_from_days(days);
+    let seconds = (local_time.rem_euclid(MS_PER_DAY) / 1000.0) as u32;
+
+    format!(
+        "{} {} {} {:02}:{:02}:{:02} GMT{}{:02}{:02} {}",
+        WEEKDAYS[weekday(days) as usize],
+        MONTHS[month as usize],
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / 60,
+        offset.abs() % 60,
+        year
+    )
+}
+
+/// The number of days since the Unix epoch of a date in the proleptic Gregorian calendar,
+/// with months counted from 0.
+///
+/// Months outside of a year carry over into the neighboring years.
+pub fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    let year = i64::from(year) + i64::from(month / 12);
+    let month = i64::from(month % 12);
+    // Count years from March, so that leap days come last.
+    let year = if month < 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 10) % 12) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The date in the proleptic Gregorian calendar of a number of days since the Unix epoch, as
+/// the year, the month counted from 0, and the day of the month counted from 1.
+pub fn civil_from_days(days: i64) -> (i32, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let march_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * march_month + 2) / 5 + 1;
+    let month = (march_month + 2) % 12;
+    let year = year_of_era + era * 400 + if month < 2 { 1 } else { 0 };
+    (year as i32, month as u32, day as u32)
+}
+
+/// The day of the week of a number of days since the Unix epoch, from 0 for Sunday.
+pub fn weekday(days: i64) -> i64 {
+    // 1970-01-01 was a Thursday.
+    (days + 4).rem_euclid(7)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS_PER_HOUR: f64 = 3_600_000.0;
+
+    /// US Eastern time, which moved its daylight saving dates in 2007.
+    fn eastern() -> RuleBasedTimeZone {
+        RuleBasedTimeZone::new(-300)
+            .with_rule(
+                2007,
+                Some(DstRule {
+                    start: DstTransition {
+                        month: 3,
+                        week: 2,
+                        weekday: 0,
+                        time: 120,
+                    },
+                    end: DstTransition {
+                        month: 11,
+                        week: 1,
+                        weekday: 0,
+                        time: 120,
+                    },
+                    saving: 60,
+                }),
+            )
+            .with_rule(
+                1987,
+                Some(DstRule {
+                    start: DstTransition {
+                        month: 4,
+                        week: 1,
+                        weekday: 0,
+                        time: 120,
+                    },
+                    end: DstTransition {
+                        month: 10,
+                        week: 5,
+                        weekday: 0,
+                        time: 120,
+                    },
+                    saving: 60,
+                }),
+            )
+    }
+
+    /// Milliseconds since the Unix epoch of a UTC date and hour, with months counted from 1.
+    fn utc(year: i32, month: u32, day: u32, hour: u32) -> f64 {
+        days_from_civil(year, month - 1, day) as f64 * MS_PER_DAY + f64::from(hour) * MS_PER_HOUR
+    }
+
+    #[test]
+    fn civil_dates_round_trip() {
+        assert_eq!(days_from_civil(1970, 0, 1), 0);
+        assert_eq!(days_from_civil(2000, 1, 29), 11_016);
+        assert_eq!(days_from_civil(1969, 11, 31), -1);
+        assert_eq!(days_from_civil(2003, 12, 1), days_from_civil(2004, 0, 1));
+        for &days in &[-719_468, -1, 0, 11_016, 12_422, 2_932_896] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+        assert_eq!(civil_from_days(12_422), (2004, 0, 5));
+        assert_eq!(weekday(12_422), 1);
+    }
+
+    #[test]
+    fn dst_starts_on_the_rule_of_its_year() {
+        let zone = eastern();
+
+        // In 2004, clocks went forward at 2am on April 4th, 7am UTC.
+        assert_eq!(zone.utc_offset(utc(2004, 4, 4, 7) - 1.0), -300);
+        assert_eq!(zone.utc_offset(utc(2004, 4, 4, 7)), -240);
+
+        // In 2010, they went forward on March 14th instead.
+        assert_eq!(zone.utc_offset(utc(2010, 3, 14, 7) - 1.0), -300);
+        assert_eq!(zone.utc_offset(utc(2010, 3, 14, 7)), -240);
+        assert_eq!(zone.utc_offset(utc(2010, 4, 1, 12)), -240);
+    }
+
+    #[test]
+    fn dst_ends_on_the_rule_of_its_year() {
+        let zone = eastern();
+
+        // In 2004, clocks went back at 2am daylight time on October 31st, 6am UTC.
+        assert_eq!(zone.utc_offset(utc(2004, 10, 31, 6) - 1.0), -240);
+        assert_eq!(zone.utc_offset(utc(2004, 10, 31, 6)), -300);
+
+        // In 2010, they went back on November 7th.
+        assert_eq!(zone.utc_offset(utc(2010, 11, 7, 6) - 1.0), -240);
+        assert_eq!(zone.utc_offset(utc(2010, 11, 7, 6)), -300);
+
+        // Before any rule, there was no daylight saving time.
+        assert_eq!(zone.utc_offset(utc(1980, 7, 1, 12)), -300);
+    }
+
+    #[test]
+    fn southern_dst_spans_the_new_year() {
+        // Australian Eastern time, from the first Sunday of October to the first Sunday of April.
+        let zone = RuleBasedTimeZone::new(600).with_rule(
+            2008,
+            Some(DstRule {
+                start: DstTransition {
+                    month: 10,
+                    week: 1,
+                    weekday: 0,
+                    time: 120,
+                },
+                end: DstTransition {
+                    month: 4,
+                    week: 1,
+                    weekday: 0,
+                    time: 180,
+                },
+                saving: 60,
+            }),
+        );
+        assert_eq!(zone.utc_offset(utc(2010, 1, 15, 0)), 660);
+        assert_eq!(zone.utc_offset(utc(2010, 7, 15, 0)), 600);
+        // 3am daylight time on April 4th 2010 was 4pm UTC the day before.
+        assert_eq!(zone.utc_offset(utc(2010, 4, 3, 16) - 1.0), 660);
+        assert_eq!(zone.utc_offset(utc(2010, 4, 3, 16)), 600);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn local_times_convert_to_utc() {
+        let zone = eastern();
+        let summer = utc(2010, 7, 1, 12);
+        assert_eq!(zone.utc_to_local(summer), summer - 4.0 * MS_PER_HOUR);
+        assert_eq!(zone.local_to_utc(summer - 4.0 * MS_PER_HOUR), summer);
+        let winter = utc(2010, 1, 1, 12);
+        assert_eq!(zone.local_to_utc(winter - 5.0 * MS_PER_HOUR), winter);
+    }
+
+    #[test]
+    fn dates_are_formatted_like_flash() {
+        let time = utc(2004, 1, 5, 9);
+        assert_eq!(
+            format_date(time, &FixedOffsetTimeZone(60)),
+            "Mon Jan 5 10:00:00 GMT+0100 2004"
+        );
+        assert_eq!(
+            format_date(time, &eastern()),
+            "Mon Jan 5 04:00:00 GMT-0500 2004"
+        );
+        assert_eq!(
+            format_date(utc(2004, 7, 4, 16) + 61_000.0, &eastern()),
+            "Sun Jul 4 12:01:01 GMT-0400 2004"
+        );
+        assert_eq!(
+            format_date(0.0, &FixedOffsetTimeZone(-570)),
+            "Wed Dec 31 14:30:00 GMT-0930 1969"
+        );
+        assert_eq!(
+            format_date(std::f64::NAN, &FixedOffsetTimeZone::utc()),
+            "Invalid Date"
+        );
+    }
+}
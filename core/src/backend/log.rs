@@ -0,0 +1,66 @@
+//! Output of AVM debugging messages, such as `trace`.
+
+use downcast_rs::Downcast;
+use std::fmt;
+
+pub trait LogBackend: Downcast {
+    /// Outputs a message from an AVM `trace` call, or a similar debugging message that Flash
+    /// Player shows in the authoring tool's output panel.
+    fn avm_trace(&mut self, message: &str);
+
+    /// Outputs a message from an AVM `trace` call, along with where it came from.
+    ///
+    /// Backends that don't care about the source of a message only need to implement
+    /// `avm_trace`, which this forwards to by default.
+    fn avm_trace_with_context(&mut self, message: &str, context: &TraceContext) {
+        let _ = context;
+        self.avm_trace(message);
+    }
+}
+impl_downcast!(LogBackend);
+
+/// Where a traced message came from.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TraceContext {
+    /// The path of the clip running the code that traced the message, e.g. `_level0.clip`.
+    pub target_path: String,
+
+    /// The current frame of that clip, if it is a movie clip.
+    pub frame: Option<u16>,
+
+    /// The URL of the movie that defines that clip, if known.
+    pub movie_url: Option<String>,
+}
+
+/// Formats the clip and frame a message came from, e.g. `_level0.clip@3`.
+/// The movie is left out, as it is usually shown once for many messages.
+impl fmt::Display for TraceContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.target_path)?;
+        if let Some(frame) = self.frame {
+            write!(f, "@{}", frame)?;
+        }
+        Ok(())
+    }
+}
+
+/// Log backend that forwards trace output to the `log` crate, under the `avm_trace` target.
+pub struct NullLogBackend {}
+
+impl NullLogBackend {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl LogBackend for NullLogBackend {
+    fn avm_trace(&mut self, message: &str) {
+        log::info!(target: "avm_trace", "{}", message);
+    }
+}
+
+impl Default for NullLogBackend {
+    fn default() -> Self {
+        NullLogBackend::new()
+    }
+}
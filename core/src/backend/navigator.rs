@@ -1,18 +1,251 @@
 //! Browser-related platform functions
 
+use percent_encoding::percent_decode_str;
+use std::borrow::Cow;
+use std::cell::Cell;
 use std::collections::{HashMap, VecDeque};
+use std::fmt;
 use std::fs;
 use std::future::Future;
-use std::path::{Path, PathBuf};
+use std::io;
+use std::path::{Component, Path, PathBuf};
 use std::pin::Pin;
 use std::ptr::null;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
 use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
 use std::time::Duration;
 use swf::avm1::types::SendVarsMethod;
+use url::Url;
 
 pub type Error = Box<dyn std::error::Error>;
 
+/// The error returned by `fetch` when the server answered with an HTTP status
+/// other than success, such as 404 or 500.
+///
+/// Backends should return this rather than an opaque error whenever they
+/// received a response, so that movies can tell a missing file apart from a
+/// network failure.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HttpStatusError(pub u16);
+
+impl HttpStatusError {
+    /// Get the HTTP status of a failed fetch, if the backend reported one.
+    pub fn status_of(error: &Error) -> Option<u16> {
+        error
+            .downcast_ref::<HttpStatusError>()
+            .map(|HttpStatusError(status)| *status)
+    }
+
+    /// Convert an error reading a local file into a fetch error.
+    ///
+    /// A file that doesn't exist is reported as a 404, as a web server would.
+    pub fn from_io(error: io::Error) -> Error {
+        match error.kind() {
+            io::ErrorKind::NotFound => HttpStatusError(404).into(),
+            _ => error.into(),
+        }
+    }
+}
+
+impl fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Server responded with HTTP status {}", self.0)
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+/// The contents of a `data:` URL, which carries its data in the URL itself
+/// rather than pointing to it, such as `data:text/plain;base64,SGVsbG8=`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DataUrl {
+    /// The mime type of the data, which is `text/plain` if the URL doesn't
+    /// give one.
+    pub mime_type: String,
+
+    /// The decoded data.
+    pub data: Vec<u8>,
+}
+
+/// The scheme of a `data:` URL, which is matched case-insensitively.
+const DATA_SCHEME: &str = "data:";
+
+/// Returns whether `url` is a `data:` URL, without decoding its data.
+pub fn is_data_url(url: &str) -> bool {
+    url.get(..DATA_SCHEME.len())
+        .map_or(false, |scheme| scheme.eq_ignore_ascii_case(DATA_SCHEME))
+}
+
+impl DataUrl {
+    /// Decode a `data:` URL.
+    ///
+    /// Returns an error if `url` isn't a `data:` URL or is a malformed one.
+    /// The data is either percent-encoded or, if the mime type is followed by
+    /// `;base64`, base64.
+    pub fn parse(url: &str) -> Result<Self, Error> {
+        if !is_data_url(url) {
+            return Err(format!("Not a data URL: {}", url).into());
+        }
+        Self::decode(&url[DATA_SCHEME.len()..])
+    }
+
+    fn decode(contents: &str) -> Result<Self, Error> {
+        let comma = contents
+            .find(',')
+            .ok_or("Malformed data URL: missing comma before the data")?;
+        let (header, payload) = (&contents[..comma], &contents[comma + 1..]);
+
+        let (media_type, is_base64) = match header.rfind(';') {
+            Some(i) if header[i + 1..].trim().eq_ignore_ascii_case("base64") => {
+                (&header[..i], true)
+            }
+            _ => (header, false),
+        };
+        // Parameters such as the charset are dropped.
+        let mime_type = media_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        let mime_type = if mime_type.is_empty() {
+            "text/plain".to_string()
+        } else {
+            mime_type
+        };
+
+        let data: Vec<u8> = percent_decode_str(payload).collect();
+        let data = if is_base64 {
+            let encoded: Vec<u8> = data
+                .into_iter()
+                .filter(|byte| !byte.is_ascii_whitespace())
+                .collect();
+            base64::decode(&encoded).map_err(|e| format!("Malformed data URL: {}", e))?
+        } else {
+            data
+        };
+
+        Ok(Self { mime_type, data })
+    }
+}
+
+/// Resolve a URL requested by a movie into a file under `base_path`, the
+/// directory the movie was loaded from.
+///
+/// The URL is joined with URL semantics rather than pushed onto the path:
+/// `..` segments go up a directory, a leading slash starts over from
+/// `base_path` itself, percent-encoded characters are decoded, and the query
+/// and fragment are dropped. An absolute `file:` URL, or an absolute Windows
+/// path such as `C:\movies\data.txt`, is allowed if it points inside
+/// `base_path`.
+///
+/// Returns an error for any other scheme, and for any URL that would escape
+/// `base_path`, so that a movie can only read the files next to it.
+pub fn resolve_local_path(base_path: &Path, url: &str) -> Result<PathBuf, Error> {
+    match Url::parse(url) {
+        // A one-letter scheme is the drive of an absolute Windows path.
+        Ok(absolute) if absolute.scheme().len() > 1 => {
+            if absolute.scheme() != "file" {
+                return Err(format!("Unsupported URL scheme for a local fetch: {}", url).into());
+            }
+            let path = absolute
+                .to_file_path()
+                .map_err(|_| format!("Invalid file URL: {}", url))?;
+            return absolute_path_within_base(base_path, &path, url);
+        }
+        Ok(_) if Path::new(url).is_absolute() => {
+            return absolute_path_within_base(base_path, Path::new(url), url);
+        }
+        _ => {}
+    }
+
+    let end = url
+        .find(|c: char| c == '?' || c == '#')
+        .unwrap_or_else(|| url.len());
+    // `..` may step out of the base directory, as long as the URL comes back into it.
+    let base: Vec<Cow<str>> = base_path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect();
+    let mut segments = base.clone();
+    for segment in url[..end].split(|c: char| c == '/' || c == '\\') {
+        let segment = percent_decode_str(segment).decode_utf8_lossy();
+        match segment.as_ref() {
+            "" | "." => {}
+            ".." => {
+                if segments.pop().is_none() {
+                    return Err(format!("URL is outside of the movie's directory: {}", url).into());
+                }
+            }
+            _ if segment.contains(|c: char| c == '/' || c == '\\') => {
+                return Err(format!("Invalid path segment in URL: {}", url).into());
+            }
+            _ => segments.push(segment),
+        }
+    }
+    if !segments.starts_with(&base) {
+        return Err(format!("URL is outside of the movie's directory: {}", url).into());
+    }
+
+    let mut path = base_path.to_path_buf();
+    path.extend(
+        segments[base.len()..]
+            .iter()
+            .map(|segment| segment.as_ref()),
+    );
+    Ok(path)
+}
+
+/// Turn an absolute path requested as `url` back into a path under
+/// `base_path`, or return an error if it is outside of it.
+fn absolute_path_within_base(base_path: &Path, path: &Path, url: &str) -> Result<PathBuf, Error> {
+    let absolute_base = absolute_path(base_path)?;
+    match path.strip_prefix(&absolute_base) {
+        Ok(relative) => Ok(base_path.join(relative)),
+        Err(_) => Err(format!("URL is outside of the movie's directory: {}", url).into()),
+    }
+}
+
+/// Returns the canonical form of `path`, so that the same file always gets
+/// the same URL no matter which directory it was named from. A path that
+/// doesn't exist yet is taken from the current directory instead.
+fn absolute_path(path: &Path) -> std::io::Result<PathBuf> {
+    if let Ok(canonical) = std::fs::canonicalize(path) {
+        return Ok(canonical);
+    }
+    Ok(std::env::current_dir()?
+        .join(path)
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect())
+}
+
+/// Returns the `file:` URL of a file on disk, such as a movie opened from a
+/// path.
+pub fn url_from_file_path(path: &Path) -> Option<String> {
+    let path = absolute_path(path).ok()?;
+    Url::from_file_path(path)
+        .ok()
+        .map(|url| url.as_str().to_string())
+}
+
+/// Returns the file on disk that a movie's URL refers to, which is either a
+/// `file:` URL or a plain path.
+pub fn file_path_from_url(url: &str) -> PathBuf {
+    match Url::parse(url) {
+        Ok(file_url) if file_url.scheme() == "file" => file_url
+            .to_file_path()
+            .unwrap_or_else(|_| PathBuf::from(url)),
+        _ => PathBuf::from(url),
+    }
+}
+
 /// Enumerates all possible navigation methods.
 #[derive(Copy, Clone)]
 pub enum NavigationMethod {
@@ -52,6 +285,12 @@ pub struct RequestOptions {
     ///
     /// The body consists of data and a mime type.
     body: Option<(Vec<u8>, String)>,
+
+    /// Additional HTTP headers to be sent with the request, keyed by header name.
+    headers: HashMap<String, String>,
+
+    /// How long to wait for the response before giving up, if there is a limit.
+    timeout: Option<Duration>,
 }
 
 impl RequestOptions {
@@ -60,6 +299,8 @@ impl RequestOptions {
         Self {
             method: NavigationMethod::GET,
             body: None,
+            headers: HashMap::new(),
+            timeout: None,
         }
     }
 
@@ -68,6 +309,8 @@ impl RequestOptions {
         Self {
             method: NavigationMethod::POST,
             body,
+            headers: HashMap::new(),
+            timeout: None,
         }
     }
 
@@ -80,12 +323,134 @@ impl RequestOptions {
     pub fn body(&self) -> &Option<(Vec<u8>, String)> {
         &self.body
     }
+
+    /// Set the additional HTTP headers to be sent with this request, such as
+    /// `Content-Type` or `SOAPAction`.
+    pub fn with_headers(mut self, headers: HashMap<String, String>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Retrieve the additional HTTP headers of this request.
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+
+    /// Set how long to wait for the response before the fetch fails.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Retrieve how long to wait for the response, if there is a limit.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
 }
 
 /// Type alias for pinned, boxed, and owned futures that output a falliable
 /// result of type `Result<T, E>`.
 pub type OwnedFuture<T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + 'static>>;
 
+/// A closure told how many bytes of a fetch have arrived so far, and how many there are in
+/// total, if that is known.
+pub type ProgressCallback = Box<dyn FnMut(usize, Option<usize>)>;
+
+/// A future handed to an executor, along with the token that cancels it.
+pub type SpawnedFuture = (OwnedFuture<(), Error>, CancellationToken);
+
+/// A signal shared by a player and the futures it spawns, telling them that
+/// the player is shutting down.
+///
+/// Cancellation is cooperative: executors never poll a future again once its
+/// token is cancelled, and a future may check `is_cancelled` between `await`s
+/// to stop early. All clones of a token share the same state.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+
+    /// The token this one was derived from with `child`.
+    parent: Option<Arc<CancellationToken>>,
+}
+
+impl CancellationToken {
+    /// Construct a token that has not been cancelled yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Construct a token that is cancelled along with this one, but which can
+    /// also be cancelled on its own.
+    pub fn child(&self) -> Self {
+        Self {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            parent: Some(Arc::new(self.clone())),
+        }
+    }
+
+    /// Cancel every future spawned with this token.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns `true` if this token, or any token it was derived from, has
+    /// been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+            || self
+                .parent
+                .as_ref()
+                .map_or(false, |parent| parent.is_cancelled())
+    }
+}
+
+/// The handle to a single future, returned by `NavigatorBackend::spawn_future`.
+///
+/// Dropping the handle, or calling `cancel`, stops the future from being
+/// polled again. Futures that should run to completion on their own must be
+/// `detach`ed.
+#[must_use = "dropping a SpawnHandle cancels its future; call `detach` to let it run"]
+#[derive(Debug)]
+pub struct SpawnHandle(Option<CancellationToken>);
+
+impl SpawnHandle {
+    /// Construct a handle that cancels `token` once it is dropped.
+    ///
+    /// Backends should spawn each future with its own `child` of the token
+    /// they were given, and return a handle to that child.
+    pub fn new(token: CancellationToken) -> Self {
+        Self(Some(token))
+    }
+
+    /// Stop the future from being polled again.
+    pub fn cancel(mut self) {
+        if let Some(token) = self.0.take() {
+            token.cancel();
+        }
+    }
+
+    /// Let the future run until it completes or its parent token is cancelled.
+    pub fn detach(mut self) {
+        self.0 = None;
+    }
+
+    /// Returns `true` if the future has been cancelled, either through this
+    /// handle or through the token it was spawned with.
+    pub fn is_cancelled(&self) -> bool {
+        self.0
+            .as_ref()
+            .map_or(false, CancellationToken::is_cancelled)
+    }
+}
+
+impl Drop for SpawnHandle {
+    fn drop(&mut self) {
+        if let Some(token) = self.0.take() {
+            token.cancel();
+        }
+    }
+}
+
 /// A backend interacting with a browser environment.
 pub trait NavigatorBackend {
     /// Cause a browser navigation to a given URL.
@@ -118,8 +483,43 @@ pub trait NavigatorBackend {
     );
 
     /// Fetch data at a given URL and return it some time in the future.
+    ///
+    /// Backends should send the request with the method, body and headers
+    /// given by `request_options`. A header that the platform doesn't allow
+    /// to be set, such as one that browsers control themselves, may be
+    /// dropped.
+    ///
+    /// If the server responds with a status other than success, the returned
+    /// error should be an `HttpStatusError` carrying that status.
+    ///
+    /// If `request_options` has a timeout and no response arrives within it,
+    /// the returned future should resolve with an `io::ErrorKind::TimedOut`
+    /// error rather than wait any longer. Backends whose fetches can't hang,
+    /// such as ones that read from the filesystem, may ignore the timeout.
     fn fetch(&self, url: String, request_options: RequestOptions) -> OwnedFuture<Vec<u8>, Error>;
 
+    /// Fetch data at a given URL like `fetch`, reporting progress as it arrives.
+    ///
+    /// `progress` is called with the number of bytes received so far and the
+    /// total number of bytes, if the backend knows it. This is what movies see
+    /// through `onLoadProgress` and `MovieClipLoader.getProgress`, so backends
+    /// that receive data in parts should report each of them. Backends that
+    /// read the data in one go, such as from the filesystem, may report it
+    /// once it is complete, which is all the default does.
+    fn fetch_with_progress(
+        &self,
+        url: String,
+        request_options: RequestOptions,
+        mut progress: ProgressCallback,
+    ) -> OwnedFuture<Vec<u8>, Error> {
+        let fetch = self.fetch(url, request_options);
+        Box::pin(async move {
+            let data = fetch.await?;
+            progress(data.len(), Some(data.len()));
+            Ok(data)
+        })
+    }
+
     /// Get the amount of time since the SWF was launched.
     /// Used by the `getTimer` ActionScript call.
     fn time_since_launch(&mut self) -> Duration;
@@ -130,18 +530,208 @@ pub trait NavigatorBackend {
     /// The future must output an empty value and not hold any stack references
     /// which would cause it to become invalidated.
     ///
-    /// TODO: For some reason, `wasm_bindgen_futures` wants unpinnable futures.
-    /// This seems highly limiting.
-    fn spawn_future(&mut self, future: OwnedFuture<(), Error>);
+    /// Backends should poll spawned futures on their own event loop, so that
+    /// a future which is woken (e.g. by a completed fetch) continues without
+    /// requiring any further input from the user.
+    ///
+    /// Once `token` is cancelled, the future must not be polled again. The same
+    /// goes for the returned handle, which cancels the future when dropped;
+    /// backends implement this by spawning the future with `token.child()`.
+    fn spawn_future(
+        &mut self,
+        future: OwnedFuture<(), Error>,
+        token: CancellationToken,
+    ) -> SpawnHandle;
+
+    /// Drop every spawned future whose token has been cancelled.
+    ///
+    /// This is called by `Player::shutdown` after cancelling the player's
+    /// token. If the executor is busy, backends may wait for it for up to
+    /// `timeout`; any future left over is dropped by the executor instead of
+    /// being polled next time it runs, which is all the default does.
+    fn drain_futures(&mut self, _timeout: Duration) {}
+
+    /// Inform the backend of the URL of a newly loaded root movie.
+    ///
+    /// Relative fetches made after this call should resolve next to the new
+    /// movie rather than the previous one.
+    fn set_base_url(&mut self, _url: &str) {}
+
+    /// Resolve a URL given to `fetch` into the location it will actually be fetched from.
+    ///
+    /// This is used to tell whether two loads refer to the same movie, so it should be
+    /// consistent with the base URL given to `set_base_url`.
+    fn resolve_relative_url<'a>(&self, url: &'a str) -> Cow<'a, str> {
+        url.into()
+    }
+}
+
+/// What happens to a fetch whose URL matches a rule in `UrlRewriteRules`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UrlRule {
+    /// The URL is fetched as is.
+    Allow,
+
+    /// The fetch fails without making any request.
+    Deny,
+
+    /// The matching prefix is replaced with another one, such as a local directory.
+    Redirect(String),
+}
+
+/// A list of URL prefixes that decides which URLs movies may fetch, and where from.
+///
+/// This lets archived movies load content from servers that no longer exist, and keeps them
+/// from contacting servers that do. The first rule whose prefix matches a URL applies; URLs
+/// that match no rule are fetched as is.
+#[derive(Clone, Debug, Default)]
+pub struct UrlRewriteRules {
+    rules: Vec<(String, UrlRule)>,
+}
+
+impl UrlRewriteRules {
+    /// Construct an empty list of rules, which fetches every URL as is.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a rule for URLs starting with `prefix`, after all existing rules.
+    pub fn add_rule(&mut self, prefix: impl Into<String>, rule: UrlRule) {
+        self.rules.push((prefix.into(), rule));
+    }
+
+    /// Allow URLs starting with `prefix` to be fetched as is.
+    pub fn allow(mut self, prefix: impl Into<String>) -> Self {
+        self.add_rule(prefix, UrlRule::Allow);
+        self
+    }
+
+    /// Deny fetches of URLs starting with `prefix`.
+    pub fn deny(mut self, prefix: impl Into<String>) -> Self {
+        self.add_rule(prefix, UrlRule::Deny);
+        self
+    }
+
+    /// Fetch URLs starting with `prefix` from `replacement` instead.
+    pub fn redirect(mut self, prefix: impl Into<String>, replacement: impl Into<String>) -> Self {
+        self.add_rule(prefix, UrlRule::Redirect(replacement.into()));
+        self
+    }
+
+    /// Returns the URL that a fetch of `url` is made to, or `None` if it is denied.
+    pub fn rewrite_url<'a>(&self, url: &'a str) -> Option<Cow<'a, str>> {
+        let (prefix, rule) = match self
+            .rules
+            .iter()
+            .find(|(prefix, _)| url.starts_with(prefix))
+        {
+            Some(matching) => matching,
+            None => return Some(url.into()),
+        };
+        match rule {
+            UrlRule::Allow => Some(url.into()),
+            UrlRule::Deny => None,
+            UrlRule::Redirect(replacement) => {
+                Some(format!("{}{}", replacement, &url[prefix.len()..]).into())
+            }
+        }
+    }
+}
+
+/// A navigator that applies `UrlRewriteRules` to every fetch before passing it on to
+/// another navigator.
+///
+/// The player hands this to movies in place of its own navigator, so that every way of
+/// loading content follows the same rules.
+pub struct RewritingNavigator<'a> {
+    navigator: &'a mut dyn NavigatorBackend,
+    rules: &'a UrlRewriteRules,
+}
+
+impl<'a> RewritingNavigator<'a> {
+    pub fn new(navigator: &'a mut dyn NavigatorBackend, rules: &'a UrlRewriteRules) -> Self {
+        Self { navigator, rules }
+    }
+}
+
+impl<'a> NavigatorBackend for RewritingNavigator<'a> {
+    fn navigate_to_url(
+        &self,
+        url: String,
+        window: Option<String>,
+        vars_method: Option<(NavigationMethod, HashMap<String, String>)>,
+    ) {
+        self.navigator.navigate_to_url(url, window, vars_method)
+    }
+
+    fn fetch(&self, url: String, request_options: RequestOptions) -> OwnedFuture<Vec<u8>, Error> {
+        match self.rules.rewrite_url(&url) {
+            Some(rewritten) => self
+                .navigator
+                .fetch(rewritten.into_owned(), request_options),
+            None => {
+                log::info!("Denied fetch of {}", url);
+                Box::pin(async move { Err(format!("Fetching {} is not allowed", url).into()) })
+            }
+        }
+    }
+
+    fn fetch_with_progress(
+        &self,
+        url: String,
+        request_options: RequestOptions,
+        progress: ProgressCallback,
+    ) -> OwnedFuture<Vec<u8>, Error> {
+        match self.rules.rewrite_url(&url) {
+            Some(rewritten) => self.navigator.fetch_with_progress(
+                rewritten.into_owned(),
+                request_options,
+                progress,
+            ),
+            None => self.fetch(url, request_options),
+        }
+    }
+
+    fn time_since_launch(&mut self) -> Duration {
+        self.navigator.time_since_launch()
+    }
+
+    fn spawn_future(
+        &mut self,
+        future: OwnedFuture<(), Error>,
+        token: CancellationToken,
+    ) -> SpawnHandle {
+        self.navigator.spawn_future(future, token)
+    }
+
+    fn drain_futures(&mut self, timeout: Duration) {
+        self.navigator.drain_futures(timeout)
+    }
+
+    fn set_base_url(&mut self, url: &str) {
+        self.navigator.set_base_url(url)
+    }
+
+    fn resolve_relative_url<'b>(&self, url: &'b str) -> Cow<'b, str> {
+        match self.rules.rewrite_url(url) {
+            Some(Cow::Borrowed(url)) => self.navigator.resolve_relative_url(url),
+            Some(Cow::Owned(rewritten)) => self
+                .navigator
+                .resolve_relative_url(&rewritten)
+                .into_owned()
+                .into(),
+            None => url.into(),
+        }
+    }
 }
 
 /// A null implementation of an event loop that only supports blocking.
 pub struct NullExecutor {
     /// The list of outstanding futures spawned on this executor.
-    futures_queue: VecDeque<OwnedFuture<(), Error>>,
+    futures_queue: VecDeque<SpawnedFuture>,
 
     /// The source of any additional futures.
-    channel: Receiver<OwnedFuture<(), Error>>,
+    channel: Receiver<SpawnedFuture>,
 }
 
 unsafe fn do_nothing(_data: *const ()) {}
@@ -157,7 +747,7 @@ impl NullExecutor {
     ///
     /// The sender yielded as part of construction should be given to a
     /// `NullNavigatorBackend` so that it can spawn futures on this executor.
-    pub fn new() -> (Self, Sender<OwnedFuture<(), Error>>) {
+    pub fn new() -> (Self, Sender<SpawnedFuture>) {
         let (send, recv) = channel();
 
         (
@@ -178,49 +768,111 @@ impl NullExecutor {
         RawWaker::new(null(), &NULL_VTABLE)
     }
 
-    /// Copy all outstanding futures into the local queue.
-    fn flush_channel(&mut self) {
+    /// Copy all outstanding futures into the local queue, returning how many
+    /// there were.
+    fn flush_channel(&mut self) -> usize {
+        let len = self.futures_queue.len();
         for future in self.channel.try_iter() {
             self.futures_queue.push_back(future);
         }
+
+        self.futures_queue.len() - len
     }
 
     /// Poll all in-progress futures.
     ///
+    /// Futures are polled in passes until a pass neither completes a future
+    /// nor spawns a new one, so futures that are unblocked by others finishing
+    /// make progress in the same call regardless of the order they were
+    /// spawned in. More work may still be available afterwards.
+    ///
     /// If any task in the executor yields an error, then this function will
     /// stop polling futures and return that error. Otherwise, it will yield
-    /// `Ok`, indicating that no errors occured. More work may still be
-    /// available,
+    /// `Ok`, indicating that no errors occured.
+    ///
+    /// Cancelled futures are dropped without being polled.
     pub fn poll_all(&mut self) -> Result<(), Error> {
+        match self.poll_until_settled(true).pop() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Poll all in-progress futures like `poll_all`, but keep polling after
+    /// a future yields an error, returning every error in the order they
+    /// occured.
+    pub fn poll_all_collect_errors(&mut self) -> Vec<Error> {
+        self.poll_until_settled(false)
+    }
+
+    fn poll_until_settled(&mut self, stop_on_error: bool) -> Vec<Error> {
+        let mut errors = vec![];
+
         self.flush_channel();
+        loop {
+            let mut unfinished_futures = VecDeque::new();
+            let mut completed_any = false;
 
-        let mut unfinished_futures = VecDeque::new();
-        let mut result = Ok(());
+            while let Some((mut future, token)) = self.futures_queue.pop_front() {
+                if token.is_cancelled() {
+                    continue;
+                }
 
-        while let Some(mut future) = self.futures_queue.pop_front() {
-            let waker = unsafe { Waker::from_raw(Self::raw_waker()) };
-            let mut context = Context::from_waker(&waker);
+                let waker = unsafe { Waker::from_raw(Self::raw_waker()) };
+                let mut context = Context::from_waker(&waker);
 
-            match future.as_mut().poll(&mut context) {
-                Poll::Ready(v) if v.is_err() => {
-                    result = v;
-                    break;
+                match future.as_mut().poll(&mut context) {
+                    Poll::Ready(result) => {
+                        completed_any = true;
+                        if let Err(error) = result {
+                            errors.push(error);
+                            if stop_on_error {
+                                break;
+                            }
+                        }
+                    }
+                    Poll::Pending => unfinished_futures.push_back((future, token)),
                 }
-                Poll::Ready(_) => continue,
-                Poll::Pending => unfinished_futures.push_back(future),
             }
-        }
 
-        for future in unfinished_futures {
-            self.futures_queue.push_back(future);
+            // Futures left unpolled by an error keep their place in the queue.
+            unfinished_futures.append(&mut self.futures_queue);
+            self.futures_queue = unfinished_futures;
+
+            if stop_on_error && !errors.is_empty() {
+                break;
+            }
+
+            let spawned = self.flush_channel();
+            if !completed_any && spawned == 0 {
+                break;
+            }
         }
 
-        result
+        errors
+    }
+
+    /// Drop all futures whose token has been cancelled, returning how many
+    /// there were.
+    pub fn drain(&mut self) -> usize {
+        self.flush_channel();
+
+        let len = self.futures_queue.len();
+        let futures: VecDeque<SpawnedFuture> = self
+            .futures_queue
+            .drain(..)
+            .filter(|(_, token)| !token.is_cancelled())
+            .collect();
+        self.futures_queue = futures;
+
+        len - self.futures_queue.len()
     }
 
     /// Check if work remains in the executor.
+    ///
+    /// Cancelled futures are not counted.
     pub fn has_work(&mut self) -> bool {
-        self.flush_channel();
+        self.drain();
 
         !self.futures_queue.is_empty()
     }
@@ -241,10 +893,14 @@ impl NullExecutor {
 /// futures and runs them to completion, blockingly.
 pub struct NullNavigatorBackend {
     /// The channel upon which all spawned futures will be sent.
-    channel: Option<Sender<OwnedFuture<(), Error>>>,
+    channel: Option<Sender<SpawnedFuture>>,
 
     /// The base path for all relative fetches.
     relative_base_path: PathBuf,
+
+    /// The virtual time since launch, which only advances when told to via
+    /// the handle returned by `clock`.
+    time: Rc<Cell<Duration>>,
 }
 
 impl NullNavigatorBackend {
@@ -254,14 +910,12 @@ impl NullNavigatorBackend {
         NullNavigatorBackend {
             channel: None,
             relative_base_path: PathBuf::new(),
+            time: Rc::new(Cell::new(Duration::from_millis(0))),
         }
     }
 
     /// Construct a navigator backend with fetch and async capability.
-    pub fn with_base_path<P: AsRef<Path>>(
-        path: P,
-        channel: Sender<OwnedFuture<(), Error>>,
-    ) -> Self {
+    pub fn with_base_path<P: AsRef<Path>>(path: P, channel: Sender<SpawnedFuture>) -> Self {
         let mut relative_base_path = PathBuf::new();
 
         relative_base_path.push(path);
@@ -269,8 +923,18 @@ impl NullNavigatorBackend {
         NullNavigatorBackend {
             channel: Some(channel),
             relative_base_path,
+            time: Rc::new(Cell::new(Duration::from_millis(0))),
         }
     }
+
+    /// Get a handle to the virtual clock returned by `time_since_launch`.
+    ///
+    /// The clock starts at zero and stays there until it is advanced through
+    /// this handle, which keeps working after the backend is given to a
+    /// player.
+    pub fn clock(&self) -> Rc<Cell<Duration>> {
+        self.time.clone()
+    }
 }
 
 impl Default for NullNavigatorBackend {
@@ -289,21 +953,590 @@ impl NavigatorBackend for NullNavigatorBackend {
     }
 
     fn fetch(&self, url: String, _opts: RequestOptions) -> OwnedFuture<Vec<u8>, Error> {
-        let mut path = self.relative_base_path.clone();
-        path.push(url);
+        if is_data_url(&url) {
+            return Box::pin(async move { Ok(DataUrl::parse(&url)?.data) });
+        }
+
+        let path = resolve_local_path(&self.relative_base_path, &url);
 
-        Box::pin(async move { fs::read(path).map_err(|e| e.into()) })
+        Box::pin(async move { fs::read(path?).map_err(HttpStatusError::from_io) })
     }
 
     fn time_since_launch(&mut self) -> Duration {
-        Duration::from_millis(0)
+        self.time.get()
     }
 
-    fn spawn_future(&mut self, future: OwnedFuture<(), Error>) {
+    fn spawn_future(
+        &mut self,
+        future: OwnedFuture<(), Error>,
+        token: CancellationToken,
+    ) -> SpawnHandle {
+        let token = token.child();
         self.channel
             .as_ref()
             .expect("Expected ability to execute futures")
-            .send(future)
+            .send((future, token.clone()))
             .unwrap();
+
+        SpawnHandle::new(token)
+    }
+
+    fn set_base_url(&mut self, url: &str) {
+        self.relative_base_path = file_path_from_url(url)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+    }
+
+    fn resolve_relative_url<'a>(&self, url: &'a str) -> Cow<'a, str> {
+        if is_data_url(url) {
+            return url.into();
+        }
+
+        match resolve_local_path(&self.relative_base_path, url) {
+            // Movies opened from disk have `file:` URLs, so loaded ones do too.
+            Ok(path) => url_from_file_path(&path)
+                .unwrap_or_else(|| path.to_string_lossy().into_owned())
+                .into(),
+            // The fetch will fail, and there is nothing to compare it with.
+            Err(_) => url.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    /// A future that stays pending for a number of polls, like a JS promise
+    /// that resolves on a later turn of the event loop.
+    struct Deferred(u32);
+
+    impl Future for Deferred {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 == 0 {
+                Poll::Ready(())
+            } else {
+                self.0 -= 1;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    #[test]
+    fn multi_await_future_completes_across_polls() {
+        let (mut executor, channel) = NullExecutor::new();
+        let mut navigator = NullNavigatorBackend::with_base_path("", channel);
+        let attached = Rc::new(Cell::new(None));
+        let attached_in_future = attached.clone();
+
+        navigator
+            .spawn_future(
+                Box::pin(async move {
+                    // Fetch, decode, then attach, each resolving on a later poll.
+                    Deferred(2).await;
+                    let data = vec![1u8, 2, 3];
+                    Deferred(1).await;
+                    let decoded: u32 = data.iter().map(|b| u32::from(*b)).sum();
+                    Deferred(1).await;
+                    attached_in_future.set(Some(decoded));
+
+                    Ok(())
+                }),
+                CancellationToken::new(),
+            )
+            .detach();
+
+        let mut polls = 0;
+        while executor.has_work() {
+            executor.poll_all().unwrap();
+            polls += 1;
+            assert!(polls <= 10, "Future did not complete");
+        }
+
+        assert!(polls > 1);
+        assert_eq!(attached.get(), Some(6));
+    }
+
+    #[test]
+    fn cancelled_futures_are_dropped_without_polling() {
+        let (mut executor, channel) = NullExecutor::new();
+        let mut navigator = NullNavigatorBackend::with_base_path("", channel);
+        let cancelled = CancellationToken::new();
+        let other = CancellationToken::new();
+        let polls = Rc::new(Cell::new(0));
+
+        for token in &[&cancelled, &cancelled, &other] {
+            let polls = polls.clone();
+            navigator
+                .spawn_future(
+                    Box::pin(async move {
+                        loop {
+                            polls.set(polls.get() + 1);
+                            Deferred(1).await;
+                        }
+                    }),
+                    (*token).clone(),
+                )
+                .detach();
+        }
+
+        executor.poll_all().unwrap();
+        assert_eq!(polls.get(), 3);
+
+        cancelled.cancel();
+        assert_eq!(executor.drain(), 2);
+        assert!(executor.has_work());
+        executor.poll_all().unwrap();
+        assert_eq!(polls.get(), 4);
+
+        other.cancel();
+        executor.poll_all().unwrap();
+        assert!(!executor.has_work());
+        assert_eq!(polls.get(), 4);
+    }
+
+    /// Stays pending until the flag is set.
+    struct WaitFor(Rc<Cell<bool>>);
+
+    impl Future for WaitFor {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+            if self.0.get() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }
+    }
+
+    #[test]
+    fn futures_unblocked_by_later_futures_complete_in_one_poll() {
+        let (mut executor, channel) = NullExecutor::new();
+        let mut navigator = NullNavigatorBackend::with_base_path("", channel);
+        let first_done = Rc::new(Cell::new(false));
+        let second_done = Rc::new(Cell::new(false));
+        let order = Rc::new(RefCell::new(vec![]));
+
+        // Each future waits on one spawned after it.
+        for (name, wait_for, done) in vec![
+            ("waits for second", second_done.clone(), None),
+            (
+                "waits for first",
+                first_done.clone(),
+                Some(second_done.clone()),
+            ),
+        ] {
+            let order = order.clone();
+            navigator
+                .spawn_future(
+                    Box::pin(async move {
+                        WaitFor(wait_for).await;
+                        order.borrow_mut().push(name);
+                        if let Some(done) = done {
+                            done.set(true);
+                        }
+                        Ok(())
+                    }),
+                    CancellationToken::new(),
+                )
+                .detach();
+        }
+        let first_done_in_future = first_done.clone();
+        navigator
+            .spawn_future(
+                Box::pin(async move {
+                    first_done_in_future.set(true);
+                    Ok(())
+                }),
+                CancellationToken::new(),
+            )
+            .detach();
+
+        executor.poll_all().unwrap();
+        assert!(!executor.has_work());
+        assert_eq!(*order.borrow(), ["waits for first", "waits for second"]);
+    }
+
+    #[test]
+    fn errors_can_be_collected_from_every_future() {
+        let (mut executor, channel) = NullExecutor::new();
+        let mut navigator = NullNavigatorBackend::with_base_path("", channel);
+        let completed = Rc::new(Cell::new(0));
+
+        for i in 0..4 {
+            let completed = completed.clone();
+            navigator
+                .spawn_future(
+                    Box::pin(async move {
+                        completed.set(completed.get() + 1);
+                        if i % 2 == 0 {
+                            Err(Error::from(format!("Future {} failed", i)))
+                        } else {
+                            Ok(())
+                        }
+                    }),
+                    CancellationToken::new(),
+                )
+                .detach();
+        }
+
+        let errors: Vec<_> = executor
+            .poll_all_collect_errors()
+            .iter()
+            .map(|error| error.to_string())
+            .collect();
+        assert_eq!(errors, ["Future 0 failed", "Future 2 failed"]);
+        assert_eq!(completed.get(), 4);
+        assert!(!executor.has_work());
+    }
+
+    #[test]
+    fn poll_all_stops_at_the_first_error() {
+        let (mut executor, channel) = NullExecutor::new();
+        let mut navigator = NullNavigatorBackend::with_base_path("", channel);
+        let completed = Rc::new(Cell::new(0));
+
+        for i in 0..3 {
+            let completed = completed.clone();
+            navigator
+                .spawn_future(
+                    Box::pin(async move {
+                        completed.set(completed.get() + 1);
+                        if i == 0 {
+                            Err(Error::from("First future failed"))
+                        } else {
+                            Ok(())
+                        }
+                    }),
+                    CancellationToken::new(),
+                )
+                .detach();
+        }
+
+        assert!(executor.poll_all().is_err());
+        assert_eq!(completed.get(), 1);
+        executor.poll_all().unwrap();
+        assert_eq!(completed.get(), 3);
+    }
+
+    #[test]
+    fn spawn_handles_cancel_their_own_future() {
+        let (mut executor, channel) = NullExecutor::new();
+        let mut navigator = NullNavigatorBackend::with_base_path("", channel);
+        let token = CancellationToken::new();
+        let polls = Rc::new(RefCell::new(vec![0; 3]));
+
+        let mut handles = vec![];
+        for i in 0..3 {
+            let polls = polls.clone();
+            handles.push(navigator.spawn_future(
+                Box::pin(async move {
+                    loop {
+                        polls.borrow_mut()[i] += 1;
+                        Deferred(1).await;
+                    }
+                }),
+                token.clone(),
+            ));
+        }
+
+        executor.poll_all().unwrap();
+        assert_eq!(*polls.borrow(), [1, 1, 1]);
+
+        // Cancelling or dropping a handle stops only its own future.
+        let mut handles = handles.into_iter();
+        handles.next().unwrap().cancel();
+        drop(handles.next());
+        let detached = handles.next().unwrap();
+        assert!(!detached.is_cancelled());
+        detached.detach();
+        assert_eq!(executor.drain(), 2);
+        executor.poll_all().unwrap();
+        assert_eq!(*polls.borrow(), [1, 1, 2]);
+
+        // A detached future still stops with the token it was spawned with.
+        token.cancel();
+        assert!(!executor.has_work());
+        assert_eq!(*polls.borrow(), [1, 1, 2]);
+    }
+
+    #[test]
+    fn first_matching_url_rule_applies() {
+        let rules = UrlRewriteRules::new()
+            .allow("http://www.example.com/public/")
+            .deny("http://www.example.com/")
+            .redirect("http://cdn.example.com/", "archive/cdn/");
+
+        assert_eq!(
+            rules.rewrite_url("http://www.example.com/public/data.txt"),
+            Some("http://www.example.com/public/data.txt".into())
+        );
+        assert_eq!(rules.rewrite_url("http://www.example.com/stats"), None);
+        assert_eq!(
+            rules.rewrite_url("http://cdn.example.com/movie.swf"),
+            Some("archive/cdn/movie.swf".into())
+        );
+        assert_eq!(rules.rewrite_url("movie.swf"), Some("movie.swf".into()));
+    }
+
+    #[test]
+    fn files_report_progress_once_complete() {
+        let (mut executor, channel) = NullExecutor::new();
+        let mut navigator =
+            NullNavigatorBackend::with_base_path("tests/swfs/avm1/registered_font", channel);
+        let updates = Rc::new(RefCell::new(vec![]));
+        let updates_in_callback = updates.clone();
+
+        let fetch = navigator.fetch_with_progress(
+            "test.ttf".to_string(),
+            RequestOptions::get(),
+            Box::new(move |loaded, total| updates_in_callback.borrow_mut().push((loaded, total))),
+        );
+        let fetched = Rc::new(Cell::new(0));
+        let fetched_in_future = fetched.clone();
+        navigator
+            .spawn_future(
+                Box::pin(async move {
+                    fetched_in_future.set(fetch.await?.len());
+                    Ok(())
+                }),
+                CancellationToken::new(),
+            )
+            .detach();
+        executor.block_all().unwrap();
+
+        assert_eq!(fetched.get(), 492);
+        assert_eq!(&*updates.borrow(), &[(492, Some(492))]);
+    }
+
+    #[test]
+    fn missing_files_fail_with_not_found_status() {
+        let (mut executor, channel) = NullExecutor::new();
+        let mut navigator =
+            NullNavigatorBackend::with_base_path("tests/swfs/avm1/registered_font", channel);
+
+        let fetch = navigator.fetch("missing.ttf".to_string(), RequestOptions::get());
+        let status = Rc::new(Cell::new(None));
+        let status_in_future = status.clone();
+        navigator
+            .spawn_future(
+                Box::pin(async move {
+                    let error = fetch.await.expect_err("Missing file was fetched");
+                    status_in_future.set(HttpStatusError::status_of(&error));
+                    Ok(())
+                }),
+                CancellationToken::new(),
+            )
+            .detach();
+        executor.block_all().unwrap();
+
+        assert_eq!(status.get(), Some(404));
+    }
+
+    #[test]
+    fn request_timeout_is_kept() {
+        assert_eq!(RequestOptions::get().timeout(), None);
+        assert_eq!(RequestOptions::post(None).timeout(), None);
+
+        let timeout = Duration::from_secs(30);
+        assert_eq!(
+            RequestOptions::get().with_timeout(timeout).timeout(),
+            Some(timeout)
+        );
+        assert_eq!(
+            RequestOptions::post(None).with_timeout(timeout).timeout(),
+            Some(timeout)
+        );
+    }
+
+    #[test]
+    fn request_headers_default_to_empty() {
+        assert!(RequestOptions::get().headers().is_empty());
+        assert!(RequestOptions::post(None).headers().is_empty());
+
+        let mut headers = HashMap::new();
+        headers.insert("SOAPAction".to_string(), "urn:example#Ping".to_string());
+        let options = RequestOptions::post(Some((vec![], "text/xml".to_string())))
+            .with_headers(headers.clone());
+        assert_eq!(options.headers(), &headers);
+    }
+
+    fn data_url(url: &str) -> DataUrl {
+        DataUrl::parse(url).expect("Expected a valid data URL")
+    }
+
+    #[test]
+    fn data_urls_are_decoded() {
+        assert_eq!(
+            data_url("data:text/plain;base64,SGVsbG8="),
+            DataUrl {
+                mime_type: "text/plain".to_string(),
+                data: b"Hello".to_vec(),
+            }
+        );
+        assert_eq!(
+            data_url("DATA:Application/X-WWW-Form-Urlencoded,a=1%26b%3D2"),
+            DataUrl {
+                mime_type: "application/x-www-form-urlencoded".to_string(),
+                data: b"a=1&b=2".to_vec(),
+            }
+        );
+
+        // The mime type defaults to text, and its parameters are dropped.
+        assert_eq!(data_url("data:,caf%C3%A9").mime_type, "text/plain");
+        assert_eq!(data_url("data:,caf%C3%A9").data, "caf\u{e9}".as_bytes());
+        assert_eq!(
+            data_url("data:text/xml;charset=utf-8;base64,PGEvPg%3D%3D").mime_type,
+            "text/xml"
+        );
+        assert_eq!(data_url("data:;base64,PGEv\nPg==").data, b"<a/>");
+    }
+
+    #[test]
+    fn malformed_data_urls_are_errors() {
+        assert!(!is_data_url("movie.swf"));
+        assert!(!is_data_url("http://example.com/data:,a"));
+        assert!(is_data_url("Data:text/plain"));
+        assert!(DataUrl::parse("movie.swf").is_err());
+        assert!(DataUrl::parse("data:text/plain").is_err());
+        assert!(DataUrl::parse("data:;base64,not base64!").is_err());
+    }
+
+    #[test]
+    fn data_urls_are_fetched_without_files() {
+        let (mut executor, channel) = NullExecutor::new();
+        let mut navigator = NullNavigatorBackend::with_base_path("missing", channel);
+        let results = Rc::new(RefCell::new(vec![]));
+
+        for url in &["data:,a%20b", "data:;base64,%%%"] {
+            let fetch = navigator.fetch(url.to_string(), RequestOptions::get());
+            let results = results.clone();
+            navigator
+                .spawn_future(
+                    Box::pin(async move {
+                        let result = fetch.await.map_err(|e| e.to_string());
+                        results.borrow_mut().push(result);
+                        Ok(())
+                    }),
+                    CancellationToken::new(),
+                )
+                .detach();
+        }
+        executor.poll_all().unwrap();
+
+        let results = results.borrow();
+        assert_eq!(results[0], Ok(b"a b".to_vec()));
+        assert!(results[1]
+            .as_ref()
+            .unwrap_err()
+            .starts_with("Malformed data URL"));
+    }
+
+    fn resolve(url: &str) -> Result<PathBuf, String> {
+        resolve_local_path(Path::new("movies/game"), url).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn local_paths_resolve_parent_segments_within_the_base() {
+        assert_eq!(
+            resolve("levels/../data.txt"),
+            Ok(PathBuf::from("movies/game/data.txt"))
+        );
+        assert_eq!(
+            resolve("../game/./data.txt"),
+            Ok(PathBuf::from("movies/game/data.txt"))
+        );
+        assert_eq!(
+            resolve("levels%2F..%2F..%2Fsecret.txt").map(|_| ()),
+            Err("Invalid path segment in URL: levels%2F..%2F..%2Fsecret.txt".to_string())
+        );
+        for url in &[
+            "../other.txt",
+            "levels/../../other.txt",
+            "..\\other.txt",
+            "%2e%2e/x",
+        ] {
+            assert!(resolve(url)
+                .unwrap_err()
+                .starts_with("URL is outside of the movie's directory"));
+        }
+    }
+
+    #[test]
+    fn file_urls_name_the_canonical_path() {
+        let url = url_from_file_path(Path::new("src/lib.rs")).unwrap();
+        assert_eq!(
+            url_from_file_path(Path::new("./src/../src/lib.rs")),
+            Some(url.clone())
+        );
+        let absolute = std::fs::canonicalize("src/lib.rs").unwrap();
+        assert_eq!(url_from_file_path(&absolute), Some(url));
+    }
+
+    #[test]
+    fn local_paths_resolve_absolute_urls() {
+        // A leading slash starts over from the movie's directory.
+        assert_eq!(
+            resolve("/levels/1.swf"),
+            Ok(PathBuf::from("movies/game/levels/1.swf"))
+        );
+        assert!(resolve("/../other.txt").is_err());
+
+        let dir = std::env::current_dir().unwrap();
+        let inside = Url::from_file_path(dir.join("movies/game/levels/1.swf")).unwrap();
+        assert_eq!(
+            resolve(inside.as_str()),
+            Ok(PathBuf::from("movies/game/levels/1.swf"))
+        );
+        let outside = Url::from_file_path(dir.join("movies/other.swf")).unwrap();
+        assert!(resolve(outside.as_str())
+            .unwrap_err()
+            .starts_with("URL is outside of the movie's directory"));
+
+        assert!(resolve("http://example.com/data.txt")
+            .unwrap_err()
+            .starts_with("Unsupported URL scheme"));
+    }
+
+    #[test]
+    fn local_paths_resolve_windows_absolute_paths() {
+        // The drive letter isn't mistaken for a URL scheme.
+        for url in &["C:\\Games\\movie.swf", "c:/Games/movie.swf"] {
+            if let Err(error) = resolve(url) {
+                assert!(!error.starts_with("Unsupported URL scheme"));
+            }
+        }
+
+        if cfg!(windows) {
+            let dir = std::env::current_dir().unwrap();
+            let inside = dir.join("movies\\game\\levels\\1.swf");
+            assert_eq!(
+                resolve(inside.to_str().unwrap()),
+                Ok(PathBuf::from("movies/game/levels/1.swf"))
+            );
+            let outside = dir.join("movies\\other.swf");
+            assert!(resolve(outside.to_str().unwrap())
+                .unwrap_err()
+                .starts_with("URL is outside of the movie's directory"));
+        }
+    }
+
+    #[test]
+    fn local_paths_drop_the_query_and_fragment() {
+        assert_eq!(
+            resolve("vars.txt?level=../../secret#top"),
+            Ok(PathBuf::from("movies/game/vars.txt"))
+        );
+        assert_eq!(
+            resolve("high%20scores.txt#/../.."),
+            Ok(PathBuf::from("movies/game/high scores.txt"))
+        );
     }
 }
@@ -1,8 +1,10 @@
 pub use crate::{transform::Transform, Color};
+use downcast_rs::Downcast;
+use std::borrow::Cow;
 use std::io::Read;
 pub use swf;
 
-pub trait RenderBackend {
+pub trait RenderBackend: Downcast {
     fn set_viewport_dimensions(&mut self, width: u32, height: u32);
     fn register_shape(&mut self, shape: &swf::Shape) -> ShapeHandle;
     fn register_glyph_shape(&mut self, shape: &swf::Glyph) -> ShapeHandle;
@@ -21,16 +23,90 @@ pub trait RenderBackend {
     ) -> BitmapInfo;
     fn register_bitmap_png(&mut self, swf_tag: &swf::DefineBitsLossless) -> BitmapInfo;
 
+    /// Registers a bitmap from premultiplied RGBA pixels, such as the contents of a `BitmapData`.
+    fn register_bitmap_raw(&mut self, width: u32, height: u32, rgba: Vec<u8>) -> BitmapHandle;
+
+    /// Replaces a region of a bitmap registered with `register_bitmap_raw`.
+    ///
+    /// `rgba` holds the premultiplied pixels of the region only, as tightly packed rows.
+    fn update_bitmap_region(&mut self, bitmap: BitmapHandle, region: PixelRegion, rgba: &[u8]);
+
+    /// Releases a shape registered with `register_shape` or `register_glyph_shape`.
+    ///
+    /// The handle must not be used afterwards.
+    fn unregister_shape(&mut self, shape: ShapeHandle);
+
+    /// Releases a bitmap registered with any of the `register_bitmap` methods.
+    ///
+    /// The handle must not be used afterwards.
+    fn unregister_bitmap(&mut self, bitmap: BitmapHandle);
+
     fn begin_frame(&mut self);
     fn clear(&mut self, color: Color);
-    fn render_bitmap(&mut self, bitmap: BitmapHandle, transform: &Transform);
+
+    /// Draws a bitmap at its natural size, transformed by `transform`.
+    ///
+    /// `smoothing` selects whether it is sampled with bilinear filtering or nearest-neighbor
+    /// when scaled.
+    fn render_bitmap(&mut self, bitmap: BitmapHandle, transform: &Transform, smoothing: bool);
     fn render_shape(&mut self, shape: ShapeHandle, transform: &Transform);
     fn end_frame(&mut self);
     fn draw_letterbox(&mut self, letterbox: Letterbox);
+
+    /// Starts drawing a mask. Everything rendered until `activate_mask` makes up the mask.
+    ///
+    /// Only the coverage of the mask's shapes and bitmaps counts, not their colors or alpha,
+    /// as in Flash Player. Masks nest: a mask pushed while another is active is itself clipped
+    /// to the enclosing mask. Every `push_mask` is followed by one `activate_mask` and one
+    /// `pop_mask` within the same frame.
     fn push_mask(&mut self);
+
+    /// Clips everything rendered until the matching `pop_mask` to the mask.
+    ///
+    /// The mask clips the final result of each masked object, so effects applied to the
+    /// masked content, such as blend modes, are clipped rather than being applied to the
+    /// clipped content.
     fn activate_mask(&mut self);
+
+    /// Removes the innermost mask, restoring the mask that was active before it.
+    ///
+    /// Nothing of the removed mask is left behind, so content drawn afterwards, including
+    /// content that a later mask reveals, is clipped only by the masks still active.
     fn pop_mask(&mut self);
+
+    /// Draws the shapes and bitmaps rendered until the matching `pop_blend_mode` call
+    /// with the given blend mode.
+    ///
+    /// Backends that don't support a blend mode draw with the normal blend mode instead.
+    fn push_blend_mode(&mut self, blend_mode: swf::BlendMode) {
+        let _ = blend_mode;
+    }
+
+    /// Restores the blend mode used before the last `push_blend_mode` call.
+    fn pop_blend_mode(&mut self) {}
+
+    /// Sets whether bitmaps are smoothed when scaled, for bitmaps that ask to be.
+    /// When disabled, every bitmap is drawn with nearest-neighbor sampling.
+    ///
+    /// Backends that can't change how bitmaps are sampled ignore this.
+    fn set_bitmap_smoothing_enabled(&mut self, enabled: bool) {
+        let _ = enabled;
+    }
+
+    /// The limits of this renderer that content has to fit within.
+    fn capabilities(&self) -> RenderCapabilities {
+        RenderCapabilities::default()
+    }
+
+    /// Whether this renderer can no longer draw, such as when its graphics device was lost.
+    ///
+    /// The player tells the user once this happens, as nothing will be drawn until the host
+    /// creates a new renderer.
+    fn is_lost(&self) -> bool {
+        false
+    }
 }
+impl_downcast!(RenderBackend);
 
 #[derive(Copy, Clone, Debug)]
 pub struct ShapeHandle(pub usize);
@@ -46,11 +122,153 @@ pub struct BitmapInfo {
     pub height: u16,
 }
 
+/// The limits of a renderer, as returned by `RenderBackend::capabilities`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RenderCapabilities {
+    /// The largest width or height of a texture, in pixels.
+    ///
+    /// Bitmaps defined in a SWF that exceed this are downscaled by the renderer, while
+    /// `BitmapData`s this large can't be created at all.
+    pub max_texture_size: u32,
+}
+
+impl Default for RenderCapabilities {
+    fn default() -> Self {
+        Self {
+            max_texture_size: u32::max_value(),
+        }
+    }
+}
+
+/// A rectangle of pixels within a bitmap.
+///
+/// The minimum coordinates are inclusive, and the maximum coordinates are exclusive.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PixelRegion {
+    pub x_min: u32,
+    pub y_min: u32,
+    pub x_max: u32,
+    pub y_max: u32,
+}
+
+impl PixelRegion {
+    /// Clamps a rectangle to a bitmap of the given size.
+    ///
+    /// Returns `None` if no pixels of the rectangle lie within the bitmap.
+    pub fn clamped(
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        max_x: u32,
+        max_y: u32,
+    ) -> Option<Self> {
+        let clamp = |value: i64, max: u32| value.max(0).min(i64::from(max)) as u32;
+        let region = Self {
+            x_min: clamp(x.into(), max_x),
+            y_min: clamp(y.into(), max_y),
+            x_max: clamp(i64::from(x) + i64::from(width), max_x),
+            y_max: clamp(i64::from(y) + i64::from(height), max_y),
+        };
+        if region.width() > 0 && region.height() > 0 {
+            Some(region)
+        } else {
+            None
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.x_max.saturating_sub(self.x_min)
+    }
+
+    pub fn height(&self) -> u32 {
+        self.y_max.saturating_sub(self.y_min)
+    }
+
+    /// The number of pixels in this region.
+    pub fn area(&self) -> u64 {
+        u64::from(self.width()) * u64::from(self.height())
+    }
+
+    /// The smallest region containing both this region and `other`.
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            x_min: self.x_min.min(other.x_min),
+            y_min: self.y_min.min(other.y_min),
+            x_max: self.x_max.max(other.x_max),
+            y_max: self.y_max.max(other.y_max),
+        }
+    }
+
+    /// Whether this region overlaps or shares an edge with `other`.
+    pub fn touches(&self, other: &Self) -> bool {
+        self.x_min <= other.x_max
+            && other.x_min <= self.x_max
+            && self.y_min <= other.y_max
+            && other.y_min <= self.y_max
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Letterbox {
     None,
     Letterbox(f32),
     Pillarbox(f32),
+    /// Bars on every side of the stage, which is drawn in the given region of the viewport,
+    /// such as when the stage is only scaled by whole numbers.
+    Windowbox {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
+}
+
+impl Letterbox {
+    /// The bars covering the viewport outside of the stage, as `(x, y, width, height)`
+    /// rectangles in pixels.
+    pub fn bars(self, viewport_width: f32, viewport_height: f32) -> Vec<(f32, f32, f32, f32)> {
+        let bars = match self {
+            Letterbox::None => vec![],
+            Letterbox::Letterbox(margin_height) => vec![
+                (0.0, 0.0, viewport_width, margin_height),
+                (
+                    0.0,
+                    viewport_height - margin_height,
+                    viewport_width,
+                    margin_height,
+                ),
+            ],
+            Letterbox::Pillarbox(margin_width) => vec![
+                (0.0, 0.0, margin_width, viewport_height),
+                (
+                    viewport_width - margin_width,
+                    0.0,
+                    margin_width,
+                    viewport_height,
+                ),
+            ],
+            Letterbox::Windowbox {
+                x,
+                y,
+                width,
+                height,
+            } => vec![
+                (0.0, 0.0, viewport_width, y),
+                (
+                    0.0,
+                    y + height,
+                    viewport_width,
+                    viewport_height - y - height,
+                ),
+                (0.0, y, x, height),
+                (x + width, y, viewport_width - x - width, height),
+            ],
+        };
+        bars.into_iter()
+            .filter(|&(_, _, width, height)| width > 0.0 && height > 0.0)
+            .collect()
+    }
 }
 
 pub struct NullRenderer;
@@ -113,10 +331,16 @@ impl RenderBackend for NullRenderer {
             height: 0,
         }
     }
+    fn register_bitmap_raw(&mut self, _width: u32, _height: u32, _rgba: Vec<u8>) -> BitmapHandle {
+        BitmapHandle(0)
+    }
+    fn update_bitmap_region(&mut self, _bitmap: BitmapHandle, _region: PixelRegion, _rgba: &[u8]) {}
+    fn unregister_shape(&mut self, _shape: ShapeHandle) {}
+    fn unregister_bitmap(&mut self, _bitmap: BitmapHandle) {}
     fn begin_frame(&mut self) {}
     fn end_frame(&mut self) {}
     fn clear(&mut self, _color: Color) {}
-    fn render_bitmap(&mut self, _bitmap: BitmapHandle, _transform: &Transform) {}
+    fn render_bitmap(&mut self, _bitmap: BitmapHandle, _transform: &Transform, _smoothing: bool) {}
     fn render_shape(&mut self, _shape: ShapeHandle, _transform: &Transform) {}
     fn draw_letterbox(&mut self, _letterbox: Letterbox) {}
     fn push_mask(&mut self) {}
@@ -175,7 +399,8 @@ pub fn remove_invalid_jpeg_data(mut data: &[u8]) -> std::borrow::Cow<[u8]> {
 }
 
 /// Decodes a JPEG with optional alpha data.
-/// The JPEG data will already be pre-multiplied by the alpha.
+/// The JPEG data should already be pre-multiplied by the alpha, but some tools store it
+/// straight, so colors are clamped to the alpha to keep the pixels valid.
 pub fn define_bits_jpeg_to_rgba(
     jpeg_data: &[u8],
     alpha_data: &[u8],
@@ -199,10 +424,11 @@ pub fn define_bits_jpeg_to_rgba(
     let mut i = 0;
     let mut a = 0;
     while i < decoded_data.len() {
-        rgba.push(decoded_data[i]);
-        rgba.push(decoded_data[i + 1]);
-        rgba.push(decoded_data[i + 2]);
-        rgba.push(alpha_data[a]);
+        let alpha = alpha_data[a];
+        rgba.push(decoded_data[i].min(alpha));
+        rgba.push(decoded_data[i + 1].min(alpha));
+        rgba.push(decoded_data[i + 2].min(alpha));
+        rgba.push(alpha);
         i += 3;
         a += 1;
     }
@@ -210,6 +436,32 @@ pub fn define_bits_jpeg_to_rgba(
     Ok((metadata.width.into(), metadata.height.into(), rgba))
 }
 
+/// Decodes a JPEG without alpha data into opaque RGBA.
+pub fn jpeg_to_rgba(jpeg_data: &[u8]) -> Result<(u32, u32, Vec<u8>), Box<dyn std::error::Error>> {
+    let jpeg_data = remove_invalid_jpeg_data(jpeg_data);
+
+    let mut decoder = jpeg_decoder::Decoder::new(&jpeg_data[..]);
+    let decoded_data = decoder.decode()?;
+    let metadata = decoder.info().ok_or("JPEG has no image info")?;
+
+    let mut rgba = Vec::with_capacity(metadata.width as usize * metadata.height as usize * 4);
+    match metadata.pixel_format {
+        jpeg_decoder::PixelFormat::RGB24 => {
+            for rgb in decoded_data.chunks_exact(3) {
+                rgba.extend_from_slice(&[rgb[0], rgb[1], rgb[2], 255]);
+            }
+        }
+        jpeg_decoder::PixelFormat::L8 => {
+            for &luma in &decoded_data {
+                rgba.extend_from_slice(&[luma, luma, luma, 255]);
+            }
+        }
+        jpeg_decoder::PixelFormat::CMYK32 => return Err("CMYK JPEGs are not supported".into()),
+    }
+
+    Ok((metadata.width.into(), metadata.height.into(), rgba))
+}
+
 /// Decodes the bitmap data in DefineBitsLossless tag into RGBA.
 /// DefineBitsLossless is Zlib encoded pixel data (similar to PNG), possibly
 /// palletized.
@@ -340,3 +592,141 @@ pub fn unmultiply_alpha_rgba(rgba: &mut [u8]) {
         }
     })
 }
+
+/// Flash Player limits the focal point of focal gradients to this distance from the center.
+pub const MAX_FOCAL_POINT: f32 = 0.998;
+
+/// Adjusts a fill style to what Flash Player actually renders:
+/// * Gradients whose matrix can't be inverted are squashed to nothing, leaving only their last
+///   color visible, so they become solid fills of that color.
+/// * Focal points are clamped to `MAX_FOCAL_POINT`. Further out, the focal point would touch
+///   the edge of the gradient, and the gradient would be infinitely stretched.
+pub fn normalize_fill_style(style: &swf::FillStyle) -> Cow<'_, swf::FillStyle> {
+    use swf::FillStyle;
+    match style {
+        FillStyle::LinearGradient(gradient)
+        | FillStyle::RadialGradient(gradient)
+        | FillStyle::FocalGradient { gradient, .. }
+            if !is_invertible(&gradient.matrix) =>
+        {
+            let color = gradient
+                .records
+                .last()
+                .map(|record| record.color.clone())
+                .unwrap_or(swf::Color {
+                    r: 0,
+                    g: 0,
+                    b: 0,
+                    a: 0,
+                });
+            Cow::Owned(FillStyle::Color(color))
+        }
+        FillStyle::FocalGradient {
+            gradient,
+            focal_point,
+        } if focal_point.abs() > MAX_FOCAL_POINT => Cow::Owned(FillStyle::FocalGradient {
+            gradient: gradient.clone(),
+            focal_point: focal_point.max(-MAX_FOCAL_POINT).min(MAX_FOCAL_POINT),
+        }),
+        _ => Cow::Borrowed(style),
+    }
+}
+
+/// Whether a matrix can be inverted, so that shape coordinates can be mapped back to it.
+fn is_invertible(m: &swf::Matrix) -> bool {
+    // The fixed point scale and skew values may need all of the precision of an `f64` here.
+    let det = f64::from(m.scale_x) * f64::from(m.scale_y)
+        - f64::from(m.rotate_skew_0) * f64::from(m.rotate_skew_1);
+    det != 0.0 && det.is_finite()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use swf::{FillStyle, Gradient, GradientInterpolation, GradientRecord, GradientSpread, Twips};
+
+    fn color(r: u8) -> swf::Color {
+        swf::Color {
+            r,
+            g: 0,
+            b: 0,
+            a: 255,
+        }
+    }
+
+    fn gradient(scale_x: f32, scale_y: f32, rotate_skew_0: f32, rotate_skew_1: f32) -> Gradient {
+        Gradient {
+            matrix: swf::Matrix {
+                translate_x: Twips::new(100),
+                translate_y: Twips::new(100),
+                scale_x,
+                scale_y,
+                rotate_skew_0,
+                rotate_skew_1,
+            },
+            spread: GradientSpread::Repeat,
+            interpolation: GradientInterpolation::RGB,
+            records: vec![
+                GradientRecord {
+                    ratio: 0,
+                    color: color(1),
+                },
+                GradientRecord {
+                    ratio: 255,
+                    color: color(2),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn degenerate_gradients_fill_with_their_last_color() {
+        for style in vec![
+            FillStyle::LinearGradient(gradient(0.0, 1.0, 0.0, 0.0)),
+            FillStyle::RadialGradient(gradient(1.0, 1.0, 1.0, 1.0)),
+            FillStyle::FocalGradient {
+                gradient: gradient(0.0, 0.0, 0.0, 0.0),
+                focal_point: 0.5,
+            },
+        ] {
+            assert_eq!(
+                normalize_fill_style(&style).into_owned(),
+                FillStyle::Color(color(2))
+            );
+        }
+    }
+
+    #[test]
+    fn tiny_gradients_are_kept() {
+        // The smallest scale a SWF can store.
+        let scale = 1.0 / 65536.0;
+        let style = FillStyle::LinearGradient(gradient(scale, scale, 0.0, 0.0));
+        assert!(matches!(normalize_fill_style(&style), Cow::Borrowed(_)));
+
+        let style = FillStyle::LinearGradient(gradient(scale, scale, scale, -scale));
+        assert!(matches!(normalize_fill_style(&style), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn focal_points_are_clamped() {
+        for &(focal_point, expected) in &[
+            (1.0, MAX_FOCAL_POINT),
+            (-1.0, -MAX_FOCAL_POINT),
+            (127.0, MAX_FOCAL_POINT),
+            (0.5, 0.5),
+            (-0.998, -0.998),
+        ] {
+            let style = FillStyle::FocalGradient {
+                gradient: gradient(1.0, 1.0, 0.0, 0.0),
+                focal_point,
+            };
+            assert_eq!(
+                normalize_fill_style(&style).into_owned(),
+                FillStyle::FocalGradient {
+                    gradient: gradient(1.0, 1.0, 0.0, 0.0),
+                    focal_point: expected,
+                }
+            );
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! Persistent storage of local shared objects.
+
+use crate::backend::navigator::OwnedFuture;
+use std::collections::HashMap;
+
+type Error = Box<dyn std::error::Error>;
+
+/// Storage for the data of `SharedObject`s, as serialized AMF0.
+///
+/// ActionScript expects reads and writes to happen synchronously, so backends should keep what
+/// they store in memory and persist it in the background.
+pub trait StorageBackend {
+    /// Returns the data stored under `key`, if any.
+    fn get(&self, key: &str) -> Option<Vec<u8>>;
+
+    /// Stores `data` under `key`.
+    ///
+    /// Later calls to `get` see the new data straight away. The returned future completes once
+    /// the data has been persisted, and fails if it could not be, e.g. because it doesn't fit
+    /// in the storage quota. Data that doesn't fit is not stored at all.
+    fn put(&mut self, key: &str, data: Vec<u8>) -> OwnedFuture<(), Error>;
+
+    /// Deletes the data stored under `key`.
+    fn remove(&mut self, key: &str);
+}
+
+/// Storage backend that keeps data in memory for as long as the player lives.
+#[derive(Default)]
+pub struct MemoryStorageBackend {
+    data: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryStorageBackend {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl StorageBackend for MemoryStorageBackend {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        self.data.get(key).cloned()
+    }
+
+    fn put(&mut self, key: &str, data: Vec<u8>) -> OwnedFuture<(), Error> {
+        self.data.insert(key.to_string(), data);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn remove(&mut self, key: &str) {
+        self.data.remove(key);
+    }
+}
@@ -0,0 +1,137 @@
+//! User interface integration with the host: messages, the clipboard, the mouse cursor and
+//! fullscreen.
+
+use crate::backend::navigator::OwnedFuture;
+use downcast_rs::Downcast;
+
+pub type Error = Box<dyn std::error::Error>;
+
+pub trait UiBackend: Downcast {
+    /// Show a message to the user.
+    ///
+    /// If `choices` is not empty, the user is asked to pick one of them, and the returned future
+    /// resolves to the index of their choice. It resolves to `None` if the message was dismissed
+    /// without a choice, or if the backend cannot ask the user.
+    fn message(
+        &mut self,
+        level: MessageLevel,
+        message: &str,
+        choices: &[&str],
+    ) -> OwnedFuture<Option<usize>, Error>;
+
+    /// Replaces the contents of the system clipboard.
+    /// Used by the AVM1 `System.setClipboard` call.
+    fn set_clipboard_content(&mut self, content: String);
+
+    fn mouse_visible(&self) -> bool;
+
+    /// Shows or hides the mouse cursor while it is over the player.
+    fn set_mouse_visible(&mut self, visible: bool);
+
+    /// Changes the mouse cursor image.
+    fn set_mouse_cursor(&mut self, cursor: MouseCursor);
+
+    fn display_state(&self) -> DisplayState;
+
+    /// Requests a change of display state, such as entering fullscreen.
+    ///
+    /// Returns an error if the host refused the change.
+    fn set_display_state(&mut self, state: DisplayState) -> Result<(), Error>;
+}
+impl_downcast!(UiBackend);
+
+/// The severity of a message shown to the user.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+/// How the player is displayed by the host.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisplayState {
+    /// The player is shown inside its window or page.
+    /// Equivalent to AVM1 `Stage.displayState = "normal"`.
+    Normal,
+
+    /// The player covers the whole screen.
+    /// Equivalent to AVM1 `Stage.displayState = "fullScreen"`.
+    FullScreen,
+}
+
+/// A mouse cursor icon displayed by the Flash Player.
+/// Communicated from the core to the UI backend via `UiBackend::set_mouse_cursor`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MouseCursor {
+    /// The default arrow icon.
+    /// Equivalent to AS3 `MouseCursor.ARROW`.
+    Arrow,
+
+    /// The hand icon incdicating a button or link.
+    /// Equivalent to AS3 `MouseCursor.BUTTON`.
+    Hand,
+
+    /// The text I-beam.
+    /// Equivalent to AS3 `MouseCursor.IBEAM`.
+    IBeam,
+
+    /// The grabby-dragging hand icon.
+    /// Equivalent to AS3 `MouseCursor.HAND`.
+    Grab,
+}
+
+/// UI backend that logs messages and otherwise does nothing.
+pub struct NullUiBackend {
+    mouse_visible: bool,
+}
+
+impl NullUiBackend {
+    pub fn new() -> Self {
+        Self {
+            mouse_visible: true,
+        }
+    }
+}
+
+impl UiBackend for NullUiBackend {
+    fn message(
+        &mut self,
+        level: MessageLevel,
+        message: &str,
+        _choices: &[&str],
+    ) -> OwnedFuture<Option<usize>, Error> {
+        match level {
+            MessageLevel::Info => log::info!("{}", message),
+            MessageLevel::Warning => log::warn!("{}", message),
+            MessageLevel::Error => log::error!("{}", message),
+        }
+        Box::pin(async { Ok(None) })
+    }
+
+    fn set_clipboard_content(&mut self, _content: String) {}
+
+    fn mouse_visible(&self) -> bool {
+        self.mouse_visible
+    }
+
+    fn set_mouse_visible(&mut self, visible: bool) {
+        self.mouse_visible = visible;
+    }
+
+    fn set_mouse_cursor(&mut self, _cursor: MouseCursor) {}
+
+    fn display_state(&self) -> DisplayState {
+        DisplayState::Normal
+    }
+
+    fn set_display_state(&mut self, _state: DisplayState) -> Result<(), Error> {
+        Err("Display state changes are not supported".into())
+    }
+}
+
+impl Default for NullUiBackend {
+    fn default() -> Self {
+        NullUiBackend::new()
+    }
+}
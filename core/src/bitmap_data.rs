@@ -0,0 +1,496 @@
+//! Pixel buffers backing `BitmapData`
+
+use crate::backend::render::{BitmapHandle, PixelRegion, RenderBackend, RenderCapabilities};
+
+type Error = Box<dyn std::error::Error>;
+
+/// The largest width or height of a `BitmapData`.
+pub const MAX_BITMAP_SIZE: u32 = 8191;
+
+/// The largest number of pixels in a `BitmapData`.
+pub const MAX_BITMAP_PIXELS: u32 = 16_777_215;
+
+/// The maximum number of separate regions uploaded to the renderer per update.
+///
+/// Once this many regions are dirty, the closest pair is merged so that many small
+/// writes scattered across a bitmap don't turn into as many texture copies.
+const MAX_DIRTY_REGIONS: usize = 8;
+
+/// An editable bitmap, whose changes are uploaded to the renderer incrementally.
+///
+/// Pixels are stored as unmultiplied ARGB, as seen by ActionScript.
+pub struct BitmapData {
+    width: u32,
+    height: u32,
+    transparent: bool,
+    pixels: Vec<u32>,
+
+    /// The texture holding the contents of this bitmap, once it has been uploaded.
+    handle: Option<BitmapHandle>,
+
+    /// The regions changed since the last upload.
+    dirty_regions: Vec<PixelRegion>,
+}
+
+impl BitmapData {
+    pub fn new(width: u32, height: u32, transparent: bool, fill_color: u32) -> Self {
+        let fill_color = if transparent {
+            fill_color
+        } else {
+            fill_color | 0xFF00_0000
+        };
+
+        Self {
+            width,
+            height,
+            transparent,
+            pixels: vec![fill_color; width as usize * height as usize],
+            handle: None,
+            dirty_regions: vec![],
+        }
+    }
+
+    /// Creates a bitmap of the size requested by ActionScript.
+    ///
+    /// Like Flash Player, this fails with an "Invalid BitmapData" error when the size is empty or
+    /// too large. Bitmaps that would exceed the renderer's texture size are also rejected, as
+    /// they couldn't be updated incrementally.
+    pub fn try_new(
+        width: u32,
+        height: u32,
+        transparent: bool,
+        fill_color: u32,
+        capabilities: RenderCapabilities,
+    ) -> Result<Self, Error> {
+        let max_size = MAX_BITMAP_SIZE.min(capabilities.max_texture_size);
+        if width == 0
+            || height == 0
+            || width > max_size
+            || height > max_size
+            || u64::from(width) * u64::from(height) > u64::from(MAX_BITMAP_PIXELS)
+        {
+            log::warn!(
+                "Can't create a {}x{} BitmapData (maximum size is {})",
+                width,
+                height,
+                max_size
+            );
+            return Err("Error #2015: Invalid BitmapData.".into());
+        }
+
+        Ok(Self::new(width, height, transparent, fill_color))
+    }
+
+    /// Creates a bitmap holding a copy of premultiplied RGBA pixels, such as a decoded library
+    /// bitmap.
+    pub fn from_premultiplied_rgba(
+        width: u32,
+        height: u32,
+        transparent: bool,
+        rgba: &[u8],
+    ) -> Self {
+        let mut pixels: Vec<u32> = rgba
+            .chunks_exact(4)
+            .map(|rgba| {
+                let a = u32::from(rgba[3]);
+                let unmultiply = |c: u8| {
+                    if a > 0 {
+                        (u32::from(c) * 255 / a).min(255)
+                    } else {
+                        0
+                    }
+                };
+                (a << 24)
+                    | (unmultiply(rgba[0]) << 16)
+                    | (unmultiply(rgba[1]) << 8)
+                    | unmultiply(rgba[2])
+            })
+            .collect();
+        pixels.resize(width as usize * height as usize, 0);
+
+        Self {
+            width,
+            height,
+            transparent,
+            pixels,
+            handle: None,
+            dirty_regions: vec![],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn transparent(&self) -> bool {
+        self.transparent
+    }
+
+    /// The regions that will be uploaded by the next call to `update_texture`.
+    pub fn dirty_regions(&self) -> &[PixelRegion] {
+        &self.dirty_regions
+    }
+
+    /// Returns the ARGB color of the given pixel, or 0 if it is out of bounds.
+    pub fn get_pixel32(&self, x: i32, y: i32) -> u32 {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height {
+            return 0;
+        }
+
+        self.pixels[y as usize * self.width as usize + x as usize]
+    }
+
+    pub fn set_pixel32(&mut self, x: i32, y: i32, color: u32) {
+        if let Some(region) = PixelRegion::clamped(x, y, 1, 1, self.width, self.height) {
+            let color = self.opaque_if_needed(color);
+            self.pixels[y as usize * self.width as usize + x as usize] = color;
+            self.mark_dirty(region);
+        }
+    }
+
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: i32, height: i32, color: u32) {
+        if let Some(region) = PixelRegion::clamped(x, y, width, height, self.width, self.height) {
+            let color = self.opaque_if_needed(color);
+            for row in region.y_min..region.y_max {
+                let start = (row * self.width + region.x_min) as usize;
+                let end = (row * self.width + region.x_max) as usize;
+                for pixel in &mut self.pixels[start..end] {
+                    *pixel = color;
+                }
+            }
+            self.mark_dirty(region);
+        }
+    }
+
+    /// Copies a rectangle of pixels from `source` to the given position in this bitmap.
+    ///
+    /// Only the part of the rectangle that lies within both bitmaps is copied.
+    pub fn copy_pixels(
+        &mut self,
+        source: &BitmapData,
+        source_rect: (i32, i32, i32, i32),
+        dest_point: (i32, i32),
+    ) {
+        let (src_x, src_y, width, height) = source_rect;
+        let (dest_x, dest_y) = dest_point;
+
+        // Clip the rectangle to the source, then to the destination.
+        let src =
+            match PixelRegion::clamped(src_x, src_y, width, height, source.width, source.height) {
+                Some(src) => src,
+                None => return,
+            };
+        let offset_x = dest_x - src_x;
+        let offset_y = dest_y - src_y;
+        let dest = match PixelRegion::clamped(
+            src.x_min as i32 + offset_x,
+            src.y_min as i32 + offset_y,
+            src.width() as i32,
+            src.height() as i32,
+            self.width,
+            self.height,
+        ) {
+            Some(dest) => dest,
+            None => return,
+        };
+
+        for row in dest.y_min..dest.y_max {
+            let src_row = (row as i32 - offset_y) as u32;
+            let src_start =
+                (src_row * source.width) as usize + (dest.x_min as i32 - offset_x) as usize;
+            let dest_start = (row * self.width + dest.x_min) as usize;
+            let len = dest.width() as usize;
+            for i in 0..len {
+                let color = self.opaque_if_needed(source.pixels[src_start + i]);
+                self.pixels[dest_start + i] = color;
+            }
+        }
+        self.mark_dirty(dest);
+    }
+
+    /// Uploads any changes to the renderer, returning the handle of the bitmap's texture.
+    ///
+    /// The first call uploads the whole bitmap; later calls only upload the regions that have
+    /// changed since.
+    pub fn update_texture(&mut self, renderer: &mut dyn RenderBackend) -> BitmapHandle {
+        let handle = match self.handle {
+            Some(handle) => handle,
+            None => {
+                let full = PixelRegion {
+                    x_min: 0,
+                    y_min: 0,
+                    x_max: self.width,
+                    y_max: self.height,
+                };
+                let handle = renderer.register_bitmap_raw(
+                    self.width,
+                    self.height,
+                    self.region_to_rgba(full),
+                );
+                self.handle = Some(handle);
+                self.dirty_regions.clear();
+                return handle;
+            }
+        };
+
+        for region in std::mem::take(&mut self.dirty_regions) {
+            renderer.update_bitmap_region(handle, region, &self.region_to_rgba(region));
+        }
+
+        handle
+    }
+
+    fn opaque_if_needed(&self, color: u32) -> u32 {
+        if self.transparent {
+            color
+        } else {
+            color | 0xFF00_0000
+        }
+    }
+
+    /// Records that a region has changed, merging it with the regions already recorded.
+    fn mark_dirty(&mut self, mut region: PixelRegion) {
+        // Absorb every region touching the new one, so that runs of adjacent writes
+        // (such as setting each pixel of a row) grow a single region.
+        while let Some(i) = self.dirty_regions.iter().position(|r| r.touches(&region)) {
+            region = region.union(&self.dirty_regions.swap_remove(i));
+        }
+        self.dirty_regions.push(region);
+
+        if self.dirty_regions.len() > MAX_DIRTY_REGIONS {
+            // Merge the pair of regions that wastes the fewest pixels when combined.
+            let mut best = (0, 1, u64::max_value());
+            for i in 0..self.dirty_regions.len() {
+                for j in i + 1..self.dirty_regions.len() {
+                    let (a, b) = (&self.dirty_regions[i], &self.dirty_regions[j]);
+                    let waste = a.union(b).area().saturating_sub(a.area() + b.area());
+                    if waste < best.2 {
+                        best = (i, j, waste);
+                    }
+                }
+            }
+
+            // `i < j`, so removing `j` first leaves `i` in place.
+            let (i, j, _) = best;
+            let b = self.dirty_regions.swap_remove(j);
+            let a = self.dirty_regions.swap_remove(i);
+            self.mark_dirty(a.union(&b));
+        }
+    }
+
+    /// Converts a region of this bitmap to premultiplied RGBA, as expected by the renderer.
+    fn region_to_rgba(&self, region: PixelRegion) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(region.area() as usize * 4);
+        for row in region.y_min..region.y_max {
+            let start = (row * self.width + region.x_min) as usize;
+            let end = (row * self.width + region.x_max) as usize;
+            for &argb in &self.pixels[start..end] {
+                let a = argb >> 24;
+                let premultiply = |c: u32| ((c & 0xFF) * a / 255) as u8;
+                rgba.extend_from_slice(&[
+                    premultiply(argb >> 16),
+                    premultiply(argb >> 8),
+                    premultiply(argb),
+                    a as u8,
+                ]);
+            }
+        }
+        rgba
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::render::{BitmapInfo, Color, Letterbox, ShapeHandle, Transform};
+
+    /// A renderer that counts the bytes of bitmap data uploaded to it.
+    #[derive(Default)]
+    struct UploadCounter {
+        bytes_uploaded: usize,
+        uploads: usize,
+    }
+
+    impl RenderBackend for UploadCounter {
+        fn set_viewport_dimensions(&mut self, _width: u32, _height: u32) {}
+        fn register_shape(&mut self, _shape: &swf::Shape) -> ShapeHandle {
+            ShapeHandle(0)
+        }
+        fn register_glyph_shape(&mut self, _shape: &swf::Glyph) -> ShapeHandle {
+            ShapeHandle(0)
+        }
+        fn register_bitmap_jpeg(
+            &mut self,
+            _id: swf::CharacterId,
+            _data: &[u8],
+            _jpeg_tables: Option<&[u8]>,
+        ) -> BitmapInfo {
+            unimplemented!()
+        }
+        fn register_bitmap_jpeg_2(&mut self, _id: swf::CharacterId, _data: &[u8]) -> BitmapInfo {
+            unimplemented!()
+        }
+        fn register_bitmap_jpeg_3(
+            &mut self,
+            _id: swf::CharacterId,
+            _jpeg_data: &[u8],
+            _alpha_data: &[u8],
+        ) -> BitmapInfo {
+            unimplemented!()
+        }
+        fn register_bitmap_png(&mut self, _swf_tag: &swf::DefineBitsLossless) -> BitmapInfo {
+            unimplemented!()
+        }
+        fn register_bitmap_raw(
+            &mut self,
+            _width: u32,
+            _height: u32,
+            rgba: Vec<u8>,
+        ) -> BitmapHandle {
+            self.bytes_uploaded += rgba.len();
+            self.uploads += 1;
+            BitmapHandle(0)
+        }
+        fn update_bitmap_region(
+            &mut self,
+            _bitmap: BitmapHandle,
+            region: PixelRegion,
+            rgba: &[u8],
+        ) {
+            assert_eq!(rgba.len() as u64, region.area() * 4);
+            self.bytes_uploaded += rgba.len();
+            self.uploads += 1;
+        }
+        fn unregister_shape(&mut self, _shape: ShapeHandle) {}
+        fn unregister_bitmap(&mut self, _bitmap: BitmapHandle) {}
+        fn begin_frame(&mut self) {}
+        fn clear(&mut self, _color: Color) {}
+        fn render_bitmap(
+            &mut self,
+            _bitmap: BitmapHandle,
+            _transform: &Transform,
+            _smoothing: bool,
+        ) {
+        }
+        fn render_shape(&mut self, _shape: ShapeHandle, _transform: &Transform) {}
+        fn end_frame(&mut self) {}
+        fn draw_letterbox(&mut self, _letterbox: Letterbox) {}
+        fn push_mask(&mut self) {}
+        fn activate_mask(&mut self) {}
+        fn pop_mask(&mut self) {}
+    }
+
+    fn region(x_min: u32, y_min: u32, x_max: u32, y_max: u32) -> PixelRegion {
+        PixelRegion {
+            x_min,
+            y_min,
+            x_max,
+            y_max,
+        }
+    }
+
+    #[test]
+    fn animated_region_uploads_only_dirty_pixels() {
+        let mut renderer = UploadCounter::default();
+        let mut bitmap = BitmapData::new(1024, 1024, true, 0);
+        bitmap.update_texture(&mut renderer);
+        assert_eq!(renderer.bytes_uploaded, 1024 * 1024 * 4);
+
+        const FRAMES: usize = 60;
+        for frame in 0..FRAMES {
+            renderer.bytes_uploaded = 0;
+            renderer.uploads = 0;
+            for y in 0..16 {
+                for x in 0..16 {
+                    let color = 0xFF00_0000 | ((x * y + frame as i32) as u32 & 0xFF);
+                    bitmap.set_pixel32(500 + x, 300 + y, color);
+                }
+            }
+            bitmap.update_texture(&mut renderer);
+
+            // The 256 individual writes are coalesced into a single 16x16 upload.
+            assert_eq!(renderer.uploads, 1);
+            assert_eq!(renderer.bytes_uploaded, 16 * 16 * 4);
+        }
+        assert!(bitmap.dirty_regions().is_empty());
+    }
+
+    #[test]
+    fn fill_rect_and_copy_pixels_mark_precise_regions() {
+        let mut bitmap = BitmapData::new(100, 100, false, 0);
+        bitmap.fill_rect(10, 20, 30, 40, 0xFF12_3456);
+        assert_eq!(bitmap.dirty_regions(), &[region(10, 20, 40, 60)]);
+        assert_eq!(bitmap.get_pixel32(39, 59), 0xFF12_3456);
+        assert_eq!(bitmap.get_pixel32(40, 59), 0xFF00_0000);
+
+        let mut bitmap = BitmapData::new(100, 100, false, 0);
+        bitmap.fill_rect(-10, 90, 20, 20, 0xFF12_3456);
+        assert_eq!(bitmap.dirty_regions(), &[region(0, 90, 10, 100)]);
+
+        let source = BitmapData::new(50, 50, true, 0x8000_FF00);
+        let mut bitmap = BitmapData::new(100, 100, true, 0);
+        bitmap.copy_pixels(&source, (40, 40, 20, 20), (90, 0));
+        assert_eq!(bitmap.dirty_regions(), &[region(90, 0, 100, 10)]);
+        assert_eq!(bitmap.get_pixel32(95, 5), 0x8000_FF00);
+        assert_eq!(bitmap.get_pixel32(89, 5), 0);
+    }
+
+    #[test]
+    fn oversized_bitmaps_are_rejected() {
+        let unlimited = RenderCapabilities::default();
+        assert!(BitmapData::try_new(8191, 2048, true, 0, unlimited).is_ok());
+        assert!(BitmapData::try_new(0, 10, true, 0, unlimited).is_err());
+        assert!(BitmapData::try_new(8192, 1, true, 0, unlimited).is_err());
+        assert!(BitmapData::try_new(4096, 4097, true, 0, unlimited).is_err());
+
+        let limited = RenderCapabilities {
+            max_texture_size: 4096,
+        };
+        assert!(BitmapData::try_new(4096, 4095, true, 0, limited).is_ok());
+        let error = BitmapData::try_new(4097, 16, true, 0, limited)
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "Error #2015: Invalid BitmapData.");
+    }
+
+    #[test]
+    fn scattered_writes_are_bounded() {
+        let mut renderer = UploadCounter::default();
+        let mut bitmap = BitmapData::new(256, 256, true, 0);
+        bitmap.update_texture(&mut renderer);
+
+        for i in 0..32 {
+            bitmap.set_pixel32(i * 8, i * 8, 0xFFFF_FFFF);
+        }
+        assert!(bitmap.dirty_regions().len() <= MAX_DIRTY_REGIONS);
+        for i in 0..32 {
+            assert!(bitmap.dirty_regions().iter().any(|r| r.touches(&region(
+                i * 8,
+                i * 8,
+                i * 8 + 1,
+                i * 8 + 1
+            ))));
+        }
+
+        renderer.uploads = 0;
+        bitmap.update_texture(&mut renderer);
+        assert!(renderer.uploads <= MAX_DIRTY_REGIONS);
+    }
+
+    #[test]
+    fn premultiplied_pixels_are_unmultiplied() {
+        let rgba = [255, 0, 0, 255, 0, 128, 0, 128, 0, 0, 0, 0];
+        let bitmap = BitmapData::from_premultiplied_rgba(2, 2, true, &rgba);
+        assert_eq!(bitmap.get_pixel32(0, 0), 0xFFFF_0000);
+        assert_eq!(bitmap.get_pixel32(1, 0), 0x8000_FF00);
+        assert_eq!(bitmap.get_pixel32(0, 1), 0);
+
+        // Missing pixels are transparent.
+        assert_eq!(bitmap.get_pixel32(1, 1), 0);
+        assert!(bitmap.dirty_regions().is_empty());
+    }
+}
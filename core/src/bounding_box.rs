@@ -50,7 +50,7 @@ impl BoundingBox {
             self.x_max = max(self.x_max, other.x_max);
             self.y_min = min(self.y_min, other.y_min);
             self.y_max = max(self.y_max, other.y_max);
-        } else {
+        } else if other.valid {
             *self = other.clone();
         }
     }
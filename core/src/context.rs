@@ -4,19 +4,32 @@ use crate::avm1;
 use crate::avm1::listeners::SystemListener;
 use crate::avm1::{Object, Value};
 use crate::backend::input::InputBackend;
-use crate::backend::{audio::AudioBackend, navigator::NavigatorBackend, render::RenderBackend};
+use crate::backend::log::LogBackend;
+use crate::backend::ui::UiBackend;
+use crate::backend::{
+    audio::AudioBackend,
+    locale::TimeZoneProvider,
+    navigator::{CancellationToken, NavigatorBackend},
+    render::RenderBackend,
+    storage::StorageBackend,
+};
+use crate::context_menu::ContextMenuState;
+use crate::events::KeyboardState;
+use crate::external::ExternalInterface;
 use crate::library::Library;
 use crate::loader::LoadManager;
 use crate::player::Player;
 use crate::prelude::*;
 use crate::tag_utils::{SwfMovie, SwfSlice};
 use crate::transform::TransformStack;
+use crate::viewport::StageScaleMode;
 use core::fmt;
 use gc_arena::{Collect, MutationContext};
 use rand::rngs::SmallRng;
 use std::collections::BTreeMap;
 use std::collections::VecDeque;
 use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
 
 /// `UpdateContext` holds shared data that is used by the various subsystems of Ruffle.
 /// `Player` crates this when it begins a tick and passes it through the call stack to
@@ -37,6 +50,9 @@ pub struct UpdateContext<'a, 'gc, 'gc_context> {
     /// Used by AVM1 `GetTime` action and `getTimer` function.
     pub global_time: u64,
 
+    /// The time the player spent suspended by modal dialogs, which `getTimer` leaves out.
+    pub suspended_time: Duration,
+
     /// The library containing character definitions for this SWF.
     /// Used to instantiate a `DisplayObject` of a given ID.
     pub library: &'a mut Library<'gc>,
@@ -63,6 +79,15 @@ pub struct UpdateContext<'a, 'gc, 'gc_context> {
     /// The input backend, used to detect user interactions.
     pub input: &'a mut dyn InputBackend,
 
+    /// The UI backend, used to show messages and control the mouse cursor.
+    pub ui: &'a mut dyn UiBackend,
+
+    /// The log backend, used to output `trace` messages.
+    pub log: &'a mut dyn LogBackend,
+
+    /// The storage backend, used by `SharedObject` to persist data.
+    pub storage: &'a mut dyn StorageBackend,
+
     /// The RNG, used by the AVM `RandomNumber` opcode,  `Math.random(),` and `random()`.
     pub rng: &'a mut SmallRng,
 
@@ -79,9 +104,15 @@ pub struct UpdateContext<'a, 'gc, 'gc_context> {
     /// The location of the mouse when it was last over the player.
     pub mouse_position: &'a (Twips, Twips),
 
+    /// The state of the modifier and lock keys.
+    pub keyboard: &'a KeyboardState,
+
     /// The object being dragged via a `startDrag` action.
     pub drag_object: &'a mut Option<crate::player::DragObject<'gc>>,
 
+    /// The text field with keyboard focus, set by `Selection.setFocus`.
+    pub focused_object: &'a mut Option<DisplayObject<'gc>>,
+
     /// The dimensions of the stage.
     pub stage_size: (Twips, Twips),
 
@@ -96,6 +127,52 @@ pub struct UpdateContext<'a, 'gc, 'gc_context> {
     /// This is required for asynchronous behavior, such as fetching data from
     /// a URL.
     pub load_manager: &'a mut LoadManager<'gc>,
+
+    /// The context menu currently shown by the frontend.
+    pub context_menu: &'a mut ContextMenuState<'gc>,
+
+    /// The host of the movie, and the callbacks ActionScript has exposed to it.
+    pub external_interface: &'a mut ExternalInterface<'gc>,
+
+    /// The objects returned by `SharedObject.getLocal`, by storage key.
+    pub shared_objects: &'a mut BTreeMap<String, Object<'gc>>,
+
+    /// The number of seconds of streaming sound to buffer before it starts playing.
+    /// Set by the global `_soundbuftime` property.
+    pub sound_buffer_time: &'a mut i32,
+
+    /// Whether the stage needs to be redrawn, even if the timeline doesn't advance.
+    /// Set by `updateAfterEvent`.
+    pub needs_render: &'a mut bool,
+
+    /// How the stage is scaled to fit the viewport, set by `Stage.scaleMode`.
+    pub scale_mode: &'a mut StageScaleMode,
+
+    /// The time zone that local times are shown in.
+    pub time_zone: &'a dyn TimeZoneProvider,
+
+    /// The locale of the host, such as `en-US`.
+    pub locale: &'a str,
+
+    /// The token that futures spawned by the player must be spawned with, which is cancelled
+    /// when the player shuts down.
+    pub cancellation_token: &'a CancellationToken,
+
+    /// The URL that the root movie reports as its own, if the host spoofs it.
+    pub spoofed_url: Option<&'a str>,
+}
+
+impl<'a, 'gc, 'gc_context> UpdateContext<'a, 'gc, 'gc_context> {
+    /// Returns the URL that a movie reports as its own, such as in `_url`.
+    ///
+    /// This is the spoofed URL for the root movie if the host set one, and where the movie was
+    /// loaded from otherwise.
+    pub fn movie_url<'b>(&'b self, movie: &'b SwfMovie) -> Option<&'b str> {
+        match self.spoofed_url {
+            Some(url) if std::ptr::eq(movie, self.swf.as_ref()) => Some(url),
+            _ => movie.url(),
+        }
+    }
 }
 
 /// A queued ActionScript call.
@@ -198,6 +275,10 @@ pub struct RenderContext<'a, 'gc> {
     /// The bounds of the current viewport in twips. Used for culling.
     pub view_bounds: BoundingBox,
 
+    /// Whether display objects that are entirely outside of `view_bounds` are skipped,
+    /// along with their children.
+    pub cull_offscreen_objects: bool,
+
     /// The stack of clip depths, used in masking.
     pub clip_depth_stack: Vec<Depth>,
 }
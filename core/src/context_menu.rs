@@ -0,0 +1,380 @@
+//! Context menu support
+//!
+//! The context menu shown by the frontend is built from the `menu` property of the object under
+//! the mouse cursor, plus the built-in player items.
+
+use crate::avm1::{Activation, Avm1, Object, TObject, Value};
+use crate::context::UpdateContext;
+use crate::prelude::*;
+use gc_arena::{Collect, GcCell};
+use std::cmp::Reverse;
+
+type Error = Box<dyn std::error::Error>;
+
+/// An item of the context menu, as displayed by the frontend.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContextMenuItem {
+    /// The text of this item.
+    pub caption: String,
+
+    /// Whether this item can be selected.
+    pub enabled: bool,
+
+    /// Whether this item should display a check mark.
+    pub checked: bool,
+
+    /// Whether a separator should be displayed above this item.
+    pub separator_before: bool,
+}
+
+/// The action taken when a context menu item is selected.
+#[derive(Clone, Collect)]
+#[collect(no_drop)]
+pub enum ContextMenuCallback<'gc> {
+    Play,
+    Loop,
+    Rewind,
+    Forward,
+    Back,
+
+    /// A `ContextMenuItem` created by ActionScript.
+    Avm1 {
+        /// The `ContextMenuItem` object, whose `onSelect` method will be called.
+        item: Object<'gc>,
+
+        /// The object the menu was opened on.
+        target: Object<'gc>,
+    },
+}
+
+/// The callbacks of the context menu currently displayed by the frontend.
+#[derive(Default, Collect)]
+#[collect(no_drop)]
+pub struct ContextMenuState<'gc> {
+    callbacks: Vec<ContextMenuCallback<'gc>>,
+}
+
+impl<'gc> ContextMenuState<'gc> {
+    /// Build the context menu for the object at the given stage position.
+    ///
+    /// The menu is taken from the topmost object under the cursor with a `menu` property,
+    /// searching up its parents. The `onSelect` method of the menu is called before its items
+    /// are read, so that it may modify them.
+    pub fn build(
+        avm: &mut Avm1<'gc>,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+        point: (Twips, Twips),
+    ) -> (Self, Vec<ContextMenuItem>) {
+        let mut menu = Self::default();
+        let mut items = vec![];
+
+        let root = match context.levels.get(&0) {
+            Some(root) => *root,
+            None => return (menu, items),
+        };
+
+        let mut hit = None;
+        for level in context.levels.values().rev() {
+            hit = pick_object(*level, point);
+            if hit.is_some() {
+                break;
+            }
+        }
+
+        Self::push_dummy_frame(avm, context, root);
+        let target = find_menu_target(avm, context, hit.unwrap_or(root));
+        let _ = avm.run_stack_till_empty(context);
+
+        let (target, menu_object) = match target {
+            Ok(Some(target)) => target,
+            Ok(None) => return (menu, items),
+            Err(e) => {
+                log::error!("Unable to find context menu: {}", e);
+                return (menu, items);
+            }
+        };
+
+        if let Some(menu_object) = menu_object {
+            avm.insert_stack_frame_for_method(
+                root,
+                menu_object,
+                context.swf.header().version,
+                context,
+                "onSelect",
+                &[target.into(), menu_object.into()],
+            );
+            let _ = avm.run_stack_till_empty(context);
+        }
+
+        Self::push_dummy_frame(avm, context, root);
+        if let Err(e) = menu.build_items(avm, context, root, target, menu_object, &mut items) {
+            log::error!("Unable to build context menu: {}", e);
+        }
+        let _ = avm.run_stack_till_empty(context);
+
+        (menu, items)
+    }
+
+    /// Run the action of the context menu item at the given index.
+    pub fn run_callback(
+        &self,
+        avm: &mut Avm1<'gc>,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+        index: usize,
+    ) {
+        let root = match context.levels.get(&0) {
+            Some(root) => *root,
+            None => return,
+        };
+        let root_clip = root.as_movie_clip();
+
+        match self.callbacks.get(index) {
+            Some(ContextMenuCallback::Avm1 { item, target }) => {
+                avm.insert_stack_frame_for_method(
+                    root,
+                    *item,
+                    context.swf.header().version,
+                    context,
+                    "onSelect",
+                    &[(*target).into(), (*item).into()],
+                );
+                let _ = avm.run_stack_till_empty(context);
+            }
+            Some(ContextMenuCallback::Play) => {
+                if let Some(root_clip) = root_clip {
+                    if root_clip.playing() {
+                        root_clip.stop(context);
+                    } else {
+                        root_clip.play(context);
+                    }
+                }
+            }
+            Some(ContextMenuCallback::Loop) => {
+                if let Some(root_clip) = root_clip {
+                    root_clip.set_looping(context.gc_context, !root_clip.is_looping());
+                }
+            }
+            Some(ContextMenuCallback::Rewind) => {
+                if let Some(root_clip) = root_clip {
+                    root_clip.goto_frame(avm, context, 1, true);
+                }
+            }
+            Some(ContextMenuCallback::Forward) => {
+                if let Some(root_clip) = root_clip {
+                    root_clip.next_frame(avm, context);
+                }
+            }
+            Some(ContextMenuCallback::Back) => {
+                if let Some(root_clip) = root_clip {
+                    root_clip.prev_frame(avm, context);
+                }
+            }
+            None => log::warn!("Invalid context menu item index {}", index),
+        }
+    }
+
+    /// Push a stack frame so that AVM properties can be read outside of any script.
+    fn push_dummy_frame(
+        avm: &mut Avm1<'gc>,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+        root: DisplayObject<'gc>,
+    ) {
+        avm.insert_stack_frame(GcCell::allocate(
+            context.gc_context,
+            Activation::from_nothing(
+                context.swf.header().version,
+                avm.global_object_cell(),
+                context.gc_context,
+                root,
+            ),
+        ));
+    }
+
+    fn push(
+        &mut self,
+        items: &mut Vec<ContextMenuItem>,
+        caption: &str,
+        callback: ContextMenuCallback<'gc>,
+    ) {
+        items.push(ContextMenuItem {
+            caption: caption.to_string(),
+            enabled: true,
+            checked: false,
+            separator_before: false,
+        });
+        self.callbacks.push(callback);
+    }
+
+    /// Put a separator above the item at `group_start`, unless it is the first item.
+    fn separate(items: &mut Vec<ContextMenuItem>, group_start: usize) {
+        if group_start > 0 {
+            if let Some(item) = items.get_mut(group_start) {
+                item.separator_before = true;
+            }
+        }
+    }
+
+    fn build_items(
+        &mut self,
+        avm: &mut Avm1<'gc>,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+        root: DisplayObject<'gc>,
+        target: Object<'gc>,
+        menu_object: Option<Object<'gc>>,
+        items: &mut Vec<ContextMenuItem>,
+    ) -> Result<(), Error> {
+        let swf_version = context.swf.header().version;
+        let mut built_in_items = None;
+
+        if let Some(menu_object) = menu_object {
+            if let Value::Object(custom_items) = menu_object
+                .get("customItems", avm, context)?
+                .resolve(avm, context)?
+            {
+                for item in custom_items.array() {
+                    if let Value::Object(item) = item {
+                        let visible = item.get("visible", avm, context)?.resolve(avm, context)?;
+                        if !visible.as_bool(swf_version) {
+                            continue;
+                        }
+
+                        let caption = item
+                            .get("caption", avm, context)?
+                            .resolve(avm, context)?
+                            .coerce_to_string(avm, context)?;
+                        let enabled = item.get("enabled", avm, context)?.resolve(avm, context)?;
+                        let separator_before = item
+                            .get("separatorBefore", avm, context)?
+                            .resolve(avm, context)?;
+
+                        items.push(ContextMenuItem {
+                            caption,
+                            enabled: enabled.as_bool(swf_version),
+                            checked: false,
+                            separator_before: separator_before.as_bool(swf_version),
+                        });
+                        self.callbacks
+                            .push(ContextMenuCallback::Avm1 { item, target });
+                    }
+                }
+            }
+
+            if let Value::Object(object) = menu_object
+                .get("builtInItems", avm, context)?
+                .resolve(avm, context)?
+            {
+                built_in_items = Some(object);
+            }
+        }
+
+        // Built-in items are shown unless the menu explicitly hides them.
+        let mut is_shown = |name: &str| -> Result<bool, Error> {
+            match built_in_items {
+                Some(object) => match object.get(name, avm, context)?.resolve(avm, context)? {
+                    Value::Undefined => Ok(true),
+                    value => Ok(value.as_bool(swf_version)),
+                },
+                None => Ok(true),
+            }
+        };
+        let show_play = is_shown("play")?;
+        let show_loop = is_shown("loop")?;
+        let show_rewind = is_shown("rewind")?;
+        let show_forward_back = is_shown("forward_back")?;
+
+        // The zoom, quality and print items of Flash Player are left out, as the player can't
+        // zoom into the stage, change the rendering quality or print.
+
+        // Playback controls only apply to a root timeline with more than one frame.
+        if let Some(root_clip) = root.as_movie_clip().filter(|clip| clip.total_frames() > 1) {
+            let group_start = items.len();
+            if show_play {
+                self.push(items, "Play", ContextMenuCallback::Play);
+                if let Some(item) = items.last_mut() {
+                    item.checked = root_clip.playing();
+                }
+            }
+            if show_loop {
+                self.push(items, "Loop", ContextMenuCallback::Loop);
+                if let Some(item) = items.last_mut() {
+                    item.checked = root_clip.is_looping();
+                }
+            }
+            Self::separate(items, group_start);
+
+            let group_start = items.len();
+            if show_rewind {
+                self.push(items, "Rewind", ContextMenuCallback::Rewind);
+            }
+            if show_forward_back {
+                self.push(items, "Forward", ContextMenuCallback::Forward);
+                self.push(items, "Back", ContextMenuCallback::Back);
+            }
+            Self::separate(items, group_start);
+        }
+
+        Ok(())
+    }
+}
+
+/// Find the topmost visible display object containing the given stage position.
+///
+/// Invisible objects and masks are skipped along with all of their children.
+fn pick_object<'gc>(
+    object: DisplayObject<'gc>,
+    point: (Twips, Twips),
+) -> Option<DisplayObject<'gc>> {
+    if !object.visible() || object.clip_depth() > 0 {
+        return None;
+    }
+
+    if object.as_button().is_some() {
+        return if object.hit_test(point) {
+            Some(object)
+        } else {
+            None
+        };
+    }
+
+    let mut children: Vec<_> = object.children().collect();
+    if children.is_empty() {
+        if object.world_bounds().contains(point) {
+            Some(object)
+        } else {
+            None
+        }
+    } else {
+        children.sort_by_key(|child| Reverse(child.depth()));
+        children
+            .into_iter()
+            .find_map(|child| pick_object(child, point))
+    }
+}
+
+/// Search up from the object under the cursor for the menu to display.
+///
+/// Returns the nearest object with an AVM representation, which is passed to `onSelect`
+/// callbacks, and the nearest `menu` property of that object or its parents.
+fn find_menu_target<'gc>(
+    avm: &mut Avm1<'gc>,
+    context: &mut UpdateContext<'_, 'gc, '_>,
+    hit: DisplayObject<'gc>,
+) -> Result<Option<(Object<'gc>, Option<Object<'gc>>)>, Error> {
+    let mut target = None;
+    let mut node = Some(hit);
+    while let Some(display_object) = node {
+        if let Value::Object(object) = display_object.object() {
+            if target.is_none() {
+                target = Some(object);
+            }
+
+            if let Value::Object(menu) = object.get("menu", avm, context)?.resolve(avm, context)? {
+                return Ok(target.map(|target| (target, Some(menu))));
+            }
+        }
+
+        node = display_object.parent();
+    }
+
+    Ok(target.map(|target| (target, None)))
+}
@@ -11,6 +11,7 @@ use std::cell::{Ref, RefMut};
 use std::cmp::min;
 use std::fmt::Debug;
 use std::sync::Arc;
+use swf::BlendMode;
 
 mod bitmap;
 mod button;
@@ -21,7 +22,7 @@ mod movie_clip;
 mod text;
 
 use crate::events::{ButtonEvent, ButtonEventResult, ClipEvent};
-pub use bitmap::Bitmap;
+pub use bitmap::{Bitmap, BitmapSource, PixelSnapping};
 pub use button::Button;
 pub use edit_text::EditText;
 pub use graphic::Graphic;
@@ -38,6 +39,9 @@ pub struct DisplayObjectBase<'gc> {
     name: String,
     clip_depth: Depth,
 
+    /// How this object is composited onto the objects below it.
+    blend_mode: BlendMode,
+
     // Cached transform properties `_xscale`, `_yscale`, `_rotation`.
     // These are expensive to calculate, so they will be calculated and cached when AS requests
     // one of these properties.
@@ -69,6 +73,7 @@ impl<'gc> Default for DisplayObjectBase<'gc> {
             transform: Default::default(),
             name: Default::default(),
             clip_depth: Default::default(),
+            blend_mode: BlendMode::Normal,
             rotation: 0.0,
             scale_x: 1.0,
             scale_y: 1.0,
@@ -114,19 +119,28 @@ impl<'gc> DisplayObjectBase<'gc> {
     fn set_place_frame(&mut self, _context: MutationContext<'gc, '_>, frame: u16) {
         self.place_frame = frame;
     }
-    fn transform(&self) -> &Transform {
-        &self.transform
+    fn transform(&self) -> Transform {
+        Transform {
+            matrix: self.matrix(),
+            color_transform: self.transform.color_transform,
+        }
     }
 
-    fn matrix(&self) -> &Matrix {
-        &self.transform.matrix
+    fn matrix(&self) -> Matrix {
+        if self.flags.contains(DisplayObjectFlags::MatrixDirty) {
+            self.composed_matrix()
+        } else {
+            self.transform.matrix
+        }
     }
     fn matrix_mut(&mut self, _context: MutationContext<'gc, '_>) -> &mut Matrix {
+        self.recompose_matrix();
         &mut self.transform.matrix
     }
     fn set_matrix(&mut self, _context: MutationContext<'gc, '_>, matrix: &Matrix) {
         self.transform.matrix = *matrix;
         self.flags.remove(DisplayObjectFlags::ScaleRotationCached);
+        self.flags.remove(DisplayObjectFlags::MatrixDirty);
     }
     fn color_transform(&self) -> &ColorTransform {
         &self.transform.color_transform
@@ -193,8 +207,37 @@ impl<'gc> DisplayObjectBase<'gc> {
         }
     }
 
+    /// The matrix with the cached scale, rotation and skew, and the translation of the current
+    /// matrix.
+    fn composed_matrix(&self) -> Matrix {
+        let cos_x = f64::cos(self.rotation);
+        let sin_x = f64::sin(self.rotation);
+        let cos_y = f64::cos(self.rotation + self.skew);
+        let sin_y = f64::sin(self.rotation + self.skew);
+        Matrix {
+            a: (self.scale_x * cos_x) as f32,
+            b: (self.scale_x * sin_x) as f32,
+            c: (self.scale_y * -sin_y) as f32,
+            d: (self.scale_y * cos_y) as f32,
+            ..self.transform.matrix
+        }
+    }
+
+    /// Stores the matrix composed from the scale and rotation that scripts have changed.
+    ///
+    /// Scripts often change several of `_xscale`, `_yscale` and `_rotation` at once, so the
+    /// matrix is recomposed once per frame instead of on every change. Until then, reading the
+    /// matrix composes it on the fly.
+    fn recompose_matrix(&mut self) {
+        if self.flags.contains(DisplayObjectFlags::MatrixDirty) {
+            self.transform.matrix = self.composed_matrix();
+            self.flags.remove(DisplayObjectFlags::MatrixDirty);
+        }
+    }
+
     fn set_scale(&mut self, scale_x: f32, scale_y: f32, rotation: f32) {
         self.cache_scale_rotation();
+        self.flags.remove(DisplayObjectFlags::MatrixDirty);
         let mut matrix = &mut self.transform.matrix;
         let rotation = rotation.to_radians();
         let cos_x = f32::cos(rotation);
@@ -208,6 +251,9 @@ impl<'gc> DisplayObjectBase<'gc> {
         matrix.d = (scale_y * cos_x) as f32;
     }
 
+    // Scripts often write the same transform values every frame, so the setters below only
+    // mark the matrix as dirty when the value differs from the one already cached. The
+    // comparison is exact, so reading the value back still gives what was written.
     fn rotation(&mut self) -> f64 {
         self.cache_scale_rotation();
         self.rotation
@@ -215,16 +261,10 @@ impl<'gc> DisplayObjectBase<'gc> {
     fn set_rotation(&mut self, radians: f64) {
         self.set_transformed_by_script(true);
         self.cache_scale_rotation();
-        self.rotation = radians;
-        let cos_x = f64::cos(radians);
-        let sin_x = f64::sin(radians);
-        let cos_y = f64::cos(radians + self.skew);
-        let sin_y = f64::sin(radians + self.skew);
-        let mut matrix = &mut self.transform.matrix;
-        matrix.a = (self.scale_x * cos_x) as f32;
-        matrix.b = (self.scale_x * sin_x) as f32;
-        matrix.c = (self.scale_y * -sin_y) as f32;
-        matrix.d = (self.scale_y * cos_y) as f32;
+        if radians != self.rotation {
+            self.rotation = radians;
+            self.flags.insert(DisplayObjectFlags::MatrixDirty);
+        }
     }
     fn scale_x(&mut self) -> f64 {
         self.cache_scale_rotation();
@@ -233,12 +273,10 @@ impl<'gc> DisplayObjectBase<'gc> {
     fn set_scale_x(&mut self, value: f64) {
         self.set_transformed_by_script(true);
         self.cache_scale_rotation();
-        self.scale_x = value;
-        let cos = f64::cos(self.rotation);
-        let sin = f64::sin(self.rotation);
-        let mut matrix = &mut self.transform.matrix;
-        matrix.a = (cos * value) as f32;
-        matrix.b = (sin * value) as f32;
+        if value != self.scale_x {
+            self.scale_x = value;
+            self.flags.insert(DisplayObjectFlags::MatrixDirty);
+        }
     }
     fn scale_y(&mut self) -> f64 {
         self.cache_scale_rotation();
@@ -247,12 +285,10 @@ impl<'gc> DisplayObjectBase<'gc> {
     fn set_scale_y(&mut self, value: f64) {
         self.set_transformed_by_script(true);
         self.cache_scale_rotation();
-        self.scale_y = value;
-        let cos = f64::cos(self.rotation + self.skew);
-        let sin = f64::sin(self.rotation + self.skew);
-        let mut matrix = &mut self.transform.matrix;
-        matrix.c = (-sin * value) as f32;
-        matrix.d = (cos * value) as f32;
+        if value != self.scale_y {
+            self.scale_y = value;
+            self.flags.insert(DisplayObjectFlags::MatrixDirty);
+        }
     }
 
     fn name(&self) -> &str {
@@ -274,6 +310,12 @@ impl<'gc> DisplayObjectBase<'gc> {
     fn set_clip_depth(&mut self, _context: MutationContext<'gc, '_>, depth: Depth) {
         self.clip_depth = depth;
     }
+    fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+    fn set_blend_mode(&mut self, _context: MutationContext<'gc, '_>, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
     fn parent(&self) -> Option<DisplayObject<'gc>> {
         self.parent
     }
@@ -409,7 +451,7 @@ pub trait TDisplayObject<'gc>: 'gc + Collect + Debug + Into<DisplayObject<'gc>>
     fn bounds_with_transform(&self, matrix: &Matrix) -> BoundingBox {
         let mut bounds = self.self_bounds().transform(matrix);
         for child in self.children() {
-            let matrix = *matrix * *child.matrix();
+            let matrix = *matrix * child.matrix();
             bounds.union(&child.bounds_with_transform(&matrix));
         }
         bounds
@@ -418,10 +460,14 @@ pub trait TDisplayObject<'gc>: 'gc + Collect + Debug + Into<DisplayObject<'gc>>
     fn place_frame(&self) -> u16;
     fn set_place_frame(&mut self, context: MutationContext<'gc, '_>, frame: u16);
 
-    fn transform(&self) -> Ref<Transform>;
-    fn matrix(&self) -> Ref<Matrix>;
+    fn transform(&self) -> Transform;
+    fn matrix(&self) -> Matrix;
     fn matrix_mut(&mut self, context: MutationContext<'gc, '_>) -> RefMut<Matrix>;
     fn set_matrix(&mut self, context: MutationContext<'gc, '_>, matrix: &Matrix);
+
+    /// Stores the matrix composed from the `_xscale`, `_yscale` and `_rotation` that scripts
+    /// have changed. This is done once per frame; the matrix is up to date whenever it's read.
+    fn recompose_matrix(&self, context: MutationContext<'gc, '_>);
     fn color_transform(&self) -> Ref<ColorTransform>;
     fn color_transform_mut(&self, context: MutationContext<'gc, '_>) -> RefMut<ColorTransform>;
     fn set_color_transform(
@@ -433,9 +479,9 @@ pub trait TDisplayObject<'gc>: 'gc + Collect + Debug + Into<DisplayObject<'gc>>
     /// Returns the matrix for transforming from this object's local space to global stage space.
     fn local_to_global_matrix(&self) -> Matrix {
         let mut node = self.parent();
-        let mut matrix = *self.matrix();
+        let mut matrix = self.matrix();
         while let Some(display_object) = node {
-            matrix = *display_object.matrix() * matrix;
+            matrix = display_object.matrix() * matrix;
             node = display_object.parent();
         }
 
@@ -445,9 +491,9 @@ pub trait TDisplayObject<'gc>: 'gc + Collect + Debug + Into<DisplayObject<'gc>>
     /// Returns the matrix for transforming from global stage to this object's local space.
     fn global_to_local_matrix(&self) -> Matrix {
         let mut node = self.parent();
-        let mut matrix = *self.matrix();
+        let mut matrix = self.matrix();
         while let Some(display_object) = node {
-            matrix = *display_object.matrix() * matrix;
+            matrix = display_object.matrix() * matrix;
             node = display_object.parent();
         }
 
@@ -529,13 +575,21 @@ pub trait TDisplayObject<'gc>: 'gc + Collect + Debug + Into<DisplayObject<'gc>>
         let object_bounds = self.bounds();
         let object_width = (object_bounds.x_max - object_bounds.x_min).to_pixels();
         let object_height = (object_bounds.y_max - object_bounds.y_min).to_pixels();
-        let aspect_ratio = object_height / object_width;
 
-        let (target_scale_x, target_scale_y) = if object_width != 0.0 {
-            (value / object_width, value / object_height)
-        } else {
-            (0.0, 0.0)
-        };
+        // Resizing an empty object does nothing.
+        if object_width == 0.0 {
+            return;
+        }
+
+        // A flat object has no aspect ratio, so just stretch it along its own axis.
+        if object_height == 0.0 {
+            self.set_scale_x(gc_context, value / object_width);
+            return;
+        }
+
+        let aspect_ratio = object_height / object_width;
+        let target_scale_x = value / object_width;
+        let target_scale_y = value / object_height;
 
         // No idea about the derivation of this -- figured it out via lots of trial and error.
         // It has to do with the length of the sides A, B of an AABB enclosing the object's OBB with sides a, b:
@@ -566,13 +620,21 @@ pub trait TDisplayObject<'gc>: 'gc + Collect + Debug + Into<DisplayObject<'gc>>
         let object_bounds = self.bounds();
         let object_width = (object_bounds.x_max - object_bounds.x_min).to_pixels();
         let object_height = (object_bounds.y_max - object_bounds.y_min).to_pixels();
-        let aspect_ratio = object_width / object_height;
 
-        let (target_scale_x, target_scale_y) = if object_height != 0.0 {
-            (value / object_width, value / object_height)
-        } else {
-            (0.0, 0.0)
-        };
+        // Resizing an empty object does nothing.
+        if object_height == 0.0 {
+            return;
+        }
+
+        // A flat object has no aspect ratio, so just stretch it along its own axis.
+        if object_width == 0.0 {
+            self.set_scale_y(gc_context, value / object_height);
+            return;
+        }
+
+        let aspect_ratio = object_width / object_height;
+        let target_scale_x = value / object_width;
+        let target_scale_y = value / object_height;
 
         // No idea about the derivation of this -- figured it out via lots of trial and error.
         // It has to do with the length of the sides A, B of an AABB enclosing the object's OBB with sides a, b:
@@ -630,6 +692,11 @@ pub trait TDisplayObject<'gc>: 'gc + Collect + Debug + Into<DisplayObject<'gc>>
 
     fn clip_depth(&self) -> Depth;
     fn set_clip_depth(&mut self, context: MutationContext<'gc, '_>, depth: Depth);
+
+    /// How this object is composited onto the objects below it.
+    fn blend_mode(&self) -> BlendMode;
+    fn set_blend_mode(&mut self, context: MutationContext<'gc, '_>, blend_mode: BlendMode);
+
     fn parent(&self) -> Option<DisplayObject<'gc>>;
     fn set_parent(&mut self, context: MutationContext<'gc, '_>, parent: Option<DisplayObject<'gc>>);
     fn first_child(&self) -> Option<DisplayObject<'gc>>;
@@ -764,7 +831,8 @@ pub trait TDisplayObject<'gc>: 'gc + Collect + Debug + Into<DisplayObject<'gc>>
         gc_context: MutationContext<'gc, '_>,
         place_object: &swf::PlaceObject,
     ) {
-        // PlaceObject tags only apply if this onject has not been dynamically moved by AS code.
+        // PlaceObject tags only apply if this object has not been dynamically moved by AS code.
+        // Each attribute is only changed if the tag specifies it; anything else is left as is.
         if !self.transformed_by_script() {
             if let Some(matrix) = &place_object.matrix {
                 self.set_matrix(gc_context, &matrix.clone().into());
@@ -778,13 +846,22 @@ pub trait TDisplayObject<'gc>: 'gc + Collect + Debug + Into<DisplayObject<'gc>>
             if let Some(clip_depth) = place_object.clip_depth {
                 self.set_clip_depth(gc_context, clip_depth.into());
             }
+            // The blend mode can't be told apart from a missing one, so only a blend mode
+            // other than `Normal` is applied.
+            if place_object.blend_mode != BlendMode::Normal {
+                self.set_blend_mode(gc_context, place_object.blend_mode);
+            }
             if let Some(ratio) = place_object.ratio {
                 if let Some(mut morph_shape) = self.as_morph_shape() {
                     morph_shape.set_ratio(gc_context, ratio);
                 }
             }
-            // Clip events only apply to movie clips.
-            if let Some(clip) = self.as_movie_clip() {
+            // TODO: Others will go here eventually.
+        }
+        // Clip events only apply to movie clips.
+        // An empty list means the tag has no clip actions, so the previous ones are kept.
+        if let Some(clip) = self.as_movie_clip() {
+            if !place_object.clip_actions.is_empty() {
                 // Convert from `swf::ClipAction` to Ruffle's `ClipAction`.
                 use crate::display_object::movie_clip::ClipAction;
                 clip.set_clip_actions(
@@ -797,7 +874,6 @@ pub trait TDisplayObject<'gc>: 'gc + Collect + Debug + Into<DisplayObject<'gc>>
                         .collect(),
                 );
             }
-            // TODO: Others will go here eventually.
         }
     }
 
@@ -806,10 +882,13 @@ pub trait TDisplayObject<'gc>: 'gc + Collect + Debug + Into<DisplayObject<'gc>>
         gc_context: MutationContext<'gc, '_>,
         other: DisplayObject<'gc>,
     ) {
-        self.set_matrix(gc_context, &*other.matrix());
+        self.set_matrix(gc_context, &other.matrix());
         self.set_color_transform(gc_context, &*other.color_transform());
         self.set_clip_depth(gc_context, other.clip_depth());
+        self.set_blend_mode(gc_context, other.blend_mode());
         self.set_name(gc_context, &*other.name());
+        // A replaced object that was moved by AS code stays under script control.
+        self.set_transformed_by_script(gc_context, other.transformed_by_script());
         if let (Some(mut me), Some(other)) = (self.as_morph_shape(), other.as_morph_shape()) {
             me.set_ratio(gc_context, other.ratio());
         }
@@ -915,11 +994,11 @@ macro_rules! impl_display_object {
         fn set_place_frame(&mut self, context: gc_arena::MutationContext<'gc, '_>, frame: u16) {
             self.0.write(context).$field.set_place_frame(context, frame)
         }
-        fn transform(&self) -> std::cell::Ref<crate::transform::Transform> {
-            std::cell::Ref::map(self.0.read(), |o| o.$field.transform())
+        fn transform(&self) -> crate::transform::Transform {
+            self.0.read().$field.transform()
         }
-        fn matrix(&self) -> std::cell::Ref<crate::matrix::Matrix> {
-            std::cell::Ref::map(self.0.read(), |o| o.$field.matrix())
+        fn matrix(&self) -> crate::matrix::Matrix {
+            self.0.read().$field.matrix()
         }
         fn matrix_mut(
             &mut self,
@@ -934,6 +1013,9 @@ macro_rules! impl_display_object {
         ) {
             self.0.write(context).$field.set_matrix(context, matrix)
         }
+        fn recompose_matrix(&self, context: gc_arena::MutationContext<'gc, '_>) {
+            self.0.write(context).$field.recompose_matrix()
+        }
         fn color_transform(&self) -> std::cell::Ref<crate::color_transform::ColorTransform> {
             std::cell::Ref::map(self.0.read(), |o| o.$field.color_transform())
         }
@@ -1005,6 +1087,19 @@ macro_rules! impl_display_object {
         ) {
             self.0.write(context).$field.set_clip_depth(context, depth)
         }
+        fn blend_mode(&self) -> swf::BlendMode {
+            self.0.read().$field.blend_mode()
+        }
+        fn set_blend_mode(
+            &mut self,
+            context: gc_arena::MutationContext<'gc, '_>,
+            blend_mode: swf::BlendMode,
+        ) {
+            self.0
+                .write(context)
+                .$field
+                .set_blend_mode(context, blend_mode)
+        }
         fn parent(&self) -> Option<crate::display_object::DisplayObject<'gc>> {
             self.0.read().$field.parent()
         }
@@ -1089,6 +1184,18 @@ macro_rules! impl_display_object {
     };
 }
 
+/// Stores the matrices of a display object and its descendants that scripts have changed, so
+/// that they aren't composed again every time they are read.
+pub fn recompose_matrices<'gc>(
+    gc_context: MutationContext<'gc, '_>,
+    display_object: DisplayObject<'gc>,
+) {
+    display_object.recompose_matrix(gc_context);
+    for child in display_object.children() {
+        recompose_matrices(gc_context, child);
+    }
+}
+
 /// Renders the children of a display object, taking masking into account.
 // TODO(Herschel): Move this into an IDisplayObject/IDisplayObjectContainer trait when
 // we figure out inheritance
@@ -1114,8 +1221,23 @@ pub fn render_children<'gc>(
             child.render(context);
             context.renderer.activate_mask();
         } else if child.visible() {
+            if context.cull_offscreen_objects {
+                // Objects without bounds, such as empty clips, are always rendered.
+                let bounds = child.world_bounds();
+                if bounds.valid && !bounds.intersects(&context.view_bounds) {
+                    continue;
+                }
+            }
+
             // Normal child.
-            child.render(context);
+            let blend_mode = child.blend_mode();
+            if blend_mode != BlendMode::Normal {
+                context.renderer.push_blend_mode(blend_mode);
+                child.render(context);
+                context.renderer.pop_blend_mode();
+            } else {
+                child.render(context);
+            }
         }
     }
 
@@ -1148,6 +1270,10 @@ enum DisplayObjectFlags {
     /// Whether this object has been transformed by ActionScript.
     /// When this flag is set, changes from SWF `PlaceObject` tags are ignored.
     TransformedByScript,
+
+    /// Whether the cached `_xscale`, `_yscale` or `_rotation` have been changed since the
+    /// matrix was last composed from them.
+    MatrixDirty,
 }
 
 pub struct ChildIter<'gc> {
@@ -1164,3 +1290,57 @@ impl<'gc> Iterator for ChildIter<'gc> {
         cur
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rotated_base<'gc>() -> DisplayObjectBase<'gc> {
+        let mut base = DisplayObjectBase::default();
+        base.set_scale(50.0, 200.0, 90.0);
+        base
+    }
+
+    #[test]
+    fn redundant_transform_writes_leave_the_matrix_untouched() {
+        let mut base = rotated_base();
+        let matrix = base.transform.matrix;
+
+        let (rotation, scale_x, scale_y) = (base.rotation(), base.scale_x(), base.scale_y());
+        base.set_rotation(rotation);
+        base.set_scale_x(scale_x);
+        base.set_scale_y(scale_y);
+
+        assert!(!base.flags.contains(DisplayObjectFlags::MatrixDirty));
+        assert_eq!(base.transform.matrix, matrix);
+        assert_eq!(base.matrix(), matrix);
+        assert!(base.transformed_by_script());
+    }
+
+    #[test]
+    fn transform_writes_are_composed_once() {
+        let mut base = rotated_base();
+        let matrix = base.transform.matrix;
+
+        base.set_rotation(0.0);
+        base.set_scale_x(2.0);
+        base.set_scale_y(3.0);
+
+        // The stored matrix is only recomposed once, but reads see the changes right away.
+        assert!(base.flags.contains(DisplayObjectFlags::MatrixDirty));
+        assert_eq!(base.transform.matrix, matrix);
+        let composed = base.matrix();
+        assert_eq!(
+            (composed.a, composed.b, composed.c, composed.d),
+            (2.0, 0.0, 0.0, 3.0)
+        );
+
+        base.recompose_matrix();
+        assert!(!base.flags.contains(DisplayObjectFlags::MatrixDirty));
+        assert_eq!(base.transform.matrix, composed);
+        assert_eq!(
+            (base.rotation(), base.scale_x(), base.scale_y()),
+            (0.0, 2.0, 3.0)
+        );
+    }
+}
@@ -1,15 +1,25 @@
 //! Bitmap display object
 
 use crate::avm1::Avm1;
-use crate::backend::render::BitmapHandle;
+use crate::backend::render::{
+    define_bits_jpeg_to_rgba, define_bits_lossless_to_rgba, glue_tables_to_jpeg, jpeg_to_rgba,
+    BitmapHandle,
+};
 use crate::context::{RenderContext, UpdateContext};
 use crate::display_object::{DisplayObjectBase, TDisplayObject};
 use crate::prelude::*;
+use crate::tag_utils::{SwfMovie, SwfSlice};
 use gc_arena::{Collect, Gc, GcCell};
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::sync::Arc;
+use swf::TagCode;
 
 /// A Bitmap display object is a raw bitamp on the stage.
 /// This can only be instanitated on the display list in SWFv9 AVM2 files.
-/// In AVM1, this is only a library symbol that is referenced by `Graphic`.
+/// In AVM1, this is a library symbol that is referenced by `Graphic`, or a `BitmapData`
+/// attached with `MovieClip.attachBitmap`.
 /// Normally bitmaps are drawn in Flash as part of a Shape tag (`Graphic`),
 /// but starting in AVM2, a raw `Bitmap` display object can be crated
 /// with the `PlaceObject3` tag.
@@ -18,10 +28,17 @@ use gc_arena::{Collect, Gc, GcCell};
 #[collect(no_drop)]
 pub struct Bitmap<'gc>(GcCell<'gc, BitmapData<'gc>>);
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct BitmapData<'gc> {
     base: DisplayObjectBase<'gc>,
     static_data: Gc<'gc, BitmapStatic>,
+
+    /// The pixels of a `BitmapData` attached with `attachBitmap`, which are drawn instead of
+    /// the library bitmap. Changes to them are uploaded before each render.
+    bitmap_data: Option<Rc<RefCell<crate::bitmap_data::BitmapData>>>,
+
+    pixel_snapping: PixelSnapping,
+    smoothing: bool,
 }
 
 impl<'gc> Bitmap<'gc> {
@@ -31,6 +48,7 @@ impl<'gc> Bitmap<'gc> {
         bitmap_handle: BitmapHandle,
         width: u16,
         height: u16,
+        source: Option<BitmapSource>,
     ) -> Self {
         Bitmap(GcCell::allocate(
             context.gc_context,
@@ -43,12 +61,135 @@ impl<'gc> Bitmap<'gc> {
                         bitmap_handle,
                         width,
                         height,
+                        source,
                     },
                 ),
+                bitmap_data: None,
+                // Bitmaps placed by the timeline are drawn as they always have been.
+                pixel_snapping: PixelSnapping::Never,
+                smoothing: true,
             },
         ))
     }
 
+    /// Creates a bitmap that draws the pixels of a `BitmapData`, as `MovieClip.attachBitmap`
+    /// does.
+    pub fn from_bitmap_data(
+        context: &mut UpdateContext<'_, 'gc, '_>,
+        bitmap_data: Rc<RefCell<crate::bitmap_data::BitmapData>>,
+        pixel_snapping: PixelSnapping,
+        smoothing: bool,
+    ) -> Self {
+        let (bitmap_handle, width, height) = {
+            let mut pixels = bitmap_data.borrow_mut();
+            (
+                pixels.update_texture(context.renderer),
+                pixels.width() as u16,
+                pixels.height() as u16,
+            )
+        };
+
+        Bitmap(GcCell::allocate(
+            context.gc_context,
+            BitmapData {
+                base: Default::default(),
+                static_data: Gc::allocate(
+                    context.gc_context,
+                    BitmapStatic {
+                        id: 0,
+                        bitmap_handle,
+                        width,
+                        height,
+                        source: None,
+                    },
+                ),
+                bitmap_data: Some(bitmap_data),
+                pixel_snapping,
+                smoothing,
+            },
+        ))
+    }
+
+    /// Decodes the pixels of this library bitmap from the tag that defined it, for
+    /// `BitmapData.loadBitmap`.
+    ///
+    /// The renderer keeps only its own copy of each bitmap, so this decodes the tag again
+    /// from `movie`, which must be the movie that defined this bitmap. `DefineBits` tags need
+    /// the movie's `JPEGTables`.
+    pub fn decode(
+        self,
+        movie: Arc<SwfMovie>,
+        jpeg_tables: Option<&[u8]>,
+    ) -> Result<crate::bitmap_data::BitmapData, Box<dyn std::error::Error>> {
+        let source = self
+            .0
+            .read()
+            .static_data
+            .source
+            .clone()
+            .ok_or("Bitmap has no tag to decode")?;
+        if source.end > movie.data().len() {
+            return Err("Bitmap tag is outside of the movie".into());
+        }
+        let tag = SwfSlice {
+            movie,
+            start: source.start,
+            end: source.end,
+        };
+        let data = tag.data();
+        let jpeg_data = data.get(2..).ok_or("Bitmap tag is too short")?;
+
+        let (width, height, transparent, rgba) = match source.tag_code {
+            TagCode::DefineBits => {
+                let (width, height, rgba) =
+                    jpeg_to_rgba(&glue_tables_to_jpeg(jpeg_data, jpeg_tables))?;
+                (width, height, false, rgba)
+            }
+            TagCode::DefineBitsJpeg2 => {
+                let (width, height, rgba) = jpeg_to_rgba(jpeg_data)?;
+                (width, height, false, rgba)
+            }
+            TagCode::DefineBitsJpeg3 | TagCode::DefineBitsJpeg4 => {
+                let jpeg_len = match data.get(2..6) {
+                    Some(&[a, b, c, d]) => u32::from_le_bytes([a, b, c, d]) as usize,
+                    _ => return Err("Bitmap tag is too short".into()),
+                };
+                // DefineBitsJpeg4 has a deblocking parameter before the JPEG data.
+                let jpeg_start = if source.tag_code == TagCode::DefineBitsJpeg4 {
+                    8
+                } else {
+                    6
+                };
+                let alpha_start = jpeg_start + jpeg_len;
+                let (jpeg_data, alpha_data) =
+                    match (data.get(jpeg_start..alpha_start), data.get(alpha_start..)) {
+                        (Some(jpeg_data), Some(alpha_data)) => (jpeg_data, alpha_data),
+                        _ => return Err("Bitmap tag is too short".into()),
+                    };
+                let (width, height, rgba) = define_bits_jpeg_to_rgba(jpeg_data, alpha_data)?;
+                (width, height, true, rgba)
+            }
+            TagCode::DefineBitsLossless | TagCode::DefineBitsLossless2 => {
+                let version = if source.tag_code == TagCode::DefineBitsLossless2 {
+                    2
+                } else {
+                    1
+                };
+                let tag = tag.read_from(0).read_define_bits_lossless(version)?;
+                let rgba = define_bits_lossless_to_rgba(&tag)?;
+                (tag.width.into(), tag.height.into(), version == 2, rgba)
+            }
+            _ => return Err(format!("{:?} doesn't define a bitmap", source.tag_code).into()),
+        };
+
+        Ok(crate::bitmap_data::BitmapData::from_premultiplied_rgba(
+            width,
+            height,
+            transparent,
+            &rgba,
+        ))
+    }
+
     #[allow(dead_code)]
     pub fn bitmap_handle(self) -> BitmapHandle {
         self.0.read().static_data.bitmap_handle
@@ -90,17 +231,38 @@ impl<'gc> TDisplayObject<'gc> for Bitmap<'gc> {
             return;
         }
 
-        context.transform_stack.push(&*self.transform());
+        context.transform_stack.push(&self.transform());
 
-        context.renderer.render_bitmap(
-            self.0.read().static_data.bitmap_handle,
-            context.transform_stack.transform(),
-        );
+        let bitmap = self.0.read();
+        let bitmap_handle = match &bitmap.bitmap_data {
+            Some(bitmap_data) => bitmap_data.borrow_mut().update_texture(context.renderer),
+            None => bitmap.static_data.bitmap_handle,
+        };
+
+        let mut transform = context.transform_stack.transform().clone();
+        if bitmap.pixel_snapping.snaps(&transform.matrix) {
+            let snap = |twips: Twips| Twips::from_pixels(twips.to_pixels().round());
+            transform.matrix.tx = snap(transform.matrix.tx);
+            transform.matrix.ty = snap(transform.matrix.ty);
+        }
+        context
+            .renderer
+            .render_bitmap(bitmap_handle, &transform, bitmap.smoothing);
 
         context.transform_stack.pop();
     }
 }
 
+impl fmt::Debug for BitmapData<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BitmapData")
+            .field("base", &self.base)
+            .field("pixel_snapping", &self.pixel_snapping)
+            .field("smoothing", &self.smoothing)
+            .finish()
+    }
+}
+
 unsafe impl<'gc> gc_arena::Collect for BitmapData<'gc> {
     fn trace(&self, cc: gc_arena::CollectionContext) {
         self.base.trace(cc);
@@ -108,6 +270,63 @@ unsafe impl<'gc> gc_arena::Collect for BitmapData<'gc> {
     }
 }
 
+/// How far from 100% a bitmap may be scaled and still count as untransformed for
+/// `PixelSnapping::Auto`. Flash Player allows 0.1%.
+const SNAPPING_SCALE_TOLERANCE: f32 = 0.001;
+
+/// How a bitmap's position is rounded to whole pixels when it is drawn.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PixelSnapping {
+    /// The bitmap is drawn where it is, including fractions of a pixel.
+    Never,
+
+    /// The bitmap is always moved to the nearest whole pixel.
+    Always,
+
+    /// The bitmap is moved to the nearest whole pixel when it is only translated, so that it
+    /// is drawn exactly as its pixels are. Scaled or rotated bitmaps are drawn where they are.
+    Auto,
+}
+
+impl PixelSnapping {
+    /// Parses the `pixelSnapping` argument of `attachBitmap`, which defaults to `"auto"`.
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "always" => PixelSnapping::Always,
+            "never" => PixelSnapping::Never,
+            _ => PixelSnapping::Auto,
+        }
+    }
+
+    /// Whether a bitmap drawn with the given matrix is moved to a whole pixel.
+    fn snaps(self, matrix: &Matrix) -> bool {
+        match self {
+            PixelSnapping::Never => false,
+            PixelSnapping::Always => true,
+            PixelSnapping::Auto => {
+                (matrix.a - 1.0).abs() <= SNAPPING_SCALE_TOLERANCE
+                    && (matrix.d - 1.0).abs() <= SNAPPING_SCALE_TOLERANCE
+                    && matrix.b.abs() <= SNAPPING_SCALE_TOLERANCE
+                    && matrix.c.abs() <= SNAPPING_SCALE_TOLERANCE
+            }
+        }
+    }
+}
+
+/// The tag that defined a library bitmap, kept so that its pixels can be decoded again.
+///
+/// Only the position of the tag in its movie is kept: the bitmap lives in the library of that
+/// movie, and holding on to the movie itself would keep the library from ever being released.
+#[derive(Clone, Debug)]
+pub struct BitmapSource {
+    pub tag_code: TagCode,
+
+    /// The range of the movie's data holding the body of the tag, starting with the character
+    /// ID.
+    pub start: usize,
+    pub end: usize,
+}
+
 /// Static data shared between all instances of a bitmap.
 #[derive(Clone)]
 struct BitmapStatic {
@@ -115,6 +334,7 @@ struct BitmapStatic {
     bitmap_handle: BitmapHandle,
     width: u16,
     height: u16,
+    source: Option<BitmapSource>,
 }
 
 unsafe impl<'gc> gc_arena::Collect for BitmapStatic {
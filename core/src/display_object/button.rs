@@ -1,3 +1,4 @@
+use crate::avm1::globals::button::attach_virtual_properties;
 use crate::avm1::{Avm1, Object, StageObject, Value};
 use crate::context::{ActionType, RenderContext, UpdateContext};
 use crate::display_object::{DisplayObjectBase, TDisplayObject};
@@ -88,6 +89,22 @@ impl<'gc> Button<'gc> {
             .handle_button_event((*self).into(), avm, context, event)
     }
 
+    /// Whether this button tracks the mouse as a menu item.
+    ///
+    /// Menu buttons can be rolled over and released while the mouse is held down, even if the
+    /// press started somewhere else. Push buttons capture the mouse while they are pressed.
+    pub fn track_as_menu(self) -> bool {
+        self.0.read().tracking == ButtonTracking::Menu
+    }
+
+    pub fn set_track_as_menu(self, gc_context: MutationContext<'gc, '_>, track_as_menu: bool) {
+        self.0.write(gc_context).tracking = if track_as_menu {
+            ButtonTracking::Menu
+        } else {
+            ButtonTracking::Push
+        };
+    }
+
     pub fn set_sounds(self, gc_context: MutationContext<'gc, '_>, sounds: swf::ButtonSounds) {
         let button = self.0.write(gc_context);
         let mut static_data = button.static_data.write(gc_context);
@@ -140,8 +157,12 @@ impl<'gc> TDisplayObject<'gc> for Button<'gc> {
                 context.gc_context,
                 display_object,
                 Some(context.system_prototypes.button),
-            );
-            mc.object = Some(object.into());
+            )
+            .into();
+
+            attach_virtual_properties(context.gc_context, object);
+
+            mc.object = Some(object);
         }
     }
 
@@ -152,7 +173,7 @@ impl<'gc> TDisplayObject<'gc> for Button<'gc> {
     }
 
     fn render(&self, context: &mut RenderContext<'_, 'gc>) {
-        context.transform_stack.push(&*self.transform());
+        context.transform_stack.push(&self.transform());
 
         crate::display_object::render_children(context, &self.0.read().children);
 
@@ -164,6 +185,16 @@ impl<'gc> TDisplayObject<'gc> for Button<'gc> {
         BoundingBox::default()
     }
 
+    fn bounds_with_transform(&self, matrix: &Matrix) -> BoundingBox {
+        // The children of the current state aren't linked as display list children.
+        let mut bounds = self.self_bounds().transform(matrix);
+        for child in self.0.read().children.values() {
+            let matrix = *matrix * child.matrix();
+            bounds.union(&child.bounds_with_transform(&matrix));
+        }
+        bounds
+    }
+
     fn hit_test(&self, point: (Twips, Twips)) -> bool {
         for child in self.0.read().hit_area.values().rev() {
             if child.world_bounds().contains(point) {
@@ -316,6 +347,7 @@ impl<'gc> ButtonData<'gc> {
         let new_state = match event {
             ButtonEvent::RollOut => ButtonState::Up,
             ButtonEvent::RollOver => ButtonState::Over,
+            ButtonEvent::DragOver => ButtonState::Down,
             ButtonEvent::Press => ButtonState::Down,
             ButtonEvent::Release => ButtonState::Over,
             ButtonEvent::KeyPress { key_code } => {
@@ -349,6 +381,15 @@ impl<'gc> ButtonData<'gc> {
                 self.play_sound(context, self.static_data.read().down_to_over_sound.as_ref());
                 Some("onRelease")
             }
+            // Only menu buttons see the mouse come and go while it's held down.
+            (ButtonState::Up, ButtonState::Down) => {
+                self.run_actions(context, swf::ButtonActionCondition::IdleToOverDown, None);
+                Some("onDragOver")
+            }
+            (ButtonState::Down, ButtonState::Up) => {
+                self.run_actions(context, swf::ButtonActionCondition::OverDownToIdle, None);
+                Some("onDragOut")
+            }
             _ => None,
         };
 
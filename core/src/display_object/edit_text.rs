@@ -1,14 +1,16 @@
 //! `EditText` display object and support code.
 use crate::avm1::globals::text_field::attach_virtual_properties;
-use crate::avm1::{Avm1, Object, StageObject, Value};
+use crate::avm1::property::Attribute;
+use crate::avm1::{Avm1, Object, ScriptObject, StageObject, TObject, Value};
 use crate::context::{RenderContext, UpdateContext};
 use crate::display_object::{DisplayObjectBase, TDisplayObject};
-use crate::font::{Font, Glyph, TextFormat};
+use crate::font::{bidi, Font, FormatSpans, Glyph, TextFormat};
 use crate::library::Library;
 use crate::prelude::*;
 use crate::tag_utils::SwfMovie;
 use crate::transform::Transform;
 use gc_arena::{Collect, Gc, GcCell, MutationContext};
+use std::borrow::Cow;
 use std::sync::Arc;
 
 /// A dynamic text field.
@@ -32,12 +34,21 @@ pub struct EditTextData<'gc> {
     /// Static data shared among all instances of this `EditText`.
     static_data: Gc<'gc, EditTextStatic>,
 
-    /// The current text displayed by this text field.
-    text: String,
+    /// The current text displayed by this text field, along with its formatting.
+    text_spans: FormatSpans,
 
     /// The text formatting for newly inserted text spans.
     new_format: TextFormat,
 
+    /// If the text field's contents are set and read as HTML by `htmlText`.
+    is_html: bool,
+
+    /// If runs of white space in HTML set by `htmlText` are collapsed into a single space.
+    condense_white: bool,
+
+    /// The selected range of text, which is kept while the text field is not focused.
+    selection: TextSelection,
+
     /// If the text is in multi-line mode or single-line mode.
     is_multiline: bool,
 
@@ -60,36 +71,25 @@ impl<'gc> EditText<'gc> {
     ) -> Self {
         let is_multiline = swf_tag.is_multiline;
         let is_word_wrap = swf_tag.is_word_wrap;
+        let is_html = swf_tag.is_html;
 
-        let text = if swf_tag.is_html {
-            let mut result = String::new();
-            let tag_text = swf_tag.initial_text.clone().unwrap_or_default();
-            let mut chars = tag_text.chars().peekable();
-
-            while let Some(c) = chars.next() {
-                // TODO: SWF text fields can contain a limited subset of HTML (and often do in SWF versions >6).
-                // This is a quicky-and-dirty way to skip the HTML tags. This is obviously not correct
-                // and we will need to properly parse and handle the HTML at some point.
-                // See SWF19 pp. 173-174 for supported HTML tags.
-                if c == '<' {
-                    // Skip characters until we see a close bracket.
-                    chars.by_ref().find(|&x| x == '>');
-                } else {
-                    result.push(c);
-                }
-            }
-
-            result
+        let default_format = default_text_format(context, swf_movie.clone(), &swf_tag);
+        let initial_text = swf_tag.initial_text.clone().unwrap_or_default();
+        let text_spans = if is_html {
+            FormatSpans::from_html(&initial_text, &default_format, false)
         } else {
-            swf_tag.initial_text.clone().unwrap_or_default()
+            FormatSpans::new(initial_text, default_format.clone())
         };
 
         EditText(GcCell::allocate(
             context.gc_context,
             EditTextData {
                 base: Default::default(),
-                text,
-                new_format: TextFormat::default(),
+                text_spans,
+                new_format: default_format,
+                is_html,
+                condense_white: false,
+                selection: TextSelection::default(),
                 static_data: gc_arena::Gc::allocate(
                     context.gc_context,
                     EditTextStatic {
@@ -124,7 +124,7 @@ impl<'gc> EditText<'gc> {
             },
             font_id: None,
             font_class_name: None,
-            height: Some(Twips::from_pixels(height)),
+            height: Some(Twips::from_pixels(12.0)),
             color: Some(swf::Color {
                 r: 0,
                 g: 0,
@@ -156,23 +156,115 @@ impl<'gc> EditText<'gc> {
         Self::from_swf_tag(context, swf_movie, swf_tag)
     }
 
-    // TODO: This needs to strip away HTML
     pub fn text(self) -> String {
-        self.0.read().text.to_owned()
+        self.0.read().text_spans.text().to_owned()
     }
 
+    /// Replace the text of this field, formatting it with the new text format.
     pub fn set_text(self, text: String, gc_context: MutationContext<'gc, '_>) {
-        self.0.write(gc_context).cached_break_points = None;
-        self.0.write(gc_context).text = text;
+        let mut edit_text = self.0.write(gc_context);
+        edit_text.text_spans = FormatSpans::new(text, edit_text.new_format.clone());
+        edit_text.cached_break_points = None;
+        edit_text.clamp_selection();
+    }
+
+    /// The contents of this field as returned by `htmlText`.
+    /// This is the plain text if the field is not an HTML field.
+    pub fn html_text(self) -> String {
+        let edit_text = self.0.read();
+        if edit_text.is_html {
+            edit_text.text_spans.to_html()
+        } else {
+            edit_text.text_spans.text().to_owned()
+        }
+    }
+
+    /// Replace the contents of this field as set by `htmlText`.
+    /// The HTML is only parsed if this is an HTML field.
+    pub fn set_html_text(self, html: String, gc_context: MutationContext<'gc, '_>) {
+        if self.is_html() {
+            let mut edit_text = self.0.write(gc_context);
+            edit_text.text_spans =
+                FormatSpans::from_html(&html, &edit_text.new_format, edit_text.condense_white);
+            edit_text.cached_break_points = None;
+            edit_text.clamp_selection();
+        } else {
+            self.set_text(html, gc_context);
+        }
+    }
+
+    pub fn is_html(self) -> bool {
+        self.0.read().is_html
+    }
+
+    pub fn set_is_html(self, is_html: bool, gc_context: MutationContext<'gc, '_>) {
+        self.0.write(gc_context).is_html = is_html;
+    }
+
+    pub fn condense_white(self) -> bool {
+        self.0.read().condense_white
+    }
+
+    /// Set whether white space is collapsed in HTML set later by `htmlText`.
+    /// The current text is unchanged.
+    pub fn set_condense_white(self, condense_white: bool, gc_context: MutationContext<'gc, '_>) {
+        self.0.write(gc_context).condense_white = condense_white;
+    }
+
+    /// Replace the characters from `from` up to `to` with `text`, formatted with the new text
+    /// format. Out of range indices are clamped to the text.
+    pub fn replace_text(
+        self,
+        from: usize,
+        to: usize,
+        text: &str,
+        gc_context: MutationContext<'gc, '_>,
+    ) {
+        let mut edit_text = self.0.write(gc_context);
+        let format = edit_text.new_format.clone();
+        edit_text.text_spans.replace_text(from, to, text, &format);
+        edit_text.cached_break_points = None;
+        edit_text.clamp_selection();
+    }
+
+    /// Replace the selected text with `text`, leaving the caret after the inserted text.
+    pub fn replace_selection(self, text: &str, gc_context: MutationContext<'gc, '_>) {
+        let selection = self.selection();
+        self.replace_text(selection.begin, selection.end, text, gc_context);
+
+        let caret = selection.begin + text.chars().count();
+        self.set_selection(caret, caret, gc_context);
+    }
+
+    pub fn selection(self) -> TextSelection {
+        self.0.read().selection
+    }
+
+    /// Select the characters from `begin` up to `end`, leaving the caret at `end`.
+    /// Out of range indices are clamped to the text.
+    pub fn set_selection(self, begin: usize, end: usize, gc_context: MutationContext<'gc, '_>) {
+        let mut edit_text = self.0.write(gc_context);
+        edit_text.selection = TextSelection {
+            begin: begin.min(end),
+            end: begin.max(end),
+            caret: end,
+        };
+        edit_text.clamp_selection();
+    }
+
+    pub fn is_selectable(self) -> bool {
+        self.0.read().static_data.text.is_selectable
     }
 
     pub fn new_text_format(self) -> TextFormat {
         self.0.read().new_format.clone()
     }
 
+    /// Set the format of newly inserted text.
+    /// Properties that are not set in `tf` are left unchanged.
     pub fn set_new_text_format(self, tf: TextFormat, gc_context: MutationContext<'gc, '_>) {
         self.0.write(gc_context).cached_break_points = None;
-        self.0.write(gc_context).new_format = tf;
+        self.0.write(gc_context).new_format.merge_with(tf);
     }
 
     pub fn is_multiline(self) -> bool {
@@ -269,6 +361,32 @@ impl<'gc> EditText<'gc> {
         base_width
     }
 
+    /// The horizontal offset of a right-to-left line of the given width from
+    /// the left edge of the text, according to the alignment of this
+    /// `EditText`.
+    ///
+    /// As in Flash Player, the alignment does not depend on the direction of
+    /// the text: right-to-left text is still aligned to the left by default.
+    /// Left-to-right lines are not offset yet.
+    fn alignment_offset(self, width: Twips) -> Twips {
+        let align = self
+            .0
+            .read()
+            .static_data
+            .text
+            .layout
+            .as_ref()
+            .map(|layout| layout.align)
+            .unwrap_or(swf::TextAlign::Left);
+
+        let free_space = self.line_width() - width;
+        match align {
+            swf::TextAlign::Right => free_space,
+            swf::TextAlign::Center => Twips::new(free_space.get() / 2),
+            swf::TextAlign::Left | swf::TextAlign::Justify => Twips::new(0),
+        }
+    }
+
     /// Compute all "break points" between lines.
     ///
     /// The breakpoints are the character indicies of every point in the string
@@ -301,7 +419,7 @@ impl<'gc> EditText<'gc> {
                     .height
                     .unwrap_or_else(|| Twips::from_pixels(font.scale().into()));
 
-                for natural_line in edit_text.text.split('\n') {
+                for natural_line in edit_text.text_spans.text().split('\n') {
                     if break_base != 0 {
                         breakpoints.push(break_base);
                     }
@@ -360,22 +478,13 @@ impl<'gc> EditText<'gc> {
         let mut size: (Twips, Twips) = Default::default();
 
         if let Some(font) = self.font(context.library) {
-            let mut start = 0;
-            let mut chunks = vec![];
-            for breakpoint in breakpoints {
-                chunks.push(&edit_text.text[start..breakpoint]);
-                start = breakpoint;
-            }
-
-            chunks.push(&edit_text.text[start..]);
-
             let height = static_data
                 .text
                 .height
                 .unwrap_or_else(|| Twips::from_pixels(font.scale().into()));
 
-            for chunk in chunks {
-                let chunk_size = font.measure(chunk, height);
+            for (line, _) in display_lines(&edit_text.text_spans, &breakpoints, font) {
+                let chunk_size = font.measure(&line, height);
 
                 size.0 = size.0.max(chunk_size.0);
                 if let Some(layout) = &static_data.text.layout {
@@ -388,26 +497,165 @@ impl<'gc> EditText<'gc> {
         size
     }
 
-    /// Returns the device font if this is text field should not use outline glyphs,
-    /// or if the font is not found.
+    /// Returns the font registered by the host of the player under the name of this text
+    /// field's font, if any. Otherwise, returns the device font if this text field should not
+    /// use outline glyphs, or if the font is not found.
+    ///
+    /// If the device font lacks glyphs for some of the text, a registered font that has them
+    /// all is used instead, as Flash Player falls back to other fonts of the system.
     fn font(self, library: &Library<'gc>) -> Option<Font<'gc>> {
-        let static_data = self.0.read().static_data;
-        let library = library.library_for_movie(static_data.swf.clone()).unwrap();
-        if static_data.text.is_device_font {
+        let edit_text = self.0.read();
+        let registered_font = edit_text
+            .text_spans
+            .format_at(0)
+            .font
+            .as_ref()
+            .and_then(|name| library.registered_font(name));
+        if registered_font.is_some() {
+            return registered_font;
+        }
+
+        let static_data = edit_text.static_data;
+        let movie_library = library.library_for_movie(static_data.swf.clone()).unwrap();
+        let device_font = if static_data.text.is_device_font {
             // We're cheating a bit and not actually rendering "device text" using the OS/web.
             // Instead, we embed an SWF version of Noto Sans to use as the "device font", and render
             // it the same as any other SWF outline text.
-            library.device_font()
+            movie_library.device_font()
         } else {
             let font_id = static_data.text.font_id.unwrap_or_default();
-            library
+            let font = movie_library
                 .get_font(font_id)
-                .filter(|font| font.has_glyphs())
-                .or_else(|| library.device_font())
+                .filter(|font| font.has_glyphs());
+            if font.is_some() {
+                return font;
+            }
+            movie_library.device_font()
+        };
+
+        let text = edit_text.text_spans.text();
+        match device_font {
+            Some(font) if !font.has_glyphs_for(text) => library
+                .registered_font_with_glyphs_for(text)
+                .or(device_font),
+            _ => device_font,
         }
     }
 }
 
+impl<'gc> EditTextData<'gc> {
+    /// Keep the selection within the text after the text changes.
+    fn clamp_selection(&mut self) {
+        let length = self.text_spans.len_chars();
+        self.selection.begin = self.selection.begin.min(length);
+        self.selection.end = self.selection.end.min(length);
+        self.selection.caret = self.selection.caret.min(length);
+    }
+}
+
+/// A range of selected characters in a text field.
+///
+/// Indices count characters, and `begin` is never after `end`. The caret is at one end of the
+/// selection, depending on the direction it was made in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TextSelection {
+    pub begin: usize,
+    pub end: usize,
+    pub caret: usize,
+}
+
+/// The format of a text field's text before any formatting is applied, from its
+/// `DefineEditText` tag.
+fn default_text_format(
+    context: &UpdateContext<'_, '_, '_>,
+    swf_movie: Arc<SwfMovie>,
+    swf_tag: &swf::EditText,
+) -> TextFormat {
+    // Device text without a font uses the default font of the Flash IDE.
+    let font = swf_tag
+        .font_id
+        .and_then(|font_id| {
+            context
+                .library
+                .library_for_movie(swf_movie)?
+                .get_font(font_id)
+        })
+        .map(|font| font.name().to_string())
+        .unwrap_or_else(|| "Times New Roman".to_string());
+    let color = swf_tag.color.clone().unwrap_or(swf::Color {
+        r: 0,
+        g: 0,
+        b: 0,
+        a: 0xFF,
+    });
+    let layout = swf_tag.layout.clone().unwrap_or(swf::TextLayout {
+        align: swf::TextAlign::Left,
+        left_margin: Twips::new(0),
+        right_margin: Twips::new(0),
+        indent: Twips::new(0),
+        leading: Twips::new(0),
+    });
+
+    TextFormat {
+        font: Some(font),
+        size: Some(swf_tag.height.map(Twips::to_pixels).unwrap_or(12.0)),
+        color: Some(color),
+        align: Some(layout.align),
+        bold: Some(false),
+        italic: Some(false),
+        underline: Some(false),
+        left_margin: Some(layout.left_margin.to_pixels()),
+        right_margin: Some(layout.right_margin.to_pixels()),
+        indent: Some(layout.indent.to_pixels()),
+        block_indent: Some(0.0),
+        kerning: Some(false),
+        leading: Some(layout.leading.to_pixels()),
+        letter_spacing: Some(0.0),
+        tab_stops: Some(vec![]),
+        bullet: Some(false),
+        url: Some("".to_string()),
+        target: Some("".to_string()),
+        direction: None,
+    }
+}
+
+/// Split text into lines at the given break points, and convert each line
+/// into display order for the given font, along with whether the line is in a
+/// right-to-left paragraph.
+///
+/// Each paragraph takes its direction from the `DIR` attribute of its HTML
+/// `P` tag, or failing that, from its first strongly directional character.
+fn display_lines<'a>(
+    text_spans: &'a FormatSpans,
+    breakpoints: &[usize],
+    font: Font<'_>,
+) -> Vec<(Cow<'a, str>, bool)> {
+    let text = text_spans.text();
+    let mut starts = vec![0];
+    starts.extend_from_slice(breakpoints);
+
+    let mut lines = vec![];
+    for (i, &start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or_else(|| text.len());
+        let line = text[start..end].trim_end_matches('\n');
+
+        let paragraph_start = text[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let paragraph_end = text[start..]
+            .find('\n')
+            .map(|i| start + i)
+            .unwrap_or_else(|| text.len());
+        let is_rtl = match text_spans.format_at(paragraph_start).direction {
+            Some(direction) => direction == bidi::Direction::RightToLeft,
+            None => bidi::is_rtl_paragraph(&text[paragraph_start..paragraph_end]),
+        };
+
+        let line = bidi::display_line(line, is_rtl, |c| font.get_glyph_for_char(c).is_some());
+        lines.push((line, is_rtl));
+    }
+
+    lines
+}
+
 impl<'gc> TDisplayObject<'gc> for EditText<'gc> {
     impl_display_object!(base);
 
@@ -429,14 +677,14 @@ impl<'gc> TDisplayObject<'gc> for EditText<'gc> {
 
     fn post_instantiation(
         &mut self,
-        _avm: &mut Avm1<'gc>,
+        avm: &mut Avm1<'gc>,
         context: &mut UpdateContext<'_, 'gc, '_>,
         display_object: DisplayObject<'gc>,
         _init_object: Option<Object<'gc>>,
     ) {
         let mut text = self.0.write(context.gc_context);
         if text.object.is_none() {
-            let object = StageObject::for_display_object(
+            let object: Object<'gc> = StageObject::for_display_object(
                 context.gc_context,
                 display_object,
                 Some(context.system_prototypes.text_field),
@@ -445,6 +693,16 @@ impl<'gc> TDisplayObject<'gc> for EditText<'gc> {
 
             attach_virtual_properties(context.gc_context, object);
 
+            // Text fields start out listening to themselves.
+            let listeners = ScriptObject::array(context.gc_context, Some(avm.prototypes().array));
+            listeners.set_array_element(0, object.into(), context.gc_context);
+            object.define_value(
+                context.gc_context,
+                "_listeners",
+                Value::Object(listeners.into()),
+                Attribute::DontEnum.into(),
+            );
+
             text.object = Some(object);
         }
     }
@@ -462,7 +720,7 @@ impl<'gc> TDisplayObject<'gc> for EditText<'gc> {
     }
 
     fn render(&self, context: &mut RenderContext<'_, 'gc>) {
-        context.transform_stack.push(&*self.transform());
+        context.transform_stack.push(&self.transform());
 
         let mut text_transform = self.text_transform();
 
@@ -483,29 +741,22 @@ impl<'gc> TDisplayObject<'gc> for EditText<'gc> {
                 .cached_break_points
                 .clone()
                 .unwrap_or_else(|| self.line_breaks(context.library));
-            let mut start = 0;
-            let mut chunks = vec![];
-            for breakpoint in breakpoints {
-                chunks.push(&edit_text.text[start..breakpoint]);
-                start = breakpoint;
-            }
 
-            chunks.push(&edit_text.text[start..]);
-
-            for chunk in chunks {
-                font.evaluate(
-                    chunk,
-                    text_transform.clone(),
-                    height,
-                    |transform, glyph: &Glyph| {
-                        // Render glyph.
-                        context.transform_stack.push(transform);
-                        context
-                            .renderer
-                            .render_shape(glyph.shape, context.transform_stack.transform());
-                        context.transform_stack.pop();
-                    },
-                );
+            for (line, is_rtl) in display_lines(&edit_text.text_spans, &breakpoints, font) {
+                let mut line_transform = text_transform.clone();
+                if is_rtl {
+                    line_transform.matrix.tx +=
+                        self.alignment_offset(font.advance_width(&line, height));
+                }
+
+                font.evaluate(&line, line_transform, height, |transform, glyph: &Glyph| {
+                    // Render glyph.
+                    context.transform_stack.push(transform);
+                    context
+                        .renderer
+                        .render_shape(glyph.shape, context.transform_stack.transform());
+                    context.transform_stack.pop();
+                });
 
                 text_transform = self.newline(height, text_transform);
             }
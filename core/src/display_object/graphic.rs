@@ -30,6 +30,11 @@ impl<'gc> Graphic<'gc> {
             },
         ))
     }
+
+    /// The handle of the shape registered with the renderer.
+    pub fn shape_handle(self) -> ShapeHandle {
+        self.0.read().static_data.render_handle
+    }
 }
 
 impl<'gc> TDisplayObject<'gc> for Graphic<'gc> {
@@ -48,7 +53,7 @@ impl<'gc> TDisplayObject<'gc> for Graphic<'gc> {
         let mut bounds = self.local_bounds();
         let mut node = self.parent();
         while let Some(display_object) = node {
-            bounds = bounds.transform(&*display_object.matrix());
+            bounds = bounds.transform(&display_object.matrix());
             node = display_object.parent();
         }
         bounds
@@ -64,7 +69,7 @@ impl<'gc> TDisplayObject<'gc> for Graphic<'gc> {
             return;
         }
 
-        context.transform_stack.push(&*self.transform());
+        context.transform_stack.push(&self.transform());
 
         context.renderer.render_shape(
             self.0.read().static_data.render_handle,
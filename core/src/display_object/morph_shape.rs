@@ -39,6 +39,17 @@ impl<'gc> MorphShape<'gc> {
     pub fn set_ratio(&mut self, gc_context: MutationContext<'gc, '_>, ratio: u16) {
         self.0.write(gc_context).ratio = ratio;
     }
+
+    /// The handles of the shapes registered with the renderer for each precalculated ratio.
+    pub fn shape_handles(self) -> Vec<ShapeHandle> {
+        self.0
+            .read()
+            .static_data
+            .frames
+            .values()
+            .map(|frame| frame.shape)
+            .collect()
+    }
 }
 
 impl<'gc> TDisplayObject<'gc> for MorphShape<'gc> {
@@ -57,7 +68,7 @@ impl<'gc> TDisplayObject<'gc> for MorphShape<'gc> {
     }
 
     fn render(&self, context: &mut RenderContext) {
-        context.transform_stack.push(&*self.transform());
+        context.transform_stack.push(&self.transform());
 
         if let Some(frame) = self.0.read().static_data.frames.get(&self.ratio()) {
             context
@@ -4,7 +4,8 @@ use crate::backend::audio::AudioStreamHandle;
 use crate::character::Character;
 use crate::context::{ActionType, RenderContext, UpdateContext};
 use crate::display_object::{
-    Bitmap, Button, DisplayObjectBase, EditText, Graphic, MorphShapeStatic, TDisplayObject, Text,
+    Bitmap, BitmapSource, Button, DisplayObjectBase, EditText, Graphic, MorphShapeStatic,
+    TDisplayObject, Text,
 };
 use crate::events::{ButtonKeyCode, ClipEvent};
 use crate::font::Font;
@@ -155,11 +156,11 @@ impl<'gc> MovieClip<'gc> {
             TagCode::DefineBitsLossless => self
                 .0
                 .write(context.gc_context)
-                .define_bits_lossless(context, reader, 1),
+                .define_bits_lossless(context, reader, tag_len, 1),
             TagCode::DefineBitsLossless2 => self
                 .0
                 .write(context.gc_context)
-                .define_bits_lossless(context, reader, 2),
+                .define_bits_lossless(context, reader, tag_len, 2),
             TagCode::DefineButton => self
                 .0
                 .write(context.gc_context)
@@ -193,6 +194,14 @@ impl<'gc> MovieClip<'gc> {
                 .write(context.gc_context)
                 .define_font_3(context, reader),
             TagCode::DefineFont4 => unimplemented!(),
+            TagCode::DefineFontInfo => self
+                .0
+                .write(context.gc_context)
+                .define_font_info(context, reader, tag_len, 1),
+            TagCode::DefineFontInfo2 => self
+                .0
+                .write(context.gc_context)
+                .define_font_info(context, reader, tag_len, 2),
             TagCode::DefineMorphShape => self.0.write(context.gc_context).define_morph_shape(
                 context,
                 reader,
@@ -240,6 +249,10 @@ impl<'gc> MovieClip<'gc> {
                 .0
                 .write(context.gc_context)
                 .define_text(context, reader, 2),
+            TagCode::DoAbc => self
+                .0
+                .write(context.gc_context)
+                .preload_do_abc(context, reader),
             TagCode::DoInitAction => self.do_init_action(avm, context, reader, tag_len),
             TagCode::ExportAssets => self
                 .0
@@ -368,7 +381,6 @@ impl<'gc> MovieClip<'gc> {
         Ok(())
     }
 
-    #[allow(dead_code)]
     pub fn playing(self) -> bool {
         self.0.read().playing()
     }
@@ -383,6 +395,20 @@ impl<'gc> MovieClip<'gc> {
         self.0.write(context.gc_context).play()
     }
 
+    /// Whether this clip goes back to its first frame after playing its last one.
+    pub fn is_looping(self) -> bool {
+        !self.0.read().flags.contains(MovieClipFlags::NoLoop)
+    }
+
+    pub fn set_looping(self, gc_context: MutationContext<'gc, '_>, value: bool) {
+        let flags = &mut self.0.write(gc_context).flags;
+        if value {
+            flags.remove(MovieClipFlags::NoLoop);
+        } else {
+            flags.insert(MovieClipFlags::NoLoop);
+        }
+    }
+
     pub fn prev_frame(self, avm: &mut Avm1<'gc>, context: &mut UpdateContext<'_, 'gc, '_>) {
         if self.current_frame() > 1 {
             self.goto_frame(avm, context, self.current_frame() - 1, true);
@@ -420,6 +446,11 @@ impl<'gc> MovieClip<'gc> {
         self.0.read().static_data.total_frames
     }
 
+    /// The constructor registered for this clip with `Object.registerClass`.
+    pub fn avm1_constructor(self) -> Option<Object<'gc>> {
+        self.0.read().avm1_constructor
+    }
+
     pub fn set_avm1_constructor(
         self,
         gc_context: MutationContext<'gc, '_>,
@@ -436,7 +467,19 @@ impl<'gc> MovieClip<'gc> {
 
     /// Returns the highest depth in use by this movie clip, or `None` if there are no children.
     pub fn highest_depth(self) -> Option<Depth> {
-        self.0.read().children.keys().copied().rev().next()
+        self.0
+            .read()
+            .children
+            .iter()
+            .rev()
+            .find(|(_, child)| !child.removed())
+            .map(|(depth, _)| *depth)
+    }
+
+    /// Returns the child display object at the given depth, if any.
+    /// All display object types (clips, text fields, buttons, etc.) share the same depth space.
+    pub fn child_by_depth(self, depth: Depth) -> Option<DisplayObject<'gc>> {
+        self.0.read().children.get(&depth).copied()
     }
 
     /// Gets the clip events for this movieclip.
@@ -564,6 +607,9 @@ impl<'gc> TDisplayObject<'gc> for MovieClip<'gc> {
     }
 
     fn run_frame(&mut self, avm: &mut Avm1<'gc>, context: &mut UpdateContext<'_, 'gc, '_>) {
+        // Children unloaded during the last frame have been rendered for the last time.
+        self.0.write(context.gc_context).remove_unloaded_children();
+
         // Children must run first.
         for mut child in self.children() {
             child.run_frame(avm, context);
@@ -580,7 +626,7 @@ impl<'gc> TDisplayObject<'gc> for MovieClip<'gc> {
         }
 
         // Run my SWF tags.
-        if mc.playing() {
+        if mc.playing() && !mc.update_audio_stream_buffering(context) {
             mc.run_frame_internal((*self).into(), avm, context, true);
         }
 
@@ -590,7 +636,7 @@ impl<'gc> TDisplayObject<'gc> for MovieClip<'gc> {
     }
 
     fn render(&self, context: &mut RenderContext<'_, 'gc>) {
-        context.transform_stack.push(&*self.transform());
+        context.transform_stack.push(&self.transform());
         crate::display_object::render_children(context, &self.0.read().children);
         context.transform_stack.pop();
     }
@@ -610,6 +656,9 @@ impl<'gc> TDisplayObject<'gc> for MovieClip<'gc> {
         point: (Twips, Twips),
     ) -> Option<DisplayObject<'gc>> {
         for child in self.0.read().children.values().rev() {
+            if child.removed() {
+                continue;
+            }
             let result = child.mouse_pick(*child, point);
             if result.is_some() {
                 return result;
@@ -871,14 +920,14 @@ impl<'gc> MovieClipData<'gc> {
         // Advance frame number.
         if self.current_frame < self.total_frames() {
             self.current_frame += 1;
-        } else if self.total_frames() > 1 {
+        } else if self.total_frames() > 1 && !self.flags.contains(MovieClipFlags::NoLoop) {
             // Looping acts exactly like a gotoAndPlay(1).
             // Specifically, object that existed on frame 1 should not be destroyed
             // and recreated.
             self.run_goto(self_display_object, avm, context, 1);
             return;
         } else {
-            // Single frame clips do not play.
+            // Single frame clips do not play, and clips that don't loop stop at the end.
             self.stop(context);
         }
 
@@ -980,13 +1029,21 @@ impl<'gc> MovieClipData<'gc> {
         }
         self.set_first_child(gc_context, Some(child));
     }
-    /// Removes a child from the execution list.
-    /// This does not affect the render list.
+    /// Removes a child from the execution list, and unloads it.
+    /// This should be called after removing the child from the render list.
+    ///
+    /// Like Flash, an unloaded movie clip is put back on the render list below every other child
+    /// until the next frame. This keeps it intact while its `onUnload` handlers run.
     fn remove_child_from_exec_list(
         &mut self,
         context: &mut UpdateContext<'_, 'gc, '_>,
         mut child: DisplayObject<'gc>,
     ) {
+        // Clips that are already unloading have left the execution list.
+        if child.removed() {
+            return;
+        }
+
         // Remove from children linked list.
         let prev = child.prev_sibling();
         let next = child.next_sibling();
@@ -1003,6 +1060,26 @@ impl<'gc> MovieClipData<'gc> {
         }
         // Flag child as removed.
         child.unload(context);
+
+        if child.as_movie_clip().is_some() {
+            // `getDepth` reports this as -32769 minus the previous depth.
+            let depth = -1 - child.depth();
+            child.set_depth(context.gc_context, depth);
+            self.children.insert(depth, child);
+        }
+    }
+
+    /// Remove the movie clips that were kept on the render list while they unloaded.
+    fn remove_unloaded_children(&mut self) {
+        let depths: SmallVec<[_; 16]> = self
+            .children
+            .iter()
+            .filter(|(_, child)| child.removed())
+            .map(|(depth, _)| *depth)
+            .collect();
+        for depth in depths {
+            self.children.remove(&depth);
+        }
     }
     pub fn run_goto(
         &mut self,
@@ -1350,11 +1427,56 @@ impl<'gc> MovieClipData<'gc> {
         if let Some(audio_stream) = self.audio_stream.take() {
             context.audio.stop_stream(audio_stream);
         }
+        self.flags.remove(MovieClipFlags::AudioStreamBuffering);
+    }
+
+    /// Starts or finishes buffering the audio stream, returning whether the timeline should be
+    /// held on the current frame until it is done.
+    ///
+    /// Like Flash, a stream doesn't start playing until `_soundbuftime` seconds of its audio are
+    /// ready, and goes back to buffering whenever it runs out.
+    fn update_audio_stream_buffering(&mut self, context: &mut UpdateContext<'_, 'gc, '_>) -> bool {
+        let audio_stream = match self.audio_stream {
+            Some(audio_stream) => audio_stream,
+            None => return false,
+        };
+        let buffer_time = f64::from(*context.sound_buffer_time) * 1000.0;
+        let buffered = context.audio.stream_buffered_duration(audio_stream);
+        let is_buffering = self.flags.contains(MovieClipFlags::AudioStreamBuffering);
+        if is_buffering && buffered >= buffer_time {
+            self.flags.remove(MovieClipFlags::AudioStreamBuffering);
+            context.audio.set_stream_buffering(audio_stream, false);
+            false
+        } else if !is_buffering && buffered <= 0.0 && buffer_time > 0.0 {
+            self.flags.insert(MovieClipFlags::AudioStreamBuffering);
+            context.audio.set_stream_buffering(audio_stream, true);
+            true
+        } else {
+            is_buffering
+        }
     }
 
     pub fn movie(&self) -> Arc<SwfMovie> {
         self.static_data.swf.movie.clone()
     }
+
+    /// Keeps a reference to the body of a bitmap tag, so that `BitmapData.loadBitmap` can
+    /// decode it later. `reader` must be at the start of the tag body.
+    fn bitmap_source(
+        &self,
+        reader: &mut SwfStream<&[u8]>,
+        tag_code: swf::TagCode,
+        tag_len: usize,
+    ) -> Option<BitmapSource> {
+        self.static_data
+            .swf
+            .resize_to_reader(reader, tag_len)
+            .map(|data| BitmapSource {
+                tag_code,
+                start: data.start,
+                end: data.end,
+            })
+    }
 }
 
 // Preloading of definition tags
@@ -1364,8 +1486,15 @@ impl<'gc, 'a> MovieClipData<'gc> {
         &mut self,
         context: &mut UpdateContext<'_, 'gc, '_>,
         reader: &mut SwfStream<&'a [u8]>,
+        tag_len: usize,
         version: u8,
     ) -> DecodeResult {
+        let tag_code = if version == 2 {
+            swf::TagCode::DefineBitsLossless2
+        } else {
+            swf::TagCode::DefineBitsLossless
+        };
+        let source = self.bitmap_source(reader, tag_code, tag_len);
         let define_bits_lossless = reader.read_define_bits_lossless(version)?;
         let bitmap_info = context.renderer.register_bitmap_png(&define_bits_lossless);
         let bitmap = crate::display_object::Bitmap::new(
@@ -1374,6 +1503,7 @@ impl<'gc, 'a> MovieClipData<'gc> {
             bitmap_info.handle,
             bitmap_info.width,
             bitmap_info.height,
+            source,
         );
         context
             .library
@@ -1509,6 +1639,7 @@ impl<'gc, 'a> MovieClipData<'gc> {
         tag_len: usize,
     ) -> DecodeResult {
         use std::io::Read;
+        let source = self.bitmap_source(reader, swf::TagCode::DefineBits, tag_len);
         let id = reader.read_u16()?;
         let data_len = tag_len - 2;
         let mut jpeg_data = Vec::with_capacity(data_len);
@@ -1530,6 +1661,7 @@ impl<'gc, 'a> MovieClipData<'gc> {
             bitmap_info.handle,
             bitmap_info.width,
             bitmap_info.height,
+            source,
         );
         context
             .library
@@ -1546,6 +1678,7 @@ impl<'gc, 'a> MovieClipData<'gc> {
         tag_len: usize,
     ) -> DecodeResult {
         use std::io::Read;
+        let source = self.bitmap_source(reader, swf::TagCode::DefineBitsJpeg2, tag_len);
         let id = reader.read_u16()?;
         let data_len = tag_len - 2;
         let mut jpeg_data = Vec::with_capacity(data_len);
@@ -1560,6 +1693,7 @@ impl<'gc, 'a> MovieClipData<'gc> {
             bitmap_info.handle,
             bitmap_info.width,
             bitmap_info.height,
+            source,
         );
         context
             .library
@@ -1576,6 +1710,7 @@ impl<'gc, 'a> MovieClipData<'gc> {
         tag_len: usize,
     ) -> DecodeResult {
         use std::io::Read;
+        let source = self.bitmap_source(reader, swf::TagCode::DefineBitsJpeg3, tag_len);
         let id = reader.read_u16()?;
         let jpeg_len = reader.read_u32()? as usize;
         let alpha_len = tag_len - 6 - jpeg_len;
@@ -1598,6 +1733,7 @@ impl<'gc, 'a> MovieClipData<'gc> {
             bitmap_info.handle,
             bitmap_info.width,
             bitmap_info.height,
+            source,
         );
         context
             .library
@@ -1614,6 +1750,7 @@ impl<'gc, 'a> MovieClipData<'gc> {
         tag_len: usize,
     ) -> DecodeResult {
         use std::io::Read;
+        let source = self.bitmap_source(reader, swf::TagCode::DefineBitsJpeg4, tag_len);
         let id = reader.read_u16()?;
         let jpeg_len = reader.read_u32()? as usize;
         let _deblocking = reader.read_u16()?;
@@ -1637,6 +1774,7 @@ impl<'gc, 'a> MovieClipData<'gc> {
             bitmap_info.handle,
             bitmap_info.width,
             bitmap_info.height,
+            source,
         );
         context
             .library
@@ -1830,6 +1968,34 @@ impl<'gc, 'a> MovieClipData<'gc> {
         Ok(())
     }
 
+    #[inline]
+    fn define_font_info(
+        &mut self,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+        reader: &mut SwfStream<&'a [u8]>,
+        tag_len: usize,
+        version: u8,
+    ) -> DecodeResult {
+        // The code table runs to the end of the tag, so don't let it read past it.
+        use std::io::Read;
+        let mut reader = swf::read::Reader::new(
+            reader.get_mut().take(tag_len as u64),
+            self.static_data.swf.version(),
+        );
+        let font_info = match reader.read_define_font_info(version)? {
+            swf::Tag::DefineFontInfo(font_info) => font_info,
+            _ => unreachable!(),
+        };
+        let library = context.library.library_for_movie_mut(self.movie());
+        if let Some(font) = library.get_font(font_info.id) {
+            let font = font.with_font_info(context.gc_context, &font_info);
+            library.replace_character(font_info.id, Character::Font(font));
+        } else {
+            log::warn!("DefineFontInfo: Font ID {} doesn't exist", font_info.id);
+        }
+        Ok(())
+    }
+
     #[inline]
     fn define_sound(
         &mut self,
@@ -1979,6 +2145,23 @@ impl<'gc, 'a> MovieClipData<'gc> {
         Ok(())
     }
 
+    #[inline]
+    fn preload_do_abc(
+        &mut self,
+        _context: &mut UpdateContext<'_, 'gc, '_>,
+        reader: &mut SwfStream<&'a [u8]>,
+    ) -> DecodeResult {
+        // AVM2 is not supported, so only the AVM1 scripts of hybrid movies run.
+        let _flags = reader.read_u32()?;
+        let name = reader.read_c_string()?;
+        log::warn!(
+            "Skipping DoABC block \"{}\" in movie clip {}: AVM2 is not supported",
+            name,
+            self.id()
+        );
+        Ok(())
+    }
+
     #[inline]
     fn preload_show_frame(
         &mut self,
@@ -2116,13 +2299,13 @@ impl<'gc, 'a> MovieClipData<'gc> {
                         "Invalid slice generated when constructing sound stream block",
                     )
                 })?;
-            let audio_stream = context.audio.start_stream(
-                self.id(),
-                self.current_frame() + 1,
-                slice,
-                &stream_info,
-            );
+            // The current frame has already advanced to the frame holding this block.
+            let audio_stream =
+                context
+                    .audio
+                    .start_stream(self.id(), self.current_frame(), slice, &stream_info);
             self.audio_stream = Some(audio_stream);
+            self.update_audio_stream_buffering(context);
         }
 
         Ok(())
@@ -2298,6 +2481,9 @@ impl GotoPlaceObject {
         if next_place.background_color.is_some() {
             cur_place.background_color = next_place.background_color.take();
         }
+        if !next_place.clip_actions.is_empty() {
+            cur_place.clip_actions = std::mem::take(&mut next_place.clip_actions);
+        }
         // TODO: Other stuff.
     }
 }
@@ -2310,6 +2496,12 @@ enum MovieClipFlags {
 
     /// Whether this `MovieClip` is playing or stopped.
     Playing,
+
+    /// Whether this `MovieClip` is held on its current frame while its audio stream buffers.
+    AudioStreamBuffering,
+
+    /// Whether this `MovieClip` stops at its last frame instead of looping back to the first.
+    NoLoop,
 }
 
 /// Actions that are attached to a `MovieClip` event in
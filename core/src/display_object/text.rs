@@ -59,54 +59,63 @@ impl<'gc> TDisplayObject<'gc> for Text<'gc> {
 
     fn render(&self, context: &mut RenderContext) {
         let tf = self.0.read();
-        context.transform_stack.push(&*self.transform());
+        context.transform_stack.push(&self.transform());
         context.transform_stack.push(&Transform {
             matrix: tf.static_data.text_transform,
             ..Default::default()
         });
 
+        // Text records form a state machine: the font, height, color and position
+        // carry over between records until a record changes them.
         let mut color = swf::Color {
             r: 0,
             g: 0,
             b: 0,
-            a: 0,
+            a: 255,
         };
         let mut font_id = 0;
         let mut height = Twips::new(0);
         let mut transform: Transform = Default::default();
+        let library = context
+            .library
+            .library_for_movie(self.movie().unwrap())
+            .unwrap();
         for block in &tf.static_data.text_blocks {
+            // X/Y offsets are absolute positions in the text's coordinate space, not deltas.
             if let Some(x) = block.x_offset {
                 transform.matrix.tx = x;
             }
             if let Some(y) = block.y_offset {
                 transform.matrix.ty = y;
             }
-            color = block.color.as_ref().unwrap_or(&color).clone();
+            if let Some(block_color) = &block.color {
+                color = block_color.clone();
+            }
             font_id = block.font_id.unwrap_or(font_id);
             height = block.height.unwrap_or(height);
-            if let Some(font) = context
-                .library
-                .library_for_movie(self.movie().unwrap())
-                .unwrap()
-                .get_font(font_id)
-            {
+            transform.color_transform.r_mult = f32::from(color.r) / 255.0;
+            transform.color_transform.g_mult = f32::from(color.g) / 255.0;
+            transform.color_transform.b_mult = f32::from(color.b) / 255.0;
+            transform.color_transform.a_mult = f32::from(color.a) / 255.0;
+
+            let font = library.get_font(font_id);
+            if let Some(font) = font {
+                // Glyph coordinates are in the font's EM square (1024 units for DefineFont1/2,
+                // 20480 for DefineFont3), so scale them to the requested height.
                 let scale = (height.get() as f32) / font.scale();
                 transform.matrix.a = scale;
                 transform.matrix.d = scale;
-                transform.color_transform.r_mult = f32::from(color.r) / 255.0;
-                transform.color_transform.g_mult = f32::from(color.g) / 255.0;
-                transform.color_transform.b_mult = f32::from(color.b) / 255.0;
-                transform.color_transform.a_mult = f32::from(color.a) / 255.0;
-                for c in &block.glyphs {
-                    if let Some(glyph) = font.get_glyph(c.index as usize) {
-                        context.transform_stack.push(&transform);
-                        context
-                            .renderer
-                            .render_shape(glyph.shape, context.transform_stack.transform());
-                        context.transform_stack.pop();
-                        transform.matrix.tx += Twips::new(c.advance);
-                    }
+            }
+            for c in &block.glyphs {
+                if let Some(glyph) = font.and_then(|font| font.get_glyph(c.index as usize)) {
+                    context.transform_stack.push(&transform);
+                    context
+                        .renderer
+                        .render_shape(glyph.shape, context.transform_stack.transform());
+                    context.transform_stack.pop();
                 }
+                // The pen advances even if the glyph is missing from the font.
+                transform.matrix.tx += Twips::new(c.advance);
             }
         }
         context.transform_stack.pop();
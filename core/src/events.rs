@@ -1,15 +1,143 @@
 use num_enum::{IntoPrimitive, TryFromPrimitive};
+use std::convert::TryFrom;
 
 #[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 pub enum PlayerEvent {
-    KeyDown { key_code: KeyCode },
-    KeyUp { key_code: KeyCode },
-    MouseMove { x: f64, y: f64 },
-    MouseUp { x: f64, y: f64 },
-    MouseDown { x: f64, y: f64 },
-    MouseLeft,
-    TextInput { codepoint: char },
+    KeyDown {
+        key_code: KeyCode,
+    },
+    KeyUp {
+        key_code: KeyCode,
+    },
+    MouseMove {
+        x: f64,
+        y: f64,
+    },
+    MouseUp {
+        x: f64,
+        y: f64,
+    },
+    MouseDown {
+        x: f64,
+        y: f64,
+    },
+
+    /// The mouse left the player, such as the window or the canvas it is drawn in.
+    ///
+    /// This notifies Mouse listeners with `onMouseLeave`. The mouse position keeps its last
+    /// value until the mouse moves over the player again.
+    MouseLeave,
+
+    TextInput {
+        codepoint: char,
+    },
+
+    /// Reports the true state of the modifier and lock keys.
+    ///
+    /// Backends send this whenever the host tells them the state, which catches changes made
+    /// while the player did not have focus.
+    ModifiersChanged {
+        modifiers: KeyModifiers,
+    },
+}
+
+/// The state of the modifier and lock keys, as reported by the host.
+///
+/// The lock states are `None` if the host cannot query them.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct KeyModifiers {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+    pub caps_lock: Option<bool>,
+    pub num_lock: Option<bool>,
+}
+
+/// The state of the modifier and lock keys, tracked by the player from input events.
+///
+/// Without a `PlayerEvent::ModifiersChanged` event, the state is inferred from key presses, and
+/// so misses changes made while the player did not have focus. Lock states reported by the host
+/// are used as-is from then on.
+#[derive(Debug, Default, Clone)]
+pub struct KeyboardState {
+    modifiers: KeyModifiers,
+    caps_lock: LockState,
+    num_lock: LockState,
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+struct LockState {
+    is_toggled: bool,
+    is_reported: bool,
+}
+
+impl LockState {
+    fn press(&mut self) {
+        if !self.is_reported {
+            self.is_toggled = !self.is_toggled;
+        }
+    }
+
+    fn report(&mut self, is_toggled: Option<bool>) {
+        if let Some(is_toggled) = is_toggled {
+            self.is_toggled = is_toggled;
+            self.is_reported = true;
+        }
+    }
+}
+
+impl KeyboardState {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Updates the state from an input event.
+    pub fn handle_event(&mut self, event: &PlayerEvent) {
+        match *event {
+            PlayerEvent::KeyDown { key_code } => match key_code {
+                KeyCode::Shift => self.modifiers.shift = true,
+                KeyCode::Control => self.modifiers.control = true,
+                KeyCode::Alt => self.modifiers.alt = true,
+                KeyCode::CapsLock => self.caps_lock.press(),
+                KeyCode::NumLock => self.num_lock.press(),
+                _ => (),
+            },
+            PlayerEvent::KeyUp { key_code } => match key_code {
+                KeyCode::Shift => self.modifiers.shift = false,
+                KeyCode::Control => self.modifiers.control = false,
+                KeyCode::Alt => self.modifiers.alt = false,
+                _ => (),
+            },
+            PlayerEvent::ModifiersChanged { modifiers } => {
+                self.modifiers = modifiers;
+                self.caps_lock.report(modifiers.caps_lock);
+                self.num_lock.report(modifiers.num_lock);
+            }
+            _ => (),
+        }
+    }
+
+    /// Returns whether a modifier key is held, or `None` if `key` is not a modifier key.
+    pub fn is_modifier_down(&self, key: KeyCode) -> Option<bool> {
+        match key {
+            KeyCode::Shift => Some(self.modifiers.shift),
+            KeyCode::Control => Some(self.modifiers.control),
+            KeyCode::Alt => Some(self.modifiers.alt),
+            _ => None,
+        }
+    }
+
+    /// Returns whether a lock key is toggled on.
+    /// Used by the AVM1 `Key.isToggled` call; keys other than Caps Lock and Num Lock are never
+    /// toggled.
+    pub fn is_toggled(&self, key: KeyCode) -> bool {
+        match key {
+            KeyCode::CapsLock => self.caps_lock.is_toggled,
+            KeyCode::NumLock => self.num_lock.is_toggled,
+            _ => false,
+        }
+    }
 }
 
 /// The events that an AVM1 button can fire.
@@ -27,7 +155,11 @@ pub enum ButtonEvent {
     Release,
     RollOut,
     RollOver,
-    KeyPress { key_code: ButtonKeyCode },
+    /// The mouse moved over the button while held down. Only menu buttons receive this.
+    DragOver,
+    KeyPress {
+        key_code: ButtonKeyCode,
+    },
 }
 
 /// Whether this button event was handled by some child.
@@ -69,6 +201,7 @@ pub enum ClipEvent {
 pub enum KeyCode {
     Unknown = 0,
     Backspace = 8,
+    Tab = 9,
     Return = 13,
     Shift = 16,
     Control = 17,
@@ -149,6 +282,7 @@ pub enum KeyCode {
     Insert = 45,
     Delete = 46,
     Pause = 19,
+    NumLock = 144,
     ScrollLock = 145,
     F1 = 112,
     F2 = 113,
@@ -164,6 +298,71 @@ pub enum KeyCode {
     F12 = 123,
 }
 
+/// Resolves the Flash key code reported for a key press.
+///
+/// Flash reports Windows virtual key codes, which follow the keyboard layout for letter and
+/// punctuation keys and the physical position for everything else. For example, the key in the
+/// US "Q" position types 'a' on an AZERTY keyboard and reports `KeyCode::A`, while the digit row
+/// reports `KeyCode::Key1` and so on even though it types symbols unshifted.
+///
+/// `positional` is the key code of the physical key on a US layout, and `character` is the
+/// character the key produced under the current layout, if any.
+pub fn key_code_for_key(positional: KeyCode, character: Option<char>) -> KeyCode {
+    let is_letter_key = (u8::from(KeyCode::A)..=u8::from(KeyCode::Z)).contains(&positional.into());
+    let is_character_key = is_letter_key
+        || match positional {
+            KeyCode::Semicolon
+            | KeyCode::Equals
+            | KeyCode::Comma
+            | KeyCode::Minus
+            | KeyCode::Period
+            | KeyCode::Slash
+            | KeyCode::Grave
+            | KeyCode::LBracket
+            | KeyCode::Backslash
+            | KeyCode::RBracket
+            | KeyCode::Apostrophe => true,
+            _ => false,
+        };
+
+    let layout_key_code = match character {
+        Some(c) if c.is_ascii_alphabetic() => KeyCode::try_from(c.to_ascii_uppercase() as u8).ok(),
+        Some(';') => Some(KeyCode::Semicolon),
+        Some('=') => Some(KeyCode::Equals),
+        Some(',') => Some(KeyCode::Comma),
+        Some('-') => Some(KeyCode::Minus),
+        Some('.') => Some(KeyCode::Period),
+        Some('/') => Some(KeyCode::Slash),
+        Some('`') => Some(KeyCode::Grave),
+        Some('[') => Some(KeyCode::LBracket),
+        Some('\\') => Some(KeyCode::Backslash),
+        Some(']') => Some(KeyCode::RBracket),
+        Some('\'') => Some(KeyCode::Apostrophe),
+        _ => None,
+    };
+
+    match layout_key_code {
+        Some(key_code) if is_character_key => key_code,
+        _ => positional,
+    }
+}
+
+/// The value of `Key.getAscii` for a key press.
+///
+/// This is the character produced by the key, or the control character of keys such as
+/// Enter that produce no printable character.
+pub fn key_ascii(key_code: KeyCode, character: Option<char>) -> u32 {
+    match (character, key_code) {
+        (Some(c), _) => c as u32,
+        (None, KeyCode::Backspace) => 8,
+        (None, KeyCode::Tab) => 9,
+        (None, KeyCode::Return) => 13,
+        (None, KeyCode::Escape) => 27,
+        (None, KeyCode::Delete) => 127,
+        (None, _) => 0,
+    }
+}
+
 /// Key codes for SWF4 keyPress button handlers. These are annoyingly different than
 /// `Key.isDown` key codes.
 /// TODO: After 18, these are mostly ASCII... should we just use u8? How are different
@@ -294,6 +493,7 @@ pub fn key_code_to_button_key_code(key_code: KeyCode) -> Option<ButtonKeyCode> {
         KeyCode::Delete => ButtonKeyCode::Delete,
         KeyCode::Backspace => ButtonKeyCode::Backspace,
         KeyCode::Return => ButtonKeyCode::Return,
+        KeyCode::Tab => ButtonKeyCode::Tab,
         KeyCode::Up => ButtonKeyCode::Up,
         KeyCode::Down => ButtonKeyCode::Down,
         KeyCode::PgUp => ButtonKeyCode::PgUp,
@@ -303,3 +503,82 @@ pub fn key_code_to_button_key_code(key_code: KeyCode) -> Option<ButtonKeyCode> {
     };
     Some(out)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_codes_follow_layout_for_character_keys() {
+        let cases = [
+            // US layout.
+            (KeyCode::A, Some('a'), KeyCode::A),
+            (KeyCode::A, Some('A'), KeyCode::A),
+            (KeyCode::Key1, Some('!'), KeyCode::Key1),
+            (KeyCode::Slash, Some('?'), KeyCode::Slash),
+            // AZERTY: letters move, digits and arrows stay put.
+            (KeyCode::Q, Some('a'), KeyCode::A),
+            (KeyCode::W, Some('z'), KeyCode::Z),
+            (KeyCode::Semicolon, Some('m'), KeyCode::M),
+            (KeyCode::M, Some(','), KeyCode::Comma),
+            (KeyCode::Key1, Some('&'), KeyCode::Key1),
+            (KeyCode::Key2, Some('\u{e9}'), KeyCode::Key2),
+            // Non-ASCII letters keep the key's position.
+            (KeyCode::Apostrophe, Some('\u{f9}'), KeyCode::Apostrophe),
+            // Numpad keys have distinct codes from the digit row.
+            (KeyCode::Numpad1, Some('1'), KeyCode::Numpad1),
+            (KeyCode::NumpadPeriod, Some('.'), KeyCode::NumpadPeriod),
+            (KeyCode::Left, None, KeyCode::Left),
+            (KeyCode::Shift, None, KeyCode::Shift),
+        ];
+
+        for &(positional, character, expected) in &cases {
+            assert_eq!(
+                key_code_for_key(positional, character),
+                expected,
+                "{:?} typing {:?}",
+                positional,
+                character
+            );
+        }
+    }
+
+    #[test]
+    fn lock_state_is_inferred_until_reported() {
+        let press = |state: &mut KeyboardState, key_code| {
+            state.handle_event(&PlayerEvent::KeyDown { key_code });
+            state.handle_event(&PlayerEvent::KeyUp { key_code });
+        };
+
+        let mut state = KeyboardState::new();
+        press(&mut state, KeyCode::CapsLock);
+        assert!(state.is_toggled(KeyCode::CapsLock));
+        press(&mut state, KeyCode::CapsLock);
+        assert!(!state.is_toggled(KeyCode::CapsLock));
+
+        state.handle_event(&PlayerEvent::ModifiersChanged {
+            modifiers: KeyModifiers {
+                caps_lock: Some(true),
+                ..Default::default()
+            },
+        });
+        assert!(state.is_toggled(KeyCode::CapsLock));
+
+        // The host already reports the toggle caused by a press.
+        press(&mut state, KeyCode::CapsLock);
+        assert!(state.is_toggled(KeyCode::CapsLock));
+
+        // Num Lock was never reported, so it is still inferred.
+        press(&mut state, KeyCode::NumLock);
+        assert!(state.is_toggled(KeyCode::NumLock));
+    }
+
+    #[test]
+    fn key_ascii_follows_character() {
+        assert_eq!(key_ascii(KeyCode::A, Some('a')), 97);
+        assert_eq!(key_ascii(KeyCode::A, Some('A')), 65);
+        assert_eq!(key_ascii(KeyCode::Key1, Some('!')), 33);
+        assert_eq!(key_ascii(KeyCode::Return, None), 13);
+        assert_eq!(key_ascii(KeyCode::Left, None), 0);
+    }
+}
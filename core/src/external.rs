@@ -0,0 +1,179 @@
+//! Communication with the page or application hosting a movie
+//!
+//! ActionScript talks to its host through the `ExternalInterface` class: `call` runs a function
+//! provided by the host, and `addCallback` exposes an ActionScript function which the host can run
+//! with `Player::call_internal_interface`. Values cross this boundary as `Value`s, which are
+//! plain trees that don't depend on the object model of either side.
+
+use crate::avm1::globals::external_interface::{external_to_value, value_to_external};
+use crate::avm1::{Activation, Avm1, Object, TObject};
+use crate::context::UpdateContext;
+use gc_arena::{Collect, CollectionContext, GcCell};
+use std::collections::BTreeMap;
+
+type Error = Box<dyn std::error::Error>;
+
+/// A value passed between ActionScript and the host.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Object(BTreeMap<String, Value>),
+    List(Vec<Value>),
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Number(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+/// The host of a movie, as seen by `ExternalInterface`.
+pub trait ExternalInterfaceProvider {
+    /// Call a function of the host with the given arguments.
+    ///
+    /// An error is reported if the function does not exist or fails; ActionScript then
+    /// receives `undefined`.
+    fn call_method(&mut self, name: &str, args: &[Value]) -> Result<Value, Error>;
+
+    /// Called when ActionScript exposes a callback with `addCallback`, so that the host can
+    /// make it callable by its own scripts.
+    fn on_callback_available(&mut self, name: &str);
+}
+
+/// A function exposed to the host by `ExternalInterface.addCallback`.
+#[derive(Clone, Copy, Collect)]
+#[collect(no_drop)]
+pub struct Callback<'gc> {
+    /// The object used as `this` when the function is called.
+    this: Object<'gc>,
+
+    /// The function to call.
+    method: Object<'gc>,
+}
+
+impl<'gc> Callback<'gc> {
+    pub fn new(this: Object<'gc>, method: Object<'gc>) -> Self {
+        Self { this, method }
+    }
+
+    /// Call the function with arguments sent by the host, returning its result.
+    pub fn call(
+        &self,
+        avm: &mut Avm1<'gc>,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+        name: &str,
+        args: &[Value],
+    ) -> Value {
+        let root = match context.levels.get(&0) {
+            Some(root) => *root,
+            None => return Value::Undefined,
+        };
+
+        // Push a stack frame so that the function can run outside of any script.
+        avm.insert_stack_frame(GcCell::allocate(
+            context.gc_context,
+            Activation::from_nothing(
+                context.swf.header().version,
+                avm.global_object_cell(),
+                context.gc_context,
+                root,
+            ),
+        ));
+
+        let args: Vec<_> = args
+            .iter()
+            .map(|arg| external_to_value(avm, context.gc_context, arg))
+            .collect();
+        let result = self
+            .method
+            .call(avm, context, self.this, None, &args)
+            .and_then(|result| result.resolve(avm, context))
+            .and_then(|result| value_to_external(avm, context, result, &mut vec![]));
+        let _ = avm.run_stack_till_empty(context);
+
+        match result {
+            Ok(result) => result,
+            Err(e) => {
+                log::error!("Error in ExternalInterface callback {}: {}", name, e);
+                Value::Undefined
+            }
+        }
+    }
+}
+
+/// The state of `ExternalInterface`: the host, and the callbacks exposed to it.
+#[derive(Default)]
+pub struct ExternalInterface<'gc> {
+    provider: Option<Box<dyn ExternalInterfaceProvider>>,
+    callbacks: BTreeMap<String, Callback<'gc>>,
+}
+
+unsafe impl<'gc> Collect for ExternalInterface<'gc> {
+    #[inline]
+    fn trace(&self, cc: CollectionContext) {
+        self.callbacks.trace(cc);
+    }
+}
+
+impl<'gc> ExternalInterface<'gc> {
+    /// Set the host, and announce the callbacks that were already added to it.
+    pub fn set_provider(&mut self, mut provider: Box<dyn ExternalInterfaceProvider>) {
+        for name in self.callbacks.keys() {
+            provider.on_callback_available(name);
+        }
+        self.provider = Some(provider);
+    }
+
+    /// Whether the movie has a host to talk to.
+    pub fn available(&self) -> bool {
+        self.provider.is_some()
+    }
+
+    /// Expose a callback to the host under the given name, replacing any previous one.
+    pub fn add_callback(&mut self, name: String, callback: Callback<'gc>) {
+        if let Some(provider) = &mut self.provider {
+            provider.on_callback_available(&name);
+        }
+        self.callbacks.insert(name, callback);
+    }
+
+    /// Get the callback exposed under the given name.
+    pub fn get_callback(&self, name: &str) -> Option<Callback<'gc>> {
+        self.callbacks.get(name).copied()
+    }
+
+    /// Remove all callbacks, which belong to a movie that is being unloaded.
+    pub fn clear_callbacks(&mut self) {
+        self.callbacks.clear();
+    }
+
+    /// Call a function of the host.
+    pub fn call_method(&mut self, name: &str, args: &[Value]) -> Result<Value, Error> {
+        match &mut self.provider {
+            Some(provider) => provider.call_method(name, args),
+            None => Err("ExternalInterface is not available".into()),
+        }
+    }
+}
@@ -5,8 +5,12 @@ use gc_arena::{Collect, Gc, MutationContext};
 
 type Error = Box<dyn std::error::Error>;
 
+pub mod bidi;
+mod format_spans;
 mod text_format;
+mod truetype;
 
+pub use format_spans::FormatSpans;
 pub use text_format::TextFormat;
 
 #[derive(Debug, Clone, Collect, Copy)]
@@ -16,6 +20,9 @@ pub struct Font<'gc>(Gc<'gc, FontData>);
 #[derive(Debug, Clone, Collect)]
 #[collect(require_static)]
 struct FontData {
+    /// The name of the font, as given in the `DefineFont` tag.
+    name: String,
+
     /// The list of glyphs defined in the font.
     /// Used directly by `DefineText` tags.
     glyphs: Vec<Glyph>,
@@ -39,12 +46,18 @@ impl<'gc> Font<'gc> {
         renderer: &mut dyn RenderBackend,
         tag: &swf::Font,
     ) -> Result<Font<'gc>, Error> {
+        // DefineFont3 stores coordinates at 20x the scale of DefineFont1/2.
+        // (SWF19 p.164)
+        let scale = if tag.version >= 3 { 20480.0 } else { 1024.0 };
+
         let mut glyphs = vec![];
         let mut code_point_to_glyph = fnv::FnvHashMap::default();
         for swf_glyph in &tag.glyphs {
             let glyph = Glyph {
                 shape: renderer.register_glyph_shape(swf_glyph),
-                advance: swf_glyph.advance.unwrap_or(0),
+                advance: swf_glyph
+                    .advance
+                    .unwrap_or_else(|| glyph_bounds_advance(swf_glyph, scale)),
             };
             let index = glyphs.len();
             glyphs.push(glyph);
@@ -62,23 +75,80 @@ impl<'gc> Font<'gc> {
         Ok(Font(Gc::allocate(
             gc_context,
             FontData {
+                name: tag.name.clone(),
                 glyphs,
                 code_point_to_glyph,
-
-                /// DefineFont3 stores coordinates at 20x the scale of DefineFont1/2.
-                /// (SWF19 p.164)
-                scale: if tag.version >= 3 { 20480.0 } else { 1024.0 },
+                scale,
                 kerning_pairs,
             },
         )))
     }
 
+    /// Creates a font from a TrueType font file, such as one registered by the host of the
+    /// player rather than defined by a movie.
+    ///
+    /// Its glyphs are converted into SWF glyph shapes, so that it is drawn and laid out like a
+    /// `DefineFont3` font.
+    pub fn from_truetype(
+        gc_context: MutationContext<'gc, '_>,
+        renderer: &mut dyn RenderBackend,
+        name: &str,
+        data: &[u8],
+    ) -> Result<Font<'gc>, Error> {
+        let tag = truetype::to_swf_font(name, data)?;
+        Self::from_swf_tag(gc_context, renderer, &tag)
+    }
+
+    /// Applies a `DefineFontInfo` tag to this font, returning the updated font.
+    ///
+    /// `DefineFont` tags only contain glyph shapes; the font name and the character each glyph
+    /// represents come from a separate `DefineFontInfo` tag with the same ID.
+    pub fn with_font_info(
+        self,
+        gc_context: MutationContext<'gc, '_>,
+        info: &swf::FontInfo,
+    ) -> Font<'gc> {
+        let mut data = (*self.0).clone();
+        data.name = info.name.clone();
+        data.code_point_to_glyph = fnv::FnvHashMap::default();
+        for (index, &code) in info.code_table.iter().enumerate().take(data.glyphs.len()) {
+            if let Some(code_point) = font_info_code_to_unicode(info, code) {
+                data.code_point_to_glyph.insert(code_point, index);
+            } else {
+                log::warn!(
+                    "DefineFontInfo: Unsupported character code {:#x} in font {}",
+                    code,
+                    info.id
+                );
+            }
+        }
+        Font(Gc::allocate(gc_context, data))
+    }
+
+    /// Returns the name of this font.
+    pub fn name(&self) -> &str {
+        &self.0.name
+    }
+
     /// Returns whether this font contains glyph shapes.
     /// If not, this font should be rendered as a device font.
     pub fn has_glyphs(self) -> bool {
         !self.0.glyphs.is_empty()
     }
 
+    /// Returns whether this font has a glyph for every character of the given text, other than
+    /// control characters such as line breaks.
+    pub fn has_glyphs_for(self, text: &str) -> bool {
+        text.chars()
+            .filter(|c| !c.is_control())
+            .all(|c| self.get_glyph_for_char(c).is_some())
+    }
+
+    /// The handles of the glyph shapes registered with the renderer.
+    pub fn glyph_shape_handles(self) -> Vec<ShapeHandle> {
+        self.0.glyphs.iter().map(|glyph| glyph.shape).collect()
+    }
+
     /// Returns a glyph entry by index.
     /// Used by `Text` display objects.
     pub fn get_glyph(self, i: usize) -> Option<Glyph> {
@@ -171,6 +241,19 @@ impl<'gc> Font<'gc> {
         size
     }
 
+    /// Measure the width of a line of text, up to the end of the advance of
+    /// its last glyph.
+    pub fn advance_width(self, text: &str, height: Twips) -> Twips {
+        let mut width = Twips::new(0);
+
+        self.evaluate(text, Default::default(), height, |transform, glyph| {
+            let advance = f32::from(glyph.advance) * transform.matrix.a;
+            width = transform.matrix.tx + Twips::new(advance as i32);
+        });
+
+        width
+    }
+
     /// Given a line of text, split it into the shortest number of lines that
     /// are shorter than `width`.
     ///
@@ -221,6 +304,50 @@ impl<'gc> Font<'gc> {
     }
 }
 
+/// Derives the advance of a glyph from its shape, for fonts without layout information.
+///
+/// Fonts defined by `DefineFont` (and `DefineFont2/3` without layout) have no advances,
+/// so each glyph advances by its right edge. Empty glyphs, such as spaces, advance by
+/// a quarter of the EM square.
+fn glyph_bounds_advance(glyph: &swf::Glyph, scale: f32) -> i16 {
+    let bounds = crate::shape_utils::calculate_shape_bounds(&glyph.shape_records);
+    if bounds.x_max > Twips::new(0) {
+        bounds.x_max.get().min(i32::from(i16::MAX)) as i16
+    } else {
+        (scale / 4.0) as i16
+    }
+}
+
+/// Converts a character code from a `DefineFontInfo` code table to a Unicode code point.
+///
+/// `DefineFontInfo2` and SWF6+ `DefineFontInfo` tags use UCS-2 codes. Earlier movies use
+/// the code page given by the tag's ANSI or Shift-JIS flags.
+fn font_info_code_to_unicode(info: &swf::FontInfo, code: u16) -> Option<u16> {
+    if info.version >= 2 || !(info.is_ansi || info.is_shift_jis) {
+        Some(code)
+    } else if info.is_shift_jis {
+        // TODO: Double-byte Shift-JIS codes need a full conversion table.
+        match code {
+            0..=0x7f => Some(code),
+            // Half-width katakana.
+            0xa1..=0xdf => Some(code - 0xa1 + 0xff61),
+            _ => None,
+        }
+    } else {
+        // ANSI is Windows-1252, which only differs from Latin-1 in the 0x80-0x9F range.
+        const WINDOWS_1252: [u16; 32] = [
+            0x20ac, 0x81, 0x201a, 0x192, 0x201e, 0x2026, 0x2020, 0x2021, 0x2c6, 0x2030, 0x160,
+            0x2039, 0x152, 0x8d, 0x17d, 0x8f, 0x90, 0x2018, 0x2019, 0x201c, 0x201d, 0x2022, 0x2013,
+            0x2014, 0x2dc, 0x2122, 0x161, 0x203a, 0x153, 0x9d, 0x17e, 0x178,
+        ];
+        match code {
+            0x80..=0x9f => Some(WINDOWS_1252[usize::from(code - 0x80)]),
+            0..=0xff => Some(code),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Glyph {
     pub shape: ShapeHandle,
@@ -0,0 +1,305 @@
+//! Bidirectional text and Arabic shaping
+//!
+//! Text is stored in logical order, and only converted to display order when a line is laid
+//! out. Like Flash Player, caret positions and selections always refer to the logical order.
+
+use std::borrow::Cow;
+use unicode_bidi::{bidi_class, BidiClass, BidiInfo, Level};
+
+/// The base direction of a paragraph, as set by the `DIR` attribute of an HTML `P` tag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    LeftToRight,
+    RightToLeft,
+}
+
+/// Returns whether a paragraph has a right-to-left base direction.
+///
+/// The direction is that of the first strongly directional character in the paragraph (UAX#9
+/// rules P2 and P3). Paragraphs without any such character are left-to-right.
+pub fn is_rtl_paragraph(paragraph: &str) -> bool {
+    BidiInfo::new(paragraph, None)
+        .paragraphs
+        .first()
+        .map(|para| para.level.is_rtl())
+        .unwrap_or(false)
+}
+
+/// Convert a line of text from logical order into the order in which its glyphs are drawn, from
+/// left to right.
+///
+/// Arabic letters are first replaced with the presentation form for their position in the word.
+/// `has_glyph` reports whether the font can draw a given character; presentation forms that the
+/// font lacks are left unshaped.
+pub fn display_line<'a>(
+    line: &'a str,
+    is_rtl: bool,
+    has_glyph: impl Fn(char) -> bool,
+) -> Cow<'a, str> {
+    let level = if is_rtl { Level::rtl() } else { Level::ltr() };
+    if !BidiInfo::new(line, Some(level)).has_rtl() {
+        return Cow::Borrowed(line);
+    }
+
+    let shaped = shape_arabic(line, has_glyph);
+    let mut result = String::with_capacity(shaped.len());
+
+    // Reorder each paragraph on its own, as levels are only resolved per paragraph.
+    let mut start = 0;
+    for (i, c) in shaped.char_indices() {
+        if bidi_class(c) == BidiClass::B {
+            let end = i + c.len_utf8();
+            reorder_paragraph(&shaped[start..end], level, &mut result);
+            start = end;
+        }
+    }
+    reorder_paragraph(&shaped[start..], level, &mut result);
+
+    result.into()
+}
+
+/// Append a paragraph to `out` in display order, mirroring characters in right-to-left runs.
+fn reorder_paragraph(paragraph: &str, level: Level, out: &mut String) {
+    let info = BidiInfo::new(paragraph, Some(level));
+    for para in &info.paragraphs {
+        let (levels, runs) = info.visual_runs(para, para.range.clone());
+        for run in runs {
+            if levels[run.start].is_rtl() {
+                out.extend(paragraph[run].chars().rev().map(mirror));
+            } else {
+                out.push_str(&paragraph[run]);
+            }
+        }
+    }
+}
+
+/// Returns the mirrored form of a paired punctuation character, for display in right-to-left runs.
+fn mirror(c: char) -> char {
+    match c {
+        '(' => ')',
+        ')' => '(',
+        '<' => '>',
+        '>' => '<',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '«' => '»',
+        '»' => '«',
+        '‹' => '›',
+        '›' => '‹',
+        _ => c,
+    }
+}
+
+const LAM: char = '\u{0644}';
+
+/// How an Arabic character connects to its neighbours.
+#[derive(Clone, Copy, PartialEq)]
+enum Joining {
+    /// Does not connect to either neighbour.
+    None,
+
+    /// Connects only to the preceding letter (which is to its right).
+    Right,
+
+    /// Connects to both neighbours.
+    Dual,
+
+    /// Causes its neighbours to connect to it, but has no forms of its own (e.g. tatweel).
+    Causing,
+
+    /// Is skipped over when connecting letters (e.g. vowel marks).
+    Transparent,
+}
+
+/// Returns how a character connects to its neighbours, and its isolated presentation form.
+///
+/// The final, initial and medial forms follow the isolated form, in that order. Right-joining
+/// letters only have isolated and final forms.
+fn arabic_joining(c: char) -> (Joining, Option<u32>) {
+    let isolated = match c {
+        '\u{0621}' => return (Joining::None, Some(0xFE80)),
+        '\u{0622}' => 0xFE81,
+        '\u{0623}' => 0xFE83,
+        '\u{0624}' => 0xFE85,
+        '\u{0625}' => 0xFE87,
+        '\u{0626}' => 0xFE89,
+        '\u{0627}' => 0xFE8D,
+        '\u{0628}' => 0xFE8F,
+        '\u{0629}' => 0xFE93,
+        '\u{062A}'..='\u{063A}' => {
+            // TEH to GHAIN are laid out in order, DAL to ZAIN being right-joining.
+            const FORMS: [u32; 17] = [
+                0xFE95, 0xFE99, 0xFE9D, 0xFEA1, 0xFEA5, 0xFEA9, 0xFEAB, 0xFEAD, 0xFEAF, 0xFEB1,
+                0xFEB5, 0xFEB9, 0xFEBD, 0xFEC1, 0xFEC5, 0xFEC9, 0xFECD,
+            ];
+            FORMS[c as usize - 0x062A]
+        }
+        '\u{0640}' | '\u{200D}' => return (Joining::Causing, None),
+        '\u{0641}'..='\u{0647}' => 0xFED1 + 4 * (c as u32 - 0x0641),
+        '\u{0648}' => 0xFEED,
+        '\u{0649}' => 0xFEEF,
+        '\u{064A}' => 0xFEF1,
+        '\u{064B}'..='\u{065F}' | '\u{0670}' => return (Joining::Transparent, None),
+        _ => return (Joining::None, None),
+    };
+
+    let joining = match c {
+        '\u{0622}'..='\u{0625}' | '\u{0627}' | '\u{0629}' | '\u{062F}'..='\u{0632}' => {
+            Joining::Right
+        }
+        '\u{0648}' | '\u{0649}' => Joining::Right,
+        _ => Joining::Dual,
+    };
+
+    (joining, Some(isolated))
+}
+
+/// Returns the isolated form of the ligature of LAM followed by the given ALEF, if there is one.
+fn lam_alef_ligature(alef: char) -> Option<u32> {
+    match alef {
+        '\u{0622}' => Some(0xFEF5),
+        '\u{0623}' => Some(0xFEF7),
+        '\u{0625}' => Some(0xFEF9),
+        '\u{0627}' => Some(0xFEFB),
+        _ => None,
+    }
+}
+
+/// Replace Arabic letters with the presentation forms for their position in a word.
+fn shape_arabic(text: &str, has_glyph: impl Fn(char) -> bool) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let joining = |c: char| arabic_joining(c).0;
+    let form = |code: u32| std::char::from_u32(code).filter(|&c| has_glyph(c));
+
+    // The nearest neighbours that aren't transparent to joining.
+    let previous = |i: usize| {
+        chars[..i]
+            .iter()
+            .rev()
+            .copied()
+            .find(|&c| joining(c) != Joining::Transparent)
+    };
+    let next = |i: usize| {
+        chars[i + 1..]
+            .iter()
+            .copied()
+            .find(|&c| joining(c) != Joining::Transparent)
+    };
+
+    let mut result = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let joins_previous = previous(i)
+            .map(|c| matches!(joining(c), Joining::Dual | Joining::Causing))
+            .unwrap_or(false);
+
+        if c == LAM {
+            let ligature = chars
+                .get(i + 1)
+                .and_then(|&alef| lam_alef_ligature(alef))
+                .and_then(|isolated| form(isolated + joins_previous as u32));
+            if let Some(ligature) = ligature {
+                result.push(ligature);
+                i += 2;
+                continue;
+            }
+        }
+
+        let shaped = match arabic_joining(c) {
+            (Joining::Right, Some(isolated)) => form(isolated + joins_previous as u32),
+            (Joining::Dual, Some(isolated)) => {
+                let joins_next = next(i)
+                    .map(|c| {
+                        matches!(
+                            joining(c),
+                            Joining::Right | Joining::Dual | Joining::Causing
+                        )
+                    })
+                    .unwrap_or(false);
+                let offset = match (joins_previous, joins_next) {
+                    (false, false) => 0,
+                    (true, false) => 1,
+                    (false, true) => 2,
+                    (true, true) => 3,
+                };
+                form(isolated + offset)
+            }
+            (_, Some(isolated)) => form(isolated),
+            _ => None,
+        };
+
+        result.push(shaped.unwrap_or(c));
+        i += 1;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paragraph_direction_follows_first_strong_character() {
+        assert!(!is_rtl_paragraph("Hello שלום"));
+        assert!(is_rtl_paragraph("שלום Hello"));
+        assert!(is_rtl_paragraph("123 مرحبا"));
+        assert!(!is_rtl_paragraph("123 !?"));
+    }
+
+    #[test]
+    fn runs_are_reordered() {
+        let all_glyphs = |_| true;
+        assert_eq!(display_line("abc def", false, all_glyphs), "abc def");
+        assert_eq!(display_line("אבג דהו", true, all_glyphs), "והד גבא");
+        assert_eq!(
+            display_line("abc אבג def", false, all_glyphs),
+            "abc גבא def"
+        );
+        assert_eq!(display_line("אבג abc דהו", true, all_glyphs), "והד abc גבא");
+
+        // Numbers keep their digit order, and brackets are mirrored.
+        assert_eq!(display_line("אבג (123)", true, all_glyphs), "(123) גבא");
+    }
+
+    #[test]
+    fn arabic_letters_are_shaped() {
+        let all_glyphs = |_| true;
+
+        // BEH TEH BEH: initial, medial, final; shown right-to-left.
+        assert_eq!(
+            display_line("\u{0628}\u{062A}\u{0628}", true, all_glyphs),
+            "\u{FE90}\u{FE98}\u{FE91}"
+        );
+
+        // ALEF does not join the letter after it.
+        assert_eq!(
+            display_line("\u{0628}\u{0627}\u{0628}", true, all_glyphs),
+            "\u{FE8F}\u{FE8E}\u{FE91}"
+        );
+
+        // Vowel marks don't interrupt joining.
+        assert_eq!(
+            display_line("\u{0628}\u{064E}\u{0628}", true, all_glyphs),
+            "\u{FE90}\u{064E}\u{FE91}"
+        );
+
+        // LAM ALEF forms a ligature.
+        assert_eq!(
+            display_line("\u{0628}\u{0644}\u{0627}", true, all_glyphs),
+            "\u{FEFC}\u{FE91}"
+        );
+    }
+
+    #[test]
+    fn missing_presentation_forms_are_left_unshaped() {
+        let base_letters_only = |c| c < '\u{FB50}';
+        assert_eq!(
+            display_line("\u{0628}\u{0644}\u{0627}", true, base_letters_only),
+            "\u{0627}\u{0644}\u{0628}"
+        );
+    }
+}
@@ -0,0 +1,721 @@
+//! Formatted text, stored as runs of characters that share a `TextFormat`
+
+use crate::font::bidi::Direction;
+use crate::font::TextFormat;
+use std::ops::Range;
+
+/// A run of text that shares a single format.
+#[derive(Clone, Debug, PartialEq)]
+struct TextSpan {
+    /// The length of the run, in bytes.
+    length: usize,
+
+    format: TextFormat,
+}
+
+/// Text along with the formatting of each of its characters.
+///
+/// The spans cover the whole text, and there is always at least one span, so that empty text
+/// still has a format for text inserted into it. Adjacent spans never share the same format.
+///
+/// Paragraphs are separated by `\n`. Character indices taken by the methods here count Unicode
+/// characters, as in AVM1 `TextField` and `Selection` methods.
+#[derive(Clone, Debug)]
+pub struct FormatSpans {
+    text: String,
+    spans: Vec<TextSpan>,
+}
+
+impl FormatSpans {
+    /// Create text that uses a single format.
+    pub fn new(text: String, format: TextFormat) -> Self {
+        Self {
+            spans: vec![TextSpan {
+                length: text.len(),
+                format,
+            }],
+            text,
+        }
+    }
+
+    /// Parse the HTML subset supported by Flash text fields.
+    ///
+    /// Text outside of any formatting tag uses `default_format`. Unknown tags are ignored, and
+    /// unclosed tags are closed at the end of the text. (SWF19 pp. 173-174)
+    ///
+    /// If `condense_white` is set, each run of white space in the source is read as a single
+    /// space, and white space at the start of a paragraph is dropped, as for text fields with
+    /// `condenseWhite` set. Otherwise white space is kept as written, and line breaks in the
+    /// source start new paragraphs.
+    pub fn from_html(html: &str, default_format: &TextFormat, condense_white: bool) -> Self {
+        let mut builder = HtmlBuilder {
+            text: String::new(),
+            spans: vec![],
+            formats: vec![(String::new(), default_format.clone())],
+            paragraph_ended: false,
+            condense_white,
+        };
+
+        let mut rest = html;
+        while let Some(tag_start) = rest.find('<') {
+            builder.push_source_text(&rest[..tag_start]);
+            rest = &rest[tag_start + 1..];
+
+            let tag_end = rest.find('>').unwrap_or_else(|| rest.len());
+            builder.push_tag(&rest[..tag_end]);
+            rest = rest.get(tag_end + 1..).unwrap_or("");
+        }
+        builder.push_source_text(rest);
+
+        let mut spans = builder.spans;
+        if spans.is_empty() {
+            spans.push(TextSpan {
+                length: 0,
+                format: default_format.clone(),
+            });
+        }
+
+        Self {
+            text: builder.text,
+            spans,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The number of characters in the text.
+    pub fn len_chars(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    /// Iterate over the byte range covered by each span, along with its format.
+    pub fn iter_spans(&self) -> impl Iterator<Item = (Range<usize>, &TextFormat)> {
+        let mut start = 0;
+        self.spans.iter().map(move |span| {
+            let range = start..start + span.length;
+            start = range.end;
+            (range, &span.format)
+        })
+    }
+
+    /// The format of the character that starts at the given byte position.
+    ///
+    /// The end of the text takes the format of the last character.
+    pub fn format_at(&self, position: usize) -> &TextFormat {
+        self.iter_spans()
+            .find(|(range, _)| range.contains(&position))
+            .map(|(_, format)| format)
+            .unwrap_or_else(|| &self.spans.last().unwrap().format)
+    }
+
+    /// Replace the characters from `from` up to `to` with `text`, formatted with `format`.
+    ///
+    /// Indices past the end of the text are clamped to it, and `to` is clamped to be at least
+    /// `from`. The formatting of the text outside of the range is unchanged, even if the range
+    /// starts or ends in the middle of a span.
+    pub fn replace_text(&mut self, from: usize, to: usize, text: &str, format: &TextFormat) {
+        let from = byte_index(&self.text, from);
+        let to = byte_index(&self.text, to).max(from);
+
+        let mut spans: Vec<TextSpan> = vec![];
+        let mut push_span = |length, format: &TextFormat| {
+            if length == 0 {
+                return;
+            }
+            match spans.last_mut() {
+                Some(last) if last.format == *format => last.length += length,
+                _ => spans.push(TextSpan {
+                    length,
+                    format: format.clone(),
+                }),
+            }
+        };
+
+        for (range, span_format) in self.iter_spans() {
+            push_span(range.end.min(from).saturating_sub(range.start), span_format);
+        }
+        push_span(text.len(), format);
+        for (range, span_format) in self.iter_spans() {
+            push_span(range.end.saturating_sub(range.start.max(to)), span_format);
+        }
+
+        if spans.is_empty() {
+            spans.push(TextSpan {
+                length: 0,
+                format: format.clone(),
+            });
+        }
+
+        self.text.replace_range(from..to, text);
+        self.spans = spans;
+    }
+
+    /// Generate the HTML that Flash Player returns for this text from the AVM1
+    /// `TextField.htmlText` property.
+    ///
+    /// Each paragraph is wrapped in a `P` tag, and each run of characters with the same font in a
+    /// `FONT` tag, with every attribute spelled out.
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+
+        let mut paragraph_start = 0;
+        for paragraph in self.text.split('\n') {
+            let paragraph_end = paragraph_start + paragraph.len();
+            let paragraph_format = self.format_at(paragraph_start);
+
+            let text_format_attributes: Vec<String> = [
+                ("LEFTMARGIN", paragraph_format.left_margin),
+                ("RIGHTMARGIN", paragraph_format.right_margin),
+                ("INDENT", paragraph_format.indent),
+                ("BLOCKINDENT", paragraph_format.block_indent),
+                ("LEADING", paragraph_format.leading),
+            ]
+            .iter()
+            .filter_map(|(name, value)| match value {
+                Some(value) if *value != 0.0 => {
+                    Some(format!(" {}=\"{}\"", name, html_number(*value)))
+                }
+                _ => None,
+            })
+            .collect();
+            if !text_format_attributes.is_empty() {
+                html.push_str("<TEXTFORMAT");
+                html.push_str(&text_format_attributes.concat());
+                html.push('>');
+            }
+
+            let align = match paragraph_format.align.unwrap_or(swf::TextAlign::Left) {
+                swf::TextAlign::Left => "LEFT",
+                swf::TextAlign::Center => "CENTER",
+                swf::TextAlign::Right => "RIGHT",
+                swf::TextAlign::Justify => "JUSTIFY",
+            };
+            let dir = match paragraph_format.direction {
+                Some(Direction::LeftToRight) => " DIR=\"LTR\"",
+                Some(Direction::RightToLeft) => " DIR=\"RTL\"",
+                None => "",
+            };
+            html.push_str(&format!("<P ALIGN=\"{}\"{}>", align, dir));
+
+            // An empty paragraph still reports the format of its position.
+            let mut runs: Vec<(Range<usize>, &TextFormat)> = self
+                .iter_spans()
+                .filter(|(range, _)| range.start < paragraph_end && range.end > paragraph_start)
+                .map(|(range, format)| {
+                    (
+                        range.start.max(paragraph_start)..range.end.min(paragraph_end),
+                        format,
+                    )
+                })
+                .collect();
+            if runs.is_empty() {
+                runs.push((paragraph_start..paragraph_end, paragraph_format));
+            }
+
+            let mut open_font: Option<&TextFormat> = None;
+            for (range, format) in runs {
+                if open_font
+                    .map(|open| !same_font(open, format))
+                    .unwrap_or(true)
+                {
+                    if open_font.is_some() {
+                        html.push_str("</FONT>");
+                    }
+                    push_font_tag(&mut html, format);
+                    open_font = Some(format);
+                }
+
+                let mut close_tags = vec![];
+                if let Some(url) = format.url.as_ref().filter(|url| !url.is_empty()) {
+                    html.push_str(&format!(
+                        "<A HREF=\"{}\" TARGET=\"{}\">",
+                        encode_entities(url),
+                        encode_entities(format.target.as_deref().unwrap_or(""))
+                    ));
+                    close_tags.push("</A>");
+                }
+                for &(is_set, open, close) in &[
+                    (format.bold, "<B>", "</B>"),
+                    (format.italic, "<I>", "</I>"),
+                    (format.underline, "<U>", "</U>"),
+                ] {
+                    if is_set == Some(true) {
+                        html.push_str(open);
+                        close_tags.push(close);
+                    }
+                }
+
+                html.push_str(&encode_entities(&self.text[range]));
+
+                for close in close_tags.iter().rev() {
+                    html.push_str(close);
+                }
+            }
+            html.push_str("</FONT></P>");
+
+            if !text_format_attributes.is_empty() {
+                html.push_str("</TEXTFORMAT>");
+            }
+
+            paragraph_start = paragraph_end + 1;
+        }
+
+        html
+    }
+}
+
+/// Accumulates text and spans while parsing HTML.
+struct HtmlBuilder {
+    text: String,
+    spans: Vec<TextSpan>,
+
+    /// The formats of the currently open tags, along with the tag names.
+    formats: Vec<(String, TextFormat)>,
+
+    /// Whether a paragraph was closed, and the next text should start a new paragraph.
+    paragraph_ended: bool,
+
+    /// Whether runs of white space in the source are read as a single space.
+    condense_white: bool,
+}
+
+impl HtmlBuilder {
+    fn format(&self) -> &TextFormat {
+        &self.formats.last().unwrap().1
+    }
+
+    /// Add text from the HTML source, which may contain entities.
+    fn push_source_text(&mut self, source: &str) {
+        if !self.condense_white {
+            self.push_text(&decode_entities(source));
+            return;
+        }
+
+        let mut condensed = String::with_capacity(source.len());
+        for c in source.chars() {
+            if is_html_white_space(c) {
+                let at_paragraph_start = if condensed.is_empty() {
+                    self.paragraph_ended || self.text.is_empty() || self.text.ends_with('\n')
+                } else {
+                    false
+                };
+                if !at_paragraph_start && !condensed.ends_with(' ') {
+                    condensed.push(' ');
+                }
+            } else {
+                condensed.push(c);
+            }
+        }
+        self.push_text(&decode_entities(&condensed));
+    }
+
+    fn push_text(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        if self.paragraph_ended {
+            self.paragraph_ended = false;
+            self.push_text("\n");
+        }
+
+        self.text.push_str(text);
+        let format = self.format().clone();
+        match self.spans.last_mut() {
+            Some(last) if last.format == format => last.length += text.len(),
+            _ => self.spans.push(TextSpan {
+                length: text.len(),
+                format,
+            }),
+        }
+    }
+
+    /// Handle the contents of a tag, without the angle brackets.
+    fn push_tag(&mut self, tag: &str) {
+        if tag.starts_with('/') {
+            let name = tag[1..].trim().to_ascii_lowercase();
+            if name == "p" {
+                self.paragraph_ended = true;
+            }
+
+            // Close the tag, along with any tags left open inside it.
+            if let Some(i) = self.formats.iter().rposition(|(open, _)| *open == name) {
+                if i > 0 {
+                    self.formats.truncate(i);
+                }
+            }
+            return;
+        }
+
+        let tag = tag.trim_end_matches('/');
+        let name_end = tag
+            .find(|c: char| c.is_whitespace())
+            .unwrap_or_else(|| tag.len());
+        let name = tag[..name_end].to_ascii_lowercase();
+        let attributes = parse_attributes(&tag[name_end..]);
+        let attribute = |attribute: &str| {
+            attributes
+                .iter()
+                .find(|(name, _)| name == attribute)
+                .map(|(_, value)| value.as_str())
+        };
+
+        let mut format = self.format().clone();
+        match name.as_str() {
+            "br" | "sbr" => {
+                self.paragraph_ended = false;
+                self.push_text("\n");
+                return;
+            }
+            "p" => {
+                if self.paragraph_ended || (!self.text.is_empty() && !self.text.ends_with('\n')) {
+                    self.paragraph_ended = false;
+                    self.push_text("\n");
+                }
+                format.align = match attribute("align").map(|a| a.to_ascii_lowercase()) {
+                    Some(ref a) if a == "left" => Some(swf::TextAlign::Left),
+                    Some(ref a) if a == "center" => Some(swf::TextAlign::Center),
+                    Some(ref a) if a == "right" => Some(swf::TextAlign::Right),
+                    Some(ref a) if a == "justify" => Some(swf::TextAlign::Justify),
+                    _ => format.align,
+                };
+                format.direction = match attribute("dir").map(|a| a.to_ascii_lowercase()) {
+                    Some(ref d) if d == "ltr" => Some(Direction::LeftToRight),
+                    Some(ref d) if d == "rtl" => Some(Direction::RightToLeft),
+                    _ => format.direction,
+                };
+            }
+            "b" => format.bold = Some(true),
+            "i" => format.italic = Some(true),
+            "u" => format.underline = Some(true),
+            "a" => {
+                format.url = attribute("href").map(str::to_string);
+                format.target = attribute("target").map(str::to_string);
+            }
+            "font" => {
+                if let Some(face) = attribute("face") {
+                    format.font = Some(face.to_string());
+                }
+                if let Some(size) = attribute("size") {
+                    // Sizes starting with a sign are relative to the current size.
+                    let base = match size.chars().next() {
+                        Some('+') | Some('-') => format.size.unwrap_or(0.0),
+                        _ => 0.0,
+                    };
+                    if let Ok(size) = size.trim_start_matches('+').parse::<f64>() {
+                        format.size = Some(base + size);
+                    }
+                }
+                if let Some(color) = attribute("color") {
+                    if let Ok(rgb) = u32::from_str_radix(color.trim_start_matches('#'), 16) {
+                        format.color = Some(swf::Color {
+                            r: (rgb >> 16) as u8,
+                            g: (rgb >> 8) as u8,
+                            b: rgb as u8,
+                            a: 0xFF,
+                        });
+                    }
+                }
+                if let Some(letter_spacing) = attribute("letterspacing") {
+                    format.letter_spacing = letter_spacing.parse().ok().or(format.letter_spacing);
+                }
+                if let Some(kerning) = attribute("kerning") {
+                    format.kerning = Some(kerning != "0");
+                }
+            }
+            "textformat" => {
+                let number = |name| attribute(name).and_then(|value| value.parse().ok());
+                format.left_margin = number("leftmargin").or(format.left_margin);
+                format.right_margin = number("rightmargin").or(format.right_margin);
+                format.indent = number("indent").or(format.indent);
+                format.block_indent = number("blockindent").or(format.block_indent);
+                format.leading = number("leading").or(format.leading);
+            }
+            "li" => format.bullet = Some(true),
+            _ => return,
+        }
+
+        self.formats.push((name, format));
+    }
+}
+
+/// Parse the attributes of an HTML tag into lowercase names and decoded values.
+fn parse_attributes(mut attributes: &str) -> Vec<(String, String)> {
+    let mut result = vec![];
+    loop {
+        attributes = attributes.trim_start();
+        let name_end = attributes
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or_else(|| attributes.len());
+        if name_end == 0 {
+            break;
+        }
+        let name = attributes[..name_end].to_ascii_lowercase();
+        attributes = attributes[name_end..].trim_start();
+
+        let value = if attributes.starts_with('=') {
+            let value = attributes[1..].trim_start();
+            let (value, rest) = match value.chars().next() {
+                Some(quote) if quote == '"' || quote == '\'' => {
+                    let value = &value[1..];
+                    let end = value.find(quote).unwrap_or_else(|| value.len());
+                    (&value[..end], value.get(end + 1..).unwrap_or(""))
+                }
+                _ => {
+                    let end = value
+                        .find(char::is_whitespace)
+                        .unwrap_or_else(|| value.len());
+                    (&value[..end], &value[end..])
+                }
+            };
+            attributes = rest;
+            decode_entities(value)
+        } else {
+            String::new()
+        };
+
+        result.push((name, value));
+    }
+    result
+}
+
+/// Returns whether a character of HTML source is white space that `condenseWhite` collapses.
+///
+/// Characters written as entities, such as `&nbsp;`, are never collapsed.
+fn is_html_white_space(c: char) -> bool {
+    c == ' ' || c == '\t' || c == '\n' || c == '\r'
+}
+
+/// Replace the character entities in some HTML text with the characters they stand for.
+fn decode_entities(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest.find(';').and_then(|end| {
+            let c = match &rest[1..end] {
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "amp" => Some('&'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{A0}'),
+                entity if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16)
+                    .ok()
+                    .and_then(std::char::from_u32),
+                entity if entity.starts_with('#') => {
+                    entity[1..].parse().ok().and_then(std::char::from_u32)
+                }
+                _ => None,
+            };
+            c.map(|c| (c, end))
+        });
+
+        match decoded {
+            Some((c, end)) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Escape the characters in some text that have a special meaning in HTML.
+fn encode_entities(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '&' => result.push_str("&amp;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Returns whether two formats can share a `FONT` tag.
+fn same_font(a: &TextFormat, b: &TextFormat) -> bool {
+    a.font == b.font
+        && a.size == b.size
+        && a.color == b.color
+        && a.letter_spacing == b.letter_spacing
+        && a.kerning == b.kerning
+}
+
+fn push_font_tag(html: &mut String, format: &TextFormat) {
+    html.push_str("<FONT");
+    if let Some(font) = &format.font {
+        html.push_str(&format!(" FACE=\"{}\"", encode_entities(font)));
+    }
+    if let Some(size) = format.size {
+        html.push_str(&format!(" SIZE=\"{}\"", html_number(size)));
+    }
+    if let Some(color) = &format.color {
+        html.push_str(&format!(
+            " COLOR=\"#{:02X}{:02X}{:02X}\"",
+            color.r, color.g, color.b
+        ));
+    }
+    if let Some(letter_spacing) = format.letter_spacing {
+        html.push_str(&format!(
+            " LETTERSPACING=\"{}\"",
+            html_number(letter_spacing)
+        ));
+    }
+    if let Some(kerning) = format.kerning {
+        html.push_str(&format!(" KERNING=\"{}\"", kerning as u8));
+    }
+    html.push('>');
+}
+
+/// Format a number as an HTML attribute, without a fractional part for whole numbers.
+fn html_number(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value as i64)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Returns the byte position of the given character index, clamped to the end of the text.
+fn byte_index(text: &str, char_index: usize) -> usize {
+    text.char_indices()
+        .nth(char_index)
+        .map(|(i, _)| i)
+        .unwrap_or_else(|| text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(bold: bool) -> TextFormat {
+        TextFormat {
+            bold: Some(bold),
+            ..Default::default()
+        }
+    }
+
+    fn spans(text: &FormatSpans) -> Vec<(&str, Option<bool>)> {
+        text.iter_spans()
+            .map(|(range, format)| (&text.text()[range], format.bold))
+            .collect()
+    }
+
+    #[test]
+    fn replace_text_splits_spans() {
+        let mut text = FormatSpans::from_html("<b>bold</b>plain", &format(false), false);
+        assert_eq!(spans(&text), [("bold", Some(true)), ("plain", Some(false))]);
+
+        // Across the boundary between spans.
+        text.replace_text(2, 6, "--", &format(false));
+        assert_eq!(text.text(), "bo--ain");
+        assert_eq!(spans(&text), [("bo", Some(true)), ("--ain", Some(false))]);
+
+        // Inside a span.
+        text.replace_text(1, 1, "x", &format(false));
+        assert_eq!(
+            spans(&text),
+            [
+                ("b", Some(true)),
+                ("x", Some(false)),
+                ("o", Some(true)),
+                ("--ain", Some(false))
+            ]
+        );
+
+        // Out of range indices are clamped.
+        text.replace_text(100, 50, "!", &format(true));
+        assert_eq!(text.text(), "bxo--ain!");
+        assert_eq!(spans(&text).last(), Some(&("!", Some(true))));
+
+        // Removing everything keeps a span for the format of new text.
+        text.replace_text(0, 100, "", &format(true));
+        assert_eq!(text.text(), "");
+        assert_eq!(spans(&text), [("", Some(true))]);
+    }
+
+    #[test]
+    fn html_paragraphs_and_entities() {
+        let text = FormatSpans::from_html(
+            "<p align=\"center\">a &amp; b</p><p>c<br>d &lt;e&gt;</p>",
+            &TextFormat::default(),
+            false,
+        );
+        assert_eq!(text.text(), "a & b\nc\nd <e>");
+        assert_eq!(text.format_at(0).align, Some(swf::TextAlign::Center));
+        assert_eq!(text.format_at(6).align, None);
+    }
+
+    #[test]
+    fn html_white_space_is_condensed() {
+        let html = "<p>  a \t b  </p>\n  <p>c\r\n d&nbsp;&#32; e</p>";
+
+        let text = FormatSpans::from_html(html, &TextFormat::default(), true);
+        assert_eq!(text.text(), "a b \nc d\u{A0}  e");
+
+        let text = FormatSpans::from_html(html, &TextFormat::default(), false);
+        assert_eq!(text.text(), "  a \t b  \n\n  \nc\r\n d\u{A0}  e");
+    }
+
+    #[test]
+    fn html_is_generated_per_paragraph() {
+        let default_format = TextFormat {
+            font: Some("Arial".to_string()),
+            size: Some(12.0),
+            color: Some(swf::Color {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0xFF,
+            }),
+            align: Some(swf::TextAlign::Left),
+            bold: Some(false),
+            letter_spacing: Some(0.0),
+            kerning: Some(false),
+            ..Default::default()
+        };
+        let text = FormatSpans::from_html(
+            "<b>a</b>b<br><font color='#ff0000'>c</font>",
+            &default_format,
+            false,
+        );
+        assert_eq!(
+            text.to_html(),
+            "<P ALIGN=\"LEFT\"><FONT FACE=\"Arial\" SIZE=\"12\" COLOR=\"#000000\" \
+             LETTERSPACING=\"0\" KERNING=\"0\"><B>a</B>b</FONT></P>\
+             <P ALIGN=\"LEFT\"><FONT FACE=\"Arial\" SIZE=\"12\" COLOR=\"#FF0000\" \
+             LETTERSPACING=\"0\" KERNING=\"0\">c</FONT></P>"
+        );
+    }
+
+    #[test]
+    fn paragraph_direction_is_read_from_html() {
+        let text = FormatSpans::from_html(
+            "<p dir='rtl'>a</p><p>b</p><P DIR=\"LTR\">c</P>",
+            &TextFormat::default(),
+            false,
+        );
+        assert_eq!(text.text(), "a\nb\nc");
+        assert_eq!(text.format_at(0).direction, Some(Direction::RightToLeft));
+        assert_eq!(text.format_at(2).direction, None);
+        assert_eq!(text.format_at(4).direction, Some(Direction::LeftToRight));
+        assert_eq!(
+            text.to_html(),
+            "<P ALIGN=\"LEFT\" DIR=\"RTL\"><FONT>a</FONT></P><P ALIGN=\"LEFT\"><FONT>b</FONT></P>\
+             <P ALIGN=\"LEFT\" DIR=\"LTR\"><FONT>c</FONT></P>"
+        );
+    }
+}
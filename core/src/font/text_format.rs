@@ -1,6 +1,7 @@
 //! Classes that store formatting options
 use crate::avm1::{Avm1, Object, ScriptObject, TObject, Value};
 use crate::context::UpdateContext;
+use crate::font::bidi::Direction;
 
 type Error = Box<dyn std::error::Error>;
 
@@ -13,26 +14,30 @@ type Error = Box<dyn std::error::Error>;
 /// means that multiple regions of text apply. When setting the format of a
 /// particular region of text, `None` means that the existing setting for that
 /// property will be retained.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct TextFormat {
-    font: Option<String>,
-    size: Option<f64>,
-    color: Option<swf::Color>,
-    align: Option<swf::TextAlign>,
-    bold: Option<bool>,
-    italic: Option<bool>,
-    underline: Option<bool>,
-    left_margin: Option<f64>,
-    right_margin: Option<f64>,
-    indent: Option<f64>,
-    block_indent: Option<f64>,
-    kerning: Option<bool>,
-    leading: Option<f64>,
-    letter_spacing: Option<f64>,
-    tab_stops: Option<Vec<f64>>,
-    bullet: Option<bool>,
-    url: Option<String>,
-    target: Option<String>,
+    pub font: Option<String>,
+    pub size: Option<f64>,
+    pub color: Option<swf::Color>,
+    pub align: Option<swf::TextAlign>,
+    pub bold: Option<bool>,
+    pub italic: Option<bool>,
+    pub underline: Option<bool>,
+    pub left_margin: Option<f64>,
+    pub right_margin: Option<f64>,
+    pub indent: Option<f64>,
+    pub block_indent: Option<f64>,
+    pub kerning: Option<bool>,
+    pub leading: Option<f64>,
+    pub letter_spacing: Option<f64>,
+    pub tab_stops: Option<Vec<f64>>,
+    pub bullet: Option<bool>,
+    pub url: Option<String>,
+    pub target: Option<String>,
+
+    /// The base direction of a paragraph. Paragraphs without one take their direction from
+    /// their text. Only set from HTML, as `TextFormat` objects have no such property.
+    pub direction: Option<Direction>,
 }
 
 impl Default for TextFormat {
@@ -56,6 +61,7 @@ impl Default for TextFormat {
             bullet: None,
             url: None,
             target: None,
+            direction: None,
         }
     }
 }
@@ -100,6 +106,41 @@ fn getbool_from_avm1_object<'gc>(
 }
 
 impl TextFormat {
+    /// Overwrite the properties of this format that are set in `other`.
+    pub fn merge_with(&mut self, other: TextFormat) {
+        macro_rules! merge {
+            ($($field:ident),*) => {
+                $(
+                    if other.$field.is_some() {
+                        self.$field = other.$field;
+                    }
+                )*
+            };
+        }
+
+        merge!(
+            font,
+            size,
+            color,
+            align,
+            bold,
+            italic,
+            underline,
+            left_margin,
+            right_margin,
+            indent,
+            block_indent,
+            kerning,
+            leading,
+            letter_spacing,
+            tab_stops,
+            bullet,
+            url,
+            target,
+            direction
+        );
+    }
+
     /// Construct a `TextFormat` from an object that is
     pub fn from_avm1_object<'gc>(
         object1: Object<'gc>,
@@ -138,6 +179,7 @@ impl TextFormat {
             bullet: getbool_from_avm1_object(object1, "bullet", avm1, uc)?,
             url: getstr_from_avm1_object(object1, "url", avm1, uc)?,
             target: getstr_from_avm1_object(object1, "target", avm1, uc)?,
+            direction: None,
         })
     }
 
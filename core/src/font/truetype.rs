@@ -0,0 +1,335 @@
+//! Conversion of TrueType font files into SWF fonts.
+//!
+//! This lets fonts that aren't defined by a movie, such as ones registered by the host of the
+//! player, be drawn and laid out exactly like `DefineFont3` fonts. The font file is read with
+//! `ttf-parser`; only the glyph outlines, the metrics, the character map and the `kern` table
+//! are used. Fonts with CFF outlines and variable fonts are rejected.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use swf::{FontLayout, Glyph, KerningRecord, Rectangle, ShapeRecord, StyleChangeData, Twips};
+use ttf_parser::{kern, Face, FaceParsingError, GlyphId, OutlineBuilder};
+
+type Error = Box<dyn std::error::Error>;
+
+/// The size of the EM square of `DefineFont3` glyphs.
+const EM_SQUARE: f64 = 20480.0;
+
+/// Converts a TrueType (or OpenType with TrueType outlines) font file into a `DefineFont3`
+/// font with the given name.
+///
+/// Characters outside the Basic Multilingual Plane are left out, as SWF fonts map glyphs from
+/// 16-bit character codes.
+pub fn to_swf_font(name: &str, data: &[u8]) -> Result<swf::Font, Error> {
+    let face = Face::from_slice(data, 0).map_err(|error| -> Error {
+        match error {
+            FaceParsingError::UnknownMagic => "Not a TrueType font".into(),
+            error => format!("Invalid font: {}", error).into(),
+        }
+    })?;
+    if face.is_variable() {
+        return Err("Variable fonts are not supported".into());
+    }
+    if face.tables().cff.is_some() || face.tables().cff2.is_some() {
+        return Err("Fonts with CFF outlines are not supported".into());
+    }
+    if face.tables().glyf.is_none() {
+        return Err("Font has no glyph outlines".into());
+    }
+    if face.tables().cmap.is_none() {
+        return Err("Font has no Unicode character map".into());
+    }
+
+    let scale = EM_SQUARE / f64::from(face.units_per_em());
+    let to_twips = |value: f64| Twips::new((value * scale).round() as i32);
+    let to_i16 = |value: f64| {
+        (value * scale)
+            .round()
+            .max(f64::from(i16::MIN))
+            .min(f64::from(i16::MAX)) as i16
+    };
+
+    // The glyph of each character, ordered by character code.
+    // Looking up every code is bounded, unlike walking the ranges of the character map.
+    let code_points: Vec<(u16, GlyphId)> = (0..0xffff_u32)
+        .filter_map(|code| {
+            let glyph_id = face.glyph_index(std::char::from_u32(code)?)?;
+            if glyph_id.0 != 0 && glyph_id.0 < face.number_of_glyphs() {
+                Some((code as u16, glyph_id))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let mut outlines: HashMap<GlyphId, (Vec<ShapeRecord>, Rectangle)> = HashMap::new();
+    let mut glyphs = Vec::with_capacity(code_points.len());
+    for &(code, glyph_id) in &code_points {
+        let (shape_records, bounds) = match outlines.entry(glyph_id) {
+            Entry::Occupied(entry) => entry.get().clone(),
+            Entry::Vacant(entry) => entry.insert(outline(&face, glyph_id, scale)).clone(),
+        };
+        let advance = face.glyph_hor_advance(glyph_id).unwrap_or(0);
+        glyphs.push(Glyph {
+            shape_records,
+            code,
+            advance: Some(to_i16(f64::from(advance))),
+            bounds: Some(bounds),
+        });
+    }
+
+    let mut codes_of_glyphs: HashMap<GlyphId, Vec<u16>> = HashMap::new();
+    for &(code, glyph_id) in &code_points {
+        codes_of_glyphs.entry(glyph_id).or_default().push(code);
+    }
+    let mut kerning = vec![];
+    for (left, right, adjustment) in kerning_pairs(&face) {
+        if let (Some(left_codes), Some(right_codes)) =
+            (codes_of_glyphs.get(&left), codes_of_glyphs.get(&right))
+        {
+            for &left_code in left_codes {
+                for &right_code in right_codes {
+                    kerning.push(KerningRecord {
+                        left_code,
+                        right_code,
+                        adjustment: to_twips(f64::from(adjustment)),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(swf::Font {
+        version: 3,
+        id: 0,
+        name: name.to_string(),
+        language: swf::Language::Unknown,
+        layout: Some(FontLayout {
+            ascent: to_i16(f64::from(face.ascender()).max(0.0)) as u16,
+            descent: to_i16(f64::from(-face.descender()).max(0.0)) as u16,
+            leading: to_i16(f64::from(face.line_gap())),
+            kerning,
+        }),
+        glyphs,
+        is_small_text: false,
+        is_shift_jis: false,
+        is_ansi: false,
+        is_bold: face.is_bold(),
+        is_italic: face.is_italic(),
+    })
+}
+
+/// Reads the horizontal kerning pairs of the `kern` table, in font units.
+///
+/// Only subtables with a list of glyph pairs are supported. Kerning that is only given by the
+/// OpenType `GPOS` table is not read.
+fn kerning_pairs(face: &Face) -> Vec<(GlyphId, GlyphId, i16)> {
+    let mut pairs = vec![];
+    if let Some(kern) = face.tables().kern {
+        for subtable in kern.subtables {
+            if !subtable.horizontal || subtable.variable || subtable.has_cross_stream {
+                continue;
+            }
+            if let kern::Format::Format0(subtable) = subtable.format {
+                pairs.extend(
+                    subtable
+                        .pairs
+                        .into_iter()
+                        .map(|pair| (pair.left(), pair.right(), pair.value)),
+                );
+            }
+        }
+    }
+    pairs
+}
+
+/// Converts the outline of a glyph into the shape records of an SWF glyph, along with their
+/// bounds. Glyphs without outlines, such as spaces, have no shape records.
+fn outline(face: &Face, glyph_id: GlyphId, scale: f64) -> (Vec<ShapeRecord>, Rectangle) {
+    let mut builder = ShapeRecordBuilder {
+        scale,
+        records: vec![],
+        position: (Twips::new(0), Twips::new(0)),
+    };
+    match face.outline_glyph(glyph_id, &mut builder) {
+        Some(rect) => {
+            let to_twips = |value: f64| Twips::new((value * scale).round() as i32);
+            let bounds = Rectangle {
+                x_min: to_twips(f64::from(rect.x_min)),
+                x_max: to_twips(f64::from(rect.x_max)),
+                y_min: to_twips(-f64::from(rect.y_max)),
+                y_max: to_twips(-f64::from(rect.y_min)),
+            };
+            (builder.records, bounds)
+        }
+        None => (vec![], Rectangle::default()),
+    }
+}
+
+/// Builds SWF shape records from the contours of a TrueType glyph.
+///
+/// TrueType contours are quadratic curves like SWF edges, so they convert directly.
+struct ShapeRecordBuilder {
+    scale: f64,
+    records: Vec<ShapeRecord>,
+    position: (Twips, Twips),
+}
+
+impl ShapeRecordBuilder {
+    /// Converts a point in font units with y pointing up into twips with y pointing down.
+    ///
+    /// Positions are rounded to twips before taking deltas, so that rounding errors
+    /// don't add up along a contour.
+    fn to_twips(&self, x: f32, y: f32) -> (Twips, Twips) {
+        (
+            Twips::new((f64::from(x) * self.scale).round() as i32),
+            Twips::new((f64::from(-y) * self.scale).round() as i32),
+        )
+    }
+}
+
+impl OutlineBuilder for ShapeRecordBuilder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let position = self.to_twips(x, y);
+        let fill_style_1 = if self.records.is_empty() {
+            Some(1)
+        } else {
+            None
+        };
+        self.records.push(ShapeRecord::StyleChange(StyleChangeData {
+            move_to: Some(position),
+            fill_style_0: None,
+            fill_style_1,
+            line_style: None,
+            new_styles: None,
+        }));
+        self.position = position;
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let anchor = self.to_twips(x, y);
+        self.records.push(ShapeRecord::StraightEdge {
+            delta_x: anchor.0 - self.position.0,
+            delta_y: anchor.1 - self.position.1,
+        });
+        self.position = anchor;
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let control = self.to_twips(x1, y1);
+        let anchor = self.to_twips(x, y);
+        self.records.push(ShapeRecord::CurvedEdge {
+            control_delta_x: control.0 - self.position.0,
+            control_delta_y: control.1 - self.position.1,
+            anchor_delta_x: anchor.0 - control.0,
+            anchor_delta_y: anchor.1 - control.1,
+        });
+        self.position = anchor;
+    }
+
+    fn curve_to(&mut self, _x1: f32, _y1: f32, _x2: f32, _y2: f32, x: f32, y: f32) {
+        // Cubic curves only come from CFF outlines, which are rejected before outlining.
+        self.line_to(x, y);
+    }
+
+    fn close(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_FONT: &[u8] = include_bytes!("../../tests/swfs/avm1/registered_font/test.ttf");
+
+    fn glyph(font: &swf::Font, c: char) -> &Glyph {
+        font.glyphs
+            .iter()
+            .find(|glyph| glyph.code == c as u16)
+            .unwrap()
+    }
+
+    fn move_tos(glyph: &Glyph) -> Vec<(Twips, Twips)> {
+        glyph
+            .shape_records
+            .iter()
+            .filter_map(|record| match record {
+                ShapeRecord::StyleChange(style_change) => style_change.move_to,
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn metrics_are_scaled_to_the_swf_em_square() {
+        // The test font has 1000 units per EM, which are 20.48 twips each.
+        let font = to_swf_font("Test", TEST_FONT).unwrap();
+        assert_eq!(font.name, "Test");
+        assert_eq!(font.version, 3);
+        let codes: Vec<u16> = font.glyphs.iter().map(|glyph| glyph.code).collect();
+        assert_eq!(codes, vec![0x20, 'H' as u16, 'I' as u16, 'O' as u16]);
+        assert_eq!(glyph(&font, ' ').advance, Some(5120));
+        assert_eq!(glyph(&font, 'I').advance, Some(6144));
+        assert_eq!(
+            glyph(&font, 'I').bounds,
+            Some(Rectangle {
+                x_min: Twips::new(2048),
+                x_max: Twips::new(4096),
+                y_min: Twips::new(-14336),
+                y_max: Twips::new(0),
+            })
+        );
+
+        let layout = font.layout.unwrap();
+        assert_eq!(
+            (layout.ascent, layout.descent, layout.leading),
+            (16384, 4096, 2048)
+        );
+        assert_eq!(
+            layout.kerning,
+            vec![
+                KerningRecord {
+                    left_code: 'H' as u16,
+                    right_code: 'I' as u16,
+                    adjustment: Twips::new(614),
+                },
+                KerningRecord {
+                    left_code: 'I' as u16,
+                    right_code: 'O' as u16,
+                    adjustment: Twips::new(-1024),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn composite_glyphs_place_their_components() {
+        let font = to_swf_font("Test", TEST_FONT).unwrap();
+        assert_eq!(
+            move_tos(glyph(&font, 'H')),
+            vec![
+                (Twips::new(2048), Twips::new(0)),
+                (Twips::new(10240), Twips::new(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn off_curve_points_imply_on_curve_points_between_them() {
+        // The outer contour of the 'O' only has off-curve points at the corners of a square.
+        let font = to_swf_font("Test", TEST_FONT).unwrap();
+        let o = glyph(&font, 'O');
+        let curves = o
+            .shape_records
+            .iter()
+            .filter(|record| matches!(record, ShapeRecord::CurvedEdge { .. }))
+            .count();
+        assert_eq!(curves, 4);
+    }
+
+    #[test]
+    fn unsupported_fonts_are_rejected() {
+        let error = |data: &[u8]| to_swf_font("Test", data).unwrap_err().to_string();
+        assert_eq!(error(b"GIF89a"), "Not a TrueType font");
+        assert!(error(&TEST_FONT[..100]).starts_with("Invalid font: "));
+    }
+}
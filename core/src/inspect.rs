@@ -0,0 +1,637 @@
+//! Inspection of a movie's contents without playing it.
+//!
+//! `inspect_movie` walks the tag stream of a `SwfMovie` and summarizes what it contains: the
+//! tags of each frame, the character dictionary, fonts, sounds, scripts and the external files
+//! the scripts refer to. Nothing is instantiated or executed.
+
+use crate::tag_utils::{DecodeResult, ScriptKind, SwfMovie, SwfStream};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use swf::avm1::read::Reader as ActionReader;
+use swf::avm1::types::{Action, Value};
+use swf::read::SwfRead;
+use swf::{CharacterId, TagCode};
+
+/// A summary of everything in a movie.
+#[derive(Debug, Clone, Serialize)]
+pub struct MovieSummary {
+    /// The URL the movie was loaded from.
+    pub url: Option<String>,
+
+    /// The SWF version of the movie.
+    pub version: u8,
+
+    /// How the movie's data is compressed in the SWF file.
+    pub compression: String,
+
+    /// The stage width, in pixels.
+    pub width: u32,
+
+    /// The stage height, in pixels.
+    pub height: u32,
+
+    pub frame_rate: f32,
+
+    /// The number of frames declared in the header.
+    pub num_frames: u16,
+
+    /// The size of the tag stream once decompressed, in bytes.
+    pub uncompressed_length: usize,
+
+    /// Which virtual machines the movie's scripts are written for.
+    pub script_kind: ScriptKind,
+
+    /// The tags of each frame of the main timeline.
+    pub frames: Vec<FrameSummary>,
+
+    /// Every character defined by the movie, in definition order.
+    pub characters: Vec<CharacterSummary>,
+
+    pub fonts: Vec<FontSummary>,
+
+    pub sounds: Vec<SoundSummary>,
+
+    /// Every block of AVM1 code placed on a timeline, including those inside sprites.
+    pub scripts: Vec<ScriptSummary>,
+
+    /// The URLs and file names that scripts refer to, sorted and without duplicates.
+    ///
+    /// These are found by scanning the byte code for `GetURL` actions and for strings that
+    /// look like URLs or file names, so computed URLs are missed.
+    pub external_references: Vec<String>,
+
+    /// Problems found while reading the movie. The summary covers everything before them.
+    pub warnings: Vec<String>,
+}
+
+/// The tags of a single frame, up to and including its `ShowFrame` tag.
+#[derive(Debug, Clone, Serialize)]
+pub struct FrameSummary {
+    /// The frame number, starting from 1.
+    pub frame: u16,
+
+    pub label: Option<String>,
+
+    pub tags: Vec<TagSummary>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TagSummary {
+    pub code: u16,
+
+    /// The name of the tag, or `Unknown` for tag codes that are not part of the SWF format.
+    pub name: String,
+
+    /// The length of the tag body, in bytes.
+    pub length: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CharacterKind {
+    BinaryData,
+    Bitmap,
+    Button,
+    EditText,
+    Font,
+    MorphShape,
+    Shape,
+    Sound,
+    Sprite,
+    Text,
+    Video,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CharacterSummary {
+    pub id: CharacterId,
+
+    pub kind: CharacterKind,
+
+    /// The name the character is exported under with `ExportAssets`, if any.
+    pub export_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FontSummary {
+    pub id: CharacterId,
+
+    /// The font name. `DefineFont` tags don't carry one.
+    pub name: Option<String>,
+
+    /// The number of glyphs. `DefineFont4` tags hold an OpenType font that isn't parsed.
+    pub glyph_count: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SoundSummary {
+    pub id: CharacterId,
+
+    /// The codec the sound is encoded with.
+    pub compression: String,
+
+    pub sample_rate: u16,
+
+    pub is_stereo: bool,
+
+    pub is_16_bit: bool,
+
+    pub num_samples: u32,
+
+    /// The length of the sound, in milliseconds.
+    pub duration: u32,
+
+    /// The size of the encoded sound data, in bytes.
+    pub size: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptSummary {
+    /// The sprite whose timeline holds the script, or `None` for the main timeline.
+    pub sprite_id: Option<CharacterId>,
+
+    /// The frame the script is on, starting from 1.
+    pub frame: u16,
+
+    /// For `DoInitAction` tags, the sprite that the script initializes.
+    pub init_sprite_id: Option<CharacterId>,
+
+    /// The size of the action byte code, in bytes.
+    pub size: usize,
+}
+
+/// Summarize the contents of a movie without playing it.
+pub fn inspect_movie(movie: &SwfMovie) -> MovieSummary {
+    let mut inspector = Inspector {
+        summary: MovieSummary {
+            url: movie.url().map(str::to_string),
+            version: movie.version(),
+            compression: format!("{:?}", movie.header().compression),
+            width: movie.width(),
+            height: movie.height(),
+            frame_rate: movie.header().frame_rate,
+            num_frames: movie.header().num_frames,
+            uncompressed_length: movie.data().len(),
+            script_kind: movie.script_kind(),
+            frames: vec![],
+            characters: vec![],
+            fonts: vec![],
+            sounds: vec![],
+            scripts: vec![],
+            external_references: vec![],
+            warnings: vec![],
+        },
+        export_names: HashMap::new(),
+        external_references: BTreeSet::new(),
+    };
+
+    let mut reader = swf::read::Reader::new(std::io::Cursor::new(movie.data()), movie.version());
+    if let Err(e) = inspector.inspect_timeline(&mut reader, None) {
+        inspector
+            .summary
+            .warnings
+            .push(format!("Error reading tag stream: {}", e));
+    }
+
+    let mut summary = inspector.summary;
+    for character in &mut summary.characters {
+        character.export_name = inspector.export_names.get(&character.id).cloned();
+    }
+    summary.external_references = inspector.external_references.into_iter().collect();
+    summary
+}
+
+struct Inspector {
+    summary: MovieSummary,
+
+    /// Export names by character ID. `ExportAssets` may come before or after the definition.
+    export_names: HashMap<CharacterId, String>,
+
+    external_references: BTreeSet<String>,
+}
+
+impl Inspector {
+    /// Walk the tags of a timeline, which is either the main timeline or the timeline of the
+    /// given sprite.
+    ///
+    /// Frames are only listed for the main timeline; scripts and references are collected
+    /// from every timeline.
+    fn inspect_timeline(
+        &mut self,
+        reader: &mut SwfStream<&[u8]>,
+        sprite_id: Option<CharacterId>,
+    ) -> DecodeResult {
+        use std::io::{Seek, SeekFrom};
+        let mut frame = FrameSummary {
+            frame: 1,
+            label: None,
+            tags: vec![],
+        };
+        loop {
+            let (tag_code, tag_len) = reader.read_tag_code_and_length()?;
+            let start_pos = reader.get_ref().position();
+            let end_pos = start_pos + tag_len as u64;
+            let tag = TagCode::from_u16(tag_code);
+
+            if sprite_id.is_none() && tag != Some(TagCode::End) {
+                frame.tags.push(TagSummary {
+                    code: tag_code,
+                    name: tag.map_or_else(|| "Unknown".to_string(), |tag| format!("{:?}", tag)),
+                    length: tag_len,
+                });
+            }
+
+            match tag {
+                Some(TagCode::End) => break,
+                Some(TagCode::ShowFrame) => {
+                    let next_frame = frame.frame.wrapping_add(1);
+                    let frame = std::mem::replace(
+                        &mut frame,
+                        FrameSummary {
+                            frame: next_frame,
+                            label: None,
+                            tags: vec![],
+                        },
+                    );
+                    if sprite_id.is_none() {
+                        self.summary.frames.push(frame);
+                    }
+                }
+                Some(TagCode::FrameLabel) => match reader.read_frame_label(tag_len) {
+                    Ok(frame_label) => frame.label = Some(frame_label.label),
+                    Err(e) => self.summary.warnings.push(format!(
+                        "Error reading FrameLabel tag in frame {}: {}",
+                        frame.frame, e
+                    )),
+                },
+                Some(TagCode::DefineSprite) => {
+                    let id = reader.read_character_id()?;
+                    let _num_frames = reader.read_u16()?;
+                    self.add_character(id, CharacterKind::Sprite);
+                    if let Err(e) = self.inspect_timeline(reader, Some(id)) {
+                        self.summary
+                            .warnings
+                            .push(format!("Error reading sprite {}: {}", id, e));
+                    }
+                }
+                Some(tag) => {
+                    if let Err(e) =
+                        self.inspect_tag(reader, tag, tag_len, sprite_id, frame.frame, start_pos)
+                    {
+                        self.summary.warnings.push(format!(
+                            "Error reading {:?} tag in frame {}: {}",
+                            tag, frame.frame, e
+                        ));
+                    }
+                }
+                None => (),
+            }
+
+            reader.get_mut().seek(SeekFrom::Start(end_pos))?;
+        }
+
+        // Tags after the last `ShowFrame` belong to a frame that is never shown.
+        if sprite_id.is_none() && !frame.tags.is_empty() {
+            self.summary.frames.push(frame);
+        }
+
+        Ok(())
+    }
+
+    fn inspect_tag(
+        &mut self,
+        reader: &mut SwfStream<&[u8]>,
+        tag: TagCode,
+        tag_len: usize,
+        sprite_id: Option<CharacterId>,
+        frame: u16,
+        start_pos: u64,
+    ) -> DecodeResult {
+        let data: &[u8] = reader.get_ref().get_ref();
+        let start = start_pos as usize;
+        let tag_data = data
+            .get(start..start + tag_len)
+            .ok_or("Tag extends past the end of the movie")?;
+
+        match tag {
+            TagCode::DefineBinaryData => {
+                self.add_character(reader.read_character_id()?, CharacterKind::BinaryData)
+            }
+            TagCode::DefineBits
+            | TagCode::DefineBitsJpeg2
+            | TagCode::DefineBitsJpeg3
+            | TagCode::DefineBitsJpeg4
+            | TagCode::DefineBitsLossless
+            | TagCode::DefineBitsLossless2 => {
+                self.add_character(reader.read_character_id()?, CharacterKind::Bitmap)
+            }
+            TagCode::DefineButton => {
+                let button = reader.read_define_button_1()?;
+                self.add_character(button.id, CharacterKind::Button);
+                for action in &button.actions {
+                    self.scan_actions(&action.action_data);
+                }
+            }
+            TagCode::DefineButton2 => {
+                let button = reader.read_define_button_2()?;
+                self.add_character(button.id, CharacterKind::Button);
+                for action in &button.actions {
+                    self.scan_actions(&action.action_data);
+                }
+            }
+            TagCode::DefineEditText => {
+                self.add_character(reader.read_character_id()?, CharacterKind::EditText)
+            }
+            TagCode::DefineFont => {
+                let font = reader.read_define_font_1()?;
+                self.add_font(font.id, None, Some(font.glyphs.len()));
+            }
+            TagCode::DefineFont2 | TagCode::DefineFont3 => {
+                let version = if tag == TagCode::DefineFont2 { 2 } else { 3 };
+                let font = reader.read_define_font_2(version)?;
+                self.add_font(font.id, Some(font.name), Some(font.glyphs.len()));
+            }
+            TagCode::DefineFont4 => {
+                let font = reader.read_define_font_4()?;
+                self.add_font(font.id, Some(font.name), None);
+            }
+            TagCode::DefineMorphShape | TagCode::DefineMorphShape2 => {
+                self.add_character(reader.read_character_id()?, CharacterKind::MorphShape)
+            }
+            TagCode::DefineShape
+            | TagCode::DefineShape2
+            | TagCode::DefineShape3
+            | TagCode::DefineShape4 => {
+                self.add_character(reader.read_character_id()?, CharacterKind::Shape)
+            }
+            TagCode::DefineSound => {
+                let sound = reader.read_define_sound()?;
+                self.add_character(sound.id, CharacterKind::Sound);
+                let duration = if sound.format.sample_rate > 0 {
+                    (u64::from(sound.num_samples) * 1000 / u64::from(sound.format.sample_rate))
+                        as u32
+                } else {
+                    0
+                };
+                self.summary.sounds.push(SoundSummary {
+                    id: sound.id,
+                    compression: format!("{:?}", sound.format.compression),
+                    sample_rate: sound.format.sample_rate,
+                    is_stereo: sound.format.is_stereo,
+                    is_16_bit: sound.format.is_16_bit,
+                    num_samples: sound.num_samples,
+                    duration,
+                    size: sound.data.len(),
+                });
+            }
+            TagCode::DefineText | TagCode::DefineText2 => {
+                self.add_character(reader.read_character_id()?, CharacterKind::Text)
+            }
+            TagCode::DefineVideoStream => {
+                self.add_character(reader.read_character_id()?, CharacterKind::Video)
+            }
+            TagCode::DoAction => {
+                self.summary.scripts.push(ScriptSummary {
+                    sprite_id,
+                    frame,
+                    init_sprite_id: None,
+                    size: tag_len,
+                });
+                self.scan_actions(tag_data);
+            }
+            TagCode::DoInitAction => {
+                let init_sprite_id = reader.read_character_id()?;
+                self.summary.scripts.push(ScriptSummary {
+                    sprite_id,
+                    frame,
+                    init_sprite_id: Some(init_sprite_id),
+                    size: tag_len.saturating_sub(2),
+                });
+                self.scan_actions(tag_data.get(2..).unwrap_or_default());
+            }
+            TagCode::ExportAssets => {
+                for export in reader.read_export_assets()? {
+                    self.export_names.insert(export.id, export.name);
+                }
+            }
+            TagCode::PlaceObject2 | TagCode::PlaceObject3 => {
+                let version = if tag == TagCode::PlaceObject2 { 2 } else { 3 };
+                let place_object = reader.read_place_object_2_or_3(version)?;
+                for clip_action in &place_object.clip_actions {
+                    self.scan_actions(&clip_action.action_data);
+                }
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn add_character(&mut self, id: CharacterId, kind: CharacterKind) {
+        self.summary.characters.push(CharacterSummary {
+            id,
+            kind,
+            export_name: None,
+        });
+    }
+
+    fn add_font(&mut self, id: CharacterId, name: Option<String>, glyph_count: Option<usize>) {
+        self.add_character(id, CharacterKind::Font);
+        self.summary.fonts.push(FontSummary {
+            id,
+            name,
+            glyph_count,
+        });
+    }
+
+    /// Scan a block of AVM1 byte code for external references, without running it.
+    ///
+    /// The bodies of functions, `with` blocks and `try` blocks are scanned too.
+    fn scan_actions(&mut self, action_data: &[u8]) {
+        let mut reader = ActionReader::new(action_data, self.summary.version);
+        // Function bodies and other nested blocks aren't terminated by an `End` action.
+        while reader.pos() < action_data.len() {
+            let action = match reader.read_action() {
+                Ok(Some(action)) => action,
+                Ok(None) => break,
+                Err(e) => {
+                    self.summary
+                        .warnings
+                        .push(format!("Error reading actions: {}", e));
+                    break;
+                }
+            };
+
+            match action {
+                Action::ConstantPool(strings) => {
+                    for string in strings {
+                        self.add_reference_if_url(string);
+                    }
+                }
+                Action::DefineFunction { actions, .. } => self.scan_actions(actions),
+                Action::DefineFunction2(function) => self.scan_actions(function.actions),
+                // `fscommand` calls are compiled to `GetURL` with a special prefix.
+                Action::GetUrl { url, .. }
+                    if !url.is_empty() && !url.to_lowercase().starts_with("fscommand:") =>
+                {
+                    self.external_references.insert(url.to_string());
+                }
+                Action::Push(values) => {
+                    for value in values {
+                        if let Value::Str(string) = value {
+                            self.add_reference_if_url(string);
+                        }
+                    }
+                }
+                Action::Try(try_block) => {
+                    self.scan_actions(try_block.try_actions);
+                    if let Some((_, catch_actions)) = try_block.catch {
+                        self.scan_actions(catch_actions);
+                    }
+                    if let Some(finally_actions) = try_block.finally {
+                        self.scan_actions(finally_actions);
+                    }
+                }
+                Action::With { actions } => self.scan_actions(actions),
+                _ => (),
+            }
+        }
+    }
+
+    fn add_reference_if_url(&mut self, string: &str) {
+        if looks_like_url(string) {
+            self.external_references.insert(string.to_string());
+        }
+    }
+}
+
+/// File extensions of the files that movies commonly load or link to.
+const REFERENCE_EXTENSIONS: &[&str] = &[
+    "asp", "aspx", "cgi", "flv", "gif", "htm", "html", "jpeg", "jpg", "mp3", "php", "png", "swf",
+    "txt", "xml",
+];
+
+/// Whether a string from a script looks like a URL or the name of a file to load.
+fn looks_like_url(string: &str) -> bool {
+    if string.contains(char::is_whitespace) {
+        return false;
+    }
+
+    let lower = string.to_lowercase();
+    if lower.contains("://") || lower.starts_with("mailto:") {
+        return true;
+    }
+
+    let path = lower.split(&['?', '#'][..]).next().unwrap_or_default();
+    match path.rfind('.') {
+        Some(dot) if dot > 0 => REFERENCE_EXTENSIONS.contains(&&path[dot + 1..]),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inspect_avm1_movie() {
+        let movie = SwfMovie::from_path("tests/swfs/avm1/inspect_movie/test.swf").unwrap();
+        let summary = inspect_movie(&movie);
+
+        assert_eq!(summary.version, 8);
+        assert_eq!(summary.num_frames, 2);
+        assert_eq!(summary.script_kind, ScriptKind::Avm1);
+        assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
+
+        assert_eq!(summary.frames.len(), 2);
+        assert_eq!(summary.frames[0].label.as_deref(), Some("start"));
+        let frame_1_tags: Vec<&str> = summary.frames[0]
+            .tags
+            .iter()
+            .map(|tag| tag.name.as_str())
+            .collect();
+        assert_eq!(
+            frame_1_tags,
+            vec![
+                "SetBackgroundColor",
+                "DefineShape",
+                "DefineFont",
+                "DefineSound",
+                "ExportAssets",
+                "DefineSprite",
+                "DoInitAction",
+                "FrameLabel",
+                "PlaceObject2",
+                "DoAction",
+                "ShowFrame",
+            ]
+        );
+        assert_eq!(summary.frames[0].tags[3].length, 11032);
+        assert_eq!(summary.frames[1].label, None);
+
+        let characters: Vec<_> = summary
+            .characters
+            .iter()
+            .map(|character| {
+                (
+                    character.id,
+                    character.kind,
+                    character.export_name.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            characters,
+            vec![
+                (1, CharacterKind::Shape, None),
+                (2, CharacterKind::Font, None),
+                (3, CharacterKind::Sound, Some("beep")),
+                (4, CharacterKind::Sprite, None),
+            ]
+        );
+
+        assert_eq!(summary.fonts.len(), 1);
+        assert_eq!(summary.fonts[0].name, None);
+        assert_eq!(summary.fonts[0].glyph_count, Some(3));
+
+        assert_eq!(summary.sounds.len(), 1);
+        assert_eq!(summary.sounds[0].compression, "Uncompressed");
+        assert_eq!(summary.sounds[0].sample_rate, 11025);
+        assert_eq!(summary.sounds[0].duration, 1000);
+
+        let scripts: Vec<_> = summary
+            .scripts
+            .iter()
+            .map(|script| (script.sprite_id, script.frame, script.init_sprite_id))
+            .collect();
+        assert_eq!(
+            scripts,
+            vec![
+                (Some(4), 1, None),
+                (None, 1, Some(4)),
+                (None, 1, None),
+                (None, 2, None),
+            ]
+        );
+
+        assert_eq!(
+            summary.external_references,
+            vec![
+                "data/config.xml",
+                "http://example.com/page.html",
+                "images/photo.jpg",
+                "movies/intro.swf",
+            ]
+        );
+    }
+
+    #[test]
+    fn inspect_avm2_movie() {
+        let movie = SwfMovie::from_path("tests/swfs/avm2/script_kind_avm2/test.swf").unwrap();
+        let summary = inspect_movie(&movie);
+
+        assert_eq!(summary.script_kind, ScriptKind::Avm2);
+        assert!(summary.frames[0].tags.iter().any(|tag| tag.name == "DoAbc"));
+        assert!(summary.external_references.is_empty());
+    }
+}
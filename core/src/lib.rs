@@ -9,28 +9,35 @@ extern crate smallvec;
 #[macro_use]
 extern crate downcast_rs;
 
+pub mod amf;
 mod avm1;
+pub mod bitmap_data;
 mod bounding_box;
 mod character;
 pub mod color_transform;
 mod context;
+pub mod context_menu;
 pub mod events;
+pub mod external;
 mod font;
+pub mod inspect;
 mod library;
 mod loader;
 pub mod matrix;
 mod player;
 mod prelude;
 mod property_map;
+mod save_state;
 pub mod shape_utils;
 pub mod string_utils;
 pub mod tag_utils;
 mod transform;
+pub mod viewport;
 mod xml;
 
 pub mod backend;
 
 pub use events::PlayerEvent;
-pub use player::Player;
+pub use player::{Player, NEWEST_PLAYER_VERSION};
 pub use swf;
 pub use swf::Color;
@@ -1,4 +1,5 @@
 use crate::backend::audio::SoundHandle;
+use crate::backend::render::RenderBackend;
 use crate::character::Character;
 use crate::display_object::TDisplayObject;
 use crate::font::Font;
@@ -8,7 +9,6 @@ use gc_arena::MutationContext;
 use std::collections::HashMap;
 use std::sync::{Arc, Weak};
 use swf::CharacterId;
-use weak_table::PtrWeakKeyHashMap;
 
 /// Symbol library for a single given SWF.
 pub struct MovieLibrary<'gc> {
@@ -37,6 +37,12 @@ impl<'gc> MovieLibrary<'gc> {
         }
     }
 
+    /// Replaces a character that has already been registered, such as when a later tag adds
+    /// information to it. The old character is no longer returned for this ID.
+    pub fn replace_character(&mut self, id: CharacterId, character: Character<'gc>) {
+        self.characters.insert(id, character);
+    }
+
     /// Registers an export name for a given character ID.
     /// This character will then be instantiable from AVM1.
     pub fn register_export(&mut self, id: CharacterId, export_name: &str) {
@@ -62,6 +68,13 @@ impl<'gc> MovieLibrary<'gc> {
         }
     }
 
+    /// Iterate over the exported characters of this library, along with their export names.
+    pub fn export_characters(&self) -> impl Iterator<Item = (&str, &Character<'gc>)> {
+        self.export_characters
+            .iter()
+            .map(|(name, character)| (name.as_str(), character))
+    }
+
     pub fn contains_character(&self, id: CharacterId) -> bool {
         self.characters.contains_key(&id)
     }
@@ -173,6 +186,29 @@ impl<'gc> MovieLibrary<'gc> {
     pub fn set_device_font(&mut self, font: Option<Font<'gc>>) {
         self.device_font = font;
     }
+
+    /// Release the shapes and bitmaps registered with the renderer by this library's characters.
+    ///
+    /// The device font is shared between movies, so it is left alone.
+    fn unregister_render_resources(&self, renderer: &mut dyn RenderBackend) {
+        for character in self.characters.values() {
+            match character {
+                Character::Bitmap(bitmap) => renderer.unregister_bitmap(bitmap.bitmap_handle()),
+                Character::Font(font) => {
+                    for shape in font.glyph_shape_handles() {
+                        renderer.unregister_shape(shape);
+                    }
+                }
+                Character::Graphic(graphic) => renderer.unregister_shape(graphic.shape_handle()),
+                Character::MorphShape(morph_shape) => {
+                    for shape in morph_shape.shape_handles() {
+                        renderer.unregister_shape(shape);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
 }
 
 unsafe impl<'gc> gc_arena::Collect for MovieLibrary<'gc> {
@@ -193,38 +229,127 @@ impl Default for MovieLibrary<'_> {
 
 /// Symbol library for multiple movies.
 pub struct Library<'gc> {
-    /// All the movie libraries.
-    movie_libraries: PtrWeakKeyHashMap<Weak<SwfMovie>, MovieLibrary<'gc>>,
+    /// All the movie libraries, keyed by the address of their movie.
+    ///
+    /// A library lives for as long as its movie, which is kept alive by the display objects
+    /// instantiated from it.
+    movie_libraries: HashMap<usize, (Weak<SwfMovie>, MovieLibrary<'gc>)>,
+
+    /// Libraries whose movies were dropped, waiting for their render resources to be released.
+    unloaded_libraries: Vec<MovieLibrary<'gc>>,
+
+    /// Fonts registered by the host of the player, keyed by name.
+    /// Text fields of every movie can use them.
+    registered_fonts: HashMap<String, Font<'gc>>,
 }
 
 unsafe impl<'gc> gc_arena::Collect for Library<'gc> {
     #[inline]
     fn trace(&self, cc: gc_arena::CollectionContext) {
-        for (_, val) in self.movie_libraries.iter() {
+        for (_, val) in self.movie_libraries.values() {
+            val.trace(cc);
+        }
+        for val in &self.unloaded_libraries {
             val.trace(cc);
         }
+        for font in self.registered_fonts.values() {
+            font.trace(cc);
+        }
     }
 }
 
 impl<'gc> Library<'gc> {
     pub fn library_for_movie(&self, movie: Arc<SwfMovie>) -> Option<&MovieLibrary<'gc>> {
-        self.movie_libraries.get(&movie)
+        match self.movie_libraries.get(&movie_key(&movie)) {
+            Some((weak, library)) if is_same_movie(weak, &movie) => Some(library),
+            _ => None,
+        }
     }
 
     pub fn library_for_movie_mut(&mut self, movie: Arc<SwfMovie>) -> &mut MovieLibrary<'gc> {
-        if !self.movie_libraries.contains_key(&movie) {
-            self.movie_libraries
-                .insert(movie.clone(), MovieLibrary::default());
-        };
+        use std::collections::hash_map::Entry;
+        match self.movie_libraries.entry(movie_key(&movie)) {
+            Entry::Occupied(mut entry) => {
+                if !is_same_movie(&entry.get().0, &movie) {
+                    // The address belonged to a movie that has since been dropped.
+                    let (_, old_library) =
+                        entry.insert((Arc::downgrade(&movie), MovieLibrary::default()));
+                    self.unloaded_libraries.push(old_library);
+                }
+                &mut entry.into_mut().1
+            }
+            Entry::Vacant(entry) => {
+                &mut entry
+                    .insert((Arc::downgrade(&movie), MovieLibrary::default()))
+                    .1
+            }
+        }
+    }
+
+    /// Returns the font registered with the given name, if any.
+    pub fn registered_font(&self, name: &str) -> Option<Font<'gc>> {
+        self.registered_fonts.get(name).copied()
+    }
+
+    /// Returns the registered font with the first name, in sorted order, that has a glyph for
+    /// every character of the given text.
+    pub fn registered_font_with_glyphs_for(&self, text: &str) -> Option<Font<'gc>> {
+        let mut names: Vec<&String> = self.registered_fonts.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| self.registered_fonts[name])
+            .find(|font| font.has_glyphs_for(text))
+    }
+
+    /// Registers a font that text fields can use by name, returning the font that was
+    /// registered with the same name before, if any.
+    pub fn register_font(&mut self, name: &str, font: Font<'gc>) -> Option<Font<'gc>> {
+        self.registered_fonts.insert(name.to_string(), font)
+    }
+
+    /// Release the render resources of the libraries of movies that have been dropped.
+    ///
+    /// This should be called once per frame. Libraries are only released on the call after
+    /// their movie is found to be dropped, so that nothing from them is released in the middle
+    /// of a frame that may still draw them.
+    pub fn release_unloaded_libraries(&mut self, renderer: &mut dyn RenderBackend) {
+        for library in self.unloaded_libraries.drain(..) {
+            library.unregister_render_resources(renderer);
+        }
 
-        self.movie_libraries.get_mut(&movie).unwrap()
+        let unloaded: Vec<usize> = self
+            .movie_libraries
+            .iter()
+            .filter(|(_, (movie, _))| movie.upgrade().is_none())
+            .map(|(key, _)| *key)
+            .collect();
+        for key in unloaded {
+            if let Some((_, library)) = self.movie_libraries.remove(&key) {
+                self.unloaded_libraries.push(library);
+            }
+        }
     }
 }
 
+/// The key of a movie's library, which is the address of the movie.
+fn movie_key(movie: &Arc<SwfMovie>) -> usize {
+    &**movie as *const SwfMovie as usize
+}
+
+/// Whether the given weak reference points to the given movie, rather than to a dropped movie
+/// that had the same address.
+fn is_same_movie(weak: &Weak<SwfMovie>, movie: &Arc<SwfMovie>) -> bool {
+    weak.upgrade()
+        .map_or(false, |other| Arc::ptr_eq(&other, movie))
+}
+
 impl<'gc> Default for Library<'gc> {
     fn default() -> Self {
         Self {
-            movie_libraries: PtrWeakKeyHashMap::new(),
+            movie_libraries: HashMap::new(),
+            unloaded_libraries: Vec::new(),
+            registered_fonts: HashMap::new(),
         }
     }
 }
@@ -1,7 +1,12 @@
 //! Management of async loaders
 
+use crate::amf::Packet;
+use crate::avm1::globals::net_connection;
 use crate::avm1::{Object, TObject, Value};
-use crate::backend::navigator::OwnedFuture;
+use crate::backend::navigator::{
+    CancellationToken, HttpStatusError, NavigatorBackend, OwnedFuture, ProgressCallback,
+    RequestOptions, SpawnHandle,
+};
 use crate::context::{ActionQueue, ActionType};
 use crate::display_object::{DisplayObject, MorphShape, TDisplayObject};
 use crate::player::{Player, NEWEST_PLAYER_VERSION};
@@ -9,6 +14,7 @@ use crate::tag_utils::SwfMovie;
 use crate::xml::XMLNode;
 use gc_arena::{Collect, CollectionContext};
 use generational_arena::{Arena, Index};
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex, Weak};
 use url::form_urlencoded;
 
@@ -16,6 +22,13 @@ pub type Handle = Index;
 
 type Error = Box<dyn std::error::Error>;
 
+/// How many times a movie may be nested inside itself by loading it into one of its own
+/// clips, or into a clip of a movie it loaded.
+///
+/// Loading a movie into one of its own clips is legitimate, but a movie that does so
+/// unconditionally would otherwise keep loading copies of itself until memory runs out.
+const MAX_NESTED_LOADS: usize = 16;
+
 /// Holds all in-progress loads for the player.
 pub struct LoadManager<'gc>(Arena<Loader<'gc>>);
 
@@ -59,28 +72,111 @@ impl<'gc> LoadManager<'gc> {
         self.0.get_mut(handle)
     }
 
+    /// Remove a completed loader, invalidating its handle.
+    pub fn remove_loader(&mut self, handle: Handle) -> Option<Loader<'gc>> {
+        self.0.remove(handle)
+    }
+
+    /// Cancel every load in progress, invalidating all loader handles.
+    ///
+    /// Load processes that are still running will find their loader gone and give up.
+    pub fn cancel_all(&mut self) {
+        self.0.clear();
+    }
+
+    /// The number of loads that have not completed yet.
+    pub fn pending_loads(&self) -> usize {
+        self.0.len()
+    }
+
     /// Kick off a movie clip load.
     ///
-    /// Returns the loader's async process, which you will need to spawn.
+    /// The movie is fetched through `navigator`, with its progress reported to the clip's
+    /// `MovieClipLoader`, if any. The load's async process is spawned on `navigator` with
+    /// `token`, and stops being polled once the load is cancelled with `cancel_movie_loads`.
+    #[allow(clippy::too_many_arguments)]
     pub fn load_movie_into_clip(
         &mut self,
         player: Weak<Mutex<Player>>,
         target_clip: DisplayObject<'gc>,
-        fetch: OwnedFuture<Vec<u8>, Error>,
+        navigator: &mut dyn NavigatorBackend,
+        token: &CancellationToken,
+        url: String,
+        request_options: RequestOptions,
         target_broadcaster: Option<Object<'gc>>,
-    ) -> OwnedFuture<(), Error> {
+    ) {
+        let resolved_url = navigator.resolve_relative_url(&url).into_owned();
+        let nested_loads = nested_loads(target_clip, &resolved_url);
+        if nested_loads >= MAX_NESTED_LOADS {
+            log::warn!(
+                "Not loading {} into {}: it is already nested {} times there",
+                resolved_url,
+                target_clip.path(),
+                nested_loads
+            );
+            return;
+        }
+
         let loader = Loader::Movie {
             self_handle: None,
             target_clip,
             target_broadcaster,
             load_complete: false,
+            bytes_loaded: 0,
+            bytes_total: None,
+            spawn_handle: None,
         };
         let handle = self.add_loader(loader);
 
+        let progress = Loader::movie_progress_callback(player.clone(), handle);
+        let fetch = navigator.fetch_with_progress(url, request_options, progress);
+
         let loader = self.get_loader_mut(handle).unwrap();
         loader.introduce_loader_handle(handle);
 
-        loader.movie_loader(player, fetch)
+        let process = loader.movie_loader(player, fetch, resolved_url);
+        let spawned = navigator.spawn_future(process, token.clone());
+        if let Some(Loader::Movie { spawn_handle, .. }) = self.get_loader_mut(handle) {
+            *spawn_handle = Some(spawned);
+        }
+    }
+
+    /// Cancel every movie load into `clip`, e.g. because it was unloaded.
+    ///
+    /// The loads' async processes are not polled again.
+    pub fn cancel_movie_loads(&mut self, clip: DisplayObject<'gc>) {
+        let cancelled: Vec<Handle> = self
+            .0
+            .iter()
+            .filter_map(|(handle, loader)| match loader {
+                Loader::Movie { target_clip, .. } if DisplayObject::ptr_eq(*target_clip, clip) => {
+                    Some(handle)
+                }
+                _ => None,
+            })
+            .collect();
+
+        // Dropping a loader drops the handle to its process, which cancels it.
+        for handle in cancelled {
+            self.remove_loader(handle);
+        }
+    }
+
+    /// The number of bytes loaded so far of the movie being loaded into a clip, and the total,
+    /// if known.
+    ///
+    /// Returns `None` if no movie is being loaded into the clip.
+    pub fn movie_progress(&self, clip: DisplayObject<'gc>) -> Option<(usize, Option<usize>)> {
+        self.0.iter().find_map(|(_, loader)| match loader {
+            Loader::Movie {
+                target_clip,
+                load_complete: false,
+                bytes_loaded,
+                bytes_total,
+                ..
+            } if DisplayObject::ptr_eq(*target_clip, clip) => Some((*bytes_loaded, *bytes_total)),
+            _ => None,
+        })
     }
 
     /// Indicates that a movie clip has initialized (ran it's first frame).
@@ -148,6 +244,37 @@ impl<'gc> LoadManager<'gc> {
 
         loader.xml_loader(player, fetch)
     }
+
+    /// Kick off a Flash Remoting call on a `NetConnection`.
+    ///
+    /// The request is made by `make_request`, which is given the response URI the gateway must
+    /// reply to. Response URIs are unique among in-flight calls, so that each call picks its own
+    /// result out of the response.
+    ///
+    /// Returns the loader's async process, which you will need to spawn.
+    pub fn load_remoting_result(
+        &mut self,
+        player: Weak<Mutex<Player>>,
+        connection: Object<'gc>,
+        responder: Option<Object<'gc>>,
+        active_clip: DisplayObject<'gc>,
+        make_request: impl FnOnce(&str) -> OwnedFuture<Vec<u8>, Error>,
+    ) -> OwnedFuture<(), Error> {
+        let loader = Loader::Remoting {
+            self_handle: None,
+            active_clip,
+            connection,
+            responder,
+        };
+        let handle = self.add_loader(loader);
+        let response_uri = format!("/{}", handle.into_raw_parts().0 + 1);
+        let fetch = make_request(&response_uri);
+
+        let loader = self.get_loader_mut(handle).unwrap();
+        loader.introduce_loader_handle(handle);
+
+        loader.remoting_loader(player, fetch, response_uri)
+    }
 }
 
 impl<'gc> Default for LoadManager<'gc> {
@@ -156,6 +283,68 @@ impl<'gc> Default for LoadManager<'gc> {
     }
 }
 
+/// Count the parents of a clip that are playing the movie at the given URL.
+///
+/// The clip itself is not counted, as its movie is about to be replaced.
+fn nested_loads(target_clip: DisplayObject<'_>, url: &str) -> usize {
+    let mut count = 0;
+    let mut parent = target_clip.parent();
+    while let Some(clip) = parent {
+        if clip.movie().as_ref().and_then(|movie| movie.url()) == Some(url) {
+            count += 1;
+        }
+        parent = clip.parent();
+    }
+    count
+}
+
+/// Movies that were already loaded by a player, keyed by their resolved URL.
+///
+/// Loading the same URL again reuses the parsed movie instead of fetching it again. Each load
+/// still gets its own copy of the movie, so that clips loaded from the same URL don't share
+/// their libraries or any other runtime state.
+pub struct MovieCache {
+    enabled: bool,
+
+    /// The cached movies, along with the size of their downloaded data.
+    movies: HashMap<String, (SwfMovie, usize)>,
+}
+
+impl MovieCache {
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            movies: HashMap::new(),
+        }
+    }
+
+    /// Enable or disable the cache. Disabling it drops all cached movies.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.movies.clear();
+        }
+    }
+
+    /// Get a copy of the movie at the given URL, along with the size of its downloaded data.
+    pub fn get(&self, url: &str) -> Option<(SwfMovie, usize)> {
+        self.movies.get(url).cloned()
+    }
+
+    /// Remember a movie that was downloaded from the given URL.
+    pub fn insert(&mut self, url: String, movie: &SwfMovie, length: usize) {
+        if self.enabled {
+            self.movies.insert(url, (movie.clone(), length));
+        }
+    }
+}
+
+impl Default for MovieCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// A struct that holds garbage-collected pointers for asynchronous code.
 pub enum Loader<'gc> {
     /// Loader that is loading a new movie into a movieclip.
@@ -179,6 +368,16 @@ pub enum Loader<'gc> {
         /// or an error has occured (in which case we don't care about the
         /// loader anymore).
         load_complete: bool,
+
+        /// The number of bytes of the movie fetched so far.
+        bytes_loaded: usize,
+
+        /// The size of the movie in bytes, if the navigator knows it.
+        bytes_total: Option<usize>,
+
+        /// The spawned async process of this load, which is cancelled along
+        /// with the loader.
+        spawn_handle: Option<SpawnHandle>,
     },
 
     /// Loader that is loading form data into an AVM1 object scope.
@@ -206,6 +405,21 @@ pub enum Loader<'gc> {
         /// The target node whose contents will be replaced with the parsed XML.
         target_node: XMLNode<'gc>,
     },
+
+    /// Loader that is waiting for the result of a Flash Remoting call.
+    Remoting {
+        /// The handle to refer to this loader instance.
+        self_handle: Option<Handle>,
+
+        /// The active movie clip at the time of the call.
+        active_clip: DisplayObject<'gc>,
+
+        /// The `NetConnection` the call was made on, which is notified of failed calls.
+        connection: Object<'gc>,
+
+        /// The object whose `onResult` or `onStatus` method is called with the result.
+        responder: Option<Object<'gc>>,
+    },
 }
 
 unsafe impl<'gc> Collect for Loader<'gc> {
@@ -221,6 +435,16 @@ unsafe impl<'gc> Collect for Loader<'gc> {
             }
             Loader::Form { target_object, .. } => target_object.trace(cc),
             Loader::XML { target_node, .. } => target_node.trace(cc),
+            Loader::Remoting {
+                active_clip,
+                connection,
+                responder,
+                ..
+            } => {
+                active_clip.trace(cc);
+                connection.trace(cc);
+                responder.trace(cc);
+            }
         }
     }
 }
@@ -235,9 +459,69 @@ impl<'gc> Loader<'gc> {
             Loader::Movie { self_handle, .. } => *self_handle = Some(handle),
             Loader::Form { self_handle, .. } => *self_handle = Some(handle),
             Loader::XML { self_handle, .. } => *self_handle = Some(handle),
+            Loader::Remoting { self_handle, .. } => *self_handle = Some(handle),
         }
     }
 
+    /// Construct the callback that a movie loader's fetch reports its progress to.
+    ///
+    /// Each report is recorded in the loader, and reports of an incomplete movie are
+    /// broadcast as `onLoadProgress`. The movie loader broadcasts the final one itself, once
+    /// the movie has arrived, including when it comes from the movie cache.
+    fn movie_progress_callback(player: Weak<Mutex<Player>>, handle: Handle) -> ProgressCallback {
+        Box::new(move |loaded, total| {
+            let player = match player.upgrade() {
+                Some(player) => player,
+                None => return,
+            };
+
+            let result = player.lock().expect("Could not lock player!!").update(
+                |avm, uc| -> Result<(), Error> {
+                    let (clip, broadcaster) = match uc.load_manager.get_loader_mut(handle) {
+                        Some(Loader::Movie {
+                            target_clip,
+                            target_broadcaster,
+                            bytes_loaded,
+                            bytes_total,
+                            ..
+                        }) => {
+                            *bytes_loaded = loaded;
+                            *bytes_total = total;
+                            (*target_clip, *target_broadcaster)
+                        }
+                        _ => return Ok(()),
+                    };
+
+                    if total == Some(loaded) {
+                        return Ok(());
+                    }
+
+                    if let Some(broadcaster) = broadcaster {
+                        avm.insert_stack_frame_for_method(
+                            clip,
+                            broadcaster,
+                            NEWEST_PLAYER_VERSION,
+                            uc,
+                            "broadcastMessage",
+                            &[
+                                "onLoadProgress".into(),
+                                Value::Object(broadcaster),
+                                loaded.into(),
+                                total.map_or(Value::Undefined, Value::from),
+                            ],
+                        );
+                        avm.run_stack_till_empty(uc)?;
+                    }
+
+                    Ok(())
+                },
+            );
+            if let Err(e) = result {
+                log::warn!("Error reporting load progress: {}", e);
+            }
+        })
+    }
+
     /// Construct a future for the given movie loader.
     ///
     /// The given future should be passed immediately to an executor; it will
@@ -249,6 +533,7 @@ impl<'gc> Loader<'gc> {
         &mut self,
         player: Weak<Mutex<Player>>,
         fetch: OwnedFuture<Vec<u8>, Error>,
+        url: String,
     ) -> OwnedFuture<(), Error> {
         let handle = match self {
             Loader::Movie { self_handle, .. } => self_handle.expect("Loader not self-introduced"),
@@ -294,7 +579,25 @@ impl<'gc> Loader<'gc> {
                 },
             )?;
 
-            let data = (fetch.await).and_then(|data| Ok((data.len(), SwfMovie::from_data(&data)?)));
+            let cached = player
+                .lock()
+                .expect("Could not lock player!!")
+                .movie_cache()
+                .get(&url);
+            let data = match cached {
+                Some((movie, length)) => Ok((length, movie)),
+                None => (fetch.await).and_then(|data| {
+                    let movie = SwfMovie::from_data(&data, Some(url.clone()))?;
+                    player
+                        .lock()
+                        .expect("Could not lock player!!")
+                        .movie_cache_mut()
+                        .insert(url, &movie, data.len());
+                    Ok((data.len(), movie))
+                }),
+            };
+            // Only a fetch that got a response has a status; a movie that fails to decode doesn't.
+            let http_status = data.as_ref().err().and_then(HttpStatusError::status_of);
             if let Ok((length, movie)) = data {
                 let movie = Arc::new(movie);
 
@@ -371,11 +674,11 @@ impl<'gc> Loader<'gc> {
                         Ok(())
                     })
             } else {
-                //TODO: Inspect the fetch error.
-                //This requires cooperation from the backend to send abstract
-                //error types we can actually inspect.
-                //This also can get errors from decoding an invalid SWF file,
-                //too. We should distinguish those to player code.
+                // A server that answered with an error status is reported like a missing file.
+                let error_code = match http_status {
+                    Some(_) => "URLNotFound",
+                    None => "LoadNeverCompleted",
+                };
                 player.lock().expect("Could not lock player!!").update(
                     |avm, uc| -> Result<(), Error> {
                         let (clip, broadcaster) = match uc.load_manager.get_loader(handle) {
@@ -398,7 +701,8 @@ impl<'gc> Loader<'gc> {
                                 &[
                                     "onLoadError".into(),
                                     Value::Object(broadcaster),
-                                    "LoadNeverCompleted".into(),
+                                    error_code.into(),
+                                    http_status.unwrap_or(0).into(),
                                 ],
                             );
                             avm.run_stack_till_empty(uc)?;
@@ -432,17 +736,17 @@ impl<'gc> Loader<'gc> {
             .expect("Could not upgrade weak reference to player");
 
         Box::pin(async move {
-            let data = fetch.await?;
+            let data = fetch.await;
 
             player.lock().unwrap().update(|avm, uc| {
-                let loader = uc.load_manager.get_loader(handle);
+                let loader = uc.load_manager.remove_loader(handle);
                 let that = match loader {
-                    Some(Loader::Form { target_object, .. }) => *target_object,
+                    Some(Loader::Form { target_object, .. }) => target_object,
                     None => return Err("Load cancelled".into()),
                     _ => return Err("Non-movie loader spawned as movie loader".into()),
                 };
 
-                for (k, v) in form_urlencoded::parse(&data) {
+                for (k, v) in form_urlencoded::parse(&data?) {
                     that.set(&k, v.into_owned().into(), avm, uc)?;
                 }
 
@@ -510,17 +814,22 @@ impl<'gc> Loader<'gc> {
 
         Box::pin(async move {
             let data = fetch.await;
+            // Flash Player reports 0 if it couldn't get a status from the server.
+            let http_status = data
+                .as_ref()
+                .map(|_| 200)
+                .unwrap_or_else(|e| HttpStatusError::status_of(e).unwrap_or(0));
             if let Ok(data) = data {
                 let xmlstring = String::from_utf8(data)?;
 
                 player.lock().expect("Could not lock player!!").update(
                     |avm, uc| -> Result<(), Error> {
-                        let (mut node, active_clip) = match uc.load_manager.get_loader(handle) {
+                        let (mut node, active_clip) = match uc.load_manager.remove_loader(handle) {
                             Some(Loader::XML {
                                 target_node,
                                 active_clip,
                                 ..
-                            }) => (*target_node, *active_clip),
+                            }) => (target_node, active_clip),
                             None => return Err("Load cancelled".into()),
                             _ => unreachable!(),
                         };
@@ -533,7 +842,7 @@ impl<'gc> Loader<'gc> {
                             NEWEST_PLAYER_VERSION,
                             uc,
                             "onHTTPStatus",
-                            &[200.into()],
+                            &[http_status.into()],
                         );
                         avm.run_stack_till_empty(uc)?;
 
@@ -553,12 +862,12 @@ impl<'gc> Loader<'gc> {
             } else {
                 player.lock().expect("Could not lock player!!").update(
                     |avm, uc| -> Result<(), Error> {
-                        let (mut node, active_clip) = match uc.load_manager.get_loader(handle) {
+                        let (mut node, active_clip) = match uc.load_manager.remove_loader(handle) {
                             Some(Loader::XML {
                                 target_node,
                                 active_clip,
                                 ..
-                            }) => (*target_node, *active_clip),
+                            }) => (target_node, active_clip),
                             None => return Err("Load cancelled".into()),
                             _ => unreachable!(),
                         };
@@ -571,7 +880,7 @@ impl<'gc> Loader<'gc> {
                             NEWEST_PLAYER_VERSION,
                             uc,
                             "onHTTPStatus",
-                            &[404.into()],
+                            &[http_status.into()],
                         );
                         avm.run_stack_till_empty(uc)?;
 
@@ -593,4 +902,125 @@ impl<'gc> Loader<'gc> {
             Ok(())
         })
     }
+
+    pub fn remoting_loader(
+        &mut self,
+        player: Weak<Mutex<Player>>,
+        fetch: OwnedFuture<Vec<u8>, Error>,
+        response_uri: String,
+    ) -> OwnedFuture<(), Error> {
+        let handle = match self {
+            Loader::Remoting { self_handle, .. } => {
+                self_handle.expect("Loader not self-introduced")
+            }
+            _ => {
+                return Box::pin(async {
+                    Err("Non-remoting loader spawned as remoting loader".into())
+                })
+            }
+        };
+
+        let player = player
+            .upgrade()
+            .expect("Could not upgrade weak reference to player");
+
+        Box::pin(async move {
+            let data = fetch.await;
+
+            player
+                .lock()
+                .expect("Could not lock player!!")
+                .update(|avm, uc| -> Result<(), Error> {
+                    let (active_clip, connection, responder) =
+                        match uc.load_manager.remove_loader(handle) {
+                            Some(Loader::Remoting {
+                                active_clip,
+                                connection,
+                                responder,
+                                ..
+                            }) => (active_clip, connection, responder),
+                            None => return Err("Load cancelled".into()),
+                            _ => unreachable!(),
+                        };
+
+                    let packet = match data {
+                        Ok(data) => Packet::from_bytes(&data),
+                        Err(e) => {
+                            // The gateway could not be reached.
+                            let info = net_connection::status_object(
+                                avm,
+                                uc.gc_context,
+                                "error",
+                                "NetConnection.Call.Failed",
+                                Some(format!("HTTP: Failed ({})", e)),
+                            );
+                            uc.action_queue.queue_actions(
+                                active_clip,
+                                ActionType::Method {
+                                    object: connection,
+                                    name: "onStatus",
+                                    args: vec![info.into()],
+                                },
+                                false,
+                            );
+                            return Ok(());
+                        }
+                    };
+
+                    let result_uri = format!("{}/onResult", response_uri);
+                    let status_uri = format!("{}/onStatus", response_uri);
+                    let result = packet.map(|packet| {
+                        packet.messages.into_iter().find(|message| {
+                            message.target_uri == result_uri || message.target_uri == status_uri
+                        })
+                    });
+
+                    let (object, name, args) = match result {
+                        Ok(Some(message)) => {
+                            let value =
+                                net_connection::amf_to_value(avm, uc.gc_context, &message.value);
+                            let name = if message.target_uri == result_uri {
+                                "onResult"
+                            } else {
+                                "onStatus"
+                            };
+
+                            match responder {
+                                Some(responder) => (responder, name, vec![value]),
+                                None => return Ok(()),
+                            }
+                        }
+                        Ok(None) => {
+                            let info = net_connection::status_object(
+                                avm,
+                                uc.gc_context,
+                                "error",
+                                "NetConnection.Call.Failed",
+                                Some(format!("No response for {}", response_uri)),
+                            );
+                            (connection, "onStatus", vec![info.into()])
+                        }
+                        Err(e) => {
+                            // The response is not in a format we understand, such as AMF3.
+                            let info = net_connection::status_object(
+                                avm,
+                                uc.gc_context,
+                                "error",
+                                "NetConnection.Call.BadVersion",
+                                Some(e.to_string()),
+                            );
+                            (connection, "onStatus", vec![info.into()])
+                        }
+                    };
+
+                    uc.action_queue.queue_actions(
+                        active_clip,
+                        ActionType::Method { object, name, args },
+                        false,
+                    );
+
+                    Ok(())
+                })
+        })
+    }
 }
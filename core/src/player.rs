@@ -1,17 +1,31 @@
+use crate::avm1::globals::shared_object;
 use crate::avm1::listeners::SystemListener;
-use crate::avm1::{Activation, Avm1, TObject, Value};
-use crate::backend::input::{InputBackend, MouseCursor};
+use crate::avm1::{Activation, Avm1, Object, ScriptState, TObject, Value};
+use crate::backend::input::InputBackend;
+use crate::backend::log::LogBackend;
+use crate::backend::ui::{MessageLevel, MouseCursor, UiBackend};
 use crate::backend::{
-    audio::AudioBackend, navigator::NavigatorBackend, render::Letterbox, render::RenderBackend,
+    audio::{AudioBackend, NullAudioBackend},
+    locale::{FixedOffsetTimeZone, TimeZoneProvider, DEFAULT_LOCALE},
+    navigator::{CancellationToken, NavigatorBackend, RewritingNavigator, UrlRewriteRules},
+    render::Letterbox,
+    render::RenderBackend,
+    storage::StorageBackend,
 };
 use crate::context::{ActionQueue, ActionType, RenderContext, UpdateContext};
-use crate::display_object::{MorphShape, MovieClip};
-use crate::events::{ButtonEvent, ButtonEventResult, ButtonKeyCode, ClipEvent, PlayerEvent};
+use crate::context_menu::{ContextMenuItem, ContextMenuState};
+use crate::display_object::{recompose_matrices, MorphShape, MovieClip};
+use crate::events::{
+    ButtonEvent, ButtonEventResult, ButtonKeyCode, ClipEvent, KeyboardState, PlayerEvent,
+};
+use crate::external::{ExternalInterface, ExternalInterfaceProvider, Value as ExternalValue};
 use crate::library::Library;
-use crate::loader::LoadManager;
+use crate::loader::{LoadManager, MovieCache};
 use crate::prelude::*;
-use crate::tag_utils::SwfMovie;
+use crate::save_state::SaveState;
+use crate::tag_utils::{ScriptKind, SwfMovie};
 use crate::transform::TransformStack;
+use crate::viewport::{fit_stage, StageScaleMode};
 use gc_arena::{make_arena, ArenaParameters, Collect, GcCell};
 use log::info;
 use rand::{rngs::SmallRng, SeedableRng};
@@ -19,6 +33,7 @@ use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::ops::DerefMut;
 use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
 
 static DEVICE_FONT_TAG: &[u8] = include_bytes!("../assets/noto-sans-definefont3.bin");
 
@@ -26,6 +41,30 @@ static DEVICE_FONT_TAG: &[u8] = include_bytes!("../assets/noto-sans-definefont3.
 /// `player_version`.
 pub const NEWEST_PLAYER_VERSION: u8 = 32;
 
+/// The prompt shown when a script runs for longer than the maximum execution duration.
+const SCRIPT_TIMEOUT_MESSAGE: &str = "A script in this movie is causing Ruffle to run slowly. \
+If it continues to run, the movie may become unresponsive.";
+
+/// The choices offered by the script timeout prompt, in order.
+const SCRIPT_TIMEOUT_CHOICES: &[&str] = &["Continue", "Abort"];
+
+/// The index of the choice in `SCRIPT_TIMEOUT_CHOICES` that aborts the script.
+const SCRIPT_TIMEOUT_ABORT: usize = 1;
+
+/// The default value of `_soundbuftime`, in seconds.
+const DEFAULT_SOUND_BUFFER_TIME: i32 = 5;
+
+/// How long `Player::shutdown` waits for a busy executor to drop the player's futures.
+const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The message shown when an ActionScript 3 movie is loaded.
+const AVM2_UNSUPPORTED_MESSAGE: &str = "This movie uses ActionScript 3, which Ruffle does not \
+support yet. Only its first frame is shown, and its scripts will not run.";
+
+/// The message shown when the renderer can no longer draw.
+const RENDERER_LOST_MESSAGE: &str = "Ruffle lost access to the graphics device and can no \
+longer draw this movie. Please restart the player.";
+
 #[derive(Collect)]
 #[collect(no_drop)]
 struct GcRoot<'gc>(GcCell<'gc, GcRootData<'gc>>);
@@ -47,12 +86,24 @@ struct GcRootData<'gc> {
     /// The object being dragged via a `startDrag` action.
     drag_object: Option<DragObject<'gc>>,
 
+    /// The text field with keyboard focus.
+    focused_object: Option<DisplayObject<'gc>>,
+
     avm: Avm1<'gc>,
     action_queue: ActionQueue<'gc>,
 
     /// Object which manages asynchronous processes that need to interact with
     /// data in the GC arena.
     load_manager: LoadManager<'gc>,
+
+    /// The callbacks of the context menu currently shown by the frontend.
+    context_menu: ContextMenuState<'gc>,
+
+    /// The host of the movie, and the callbacks ActionScript has exposed to it.
+    external_interface: ExternalInterface<'gc>,
+
+    /// The objects returned by `SharedObject.getLocal`, by storage key.
+    shared_objects: BTreeMap<String, Object<'gc>>,
 }
 
 impl<'gc> GcRootData<'gc> {
@@ -66,7 +117,11 @@ impl<'gc> GcRootData<'gc> {
         &mut ActionQueue<'gc>,
         &mut Avm1<'gc>,
         &mut Option<DragObject<'gc>>,
+        &mut Option<DisplayObject<'gc>>,
         &mut LoadManager<'gc>,
+        &mut ContextMenuState<'gc>,
+        &mut ExternalInterface<'gc>,
+        &mut BTreeMap<String, Object<'gc>>,
     ) {
         (
             &mut self.levels,
@@ -74,7 +129,11 @@ impl<'gc> GcRootData<'gc> {
             &mut self.action_queue,
             &mut self.avm,
             &mut self.drag_object,
+            &mut self.focused_object,
             &mut self.load_manager,
+            &mut self.context_menu,
+            &mut self.external_interface,
+            &mut self.shared_objects,
         )
     }
 }
@@ -86,6 +145,9 @@ type Audio = Box<dyn AudioBackend>;
 type Navigator = Box<dyn NavigatorBackend>;
 type Renderer = Box<dyn RenderBackend>;
 type Input = Box<dyn InputBackend>;
+type Ui = Box<dyn UiBackend>;
+type Log = Box<dyn LogBackend>;
+type Storage = Box<dyn StorageBackend>;
 
 pub struct Player {
     /// The version of the player we're emulating.
@@ -105,10 +167,16 @@ pub struct Player {
     is_playing: bool,
     needs_render: bool,
 
+    /// Whether rendering skips display objects that are entirely off screen.
+    cull_offscreen_objects: bool,
+
     audio: Audio,
     renderer: Renderer,
     pub navigator: Navigator,
     input: Input,
+    ui: Ui,
+    log: Log,
+    storage: Storage,
     transform_stack: TransformStack,
     view_matrix: Matrix,
     inverse_view_matrix: Matrix,
@@ -128,12 +196,73 @@ pub struct Player {
     movie_height: u32,
     letterbox: Letterbox,
 
+    /// Whether the margins around the stage are covered by black bars when the viewport's
+    /// aspect ratio doesn't match the movie's.
+    letterbox_enabled: bool,
+
+    /// Whether the stage is only scaled by whole numbers, with bitmaps drawn unsmoothed, so
+    /// that pixel art stays crisp.
+    pixel_perfect: bool,
+
+    /// How the stage is scaled to fit the viewport, set by `Stage.scaleMode`.
+    scale_mode: StageScaleMode,
+
+    /// The time zone that local times are shown in.
+    time_zone: Box<dyn TimeZoneProvider>,
+
+    /// The locale of the host, such as `en-US`.
+    locale: String,
+
     mouse_pos: (Twips, Twips),
+    keyboard: KeyboardState,
     is_mouse_down: bool,
 
+    /// The master volume set by the host, ranging from 0.0 to 1.0.
+    /// This is applied on top of any volume changes made by the SWF, and is not visible to it.
+    volume: f32,
+
+    /// Whether the host has muted all audio.
+    is_muted: bool,
+
     /// The current mouse cursor icon.
     mouse_cursor: MouseCursor,
 
+    /// Whether the user is being asked whether to abort a long-running script.
+    /// The movie is paused until they answer.
+    script_timeout_pending: bool,
+
+    /// Whether the user has been told that the renderer was lost.
+    renderer_lost_reported: bool,
+
+    /// The number of modal dialogs the host is currently showing over the movie.
+    /// While there are any, the movie is suspended; see `suspend`.
+    modal_count: u32,
+
+    /// When the first of the current modal dialogs was opened, as given by the navigator.
+    suspended_since: Duration,
+
+    /// The total time the movie spent suspended, which `getTimer` leaves out.
+    suspended_time: Duration,
+
+    /// Whether `getTimer` keeps counting while the movie is suspended.
+    timer_runs_while_suspended: bool,
+
+    /// Movies loaded by `loadMovie` and similar, which are reused by later loads of the same URL.
+    movie_cache: MovieCache,
+
+    /// The number of seconds of streaming sound to buffer before it starts playing, as set by
+    /// `_soundbuftime`.
+    sound_buffer_time: i32,
+
+    /// The token given to every future spawned by this player, which is cancelled by `shutdown`.
+    cancellation_token: CancellationToken,
+
+    /// The URL that the root movie reports as its own instead of where it was loaded from.
+    spoofed_url: Option<String>,
+
+    /// The rules deciding which URLs movies may fetch, and where from.
+    url_rewrite_rules: UrlRewriteRules,
+
     /// Self-reference to ourselves.
     ///
     /// This is a weak reference that is upgraded and handed out in various
@@ -148,6 +277,9 @@ impl Player {
         audio: Audio,
         navigator: Navigator,
         input: Input,
+        ui: Ui,
+        log: Log,
+        storage: Storage,
         movie: SwfMovie,
     ) -> Result<Arc<Mutex<Self>>, Error> {
         let movie = Arc::new(movie);
@@ -168,6 +300,7 @@ impl Player {
 
             is_playing: false,
             needs_render: true,
+            cull_offscreen_objects: true,
 
             background_color: Color {
                 r: 255,
@@ -205,9 +338,13 @@ impl Player {
                         levels: BTreeMap::new(),
                         mouse_hovered_object: None,
                         drag_object: None,
+                        focused_object: None,
                         avm: Avm1::new(gc_context, NEWEST_PLAYER_VERSION),
                         action_queue: ActionQueue::new(),
                         load_manager: LoadManager::new(),
+                        context_menu: ContextMenuState::default(),
+                        external_interface: ExternalInterface::default(),
+                        shared_objects: BTreeMap::new(),
                     },
                 ))
             }),
@@ -221,28 +358,42 @@ impl Player {
             viewport_width: movie_width,
             viewport_height: movie_height,
             letterbox: Letterbox::None,
+            letterbox_enabled: true,
+            pixel_perfect: false,
+            scale_mode: StageScaleMode::default(),
+            time_zone: Box::new(FixedOffsetTimeZone::utc()),
+            locale: DEFAULT_LOCALE.to_string(),
 
             mouse_pos: (Twips::new(0), Twips::new(0)),
+            keyboard: KeyboardState::new(),
             is_mouse_down: false,
             mouse_cursor: MouseCursor::Arrow,
+            script_timeout_pending: false,
+            renderer_lost_reported: false,
+            modal_count: 0,
+            suspended_since: Duration::default(),
+            suspended_time: Duration::default(),
+            timer_runs_while_suspended: false,
+            movie_cache: MovieCache::new(),
+            sound_buffer_time: DEFAULT_SOUND_BUFFER_TIME,
+            cancellation_token: CancellationToken::new(),
+            spoofed_url: None,
+            url_rewrite_rules: UrlRewriteRules::new(),
+
+            volume: 1.0,
+            is_muted: false,
 
             renderer,
             audio,
             navigator,
             input,
+            ui,
+            log,
+            storage,
             self_reference: None,
         };
 
-        player.mutate_with_update_context(|avm, context| {
-            let mut root: DisplayObject =
-                MovieClip::from_movie(context.gc_context, movie.clone()).into();
-            root.set_depth(context.gc_context, 0);
-            root.post_instantiation(avm, context, root, None);
-            context.levels.insert(0, root);
-        });
-
-        player.build_matrices();
-        player.preload();
+        player.instantiate_root_movie();
 
         let player_box = Arc::new(Mutex::new(player));
         let mut player_lock = player_box.lock().unwrap();
@@ -252,14 +403,283 @@ impl Player {
         Ok(player_box)
     }
 
+    /// Replace the root movie with a new one, discarding all state from the previous movie.
+    ///
+    /// All levels are unloaded, sounds are stopped, and the navigator is pointed at the new
+    /// movie's URL so that relative loads resolve next to it.
+    pub fn set_root_movie(&mut self, movie: Arc<SwfMovie>) {
+        info!(
+            "Loading new root movie: {}x{}",
+            movie.header().stage_size.x_max,
+            movie.header().stage_size.y_max
+        );
+
+        let old_movie = std::mem::replace(&mut self.swf, movie.clone());
+        self.clear_stage();
+
+        // The whole old stage is garbage now; collect it so that its library can be released
+        // without waiting for enough new allocations to pay off the GC debt.
+        self.gc_arena.collect_all();
+
+        self.mutate_with_update_context(|_avm, context| {
+            // The device font belongs to the player, so carry it over to the new movie.
+            let device_font = context
+                .library
+                .library_for_movie(old_movie)
+                .and_then(|library| library.device_font());
+            context
+                .library
+                .library_for_movie_mut(movie.clone())
+                .set_device_font(device_font);
+        });
+
+        self.frame_rate = movie.header().frame_rate.into();
+        self.frame_accumulator = 0.0;
+        self.movie_width = movie.width();
+        self.movie_height = movie.height();
+        self.scale_mode = StageScaleMode::default();
+        self.build_matrices();
+        self.audio.set_frame_rate(self.frame_rate);
+        if let Some(url) = movie.url() {
+            self.navigator.set_base_url(url);
+        }
+
+        self.instantiate_root_movie();
+        self.needs_render = true;
+    }
+
+    /// The frame the root movie is on, starting from 1.
+    pub fn current_frame(&mut self) -> u16 {
+        self.mutate_with_update_context(|_avm, context| {
+            context
+                .levels
+                .get(&0)
+                .and_then(|root| root.as_movie_clip())
+                .map(|root| root.current_frame())
+                .unwrap_or_default()
+        })
+    }
+
+    /// The number of frames in the root movie.
+    pub fn total_frames(&self) -> u16 {
+        self.swf.header().num_frames
+    }
+
+    /// Sends the root movie to the given frame, starting from 1, as if it had called
+    /// `gotoAndPlay`. Frames past the end of the movie go to the last frame.
+    pub fn goto_frame(&mut self, frame: u16) {
+        self.update(|avm, context| {
+            if let Some(root) = context.levels.get(&0).and_then(|root| root.as_movie_clip()) {
+                let frame = frame.max(1).min(root.total_frames());
+                root.goto_frame(avm, context, frame, false);
+            }
+        });
+        self.needs_render = true;
+    }
+
+    /// Unload all levels, stop all sounds, and reset the AVM.
+    fn clear_stage(&mut self) {
+        let player_version = self.player_version;
+        self.mutate_with_update_context(|avm, context| {
+            Self::persist_shared_objects(avm, context);
+            context.shared_objects.clear();
+            let levels: Vec<_> = context.levels.values().copied().collect();
+            for mut level in levels {
+                level.unload(context);
+            }
+            context.levels.clear();
+            *context.drag_object = None;
+            *context.focused_object = None;
+            context.mouse_hovered_object = None;
+            *context.action_queue = ActionQueue::new();
+            *context.load_manager = LoadManager::new();
+            *context.context_menu = ContextMenuState::default();
+            context.external_interface.clear_callbacks();
+            let max_stack_size = avm.max_stack_size();
+            let max_execution_duration = avm.max_execution_duration();
+            let isolate_global_per_movie = avm.isolate_global_per_movie();
+            *avm = Avm1::new(context.gc_context, player_version);
+            avm.set_max_stack_size(max_stack_size);
+            avm.set_max_execution_duration(max_execution_duration);
+            avm.set_isolate_global_per_movie(isolate_global_per_movie);
+        });
+        self.audio.stop_all_sounds();
+        self.script_timeout_pending = false;
+    }
+
+    /// Write the data of every shared object to storage, as Flash Player does when a movie is
+    /// unloaded, whether or not the movie flushed it. Objects with no data are deleted.
+    fn persist_shared_objects<'gc>(avm: &mut Avm1<'gc>, context: &mut UpdateContext<'_, 'gc, '_>) {
+        let shared_objects: Vec<_> = context
+            .shared_objects
+            .iter()
+            .map(|(key, shared_object)| (key.clone(), *shared_object))
+            .collect();
+        for (key, shared_object) in shared_objects {
+            match shared_object::serialize_data(avm, context, shared_object) {
+                // Writes carry on in the background, so there is nothing to wait for.
+                Ok(Some(data)) => drop(context.storage.put(&key, data)),
+                Ok(None) => context.storage.remove(&key),
+                Err(e) => log::warn!("SharedObject {}: could not be saved: {}", key, e),
+            }
+        }
+    }
+
+    /// Save the state of the current movie, so that it can later be resumed with `load_state`.
+    ///
+    /// This fails if the movie is doing something that can't be saved, such as waiting for a
+    /// load to complete; the error lists everything that prevented saving.
+    pub fn save_state(&mut self) -> Result<Vec<u8>, Error> {
+        if self.script_timeout_pending {
+            return Err("A script is waiting to be continued or aborted".into());
+        }
+
+        let frame_accumulator = self.frame_accumulator;
+        let state = self.mutate_with_update_context(|avm, context| {
+            SaveState::capture(avm, context, frame_accumulator)
+        })?;
+        Ok(state.to_bytes())
+    }
+
+    /// Restore the current movie to a state returned by `save_state`.
+    ///
+    /// The state must have been saved from the same movie.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), Error> {
+        let state = SaveState::from_bytes(data)?;
+
+        self.clear_stage();
+        self.create_root_clip();
+        self.mutate_with_update_context(|avm, context| state.restore(avm, context))?;
+        self.build_matrices();
+
+        self.global_time = state.global_time();
+        self.frame_accumulator = state.frame_accumulator();
+        self.needs_render = true;
+        Ok(())
+    }
+
+    /// Build the context menu for the object under the mouse cursor.
+    ///
+    /// The returned items should be displayed by the frontend, which calls
+    /// `run_context_menu_callback` with the index of the item the user selects.
+    pub fn prepare_context_menu(&mut self) -> Vec<ContextMenuItem> {
+        let mouse_pos = self.mouse_pos;
+        self.update(|avm, context| {
+            let (menu, items) = ContextMenuState::build(avm, context, mouse_pos);
+            *context.context_menu = menu;
+            items
+        })
+    }
+
+    /// Run the action of the context menu item at the given index.
+    pub fn run_context_menu_callback(&mut self, index: usize) {
+        self.update(|avm, context| {
+            let menu = std::mem::take(context.context_menu);
+            menu.run_callback(avm, context, index);
+        });
+        self.needs_render = true;
+    }
+
+    /// Set the host that ActionScript talks to through `ExternalInterface`.
+    ///
+    /// Callbacks that the movie has already exposed are announced to the new host.
+    pub fn set_external_interface(&mut self, provider: Box<dyn ExternalInterfaceProvider>) {
+        self.mutate_with_update_context(|_avm, context| {
+            context.external_interface.set_provider(provider);
+        });
+    }
+
+    /// Call a function that ActionScript exposed to the host with `ExternalInterface.addCallback`.
+    ///
+    /// Returns `undefined` if no function was exposed under the given name.
+    pub fn call_internal_interface(&mut self, name: &str, args: &[ExternalValue]) -> ExternalValue {
+        let result =
+            self.update(
+                |avm, context| match context.external_interface.get_callback(name) {
+                    Some(callback) => callback.call(avm, context, name, args),
+                    None => {
+                        log::warn!("No ExternalInterface callback named {}", name);
+                        ExternalValue::Undefined
+                    }
+                },
+            );
+        self.needs_render = true;
+        result
+    }
+
+    /// Create the root clip of the current movie on `_level0`, and load the movie's characters.
+    fn instantiate_root_movie(&mut self) {
+        self.create_root_clip();
+        self.build_matrices();
+        self.preload();
+
+        if self.swf.script_kind() == ScriptKind::Avm2 {
+            self.show_avm2_first_frame();
+        }
+    }
+
+    /// Stop an ActionScript 3 movie on its first frame and tell the user why.
+    ///
+    /// AVM2 is not supported, so the timeline content of the first frame is shown without
+    /// running any of the movie's scripts.
+    fn show_avm2_first_frame(&mut self) {
+        log::warn!(
+            "Unsupported content: {} is an ActionScript 3 movie, and AVM2 is not supported",
+            self.swf.url().unwrap_or("<unknown>")
+        );
+        let message = self
+            .ui
+            .message(MessageLevel::Warning, AVM2_UNSUPPORTED_MESSAGE, &[]);
+        self.navigator
+            .spawn_future(
+                Box::pin(async move {
+                    message.await?;
+                    Ok(())
+                }),
+                self.cancellation_token.clone(),
+            )
+            .detach();
+
+        self.mutate_with_update_context(|avm, context| {
+            let mut root = *context.levels.get(&0).expect("root level");
+            root.run_frame(avm, context);
+            if let Some(clip) = root.as_movie_clip() {
+                clip.stop(context);
+            }
+            *context.action_queue = ActionQueue::new();
+        });
+        self.needs_render = true;
+    }
+
+    fn create_root_clip(&mut self) {
+        let movie = self.swf.clone();
+        self.mutate_with_update_context(|avm, context| {
+            let mut root: DisplayObject = MovieClip::from_movie(context.gc_context, movie).into();
+            root.set_depth(context.gc_context, 0);
+            root.post_instantiation(avm, context, root, None);
+            context.levels.insert(0, root);
+        });
+    }
+
+    /// Advances the movie by `dt` milliseconds, running as many frames as are due.
+    ///
+    /// Frames that have fallen behind are caught up on without drawing them, so `render`
+    /// should be called afterwards to show the result.
     pub fn tick(&mut self, dt: f64) {
+        if self.is_shut_down() {
+            return;
+        }
+
         // Don't run until preloading is complete.
         // TODO: Eventually we want to stream content similar to the Flash player.
         if !self.audio.is_loading_complete() {
             return;
         }
 
-        if self.is_playing() {
+        if self.is_playing() && !self.script_timeout_pending && !self.is_suspended() {
+            // Sounds keep playing in real time, even if frames have to be skipped below.
+            self.audio.tick(dt);
+
             self.frame_accumulator += dt;
             self.global_time += dt as u64;
             let frame_time = 1000.0 / self.frame_rate;
@@ -277,8 +697,6 @@ impl Player {
             if self.frame_accumulator >= frame_time {
                 self.frame_accumulator = 0.0;
             }
-
-            self.audio.tick();
         }
     }
 
@@ -308,10 +726,179 @@ impl Player {
         self.is_playing = v;
     }
 
+    /// Stop the player for good, so that nothing it started outlives it.
+    ///
+    /// Loads in progress are cancelled, the player's cancellation token tells every future it
+    /// spawned to stop, and the navigator drops those futures, waiting at most
+    /// `SHUTDOWN_DRAIN_TIMEOUT` for a busy executor. Only then is the audio backend released.
+    /// A player that has been shut down no longer runs frames or handles events.
+    ///
+    /// This is also called when the player is dropped. Calling it again does nothing.
+    pub fn shutdown(&mut self) {
+        if self.is_shut_down() {
+            return;
+        }
+
+        self.mutate_with_update_context(|avm, context| {
+            Self::persist_shared_objects(avm, context);
+            context.load_manager.cancel_all();
+        });
+        self.cancellation_token.cancel();
+        self.navigator.drain_futures(SHUTDOWN_DRAIN_TIMEOUT);
+
+        self.is_playing = false;
+        self.audio.stop_all_sounds();
+        self.audio = Box::new(NullAudioBackend::new());
+    }
+
+    /// Whether `shutdown` has been called on this player.
+    pub fn is_shut_down(&self) -> bool {
+        self.cancellation_token.is_cancelled()
+    }
+
+    /// Set the number of values the AVM1 operand stack may hold.
+    ///
+    /// Action blocks that exceed this limit are aborted, so that malformed
+    /// movies can't exhaust memory.
+    pub fn set_max_avm1_stack_size(&mut self, max_stack_size: usize) {
+        self.mutate_with_update_context(|avm, _context| avm.set_max_stack_size(max_stack_size));
+    }
+
+    /// Set how long a script may run before the user is asked whether to abort it.
+    pub fn set_max_execution_duration(&mut self, max_execution_duration: std::time::Duration) {
+        self.mutate_with_update_context(|avm, _context| {
+            avm.set_max_execution_duration(max_execution_duration)
+        });
+    }
+
+    /// Set whether movies loaded by the root movie each get an AVM1 `_global` of their own,
+    /// so that they can't overwrite each other's globals.
+    ///
+    /// By default, all movies share one `_global`, as in Flash Player.
+    pub fn set_isolate_global_per_movie(&mut self, isolate_global_per_movie: bool) {
+        self.mutate_with_update_context(|avm, _context| {
+            avm.set_isolate_global_per_movie(isolate_global_per_movie)
+        });
+    }
+
+    /// Set whether movies loaded by the root movie are cached and reused when the same URL is
+    /// loaded again. This is enabled by default.
+    pub fn set_movie_cache_enabled(&mut self, enabled: bool) {
+        self.movie_cache.set_enabled(enabled);
+    }
+
+    /// Set the URL that the root movie sees as its own, in `_url` and wherever else Flash Player
+    /// uses it, such as to tell which shared objects it may access.
+    ///
+    /// Archived movies often check that they are running on their original site; spoofing its
+    /// URL lets them run elsewhere without editing them. Relative loads still resolve next to
+    /// the movie's real URL.
+    pub fn set_spoofed_url(&mut self, url: Option<String>) {
+        self.spoofed_url = url;
+    }
+
+    /// Set the rules deciding which URLs movies may fetch, and where from.
+    ///
+    /// These apply to every fetch made by movies, including `loadMovie`, `loadVariables` and
+    /// `XML.load`.
+    pub fn set_url_rewrite_rules(&mut self, rules: UrlRewriteRules) {
+        self.url_rewrite_rules = rules;
+    }
+
+    pub(crate) fn movie_cache(&self) -> &MovieCache {
+        &self.movie_cache
+    }
+
+    pub(crate) fn movie_cache_mut(&mut self) -> &mut MovieCache {
+        &mut self.movie_cache
+    }
+
+    /// Whether a long-running script is waiting for the user to continue or abort it.
+    pub fn is_script_timeout_pending(&self) -> bool {
+        self.script_timeout_pending
+    }
+
+    /// Suspend the movie while the host shows a modal dialog over it, such as a prompt or a
+    /// permission request.
+    ///
+    /// While suspended, frames don't run, so neither do frame scripts and `onEnterFrame`
+    /// handlers, and input events are not passed to the movie. The last frame is still rendered.
+    /// Each call must be paired with a call to `resume`; dialogs may be nested.
+    pub fn suspend(&mut self) {
+        if self.modal_count == 0 {
+            self.suspended_since = self.navigator.time_since_launch();
+        }
+        self.modal_count += 1;
+    }
+
+    /// Resume the movie after a modal dialog passed to `suspend` is closed.
+    ///
+    /// Once the last dialog is closed, frames continue from where they stopped instead of
+    /// catching up on the time spent suspended. Unless `set_timer_runs_while_suspended` is
+    /// enabled, `getTimer` doesn't count that time either.
+    pub fn resume(&mut self) {
+        if self.modal_count == 0 {
+            return;
+        }
+
+        self.modal_count -= 1;
+        if self.modal_count == 0 {
+            if !self.timer_runs_while_suspended {
+                let now = self.navigator.time_since_launch();
+                self.suspended_time += now.checked_sub(self.suspended_since).unwrap_or_default();
+            }
+            self.frame_accumulator = 0.0;
+            self.needs_render = true;
+        }
+    }
+
+    /// Whether the movie is suspended because the host is showing a modal dialog.
+    pub fn is_suspended(&self) -> bool {
+        self.modal_count > 0
+    }
+
+    /// Sets whether `getTimer` counts the time that the movie spends suspended.
+    /// By default it doesn't, so the timer doesn't jump forward once a dialog is closed.
+    pub fn set_timer_runs_while_suspended(&mut self, value: bool) {
+        self.timer_runs_while_suspended = value;
+    }
+
     pub fn needs_render(&self) -> bool {
         self.needs_render
     }
 
+    /// Sets whether rendering skips display objects, and their children, that are entirely
+    /// outside of the visible area. This is on by default.
+    ///
+    /// Culled objects still run scripts and can be hit by the mouse. Culling only relies on
+    /// the bounds of objects, so it may be turned off in case those are wrong for a movie.
+    pub fn set_cull_offscreen_objects(&mut self, value: bool) {
+        self.cull_offscreen_objects = value;
+    }
+
+    /// Registers a TrueType font that text fields can use by setting their font to `name`,
+    /// whether or not they use embedded fonts.
+    ///
+    /// Fonts with CFF outlines and variable fonts are not supported.
+    pub fn register_font_from_bytes(&mut self, name: &str, data: &[u8]) -> Result<(), Error> {
+        let renderer = &mut self.renderer;
+        self.gc_arena.mutate(|gc_context, gc_root| {
+            let font =
+                crate::font::Font::from_truetype(gc_context, renderer.deref_mut(), name, data)?;
+            let replaced_font = gc_root
+                .0
+                .write(gc_context)
+                .library
+                .register_font(name, font);
+            if let Some(replaced_font) = replaced_font {
+                for shape in replaced_font.glyph_shape_handles() {
+                    renderer.unregister_shape(shape);
+                }
+            }
+            Ok(())
+        })
+    }
+
     pub fn movie_width(&self) -> u32 {
         self.movie_width
     }
@@ -320,6 +907,55 @@ impl Player {
         self.movie_height
     }
 
+    /// Sets whether the margins around the stage are covered by black bars.
+    ///
+    /// When disabled, content outside of the stage is visible in the margins, as in Flash Player.
+    pub fn set_letterbox_enabled(&mut self, enabled: bool) {
+        self.letterbox_enabled = enabled;
+        self.build_matrices();
+        self.needs_render = true;
+    }
+
+    /// Sets whether the stage is only scaled by whole numbers, for pixel art that should stay
+    /// crisp at any window size.
+    ///
+    /// The stage is scaled by the largest whole number that fits the viewport, and is placed
+    /// on whole pixels, with the remainder covered by the letterbox. Bitmaps are drawn with
+    /// nearest-neighbor sampling even if they ask to be smoothed. Vector edges are still
+    /// anti-aliased by the renderer.
+    pub fn set_pixel_perfect(&mut self, enabled: bool) {
+        self.pixel_perfect = enabled;
+        self.renderer.set_bitmap_smoothing_enabled(!enabled);
+        self.build_matrices();
+        self.needs_render = true;
+    }
+
+    pub fn scale_mode(&self) -> StageScaleMode {
+        self.scale_mode
+    }
+
+    /// Sets how the stage is scaled to fit the viewport, as movies do with `Stage.scaleMode`.
+    pub fn set_scale_mode(&mut self, scale_mode: StageScaleMode) {
+        self.scale_mode = scale_mode;
+        self.build_matrices();
+        self.needs_render = true;
+    }
+
+    /// Sets the time zone that local times are shown in. This is UTC unless the host sets it,
+    /// so that movies behave the same on every machine.
+    pub fn set_time_zone(&mut self, time_zone: Box<dyn TimeZoneProvider>) {
+        self.time_zone = time_zone;
+    }
+
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    /// Sets the locale of the host, as a language tag such as `en-US`.
+    pub fn set_locale(&mut self, locale: String) {
+        self.locale = locale;
+    }
+
     pub fn viewport_dimensions(&self) -> (u32, u32) {
         (self.viewport_width, self.viewport_height)
     }
@@ -328,10 +964,29 @@ impl Player {
         self.viewport_width = width;
         self.viewport_height = height;
         self.build_matrices();
+        self.needs_render = true;
     }
 
     pub fn handle_event(&mut self, event: PlayerEvent) {
-        let mut needs_render = self.needs_render;
+        if self.is_shut_down() {
+            return;
+        }
+
+        let mut needs_render = false;
+
+        self.keyboard.handle_event(&event);
+
+        // A suspended movie only keeps track of the state of the mouse and keyboard.
+        if self.is_suspended() {
+            if let PlayerEvent::MouseMove { x, y }
+            | PlayerEvent::MouseDown { x, y }
+            | PlayerEvent::MouseUp { x, y } = event
+            {
+                self.mouse_pos =
+                    self.inverse_view_matrix * (Twips::from_pixels(x), Twips::from_pixels(y));
+            }
+            return;
+        }
 
         // Update mouse position from mouse events.
         if let PlayerEvent::MouseMove { x, y }
@@ -382,16 +1037,32 @@ impl Player {
         }
 
         // Propagte clip events.
-        let (clip_event, mouse_event_name) = match event {
-            PlayerEvent::KeyDown { .. } => (Some(ClipEvent::KeyDown), Some("onKeyDown")),
-            PlayerEvent::KeyUp { .. } => (Some(ClipEvent::KeyUp), Some("onKeyUp")),
-            PlayerEvent::MouseMove { .. } => (Some(ClipEvent::MouseMove), Some("onMouseMove")),
-            PlayerEvent::MouseUp { .. } => (Some(ClipEvent::MouseUp), Some("onMouseUp")),
-            PlayerEvent::MouseDown { .. } => (Some(ClipEvent::MouseDown), Some("onMouseDown")),
+        let (clip_event, listener_event) = match event {
+            PlayerEvent::KeyDown { .. } => (
+                Some(ClipEvent::KeyDown),
+                Some((SystemListener::Key, "onKeyDown")),
+            ),
+            PlayerEvent::KeyUp { .. } => (
+                Some(ClipEvent::KeyUp),
+                Some((SystemListener::Key, "onKeyUp")),
+            ),
+            PlayerEvent::MouseMove { .. } => (
+                Some(ClipEvent::MouseMove),
+                Some((SystemListener::Mouse, "onMouseMove")),
+            ),
+            PlayerEvent::MouseUp { .. } => (
+                Some(ClipEvent::MouseUp),
+                Some((SystemListener::Mouse, "onMouseUp")),
+            ),
+            PlayerEvent::MouseDown { .. } => (
+                Some(ClipEvent::MouseDown),
+                Some((SystemListener::Mouse, "onMouseDown")),
+            ),
+            PlayerEvent::MouseLeave => (None, Some((SystemListener::Mouse, "onMouseLeave"))),
             _ => (None, None),
         };
 
-        if clip_event.is_some() || mouse_event_name.is_some() {
+        if clip_event.is_some() || listener_event.is_some() {
             self.mutate_with_update_context(|_avm, context| {
                 let levels: Vec<DisplayObject<'_>> = context.levels.values().copied().collect();
 
@@ -401,12 +1072,12 @@ impl Player {
                     }
                 }
 
-                if let Some(mouse_event_name) = mouse_event_name {
+                if let Some((listener, method)) = listener_event {
                     context.action_queue.queue_actions(
                         *context.levels.get(&0).expect("root level"),
                         ActionType::NotifyListeners {
-                            listener: SystemListener::Mouse,
-                            method: mouse_event_name,
+                            listener,
+                            method,
                             args: vec![],
                         },
                         false,
@@ -417,17 +1088,21 @@ impl Player {
 
         let mut is_mouse_down = self.is_mouse_down;
         self.mutate_with_update_context(|avm, context| {
+            match event {
+                PlayerEvent::MouseDown { .. } => is_mouse_down = true,
+                PlayerEvent::MouseUp { .. } => is_mouse_down = false,
+                _ => (),
+            }
+
             if let Some(node) = context.mouse_hovered_object {
                 if let Some(mut button) = node.clone().as_button() {
                     match event {
                         PlayerEvent::MouseDown { .. } => {
-                            is_mouse_down = true;
                             needs_render = true;
                             button.handle_button_event(avm, context, ButtonEvent::Press);
                         }
 
                         PlayerEvent::MouseUp { .. } => {
-                            is_mouse_down = false;
                             needs_render = true;
                             button.handle_button_event(avm, context, ButtonEvent::Release);
                         }
@@ -439,8 +1114,17 @@ impl Player {
 
             Self::run_actions(avm, context);
         });
+        let was_mouse_down = self.is_mouse_down;
         self.is_mouse_down = is_mouse_down;
-        self.needs_render = needs_render;
+        // Hovering was held back while the mouse was down, so catch up now that it's released.
+        if was_mouse_down && !is_mouse_down && self.update_roll_over() {
+            needs_render = true;
+        }
+        // Event handlers may have asked for a redraw with `updateAfterEvent`.
+        if needs_render {
+            self.needs_render = true;
+        }
+        self.prompt_script_timeout();
     }
 
     /// Update dragged object, if any.
@@ -473,15 +1157,27 @@ impl Player {
 
     /// Checks to see if a recent update has caused the current mouse hover
     /// node to change.
+    ///
+    /// While the mouse is held down, a pressed push button keeps the mouse captured, so the
+    /// hovered node doesn't change. Otherwise only buttons that track as menus can be hovered,
+    /// and they receive `DragOver` instead of `RollOver`.
     fn update_roll_over(&mut self) -> bool {
-        // TODO: While the mouse is down, maintain the hovered node.
-        if self.is_mouse_down {
-            return false;
-        }
+        let is_mouse_down = self.is_mouse_down;
         let mouse_pos = self.mouse_pos;
 
         let mut new_cursor = self.mouse_cursor;
         let hover_changed = self.mutate_with_update_context(|avm, context| {
+            let cur_hovered = context.mouse_hovered_object;
+            if is_mouse_down {
+                let is_captured = cur_hovered
+                    .and_then(|node| node.as_button())
+                    .map(|button| !button.track_as_menu())
+                    .unwrap_or(false);
+                if is_captured {
+                    return false;
+                }
+            }
+
             // Check hovered object.
             let mut new_hovered = None;
             for (_depth, level) in context.levels.iter().rev() {
@@ -492,7 +1188,13 @@ impl Player {
                 }
             }
 
-            let cur_hovered = context.mouse_hovered_object;
+            if is_mouse_down {
+                new_hovered = new_hovered.filter(|node| {
+                    node.as_button()
+                        .map(|button| button.track_as_menu())
+                        .unwrap_or(true)
+                });
+            }
 
             if cur_hovered.map(|d| d.as_ptr()) != new_hovered.map(|d| d.as_ptr()) {
                 // RollOut of previous node.
@@ -506,7 +1208,12 @@ impl Player {
                 new_cursor = MouseCursor::Arrow;
                 if let Some(node) = new_hovered {
                     if let Some(mut button) = node.as_button() {
-                        button.handle_button_event(avm, context, ButtonEvent::RollOver);
+                        let event = if is_mouse_down {
+                            ButtonEvent::DragOver
+                        } else {
+                            ButtonEvent::RollOver
+                        };
+                        button.handle_button_event(avm, context, event);
                         new_cursor = MouseCursor::Hand;
                     }
                 }
@@ -523,7 +1230,7 @@ impl Player {
         // Update mouse cursor if it has changed.
         if new_cursor != self.mouse_cursor {
             self.mouse_cursor = new_cursor;
-            self.input.set_mouse_cursor(new_cursor)
+            self.ui.set_mouse_cursor(new_cursor)
         }
 
         hover_changed
@@ -552,7 +1259,12 @@ impl Player {
         });
     }
 
+    /// Runs a single frame of the movie, regardless of the frame rate.
     pub fn run_frame(&mut self) {
+        if self.script_timeout_pending || self.is_suspended() {
+            return;
+        }
+
         self.update(|avm, update_context| {
             // TODO: In what order are levels run?
             // NOTE: We have to copy all the layer pointers into a separate list
@@ -563,18 +1275,53 @@ impl Player {
             for mut level in levels {
                 level.run_frame(avm, update_context);
             }
+
+            // Compose the matrices that scripts changed this frame once, before they're drawn.
+            for &level in update_context.levels.values() {
+                recompose_matrices(update_context.gc_context, level);
+            }
+        });
+
+        // Release the shapes and bitmaps of movies that are gone.
+        self.mutate_with_update_context(|_avm, context| {
+            context.library.release_unloaded_libraries(context.renderer)
         });
         self.needs_render = true;
     }
 
+    /// Draws the stage if anything has changed since it was last drawn.
+    ///
+    /// This doesn't run any scripts or advance the timeline, so hosts can call it as often as
+    /// they redraw, independently of `tick`.
     pub fn render(&mut self) {
-        let view_bounds = BoundingBox {
-            x_min: Twips::new(0),
-            y_min: Twips::new(0),
-            x_max: Twips::from_pixels(self.movie_width.into()),
-            y_max: Twips::from_pixels(self.movie_height.into()),
-            valid: true,
+        if self.needs_render {
+            self.force_render();
+        }
+    }
+
+    /// Draws the stage even if nothing has changed, such as when the host has lost the
+    /// contents of its window.
+    pub fn force_render(&mut self) {
+        let view_bounds = if self.letterbox_enabled {
+            BoundingBox {
+                x_min: Twips::new(0),
+                y_min: Twips::new(0),
+                x_max: Twips::from_pixels(self.movie_width.into()),
+                y_max: Twips::from_pixels(self.movie_height.into()),
+                valid: true,
+            }
+        } else {
+            // Content in the margins around the stage is visible too.
+            BoundingBox {
+                x_min: Twips::new(0),
+                y_min: Twips::new(0),
+                x_max: Twips::from_pixels(self.viewport_width.into()),
+                y_max: Twips::from_pixels(self.viewport_height.into()),
+                valid: true,
+            }
+            .transform(&self.inverse_view_matrix)
         };
+        let cull_offscreen_objects = self.cull_offscreen_objects;
 
         self.renderer.begin_frame();
 
@@ -593,6 +1340,7 @@ impl Player {
                 library: &root_data.library,
                 transform_stack,
                 view_bounds,
+                cull_offscreen_objects,
                 clip_depth_stack: vec![],
             };
 
@@ -605,6 +1353,53 @@ impl Player {
         self.renderer.draw_letterbox(self.letterbox);
         self.renderer.end_frame();
         self.needs_render = false;
+
+        if self.renderer.is_lost() && !self.renderer_lost_reported {
+            log::error!("The renderer was lost, nothing more will be drawn");
+            self.renderer_lost_reported = true;
+            let message = self
+                .ui
+                .message(MessageLevel::Error, RENDERER_LOST_MESSAGE, &[]);
+            self.navigator
+                .spawn_future(
+                    Box::pin(async move {
+                        message.await?;
+                        Ok(())
+                    }),
+                    self.cancellation_token.clone(),
+                )
+                .detach();
+        }
+    }
+
+    /// The master volume set by the host, ranging from 0.0 to 1.0.
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// Sets the master volume of this player.
+    ///
+    /// This scales the final audio mix and is independent of any volume set by the SWF itself;
+    /// values returned by `Sound.getVolume` are unaffected.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.max(0.0).min(1.0);
+        self.update_global_volume();
+    }
+
+    /// Whether the host has muted this player.
+    pub fn is_muted(&self) -> bool {
+        self.is_muted
+    }
+
+    /// Mutes or unmutes all audio from this player without affecting the master volume.
+    pub fn set_mute(&mut self, is_muted: bool) {
+        self.is_muted = is_muted;
+        self.update_global_volume();
+    }
+
+    fn update_global_volume(&mut self) {
+        let volume = if self.is_muted { 0.0 } else { self.volume };
+        self.audio.set_global_volume(volume);
     }
 
     pub fn audio(&self) -> &Audio {
@@ -636,9 +1431,100 @@ impl Player {
         self.input.deref_mut()
     }
 
+    pub fn ui(&self) -> &Ui {
+        &self.ui
+    }
+
+    pub fn ui_mut(&mut self) -> &mut dyn UiBackend {
+        self.ui.deref_mut()
+    }
+
+    pub fn log(&self) -> &Log {
+        &self.log
+    }
+
+    pub fn log_mut(&mut self) -> &mut dyn LogBackend {
+        self.log.deref_mut()
+    }
+
+    /// Ask the user whether to abort a script that ran for longer than the maximum execution
+    /// duration, if there is one.
+    ///
+    /// The movie is paused until the UI backend returns their choice, which is then passed to
+    /// `resolve_script_timeout`.
+    fn prompt_script_timeout(&mut self) {
+        let is_suspended = self.mutate_with_update_context(|avm, _context| {
+            avm.script_state() == ScriptState::Suspended
+        });
+        if !is_suspended || self.script_timeout_pending {
+            return;
+        }
+
+        self.script_timeout_pending = true;
+        self.suspend();
+        let choice = self.ui.message(
+            MessageLevel::Warning,
+            SCRIPT_TIMEOUT_MESSAGE,
+            SCRIPT_TIMEOUT_CHOICES,
+        );
+        let player = self.self_reference.clone();
+        self.navigator
+            .spawn_future(
+                Box::pin(async move {
+                    let choice = choice.await?;
+                    if let Some(player) = player.and_then(|player| player.upgrade()) {
+                        player
+                            .lock()
+                            .expect("Could not lock player!!")
+                            .resolve_script_timeout(choice == Some(SCRIPT_TIMEOUT_ABORT));
+                    }
+                    Ok(())
+                }),
+                self.cancellation_token.clone(),
+            )
+            .detach();
+    }
+
+    /// Continue or abort the script that the user was asked about by `prompt_script_timeout`.
+    ///
+    /// A continued script runs again immediately, along with any actions queued behind it.
+    fn resolve_script_timeout(&mut self, abort: bool) {
+        // The prompt has closed, even if the movie it was about has since been replaced.
+        self.resume();
+        if !self.script_timeout_pending {
+            return;
+        }
+
+        self.script_timeout_pending = false;
+        if abort {
+            self.mutate_with_update_context(|avm, context| {
+                avm.abort_script();
+                *context.action_queue = ActionQueue::new();
+            });
+        } else {
+            self.mutate_with_update_context(|avm, _context| avm.resume_script());
+            self.update(|_avm, _context| ());
+        }
+        self.needs_render = true;
+    }
+
     fn run_actions<'gc>(avm: &mut Avm1<'gc>, context: &mut UpdateContext<'_, 'gc, '_>) {
+        // ActionScript 3 movies never run AVM1 code, even if their timelines contain some.
+        if context.swf.script_kind() == ScriptKind::Avm2 {
+            *context.action_queue = ActionQueue::new();
+            return;
+        }
+
+        // A resumed script continues where it was suspended, before any other actions.
+        let _ = avm.run_stack_with_timeout(context);
+
         // Note that actions can queue further actions, so a while loop is necessary here.
-        while let Some(actions) = context.action_queue.pop_action() {
+        while avm.script_state() == ScriptState::Running {
+            let actions = match context.action_queue.pop_action() {
+                Some(actions) => actions,
+                None => break,
+            };
+
             // We don't run frame actions if the clip was removed after it queued the action.
             if !actions.is_unload && actions.clip.removed() {
                 continue;
@@ -745,45 +1631,28 @@ impl Player {
                 }
             }
             // Execute the stack frame (if any).
-            let _ = avm.run_stack_till_empty(context);
+            let _ = avm.run_stack_with_timeout(context);
+        }
+
+        // Scripts no longer run once aborted, so there's no point keeping their actions.
+        if avm.script_state() == ScriptState::Aborted {
+            *context.action_queue = ActionQueue::new();
         }
     }
 
     fn build_matrices(&mut self) {
-        // Create  view matrix to scale stage into viewport area.
-        let (movie_width, movie_height) = (self.movie_width as f32, self.movie_height as f32);
-        let (viewport_width, viewport_height) =
-            (self.viewport_width as f32, self.viewport_height as f32);
-        let movie_aspect = movie_width / movie_height;
-        let viewport_aspect = viewport_width / viewport_height;
-        let (scale, margin_width, margin_height) = if viewport_aspect > movie_aspect {
-            let scale = viewport_height / movie_height;
-            (scale, (viewport_width - movie_width * scale) / 2.0, 0.0)
-        } else {
-            let scale = viewport_width / movie_width;
-            (scale, 0.0, (viewport_height - movie_height * scale) / 2.0)
-        };
-        self.view_matrix = Matrix {
-            a: scale,
-            b: 0.0,
-            c: 0.0,
-            d: scale,
-            tx: Twips::from_pixels(margin_width.into()),
-            ty: Twips::from_pixels(margin_height.into()),
-        };
-        self.inverse_view_matrix = self.view_matrix;
+        // Create view matrix to scale stage into viewport area.
+        let (view_matrix, letterbox) = fit_stage(
+            (self.movie_width as f32, self.movie_height as f32),
+            (self.viewport_width as f32, self.viewport_height as f32),
+            self.scale_mode,
+            self.pixel_perfect,
+            self.letterbox_enabled,
+        );
+        self.view_matrix = view_matrix;
+        self.inverse_view_matrix = view_matrix;
         self.inverse_view_matrix.invert();
-
-        // Calculate letterbox dimensions.
-        // TODO: Letterbox should be an option; the original Flash Player defaults to showing content
-        // in the extra margins.
-        self.letterbox = if margin_width > 0.0 {
-            Letterbox::Pillarbox(margin_width)
-        } else if margin_height > 0.0 {
-            Letterbox::Letterbox(margin_height)
-        } else {
-            Letterbox::None
-        };
+        self.letterbox = letterbox;
     }
 
     /// Runs the closure `f` with an `UpdateContext`.
@@ -797,60 +1666,114 @@ impl Player {
         let (
             player_version,
             global_time,
+            suspended_time,
             swf,
             background_color,
             renderer,
             audio,
             navigator,
             input,
+            ui,
+            log,
+            storage,
             rng,
             mouse_position,
+            keyboard,
             stage_width,
             stage_height,
             player,
+            sound_buffer_time,
+            needs_render,
+            scale_mode,
+            time_zone,
+            locale,
+            cancellation_token,
+            spoofed_url,
         ) = (
             self.player_version,
             self.global_time,
+            self.suspended_time,
             &self.swf,
             &mut self.background_color,
             self.renderer.deref_mut(),
             self.audio.deref_mut(),
             self.navigator.deref_mut(),
             self.input.deref_mut(),
+            self.ui.deref_mut(),
+            self.log.deref_mut(),
+            self.storage.deref_mut(),
             &mut self.rng,
             &self.mouse_pos,
+            &self.keyboard,
             Twips::from_pixels(self.movie_width.into()),
             Twips::from_pixels(self.movie_height.into()),
             self.self_reference.clone(),
+            &mut self.sound_buffer_time,
+            &mut self.needs_render,
+            &mut self.scale_mode,
+            self.time_zone.as_ref(),
+            self.locale.as_str(),
+            &self.cancellation_token,
+            self.spoofed_url.as_deref(),
         );
+        let old_scale_mode = *scale_mode;
 
-        self.gc_arena.mutate(|gc_context, gc_root| {
+        // Every fetch made by movies goes through the rewrite rules.
+        let mut navigator = RewritingNavigator::new(navigator, &self.url_rewrite_rules);
+
+        let ret = self.gc_arena.mutate(|gc_context, gc_root| {
             let mut root_data = gc_root.0.write(gc_context);
             let mouse_hovered_object = root_data.mouse_hovered_object;
-            let (levels, library, action_queue, avm, drag_object, load_manager) =
-                root_data.update_context_params();
+            let (
+                levels,
+                library,
+                action_queue,
+                avm,
+                drag_object,
+                focused_object,
+                load_manager,
+                context_menu,
+                external_interface,
+                shared_objects,
+            ) = root_data.update_context_params();
 
             let mut update_context = UpdateContext {
                 player_version,
                 global_time,
+                suspended_time,
                 swf,
                 library,
                 background_color,
                 rng,
                 renderer,
                 audio,
-                navigator,
+                navigator: &mut navigator,
                 input,
+                ui,
+                log,
+                storage,
                 action_queue,
                 gc_context,
                 levels,
                 mouse_hovered_object,
                 mouse_position,
+                keyboard,
                 drag_object,
+                focused_object,
                 stage_size: (stage_width, stage_height),
                 system_prototypes: avm.prototypes().clone(),
                 player,
                 load_manager,
+                context_menu,
+                external_interface,
+                shared_objects,
+                sound_buffer_time,
+                needs_render,
+                scale_mode,
+                time_zone,
+                locale,
+                cancellation_token,
+                spoofed_url,
             };
 
             let ret = f(avm, &mut update_context);
@@ -858,7 +1781,15 @@ impl Player {
             // Hovered object may have been updated; copy it back to the GC root.
             root_data.mouse_hovered_object = update_context.mouse_hovered_object;
             ret
-        })
+        });
+
+        // A script may have changed `Stage.scaleMode`.
+        if self.scale_mode != old_scale_mode {
+            self.build_matrices();
+            self.needs_render = true;
+        }
+
+        ret
     }
 
     /// Loads font data from the given buffer.
@@ -905,10 +1836,18 @@ impl Player {
         // GC
         self.gc_arena.collect_debt();
 
+        self.prompt_script_timeout();
+
         rval
     }
 }
 
+impl Drop for Player {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
 pub struct DragObject<'gc> {
     /// The display object being dragged.
     pub display_object: DisplayObject<'gc>,
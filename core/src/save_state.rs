@@ -0,0 +1,1370 @@
+//! Save states
+//!
+//! A save state holds everything needed to resume a movie from the frame it was saved on: the
+//! playhead of every timeline, the ActionScript object graph, and the player's clock and random
+//! number generator. States are taken between frames, and can only be restored into the movie
+//! they were saved from.
+//!
+//! Display objects are recorded by the depths leading to them from their level, and are
+//! recreated by seeking each timeline to its saved frame. Objects that Ruffle creates, such as
+//! `_global` and the built-in prototypes, are recorded by their path from `_global` along with
+//! any properties that scripts have added to them. Functions are recorded by the location of
+//! their bytecode in the movie and their scope chain.
+//!
+//! Anything that can't be recreated this way, such as a clip created by `attachMovie` or a load
+//! that hasn't completed, prevents the state from being saved. Sounds are not part of a save
+//! state: restoring a state stops all sounds, and streaming sounds resume with their timelines.
+
+use crate::avm1::function::{Avm1Function, Executable, FunctionObject};
+use crate::avm1::property::Attribute;
+use crate::avm1::scope::{Scope, ScopeClass};
+use crate::avm1::{Avm1, Object, ObjectPtr, ScriptObject, SuperObject, TObject, Value};
+use crate::character::Character;
+use crate::context::{ActionQueue, UpdateContext};
+use crate::prelude::*;
+use crate::tag_utils::{SwfMovie, SwfSlice};
+use enumset::EnumSet;
+use gc_arena::{Gc, GcCell};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hasher;
+use std::sync::Arc;
+
+type Error = Box<dyn std::error::Error>;
+
+/// Identifies save state data, followed by the version of the format.
+const MAGIC: &[u8] = b"RUFFLESTATE\x01";
+
+/// How many properties away from `_global` to look for built-in objects, e.g. three for
+/// `MovieClip.prototype.gotoAndPlay` and five for `flash.display.BitmapData.prototype.getPixel`.
+const MAX_BUILT_IN_DEPTH: usize = 5;
+
+/// The path to a display object: its level, followed by the depth of each child leading to it.
+type DisplayPath = Vec<Depth>;
+
+/// A snapshot of a running movie.
+pub struct SaveState {
+    /// A hash of the movie that this state was saved from.
+    movie_hash: u64,
+
+    global_time: u64,
+    frame_accumulator: f64,
+    rng_seed: [u8; 16],
+    background_color: Color,
+
+    /// The constant pool of the last action block that defined one.
+    constant_pool: Vec<String>,
+
+    /// The global AVM1 registers.
+    registers: Vec<ValueState>,
+
+    /// Every display object with a timeline or an AVM1 object, parents before their children.
+    display_objects: Vec<DisplayObjectState>,
+
+    /// The AVM1 object graph, indexed by `ValueState::Object`.
+    objects: Vec<ObjectState>,
+
+    /// The constructors registered with `Object.registerClass`, by export name.
+    registered_classes: Vec<(String, u32)>,
+}
+
+/// The reasons that a save state could not be taken.
+#[derive(Debug)]
+pub struct SaveStateError {
+    blockers: Vec<String>,
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unable to save state:")?;
+        for blocker in &self.blockers {
+            write!(f, "\n  - {}", blocker)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SaveStateError {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum ValueState {
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Object(u32),
+}
+
+struct DisplayObjectState {
+    path: DisplayPath,
+    name: String,
+
+    /// The current frame of a movie clip, and whether it is playing.
+    timeline: Option<(u16, bool)>,
+
+    matrix: Matrix,
+    color_transform: ColorTransform,
+    visible: bool,
+    transformed_by_script: bool,
+
+    /// The contents of a text field.
+    text: Option<String>,
+}
+
+struct ObjectState {
+    kind: ObjectKind,
+
+    /// The prototype of the object. This is not recorded for built-in objects.
+    proto: Option<u32>,
+    interfaces: Vec<u32>,
+    properties: Vec<(String, ValueState, EnumSet<Attribute>)>,
+
+    /// The elements of an array.
+    array: Option<Vec<ValueState>>,
+}
+
+enum ObjectKind {
+    /// An object created by Ruffle, found by its path from `_global`.
+    BuiltIn(Vec<String>),
+
+    /// The object of a display object.
+    DisplayObject(DisplayPath),
+
+    /// An object created by a script.
+    Script,
+
+    /// A function defined by a script.
+    Function(FunctionState),
+
+    /// The `super` of a method call.
+    Super { child: u32, base_proto: u32 },
+}
+
+struct FunctionState {
+    swf_version: u8,
+
+    /// The location of the function body in the movie.
+    start: usize,
+    end: usize,
+
+    name: String,
+    register_count: u8,
+
+    /// The `DefineFunction2` flags of the function, in the order they are declared, followed by
+    /// whether the function was defined by `DefineFunction`.
+    flags: u16,
+    params: Vec<(Option<u8>, String)>,
+
+    /// The scope chain of the function, starting from the global scope.
+    scope: Vec<(ScopeClass, u32)>,
+    constant_pool: Vec<String>,
+    base_clip: DisplayPath,
+}
+
+impl FunctionState {
+    fn flags(function: &swf::avm1::types::Function, is_function2: bool) -> u16 {
+        [
+            function.preload_parent,
+            function.preload_root,
+            function.suppress_super,
+            function.preload_super,
+            function.suppress_arguments,
+            function.preload_arguments,
+            function.suppress_this,
+            function.preload_this,
+            function.preload_global,
+            !is_function2,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |flags, (i, &flag)| flags | (flag as u16) << i)
+    }
+
+    fn swf_function(&self) -> swf::avm1::types::Function<'_> {
+        let flag = |i: u16| self.flags & (1 << i) != 0;
+        swf::avm1::types::Function {
+            name: &self.name,
+            register_count: self.register_count,
+            params: self
+                .params
+                .iter()
+                .map(|(register_index, name)| swf::avm1::types::FunctionParam {
+                    name,
+                    register_index: *register_index,
+                })
+                .collect(),
+            preload_parent: flag(0),
+            preload_root: flag(1),
+            suppress_super: flag(2),
+            preload_super: flag(3),
+            suppress_arguments: flag(4),
+            preload_arguments: flag(5),
+            suppress_this: flag(6),
+            preload_this: flag(7),
+            preload_global: flag(8),
+            actions: &[],
+        }
+    }
+
+    fn is_function2(&self) -> bool {
+        self.flags & (1 << 9) == 0
+    }
+}
+
+impl SaveState {
+    /// Capture the state of the stage and the AVM.
+    ///
+    /// This must be called between frames, when no ActionScript is running. Saving a state
+    /// reseeds the random number generator, so that a restored state draws the same numbers.
+    pub fn capture<'gc>(
+        avm: &mut Avm1<'gc>,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+        frame_accumulator: f64,
+    ) -> Result<Self, SaveStateError> {
+        let mut capture = Capture::new(avm, context);
+
+        if avm.has_stack_frame() {
+            capture.block("ActionScript is running".to_string());
+        }
+        if context.load_manager.pending_loads() > 0 {
+            capture.block(format!(
+                "{} load(s) have not completed",
+                context.load_manager.pending_loads()
+            ));
+        }
+        if context.drag_object.is_some() {
+            capture.block("A clip is being dragged".to_string());
+        }
+
+        let levels: Vec<_> = context.levels.iter().map(|(l, o)| (*l, *o)).collect();
+        for (level, object) in levels {
+            if level == 0 {
+                capture.display_object(object, vec![0]);
+            } else {
+                capture.block(format!("A movie is loaded into _level{}", level));
+            }
+        }
+
+        capture.object_id(avm.global_object_cell(), "_global");
+
+        let mut registered_classes = vec![];
+        if let Some(library) = context.library.library_for_movie(context.swf.clone()) {
+            for (name, character) in library.export_characters() {
+                if let Character::MovieClip(clip) = character {
+                    if let Some(constructor) = clip.avm1_constructor() {
+                        registered_classes.push((name.to_string(), constructor));
+                    }
+                }
+            }
+        }
+        registered_classes.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let registered_classes = registered_classes
+            .into_iter()
+            .filter_map(|(name, constructor)| {
+                let location = format!("The class registered for {}", name);
+                capture
+                    .object_id(constructor, &location)
+                    .map(|id| (name, id))
+            })
+            .collect();
+
+        let registers = (0..4)
+            .map(|i| capture.value(avm.current_register(i), &format!("Register {}", i)))
+            .collect();
+
+        capture.finish()?;
+
+        let rng_seed = context.rng.gen();
+        *context.rng = SmallRng::from_seed(rng_seed);
+
+        Ok(Self {
+            movie_hash: movie_hash(context.swf),
+            global_time: context.global_time,
+            frame_accumulator,
+            rng_seed,
+            background_color: context.background_color.clone(),
+            constant_pool: avm.constant_pool().read().clone(),
+            registers,
+            display_objects: capture.display_objects,
+            objects: capture.objects,
+            registered_classes,
+        })
+    }
+
+    /// Restore this state onto a freshly instantiated root movie.
+    pub fn restore<'gc>(
+        &self,
+        avm: &mut Avm1<'gc>,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+    ) -> Result<(), Error> {
+        if self.movie_hash != movie_hash(context.swf) {
+            return Err("This state was saved from a different movie".into());
+        }
+
+        for state in &self.display_objects {
+            let mut object = resolve_display_path(context, &state.path)
+                .filter(|object| *object.name() == state.name)
+                .ok_or_else(|| format!("Unable to recreate {:?}", state.path))?;
+
+            if let (Some(clip), Some((frame, playing))) = (object.as_movie_clip(), state.timeline) {
+                clip.goto_frame(avm, context, frame, !playing);
+            }
+            object.set_matrix(context.gc_context, &state.matrix);
+            object.set_color_transform(context.gc_context, &state.color_transform);
+            object.set_visible(context.gc_context, state.visible);
+            object.set_transformed_by_script(context.gc_context, state.transformed_by_script);
+            if let (Some(text_field), Some(text)) = (object.as_edit_text(), &state.text) {
+                text_field.set_text(text.to_owned(), context.gc_context);
+            }
+        }
+
+        // Seeking the timelines queued their frame scripts and clip events, but the effects of
+        // those are already part of the saved objects.
+        *context.action_queue = ActionQueue::new();
+
+        let objects = self.restore_objects(avm, context)?;
+        let value = |state: &ValueState| -> Result<Value<'gc>, Error> {
+            Ok(match state {
+                ValueState::Undefined => Value::Undefined,
+                ValueState::Null => Value::Null,
+                ValueState::Bool(value) => Value::Bool(*value),
+                ValueState::Number(value) => Value::Number(*value),
+                ValueState::String(value) => Value::String(value.to_owned()),
+                ValueState::Object(id) => get_object(&objects, *id)?.into(),
+            })
+        };
+
+        for (state, object) in self.objects.iter().zip(&objects) {
+            let mut object = object.unwrap();
+            if let ObjectKind::Script | ObjectKind::Function(_) = state.kind {
+                let proto = match state.proto {
+                    Some(id) => Some(get_object(&objects, id)?),
+                    None => None,
+                };
+                object.set_proto(context.gc_context, proto);
+
+                let mut interfaces = vec![];
+                for id in &state.interfaces {
+                    interfaces.push(get_object(&objects, *id)?);
+                }
+                object.set_interfaces(context.gc_context, interfaces);
+            }
+
+            if let Some(array) = &state.array {
+                object.set_length(context.gc_context, array.len());
+                for (index, element) in array.iter().enumerate() {
+                    object.set_array_element(index, value(element)?, context.gc_context);
+                }
+            }
+
+            if let Some(base) = object.as_script_object() {
+                for (name, property, attributes) in &state.properties {
+                    base.define_own_property(
+                        context.gc_context,
+                        name,
+                        value(property)?,
+                        *attributes,
+                    );
+                }
+            }
+        }
+
+        let library = context.library.library_for_movie_mut(context.swf.clone());
+        for (name, character) in library.export_characters() {
+            if let Character::MovieClip(clip) = character {
+                let constructor = self
+                    .registered_classes
+                    .iter()
+                    .find(|(class_name, _)| class_name == name)
+                    .map(|(_, id)| get_object(&objects, *id))
+                    .transpose()?;
+                clip.set_avm1_constructor(context.gc_context, constructor);
+            }
+        }
+
+        for (i, register) in self.registers.iter().enumerate() {
+            avm.set_current_register(i as u8, value(register)?, context);
+        }
+        avm.set_constant_pool(GcCell::allocate(
+            context.gc_context,
+            self.constant_pool.clone(),
+        ));
+        *context.rng = SmallRng::from_seed(self.rng_seed);
+        *context.background_color = self.background_color.clone();
+
+        Ok(())
+    }
+
+    /// Find or create every object in the saved object graph, without setting any properties.
+    fn restore_objects<'gc>(
+        &self,
+        avm: &mut Avm1<'gc>,
+        context: &mut UpdateContext<'_, 'gc, '_>,
+    ) -> Result<Vec<Option<Object<'gc>>>, Error> {
+        let mut objects = vec![None; self.objects.len()];
+
+        // Functions and `super` objects are created last, as they refer to other objects.
+        for (state, object) in self.objects.iter().zip(&mut objects) {
+            *object = match &state.kind {
+                ObjectKind::BuiltIn(path) => Some(
+                    resolve_built_in(avm.global_object_cell(), path)
+                        .ok_or_else(|| format!("Unable to find _global.{}", path.join(".")))?,
+                ),
+                ObjectKind::DisplayObject(path) => Some(
+                    resolve_display_path(context, path)
+                        .and_then(|object| object.object().as_object().ok())
+                        .ok_or_else(|| format!("Unable to recreate {:?}", path))?,
+                ),
+                ObjectKind::Script if state.array.is_some() => {
+                    Some(ScriptObject::array(context.gc_context, None).into())
+                }
+                ObjectKind::Script => Some(ScriptObject::object(context.gc_context, None).into()),
+                ObjectKind::Function(_) | ObjectKind::Super { .. } => None,
+            }
+        }
+
+        for (id, state) in self.objects.iter().enumerate() {
+            if let ObjectKind::Function(function) = &state.kind {
+                let mut scope: Option<GcCell<'gc, Scope<'gc>>> = None;
+                for (class, locals) in &function.scope {
+                    let locals = get_object(&objects, *locals)?;
+                    scope = Some(GcCell::allocate(
+                        context.gc_context,
+                        match scope {
+                            Some(parent) => Scope::new(parent, *class, locals),
+                            None => Scope::from_global_object(locals),
+                        },
+                    ));
+                }
+                let scope = scope.ok_or("Function has no scope")?;
+
+                if function.start > function.end || function.end > context.swf.data().len() {
+                    return Err("Function is outside of the movie".into());
+                }
+                let data = SwfSlice {
+                    movie: context.swf.clone(),
+                    start: function.start,
+                    end: function.end,
+                };
+                let base_clip = resolve_display_path(context, &function.base_clip)
+                    .ok_or_else(|| format!("Unable to recreate {:?}", function.base_clip))?;
+
+                let constant_pool =
+                    GcCell::allocate(context.gc_context, function.constant_pool.clone());
+                let executable = if function.is_function2() {
+                    Avm1Function::from_df2(
+                        function.swf_version,
+                        data,
+                        &function.swf_function(),
+                        scope,
+                        constant_pool,
+                        base_clip,
+                    )
+                } else {
+                    let params: Vec<&str> =
+                        function.params.iter().map(|(_, name)| &name[..]).collect();
+                    Avm1Function::from_df1(
+                        function.swf_version,
+                        data,
+                        &function.name,
+                        &params,
+                        scope,
+                        constant_pool,
+                        base_clip,
+                    )
+                };
+                let executable = Gc::allocate(context.gc_context, executable);
+                objects[id] = Some(
+                    FunctionObject::bare_function(context.gc_context, executable, None).into(),
+                );
+            }
+        }
+
+        for (id, state) in self.objects.iter().enumerate() {
+            if let ObjectKind::Super { child, base_proto } = state.kind {
+                let super_object = SuperObject::from_this_and_base_proto(
+                    get_object(&objects, child)?,
+                    get_object(&objects, base_proto)?,
+                    avm,
+                    context,
+                )?;
+                objects[id] = Some(super_object.into());
+            }
+        }
+
+        Ok(objects)
+    }
+
+    pub fn global_time(&self) -> u64 {
+        self.global_time
+    }
+
+    pub fn frame_accumulator(&self) -> f64 {
+        self.frame_accumulator
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer(MAGIC.to_vec());
+        w.u64(self.movie_hash);
+        w.u64(self.global_time);
+        w.f64(self.frame_accumulator);
+        w.0.extend_from_slice(&self.rng_seed);
+        let color = &self.background_color;
+        w.0.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+        w.list(&self.constant_pool, |w, s| w.string(s));
+        w.list(&self.registers, Writer::value);
+
+        w.list(&self.display_objects, |w, state| {
+            w.path(&state.path);
+            w.string(&state.name);
+            w.option(&state.timeline, |w, (frame, playing)| {
+                w.u16(*frame);
+                w.bool(*playing);
+            });
+            let matrix = &state.matrix;
+            for value in &[matrix.a, matrix.b, matrix.c, matrix.d] {
+                w.f32(*value);
+            }
+            w.u32(matrix.tx.get() as u32);
+            w.u32(matrix.ty.get() as u32);
+            let ct = &state.color_transform;
+            for value in &[
+                ct.r_mult, ct.g_mult, ct.b_mult, ct.a_mult, ct.r_add, ct.g_add, ct.b_add, ct.a_add,
+            ] {
+                w.f32(*value);
+            }
+            w.bool(state.visible);
+            w.bool(state.transformed_by_script);
+            w.option(&state.text, |w, text| w.string(text));
+        });
+
+        w.list(&self.objects, |w, state| {
+            match &state.kind {
+                ObjectKind::BuiltIn(path) => {
+                    w.u8(0);
+                    w.list(path, |w, name| w.string(name));
+                }
+                ObjectKind::DisplayObject(path) => {
+                    w.u8(1);
+                    w.path(path);
+                }
+                ObjectKind::Script => w.u8(2),
+                ObjectKind::Function(function) => {
+                    w.u8(3);
+                    w.u8(function.swf_version);
+                    w.u32(function.start as u32);
+                    w.u32(function.end as u32);
+                    w.string(&function.name);
+                    w.u8(function.register_count);
+                    w.u16(function.flags);
+                    w.list(&function.params, |w, (register, name)| {
+                        w.option(register, |w, register| w.u8(*register));
+                        w.string(name);
+                    });
+                    w.list(&function.scope, |w, (class, locals)| {
+                        w.u8(match class {
+                            ScopeClass::Global => 0,
+                            ScopeClass::Target => 1,
+                            ScopeClass::Local => 2,
+                            ScopeClass::With => 3,
+                        });
+                        w.u32(*locals);
+                    });
+                    w.list(&function.constant_pool, |w, s| w.string(s));
+                    w.path(&function.base_clip);
+                }
+                ObjectKind::Super { child, base_proto } => {
+                    w.u8(4);
+                    w.u32(*child);
+                    w.u32(*base_proto);
+                }
+            }
+            w.option(&state.proto, |w, id| w.u32(*id));
+            w.list(&state.interfaces, |w, id| w.u32(*id));
+            w.list(&state.properties, |w, (name, value, attributes)| {
+                w.string(name);
+                w.value(value);
+                w.u8(attributes.as_u8());
+            });
+            w.option(&state.array, |w, array| w.list(array, Writer::value));
+        });
+
+        w.list(&self.registered_classes, |w, (name, id)| {
+            w.string(name);
+            w.u32(*id);
+        });
+
+        w.0
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        if !data.starts_with(MAGIC) {
+            return Err("Not a Ruffle save state, or saved by an incompatible version".into());
+        }
+
+        let mut r = Reader(&data[MAGIC.len()..]);
+        let movie_hash = r.u64()?;
+        let global_time = r.u64()?;
+        let frame_accumulator = r.f64()?;
+        let mut rng_seed = [0; 16];
+        rng_seed.copy_from_slice(r.bytes(16)?);
+        let color = r.bytes(4)?;
+        let background_color = Color {
+            r: color[0],
+            g: color[1],
+            b: color[2],
+            a: color[3],
+        };
+        let constant_pool = r.list(Reader::string)?;
+        let registers = r.list(Reader::value)?;
+
+        let display_objects = r.list(|r| {
+            Ok(DisplayObjectState {
+                path: r.path()?,
+                name: r.string()?,
+                timeline: r.option(|r| Ok((r.u16()?, r.bool()?)))?,
+                matrix: Matrix {
+                    a: r.f32()?,
+                    b: r.f32()?,
+                    c: r.f32()?,
+                    d: r.f32()?,
+                    tx: Twips::new(r.u32()? as i32),
+                    ty: Twips::new(r.u32()? as i32),
+                },
+                color_transform: ColorTransform {
+                    r_mult: r.f32()?,
+                    g_mult: r.f32()?,
+                    b_mult: r.f32()?,
+                    a_mult: r.f32()?,
+                    r_add: r.f32()?,
+                    g_add: r.f32()?,
+                    b_add: r.f32()?,
+                    a_add: r.f32()?,
+                },
+                visible: r.bool()?,
+                transformed_by_script: r.bool()?,
+                text: r.option(Reader::string)?,
+            })
+        })?;
+
+        let objects = r.list(|r| {
+            let kind = match r.u8()? {
+                0 => ObjectKind::BuiltIn(r.list(Reader::string)?),
+                1 => ObjectKind::DisplayObject(r.path()?),
+                2 => ObjectKind::Script,
+                3 => ObjectKind::Function(FunctionState {
+                    swf_version: r.u8()?,
+                    start: r.u32()? as usize,
+                    end: r.u32()? as usize,
+                    name: r.string()?,
+                    register_count: r.u8()?,
+                    flags: r.u16()?,
+                    params: r.list(|r| Ok((r.option(Reader::u8)?, r.string()?)))?,
+                    scope: r.list(|r| {
+                        let class = match r.u8()? {
+                            0 => ScopeClass::Global,
+                            1 => ScopeClass::Target,
+                            2 => ScopeClass::Local,
+                            3 => ScopeClass::With,
+                            _ => return Err("Invalid scope".into()),
+                        };
+                        Ok((class, r.u32()?))
+                    })?,
+                    constant_pool: r.list(Reader::string)?,
+                    base_clip: r.path()?,
+                }),
+                4 => ObjectKind::Super {
+                    child: r.u32()?,
+                    base_proto: r.u32()?,
+                },
+                _ => return Err("Invalid object".into()),
+            };
+            Ok(ObjectState {
+                kind,
+                proto: r.option(Reader::u32)?,
+                interfaces: r.list(Reader::u32)?,
+                properties: r
+                    .list(|r| Ok((r.string()?, r.value()?, EnumSet::from_u8_truncated(r.u8()?))))?,
+                array: r.option(|r| r.list(Reader::value))?,
+            })
+        })?;
+
+        let registered_classes = r.list(|r| Ok((r.string()?, r.u32()?)))?;
+
+        Ok(Self {
+            movie_hash,
+            global_time,
+            frame_accumulator,
+            rng_seed,
+            background_color,
+            constant_pool,
+            registers,
+            display_objects,
+            objects,
+            registered_classes,
+        })
+    }
+}
+
+/// The state of an in-progress capture.
+struct Capture<'gc> {
+    movie: Arc<SwfMovie>,
+    levels: Vec<(u32, DisplayObject<'gc>)>,
+
+    /// The built-in objects, with their path from `_global` and the same object in a new AVM.
+    built_ins: Vec<(Vec<String>, Object<'gc>, Object<'gc>)>,
+    built_in_ids: HashMap<*const ObjectPtr, usize>,
+
+    ids: HashMap<*const ObjectPtr, u32>,
+
+    /// Objects whose properties have yet to be captured, along with where they were found.
+    pending: Vec<(u32, Object<'gc>, String)>,
+
+    display_objects: Vec<DisplayObjectState>,
+    objects: Vec<ObjectState>,
+    blockers: Vec<String>,
+}
+
+impl<'gc> Capture<'gc> {
+    fn new(avm: &mut Avm1<'gc>, context: &mut UpdateContext<'_, 'gc, '_>) -> Self {
+        let mut capture = Self {
+            movie: context.swf.clone(),
+            levels: context.levels.iter().map(|(l, o)| (*l, *o)).collect(),
+            built_ins: vec![],
+            built_in_ids: HashMap::new(),
+            ids: HashMap::new(),
+            pending: vec![],
+            display_objects: vec![],
+            objects: vec![],
+            blockers: vec![],
+        };
+
+        // Built-in objects are found by comparing `_global` to that of a new AVM, so that
+        // objects stored on built-ins by scripts aren't mistaken for built-ins themselves.
+        let fresh_avm = Avm1::new(context.gc_context, context.player_version);
+        capture.add_built_in(
+            vec![],
+            avm.global_object_cell(),
+            fresh_avm.global_object_cell(),
+        );
+        let mut i = 0;
+        while i < capture.built_ins.len() {
+            let (path, object, fresh_object) = capture.built_ins[i].clone();
+            i += 1;
+            if path.len() >= MAX_BUILT_IN_DEPTH {
+                continue;
+            }
+
+            let (properties, fresh_properties) =
+                match (object.as_script_object(), fresh_object.as_script_object()) {
+                    (Some(object), Some(fresh_object)) => {
+                        (object.own_properties(), fresh_object.own_properties())
+                    }
+                    _ => continue,
+                };
+            for (name, fresh_value, _) in fresh_properties {
+                let value = properties
+                    .iter()
+                    .find(|(property_name, _, _)| *property_name == name)
+                    .and_then(|(_, value, _)| value.clone());
+                if let (Some(Value::Object(child)), Some(Value::Object(fresh_child))) =
+                    (value, fresh_value)
+                {
+                    let is_script_function =
+                        matches!(child.as_executable(), Some(Executable::Action(_)));
+                    if !is_script_function && !capture.built_in_ids.contains_key(&child.as_ptr()) {
+                        let mut child_path = path.clone();
+                        child_path.push(name);
+                        capture.add_built_in(child_path, child, fresh_child);
+                    }
+                }
+            }
+        }
+
+        capture
+    }
+
+    fn add_built_in(&mut self, path: Vec<String>, object: Object<'gc>, fresh_object: Object<'gc>) {
+        self.built_in_ids
+            .insert(object.as_ptr(), self.built_ins.len());
+        self.built_ins.push((path, object, fresh_object));
+    }
+
+    fn block(&mut self, reason: String) {
+        self.blockers.push(reason);
+    }
+
+    /// Capture the contents of all objects found so far, and report anything that couldn't be
+    /// captured.
+    fn finish(&mut self) -> Result<(), SaveStateError> {
+        loop {
+            while let Some((id, object, location)) = self.pending.pop() {
+                self.capture_properties(id, object, &location);
+            }
+
+            // Built-in objects are only referred to by the properties that scripts change, so
+            // look for any others that scripts have changed.
+            let modified: Vec<_> = (0..self.built_ins.len())
+                .filter(|index| {
+                    !self.ids.contains_key(&self.built_ins[*index].1.as_ptr())
+                        && self.is_built_in_modified(*index)
+                })
+                .collect();
+            if modified.is_empty() {
+                break;
+            }
+            for index in modified {
+                let (path, object, _) = self.built_ins[index].clone();
+                self.object_id(object, &format!("_global.{}", path.join(".")));
+            }
+        }
+
+        if self.blockers.is_empty() {
+            Ok(())
+        } else {
+            Err(SaveStateError {
+                blockers: std::mem::take(&mut self.blockers),
+            })
+        }
+    }
+
+    /// Returns the path to a display object on the stage.
+    fn display_path(&self, object: DisplayObject<'gc>) -> Option<DisplayPath> {
+        if object.removed() {
+            return None;
+        }
+
+        let mut path = vec![];
+        let mut node = object;
+        while let Some(parent) = node.parent() {
+            path.push(node.depth());
+            node = parent;
+        }
+        let level = self
+            .levels
+            .iter()
+            .find(|(_, level)| DisplayObject::ptr_eq(*level, node))?
+            .0;
+        path.push(level as Depth);
+        path.reverse();
+        Some(path)
+    }
+
+    fn display_object(&mut self, object: DisplayObject<'gc>, path: DisplayPath) {
+        let location = object.path();
+        if object.parent().is_some() && object.place_frame() == 0 {
+            self.block(format!("{} was created by ActionScript", location));
+            return;
+        }
+
+        let clip = object.as_movie_clip();
+        if let Some(movie) = clip.and_then(|clip| clip.movie()) {
+            if !Arc::ptr_eq(&movie, &self.movie) {
+                self.block(format!("{} contains a loaded movie", location));
+                return;
+            }
+        }
+
+        if let Value::Object(avm_object) = object.object() {
+            self.object_id(avm_object, &location);
+        }
+
+        self.display_objects.push(DisplayObjectState {
+            path: path.clone(),
+            name: object.name().to_string(),
+            timeline: clip.map(|clip| (clip.current_frame(), clip.playing())),
+            matrix: object.matrix(),
+            color_transform: *object.color_transform(),
+            visible: object.visible(),
+            transformed_by_script: object.transformed_by_script(),
+            text: object.as_edit_text().map(|text_field| text_field.text()),
+        });
+
+        if clip.is_some() {
+            for child in object.children() {
+                if child.as_movie_clip().is_some() || child.object().as_object().is_ok() {
+                    let mut child_path = path.clone();
+                    child_path.push(child.depth());
+                    self.display_object(child, child_path);
+                }
+            }
+        }
+    }
+
+    fn value(&mut self, value: Value<'gc>, location: &str) -> ValueState {
+        match value {
+            Value::Undefined => ValueState::Undefined,
+            Value::Null => ValueState::Null,
+            Value::Bool(value) => ValueState::Bool(value),
+            Value::Number(value) => ValueState::Number(value),
+            Value::String(value) => ValueState::String(value),
+            Value::Object(object) => match self.object_id(object, location) {
+                Some(id) => ValueState::Object(id),
+                None => ValueState::Undefined,
+            },
+        }
+    }
+
+    /// Returns the ID of an object in the saved object graph, adding it if necessary.
+    fn object_id(&mut self, object: Object<'gc>, location: &str) -> Option<u32> {
+        if let Some(id) = self.ids.get(&object.as_ptr()) {
+            return Some(*id);
+        }
+
+        let kind = if let Some(index) = self.built_in_ids.get(&object.as_ptr()) {
+            ObjectKind::BuiltIn(self.built_ins[*index].0.clone())
+        } else {
+            match object {
+                Object::StageObject(_) => {
+                    match object
+                        .as_display_object()
+                        .and_then(|display_object| self.display_path(display_object))
+                    {
+                        Some(path) => ObjectKind::DisplayObject(path),
+                        None => {
+                            self.block(format!(
+                                "{} refers to a clip that is no longer on the stage",
+                                location
+                            ));
+                            return None;
+                        }
+                    }
+                }
+                Object::ScriptObject(_) => ObjectKind::Script,
+                Object::FunctionObject(_) => match object.as_executable() {
+                    // Filled in below, once the function has an ID for its scope to refer to.
+                    Some(Executable::Action(_)) => ObjectKind::Script,
+                    _ => {
+                        self.block(format!("{} is a built-in function", location));
+                        return None;
+                    }
+                },
+                Object::IsolatedGlobalObject(_) => {
+                    self.block(format!("{} is the _global of an isolated movie", location));
+                    return None;
+                }
+                Object::SoundObject(_) => {
+                    self.block(format!("{} is a Sound object", location));
+                    return None;
+                }
+                Object::BitmapDataObject(_) => {
+                    self.block(format!("{} is a BitmapData object", location));
+                    return None;
+                }
+                Object::XMLObject(_)
+                | Object::XMLAttributesObject(_)
+                | Object::XMLIDMapObject(_) => {
+                    self.block(format!("{} is an XML object", location));
+                    return None;
+                }
+                Object::ValueObject(_) => {
+                    self.block(format!(
+                        "{} is a Boolean, Number or String object",
+                        location
+                    ));
+                    return None;
+                }
+                // Filled in below, as the parts of `super` may refer back to it.
+                Object::SuperObject(_) => ObjectKind::Script,
+                Object::ArgumentsObject(_) => {
+                    self.block(format!("{} is the arguments of a function call", location));
+                    return None;
+                }
+            }
+        };
+
+        let id = self.objects.len() as u32;
+        let is_built_in = matches!(kind, ObjectKind::BuiltIn(_));
+        self.ids.insert(object.as_ptr(), id);
+        self.objects.push(ObjectState {
+            kind,
+            proto: None,
+            interfaces: vec![],
+            properties: vec![],
+            array: None,
+        });
+
+        if !is_built_in {
+            if let Some(Executable::Action(function)) = object.as_executable() {
+                if let Some(function) = self.function_state(&function, location) {
+                    self.objects[id as usize].kind = ObjectKind::Function(function);
+                }
+            } else if let Object::SuperObject(super_object) = object {
+                let child = self.object_id(super_object.child(), location);
+                let base_proto = self.object_id(super_object.base_proto(), location);
+                if let (Some(child), Some(base_proto)) = (child, base_proto) {
+                    self.objects[id as usize].kind = ObjectKind::Super { child, base_proto };
+                }
+            }
+        }
+
+        self.pending.push((id, object, location.to_string()));
+        Some(id)
+    }
+
+    fn function_state(
+        &mut self,
+        function: &Avm1Function<'gc>,
+        location: &str,
+    ) -> Option<FunctionState> {
+        let data = function.data();
+        if !Arc::ptr_eq(&data.movie, &self.movie) {
+            self.block(format!("{} was defined by a loaded movie", location));
+            return None;
+        }
+
+        let base_clip = match self.display_path(function.base_clip()) {
+            Some(path) => path,
+            None => {
+                self.block(format!(
+                    "{} was defined by a clip that is no longer on the stage",
+                    location
+                ));
+                return None;
+            }
+        };
+
+        let mut scopes = vec![];
+        let mut scope = Some(function.scope());
+        while let Some(cell) = scope {
+            let read = cell.read();
+            scopes.push((read.class(), *read.locals()));
+            scope = read.parent_cell();
+        }
+        let mut scope = vec![];
+        for (class, locals) in scopes.into_iter().rev() {
+            let scope_location = format!("The scope of {}", location);
+            scope.push((class, self.object_id(locals, &scope_location)?));
+        }
+
+        let swf_function = function.swf_function();
+        Some(FunctionState {
+            swf_version: function.swf_version(),
+            start: data.start,
+            end: data.end,
+            name: swf_function.name.to_string(),
+            register_count: swf_function.register_count,
+            flags: FunctionState::flags(&swf_function, function.is_function2()),
+            params: swf_function
+                .params
+                .iter()
+                .map(|param| (param.register_index, param.name.to_string()))
+                .collect(),
+            scope,
+            constant_pool: function.constant_pool().read().clone(),
+            base_clip,
+        })
+    }
+
+    /// Returns whether a property of a built-in object has the value it was created with.
+    fn is_built_in_property(
+        &self,
+        fresh_properties: &[(String, Option<Value<'gc>>, EnumSet<Attribute>)],
+        name: &str,
+        value: &Value<'gc>,
+    ) -> bool {
+        fresh_properties
+            .iter()
+            .filter(|(fresh_name, _, _)| fresh_name == name)
+            .any(|(_, fresh_value, _)| match (value, fresh_value) {
+                (Value::Object(object), _) => self.built_in_ids.contains_key(&object.as_ptr()),
+                (value, Some(fresh_value)) => value == fresh_value,
+                _ => false,
+            })
+    }
+
+    /// Returns whether a script has added or changed any properties of a built-in object.
+    fn is_built_in_modified(&self, index: usize) -> bool {
+        let (_, object, fresh_object) = &self.built_ins[index];
+        let (properties, fresh_properties) =
+            match (object.as_script_object(), fresh_object.as_script_object()) {
+                (Some(object), Some(fresh_object)) => {
+                    (object.own_properties(), fresh_object.own_properties())
+                }
+                _ => return false,
+            };
+        properties.iter().any(|(name, value, _)| match value {
+            Some(value) => !self.is_built_in_property(&fresh_properties, name, value),
+            None => false,
+        })
+    }
+
+    fn capture_properties(&mut self, id: u32, object: Object<'gc>, location: &str) {
+        let base = match object.as_script_object() {
+            Some(base) => base,
+            None => return,
+        };
+
+        let (is_built_in, is_display_object) = match &self.objects[id as usize].kind {
+            ObjectKind::BuiltIn(_) => (true, false),
+            ObjectKind::DisplayObject(_) => (false, true),
+            _ => (false, false),
+        };
+
+        // Only the properties that scripts have changed are saved for built-in objects.
+        let fresh_properties = self
+            .built_in_ids
+            .get(&object.as_ptr())
+            .and_then(|index| self.built_ins[*index].2.as_script_object())
+            .map(|fresh_object| fresh_object.own_properties())
+            .unwrap_or_default();
+
+        for (name, value, attributes) in base.own_properties() {
+            let property_location = format!("{}.{}", location, name);
+            let value = match value {
+                Some(value) => value,
+                // Virtual properties of objects created by Ruffle are recreated along with them.
+                None if is_built_in || is_display_object => continue,
+                None => {
+                    self.block(format!("{} is defined with addProperty", property_location));
+                    continue;
+                }
+            };
+
+            if is_built_in && self.is_built_in_property(&fresh_properties, &name, &value) {
+                continue;
+            }
+
+            let value = self.value(value, &property_location);
+            self.objects[id as usize]
+                .properties
+                .push((name, value, attributes));
+        }
+
+        if !is_built_in {
+            if let Some(proto) = object.proto() {
+                self.objects[id as usize].proto =
+                    self.object_id(proto, &format!("{}.__proto__", location));
+            }
+
+            let mut interfaces = vec![];
+            for interface in object.interfaces() {
+                interfaces.extend(self.object_id(interface, location));
+            }
+            self.objects[id as usize].interfaces = interfaces;
+        }
+
+        if base.is_array() && !is_display_object {
+            let array = object
+                .array()
+                .into_iter()
+                .enumerate()
+                .map(|(i, element)| self.value(element, &format!("{}[{}]", location, i)))
+                .collect();
+            self.objects[id as usize].array = Some(array);
+        }
+    }
+}
+
+fn movie_hash(movie: &SwfMovie) -> u64 {
+    let mut hasher = fnv::FnvHasher::default();
+    hasher.write(movie.data());
+    hasher.finish()
+}
+
+fn get_object<'gc>(objects: &[Option<Object<'gc>>], id: u32) -> Result<Object<'gc>, Error> {
+    objects
+        .get(id as usize)
+        .copied()
+        .flatten()
+        .ok_or_else(|| "Invalid object reference".into())
+}
+
+fn resolve_display_path<'gc>(
+    context: &UpdateContext<'_, 'gc, '_>,
+    path: &[Depth],
+) -> Option<DisplayObject<'gc>> {
+    let (level, depths) = path.split_first()?;
+    let mut object = *context.levels.get(&(*level as u32))?;
+    for depth in depths {
+        object = object.as_movie_clip()?.child_by_depth(*depth)?;
+    }
+    Some(object)
+}
+
+fn resolve_built_in<'gc>(globals: Object<'gc>, path: &[String]) -> Option<Object<'gc>> {
+    let mut object = globals;
+    for name in path {
+        object = object
+            .as_script_object()?
+            .own_properties()
+            .into_iter()
+            .find(|(property_name, _, _)| property_name == name)
+            .and_then(|(_, value, _)| value)?
+            .as_object()
+            .ok()?;
+    }
+    Some(object)
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.u32(value.to_bits());
+    }
+
+    fn f64(&mut self, value: f64) {
+        self.u64(value.to_bits());
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.0.extend_from_slice(value.as_bytes());
+    }
+
+    fn path(&mut self, path: &[Depth]) {
+        self.list(path, |w, depth| w.u32(*depth as u32));
+    }
+
+    fn list<T>(&mut self, items: &[T], mut write: impl FnMut(&mut Self, &T)) {
+        self.u32(items.len() as u32);
+        for item in items {
+            write(self, item);
+        }
+    }
+
+    fn option<T>(&mut self, item: &Option<T>, write: impl FnOnce(&mut Self, &T)) {
+        self.bool(item.is_some());
+        if let Some(item) = item {
+            write(self, item);
+        }
+    }
+
+    fn value(&mut self, value: &ValueState) {
+        match value {
+            ValueState::Undefined => self.u8(0),
+            ValueState::Null => self.u8(1),
+            ValueState::Bool(value) => {
+                self.u8(2);
+                self.bool(*value);
+            }
+            ValueState::Number(value) => {
+                self.u8(3);
+                self.f64(*value);
+            }
+            ValueState::String(value) => {
+                self.u8(4);
+                self.string(value);
+            }
+            ValueState::Object(id) => {
+                self.u8(5);
+                self.u32(*id);
+            }
+        }
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if len > self.0.len() {
+            return Err("Save state is truncated".into());
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, Error> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        let mut bytes = [0; 2];
+        bytes.copy_from_slice(self.bytes(2)?);
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn f32(&mut self) -> Result<f32, Error> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    fn f64(&mut self) -> Result<f64, Error> {
+        Ok(f64::from_bits(self.u64()?))
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8(self.bytes(len)?.to_vec())?)
+    }
+
+    fn path(&mut self) -> Result<DisplayPath, Error> {
+        self.list(|r| Ok(r.u32()? as Depth))
+    }
+
+    fn list<T>(
+        &mut self,
+        mut read: impl FnMut(&mut Self) -> Result<T, Error>,
+    ) -> Result<Vec<T>, Error> {
+        let len = self.u32()? as usize;
+        // Every item takes at least one byte, so don't trust lengths beyond that.
+        let mut items = Vec::with_capacity(len.min(self.0.len()));
+        for _ in 0..len {
+            items.push(read(self)?);
+        }
+        Ok(items)
+    }
+
+    fn option<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<Option<T>, Error> {
+        if self.bool()? {
+            Ok(Some(read(self)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn value(&mut self) -> Result<ValueState, Error> {
+        Ok(match self.u8()? {
+            0 => ValueState::Undefined,
+            1 => ValueState::Null,
+            2 => ValueState::Bool(self.bool()?),
+            3 => ValueState::Number(self.f64()?),
+            4 => ValueState::String(self.string()?),
+            5 => ValueState::Object(self.u32()?),
+            _ => return Err("Invalid value".into()),
+        })
+    }
+}
@@ -1,4 +1,4 @@
-use fnv::FnvHashMap;
+use std::collections::BTreeMap;
 use std::num::NonZeroU32;
 use swf::{FillStyle, LineStyle, ShapeRecord, Twips};
 
@@ -157,6 +157,19 @@ impl PathSegment {
         self.start() == self.end()
     }
 
+    /// Closes the path segment by adding a line back to its starting point.
+    /// Flash implicitly closes fill paths that don't end where they started.
+    fn close(&mut self) {
+        if !self.is_closed() {
+            let (x, y) = self.start();
+            self.add_point(Point {
+                x,
+                y,
+                is_bezier_control: false,
+            });
+        }
+    }
+
     /// Attemps to merge another path segment.
     /// One path's start must meet the other path's end.
     /// Returns true if the merge is successful.
@@ -251,23 +264,161 @@ impl PendingPath {
         }
     }
 
-    fn into_draw_commands(self) -> impl Iterator<Item = DrawCommand> {
-        self.segments
+    /// Converts the segments of a fill into draw commands, closing any segments
+    /// that were left open.
+    fn into_closed_draw_commands(self) -> impl Iterator<Item = DrawCommand> {
+        self.split_overlapping_lines()
+            .segments
             .into_iter()
-            .map(PathSegment::into_draw_commands)
+            .map(|mut segment| {
+                segment.close();
+                segment.into_draw_commands()
+            })
             .flatten()
     }
+
+    /// Splits the straight edges of a fill where they overlap each other or touch
+    /// a corner of the fill, and drops the pieces that are covered an even number
+    /// of times, such as where a self-intersecting path doubles back on itself.
+    ///
+    /// Fills use the even-odd rule, so this doesn't change what is filled, but the
+    /// tessellator gets overlapping edges and corners lying on an edge wrong. The
+    /// remaining edges are linked back into closed paths regardless of direction.
+    fn split_overlapping_lines(self) -> Self {
+        let mut lines: BTreeMap<(i64, i64, i64), Vec<(Point, Point)>> = BTreeMap::new();
+        let mut curves = vec![];
+        let mut corners = vec![];
+        for segment in &self.segments {
+            let mut points = segment.points.iter().copied();
+            let mut start = points.next().unwrap();
+            corners.push((start.x, start.y));
+            while let Some(mut end) = points.next() {
+                if end.is_bezier_control {
+                    let control = end;
+                    end = points.next().expect("Bezier without endpoint");
+                    curves.push((start, control, end));
+                } else if let Some(key) = line_key(start, end) {
+                    lines.entry(key).or_insert_with(Vec::new).push((start, end));
+                }
+                corners.push((end.x, end.y));
+                start = end;
+            }
+        }
+        corners.sort_unstable();
+        corners.dedup();
+
+        let mut is_split = false;
+        let mut edges = vec![];
+        for (&(dx, dy, offset), line) in &lines {
+            let position =
+                |(x, y): (Twips, Twips)| dx * i64::from(x.get()) + dy * i64::from(y.get());
+
+            // Each end of an edge toggles whether the line is covered from there on.
+            let mut breaks: BTreeMap<i64, ((Twips, Twips), bool)> = BTreeMap::new();
+            let mut ends: Vec<(i64, i64)> = Vec::with_capacity(line.len());
+            for &(start, end) in line {
+                for &point in &[(start.x, start.y), (end.x, end.y)] {
+                    breaks.entry(position(point)).or_insert((point, false)).1 ^= true;
+                }
+                let (a, b) = (position((start.x, start.y)), position((end.x, end.y)));
+                ends.push((a.min(b), a.max(b)));
+            }
+            ends.sort_unstable();
+            is_split |= ends.windows(2).any(|pair| pair[1].0 < pair[0].1);
+
+            let (first, last) = match (breaks.values().next(), breaks.values().next_back()) {
+                (Some(&(first, _)), Some(&(last, _))) => (first, last),
+                _ => continue,
+            };
+            let (min_x, max_x) = (first.0.min(last.0), first.0.max(last.0));
+            let from = corners
+                .binary_search(&(min_x, Twips::new(std::i32::MIN)))
+                .unwrap_or_else(|i| i);
+            for &corner in corners[from..].iter().take_while(|&&(x, _)| x <= max_x) {
+                let on_line =
+                    dy * i64::from(corner.0.get()) - dx * i64::from(corner.1.get()) == offset;
+                let t = position(corner);
+                if on_line && t > position(first) && t < position(last) && !breaks.contains_key(&t)
+                {
+                    breaks.insert(t, (corner, false));
+                    is_split = true;
+                }
+            }
+
+            let mut is_covered = false;
+            let mut previous = None;
+            for &(point, toggle) in breaks.values() {
+                if let (true, Some(previous)) = (is_covered, previous) {
+                    let mut edge = PathSegment::new(previous);
+                    edge.add_point(Point {
+                        x: point.0,
+                        y: point.1,
+                        is_bezier_control: false,
+                    });
+                    edges.push(edge);
+                }
+                is_covered ^= toggle;
+                previous = Some(point);
+            }
+        }
+        if !is_split {
+            return self;
+        }
+
+        for (start, control, end) in curves {
+            let mut edge = PathSegment::new((start.x, start.y));
+            edge.add_point(control);
+            edge.add_point(end);
+            edges.push(edge);
+        }
+        let mut path = PendingPath::new();
+        for edge in edges {
+            path.merge_path(edge, false);
+        }
+        path
+    }
+}
+
+/// Returns a key that is the same for all straight edges on the same line: the
+/// direction of the edge in its simplest form, and the line's offset from the origin.
+/// Returns `None` for an edge of zero length.
+fn line_key(start: Point, end: Point) -> Option<(i64, i64, i64)> {
+    let mut dx = i64::from((end.x - start.x).get());
+    let mut dy = i64::from((end.y - start.y).get());
+    if dx == 0 && dy == 0 {
+        return None;
+    }
+    let divisor = gcd(dx.abs(), dy.abs());
+    dx /= divisor;
+    dy /= divisor;
+    if dx < 0 || (dx == 0 && dy < 0) {
+        dx = -dx;
+        dy = -dy;
+    }
+    let offset = dy * i64::from(start.x.get()) - dx * i64::from(start.y.get());
+    Some((dx, dy, offset))
+}
+
+/// Returns the greatest common divisor of two non-negative numbers.
+fn gcd(mut a: i64, mut b: i64) -> i64 {
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+    a
 }
 
 /// `PendingPathMap` maps from style IDs to the path associated with that style.
 /// Each path is uniquely identified by its style ID (until the style list changes).
-/// Style IDs tend to be sequential, so we just use a `Vec`.
+/// Paths are kept sorted by style ID so that overlapping fills within a layer are
+/// always drawn in the same order, with higher style IDs on top.
 #[derive(Debug)]
-pub struct PendingPathMap(FnvHashMap<NonZeroU32, PendingPath>);
+pub struct PendingPathMap(BTreeMap<NonZeroU32, PendingPath>);
 
 impl PendingPathMap {
     fn new() -> Self {
-        Self(FnvHashMap::default())
+        Self(BTreeMap::new())
     }
 
     fn merge_path(&mut self, path: ActivePath, directed: bool) {
@@ -501,16 +652,16 @@ impl<'a> ShapeConverter<'a> {
         self.fill_style1 = None;
         self.line_style = None;
 
-        //let fills = std::mem::replace(&mut self.fills.0, FnvHashMap::default());
-        //let strokes = std::mem::replace(&mut self.strokes.0, FnvHashMap::default());
+        let fills = std::mem::replace(&mut self.fills.0, BTreeMap::new());
+        let strokes = std::mem::replace(&mut self.strokes.0, BTreeMap::new());
 
         // Draw fills, and then strokes.
-        for (style_id, path) in self.fills.0.drain() {
+        for (style_id, path) in fills {
             assert!(style_id.get() > 0);
             let style = &self.fill_styles[style_id.get() as usize - 1];
             self.commands.push(DrawPath::Fill {
                 style,
-                commands: path.into_draw_commands().collect(),
+                commands: path.into_closed_draw_commands().collect(),
             });
         }
 
@@ -518,7 +669,7 @@ impl<'a> ShapeConverter<'a> {
         // Because path segments can either be open or closed, we convert each stroke segment into
         // a separate draw command.
         // TODO(Herschel): Open strokes could be grouped together into a single path.
-        for (style_id, path) in self.strokes.0.drain() {
+        for (style_id, path) in strokes {
             assert!(style_id.get() > 0);
             let style = &self.line_styles[style_id.get() as usize - 1];
             for segment in path.segments {
@@ -684,4 +835,161 @@ mod tests {
         }];
         assert_eq!(commands, expected);
     }
+
+    /// A fill path that doesn't return to its starting point is implicitly closed.
+    #[test]
+    fn open_fill_is_closed() {
+        let shape = build_shape(vec![
+            ShapeRecord::StyleChange(swf::StyleChangeData {
+                move_to: Some((Twips::from_pixels(100.0), Twips::from_pixels(100.0))),
+                fill_style_0: None,
+                fill_style_1: Some(1),
+                line_style: None,
+                new_styles: None,
+            }),
+            ShapeRecord::StraightEdge {
+                delta_x: Twips::from_pixels(100.0),
+                delta_y: Twips::from_pixels(0.0),
+            },
+            ShapeRecord::StraightEdge {
+                delta_x: Twips::from_pixels(0.0),
+                delta_y: Twips::from_pixels(100.0),
+            },
+        ]);
+        let commands = swf_shape_to_paths(&shape);
+        let expected = vec![DrawPath::Fill {
+            style: &FILL_STYLES[0],
+            commands: vec![
+                DrawCommand::MoveTo {
+                    x: Twips::from_pixels(100.0),
+                    y: Twips::from_pixels(100.0),
+                },
+                DrawCommand::LineTo {
+                    x: Twips::from_pixels(200.0),
+                    y: Twips::from_pixels(100.0),
+                },
+                DrawCommand::LineTo {
+                    x: Twips::from_pixels(200.0),
+                    y: Twips::from_pixels(200.0),
+                },
+                DrawCommand::LineTo {
+                    x: Twips::from_pixels(100.0),
+                    y: Twips::from_pixels(100.0),
+                },
+            ],
+        }];
+        assert_eq!(commands, expected);
+    }
+
+    /// Edges that double back on themselves cancel out, as they would with the
+    /// even-odd fill rule.
+    #[test]
+    fn edges_doubling_back_are_removed() {
+        let shape = build_shape(vec![
+            ShapeRecord::StyleChange(swf::StyleChangeData {
+                move_to: Some((Twips::from_pixels(0.0), Twips::from_pixels(0.0))),
+                fill_style_0: None,
+                fill_style_1: Some(1),
+                line_style: None,
+                new_styles: None,
+            }),
+            ShapeRecord::StraightEdge {
+                delta_x: Twips::from_pixels(150.0),
+                delta_y: Twips::from_pixels(0.0),
+            },
+            ShapeRecord::StraightEdge {
+                delta_x: Twips::from_pixels(-50.0),
+                delta_y: Twips::from_pixels(0.0),
+            },
+            ShapeRecord::StraightEdge {
+                delta_x: Twips::from_pixels(0.0),
+                delta_y: Twips::from_pixels(100.0),
+            },
+            ShapeRecord::StraightEdge {
+                delta_x: Twips::from_pixels(-100.0),
+                delta_y: Twips::from_pixels(-100.0),
+            },
+        ]);
+        let commands = swf_shape_to_paths(&shape);
+        let expected = vec![DrawPath::Fill {
+            style: &FILL_STYLES[0],
+            commands: vec![
+                DrawCommand::MoveTo {
+                    x: Twips::from_pixels(0.0),
+                    y: Twips::from_pixels(0.0),
+                },
+                DrawCommand::LineTo {
+                    x: Twips::from_pixels(100.0),
+                    y: Twips::from_pixels(0.0),
+                },
+                DrawCommand::LineTo {
+                    x: Twips::from_pixels(100.0),
+                    y: Twips::from_pixels(100.0),
+                },
+                DrawCommand::LineTo {
+                    x: Twips::from_pixels(0.0),
+                    y: Twips::from_pixels(0.0),
+                },
+            ],
+        }];
+        assert_eq!(commands, expected);
+    }
+
+    /// Overlapping fills in the same layer are output in order of their fill style.
+    #[test]
+    fn overlapping_fills_in_style_order() {
+        let square = |fill_style, x| {
+            vec![
+                ShapeRecord::StyleChange(swf::StyleChangeData {
+                    move_to: Some((Twips::from_pixels(x), Twips::from_pixels(0.0))),
+                    fill_style_0: None,
+                    fill_style_1: Some(fill_style),
+                    line_style: None,
+                    new_styles: None,
+                }),
+                ShapeRecord::StraightEdge {
+                    delta_x: Twips::from_pixels(100.0),
+                    delta_y: Twips::from_pixels(0.0),
+                },
+                ShapeRecord::StraightEdge {
+                    delta_x: Twips::from_pixels(0.0),
+                    delta_y: Twips::from_pixels(100.0),
+                },
+                ShapeRecord::StraightEdge {
+                    delta_x: Twips::from_pixels(-100.0),
+                    delta_y: Twips::from_pixels(0.0),
+                },
+                ShapeRecord::StraightEdge {
+                    delta_x: Twips::from_pixels(0.0),
+                    delta_y: Twips::from_pixels(-100.0),
+                },
+            ]
+        };
+        let fill_styles: Vec<FillStyle> = (0..8)
+            .map(|i| {
+                FillStyle::Color(swf::Color {
+                    r: i,
+                    g: 0,
+                    b: 0,
+                    a: 255,
+                })
+            })
+            .collect();
+        let mut records = vec![];
+        for fill_style in (1..=8).rev() {
+            records.extend(square(fill_style, f64::from(fill_style) * 10.0));
+        }
+        let mut shape = build_shape(records);
+        shape.styles.fill_styles = fill_styles.clone();
+
+        let styles: Vec<&FillStyle> = swf_shape_to_paths(&shape)
+            .into_iter()
+            .map(|path| match path {
+                DrawPath::Fill { style, .. } => style,
+                DrawPath::Stroke { .. } => panic!("Unexpected stroke"),
+            })
+            .collect();
+        let expected: Vec<&FillStyle> = fill_styles.iter().collect();
+        assert_eq!(styles, expected);
+    }
 }
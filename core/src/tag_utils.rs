@@ -1,4 +1,7 @@
+use crate::backend::navigator::url_from_file_path;
 use gc_arena::Collect;
+use serde::Serialize;
+use std::convert::TryInto;
 use std::path::Path;
 use std::sync::Arc;
 use swf::{Header, TagCode};
@@ -16,7 +19,16 @@ pub struct SwfMovie {
     header: Header,
 
     /// Uncompressed SWF data.
-    data: Vec<u8>,
+    ///
+    /// Copies of a movie share this data, so that the same movie can be loaded several times
+    /// without decompressing it again.
+    data: Arc<Vec<u8>>,
+
+    /// The URL the SWF was downloaded from.
+    url: Option<String>,
+
+    /// Which virtual machines the movie's scripts are written for.
+    script_kind: ScriptKind,
 }
 
 impl SwfMovie {
@@ -30,7 +42,9 @@ impl SwfMovie {
                 frame_rate: 1.0,
                 num_frames: 0,
             },
-            data: vec![],
+            data: Arc::new(vec![]),
+            url: None,
+            script_kind: ScriptKind::Avm1,
         }
     }
 
@@ -38,18 +52,32 @@ impl SwfMovie {
     pub fn from_movie_and_subdata(&self, data: Vec<u8>) -> Self {
         Self {
             header: self.header.clone(),
-            data,
+            data: Arc::new(data),
+            url: self.url.clone(),
+            script_kind: self.script_kind,
         }
     }
 
     /// Utility method to construct a movie from a file on disk.
+    ///
+    /// The movie's URL is the `file:` URL of the path. The file may also be a projector, or any
+    /// other file with a SWF embedded in it, in which case the embedded movie is played. Its URL
+    /// is still that of the file, so that relative URLs are resolved next to it.
     pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
-        let data = std::fs::read(path)?;
-        Self::from_data(&data)
+        let data = std::fs::read(&path)?;
+        let url = url_from_file_path(path.as_ref())
+            .unwrap_or_else(|| path.as_ref().to_string_lossy().into_owned());
+        if is_swf_signature(&data) {
+            return Self::from_data(&data, Some(url));
+        }
+        match find_embedded_swf(&data) {
+            Some(swf_data) => Self::from_data(swf_data, Some(url)),
+            None => Err(format!("No SWF file found in {}", url).into()),
+        }
     }
 
     /// Construct a movie based on the contents of the SWF datastream.
-    pub fn from_data(swf_data: &[u8]) -> Result<Self, Error> {
+    pub fn from_data(swf_data: &[u8], url: Option<String>) -> Result<Self, Error> {
         let swf_stream = swf::read::read_swf_header(&swf_data[..])?;
         let header = swf_stream.header;
         let mut reader = swf_stream.reader;
@@ -74,7 +102,14 @@ impl SwfMovie {
             data
         };
 
-        Ok(Self { header, data })
+        let script_kind = ScriptKind::from_tags(&data, header.version);
+
+        Ok(Self {
+            header,
+            data: Arc::new(data),
+            url,
+            script_kind,
+        })
     }
 
     pub fn header(&self) -> &Header {
@@ -87,7 +122,7 @@ impl SwfMovie {
     }
 
     pub fn data(&self) -> &[u8] {
-        &self.data
+        &self.data[..]
     }
 
     pub fn width(&self) -> u32 {
@@ -97,6 +132,165 @@ impl SwfMovie {
     pub fn height(&self) -> u32 {
         (self.header.stage_size.y_max - self.header.stage_size.y_min).to_pixels() as u32
     }
+
+    /// Get the URL this SWF was fetched from.
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// Get which virtual machines the movie's scripts are written for.
+    pub fn script_kind(&self) -> ScriptKind {
+        self.script_kind
+    }
+}
+
+/// The signatures at the start of uncompressed, zlib compressed and LZMA compressed SWF files.
+const SWF_SIGNATURES: [&[u8]; 3] = [b"FWS", b"CWS", b"ZWS"];
+
+/// Flash Player projectors end with this value, followed by the length of the SWF file
+/// that was appended to the executable before it.
+const PROJECTOR_FOOTER_MAGIC: u32 = 0xFA12_3456;
+
+fn is_swf_signature(data: &[u8]) -> bool {
+    SWF_SIGNATURES
+        .iter()
+        .any(|signature| data.starts_with(signature))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<usize> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+}
+
+/// Finds a SWF file embedded in a larger file, such as a projector executable.
+///
+/// The movie is located using the footer of Flash Player projectors if there is one, or else
+/// by searching for the first SWF signature that is followed by a valid header.
+pub fn find_embedded_swf(data: &[u8]) -> Option<&[u8]> {
+    if data.len() >= 8 {
+        let footer_start = data.len() - 8;
+        if read_u32(data, footer_start) == Some(PROJECTOR_FOOTER_MAGIC as usize) {
+            let swf_length = read_u32(data, footer_start + 4)?;
+            if let Some(swf_start) = footer_start.checked_sub(swf_length) {
+                if let Some(swf_data) = swf_at(&data[swf_start..footer_start]) {
+                    return Some(swf_data);
+                }
+            }
+        }
+    }
+
+    (0..data.len()).find_map(|offset| swf_at(&data[offset..]))
+}
+
+/// Returns the SWF file at the start of `data`, without any trailing bytes, if `data` starts
+/// with a valid SWF header.
+fn swf_at(data: &[u8]) -> Option<&[u8]> {
+    if !is_swf_signature(data) {
+        return None;
+    }
+
+    // The length in the header is the uncompressed length, including the header itself.
+    let uncompressed_length = read_u32(data, 4)?;
+    if uncompressed_length < 8 {
+        return None;
+    }
+    let swf_data = match data[0] {
+        // Uncompressed movies must fit in the rest of the file.
+        b'F' => data.get(..uncompressed_length)?,
+        // LZMA compressed movies store their compressed length, which doesn't include the
+        // header and the 5 bytes of LZMA properties.
+        b'Z' => data.get(..read_u32(data, 8)?.checked_add(17)?)?,
+        // The end of zlib compressed movies is only known once they are decompressed.
+        _ => data,
+    };
+
+    swf::read::read_swf_header(swf_data).ok()?;
+    Some(swf_data)
+}
+
+/// The ActionScript virtual machines a movie's scripts are written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ScriptKind {
+    /// The movie only contains AVM1 scripts, or no scripts at all.
+    Avm1,
+
+    /// The movie has AVM1 timeline scripts, but also contains ABC blocks meant for AVM2.
+    ///
+    /// These are usually exported by tools that emit both. The AVM1 scripts run as normal,
+    /// and the ABC blocks are skipped.
+    Hybrid,
+
+    /// The movie is an ActionScript 3 movie, whose scripts can only run in AVM2.
+    Avm2,
+}
+
+/// The legacy tag code of `DoAbc`, which has no name or flags.
+const DO_ABC_LEGACY_TAG_CODE: u16 = 72;
+
+/// The `FileAttributes` flag marking an ActionScript 3 movie.
+const FILE_ATTRIBUTES_AS3_FLAG: u8 = 0b0000_1000;
+
+/// The script-related tags found while scanning a movie.
+#[derive(Default)]
+struct ScriptTags {
+    is_action_script_3: bool,
+    has_avm1_code: bool,
+    has_avm2_code: bool,
+}
+
+impl ScriptKind {
+    /// Classify a movie by scanning its uncompressed tag stream, including the timelines of its
+    /// sprites.
+    ///
+    /// A movie marked as ActionScript 3 in its `FileAttributes` only runs AVM2 code, just like
+    /// in the Flash Player. Otherwise, `DoAbc` and `SymbolClass` tags mark AVM2 code, and
+    /// `DoAction` and `DoInitAction` tags mark AVM1 code.
+    fn from_tags(data: &[u8], version: u8) -> Self {
+        let mut tags = ScriptTags::default();
+        let mut reader = swf::read::Reader::new(std::io::Cursor::new(data), version);
+        if let Err(e) = Self::scan_tags(&mut reader, &mut tags) {
+            log::warn!("Error scanning SWF for scripts: {}", e);
+        }
+
+        if tags.is_action_script_3 || (tags.has_avm2_code && !tags.has_avm1_code) {
+            ScriptKind::Avm2
+        } else if tags.has_avm2_code {
+            ScriptKind::Hybrid
+        } else {
+            ScriptKind::Avm1
+        }
+    }
+
+    fn scan_tags(reader: &mut SwfStream<&[u8]>, tags: &mut ScriptTags) -> DecodeResult {
+        use std::io::{Seek, SeekFrom};
+        loop {
+            let (tag_code, tag_len) = reader.read_tag_code_and_length()?;
+            let start_pos = reader.get_ref().position();
+            let end_pos = start_pos + tag_len as u64;
+
+            match TagCode::from_u16(tag_code) {
+                Some(TagCode::End) => break,
+                Some(TagCode::FileAttributes) => {
+                    let flags = reader.get_ref().get_ref().get(start_pos as usize);
+                    tags.is_action_script_3 =
+                        flags.map_or(false, |flags| flags & FILE_ATTRIBUTES_AS3_FLAG != 0);
+                }
+                Some(TagCode::DoAction) | Some(TagCode::DoInitAction) => tags.has_avm1_code = true,
+                Some(TagCode::DoAbc) | Some(TagCode::SymbolClass) => tags.has_avm2_code = true,
+                Some(TagCode::DefineSprite) => {
+                    // Skip the sprite ID and frame count to reach the sprite's own tags.
+                    reader.get_mut().seek(SeekFrom::Start(start_pos + 4))?;
+                    Self::scan_tags(reader, tags)?;
+                }
+                None if tag_code == DO_ABC_LEGACY_TAG_CODE => tags.has_avm2_code = true,
+                _ => (),
+            }
+
+            reader.get_mut().seek(SeekFrom::Start(end_pos))?;
+        }
+
+        Ok(())
+    }
 }
 
 /// A shared-ownership reference to some portion of an SWF datastream.
@@ -280,3 +474,65 @@ where
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNCOMPRESSED_SWF: &[u8] = include_bytes!("../tests/swfs/avm1/loadmovie/target.swf");
+    const ZLIB_SWF: &[u8] = include_bytes!("../tests/swfs/avm1/loadmovie/test.swf");
+
+    /// Places a SWF at an offset in some other data, followed by a few more bytes.
+    fn embed(prefix: &[u8], swf_data: &[u8]) -> Vec<u8> {
+        let mut data = prefix.to_vec();
+        data.extend_from_slice(swf_data);
+        data.extend_from_slice(b"trailing data");
+        data
+    }
+
+    #[test]
+    fn finds_swf_at_offset() {
+        let data = embed(b"MZ not a movie", UNCOMPRESSED_SWF);
+        assert_eq!(find_embedded_swf(&data), Some(UNCOMPRESSED_SWF));
+
+        let data = embed(b"MZ not a movie", ZLIB_SWF);
+        let found = find_embedded_swf(&data).unwrap();
+        assert!(found.starts_with(ZLIB_SWF));
+        assert_eq!(
+            SwfMovie::from_data(found, None).unwrap().data(),
+            SwfMovie::from_data(ZLIB_SWF, None).unwrap().data()
+        );
+    }
+
+    #[test]
+    fn skips_invalid_headers() {
+        // Signatures with lengths that don't fit in the file, or that aren't followed by a
+        // valid header.
+        let mut prefix = b"FWS\x0a\xff\xff\xff\x7f".to_vec();
+        prefix.extend_from_slice(b"FWS\x0a\x04\x00\x00\x00");
+        prefix.extend_from_slice(b"CWS\x0a\x40\x00\x00\x00garbage");
+        let data = embed(&prefix, UNCOMPRESSED_SWF);
+        assert_eq!(find_embedded_swf(&data), Some(UNCOMPRESSED_SWF));
+
+        // The movie is cut short.
+        let data = &UNCOMPRESSED_SWF[..UNCOMPRESSED_SWF.len() - 1];
+        assert_eq!(find_embedded_swf(data), None);
+        assert_eq!(find_embedded_swf(b"no movie here"), None);
+    }
+
+    #[test]
+    fn finds_swf_using_projector_footer() {
+        // The footer takes precedence over a valid movie earlier in the file.
+        let mut data = embed(b"MZ", UNCOMPRESSED_SWF);
+        data.extend_from_slice(ZLIB_SWF);
+        data.extend_from_slice(&PROJECTOR_FOOTER_MAGIC.to_le_bytes());
+        data.extend_from_slice(&(ZLIB_SWF.len() as u32).to_le_bytes());
+        assert_eq!(find_embedded_swf(&data), Some(ZLIB_SWF));
+
+        // A footer with a length that doesn't fit is ignored.
+        let mut data = embed(b"MZ", UNCOMPRESSED_SWF);
+        data.extend_from_slice(&PROJECTOR_FOOTER_MAGIC.to_le_bytes());
+        data.extend_from_slice(&u32::max_value().to_le_bytes());
+        assert_eq!(find_embedded_swf(&data), Some(UNCOMPRESSED_SWF));
+    }
+}
@@ -0,0 +1,280 @@
+//! Fitting the stage into the viewport of the player.
+
+use crate::backend::render::Letterbox;
+use crate::matrix::Matrix;
+use swf::Twips;
+
+/// How the stage is scaled to fit the viewport, as set by `Stage.scaleMode`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StageScaleMode {
+    /// The whole stage is visible, scaled uniformly, with margins on two of its sides.
+    ShowAll,
+
+    /// The whole viewport is covered, scaled uniformly, and the stage is cropped on two of its
+    /// sides.
+    NoBorder,
+
+    /// The stage is stretched to the viewport, scaling each axis separately.
+    ExactFit,
+
+    /// The stage keeps its size in pixels, centered in the viewport.
+    NoScale,
+}
+
+impl StageScaleMode {
+    /// Looks up a scale mode by the name used by `Stage.scaleMode`, ignoring case as Flash
+    /// Player does.
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            StageScaleMode::ShowAll,
+            StageScaleMode::NoBorder,
+            StageScaleMode::ExactFit,
+            StageScaleMode::NoScale,
+        ]
+        .iter()
+        .copied()
+        .find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+
+    /// The name of this scale mode in `Stage.scaleMode`.
+    pub fn name(self) -> &'static str {
+        match self {
+            StageScaleMode::ShowAll => "showAll",
+            StageScaleMode::NoBorder => "noBorder",
+            StageScaleMode::ExactFit => "exactFit",
+            StageScaleMode::NoScale => "noScale",
+        }
+    }
+}
+
+impl Default for StageScaleMode {
+    fn default() -> Self {
+        StageScaleMode::ShowAll
+    }
+}
+
+/// Computes the view matrix that draws a stage of the given size in pixels into the viewport,
+/// along with the letterbox covering the viewport outside of the stage.
+///
+/// In pixel perfect mode, the stage is scaled by the largest whole number that fits the
+/// viewport and placed on whole pixels, regardless of the scale mode. The letterbox is only
+/// drawn if `letterbox_enabled` is set.
+pub fn fit_stage(
+    (stage_width, stage_height): (f32, f32),
+    (viewport_width, viewport_height): (f32, f32),
+    scale_mode: StageScaleMode,
+    pixel_perfect: bool,
+    letterbox_enabled: bool,
+) -> (Matrix, Letterbox) {
+    let fit_width = viewport_width / stage_width;
+    let fit_height = viewport_height / stage_height;
+    let (scale_x, scale_y) = if pixel_perfect {
+        // The stage can't be scaled down, and overflows small viewports instead.
+        let scale = fit_width.min(fit_height).floor().max(1.0);
+        (scale, scale)
+    } else {
+        match scale_mode {
+            StageScaleMode::ShowAll => {
+                let scale = fit_width.min(fit_height);
+                (scale, scale)
+            }
+            StageScaleMode::NoBorder => {
+                let scale = fit_width.max(fit_height);
+                (scale, scale)
+            }
+            StageScaleMode::ExactFit => (fit_width, fit_height),
+            StageScaleMode::NoScale => (1.0, 1.0),
+        }
+    };
+
+    let (width, height) = (stage_width * scale_x, stage_height * scale_y);
+    let (mut margin_width, mut margin_height) = (
+        (viewport_width - width) / 2.0,
+        (viewport_height - height) / 2.0,
+    );
+    if pixel_perfect {
+        margin_width = margin_width.floor();
+        margin_height = margin_height.floor();
+    }
+
+    let view_matrix = Matrix {
+        a: scale_x,
+        b: 0.0,
+        c: 0.0,
+        d: scale_y,
+        tx: Twips::from_pixels(margin_width.into()),
+        ty: Twips::from_pixels(margin_height.into()),
+    };
+
+    // The original Flash Player defaults to showing content in the extra margins.
+    let letterbox = if !letterbox_enabled {
+        Letterbox::None
+    } else if pixel_perfect || scale_mode == StageScaleMode::NoScale {
+        // Only the part of the stage inside the viewport is left uncovered. The margins on the
+        // far sides are a pixel wider when the space left over is odd.
+        Letterbox::Windowbox {
+            x: margin_width.max(0.0),
+            y: margin_height.max(0.0),
+            width: width.min(viewport_width),
+            height: height.min(viewport_height),
+        }
+    } else if margin_width > 0.0 {
+        Letterbox::Pillarbox(margin_width)
+    } else if margin_height > 0.0 {
+        Letterbox::Letterbox(margin_height)
+    } else {
+        Letterbox::None
+    };
+
+    (view_matrix, letterbox)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STAGE: (f32, f32) = (550.0, 400.0);
+    const WIDE: (f32, f32) = (1100.0, 400.0);
+    const TALL: (f32, f32) = (550.0, 800.0);
+    const DOUBLE: (f32, f32) = (1100.0, 800.0);
+    const SMALL: (f32, f32) = (275.0, 200.0);
+
+    fn matrix(scale_x: f32, scale_y: f32, tx: f64, ty: f64) -> Matrix {
+        Matrix {
+            a: scale_x,
+            b: 0.0,
+            c: 0.0,
+            d: scale_y,
+            tx: Twips::from_pixels(tx),
+            ty: Twips::from_pixels(ty),
+        }
+    }
+
+    fn fit(viewport: (f32, f32), scale_mode: StageScaleMode) -> (Matrix, Letterbox) {
+        fit_stage(STAGE, viewport, scale_mode, false, true)
+    }
+
+    #[test]
+    fn scale_modes_are_named_like_flash() {
+        assert_eq!(StageScaleMode::default(), StageScaleMode::ShowAll);
+        assert_eq!(
+            StageScaleMode::from_name("noBorder"),
+            Some(StageScaleMode::NoBorder)
+        );
+        assert_eq!(
+            StageScaleMode::from_name("EXACTFIT"),
+            Some(StageScaleMode::ExactFit)
+        );
+        assert_eq!(StageScaleMode::from_name("stretch"), None);
+        assert_eq!(StageScaleMode::NoScale.name(), "noScale");
+    }
+
+    #[test]
+    fn show_all_fits_the_stage_with_bars() {
+        assert_eq!(
+            fit(WIDE, StageScaleMode::ShowAll),
+            (matrix(1.0, 1.0, 275.0, 0.0), Letterbox::Pillarbox(275.0))
+        );
+        assert_eq!(
+            fit(TALL, StageScaleMode::ShowAll),
+            (matrix(1.0, 1.0, 0.0, 200.0), Letterbox::Letterbox(200.0))
+        );
+        assert_eq!(
+            fit(DOUBLE, StageScaleMode::ShowAll),
+            (matrix(2.0, 2.0, 0.0, 0.0), Letterbox::None)
+        );
+    }
+
+    #[test]
+    fn no_border_crops_the_stage() {
+        assert_eq!(
+            fit(WIDE, StageScaleMode::NoBorder),
+            (matrix(2.0, 2.0, 0.0, -200.0), Letterbox::None)
+        );
+        assert_eq!(
+            fit(TALL, StageScaleMode::NoBorder),
+            (matrix(2.0, 2.0, -275.0, 0.0), Letterbox::None)
+        );
+        assert_eq!(
+            fit(DOUBLE, StageScaleMode::NoBorder),
+            (matrix(2.0, 2.0, 0.0, 0.0), Letterbox::None)
+        );
+    }
+
+    #[test]
+    fn exact_fit_stretches_each_axis() {
+        assert_eq!(
+            fit(WIDE, StageScaleMode::ExactFit),
+            (matrix(2.0, 1.0, 0.0, 0.0), Letterbox::None)
+        );
+        assert_eq!(
+            fit(TALL, StageScaleMode::ExactFit),
+            (matrix(1.0, 2.0, 0.0, 0.0), Letterbox::None)
+        );
+        assert_eq!(
+            fit(SMALL, StageScaleMode::ExactFit),
+            (matrix(0.5, 0.5, 0.0, 0.0), Letterbox::None)
+        );
+    }
+
+    #[test]
+    fn no_scale_centers_the_stage() {
+        assert_eq!(
+            fit(WIDE, StageScaleMode::NoScale),
+            (
+                matrix(1.0, 1.0, 275.0, 0.0),
+                Letterbox::Windowbox {
+                    x: 275.0,
+                    y: 0.0,
+                    width: 550.0,
+                    height: 400.0
+                }
+            )
+        );
+        assert_eq!(
+            fit(DOUBLE, StageScaleMode::NoScale),
+            (
+                matrix(1.0, 1.0, 275.0, 200.0),
+                Letterbox::Windowbox {
+                    x: 275.0,
+                    y: 200.0,
+                    width: 550.0,
+                    height: 400.0
+                }
+            )
+        );
+
+        // A viewport smaller than the stage shows its middle, without bars.
+        let (view_matrix, letterbox) = fit(SMALL, StageScaleMode::NoScale);
+        assert_eq!(view_matrix, matrix(1.0, 1.0, -137.5, -100.0));
+        assert!(letterbox.bars(SMALL.0, SMALL.1).is_empty());
+    }
+
+    #[test]
+    fn pixel_perfect_overrides_the_scale_mode() {
+        let (view_matrix, letterbox) =
+            fit_stage(STAGE, (1200.0, 900.0), StageScaleMode::ExactFit, true, true);
+        assert_eq!(view_matrix, matrix(2.0, 2.0, 50.0, 50.0));
+        assert_eq!(
+            letterbox,
+            Letterbox::Windowbox {
+                x: 50.0,
+                y: 50.0,
+                width: 1100.0,
+                height: 800.0
+            }
+        );
+    }
+
+    #[test]
+    fn disabled_letterbox_has_no_bars() {
+        assert_eq!(
+            fit_stage(STAGE, WIDE, StageScaleMode::ShowAll, false, false).1,
+            Letterbox::None
+        );
+        assert_eq!(
+            fit_stage(STAGE, WIDE, StageScaleMode::NoScale, false, false).1,
+            Letterbox::None
+        );
+    }
+}
@@ -3,15 +3,39 @@
 //! Trace output can be compared with correct output from the official Flash Payer.
 
 use approx::assert_abs_diff_eq;
-use log::{Metadata, Record};
-use ruffle_core::backend::navigator::{NullExecutor, NullNavigatorBackend};
+use log::{Level, LevelFilter, Metadata, Record};
+use ruffle_core::backend::navigator::{
+    CancellationToken, HttpStatusError, NavigationMethod, NavigatorBackend, NullExecutor,
+    NullNavigatorBackend, OwnedFuture, ProgressCallback, RequestOptions, SpawnHandle,
+    UrlRewriteRules,
+};
 use ruffle_core::backend::{
-    audio::NullAudioBackend, input::NullInputBackend, render::NullRenderer,
+    audio::{swf, AudioCall, NullAudioBackend, NullAudioMonitor},
+    input::{InputBackend, NullInputBackend},
+    log::{LogBackend, TraceContext},
+    render::{
+        BitmapHandle, BitmapInfo, Letterbox, NullRenderer, PixelRegion, RenderBackend, ShapeHandle,
+        Transform,
+    },
+    storage::{MemoryStorageBackend, StorageBackend},
+    ui::{DisplayState, MessageLevel, MouseCursor, NullUiBackend, UiBackend},
 };
-use ruffle_core::tag_utils::SwfMovie;
-use ruffle_core::Player;
-use std::cell::RefCell;
+use ruffle_core::events::{key_code_for_key, KeyCode, PlayerEvent};
+use ruffle_core::external::{ExternalInterfaceProvider, Value as ExternalValue};
+use ruffle_core::swf::{BlendMode, DefineBitsLossless, Glyph, Shape, Twips};
+use ruffle_core::tag_utils::{ScriptKind, SwfMovie};
+use ruffle_core::viewport::StageScaleMode;
+use ruffle_core::{Color, Player};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
 use std::path::Path;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, Once};
+use std::task::{Context, Poll};
+use std::time::Duration;
 
 type Error = Box<dyn std::error::Error>;
 
@@ -25,6 +49,26 @@ macro_rules! swf_tests {
             test_swf(
                 concat!("tests/swfs/", $path, "/test.swf"),
                 $num_frames,
+                None,
+                concat!("tests/swfs/", $path, "/output.txt"),
+            )
+        }
+        )*
+    };
+}
+
+// This macro generates test cases for a given list of SWFs, with the clock advancing as if they
+// ran at the given frame rate instead of their own.
+macro_rules! swf_tests_timed {
+    ($($(#[$attr:meta])* ($name:ident, $path:expr, $num_frames:literal, $frame_rate:literal),)*) => {
+        $(
+        #[test]
+        $(#[$attr])*
+        fn $name() -> Result<(), Error> {
+            test_swf(
+                concat!("tests/swfs/", $path, "/test.swf"),
+                $num_frames,
+                Some($frame_rate),
                 concat!("tests/swfs/", $path, "/output.txt"),
             )
         }
@@ -50,16 +94,39 @@ macro_rules! swf_tests_approx {
     };
 }
 
+// This macro generates test cases for a given list of SWFs using `test_swf_no_errors`.
+macro_rules! swf_tests_no_errors {
+    ($($(#[$attr:meta])* ($name:ident, $path:expr, $num_frames:literal),)*) => {
+        $(
+        #[test]
+        $(#[$attr])*
+        fn $name() -> Result<(), Error> {
+            test_swf_no_errors(
+                concat!("tests/swfs/", $path, "/test.swf"),
+                $num_frames,
+                concat!("tests/swfs/", $path, "/output.txt"),
+            )
+        }
+        )*
+    };
+}
+
 // List of SWFs to test.
 // Format: (test_name, test_folder, number_of_frames_to_run)
 // The test folder is a relative to core/tests/swfs
 // Inside the folder is expected to be "test.swf" and "output.txt" with the correct output.
 swf_tests! {
     (add_property, "avm1/add_property", 1),
+    (arguments_object, "avm1/arguments_object", 1),
+    (button_track_as_menu, "avm1/button_track_as_menu", 1),
+    (math_swf4, "avm1/math_swf4", 1),
+    (math_swf8, "avm1/math_swf8", 1),
     (as_transformed_flag, "avm1/as_transformed_flag", 3),
     (attach_movie, "avm1/attach_movie", 1),
     (function_base_clip, "avm1/function_base_clip", 2),
     (call, "avm1/call", 2),
+    (call_targets, "avm1/call_targets", 3),
+    (call_missing_method_args, "avm1/call_missing_method_args", 1),
     (color, "avm1/color", 1),
     (clip_events, "avm1/clip_events", 4),
     (create_empty_movie_clip, "avm1/create_empty_movie_clip", 2),
@@ -83,6 +150,7 @@ swf_tests! {
     (goto_rewind1, "avm1/goto_rewind1", 4),
     (goto_rewind2, "avm1/goto_rewind2", 5),
     (goto_rewind3, "avm1/goto_rewind3", 2),
+    (place_object_move, "avm1/place_object_move", 10),
     (goto_execution_order, "avm1/goto_execution_order", 3),
     (goto_execution_order2, "avm1/goto_execution_order2", 2),
     (greaterthan_swf5, "avm1/greaterthan_swf5", 1),
@@ -110,6 +178,8 @@ swf_tests! {
     (movieclip_prototype_extension, "avm1/movieclip_prototype_extension", 1),
     (movieclip_hittest, "avm1/movieclip_hittest", 1),
     #[ignore] (textfield_text, "avm1/textfield_text", 1),
+    (textfield_replace_text, "avm1/textfield_replace_text", 1),
+    (textfield_html_text, "avm1/textfield_html_text", 1),
     (recursive_prototypes, "avm1/recursive_prototypes", 1),
     (stage_object_children, "avm1/stage_object_children", 2),
     (has_own_property, "avm1/has_own_property", 1),
@@ -123,6 +193,8 @@ swf_tests! {
     (logical_ops_swf4, "avm1/logical_ops_swf4", 1),
     (logical_ops_swf8, "avm1/logical_ops_swf8", 1),
     (movieclip_depth_methods, "avm1/movieclip_depth_methods", 3),
+    (static_text_records, "avm1/static_text_records", 1),
+    (mixed_display_object_depths, "avm1/mixed_display_object_depths", 1),
     (get_variable_in_scope, "avm1/get_variable_in_scope", 1),
     (movieclip_init_object, "avm1/movieclip_init_object", 1),
     (greater_swf6, "avm1/greater_swf6", 1),
@@ -167,13 +239,26 @@ swf_tests! {
     (undefined_to_string_swf6, "avm1/undefined_to_string_swf6", 1),
     (define_function2_preload, "avm1/define_function2_preload", 1),
     (define_function2_preload_order, "avm1/define_function2_preload_order", 1),
+    (define_font_1, "avm1/define_font_1", 1),
+    (blend_mode, "avm1/blend_mode", 1),
+    (nested_masks, "avm1/nested_masks", 1),
+    (offscreen_clips, "avm1/offscreen_clips", 1),
+    (gradient_spread, "avm1/gradient_spread", 1),
+    (twips_snapping, "avm1/twips_snapping", 1),
     (mcl_as_broadcaster, "avm1/mcl_as_broadcaster", 1),
+    (broadcaster_order, "avm1/broadcaster_order", 1),
     (loadmovie, "avm1/loadmovie", 2),
     (loadmovienum, "avm1/loadmovienum", 2),
     (loadmovie_method, "avm1/loadmovie_method", 2),
+    (load_movie_self, "avm1/load_movie_self", 4),
+    (load_movie_loop, "avm1/load_movie_loop", 40),
+    (global_per_movie, "avm1/global_per_movie", 4),
+    (attach_movie_export_collision, "avm1/attach_movie_export_collision", 4),
     (unloadmovie, "avm1/unloadmovie", 11),
     (unloadmovienum, "avm1/unloadmovienum", 11),
     (unloadmovie_method, "avm1/unloadmovie_method", 11),
+    (unload_timeline, "avm1/unload_timeline", 4),
+    (unload_remove_movie_clip, "avm1/unload_remove_movie_clip", 4),
     (mcl_loadclip, "avm1/mcl_loadclip", 11),
     (mcl_unloadclip, "avm1/mcl_unloadclip", 11),
     (mcl_getprogress, "avm1/mcl_getprogress", 6),
@@ -181,12 +266,15 @@ swf_tests! {
     (loadvariablesnum, "avm1/loadvariablesnum", 3),
     (loadvariables_method, "avm1/loadvariables_method", 3),
     (xml_load, "avm1/xml_load", 1),
+    (net_connection_call, "avm1/net_connection_call", 1),
     (cross_movie_root, "avm1/cross_movie_root", 5),
     (roots_and_levels, "avm1/roots_and_levels", 1),
     (swf6_case_insensitive, "avm1/swf6_case_insensitive", 1),
     (swf7_case_sensitive, "avm1/swf7_case_sensitive", 1),
-    (prototype_enumerate, "avm1/prototype_enumerate", 1),
-    (stage_object_enumerate, "avm1/stage_object_enumerate", 1),
+    (prototype_enumerate, "avm1/prototype_enumerate", 2),
+    (stage_object_enumerate, "avm1/stage_object_enumerate", 2),
+    (enumerate_swf4, "avm1/enumerate_swf4", 1),
+    (stage_object_property_writes, "avm1/stage_object_property_writes", 1),
     (as2_super_and_this_v6, "avm1/as2_super_and_this_v6", 1),
     (as2_super_and_this_v8, "avm1/as2_super_and_this_v8", 1),
     (as2_super_via_manual_prototype, "avm1/as2_super_via_manual_prototype", 1),
@@ -204,126 +292,2713 @@ swf_tests_approx! {
     (movieclip_getbounds, "avm1/movieclip_getbounds", 1, 0.051),
 }
 
-/// Wrapper around string slice that makes debug output `{:?}` to print string same way as `{}`.
-/// Used in different `assert*!` macros in combination with `pretty_assertions` crate to make
-/// test failures to show nice diffs.
-/// Courtesy of https://github.com/colin-kiegel/rust-pretty-assertions/issues/24
-#[derive(PartialEq, Eq)]
-#[doc(hidden)]
-pub struct PrettyString<'a>(pub &'a str);
-
-/// Make diff to display string as multi-line string
-impl<'a> std::fmt::Debug for PrettyString<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        f.write_str(self.0)
-    }
+// List of SWFs to test, which mustn't log any errors while they run.
+// Format: (test_name, test_folder, number_of_frames_to_run)
+swf_tests_no_errors! {
+    (stage_scale_mode, "avm1/stage_scale_mode", 1),
 }
 
-macro_rules! assert_eq {
-    ($left:expr, $right:expr) => {
-        pretty_assertions::assert_eq!(PrettyString($left.as_ref()), PrettyString($right.as_ref()));
-    };
-    ($left:expr, $right:expr, $message:expr) => {
-        pretty_assertions::assert_eq!(
-            PrettyString($left.as_ref()),
-            PrettyString($right.as_ref()),
-            $message
-        );
-    };
+// List of SWFs to test, with the clock advancing at the given frame rate.
+// Format: (test_name, test_folder, number_of_frames_to_run, frame_rate)
+swf_tests_timed! {
+    (get_timer, "avm1/get_timer", 4, 20.0),
 }
 
-/// Loads an SWF and runs it through the Ruffle core for a number of frames.
-/// Tests that the trace output matches the given expected output.
-fn test_swf(swf_path: &str, num_frames: u32, expected_output_path: &str) -> Result<(), Error> {
-    let expected_output = std::fs::read_to_string(expected_output_path)?.replace("\r\n", "\n");
-
-    let trace_log = run_swf(swf_path, num_frames)?;
+/// `logged_error` goes to a frame of a `tellTarget` target that doesn't exist, which logs an
+/// error without stopping the movie.
+#[test]
+fn logged_errors_are_captured() -> Result<(), Error> {
+    let (result, errors) =
+        capture_errors(|| run_swf("tests/swfs/avm1/logged_error/test.swf", 1, None));
     assert_eq!(
-        trace_log, expected_output,
-        "ruffle output != flash player output"
+        result?,
+        "Target not found: Target=\"missing\" Base=\"_level0\"\ndone\n"
     );
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].ends_with("GotoFrame failed: Invalid target"));
+
+    // Errors are only captured while asked to.
+    run_swf("tests/swfs/avm1/logged_error/test.swf", 1, None)?;
+    assert!(capture_errors(|| ()).1.is_empty());
 
     Ok(())
 }
 
-/// Loads an SWF and runs it through the Ruffle core for a number of frames.
-/// Tests that the trace output matches the given expected output.
-/// If a line has a floating point value, it will be compared approxinmately using the given epsilon.
-fn test_swf_approx(
-    swf_path: &str,
-    num_frames: u32,
-    expected_output_path: &str,
-    epsilon: f64,
-) -> Result<(), Error> {
-    let trace_log = run_swf(swf_path, num_frames)?;
-    let expected_data = std::fs::read_to_string(expected_output_path)?;
-    std::assert_eq!(
-        trace_log.lines().count(),
-        expected_data.lines().count(),
-        "# of lines of output didn't match"
+/// `call_tree` calls a recursive function of one parameter on even frames, and one of six
+/// parameters on odd frames, 255 times each frame. Each call pushes all of its arguments with
+/// one `Push` action, as reading a `Push` action allocates a list of its values.
+#[test]
+fn function_calls_do_not_copy_their_parameters() -> Result<(), Error> {
+    let (_executor, channel) = NullExecutor::new();
+    let movie = SwfMovie::from_path("tests/swfs/avm1/call_tree/test.swf")?;
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::with_base_path(
+            Path::new("tests/swfs/avm1/call_tree"),
+            channel,
+        )),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+
+    // The first frames define the functions and grow the stacks to their full size.
+    for _ in 0..3 {
+        player.lock().unwrap().run_frame();
+    }
+    let ((), one_parameter) = count_allocations(|| player.lock().unwrap().run_frame());
+    let ((), six_parameters) = count_allocations(|| player.lock().unwrap().run_frame());
+    player.lock().unwrap().run_frame();
+    assert_eq!(trace_log(&player), "done\n");
+
+    // Each call used to copy the name of every parameter of the function.
+    assert!(
+        six_parameters < one_parameter + 255,
+        "{} allocations with one parameter, {} with six",
+        one_parameter,
+        six_parameters
     );
+    Ok(())
+}
 
-    for (actual, expected) in trace_log.lines().zip(expected_data.lines()) {
-        // If these are numbers, compare using approx_eq.
-        if let (Ok(actual), Ok(expected)) = (actual.parse::<f64>(), expected.parse::<f64>()) {
-            // TODO: Lower this epsilon as the accuracy of the properties improves.
-            assert_abs_diff_eq!(actual, expected, epsilon = epsilon);
-        } else {
-            assert_eq!(actual, expected);
+/// `stage_scale_mode` sets `Stage.scaleMode` several times, ending with `noScale`.
+#[test]
+fn stage_scale_mode_is_set_on_the_player() -> Result<(), Error> {
+    let (_executor, channel) = NullExecutor::new();
+    let movie = SwfMovie::from_path("tests/swfs/avm1/stage_scale_mode/test.swf")?;
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::with_base_path(
+            Path::new("tests/swfs/avm1/stage_scale_mode"),
+            channel,
+        )),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+    let mut player = player.lock().unwrap();
+    assert_eq!(player.scale_mode(), StageScaleMode::ShowAll);
+
+    player.run_frame();
+    assert_eq!(player.scale_mode(), StageScaleMode::NoScale);
+
+    Ok(())
+}
+
+#[test]
+fn host_volume_is_independent_of_script() -> Result<(), Error> {
+    let audio = NullAudioBackend::new();
+    let monitor = audio.monitor();
+    let (_executor, channel) = NullExecutor::new();
+    let movie = SwfMovie::from_path("tests/swfs/avm1/single_frame/test.swf")?;
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(audio),
+        Box::new(NullNavigatorBackend::with_base_path(
+            Path::new("tests/swfs/avm1/single_frame"),
+            channel,
+        )),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+    let mut player = player.lock().unwrap();
+
+    player.set_volume(0.5);
+    assert_abs_diff_eq!(monitor.global_volume(), 0.5);
+
+    player.set_mute(true);
+    assert_abs_diff_eq!(monitor.global_volume(), 0.0);
+    assert_abs_diff_eq!(player.volume(), 0.5);
+
+    player.set_mute(false);
+    assert_abs_diff_eq!(monitor.global_volume(), 0.5);
+
+    Ok(())
+}
+
+#[test]
+fn players_running_side_by_side_have_separate_trace_output() -> Result<(), Error> {
+    // Each movie runs in its own player, with its own executor, for its own number of frames.
+    let tests = [("avm1/clip_events", 4), ("avm1/execution_order1", 3)];
+    let mut instances = vec![];
+    for (path, _) in &tests {
+        let (executor, channel) = NullExecutor::new();
+        let movie = SwfMovie::from_path(format!("tests/swfs/{}/test.swf", path))?;
+        let player = Player::new(
+            Box::new(NullRenderer),
+            Box::new(NullAudioBackend::new()),
+            Box::new(NullNavigatorBackend::with_base_path(
+                Path::new("tests/swfs").join(path),
+                channel,
+            )),
+            Box::new(NullInputBackend::new()),
+            Box::new(NullUiBackend::new()),
+            Box::new(TestLogBackend::default()),
+            Box::new(MemoryStorageBackend::new()),
+            movie,
+        )?;
+        instances.push((player, executor));
+    }
+
+    // Interleave the frames of both movies, like a host running several windows would.
+    for frame in 0..4 {
+        for ((player, executor), (_, num_frames)) in instances.iter_mut().zip(tests.iter()) {
+            if frame < *num_frames {
+                player.lock().unwrap().run_frame();
+                executor.poll_all().unwrap();
+            }
         }
     }
+
+    for ((player, executor), (path, _)) in instances.iter_mut().zip(tests.iter()) {
+        executor.block_all().unwrap();
+        let expected_output = std::fs::read_to_string(format!("tests/swfs/{}/output.txt", path))?
+            .replace("\r\n", "\n");
+        assert_eq!(
+            trace_log(player),
+            expected_output,
+            "ruffle output != flash player output"
+        );
+    }
+
     Ok(())
 }
 
-/// Loads an SWF and runs it through the Ruffle core for a number of frames.
-/// Tests that the trace output matches the given expected output.
-fn run_swf(swf_path: &str, num_frames: u32) -> Result<String, Error> {
-    let _ = log::set_logger(&TRACE_LOGGER).map(|()| log::set_max_level(log::LevelFilter::Info));
+#[test]
+fn set_root_movie_resolves_relative_loads_next_to_new_movie() -> Result<(), Error> {
+    let (mut executor, channel) = NullExecutor::new();
+    let movie = SwfMovie::from_path("tests/swfs/avm1/single_frame/test.swf")?;
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::with_base_path(
+            Path::new("tests/swfs/avm1/single_frame"),
+            channel,
+        )),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
 
-    let base_path = Path::new(swf_path).parent().unwrap();
+    // Each of these movies loads a `target.swf` that only exists in its own directory.
+    for path in &["avm1/loadmovie", "avm1/loadmovienum"] {
+        let movie = SwfMovie::from_path(format!("tests/swfs/{}/test.swf", path))?;
+        let expected_output = std::fs::read_to_string(format!("tests/swfs/{}/output.txt", path))?
+            .replace("\r\n", "\n");
+        let log_start = trace_log(&player).len();
+
+        player.lock().unwrap().set_root_movie(Arc::new(movie));
+        for _ in 0..2 {
+            player.lock().unwrap().run_frame();
+            executor.poll_all().unwrap();
+        }
+        executor.block_all().unwrap();
+
+        assert_eq!(
+            &trace_log(&player)[log_start..],
+            expected_output,
+            "ruffle output != flash player output"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn spoofed_url_and_rewritten_fetches() -> Result<(), Error> {
     let (mut executor, channel) = NullExecutor::new();
-    let movie = SwfMovie::from_path(swf_path)?;
+    let movie = SwfMovie::from_path("tests/swfs/avm1/url_rewrite/test.swf")?;
     let player = Player::new(
         Box::new(NullRenderer),
         Box::new(NullAudioBackend::new()),
-        Box::new(NullNavigatorBackend::with_base_path(base_path, channel)),
+        Box::new(NullNavigatorBackend::with_base_path(
+            Path::new("tests/swfs/avm1/url_rewrite"),
+            channel,
+        )),
         Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
         movie,
     )?;
 
-    for _ in 0..num_frames {
+    // The movie only runs on its original site, loads variables from it, and pings a tracker.
+    player
+        .lock()
+        .unwrap()
+        .set_spoofed_url(Some("http://www.example.com/games/test.swf".to_string()));
+    player.lock().unwrap().set_url_rewrite_rules(
+        UrlRewriteRules::new()
+            .deny("http://tracker.example.net/")
+            .redirect("http://www.example.com/", ""),
+    );
+
+    for _ in 0..2 {
         player.lock().unwrap().run_frame();
         executor.poll_all().unwrap();
     }
+    executor.block_all().unwrap();
+
+    let expected_output =
+        std::fs::read_to_string("tests/swfs/avm1/url_rewrite/output.txt")?.replace("\r\n", "\n");
+    assert_eq!(
+        trace_log(&player),
+        expected_output,
+        "ruffle output != flash player output"
+    );
+
+    Ok(())
+}
 
+#[test]
+fn traces_from_loaded_movies_have_their_context() -> Result<(), Error> {
+    let (mut executor, channel) = NullExecutor::new();
+    let movie = SwfMovie::from_path("tests/swfs/avm1/loadmovie/test.swf")?;
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::with_base_path(
+            Path::new("tests/swfs/avm1/loadmovie"),
+            channel,
+        )),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+    for _ in 0..2 {
+        player.lock().unwrap().run_frame();
+        executor.poll_all().unwrap();
+    }
     executor.block_all().unwrap();
 
-    Ok(trace_log())
+    let player = player.lock().unwrap();
+    let log = player.log().downcast_ref::<TestLogBackend>().unwrap();
+    assert_eq!(log.trace_output, "Loading movie\nChild movie loaded!\n");
+
+    // The root movie traces from its own timeline, and the loaded movie from the clip it was
+    // loaded into.
+    let contexts = &log.trace_contexts;
+    assert_eq!(contexts.len(), 2);
+    assert_eq!(contexts[0].target_path, "_level0");
+    assert_eq!(contexts[0].frame, Some(1));
+    assert!(contexts[0].movie_url.as_deref().map_or(false, |url| {
+        url.starts_with("file:///") && url.ends_with("/tests/swfs/avm1/loadmovie/test.swf")
+    }));
+    assert_eq!(contexts[1].target_path, "_level0.clip");
+    assert_eq!(contexts[1].frame, Some(1));
+    assert!(contexts[1]
+        .movie_url
+        .as_deref()
+        .map_or(false, |url| url.ends_with("target.swf")));
+
+    Ok(())
 }
 
-thread_local! {
-    static TRACE_LOG: RefCell<String> = RefCell::new(String::new());
+#[test]
+fn projector_plays_embedded_movie() -> Result<(), Error> {
+    // A stand-in for a projector executable, with `avm1/loadmovie/test.swf` appended to it.
+    // The movie loads a `target.swf` that is next to the executable.
+    let path = "tests/swfs/avm1/projector/projector.exe";
+    let movie = SwfMovie::from_path(path)?;
+    assert!(movie.url().map_or(false, |url| url.starts_with("file:///")
+        && url.ends_with(path)));
+    assert_eq!(
+        movie.data(),
+        SwfMovie::from_path("tests/swfs/avm1/loadmovie/test.swf")?.data()
+    );
+
+    let (mut executor, channel) = NullExecutor::new();
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::with_base_path(
+            Path::new(path).parent().unwrap(),
+            channel,
+        )),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+    for _ in 0..2 {
+        player.lock().unwrap().run_frame();
+        executor.poll_all().unwrap();
+    }
+    executor.block_all().unwrap();
+
+    let expected_output =
+        std::fs::read_to_string("tests/swfs/avm1/projector/output.txt")?.replace("\r\n", "\n");
+    assert_eq!(trace_log(&player), expected_output);
+
+    Ok(())
 }
 
-static TRACE_LOGGER: TraceLogger = TraceLogger;
+#[test]
+fn load_state_replays_from_saved_frame() -> Result<(), Error> {
+    let (mut executor, channel) = NullExecutor::new();
+    let movie = SwfMovie::from_path("tests/swfs/avm1/save_state/test.swf")?;
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::with_base_path(
+            Path::new("tests/swfs/avm1/save_state"),
+            channel,
+        )),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+    let mut run_frames = |num_frames| {
+        let log_start = trace_log(&player).len();
+        for _ in 0..num_frames {
+            player.lock().unwrap().run_frame();
+            executor.poll_all().unwrap();
+        }
+        trace_log(&player)[log_start..].to_string()
+    };
+
+    // Each frame traces a counter, a closure, a random number, both timelines, an object built up
+    // by the child clip, a class instance, a property added to `Math` and an array.
+    run_frames(10);
+    let state = player.lock().unwrap().save_state()?;
+    let expected_output = run_frames(10);
+    player.lock().unwrap().load_state(&state)?;
+    let output = run_frames(10);
 
-/// `TraceLogger` captures output from AVM trace actions into a String.
-struct TraceLogger;
+    assert!(!expected_output.is_empty());
+    assert_eq!(output, expected_output);
 
-fn trace_log() -> String {
-    TRACE_LOG.with(|log| log.borrow().clone())
+    Ok(())
 }
 
-impl log::Log for TraceLogger {
-    fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.target() == "avm_trace"
+#[test]
+fn key_codes_follow_keyboard_layout() -> Result<(), Error> {
+    let (mut executor, channel) = NullExecutor::new();
+    let movie = SwfMovie::from_path("tests/swfs/avm1/key_codes/test.swf")?;
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::with_base_path(
+            Path::new("tests/swfs/avm1/key_codes"),
+            channel,
+        )),
+        Box::new(KeyboardInputBackend::default()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+    let expected_output =
+        std::fs::read_to_string("tests/swfs/avm1/key_codes/output.txt")?.replace("\r\n", "\n");
+
+    // Each frame traces `Key.getCode()`, `Key.getAscii()`, `Key.isDown(65)` and
+    // `Key.isDown(37)` after the given keys are pressed.
+    let presses: &[&[(KeyCode, Option<char>)]] = &[
+        // 'A' on a US keyboard.
+        &[(KeyCode::A, Some('a'))],
+        // 'A' on an AZERTY keyboard, in the US 'Q' position.
+        &[(KeyCode::Q, Some('a'))],
+        &[(KeyCode::Left, None)],
+        &[(KeyCode::Numpad1, Some('1'))],
+        // Shift+1 on a US keyboard.
+        &[(KeyCode::Shift, None), (KeyCode::Key1, Some('!'))],
+        // The 1 key on an AZERTY keyboard.
+        &[(KeyCode::Key1, Some('&'))],
+        &[(KeyCode::Return, None)],
+    ];
+    for keys in presses {
+        {
+            let mut player = player.lock().unwrap();
+            let input = player
+                .input_mut()
+                .downcast_mut::<KeyboardInputBackend>()
+                .unwrap();
+            input.release_all();
+            let events: Vec<_> = keys
+                .iter()
+                .map(|&(positional, character)| input.press(positional, character))
+                .collect();
+            for key_code in events {
+                player.handle_event(PlayerEvent::KeyDown { key_code });
+            }
+            player.run_frame();
+        }
+        executor.poll_all().unwrap();
     }
 
-    fn log(&self, record: &Record) {
-        if self.enabled(record.metadata()) {
-            TRACE_LOG.with(|log| log.borrow_mut().push_str(&format!("{}\n", record.args())));
+    assert_eq!(
+        trace_log(&player),
+        expected_output,
+        "ruffle output != flash player output"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn key_toggled_state_is_inferred_from_key_events() -> Result<(), Error> {
+    let (mut executor, channel) = NullExecutor::new();
+    let movie = SwfMovie::from_path("tests/swfs/avm1/key_toggled/test.swf")?;
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::with_base_path(
+            Path::new("tests/swfs/avm1/key_toggled"),
+            channel,
+        )),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+    let expected_output =
+        std::fs::read_to_string("tests/swfs/avm1/key_toggled/output.txt")?.replace("\r\n", "\n");
+
+    // Each frame traces `Key.isToggled(20)`, `Key.isToggled(144)`, `Key.isDown(16)` and
+    // `Key.isDown(17)` after the given events.
+    let down = |key_code| PlayerEvent::KeyDown { key_code };
+    let up = |key_code| PlayerEvent::KeyUp { key_code };
+    let frames = vec![
+        vec![],
+        vec![
+            down(KeyCode::Shift),
+            down(KeyCode::CapsLock),
+            up(KeyCode::CapsLock),
+        ],
+        vec![
+            up(KeyCode::Shift),
+            down(KeyCode::NumLock),
+            up(KeyCode::NumLock),
+            down(KeyCode::Control),
+        ],
+        vec![
+            up(KeyCode::Control),
+            down(KeyCode::CapsLock),
+            up(KeyCode::CapsLock),
+        ],
+    ];
+    for events in frames {
+        {
+            let mut player = player.lock().unwrap();
+            for event in events {
+                player.handle_event(event);
+            }
+            player.run_frame();
         }
+        executor.poll_all().unwrap();
     }
 
-    fn flush(&self) {}
+    assert_eq!(
+        trace_log(&player),
+        expected_output,
+        "ruffle output != flash player output"
+    );
+
+    Ok(())
+}
+
+/// Runs the script timeout test, answering each timeout prompt with the given choice.
+///
+/// Returns the trace output and the messages shown to the user.
+fn run_script_timeout(choice: usize) -> Result<(String, Vec<(MessageLevel, String)>), Error> {
+    let messages = Rc::new(RefCell::new(vec![]));
+    let (mut executor, channel) = NullExecutor::new();
+    let movie = SwfMovie::from_path("tests/swfs/avm1/script_timeout/test.swf")?;
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(ClockNavigatorBackend {
+            inner: NullNavigatorBackend::with_base_path(
+                Path::new("tests/swfs/avm1/script_timeout"),
+                channel,
+            ),
+            time: Duration::from_millis(0),
+        }),
+        Box::new(NullInputBackend::new()),
+        Box::new(ScriptedUiBackend {
+            choice: Some(choice),
+            messages: messages.clone(),
+        }),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+    player
+        .lock()
+        .unwrap()
+        .set_max_execution_duration(Duration::from_millis(100));
+
+    // The first frame traces "start", loops for longer than the timeout, then traces
+    // "loop done". The second frame traces "frame 2".
+    for _ in 0..2 {
+        player.lock().unwrap().run_frame();
+        executor.block_all().unwrap();
+    }
+
+    let messages = messages.borrow().clone();
+    Ok((trace_log(&player), messages))
+}
+
+#[test]
+fn script_timeout_continue() -> Result<(), Error> {
+    let (output, messages) = run_script_timeout(0)?;
+
+    assert_eq!(output, "start\nloop done\nframe 2\n");
+    assert!(!messages.is_empty());
+    assert!(messages
+        .iter()
+        .all(|(level, _)| *level == MessageLevel::Warning));
+
+    Ok(())
+}
+
+#[test]
+fn script_timeout_abort() -> Result<(), Error> {
+    let (output, messages) = run_script_timeout(1)?;
+
+    assert_eq!(output, "start\n");
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].0, MessageLevel::Warning);
+
+    Ok(())
+}
+
+/// Runs a movie that traces `getTimer()` on every frame, and suspends it for 6 seconds as if a
+/// modal dialog was shown.
+///
+/// Returns the traced times from before the movie was suspended, and from after it resumed.
+fn run_suspended(timer_runs_while_suspended: bool) -> Result<(Vec<u32>, Vec<u32>), Error> {
+    let time = Rc::new(Cell::new(Duration::from_millis(0)));
+    let (mut executor, channel) = NullExecutor::new();
+    let movie = SwfMovie::from_path("tests/swfs/avm1/suspend/test.swf")?;
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(ManualClockNavigatorBackend {
+            inner: NullNavigatorBackend::with_base_path(
+                Path::new("tests/swfs/avm1/suspend"),
+                channel,
+            ),
+            time: time.clone(),
+        }),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+    player.lock().unwrap().set_is_playing(true);
+    player
+        .lock()
+        .unwrap()
+        .set_timer_runs_while_suspended(timer_runs_while_suspended);
+
+    // The movie runs at 24 frames per second, so each tick runs at least one frame.
+    let mut run_ticks = |num_ticks| -> Result<Vec<u32>, Error> {
+        let log_start = trace_log(&player).len();
+        for _ in 0..num_ticks {
+            time.set(time.get() + Duration::from_millis(50));
+            player.lock().unwrap().tick(50.0);
+            executor.poll_all()?;
+        }
+        let times = trace_log(&player)[log_start..]
+            .lines()
+            .map(|line| line.parse())
+            .collect::<Result<_, _>>()?;
+        Ok(times)
+    };
+
+    let before = run_ticks(10)?;
+    player.lock().unwrap().suspend();
+    assert!(player.lock().unwrap().is_suspended());
+    assert!(run_ticks(120)?.is_empty());
+    player.lock().unwrap().resume();
+    assert!(!player.lock().unwrap().is_suspended());
+    let after = run_ticks(1)?;
+
+    Ok((before, after))
+}
+
+#[test]
+fn suspended_movie_does_not_catch_up() -> Result<(), Error> {
+    let (before, after) = run_suspended(false)?;
+    assert!(!before.is_empty());
+
+    // Only one frame runs after resuming, and the timer ignores the time spent suspended.
+    assert_eq!(after, vec![before.last().unwrap() + 50]);
+
+    Ok(())
+}
+
+#[test]
+fn suspended_movie_timer_can_keep_running() -> Result<(), Error> {
+    let (before, after) = run_suspended(true)?;
+    assert!(!before.is_empty());
+
+    // Still only one frame runs after resuming, but the timer includes the time spent suspended.
+    assert_eq!(after, vec![before.last().unwrap() + 50 + 120 * 50]);
+
+    Ok(())
+}
+
+#[test]
+fn shutdown_cancels_spawned_futures() -> Result<(), Error> {
+    let spawned = Rc::new(Cell::new(0));
+    let polls = Rc::new(Cell::new(0));
+    let cancelled = Rc::new(Cell::new(0));
+    let (mut executor, channel) = NullExecutor::new();
+    let movie = SwfMovie::from_path("tests/swfs/avm1/loadmovie/test.swf")?;
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(StalledNavigatorBackend {
+            inner: NullNavigatorBackend::with_base_path(
+                Path::new("tests/swfs/avm1/loadmovie"),
+                channel,
+            ),
+            spawned: spawned.clone(),
+            polls: polls.clone(),
+            cancelled: cancelled.clone(),
+        }),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+
+    // The movie starts loading its child, which never arrives.
+    for _ in 0..3 {
+        player.lock().unwrap().run_frame();
+        executor.poll_all()?;
+    }
+    assert!(spawned.get() > 0);
+    assert!(polls.get() >= spawned.get());
+    assert!(executor.has_work());
+    assert_eq!(cancelled.get(), 0);
+
+    player.lock().unwrap().shutdown();
+    assert!(player.lock().unwrap().is_shut_down());
+    let polls_at_shutdown = polls.get();
+
+    // Every future was cancelled, and none of them runs again.
+    for _ in 0..3 {
+        player.lock().unwrap().run_frame();
+        player.lock().unwrap().tick(1000.0);
+        executor.poll_all()?;
+    }
+    assert!(!executor.has_work());
+    assert_eq!(polls.get(), polls_at_shutdown);
+    assert_eq!(cancelled.get(), spawned.get());
+    assert_eq!(trace_log(&player), "Loading movie\n");
+
+    // Dropping the player shuts it down again, which does nothing.
+    drop(player);
+    assert_eq!(cancelled.get(), spawned.get());
+
+    Ok(())
+}
+
+#[test]
+fn unloading_a_clip_cancels_its_load() -> Result<(), Error> {
+    let spawned = Rc::new(Cell::new(0));
+    let polls = Rc::new(Cell::new(0));
+    let cancelled = Rc::new(Cell::new(0));
+    let (mut executor, channel) = NullExecutor::new();
+    let movie = SwfMovie::from_path("tests/swfs/avm1/unload_during_load/test.swf")?;
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(StalledNavigatorBackend {
+            inner: NullNavigatorBackend::with_base_path(
+                Path::new("tests/swfs/avm1/unload_during_load"),
+                channel,
+            ),
+            spawned: spawned.clone(),
+            polls: polls.clone(),
+            cancelled: cancelled.clone(),
+        }),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+
+    // The movie starts loading into `clip`, which never arrives.
+    for _ in 0..2 {
+        player.lock().unwrap().run_frame();
+        executor.poll_all()?;
+    }
+    assert_eq!(spawned.get(), 1);
+    assert!(executor.has_work());
+    assert_eq!(cancelled.get(), 0);
+
+    // `clip.unloadMovie()` cancels the load, which is not polled again.
+    player.lock().unwrap().run_frame();
+    let polls_at_unload = polls.get();
+    for _ in 0..3 {
+        player.lock().unwrap().run_frame();
+        executor.poll_all()?;
+    }
+    assert!(!executor.has_work());
+    assert_eq!(polls.get(), polls_at_unload);
+    assert_eq!(cancelled.get(), 1);
+    assert_eq!(trace_log(&player), "Loading movie\nUnloading movie\n");
+
+    Ok(())
+}
+
+/// Runs a movie from the `avm2` test directory for the given number of frames.
+///
+/// Returns the movie's script kind, the trace output and the messages shown to the user.
+fn run_script_kind_movie(
+    name: &str,
+    num_frames: u32,
+) -> Result<(ScriptKind, String, Vec<(MessageLevel, String)>), Error> {
+    let base_path = Path::new("tests/swfs/avm2").join(name);
+    let messages = Rc::new(RefCell::new(vec![]));
+    let (mut executor, channel) = NullExecutor::new();
+    let movie = SwfMovie::from_path(base_path.join("test.swf"))?;
+    let script_kind = movie.script_kind();
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::with_base_path(&base_path, channel)),
+        Box::new(NullInputBackend::new()),
+        Box::new(ScriptedUiBackend {
+            choice: None,
+            messages: messages.clone(),
+        }),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+
+    for _ in 0..num_frames {
+        player.lock().unwrap().run_frame();
+        executor.poll_all().unwrap();
+    }
+
+    let messages = messages.borrow().clone();
+    Ok((script_kind, trace_log(&player), messages))
+}
+
+#[test]
+fn avm1_movie_runs_without_diagnostics() -> Result<(), Error> {
+    let (script_kind, output, messages) = run_script_kind_movie("script_kind_avm1", 2)?;
+
+    assert_eq!(script_kind, ScriptKind::Avm1);
+    assert_eq!(output, "frame 1\nframe 2\n");
+    assert!(messages.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn hybrid_movie_runs_avm1_scripts_and_skips_abc() -> Result<(), Error> {
+    let (script_kind, output, messages) = run_script_kind_movie("script_kind_hybrid", 2)?;
+
+    assert_eq!(script_kind, ScriptKind::Hybrid);
+    assert_eq!(output, "frame 1\nframe 2\n");
+    assert!(messages.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn avm2_movie_shows_first_frame_without_scripts() -> Result<(), Error> {
+    // The movie has a trace action on each frame, none of which should run.
+    let (script_kind, output, messages) = run_script_kind_movie("script_kind_avm2", 3)?;
+
+    assert_eq!(script_kind, ScriptKind::Avm2);
+    assert_eq!(output, "");
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].0, MessageLevel::Warning);
+    assert!(messages[0].1.contains("ActionScript 3"));
+
+    Ok(())
+}
+
+#[test]
+fn reloading_movie_keeps_render_resource_counts_flat() -> Result<(), Error> {
+    let data = std::fs::read("tests/swfs/avm1/unload_render_resources/test.swf")?;
+    let stats = Rc::new(RefCell::new(RenderResourceStats::default()));
+    let player = Player::new(
+        Box::new(CountingRenderer {
+            stats: stats.clone(),
+        }),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::new()),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        SwfMovie::from_data(&data, None)?,
+    )?;
+    player.lock().unwrap().run_frame();
+    // The device font's glyphs are registered alongside the movie's two shapes.
+    let loaded = stats.borrow().clone();
+    assert_eq!(loaded.live_bitmaps.len(), 1);
+
+    for _ in 0..100 {
+        let mut player = player.lock().unwrap();
+        player.set_root_movie(Arc::new(SwfMovie::from_data(&data, None)?));
+        // Resources of the old movie are released a frame after it becomes unreachable.
+        for _ in 0..3 {
+            player.run_frame();
+            player.render();
+        }
+        let stats = stats.borrow();
+        assert_eq!(stats.live_shapes.len(), loaded.live_shapes.len());
+        assert_eq!(stats.live_bitmaps.len(), loaded.live_bitmaps.len());
+    }
+
+    assert_eq!(
+        stats.borrow().registered_shapes,
+        loaded.registered_shapes + 100 * 2
+    );
+    assert_eq!(trace_log(&player), "loaded\n".repeat(101));
+
+    Ok(())
+}
+
+#[test]
+fn lost_renderer_is_reported_once() -> Result<(), Error> {
+    let stats = Rc::new(RefCell::new(RenderResourceStats::default()));
+    let messages = Rc::new(RefCell::new(vec![]));
+    let (mut executor, channel) = NullExecutor::new();
+    let player = Player::new(
+        Box::new(CountingRenderer {
+            stats: stats.clone(),
+        }),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::with_base_path(
+            Path::new("tests/swfs/avm1/offscreen_clips"),
+            channel,
+        )),
+        Box::new(NullInputBackend::new()),
+        Box::new(ScriptedUiBackend {
+            choice: None,
+            messages: messages.clone(),
+        }),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        SwfMovie::from_path("tests/swfs/avm1/offscreen_clips/test.swf")?,
+    )?;
+    let mut player = player.lock().unwrap();
+    player.run_frame();
+    player.force_render();
+    assert!(messages.borrow().is_empty());
+
+    stats.borrow_mut().lost = true;
+    for _ in 0..3 {
+        player.force_render();
+    }
+    executor.block_all().unwrap();
+
+    let messages = messages.borrow();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].0, MessageLevel::Error);
+    Ok(())
+}
+
+/// Renders a frame of the movie with 10000 clips off the left edge of the stage and one on it.
+///
+/// Returns the number of shapes drawn and blend modes pushed.
+fn render_offscreen_clips(cull_offscreen_objects: bool) -> Result<(usize, usize), Error> {
+    let stats = Rc::new(RefCell::new(RenderResourceStats::default()));
+    let player = Player::new(
+        Box::new(CountingRenderer {
+            stats: stats.clone(),
+        }),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::new()),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        SwfMovie::from_path("tests/swfs/avm1/offscreen_clips/test.swf")?,
+    )?;
+    let mut player = player.lock().unwrap();
+    player.set_cull_offscreen_objects(cull_offscreen_objects);
+    player.run_frame();
+    player.render();
+
+    let stats = stats.borrow();
+    Ok((stats.rendered_shapes, stats.pushed_blend_modes))
+}
+
+#[test]
+fn offscreen_clips_are_culled() -> Result<(), Error> {
+    // Each off-screen clip has a blend mode, which is only pushed if the clip isn't culled.
+    assert_eq!(render_offscreen_clips(true)?, (1, 0));
+    assert_eq!(render_offscreen_clips(false)?, (1, 10000));
+    Ok(())
+}
+
+#[test]
+fn registered_fonts_are_used_by_text_fields() -> Result<(), Error> {
+    let stats = Rc::new(RefCell::new(RenderResourceStats::default()));
+    let player = Player::new(
+        Box::new(CountingRenderer {
+            stats: stats.clone(),
+        }),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::new()),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        SwfMovie::from_path("tests/swfs/avm1/registered_font/test.swf")?,
+    )?;
+
+    {
+        let mut player = player.lock().unwrap();
+        assert!(player
+            .register_font_from_bytes("Not A Font", b"GIF89a")
+            .is_err());
+
+        // The test font has glyphs for "H", "I", "O" and the space.
+        let registered_shapes = stats.borrow().registered_shapes;
+        let font_data = std::fs::read("tests/swfs/avm1/registered_font/test.ttf")?;
+        player.register_font_from_bytes("Registered Test Font", &font_data)?;
+        assert_eq!(stats.borrow().registered_shapes, registered_shapes + 4);
+
+        player.run_frame();
+        player.render();
+    }
+
+    // The widths come from the advances and kerning of the test font.
+    let expected_output = std::fs::read_to_string("tests/swfs/avm1/registered_font/output.txt")?
+        .replace("\r\n", "\n");
+    assert_eq!(trace_log(&player), expected_output);
+
+    // The text field's last text, "I I", is drawn with a glyph for each character.
+    assert_eq!(stats.borrow().rendered_shapes, 3);
+    Ok(())
+}
+
+#[test]
+fn bidirectional_text_is_reordered() -> Result<(), Error> {
+    let stats = Rc::new(RefCell::new(RenderResourceStats::default()));
+    let player = Player::new(
+        Box::new(CountingRenderer {
+            stats: stats.clone(),
+        }),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::new()),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        SwfMovie::from_path("tests/swfs/avm1/bidi_text/test.swf")?,
+    )?;
+
+    {
+        let mut player = player.lock().unwrap();
+        let font_data = std::fs::read("tests/swfs/avm1/bidi_text/test.ttf")?;
+        player.register_font_from_bytes("Bidi Test Font", &font_data)?;
+
+        player.run_frame();
+        player.render();
+    }
+
+    // Each glyph of the test font has its own advance, so a text width, which ends at the
+    // origin of the last glyph drawn, tells which character is drawn last.
+    let expected_output =
+        std::fs::read_to_string("tests/swfs/avm1/bidi_text/output.txt")?.replace("\r\n", "\n");
+    assert_eq!(trace_log(&player), expected_output);
+
+    // Only the right-aligned text field is drawn. Its left-to-right line "A \u{5D1}" starts at
+    // the left edge, while its right-to-left line "\u{5D1} A" is drawn as "A \u{5D1}" ending at
+    // the right edge, 4000 twips in. The glyphs are 144, 60 and 132 twips wide.
+    let glyph_positions: Vec<(i32, i32)> = stats
+        .borrow()
+        .rendered_shape_transforms
+        .iter()
+        .map(|transform| (transform.matrix.tx.get(), transform.matrix.ty.get()))
+        .collect();
+    assert_eq!(
+        glyph_positions,
+        vec![
+            (0, 240),
+            (144, 240),
+            (204, 240),
+            (3664, 480),
+            (3808, 480),
+            (3868, 480)
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn attached_bitmaps_snap_to_whole_pixels() -> Result<(), Error> {
+    let stats = Rc::new(RefCell::new(RenderResourceStats::default()));
+    let player = Player::new(
+        Box::new(CountingRenderer {
+            stats: stats.clone(),
+        }),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::new()),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        SwfMovie::from_path("tests/swfs/avm1/attach_bitmap/test.swf")?,
+    )?;
+    {
+        let mut player = player.lock().unwrap();
+        player.run_frame();
+        player.render();
+    }
+
+    let expected_output =
+        std::fs::read_to_string("tests/swfs/avm1/attach_bitmap/output.txt")?.replace("\r\n", "\n");
+    assert_eq!(trace_log(&player), expected_output);
+
+    // Both clips are at (10.25, 5.5). The bitmap attached with "never" is drawn there, while the
+    // untransformed bitmap attached with the default "auto" snaps to (10, 6).
+    assert_eq!(
+        stats.borrow().rendered_bitmaps,
+        vec![
+            (Twips::new(205), Twips::new(110), true),
+            (Twips::new(200), Twips::new(120), false),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn static_text_records_carry_their_style_over() -> Result<(), Error> {
+    let stats = Rc::new(RefCell::new(RenderResourceStats::default()));
+    let player = Player::new(
+        Box::new(CountingRenderer {
+            stats: stats.clone(),
+        }),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::new()),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        SwfMovie::from_path("tests/swfs/avm1/static_text_records/test.swf")?,
+    )?;
+    {
+        let mut player = player.lock().unwrap();
+        player.run_frame();
+        player.render();
+    }
+
+    let expected_output =
+        std::fs::read_to_string("tests/swfs/avm1/static_text_records/output.txt")?
+            .replace("\r\n", "\n");
+    assert_eq!(trace_log(&player), expected_output);
+
+    // The text is at (45, 70): a clip at (40, 60) and the text matrix's (5, 10). The first two
+    // records use a DefineFont glyph square of 1024 units at 20px, the last two a DefineFont3
+    // one of 20480 units at 10px. Offsets move the pen to absolute positions.
+    let glyphs: Vec<_> = stats
+        .borrow()
+        .rendered_shape_transforms
+        .iter()
+        .map(|transform| {
+            let (matrix, color) = (transform.matrix, transform.color_transform);
+            (
+                matrix.tx.get(),
+                matrix.ty.get(),
+                matrix.a,
+                (color.r_mult, color.g_mult, color.b_mult),
+            )
+        })
+        .collect();
+    let (red, blue, green) = ((1.0, 0.0, 0.0), (0.0, 0.0, 1.0), (0.0, 128.0 / 255.0, 0.0));
+    assert_eq!(
+        glyphs,
+        vec![
+            (900, 1800, 400.0 / 1024.0, red),
+            (1300, 1800, 400.0 / 1024.0, red),
+            (1700, 1800, 400.0 / 1024.0, blue),
+            (1000, 2200, 200.0 / 20480.0, blue),
+            (1200, 2200, 200.0 / 20480.0, blue),
+            (1400, 2400, 200.0 / 20480.0, green),
+        ]
+    );
+    Ok(())
+}
+
+/// Moves the mouse over a movie a few times, rendering whenever the player asks to, like the
+/// desktop and web event loops do.
+///
+/// Returns the trace output and the number of frames rendered.
+fn run_mouse_moves(path: &str) -> Result<(String, usize), Error> {
+    let stats = Rc::new(RefCell::new(RenderResourceStats::default()));
+    let player = Player::new(
+        Box::new(CountingRenderer {
+            stats: stats.clone(),
+        }),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::new()),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        SwfMovie::from_path(Path::new("tests/swfs").join(path).join("test.swf"))?,
+    )?;
+
+    {
+        let mut player = player.lock().unwrap();
+        player.run_frame();
+        player.render();
+        for i in 0..5 {
+            player.handle_event(PlayerEvent::MouseMove {
+                x: 10.0 * f64::from(i),
+                y: 10.0,
+            });
+            if player.needs_render() {
+                player.render();
+            }
+        }
+    }
+
+    let rendered_frames = stats.borrow().rendered_frames;
+    Ok((trace_log(&player), rendered_frames))
+}
+
+#[test]
+fn update_after_event_renders_between_frames() -> Result<(), Error> {
+    // Redrawing doesn't run `onEnterFrame` or advance the timeline.
+    let (output, rendered_frames) = run_mouse_moves("avm1/update_after_event")?;
+    assert_eq!(output, "mouse move on frame 1\n".repeat(5));
+    assert_eq!(rendered_frames, 1 + 5);
+
+    let (output, rendered_frames) = run_mouse_moves("avm1/update_after_event_missing")?;
+    assert_eq!(output, "mouse move on frame 1\n".repeat(5));
+    assert_eq!(rendered_frames, 1);
+
+    Ok(())
+}
+
+/// Runs the first frame of a movie, then ticks it by each of `ticks`, in frames, calling
+/// `render` `renders_per_tick` times after every tick.
+///
+/// Returns the trace output and the number of frames drawn.
+fn run_ticks(path: &str, ticks: &[f64], renders_per_tick: usize) -> Result<(String, usize), Error> {
+    let stats = Rc::new(RefCell::new(RenderResourceStats::default()));
+    let player = Player::new(
+        Box::new(CountingRenderer {
+            stats: stats.clone(),
+        }),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::new()),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        SwfMovie::from_path(Path::new("tests/swfs").join(path).join("test.swf"))?,
+    )?;
+
+    {
+        let mut player = player.lock().unwrap();
+        player.set_is_playing(true);
+        player.run_frame();
+        player.render();
+        let frame_time = 1000.0 / player.frame_rate();
+        for frames in ticks {
+            player.tick(frames * frame_time);
+            for _ in 0..renders_per_tick {
+                player.render();
+            }
+        }
+    }
+
+    let rendered_frames = stats.borrow().rendered_frames;
+    Ok((trace_log(&player), rendered_frames))
+}
+
+#[test]
+fn scripts_run_once_per_frame_however_often_the_player_renders() -> Result<(), Error> {
+    let expected_output =
+        std::fs::read_to_string("tests/swfs/avm1/goto_frame/output.txt")?.replace("\r\n", "\n");
+
+    // Frames only run when enough time has passed, and are only drawn once.
+    for &renders_per_tick in &[0, 1, 10] {
+        let (output, rendered_frames) = run_ticks("avm1/goto_frame", &[0.25; 9], renders_per_tick)?;
+        assert_eq!(output, expected_output);
+        let expected_frames = if renders_per_tick > 0 { 3 } else { 1 };
+        assert_eq!(rendered_frames, expected_frames);
+    }
+
+    // A late tick catches up on both frames, but only draws the last one.
+    let (output, rendered_frames) = run_ticks("avm1/goto_frame", &[2.5], 3)?;
+    assert_eq!(output, expected_output);
+    assert_eq!(rendered_frames, 2);
+
+    Ok(())
+}
+
+/// Runs the first frame of a movie, then feeds it a script of mouse events.
+///
+/// Returns the trace output of the events only.
+fn run_mouse_script(path: &str, events: Vec<PlayerEvent>) -> Result<String, Error> {
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::new()),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        SwfMovie::from_path(Path::new("tests/swfs").join(path).join("test.swf"))?,
+    )?;
+
+    player.lock().unwrap().run_frame();
+    let setup_output = trace_log(&player);
+    {
+        let mut player = player.lock().unwrap();
+        for event in events {
+            player.handle_event(event);
+        }
+    }
+
+    Ok(trace_log(&player)[setup_output.len()..].to_string())
+}
+
+fn mouse_move(x: f64, y: f64) -> PlayerEvent {
+    PlayerEvent::MouseMove { x, y }
+}
+
+fn mouse_down(x: f64, y: f64) -> PlayerEvent {
+    PlayerEvent::MouseDown { x, y }
+}
+
+fn mouse_up(x: f64, y: f64) -> PlayerEvent {
+    PlayerEvent::MouseUp { x, y }
+}
+
+/// `pixel_perfect_mouse` has a 20x10 stage, and traces the mouse position whenever it moves.
+#[test]
+fn pixel_perfect_mode_maps_the_mouse_through_the_whole_number_scale() -> Result<(), Error> {
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::new()),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        SwfMovie::from_path("tests/swfs/avm1/pixel_perfect_mouse/test.swf")?,
+    )?;
+
+    {
+        let mut player = player.lock().unwrap();
+        // A 2.5x viewport only scales the stage by 2, leaving margins of 5 and 2 pixels.
+        player.set_pixel_perfect(true);
+        player.set_viewport_dimensions(50, 25);
+        player.run_frame();
+        for &(x, y) in &[(5.0, 2.0), (25.0, 12.0), (44.0, 21.0)] {
+            player.handle_event(mouse_move(x, y));
+        }
+    }
+
+    assert_eq!(trace_log(&player), "0,0\n10,5\n19.5,9.5\n");
+    Ok(())
+}
+
+/// `mouse_leave` traces the mouse position from `onMouseMove` and `onMouseLeave` listeners.
+#[test]
+fn mouse_leave_keeps_the_last_mouse_position() -> Result<(), Error> {
+    let output = run_mouse_script(
+        "avm1/mouse_leave",
+        vec![
+            mouse_move(10.0, 20.0),
+            PlayerEvent::MouseLeave,
+            mouse_move(30.0, 40.0),
+        ],
+    )?;
+    assert_eq!(output, "move 10,20\nleave 10,20\nmove 30,40\n");
+    Ok(())
+}
+
+// `button_track_as_menu` has two menu buttons, `m1` at x 0-100 and `m2` at x 200-300, and a
+// push button `p` at x 400-500. All of them span y 100-200.
+const TRACK_AS_MENU: &str = "avm1/button_track_as_menu";
+
+#[test]
+fn push_button_ignores_press_outside_release_inside() -> Result<(), Error> {
+    let output = run_mouse_script(
+        TRACK_AS_MENU,
+        vec![
+            mouse_down(350.0, 150.0),
+            mouse_move(450.0, 150.0),
+            mouse_up(450.0, 150.0),
+        ],
+    )?;
+    assert_eq!(output, "p onRollOver\n");
+    Ok(())
+}
+
+#[test]
+fn menu_button_releases_after_press_outside() -> Result<(), Error> {
+    let output = run_mouse_script(
+        TRACK_AS_MENU,
+        vec![
+            mouse_down(150.0, 150.0),
+            mouse_move(50.0, 150.0),
+            mouse_up(50.0, 150.0),
+        ],
+    )?;
+    assert_eq!(output, "m1 onDragOver\nm1 onRelease\n");
+    Ok(())
+}
+
+#[test]
+fn menu_buttons_track_while_held() -> Result<(), Error> {
+    let output = run_mouse_script(
+        TRACK_AS_MENU,
+        vec![
+            mouse_move(50.0, 150.0),
+            mouse_down(50.0, 150.0),
+            mouse_move(150.0, 150.0),
+            mouse_move(250.0, 150.0),
+            mouse_move(50.0, 150.0),
+            mouse_move(250.0, 150.0),
+            mouse_up(250.0, 150.0),
+        ],
+    )?;
+    assert_eq!(
+        output,
+        "m1 onRollOver\n\
+         m1 onPress\n\
+         m1 onDragOut\n\
+         m2 onDragOver\n\
+         m2 onDragOut\n\
+         m1 onDragOver\n\
+         m1 onDragOut\n\
+         m2 onDragOver\n\
+         m2 onRelease\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn menu_and_push_buttons_keep_separate_states() -> Result<(), Error> {
+    // A held menu press doesn't hover push buttons until it's released.
+    let output = run_mouse_script(
+        TRACK_AS_MENU,
+        vec![
+            mouse_move(50.0, 150.0),
+            mouse_down(50.0, 150.0),
+            mouse_move(450.0, 150.0),
+            mouse_up(450.0, 150.0),
+        ],
+    )?;
+    assert_eq!(
+        output,
+        "m1 onRollOver\nm1 onPress\nm1 onDragOut\np onRollOver\n"
+    );
+
+    // A pressed push button keeps the mouse captured, so menu buttons don't see it.
+    let output = run_mouse_script(
+        TRACK_AS_MENU,
+        vec![
+            mouse_move(450.0, 150.0),
+            mouse_down(450.0, 150.0),
+            mouse_move(50.0, 150.0),
+            mouse_move(250.0, 150.0),
+            mouse_up(250.0, 150.0),
+        ],
+    )?;
+    assert_eq!(
+        output,
+        "p onRollOver\np onPress\np onRelease\np onRollOut\nm2 onRollOver\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn system_events_are_dispatched_in_listeners_order() -> Result<(), Error> {
+    // After the first frame, `Key._listeners` is `[c, b, d]` and `Mouse._listeners` is `[b, a]`.
+    let output = run_mouse_script(
+        "avm1/broadcaster_order",
+        vec![
+            PlayerEvent::KeyDown {
+                key_code: KeyCode::A,
+            },
+            mouse_down(0.0, 0.0),
+        ],
+    )?;
+    assert_eq!(
+        output,
+        "c.onKeyDown\nb.onKeyDown\nd.onKeyDown\nb.onMouseDown\na.onMouseDown\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn audio_stream_starts_on_its_first_block() -> Result<(), Error> {
+    let output = run_audio_trace("avm1/audio_stream_start", &[100.0; 11], |_, _| ())?;
+    assert_audio_trace("avm1/audio_stream_start", &output)
+}
+
+#[test]
+fn audio_stream_restarts_after_goto() -> Result<(), Error> {
+    let output = run_audio_trace("avm1/audio_stream_seek", &[100.0; 10], |_, _| ())?;
+    assert_audio_trace("avm1/audio_stream_seek", &output)
+}
+
+#[test]
+fn audio_stream_runs_ahead_of_throttled_timeline() -> Result<(), Error> {
+    // A background tab only gets a tick every second, and then catches up at full speed.
+    let mut ticks = vec![100.0; 3];
+    ticks.push(1000.0);
+    ticks.extend_from_slice(&[100.0; 5]);
+    let output = run_audio_trace("avm1/audio_stream_catch_up", &ticks, |tick, monitor| {
+        // The audio device also starves for a moment when the tab comes back.
+        if tick == 4 {
+            monitor.inject_underrun(50.0);
+        }
+    })?;
+    assert_audio_trace("avm1/audio_stream_catch_up", &output)
+}
+
+#[test]
+fn audio_latency_delays_new_sounds() -> Result<(), Error> {
+    let output = run_audio_trace("avm1/audio_stream_start", &[100.0; 6], |tick, monitor| {
+        if tick == 0 {
+            monitor.set_latency(30.0);
+        }
+    })?;
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines[3], "400ms frames [4]: streams [], sounds [170ms]");
+    assert_eq!(
+        lines[5],
+        "600ms frames [6]: streams [clip 0 from frame 5 at 70ms], sounds []"
+    );
+    Ok(())
+}
+
+#[test]
+fn audio_stream_buffers_before_playing() -> Result<(), Error> {
+    // The movie is downloading at half speed, so each 100ms tick brings 50ms of stream audio.
+    let buffer = |tick, monitor: &NullAudioMonitor| {
+        if tick == 0 {
+            monitor.set_stream_data_rate(Some(0.5));
+        }
+    };
+    let held_frames = |output: &str| {
+        output
+            .lines()
+            .take_while(|line| !line.contains("frames [40]"))
+            .filter(|line| line.contains("frames []"))
+            .count()
+    };
+
+    let output = run_audio_trace("avm1/audio_stream_buffer_0s", &[100.0; 40], buffer)?;
+    assert_eq!(held_frames(&output), 0);
+    assert!(output.ends_with("frames [40]: stop stream, streams [], sounds []\n"));
+
+    let output = run_audio_trace("avm1/audio_stream_buffer_1s", &[100.0; 79], buffer)?;
+    assert_audio_trace("avm1/audio_stream_buffer_1s", &output)?;
+
+    let output = run_audio_trace("avm1/audio_stream_buffer_5s", &[100.0; 139], buffer)?;
+    assert_eq!(held_frames(&output), 99);
+    assert!(output.ends_with("frames [40]: stop stream, streams [], sounds []\n"));
+    Ok(())
+}
+
+#[test]
+fn external_interface_round_trips_values() -> Result<(), Error> {
+    let movie = SwfMovie::from_path("tests/swfs/avm1/external_interface/test.swf")?;
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::new()),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+    let host = TestExternalInterface::default();
+    player
+        .lock()
+        .unwrap()
+        .set_external_interface(Box::new(host.clone()));
+
+    player.lock().unwrap().run_frame();
+
+    // The object sent to the host contains itself, which is sent as `null`.
+    let mut inner = BTreeMap::new();
+    inner.insert("three".to_string(), ExternalValue::Bool(true));
+    let mut sent = BTreeMap::new();
+    sent.insert(
+        "list".to_string(),
+        ExternalValue::List(vec![
+            ExternalValue::Number(1.0),
+            ExternalValue::String("two".to_string()),
+            ExternalValue::Object(inner),
+        ]),
+    );
+    sent.insert("nothing".to_string(), ExternalValue::Null);
+    sent.insert("self".to_string(), ExternalValue::Null);
+    assert_eq!(
+        *host.calls.borrow(),
+        vec![
+            (
+                "roundTrip".to_string(),
+                vec![ExternalValue::Object(sent.clone())]
+            ),
+            ("missing".to_string(), vec![ExternalValue::Number(1.0)]),
+        ]
+    );
+    assert_eq!(
+        *host.callbacks.borrow(),
+        vec!["echo".to_string(), "getName".to_string()]
+    );
+
+    // Values sent by the host arrive intact, and the callback runs on the object given to
+    // `addCallback`.
+    let echoed = player
+        .lock()
+        .unwrap()
+        .call_internal_interface("echo", &[ExternalValue::Object(sent.clone())]);
+    assert_eq!(echoed, ExternalValue::Object(sent));
+    let name = player
+        .lock()
+        .unwrap()
+        .call_internal_interface("getName", &[]);
+    assert_eq!(name, ExternalValue::String("receiver".to_string()));
+    let missing = player
+        .lock()
+        .unwrap()
+        .call_internal_interface("missing", &[]);
+    assert_eq!(missing, ExternalValue::Undefined);
+
+    let output = trace_log(&player);
+    let expected = std::fs::read_to_string("tests/swfs/avm1/external_interface/output.txt")?;
+    assert_eq!(PrettyString(&output), PrettyString(&expected));
+    Ok(())
+}
+
+#[test]
+fn external_interface_is_unavailable_without_host() -> Result<(), Error> {
+    let output = run_swf("tests/swfs/avm1/external_interface/test.swf", 1, None)?;
+    let expected =
+        std::fs::read_to_string("tests/swfs/avm1/external_interface/output_no_host.txt")?;
+    assert_eq!(PrettyString(&output), PrettyString(&expected));
+    Ok(())
+}
+
+/// Runs the first frame of the `shared_object` movie, then unloads it.
+fn run_shared_object_movie(
+    data: &Rc<RefCell<HashMap<String, Vec<u8>>>>,
+    quota: usize,
+) -> Result<String, Error> {
+    let swf_path = "tests/swfs/avm1/shared_object/test.swf";
+    let (mut executor, channel) = NullExecutor::new();
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::with_base_path(
+            Path::new(swf_path).parent().unwrap(),
+            channel,
+        )),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(QuotaStorageBackend {
+            data: data.clone(),
+            quota,
+        }),
+        SwfMovie::from_path(swf_path)?,
+    )?;
+
+    player.lock().unwrap().run_frame();
+    executor.poll_all()?;
+
+    Ok(trace_log(&player))
+}
+
+#[test]
+fn shared_object_persists_across_reloads() -> Result<(), Error> {
+    let data = Rc::new(RefCell::new(HashMap::new()));
+    let expected = |count: &str| {
+        format!(
+            "count: {}\n\
+             flush: pending\n\
+             same object: true\n\
+             invalid name: null\n\
+             outside local path: null\n\
+             status SharedObject.Flush.Success\n",
+            count
+        )
+    };
+
+    assert_eq!(run_shared_object_movie(&data, 1024)?, expected("undefined"));
+    assert_eq!(run_shared_object_movie(&data, 1024)?, expected("1"));
+    assert_eq!(run_shared_object_movie(&data, 1024)?, expected("2"));
+
+    // The movie's URL is the absolute `file:` URL of its path.
+    let keys: Vec<_> = data.borrow().keys().cloned().collect();
+    assert_eq!(keys.len(), 1);
+    assert!(keys[0].starts_with("localhost/"));
+    assert!(keys[0].ends_with("/tests/swfs/avm1/shared_object/test.swf/test"));
+    Ok(())
+}
+
+#[test]
+fn shared_object_flush_fails_over_quota() -> Result<(), Error> {
+    let data = Rc::new(RefCell::new(HashMap::new()));
+    let output = run_shared_object_movie(&data, 4)?;
+    assert_eq!(
+        output,
+        "count: undefined\n\
+         flush: pending\n\
+         same object: true\n\
+         invalid name: null\n\
+         outside local path: null\n\
+         error SharedObject.Flush.Failed\n"
+    );
+
+    // Nothing was stored, even when the movie was unloaded.
+    assert!(data.borrow().is_empty());
+    assert!(run_shared_object_movie(&data, 4)?.starts_with("count: undefined\n"));
+    Ok(())
+}
+
+#[test]
+fn load_movie_loop_settles_without_movie_cache() -> Result<(), Error> {
+    let (mut executor, channel) = NullExecutor::new();
+    let movie = SwfMovie::from_path("tests/swfs/avm1/load_movie_loop/test.swf")?;
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::with_base_path(
+            Path::new("tests/swfs/avm1/load_movie_loop"),
+            channel,
+        )),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+
+    // Every load fetches the movie again, and the nesting limit still stops the loop.
+    player.lock().unwrap().set_movie_cache_enabled(false);
+    for _ in 0..40 {
+        player.lock().unwrap().run_frame();
+        executor.poll_all().unwrap();
+    }
+    executor.block_all().unwrap();
+
+    let expected = std::fs::read_to_string("tests/swfs/avm1/load_movie_loop/output.txt")?;
+    assert_eq!(PrettyString(&trace_log(&player)), PrettyString(&expected));
+    Ok(())
+}
+
+#[test]
+fn isolated_movies_keep_their_own_globals() -> Result<(), Error> {
+    let (mut executor, channel) = NullExecutor::new();
+    let movie = SwfMovie::from_path("tests/swfs/avm1/global_per_movie/test.swf")?;
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::with_base_path(
+            Path::new("tests/swfs/avm1/global_per_movie"),
+            channel,
+        )),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+
+    // Each loaded movie sees its own `_global.X`, and still reads the root's `_global.shared`.
+    player.lock().unwrap().set_isolate_global_per_movie(true);
+    for _ in 0..4 {
+        player.lock().unwrap().run_frame();
+        executor.poll_all().unwrap();
+    }
+    executor.block_all().unwrap();
+
+    let expected = std::fs::read_to_string("tests/swfs/avm1/global_per_movie/output_isolated.txt")?;
+    assert_eq!(PrettyString(&trace_log(&player)), PrettyString(&expected));
+    Ok(())
+}
+
+#[test]
+fn fetch_progress_is_broadcast_to_movie_clip_loaders() -> Result<(), Error> {
+    let (mut executor, channel) = NullExecutor::new();
+    let movie = SwfMovie::from_path("tests/swfs/avm1/mcl_loadclip/test.swf")?;
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(ProgressNavigatorBackend {
+            inner: NullNavigatorBackend::with_base_path(
+                Path::new("tests/swfs/avm1/mcl_loadclip"),
+                channel,
+            ),
+        }),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+
+    for _ in 0..11 {
+        player.lock().unwrap().run_frame();
+        executor.poll_all().unwrap();
+    }
+    executor.block_all().unwrap();
+
+    // The half-loaded movie is reported before the complete one.
+    let expected = std::fs::read_to_string("tests/swfs/avm1/mcl_loadclip/output.txt")?
+        .replace("\r\n", "\n")
+        .replacen(
+            "Event: onLoadProgress\n",
+            "Event: onLoadProgress\nEvent: onLoadProgress\n",
+            1,
+        );
+    assert_eq!(PrettyString(&trace_log(&player)), PrettyString(&expected));
+    Ok(())
+}
+
+#[test]
+fn mock_fetches_are_recorded() -> Result<(), Error> {
+    let (mut executor, channel) = NullExecutor::new();
+    let movie = SwfMovie::from_path("tests/swfs/avm1/mock_fetch/test.swf")?;
+    let requests = Rc::new(RefCell::new(vec![]));
+    let mut responses = HashMap::new();
+    responses.insert("vars.txt".to_string(), Ok(b"greeting=hello".to_vec()));
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(MockNavigatorBackend {
+            inner: NullNavigatorBackend::with_base_path(
+                Path::new("tests/swfs/avm1/mock_fetch"),
+                channel,
+            ),
+            responses,
+            requests: requests.clone(),
+        }),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+
+    player.lock().unwrap().run_frame();
+    executor.poll_all().unwrap();
+    player.lock().unwrap().run_frame();
+    executor.block_all().unwrap();
+
+    let requests = requests.borrow();
+    assert_eq!(requests.len(), 2);
+
+    // `loadVariables` posts the variables of the root as a form.
+    assert_eq!(requests[0].url, "vars.txt");
+    assert!(matches!(requests[0].method, NavigationMethod::POST));
+    let body = String::from_utf8(requests[0].body.clone().expect("POST body"))?;
+    let pairs: Vec<&str> = body.split('&').collect();
+    assert!(pairs.contains(&"name=ruffle"), "{}", body);
+    assert!(pairs.contains(&"version=8"), "{}", body);
+
+    assert_eq!(requests[1].url, "missing.xml");
+    assert!(matches!(requests[1].method, NavigationMethod::GET));
+    assert!(requests[1].body.is_none());
+
+    assert_eq!(trace_log(&player), "status 404\nhello\n");
+    Ok(())
+}
+
+/// The name and arguments of a call made with `ExternalInterface.call`.
+type ExternalCall = (String, Vec<ExternalValue>);
+
+/// An `ExternalInterface` host which records what the movie sends it.
+///
+/// `roundTrip` returns its first argument; any other function fails as if it didn't exist.
+#[derive(Clone, Default)]
+struct TestExternalInterface {
+    calls: Rc<RefCell<Vec<ExternalCall>>>,
+    callbacks: Rc<RefCell<Vec<String>>>,
+}
+
+impl ExternalInterfaceProvider for TestExternalInterface {
+    fn call_method(&mut self, name: &str, args: &[ExternalValue]) -> Result<ExternalValue, Error> {
+        self.calls
+            .borrow_mut()
+            .push((name.to_string(), args.to_vec()));
+        match name {
+            "roundTrip" => Ok(args.get(0).cloned().unwrap_or(ExternalValue::Undefined)),
+            _ => Err(format!("{} is not a function", name).into()),
+        }
+    }
+
+    fn on_callback_available(&mut self, name: &str) {
+        self.callbacks.borrow_mut().push(name.to_string());
+    }
+}
+
+/// Input backend that behaves like a frontend receiving key presses from the keyboard.
+#[derive(Default)]
+struct KeyboardInputBackend {
+    keys_down: Vec<KeyCode>,
+    last_key: Option<KeyCode>,
+    last_char: Option<char>,
+}
+
+impl KeyboardInputBackend {
+    /// Presses the key in the given US keyboard position, typing the given character.
+    fn press(&mut self, positional: KeyCode, character: Option<char>) -> KeyCode {
+        let key_code = key_code_for_key(positional, character);
+        self.keys_down.push(key_code);
+        self.last_key = Some(key_code);
+        self.last_char = character;
+        key_code
+    }
+
+    fn release_all(&mut self) {
+        self.keys_down.clear();
+    }
+}
+
+impl InputBackend for KeyboardInputBackend {
+    fn is_key_down(&self, key: KeyCode) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    fn get_last_key_code(&self) -> KeyCode {
+        self.last_key.unwrap_or(KeyCode::Unknown)
+    }
+
+    fn get_last_key_char(&self) -> Option<char> {
+        self.last_char
+    }
+}
+
+/// UI backend that answers every message with the same choice, and records the messages.
+struct ScriptedUiBackend {
+    choice: Option<usize>,
+    messages: Rc<RefCell<Vec<(MessageLevel, String)>>>,
+}
+
+impl UiBackend for ScriptedUiBackend {
+    fn message(
+        &mut self,
+        level: MessageLevel,
+        message: &str,
+        _choices: &[&str],
+    ) -> OwnedFuture<Option<usize>, Error> {
+        self.messages
+            .borrow_mut()
+            .push((level, message.to_string()));
+        let choice = self.choice;
+        Box::pin(async move { Ok(choice) })
+    }
+
+    fn set_clipboard_content(&mut self, _content: String) {}
+
+    fn mouse_visible(&self) -> bool {
+        true
+    }
+
+    fn set_mouse_visible(&mut self, _visible: bool) {}
+
+    fn set_mouse_cursor(&mut self, _cursor: MouseCursor) {}
+
+    fn display_state(&self) -> DisplayState {
+        DisplayState::Normal
+    }
+
+    fn set_display_state(&mut self, _state: DisplayState) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Counts of the resources handed out by a `CountingRenderer`.
+#[derive(Clone, Default)]
+struct RenderResourceStats {
+    next_handle: usize,
+    registered_shapes: usize,
+    live_shapes: Vec<usize>,
+    live_bitmaps: Vec<usize>,
+    rendered_frames: usize,
+    rendered_shapes: usize,
+    /// The transform of each shape drawn.
+    rendered_shape_transforms: Vec<Transform>,
+    /// The translation and smoothing of each bitmap drawn.
+    rendered_bitmaps: Vec<(Twips, Twips, bool)>,
+    pushed_blend_modes: usize,
+    lost: bool,
+}
+
+impl RenderResourceStats {
+    fn register_shape(&mut self) -> ShapeHandle {
+        self.next_handle += 1;
+        self.registered_shapes += 1;
+        self.live_shapes.push(self.next_handle);
+        ShapeHandle(self.next_handle)
+    }
+
+    fn register_bitmap(&mut self) -> BitmapInfo {
+        self.next_handle += 1;
+        self.live_bitmaps.push(self.next_handle);
+        BitmapInfo {
+            handle: BitmapHandle(self.next_handle),
+            width: 1,
+            height: 1,
+        }
+    }
+}
+
+/// Render backend that keeps track of which shapes and bitmaps are still registered.
+struct CountingRenderer {
+    stats: Rc<RefCell<RenderResourceStats>>,
+}
+
+impl RenderBackend for CountingRenderer {
+    fn set_viewport_dimensions(&mut self, _width: u32, _height: u32) {}
+    fn register_shape(&mut self, _shape: &Shape) -> ShapeHandle {
+        self.stats.borrow_mut().register_shape()
+    }
+    fn register_glyph_shape(&mut self, _shape: &Glyph) -> ShapeHandle {
+        self.stats.borrow_mut().register_shape()
+    }
+    fn register_bitmap_jpeg(
+        &mut self,
+        _id: swf::CharacterId,
+        _data: &[u8],
+        _jpeg_tables: Option<&[u8]>,
+    ) -> BitmapInfo {
+        self.stats.borrow_mut().register_bitmap()
+    }
+    fn register_bitmap_jpeg_2(&mut self, _id: swf::CharacterId, _data: &[u8]) -> BitmapInfo {
+        self.stats.borrow_mut().register_bitmap()
+    }
+    fn register_bitmap_jpeg_3(
+        &mut self,
+        _id: swf::CharacterId,
+        _data: &[u8],
+        _alpha_data: &[u8],
+    ) -> BitmapInfo {
+        self.stats.borrow_mut().register_bitmap()
+    }
+    fn register_bitmap_png(&mut self, _swf_tag: &DefineBitsLossless) -> BitmapInfo {
+        self.stats.borrow_mut().register_bitmap()
+    }
+    fn register_bitmap_raw(&mut self, _width: u32, _height: u32, _rgba: Vec<u8>) -> BitmapHandle {
+        self.stats.borrow_mut().register_bitmap().handle
+    }
+    fn update_bitmap_region(&mut self, _bitmap: BitmapHandle, _region: PixelRegion, _rgba: &[u8]) {}
+    fn unregister_shape(&mut self, shape: ShapeHandle) {
+        let live_shapes = &mut self.stats.borrow_mut().live_shapes;
+        let index = live_shapes
+            .iter()
+            .position(|handle| *handle == shape.0)
+            .expect("shape unregistered twice");
+        live_shapes.remove(index);
+    }
+    fn unregister_bitmap(&mut self, bitmap: BitmapHandle) {
+        let live_bitmaps = &mut self.stats.borrow_mut().live_bitmaps;
+        let index = live_bitmaps
+            .iter()
+            .position(|handle| *handle == bitmap.0)
+            .expect("bitmap unregistered twice");
+        live_bitmaps.remove(index);
+    }
+    fn begin_frame(&mut self) {
+        self.stats.borrow_mut().rendered_frames += 1;
+    }
+    fn end_frame(&mut self) {}
+    fn clear(&mut self, _color: Color) {}
+    fn render_bitmap(&mut self, bitmap: BitmapHandle, transform: &Transform, smoothing: bool) {
+        let mut stats = self.stats.borrow_mut();
+        assert!(stats.live_bitmaps.contains(&bitmap.0));
+        stats
+            .rendered_bitmaps
+            .push((transform.matrix.tx, transform.matrix.ty, smoothing));
+    }
+    fn render_shape(&mut self, shape: ShapeHandle, transform: &Transform) {
+        let mut stats = self.stats.borrow_mut();
+        assert!(stats.live_shapes.contains(&shape.0));
+        stats.rendered_shapes += 1;
+        stats.rendered_shape_transforms.push(transform.clone());
+    }
+    fn draw_letterbox(&mut self, _letterbox: Letterbox) {}
+    fn push_mask(&mut self) {}
+    fn activate_mask(&mut self) {}
+    fn pop_mask(&mut self) {}
+    fn push_blend_mode(&mut self, _blend_mode: BlendMode) {
+        self.stats.borrow_mut().pushed_blend_modes += 1;
+    }
+    fn is_lost(&self) -> bool {
+        self.stats.borrow().lost
+    }
+}
+
+/// `NullNavigatorBackend` wrapper whose clock advances by a millisecond each time it is read.
+struct ClockNavigatorBackend {
+    inner: NullNavigatorBackend,
+    time: Duration,
+}
+
+impl NavigatorBackend for ClockNavigatorBackend {
+    fn navigate_to_url(
+        &self,
+        url: String,
+        window: Option<String>,
+        vars_method: Option<(NavigationMethod, HashMap<String, String>)>,
+    ) {
+        self.inner.navigate_to_url(url, window, vars_method)
+    }
+
+    fn fetch(&self, url: String, request_options: RequestOptions) -> OwnedFuture<Vec<u8>, Error> {
+        self.inner.fetch(url, request_options)
+    }
+
+    fn time_since_launch(&mut self) -> Duration {
+        self.time += Duration::from_millis(1);
+        self.time
+    }
+
+    fn spawn_future(
+        &mut self,
+        future: OwnedFuture<(), Error>,
+        token: CancellationToken,
+    ) -> SpawnHandle {
+        self.inner.spawn_future(future, token)
+    }
+}
+
+/// `NullNavigatorBackend` wrapper whose clock is set by the test.
+struct ManualClockNavigatorBackend {
+    inner: NullNavigatorBackend,
+    time: Rc<Cell<Duration>>,
+}
+
+impl NavigatorBackend for ManualClockNavigatorBackend {
+    fn navigate_to_url(
+        &self,
+        url: String,
+        window: Option<String>,
+        vars_method: Option<(NavigationMethod, HashMap<String, String>)>,
+    ) {
+        self.inner.navigate_to_url(url, window, vars_method)
+    }
+
+    fn fetch(&self, url: String, request_options: RequestOptions) -> OwnedFuture<Vec<u8>, Error> {
+        self.inner.fetch(url, request_options)
+    }
+
+    fn time_since_launch(&mut self) -> Duration {
+        self.time.get()
+    }
+
+    fn spawn_future(
+        &mut self,
+        future: OwnedFuture<(), Error>,
+        token: CancellationToken,
+    ) -> SpawnHandle {
+        self.inner.spawn_future(future, token)
+    }
+}
+
+/// `NullNavigatorBackend` wrapper whose fetches report that half of the data has arrived
+/// before reporting all of it, like a download over a network.
+struct ProgressNavigatorBackend {
+    inner: NullNavigatorBackend,
+}
+
+impl NavigatorBackend for ProgressNavigatorBackend {
+    fn navigate_to_url(
+        &self,
+        url: String,
+        window: Option<String>,
+        vars_method: Option<(NavigationMethod, HashMap<String, String>)>,
+    ) {
+        self.inner.navigate_to_url(url, window, vars_method)
+    }
+
+    fn fetch(&self, url: String, request_options: RequestOptions) -> OwnedFuture<Vec<u8>, Error> {
+        self.inner.fetch(url, request_options)
+    }
+
+    fn fetch_with_progress(
+        &self,
+        url: String,
+        request_options: RequestOptions,
+        mut progress: ProgressCallback,
+    ) -> OwnedFuture<Vec<u8>, Error> {
+        let fetch = self.inner.fetch(url, request_options);
+        Box::pin(async move {
+            let data = fetch.await?;
+            progress(data.len() / 2, Some(data.len()));
+            progress(data.len(), Some(data.len()));
+            Ok(data)
+        })
+    }
+
+    fn time_since_launch(&mut self) -> Duration {
+        self.inner.time_since_launch()
+    }
+
+    fn spawn_future(
+        &mut self,
+        future: OwnedFuture<(), Error>,
+        token: CancellationToken,
+    ) -> SpawnHandle {
+        self.inner.spawn_future(future, token)
+    }
+}
+
+/// A fetch made through a `MockNavigatorBackend`.
+struct MockRequest {
+    url: String,
+    method: NavigationMethod,
+    body: Option<Vec<u8>>,
+}
+
+/// `NullNavigatorBackend` wrapper that answers fetches with canned responses instead of files,
+/// and records every fetch it is asked for.
+///
+/// URLs without a response fail with a 404, as they would on a web server.
+struct MockNavigatorBackend {
+    inner: NullNavigatorBackend,
+    responses: HashMap<String, Result<Vec<u8>, Error>>,
+    requests: Rc<RefCell<Vec<MockRequest>>>,
+}
+
+impl NavigatorBackend for MockNavigatorBackend {
+    fn navigate_to_url(
+        &self,
+        url: String,
+        window: Option<String>,
+        vars_method: Option<(NavigationMethod, HashMap<String, String>)>,
+    ) {
+        self.inner.navigate_to_url(url, window, vars_method)
+    }
+
+    fn fetch(&self, url: String, request_options: RequestOptions) -> OwnedFuture<Vec<u8>, Error> {
+        // Errors can't be cloned, so failures are answered with a copy of the canned error.
+        let response = match self.responses.get(&url) {
+            Some(Ok(data)) => Ok(data.clone()),
+            Some(Err(e)) => Err(match HttpStatusError::status_of(e) {
+                Some(status) => HttpStatusError(status).into(),
+                None => e.to_string().into(),
+            }),
+            None => Err(HttpStatusError(404).into()),
+        };
+        self.requests.borrow_mut().push(MockRequest {
+            url,
+            method: request_options.method(),
+            body: request_options
+                .body()
+                .as_ref()
+                .map(|(data, _)| data.clone()),
+        });
+        Box::pin(async move { response })
+    }
+
+    fn time_since_launch(&mut self) -> Duration {
+        self.inner.time_since_launch()
+    }
+
+    fn spawn_future(
+        &mut self,
+        future: OwnedFuture<(), Error>,
+        token: CancellationToken,
+    ) -> SpawnHandle {
+        self.inner.spawn_future(future, token)
+    }
+}
+
+/// A fetch that never finishes, like a download from a server that stopped responding.
+struct StalledFetch;
+
+impl Future for StalledFetch {
+    type Output = Result<Vec<u8>, Error>;
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Poll::Pending
+    }
+}
+
+/// Wraps a spawned future to check that it is never polled once its token is cancelled, and
+/// to count how many times it was polled and whether it was dropped because of cancellation.
+struct ProbedFuture {
+    future: OwnedFuture<(), Error>,
+    token: CancellationToken,
+    polls: Rc<Cell<u32>>,
+    cancelled: Rc<Cell<u32>>,
+}
+
+impl Future for ProbedFuture {
+    type Output = Result<(), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        assert!(
+            !self.token.is_cancelled(),
+            "future polled after it was cancelled"
+        );
+        self.polls.set(self.polls.get() + 1);
+        self.future.as_mut().poll(cx)
+    }
+}
+
+impl Drop for ProbedFuture {
+    fn drop(&mut self) {
+        if self.token.is_cancelled() {
+            self.cancelled.set(self.cancelled.get() + 1);
+        }
+    }
+}
+
+/// `NullNavigatorBackend` wrapper whose fetches never finish, and which probes every future
+/// spawned through it.
+struct StalledNavigatorBackend {
+    inner: NullNavigatorBackend,
+    spawned: Rc<Cell<u32>>,
+    polls: Rc<Cell<u32>>,
+    cancelled: Rc<Cell<u32>>,
+}
+
+impl NavigatorBackend for StalledNavigatorBackend {
+    fn navigate_to_url(
+        &self,
+        url: String,
+        window: Option<String>,
+        vars_method: Option<(NavigationMethod, HashMap<String, String>)>,
+    ) {
+        self.inner.navigate_to_url(url, window, vars_method)
+    }
+
+    fn fetch(&self, _url: String, _request_options: RequestOptions) -> OwnedFuture<Vec<u8>, Error> {
+        Box::pin(StalledFetch)
+    }
+
+    fn time_since_launch(&mut self) -> Duration {
+        self.inner.time_since_launch()
+    }
+
+    fn spawn_future(
+        &mut self,
+        future: OwnedFuture<(), Error>,
+        token: CancellationToken,
+    ) -> SpawnHandle {
+        self.spawned.set(self.spawned.get() + 1);
+        let token = token.child();
+        let probe = ProbedFuture {
+            future,
+            token: token.clone(),
+            polls: self.polls.clone(),
+            cancelled: self.cancelled.clone(),
+        };
+        self.inner
+            .spawn_future(Box::pin(probe), token.clone())
+            .detach();
+        SpawnHandle::new(token)
+    }
+}
+
+/// Storage backend whose data outlives the player, like the storage of a browser, and which
+/// refuses data bigger than its quota.
+struct QuotaStorageBackend {
+    data: Rc<RefCell<HashMap<String, Vec<u8>>>>,
+    quota: usize,
+}
+
+impl StorageBackend for QuotaStorageBackend {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        self.data.borrow().get(key).cloned()
+    }
+
+    fn put(&mut self, key: &str, data: Vec<u8>) -> OwnedFuture<(), Error> {
+        if data.len() > self.quota {
+            return Box::pin(async { Err("Quota exceeded".into()) });
+        }
+        self.data.borrow_mut().insert(key.to_string(), data);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn remove(&mut self, key: &str) {
+        self.data.borrow_mut().remove(key);
+    }
+}
+
+/// Runs a movie on a `NullAudioBackend`, ticking the player by each of the given durations, and
+/// describes what the audio backend is doing after each tick.
+///
+/// `before_tick` is called with the index of each tick, and can be used to simulate audio
+/// device problems.
+fn run_audio_trace<F>(path: &str, ticks: &[f64], mut before_tick: F) -> Result<String, Error>
+where
+    F: FnMut(usize, &NullAudioMonitor),
+{
+    let audio = NullAudioBackend::new();
+    let monitor = audio.monitor();
+    let movie = SwfMovie::from_path(format!("tests/swfs/{}/test.swf", path))?;
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(audio),
+        Box::new(NullNavigatorBackend::new()),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+    player.lock().unwrap().set_is_playing(true);
+    monitor.take_calls();
+
+    let mut output = String::new();
+    for (i, dt) in ticks.iter().enumerate() {
+        before_tick(i, &monitor);
+        let log_start = trace_log(&player).len();
+        player.lock().unwrap().tick(*dt);
+
+        let frames: Vec<String> = trace_log(&player)[log_start..]
+            .lines()
+            .map(str::to_string)
+            .collect();
+        output.push_str(&format!(
+            "{}ms frames [{}]:",
+            monitor.time(),
+            frames.join(", ")
+        ));
+        for (_, call) in monitor.take_calls() {
+            let call = match call {
+                AudioCall::StartSound { .. } => "start sound".to_string(),
+                AudioCall::StartStream { clip_frame, .. } => {
+                    format!("start stream from frame {}", clip_frame)
+                }
+                AudioCall::StopSound(_) => "stop sound".to_string(),
+                AudioCall::StopStream(_) => "stop stream".to_string(),
+                AudioCall::SetStreamBuffering { is_buffering, .. } => if is_buffering {
+                    "buffer stream"
+                } else {
+                    "play stream"
+                }
+                .to_string(),
+                call => format!("{:?}", call),
+            };
+            output.push_str(&format!(" {},", call));
+        }
+        let streams: Vec<String> = monitor
+            .stream_positions()
+            .iter()
+            .map(|stream| {
+                format!(
+                    "clip {} from frame {} at {}ms",
+                    stream.clip_id, stream.clip_frame, stream.position
+                )
+            })
+            .collect();
+        let sounds: Vec<String> = monitor
+            .sound_positions()
+            .iter()
+            .map(|sound| format!("{}ms", sound.position))
+            .collect();
+        output.push_str(&format!(
+            " streams [{}], sounds [{}]\n",
+            streams.join(", "),
+            sounds.join(", ")
+        ));
+    }
+
+    Ok(output)
+}
+
+/// Compares the output of `run_audio_trace` with the expected trace of the given test.
+fn assert_audio_trace(path: &str, output: &str) -> Result<(), Error> {
+    let expected_output = std::fs::read_to_string(format!("tests/swfs/{}/audio_trace.txt", path))?
+        .replace("\r\n", "\n");
+    assert_eq!(
+        PrettyString(output),
+        PrettyString(&expected_output),
+        "audio trace != expected audio trace"
+    );
+    Ok(())
+}
+
+/// Wrapper around string slice that makes debug output `{:?}` to print string same way as `{}`.
+/// Used in different `assert*!` macros in combination with `pretty_assertions` crate to make
+/// test failures to show nice diffs.
+/// Courtesy of https://github.com/colin-kiegel/rust-pretty-assertions/issues/24
+#[derive(PartialEq, Eq)]
+#[doc(hidden)]
+pub struct PrettyString<'a>(pub &'a str);
+
+/// Make diff to display string as multi-line string
+impl<'a> std::fmt::Debug for PrettyString<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+macro_rules! assert_eq {
+    ($left:expr, $right:expr) => {
+        pretty_assertions::assert_eq!(PrettyString($left.as_ref()), PrettyString($right.as_ref()));
+    };
+    ($left:expr, $right:expr, $message:expr) => {
+        pretty_assertions::assert_eq!(
+            PrettyString($left.as_ref()),
+            PrettyString($right.as_ref()),
+            $message
+        );
+    };
+}
+
+/// Loads an SWF and runs it through the Ruffle core for a number of frames.
+/// Tests that the trace output matches the given expected output.
+/// The clock advances at the given frame rate, or the SWF's own if there is none.
+fn test_swf(
+    swf_path: &str,
+    num_frames: u32,
+    frame_rate: Option<f64>,
+    expected_output_path: &str,
+) -> Result<(), Error> {
+    let expected_output = std::fs::read_to_string(expected_output_path)?.replace("\r\n", "\n");
+
+    let trace_log = run_swf(swf_path, num_frames, frame_rate)?;
+    assert_eq!(
+        trace_log, expected_output,
+        "ruffle output != flash player output"
+    );
+
+    Ok(())
+}
+
+/// Loads an SWF and runs it through the Ruffle core for a number of frames.
+/// Tests that the trace output matches the given expected output, and that no errors were
+/// logged while it ran.
+fn test_swf_no_errors(
+    swf_path: &str,
+    num_frames: u32,
+    expected_output_path: &str,
+) -> Result<(), Error> {
+    let (result, errors) =
+        capture_errors(|| test_swf(swf_path, num_frames, None, expected_output_path));
+    result?;
+    assert!(
+        errors.is_empty(),
+        "errors were logged while running {}:\n{}",
+        swf_path,
+        errors.join("\n")
+    );
+    Ok(())
+}
+
+/// Loads an SWF and runs it through the Ruffle core for a number of frames.
+/// Tests that the trace output matches the given expected output.
+/// If a line has a floating point value, it will be compared approxinmately using the given epsilon.
+fn test_swf_approx(
+    swf_path: &str,
+    num_frames: u32,
+    expected_output_path: &str,
+    epsilon: f64,
+) -> Result<(), Error> {
+    let trace_log = run_swf(swf_path, num_frames, None)?;
+    let expected_data = std::fs::read_to_string(expected_output_path)?;
+    std::assert_eq!(
+        trace_log.lines().count(),
+        expected_data.lines().count(),
+        "# of lines of output didn't match"
+    );
+
+    for (actual, expected) in trace_log.lines().zip(expected_data.lines()) {
+        // If these are numbers, compare using approx_eq.
+        if let (Ok(actual), Ok(expected)) = (actual.parse::<f64>(), expected.parse::<f64>()) {
+            // TODO: Lower this epsilon as the accuracy of the properties improves.
+            assert_abs_diff_eq!(actual, expected, epsilon = epsilon);
+        } else {
+            assert_eq!(actual, expected);
+        }
+    }
+    Ok(())
+}
+
+/// Loads an SWF and runs it through the Ruffle core for a number of frames.
+/// Returns the trace output.
+///
+/// `getTimer` reads a virtual clock, which advances by one frame at the given frame rate (or
+/// the SWF's own) after each frame, so that timing doesn't depend on how fast the test runs.
+fn run_swf(swf_path: &str, num_frames: u32, frame_rate: Option<f64>) -> Result<String, Error> {
+    let base_path = Path::new(swf_path).parent().unwrap();
+    let (mut executor, channel) = NullExecutor::new();
+    let movie = SwfMovie::from_path(swf_path)?;
+    let navigator = NullNavigatorBackend::with_base_path(base_path, channel);
+    let clock = navigator.clock();
+    let player = Player::new(
+        Box::new(NullRenderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(navigator),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(TestLogBackend::default()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+
+    let frame_rate = frame_rate.unwrap_or_else(|| player.lock().unwrap().frame_rate());
+    // The clock stands still for movies without a frame rate.
+    let frame_time = if frame_rate > 0.0 {
+        Duration::from_secs_f64(1.0 / frame_rate)
+    } else {
+        Duration::default()
+    };
+
+    for _ in 0..num_frames {
+        let mut player_lock = player.lock().unwrap();
+        player_lock.run_frame();
+        // Drawing the frame, like a host does, mustn't change the output.
+        player_lock.render();
+        drop(player_lock);
+        executor.poll_all().unwrap();
+        clock.set(clock.get() + frame_time);
+    }
+
+    executor.block_all().unwrap();
+
+    Ok(trace_log(&player))
+}
+
+thread_local! {
+    /// The errors logged on this thread while `capture_errors` runs.
+    static LOGGED_ERRORS: RefCell<Option<Vec<String>>> = RefCell::new(None);
+}
+
+/// Runs `f`, and returns its result along with the messages of the errors logged on this thread
+/// in the meantime.
+///
+/// Each test runs on its own thread, so errors logged by other tests aren't included.
+fn capture_errors<T>(f: impl FnOnce() -> T) -> (T, Vec<String>) {
+    static INSTALL_LOGGER: Once = Once::new();
+    INSTALL_LOGGER.call_once(|| {
+        log::set_logger(&ErrorLogger).expect("Another logger was installed");
+        log::set_max_level(LevelFilter::Error);
+    });
+
+    LOGGED_ERRORS.with(|errors| *errors.borrow_mut() = Some(vec![]));
+    let result = f();
+    let errors = LOGGED_ERRORS.with(|errors| errors.borrow_mut().take().unwrap_or_default());
+    (result, errors)
+}
+
+/// `ErrorLogger` records errors for `capture_errors`. Other records are ignored, as trace
+/// output is captured by `TestLogBackend` instead.
+struct ErrorLogger;
+
+impl log::Log for ErrorLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Error
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            LOGGED_ERRORS.with(|errors| {
+                if let Some(errors) = errors.borrow_mut().as_mut() {
+                    errors.push(format!("{}: {}", record.target(), record.args()));
+                }
+            });
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Counts the allocations made on each thread while `count_allocations` runs.
+struct CountingAllocator;
+
+thread_local! {
+    /// The number of allocations made on this thread while `count_allocations` runs.
+    static ALLOCATIONS: Cell<Option<usize>> = Cell::new(None);
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+fn count_allocation() {
+    // The counter is gone while the thread shuts down.
+    let _ = ALLOCATIONS.try_with(|count| count.set(count.get().map(|count| count + 1)));
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Runs `f`, and returns its result along with the number of allocations and reallocations
+/// made on this thread in the meantime.
+fn count_allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    ALLOCATIONS.with(|count| count.set(Some(0)));
+    let result = f();
+    let count = ALLOCATIONS.with(|count| count.take().unwrap_or_default());
+    (result, count)
+}
+
+/// Returns the trace output captured so far by the player's `TestLogBackend`.
+fn trace_log(player: &Arc<Mutex<Player>>) -> String {
+    player
+        .lock()
+        .unwrap()
+        .log()
+        .downcast_ref::<TestLogBackend>()
+        .unwrap()
+        .trace_output
+        .clone()
+}
+
+/// `TestLogBackend` captures output from AVM trace actions into a String.
+/// The context of each message is kept separately, so that the output can be compared with
+/// Flash Player's.
+#[derive(Default)]
+struct TestLogBackend {
+    trace_output: String,
+    trace_contexts: Vec<TraceContext>,
+}
+
+impl LogBackend for TestLogBackend {
+    fn avm_trace(&mut self, message: &str) {
+        self.trace_output.push_str(message);
+        self.trace_output.push('\n');
+    }
+
+    fn avm_trace_with_context(&mut self, message: &str, context: &TraceContext) {
+        self.avm_trace(message);
+        self.trace_contexts.push(context.clone());
+    }
 }
@@ -19,6 +19,9 @@ pub struct CpalAudioBackend {
 
     sounds: Arena<Sound>,
     sound_instances: Arc<Mutex<Arena<SoundInstance>>>,
+
+    /// The master volume set by the host, applied to the final mix.
+    global_volume: Arc<Mutex<f32>>,
 }
 
 type Signal = Box<dyn Send + sample::signal::Signal<Frame = [i16; 2]>>;
@@ -103,10 +106,12 @@ impl CpalAudioBackend {
             .map_err(|_| "Unable to start audio stream")?;
 
         let sound_instances: Arc<Mutex<Arena<SoundInstance>>> = Arc::new(Mutex::new(Arena::new()));
+        let global_volume = Arc::new(Mutex::new(1.0));
 
         // Start the audio thread.
         let audio_thread_handle = {
             let sound_instances = Arc::clone(&sound_instances);
+            let global_volume = Arc::clone(&global_volume);
             std::thread::spawn(move || {
                 event_loop.run(move |stream_id, stream_result| {
                     use cpal::{StreamData, UnknownTypeOutputBuffer};
@@ -120,21 +125,37 @@ impl CpalAudioBackend {
                     };
 
                     let mut sound_instances = sound_instances.lock().unwrap();
+                    let global_volume = *global_volume.lock().unwrap();
                     match stream_data {
                         StreamData::Output {
                             buffer: UnknownTypeOutputBuffer::U16(buffer),
                         } => {
-                            Self::mix_audio(&mut sound_instances, &output_format, buffer);
+                            Self::mix_audio(
+                                &mut sound_instances,
+                                &output_format,
+                                global_volume,
+                                buffer,
+                            );
                         }
                         StreamData::Output {
                             buffer: UnknownTypeOutputBuffer::I16(buffer),
                         } => {
-                            Self::mix_audio(&mut sound_instances, &output_format, buffer);
+                            Self::mix_audio(
+                                &mut sound_instances,
+                                &output_format,
+                                global_volume,
+                                buffer,
+                            );
                         }
                         StreamData::Output {
                             buffer: UnknownTypeOutputBuffer::F32(buffer),
                         } => {
-                            Self::mix_audio(&mut sound_instances, &output_format, buffer);
+                            Self::mix_audio(
+                                &mut sound_instances,
+                                &output_format,
+                                global_volume,
+                                buffer,
+                            );
                         }
                         _ => (),
                     }
@@ -148,6 +169,7 @@ impl CpalAudioBackend {
             audio_thread_handle,
             sounds: Arena::new(),
             sound_instances,
+            global_volume,
         })
     }
 
@@ -263,10 +285,12 @@ impl CpalAudioBackend {
     fn mix_audio<'a, T>(
         sound_instances: &mut Arena<SoundInstance>,
         output_format: &cpal::Format,
+        global_volume: f32,
         mut output_buffer: cpal::OutputBuffer<'a, T>,
     ) where
         T: 'a + cpal::Sample + Default + sample::Sample,
         T::Signed: sample::conv::FromSample<i16>,
+        <T::Signed as sample::Sample>::Float: sample::conv::FromSample<f32>,
     {
         use sample::{
             frame::{Frame, Stereo},
@@ -290,6 +314,9 @@ impl CpalAudioBackend {
                 }
             }
 
+            // Apply the host volume to the final mix.
+            let output_frame = output_frame.scale_amp(global_volume.to_sample());
+
             for (buf_sample, output_sample) in buf_frame.iter_mut().zip(output_frame.iter()) {
                 *buf_sample = output_sample.to_sample();
             }
@@ -417,7 +444,11 @@ impl AudioBackend for CpalAudioBackend {
             .any(|(_, instance)| instance.handle == handle && instance.active)
     }
 
-    fn tick(&mut self) {}
+    fn tick(&mut self, _dt: f64) {}
+
+    fn set_global_volume(&mut self, volume: f32) {
+        *self.global_volume.lock().unwrap() = volume;
+    }
 }
 
 /// A dummy wrapper struct to implement `AsRef<[u8]>` for `Arc<Vec<u8>`.
@@ -1,7 +1,10 @@
 //! Custom event type for desktop ruffle
 
+use winit::window::WindowId;
+
 /// User-defined events.
 pub enum RuffleEvent {
-    /// Indicates that one or more tasks are ready to poll on our executor.
-    TaskPoll,
+    /// Indicates that one or more tasks are ready to poll on the executor of the player in the
+    /// given window.
+    TaskPoll(WindowId),
 }
@@ -0,0 +1,129 @@
+//! Rendering frames of a movie headlessly and saving them as images.
+
+use crate::trace::DesktopLogBackend;
+use crate::Error;
+use ruffle_core::backend::{
+    audio::NullAudioBackend,
+    input::NullInputBackend,
+    navigator::{NullExecutor, NullNavigatorBackend},
+    storage::MemoryStorageBackend,
+    ui::NullUiBackend,
+};
+use ruffle_core::tag_utils::SwfMovie;
+use ruffle_core::Player;
+use ruffle_render_wgpu::{Descriptors, WgpuRenderBackend};
+use std::path::Path;
+use std::rc::Rc;
+
+/// How to run a movie when dumping its frames.
+pub struct DumpOptions {
+    /// The number of frames to save.
+    pub num_frames: u32,
+
+    /// The number of frames to run before the first saved frame.
+    pub skip: u32,
+
+    /// The size of the frames, before scaling. Defaults to the stage size of the movie.
+    pub size: Option<(u32, u32)>,
+
+    /// The factor the size of the frames is multiplied by.
+    pub scale: f64,
+
+    pub letterbox: bool,
+    pub pixel_perfect: bool,
+    pub trace_context: bool,
+    pub max_texture_size: Option<u32>,
+    pub msaa_sample_count: Option<u32>,
+}
+
+/// Runs a movie without a window, and saves its frames as `00001.png`, `00002.png`, etc. in
+/// `output_dir`.
+///
+/// The player doesn't depend on the wall clock, and its random numbers are always seeded the
+/// same way, so the same movie produces the same frames on every run.
+pub fn dump_frames(
+    movie_path: &Path,
+    output_dir: &Path,
+    options: &DumpOptions,
+) -> Result<(), Error> {
+    let movie = SwfMovie::from_path(movie_path)
+        .map_err(|e| format!("Couldn't load {}: {}", movie_path.display(), e))?;
+    let (width, height) = options
+        .size
+        .unwrap_or_else(|| (movie.width(), movie.height()));
+    let width = (f64::from(width) * options.scale).round().max(1.0) as u32;
+    let height = (f64::from(height) * options.scale).round().max(1.0) as u32;
+
+    let descriptors = match options.msaa_sample_count {
+        Some(msaa_sample_count) => Descriptors::with_msaa_sample_count(msaa_sample_count),
+        None => Descriptors::new(),
+    };
+    let mut descriptors = descriptors
+        .map_err(|e| format!("Couldn't find a graphics adapter to render with: {}", e))?;
+    if let Some(max_texture_size) = options.max_texture_size {
+        descriptors.set_max_texture_size(max_texture_size);
+    }
+    let renderer = WgpuRenderBackend::for_offscreen(Rc::new(descriptors), (width, height))?;
+
+    let (mut executor, channel) = NullExecutor::new();
+    let base_path = movie_path.parent().unwrap_or_else(|| Path::new(""));
+    let player = Player::new(
+        Box::new(renderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::with_base_path(base_path, channel)),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(DesktopLogBackend::new(options.trace_context)),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+    let mut player = player.lock().unwrap();
+    player.set_letterbox_enabled(options.letterbox);
+    player.set_pixel_perfect(options.pixel_perfect);
+    player.set_viewport_dimensions(width, height);
+
+    std::fs::create_dir_all(output_dir)?;
+    for frame in 0..options.skip + options.num_frames {
+        player.run_frame();
+        executor.poll_all()?;
+        if frame < options.skip {
+            continue;
+        }
+
+        player.render();
+        let image = player
+            .renderer()
+            .downcast_ref::<WgpuRenderBackend>()
+            .and_then(WgpuRenderBackend::capture_frame)
+            .ok_or("Couldn't read back the rendered frame")?;
+        let number = frame - options.skip + 1;
+        let path = output_dir.join(format!("{:05}.png", number));
+        image.save(&path)?;
+        eprintln!(
+            "Saved frame {}/{} to {}",
+            number,
+            options.num_frames,
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Parses a size given as `WIDTHxHEIGHT`, such as `640x480`.
+pub fn parse_size(size: &str) -> Result<(u32, u32), String> {
+    let error = || format!("Expected a size such as 640x480, found {}", size);
+    let mut parts = size.splitn(2, 'x');
+    let width = parts
+        .next()
+        .and_then(|w| w.parse().ok())
+        .ok_or_else(error)?;
+    let height = parts
+        .next()
+        .and_then(|h| h.parse().ok())
+        .ok_or_else(error)?;
+    if width == 0 || height == 0 {
+        return Err(error());
+    }
+    Ok((width, height))
+}
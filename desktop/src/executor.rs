@@ -3,11 +3,12 @@
 use crate::custom_event::RuffleEvent;
 use crate::task::Task;
 use generational_arena::{Arena, Index};
-use ruffle_core::backend::navigator::{Error, OwnedFuture};
+use ruffle_core::backend::navigator::SpawnedFuture;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::{Arc, Mutex, Weak};
 use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
 use winit::event_loop::EventLoopProxy;
+use winit::window::WindowId;
 
 /// Exeuctor context passed to event sources.
 ///
@@ -125,7 +126,7 @@ pub struct GlutinAsyncExecutor {
     task_queue: Arena<Task>,
 
     /// Source of tasks sent to us by the `NavigatorBackend`.
-    channel: Receiver<OwnedFuture<(), Error>>,
+    channel: Receiver<SpawnedFuture>,
 
     /// Weak reference to ourselves.
     self_ref: Weak<Mutex<Self>>,
@@ -133,6 +134,9 @@ pub struct GlutinAsyncExecutor {
     /// Event injector for the main thread event loop.
     event_loop: EventLoopProxy<RuffleEvent>,
 
+    /// The window of the player whose tasks we run.
+    window_id: WindowId,
+
     /// Whether or not we have already queued a `TaskPoll` event.
     waiting_for_poll: bool,
 }
@@ -144,13 +148,15 @@ impl GlutinAsyncExecutor {
     /// to spawn new tasks.
     pub fn new(
         event_loop: EventLoopProxy<RuffleEvent>,
-    ) -> (Arc<Mutex<Self>>, Sender<OwnedFuture<(), Error>>) {
+        window_id: WindowId,
+    ) -> (Arc<Mutex<Self>>, Sender<SpawnedFuture>) {
         let (send, recv) = channel();
         let new_self = Arc::new(Mutex::new(Self {
             task_queue: Arena::new(),
             channel: recv,
             self_ref: Weak::new(),
             event_loop,
+            window_id,
             waiting_for_poll: false,
         }));
         let self_ref = Arc::downgrade(&new_self);
@@ -160,19 +166,27 @@ impl GlutinAsyncExecutor {
         (new_self, send)
     }
 
+    /// Move all newly spawned futures into the task queue.
+    fn flush_channel(&mut self) {
+        while let Ok((fut, token)) = self.channel.try_recv() {
+            self.task_queue.insert(Task::from_future(fut, token));
+        }
+    }
+
     /// Poll all `Ready` futures.
+    ///
+    /// Cancelled tasks are dropped without being polled.
     pub fn poll_all(&mut self) {
         self.waiting_for_poll = false;
-
-        while let Ok(fut) = self.channel.try_recv() {
-            self.task_queue.insert(Task::from_future(fut));
-        }
+        self.flush_channel();
 
         let self_ref = self.self_ref.upgrade().expect("active self-reference");
         let mut completed_tasks = vec![];
 
         for (index, task) in self.task_queue.iter_mut() {
-            if task.is_ready() {
+            if task.is_cancelled() {
+                completed_tasks.push(index);
+            } else if task.is_ready() {
                 let handle = TaskHandle::for_task(index, self_ref.clone());
                 let waker = handle.waker();
                 let mut context = Context::from_waker(&waker);
@@ -195,14 +209,36 @@ impl GlutinAsyncExecutor {
         }
     }
 
+    /// Drop all cancelled tasks, returning how many there were.
+    pub fn drain(&mut self) -> usize {
+        self.flush_channel();
+
+        let cancelled_tasks: Vec<Index> = self
+            .task_queue
+            .iter()
+            .filter(|(_, task)| task.is_cancelled())
+            .map(|(index, _)| index)
+            .collect();
+
+        for index in &cancelled_tasks {
+            self.task_queue.remove(*index);
+        }
+
+        cancelled_tasks.len()
+    }
+
     /// Mark a task as ready to proceed.
     fn wake(&mut self, task: Index) {
         if let Some(task) = self.task_queue.get_mut(task) {
-            if !task.is_completed() {
+            if !task.is_completed() && !task.is_cancelled() {
                 if !self.waiting_for_poll {
                     self.waiting_for_poll = true;
 
-                    if self.event_loop.send_event(RuffleEvent::TaskPoll).is_err() {
+                    if self
+                        .event_loop
+                        .send_event(RuffleEvent::TaskPoll(self.window_id))
+                        .is_err()
+                    {
                         log::warn!("A task was queued on an event loop that has already ended. It will not be polled.");
                     }
                 } else {
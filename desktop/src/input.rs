@@ -1,24 +1,21 @@
-use ruffle_core::backend::input::{InputBackend, MouseCursor};
+use ruffle_core::backend::input::InputBackend;
 use ruffle_core::events::{KeyCode, PlayerEvent};
-use std::collections::HashSet;
-use std::rc::Rc;
-use winit::event::{ElementState, VirtualKeyCode, WindowEvent};
-use winit::window::Window;
+use std::collections::HashMap;
+use winit::event::{ElementState, ScanCode, VirtualKeyCode, WindowEvent};
 
 pub struct WinitInputBackend {
-    keys_down: HashSet<VirtualKeyCode>,
-    window: Rc<Window>,
-    cursor_visible: bool,
+    /// The Flash key codes of the keys currently held, by scancode.
+    keys_down: HashMap<ScanCode, KeyCode>,
     last_key: KeyCode,
+    last_char: Option<char>,
 }
 
 impl WinitInputBackend {
-    pub fn new(window: Rc<Window>) -> Self {
+    pub fn new() -> Self {
         Self {
-            keys_down: HashSet::new(),
-            cursor_visible: true,
+            keys_down: HashMap::new(),
             last_key: KeyCode::Unknown,
-            window,
+            last_char: None,
         }
     }
 
@@ -27,29 +24,31 @@ impl WinitInputBackend {
         match event {
             WindowEvent::KeyboardInput { input, .. } => match input.state {
                 ElementState::Pressed => {
-                    if let Some(key) = input.virtual_keycode {
-                        self.keys_down.insert(key);
-                        if let Some(key_code) = winit_to_ruffle_key_code(key) {
-                            self.last_key = key_code;
-                            return Some(PlayerEvent::KeyDown { key_code });
-                        } else {
-                            self.last_key = KeyCode::Unknown;
-                        }
+                    // winit's virtual key codes already follow the keyboard layout.
+                    let key_code = input
+                        .virtual_keycode
+                        .and_then(winit_to_ruffle_key_code)
+                        .or_else(|| scancode_to_ruffle_key_code(input.scancode));
+                    self.last_char = None;
+                    if let Some(key_code) = key_code {
+                        self.keys_down.insert(input.scancode, key_code);
+                        self.last_key = key_code;
+                        return Some(PlayerEvent::KeyDown { key_code });
+                    } else {
+                        self.last_key = KeyCode::Unknown;
                     }
                 }
                 ElementState::Released => {
-                    if let Some(key) = input.virtual_keycode {
-                        self.keys_down.remove(&key);
-                        if let Some(key_code) = winit_to_ruffle_key_code(key) {
-                            self.last_key = key_code;
-                            return Some(PlayerEvent::KeyUp { key_code });
-                        } else {
-                            self.last_key = KeyCode::Unknown;
-                        }
+                    if let Some(key_code) = self.keys_down.remove(&input.scancode) {
+                        self.last_key = key_code;
+                        return Some(PlayerEvent::KeyUp { key_code });
+                    } else {
+                        self.last_key = KeyCode::Unknown;
                     }
                 }
             },
             WindowEvent::ReceivedCharacter(codepoint) => {
+                self.last_char = Some(codepoint);
                 return Some(PlayerEvent::TextInput { codepoint });
             }
             _ => (),
@@ -58,143 +57,23 @@ impl WinitInputBackend {
     }
 }
 
+impl Default for WinitInputBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl InputBackend for WinitInputBackend {
     fn is_key_down(&self, key: KeyCode) -> bool {
-        match key {
-            KeyCode::Unknown => false,
-            KeyCode::Backspace => self.keys_down.contains(&VirtualKeyCode::Back),
-            KeyCode::Return => self.keys_down.contains(&VirtualKeyCode::Return),
-            KeyCode::Shift => {
-                self.keys_down.contains(&VirtualKeyCode::LShift)
-                    || self.keys_down.contains(&VirtualKeyCode::RShift)
-            }
-            KeyCode::Control => {
-                self.keys_down.contains(&VirtualKeyCode::LControl)
-                    || self.keys_down.contains(&VirtualKeyCode::RControl)
-            }
-            KeyCode::Alt => {
-                self.keys_down.contains(&VirtualKeyCode::LAlt)
-                    || self.keys_down.contains(&VirtualKeyCode::RAlt)
-            }
-            KeyCode::CapsLock => self.keys_down.contains(&VirtualKeyCode::Capital),
-            KeyCode::Escape => self.keys_down.contains(&VirtualKeyCode::Escape),
-            KeyCode::Space => self.keys_down.contains(&VirtualKeyCode::Space),
-            KeyCode::Key0 => self.keys_down.contains(&VirtualKeyCode::Key0),
-            KeyCode::Key1 => self.keys_down.contains(&VirtualKeyCode::Key1),
-            KeyCode::Key2 => self.keys_down.contains(&VirtualKeyCode::Key2),
-            KeyCode::Key3 => self.keys_down.contains(&VirtualKeyCode::Key3),
-            KeyCode::Key4 => self.keys_down.contains(&VirtualKeyCode::Key4),
-            KeyCode::Key5 => self.keys_down.contains(&VirtualKeyCode::Key5),
-            KeyCode::Key6 => self.keys_down.contains(&VirtualKeyCode::Key6),
-            KeyCode::Key7 => self.keys_down.contains(&VirtualKeyCode::Key7),
-            KeyCode::Key8 => self.keys_down.contains(&VirtualKeyCode::Key8),
-            KeyCode::Key9 => self.keys_down.contains(&VirtualKeyCode::Key9),
-            KeyCode::A => self.keys_down.contains(&VirtualKeyCode::A),
-            KeyCode::B => self.keys_down.contains(&VirtualKeyCode::B),
-            KeyCode::C => self.keys_down.contains(&VirtualKeyCode::C),
-            KeyCode::D => self.keys_down.contains(&VirtualKeyCode::D),
-            KeyCode::E => self.keys_down.contains(&VirtualKeyCode::E),
-            KeyCode::F => self.keys_down.contains(&VirtualKeyCode::F),
-            KeyCode::G => self.keys_down.contains(&VirtualKeyCode::G),
-            KeyCode::H => self.keys_down.contains(&VirtualKeyCode::H),
-            KeyCode::I => self.keys_down.contains(&VirtualKeyCode::I),
-            KeyCode::J => self.keys_down.contains(&VirtualKeyCode::J),
-            KeyCode::K => self.keys_down.contains(&VirtualKeyCode::K),
-            KeyCode::L => self.keys_down.contains(&VirtualKeyCode::L),
-            KeyCode::M => self.keys_down.contains(&VirtualKeyCode::M),
-            KeyCode::N => self.keys_down.contains(&VirtualKeyCode::N),
-            KeyCode::O => self.keys_down.contains(&VirtualKeyCode::O),
-            KeyCode::P => self.keys_down.contains(&VirtualKeyCode::P),
-            KeyCode::Q => self.keys_down.contains(&VirtualKeyCode::Q),
-            KeyCode::R => self.keys_down.contains(&VirtualKeyCode::R),
-            KeyCode::S => self.keys_down.contains(&VirtualKeyCode::S),
-            KeyCode::T => self.keys_down.contains(&VirtualKeyCode::T),
-            KeyCode::U => self.keys_down.contains(&VirtualKeyCode::U),
-            KeyCode::V => self.keys_down.contains(&VirtualKeyCode::V),
-            KeyCode::W => self.keys_down.contains(&VirtualKeyCode::W),
-            KeyCode::X => self.keys_down.contains(&VirtualKeyCode::X),
-            KeyCode::Y => self.keys_down.contains(&VirtualKeyCode::Y),
-            KeyCode::Z => self.keys_down.contains(&VirtualKeyCode::Z),
-            KeyCode::Semicolon => self.keys_down.contains(&VirtualKeyCode::Semicolon),
-            KeyCode::Equals => self.keys_down.contains(&VirtualKeyCode::Equals),
-            KeyCode::Comma => self.keys_down.contains(&VirtualKeyCode::Comma),
-            KeyCode::Minus => self.keys_down.contains(&VirtualKeyCode::Minus),
-            KeyCode::Period => self.keys_down.contains(&VirtualKeyCode::Period),
-            KeyCode::Slash => self.keys_down.contains(&VirtualKeyCode::Slash),
-            KeyCode::Grave => self.keys_down.contains(&VirtualKeyCode::Grave),
-            KeyCode::LBracket => self.keys_down.contains(&VirtualKeyCode::LBracket),
-            KeyCode::Backslash => self.keys_down.contains(&VirtualKeyCode::Backslash),
-            KeyCode::RBracket => self.keys_down.contains(&VirtualKeyCode::RBracket),
-            KeyCode::Apostrophe => self.keys_down.contains(&VirtualKeyCode::Apostrophe),
-            KeyCode::Numpad0 => self.keys_down.contains(&VirtualKeyCode::Numpad0),
-            KeyCode::Numpad1 => self.keys_down.contains(&VirtualKeyCode::Numpad1),
-            KeyCode::Numpad2 => self.keys_down.contains(&VirtualKeyCode::Numpad2),
-            KeyCode::Numpad3 => self.keys_down.contains(&VirtualKeyCode::Numpad3),
-            KeyCode::Numpad4 => self.keys_down.contains(&VirtualKeyCode::Numpad4),
-            KeyCode::Numpad5 => self.keys_down.contains(&VirtualKeyCode::Numpad5),
-            KeyCode::Numpad6 => self.keys_down.contains(&VirtualKeyCode::Numpad6),
-            KeyCode::Numpad7 => self.keys_down.contains(&VirtualKeyCode::Numpad7),
-            KeyCode::Numpad8 => self.keys_down.contains(&VirtualKeyCode::Numpad8),
-            KeyCode::Numpad9 => self.keys_down.contains(&VirtualKeyCode::Numpad9),
-            KeyCode::Multiply => self.keys_down.contains(&VirtualKeyCode::Multiply),
-            KeyCode::Plus => self.keys_down.contains(&VirtualKeyCode::Add),
-            KeyCode::NumpadMinus => self.keys_down.contains(&VirtualKeyCode::Subtract),
-            KeyCode::NumpadPeriod => self.keys_down.contains(&VirtualKeyCode::Decimal),
-            KeyCode::NumpadSlash => self.keys_down.contains(&VirtualKeyCode::Divide),
-            KeyCode::PgUp => self.keys_down.contains(&VirtualKeyCode::PageUp),
-            KeyCode::PgDown => self.keys_down.contains(&VirtualKeyCode::PageDown),
-            KeyCode::End => self.keys_down.contains(&VirtualKeyCode::End),
-            KeyCode::Home => self.keys_down.contains(&VirtualKeyCode::Home),
-            KeyCode::Left => self.keys_down.contains(&VirtualKeyCode::Left),
-            KeyCode::Up => self.keys_down.contains(&VirtualKeyCode::Up),
-            KeyCode::Right => self.keys_down.contains(&VirtualKeyCode::Right),
-            KeyCode::Down => self.keys_down.contains(&VirtualKeyCode::Down),
-            KeyCode::Insert => self.keys_down.contains(&VirtualKeyCode::Insert),
-            KeyCode::Delete => self.keys_down.contains(&VirtualKeyCode::Delete),
-            KeyCode::Pause => self.keys_down.contains(&VirtualKeyCode::Pause),
-            KeyCode::ScrollLock => self.keys_down.contains(&VirtualKeyCode::Scroll),
-            KeyCode::F1 => self.keys_down.contains(&VirtualKeyCode::F1),
-            KeyCode::F2 => self.keys_down.contains(&VirtualKeyCode::F2),
-            KeyCode::F3 => self.keys_down.contains(&VirtualKeyCode::F3),
-            KeyCode::F4 => self.keys_down.contains(&VirtualKeyCode::F4),
-            KeyCode::F5 => self.keys_down.contains(&VirtualKeyCode::F5),
-            KeyCode::F6 => self.keys_down.contains(&VirtualKeyCode::F6),
-            KeyCode::F7 => self.keys_down.contains(&VirtualKeyCode::F7),
-            KeyCode::F8 => self.keys_down.contains(&VirtualKeyCode::F8),
-            KeyCode::F9 => self.keys_down.contains(&VirtualKeyCode::F9),
-            KeyCode::F10 => self.keys_down.contains(&VirtualKeyCode::F10),
-            KeyCode::F11 => self.keys_down.contains(&VirtualKeyCode::F11),
-            KeyCode::F12 => self.keys_down.contains(&VirtualKeyCode::F12),
-        }
+        key != KeyCode::Unknown && self.keys_down.values().any(|&down| down == key)
     }
 
     fn get_last_key_code(&self) -> KeyCode {
         self.last_key
     }
 
-    fn mouse_visible(&self) -> bool {
-        self.cursor_visible
-    }
-
-    fn hide_mouse(&mut self) {
-        self.window.set_cursor_visible(false);
-        self.cursor_visible = false;
-    }
-
-    fn show_mouse(&mut self) {
-        self.window.set_cursor_visible(true);
-        self.cursor_visible = true;
-    }
-
-    fn set_mouse_cursor(&mut self, cursor: MouseCursor) {
-        use winit::window::CursorIcon;
-        let icon = match cursor {
-            MouseCursor::Arrow => CursorIcon::Arrow,
-            MouseCursor::Hand => CursorIcon::Hand,
-            MouseCursor::IBeam => CursorIcon::Text,
-            MouseCursor::Grab => CursorIcon::Grab,
-        };
-        self.window.set_cursor_icon(icon);
+    fn get_last_key_char(&self) -> Option<char> {
+        self.last_char
     }
 }
 
@@ -203,7 +82,8 @@ impl InputBackend for WinitInputBackend {
 fn winit_to_ruffle_key_code(key_code: VirtualKeyCode) -> Option<KeyCode> {
     let out = match key_code {
         VirtualKeyCode::Back => KeyCode::Backspace,
-        VirtualKeyCode::Return => KeyCode::Return,
+        VirtualKeyCode::Tab => KeyCode::Tab,
+        VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => KeyCode::Return,
         VirtualKeyCode::LShift | VirtualKeyCode::RShift => KeyCode::Shift,
         VirtualKeyCode::LControl | VirtualKeyCode::RControl => KeyCode::Control,
         VirtualKeyCode::LAlt | VirtualKeyCode::RAlt => KeyCode::Alt,
@@ -283,6 +163,7 @@ fn winit_to_ruffle_key_code(key_code: VirtualKeyCode) -> Option<KeyCode> {
         VirtualKeyCode::Insert => KeyCode::Insert,
         VirtualKeyCode::Delete => KeyCode::Delete,
         VirtualKeyCode::Pause => KeyCode::Pause,
+        VirtualKeyCode::Numlock => KeyCode::NumLock,
         VirtualKeyCode::Scroll => KeyCode::ScrollLock,
         VirtualKeyCode::F1 => KeyCode::F1,
         VirtualKeyCode::F2 => KeyCode::F2,
@@ -300,3 +181,104 @@ fn winit_to_ruffle_key_code(key_code: VirtualKeyCode) -> Option<KeyCode> {
     };
     Some(out)
 }
+
+/// Converts a PC keyboard scancode into a Ruffle `KeyCode`, for keys that winit has no
+/// `VirtualKeyCode` for.
+/// Returns `None` if there is no matching Flash key code.
+#[cfg(not(target_os = "macos"))]
+fn scancode_to_ruffle_key_code(scancode: ScanCode) -> Option<KeyCode> {
+    let out = match scancode {
+        0x01 => KeyCode::Escape,
+        0x02 => KeyCode::Key1,
+        0x03 => KeyCode::Key2,
+        0x04 => KeyCode::Key3,
+        0x05 => KeyCode::Key4,
+        0x06 => KeyCode::Key5,
+        0x07 => KeyCode::Key6,
+        0x08 => KeyCode::Key7,
+        0x09 => KeyCode::Key8,
+        0x0A => KeyCode::Key9,
+        0x0B => KeyCode::Key0,
+        0x0C => KeyCode::Minus,
+        0x0D => KeyCode::Equals,
+        0x0E => KeyCode::Backspace,
+        0x0F => KeyCode::Tab,
+        0x10 => KeyCode::Q,
+        0x11 => KeyCode::W,
+        0x12 => KeyCode::E,
+        0x13 => KeyCode::R,
+        0x14 => KeyCode::T,
+        0x15 => KeyCode::Y,
+        0x16 => KeyCode::U,
+        0x17 => KeyCode::I,
+        0x18 => KeyCode::O,
+        0x19 => KeyCode::P,
+        0x1A => KeyCode::LBracket,
+        0x1B => KeyCode::RBracket,
+        0x1C => KeyCode::Return,
+        0x1D => KeyCode::Control,
+        0x1E => KeyCode::A,
+        0x1F => KeyCode::S,
+        0x20 => KeyCode::D,
+        0x21 => KeyCode::F,
+        0x22 => KeyCode::G,
+        0x23 => KeyCode::H,
+        0x24 => KeyCode::J,
+        0x25 => KeyCode::K,
+        0x26 => KeyCode::L,
+        0x27 => KeyCode::Semicolon,
+        0x28 => KeyCode::Apostrophe,
+        0x29 => KeyCode::Grave,
+        0x2A | 0x36 => KeyCode::Shift,
+        0x2B => KeyCode::Backslash,
+        0x2C => KeyCode::Z,
+        0x2D => KeyCode::X,
+        0x2E => KeyCode::C,
+        0x2F => KeyCode::V,
+        0x30 => KeyCode::B,
+        0x31 => KeyCode::N,
+        0x32 => KeyCode::M,
+        0x33 => KeyCode::Comma,
+        0x34 => KeyCode::Period,
+        0x35 => KeyCode::Slash,
+        0x37 => KeyCode::Multiply,
+        0x38 => KeyCode::Alt,
+        0x39 => KeyCode::Space,
+        0x3A => KeyCode::CapsLock,
+        0x3B => KeyCode::F1,
+        0x3C => KeyCode::F2,
+        0x3D => KeyCode::F3,
+        0x3E => KeyCode::F4,
+        0x3F => KeyCode::F5,
+        0x40 => KeyCode::F6,
+        0x41 => KeyCode::F7,
+        0x42 => KeyCode::F8,
+        0x43 => KeyCode::F9,
+        0x44 => KeyCode::F10,
+        0x45 => KeyCode::NumLock,
+        0x46 => KeyCode::ScrollLock,
+        0x47 => KeyCode::Numpad7,
+        0x48 => KeyCode::Numpad8,
+        0x49 => KeyCode::Numpad9,
+        0x4A => KeyCode::NumpadMinus,
+        0x4B => KeyCode::Numpad4,
+        0x4C => KeyCode::Numpad5,
+        0x4D => KeyCode::Numpad6,
+        0x4E => KeyCode::Plus,
+        0x4F => KeyCode::Numpad1,
+        0x50 => KeyCode::Numpad2,
+        0x51 => KeyCode::Numpad3,
+        0x52 => KeyCode::Numpad0,
+        0x53 => KeyCode::NumpadPeriod,
+        0x57 => KeyCode::F11,
+        0x58 => KeyCode::F12,
+        _ => return None,
+    };
+    Some(out)
+}
+
+/// macOS scancodes don't follow the PC layout, and winit maps every key it knows of.
+#[cfg(target_os = "macos")]
+fn scancode_to_ruffle_key_code(_scancode: ScanCode) -> Option<KeyCode> {
+    None
+}
@@ -0,0 +1,44 @@
+//! Time zone and locale of the desktop.
+
+use chrono::{Local, NaiveDateTime, Offset, TimeZone};
+use ruffle_core::backend::locale::TimeZoneProvider;
+
+/// The time zone of the operating system.
+pub struct LocalTimeZone;
+
+impl TimeZoneProvider for LocalTimeZone {
+    fn utc_offset(&self, utc_time: f64) -> i32 {
+        let millis = utc_time as i64;
+        let time = if utc_time.is_finite() {
+            NaiveDateTime::from_timestamp_opt(
+                millis.div_euclid(1000),
+                (millis.rem_euclid(1000) * 1_000_000) as u32,
+            )
+        } else {
+            None
+        };
+        let offset = match time {
+            Some(time) => Local.offset_from_utc_datetime(&time).fix(),
+            None => Local::now().offset().fix(),
+        };
+        offset.local_minus_utc() / 60
+    }
+}
+
+/// The locale of the user, as a language tag such as `en-US`, taken from the usual environment
+/// variables.
+pub fn system_locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| {
+            // Values look like `de_DE.UTF-8` or `sr_RS@latin`.
+            let locale = value.split(|c| c == '.' || c == '@').next()?;
+            if locale.is_empty() || locale == "C" || locale == "POSIX" {
+                None
+            } else {
+                Some(locale.replace('_', "-"))
+            }
+        })
+}
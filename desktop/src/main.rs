@@ -2,34 +2,600 @@
 
 mod audio;
 mod custom_event;
+mod dump;
 mod executor;
 mod input;
+mod locale;
 mod navigator;
 mod task;
+mod trace;
+mod ui;
+mod watch;
 
 use crate::custom_event::RuffleEvent;
+use crate::dump::{dump_frames, parse_size, DumpOptions};
 use crate::executor::GlutinAsyncExecutor;
+use crate::locale::{system_locale, LocalTimeZone};
+use crate::trace::DesktopLogBackend;
+use crate::watch::MovieWatcher;
 use ruffle_core::{
-    backend::audio::{AudioBackend, NullAudioBackend},
-    Player,
+    backend::{
+        audio::{AudioBackend, NullAudioBackend},
+        storage::MemoryStorageBackend,
+    },
+    events::KeyModifiers,
+    inspect::inspect_movie,
+    Player, NEWEST_PLAYER_VERSION,
 };
-use ruffle_render_wgpu::WgpuRenderBackend;
-use std::path::PathBuf;
-use std::time::Instant;
+use ruffle_render_wgpu::{Descriptors, WgpuRenderBackend};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use structopt::StructOpt;
 
 use ruffle_core::tag_utils::SwfMovie;
 use std::rc::Rc;
 use winit::dpi::{LogicalSize, PhysicalPosition};
-use winit::event::{ElementState, MouseButton, WindowEvent};
-use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::WindowBuilder;
+use winit::event::{
+    ElementState, KeyboardInput, ModifiersState, MouseButton, VirtualKeyCode, WindowEvent,
+};
+use winit::event_loop::{ControlFlow, EventLoop, EventLoopProxy, EventLoopWindowTarget};
+use winit::window::{Window, WindowBuilder, WindowId};
+
+type Error = Box<dyn std::error::Error>;
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "basic")]
 struct Opt {
+    /// The SWF files to play, each in its own window. Projector executables with an embedded
+    /// SWF file can be played as well. If omitted, a SWF file can be dropped onto the window
+    /// instead.
     #[structopt(name = "FILE", parse(from_os_str))]
-    input_path: PathBuf,
+    input_paths: Vec<PathBuf>,
+
+    /// Show content in the margins around the stage, rather than covering them with black bars.
+    #[structopt(long = "no-letterbox")]
+    no_letterbox: bool,
+
+    /// Only scale the stage by whole numbers, and draw bitmaps without smoothing, so that
+    /// pixel art stays crisp. Combine with --msaa 1 to also draw vector edges without
+    /// anti-aliasing.
+    #[structopt(long = "pixel-perfect")]
+    pixel_perfect: bool,
+
+    /// Reload each movie when its file changes, keeping the size of its window.
+    #[structopt(long = "watch")]
+    watch: bool,
+
+    /// With --watch, send the reloaded movie to the frame the old one was on, or to its last
+    /// frame if it is now shorter.
+    #[structopt(long = "keep-frame")]
+    keep_frame: bool,
+
+    /// Print a JSON summary of the contents of each SWF file instead of playing them.
+    #[structopt(long = "inspect")]
+    inspect: bool,
+
+    /// The largest width or height of a texture supported by the graphics adapter. Larger
+    /// bitmaps are downscaled to fit. Defaults to a size supported by most adapters.
+    #[structopt(long = "max-texture-size")]
+    max_texture_size: Option<u32>,
+
+    /// The number of samples per pixel used for anti-aliasing, or 1 to disable it. Counts the
+    /// graphics adapter doesn't support are replaced with the nearest supported one. Defaults
+    /// to 4. F8 turns anti-aliasing on and off while playing.
+    #[structopt(long = "msaa")]
+    msaa_sample_count: Option<u32>,
+
+    /// Prefix each traced message with the movie, clip and frame that traced it.
+    #[structopt(long = "trace-context")]
+    trace_context: bool,
+
+    /// Instead of opening a window, run the movie headlessly and save this many frames as
+    /// numbered PNG files. The FILE arguments are then the directory to save the frames in,
+    /// followed by the movie.
+    #[structopt(long = "dump-frames")]
+    dump_frames: Option<u32>,
+
+    /// The number of frames to run before the first frame saved with --dump-frames.
+    #[structopt(long = "skip", default_value = "0")]
+    skip: u32,
+
+    /// The size of the frames saved with --dump-frames, such as 640x480. Defaults to the stage
+    /// size of the movie.
+    #[structopt(long = "size", parse(try_from_str = parse_size))]
+    size: Option<(u32, u32)>,
+
+    /// The factor the size of the frames saved with --dump-frames is multiplied by.
+    #[structopt(long = "scale", default_value = "1")]
+    scale: f64,
+}
+
+/// The window size used when no movie has been loaded yet.
+const DEFAULT_WINDOW_SIZE: (u32, u32) = (550, 400);
+
+/// The amount the host volume changes by when using the volume keybindings.
+const VOLUME_STEP: f32 = 0.1;
+
+/// Whether the given key is used in combination with Ctrl to control the host volume.
+/// Ctrl+M toggles mute, and Ctrl+Up/Down changes the volume.
+fn is_volume_key(key: VirtualKeyCode) -> bool {
+    match key {
+        VirtualKeyCode::M | VirtualKeyCode::Up | VirtualKeyCode::Down => true,
+        _ => false,
+    }
+}
+
+/// Window title for the given movie path.
+fn window_title(path: Option<&Path>) -> String {
+    match path {
+        Some(path) => format!(
+            "Ruffle - {}",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ),
+        None => "Ruffle - drop a SWF file here to play it".to_string(),
+    }
+}
+
+/// A window playing a movie in its own player.
+///
+/// Each window has its own player and executor, and is ticked at the frame rate of its own movie.
+/// The renderers of all windows share one graphics device.
+struct PlayerWindow {
+    window: Rc<Window>,
+    player: Arc<Mutex<Player>>,
+    executor: Arc<Mutex<GlutinAsyncExecutor>>,
+    mouse_pos: PhysicalPosition<f64>,
+    modifiers: ModifiersState,
+    has_movie: bool,
+    /// Files dropped during the current batch of events, and movies waiting to be played next.
+    dropped_files: Vec<PathBuf>,
+    movie_queue: VecDeque<PathBuf>,
+    /// The state saved with F5, which F9 restores.
+    quick_save: Option<Vec<u8>>,
+    /// The sample count that F8 turns anti-aliasing back on with.
+    msaa_sample_count: u32,
+    /// Whether to reload movies when their files change, and whether to keep the frame then.
+    watch: bool,
+    keep_frame: bool,
+    watcher: Option<MovieWatcher>,
+    time: Instant,
+    next_frame_time: Instant,
+}
+
+impl PlayerWindow {
+    fn new(
+        input_path: Option<&Path>,
+        letterbox: bool,
+        pixel_perfect: bool,
+        trace_context: bool,
+        descriptors: Rc<Descriptors>,
+        window_target: &EventLoopWindowTarget<RuffleEvent>,
+        event_loop: EventLoopProxy<RuffleEvent>,
+    ) -> Result<Self, Error> {
+        // When launched without a movie, start with an empty one and wait for a file to be dropped.
+        let (movie, window_size) = match input_path {
+            Some(input_path) => {
+                let movie = SwfMovie::from_path(input_path)?;
+                let size = (movie.width(), movie.height());
+                (movie, size)
+            }
+            None => (SwfMovie::empty(NEWEST_PLAYER_VERSION), DEFAULT_WINDOW_SIZE),
+        };
+
+        let window = Rc::new(
+            WindowBuilder::new()
+                .with_title(window_title(input_path))
+                .with_inner_size(LogicalSize::new(window_size.0, window_size.1))
+                .build(window_target)?,
+        );
+
+        let audio: Box<dyn AudioBackend> = match audio::CpalAudioBackend::new() {
+            Ok(audio) => Box::new(audio),
+            Err(e) => {
+                log::error!("Unable to create audio device: {}", e);
+                Box::new(NullAudioBackend::new())
+            }
+        };
+        let msaa_sample_count = match descriptors.msaa_sample_count() {
+            1 => 4,
+            msaa_sample_count => msaa_sample_count,
+        };
+        let size = window.inner_size();
+        let renderer = Box::new(WgpuRenderBackend::for_window(
+            descriptors,
+            window.as_ref(),
+            (size.width, size.height),
+        )?);
+        let (executor, chan) = GlutinAsyncExecutor::new(event_loop.clone(), window.id());
+        let navigator = Box::new(navigator::ExternalNavigatorBackend::with_base_path(
+            input_path
+                .and_then(Path::parent)
+                .unwrap_or_else(|| Path::new("")),
+            chan,
+            Arc::downgrade(&executor),
+            event_loop,
+            window.id(),
+        )); //TODO: actually implement this backend type
+        let input = Box::new(input::WinitInputBackend::new());
+        let ui = Box::new(ui::DesktopUiBackend::new(window.clone()));
+        let log = Box::new(DesktopLogBackend::new(trace_context));
+        // TODO: Save shared objects to disk.
+        let storage = Box::new(MemoryStorageBackend::new());
+        let player = Player::new(renderer, audio, navigator, input, ui, log, storage, movie)?;
+        player.lock().unwrap().set_is_playing(true); // Desktop player will auto-play.
+        player.lock().unwrap().set_letterbox_enabled(letterbox);
+        player.lock().unwrap().set_pixel_perfect(pixel_perfect);
+        player
+            .lock()
+            .unwrap()
+            .set_time_zone(Box::new(LocalTimeZone));
+        if let Some(locale) = system_locale() {
+            player.lock().unwrap().set_locale(locale);
+        }
+
+        player
+            .lock()
+            .unwrap()
+            .set_viewport_dimensions(size.width, size.height);
+
+        Ok(Self {
+            window,
+            player,
+            executor,
+            mouse_pos: PhysicalPosition::new(0.0, 0.0),
+            modifiers: ModifiersState::empty(),
+            has_movie: input_path.is_some(),
+            dropped_files: Vec::new(),
+            movie_queue: VecDeque::new(),
+            quick_save: None,
+            msaa_sample_count,
+            watch: false,
+            keep_frame: false,
+            watcher: None,
+            time: Instant::now(),
+            next_frame_time: Instant::now(),
+        })
+    }
+
+    /// Reload the movie whenever its file changes, and any movie played next.
+    fn watch(&mut self, path: Option<&Path>, keep_frame: bool) {
+        self.watch = true;
+        self.keep_frame = keep_frame;
+        self.watcher = path.map(MovieWatcher::new);
+    }
+
+    /// Replace the movie currently playing with the SWF at the given path.
+    fn load_movie(&mut self, path: &Path) {
+        let movie = match SwfMovie::from_path(path) {
+            Ok(movie) => movie,
+            Err(e) => {
+                log::error!("Unable to load {}: {}", path.display(), e);
+                return;
+            }
+        };
+
+        self.window.set_title(&window_title(Some(path)));
+        self.window
+            .set_inner_size(LogicalSize::new(movie.width(), movie.height()));
+
+        let mut player_lock = self.player.lock().unwrap();
+        player_lock.set_root_movie(Arc::new(movie));
+        player_lock.set_is_playing(true);
+        self.window.request_redraw();
+        self.has_movie = true;
+        self.quick_save = None;
+        if self.watch {
+            self.watcher = Some(MovieWatcher::new(path));
+        }
+    }
+
+    /// Replace the movie with a new version of it from its file, keeping the window as it is.
+    fn reload_movie(&mut self, movie: SwfMovie) {
+        log::info!("Reloading the movie");
+        watch::reload_movie(&mut self.player.lock().unwrap(), movie, self.keep_frame);
+        self.window.request_redraw();
+        self.quick_save = None;
+    }
+
+    /// The time at which the window next needs to be ticked, to run a frame or to check the
+    /// movie file for changes.
+    fn next_tick_time(&self) -> Instant {
+        match &self.watcher {
+            Some(watcher) => self.next_frame_time.min(watcher.next_poll_time()),
+            None => self.next_frame_time,
+        }
+    }
+
+    /// Runs the player for the time elapsed since the last call, once all pending events have
+    /// been handled.
+    fn tick(&mut self) {
+        // Dropping several files at once sends one event per file.
+        // Play the first one, and queue the rest behind the "next movie" key.
+        if !self.dropped_files.is_empty() {
+            let mut files = std::mem::take(&mut self.dropped_files).into_iter();
+            let first = files.next().unwrap();
+            if self.has_movie {
+                log::info!("Replacing the current movie with {}", first.display());
+            }
+            self.load_movie(&first);
+            self.movie_queue = files.collect();
+        }
+
+        let new_time = Instant::now();
+        if let Some(movie) = self
+            .watcher
+            .as_mut()
+            .and_then(|watcher| watcher.poll(new_time))
+        {
+            self.reload_movie(movie);
+        }
+
+        let dt = new_time.duration_since(self.time).as_micros();
+        if dt > 0 {
+            self.time = new_time;
+            let mut player_lock = self.player.lock().unwrap();
+            player_lock.tick(dt as f64 / 1000.0);
+            self.next_frame_time = new_time + player_lock.time_til_next_frame();
+            if player_lock.needs_render() {
+                self.window.request_redraw();
+            }
+        }
+    }
+
+    fn render(&mut self) {
+        // The window may be asking to be redrawn because its contents were lost.
+        self.player.lock().unwrap().force_render();
+    }
+
+    /// Saves the stage as it is now to a PNG in the working directory.
+    fn save_screenshot(&mut self) {
+        let mut player_lock = self.player.lock().unwrap();
+        if let Some(renderer) = player_lock
+            .renderer_mut()
+            .downcast_mut::<WgpuRenderBackend>()
+        {
+            renderer.capture_next_frame();
+        }
+        player_lock.force_render();
+        let image = match player_lock
+            .renderer()
+            .downcast_ref::<WgpuRenderBackend>()
+            .and_then(WgpuRenderBackend::capture_frame)
+        {
+            Some(image) => image,
+            None => {
+                log::error!("Unable to read back the frame for a screenshot");
+                return;
+            }
+        };
+
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        let path = PathBuf::from(format!("screenshot-{}.png", seconds));
+        match image.save(&path) {
+            Ok(()) => log::info!("Saved screenshot to {}", path.display()),
+            Err(e) => log::error!("Unable to save screenshot to {}: {}", path.display(), e),
+        }
+    }
+
+    /// Turns anti-aliasing off, or back on with the sample count it was last on with.
+    fn toggle_msaa(&mut self) {
+        let mut player_lock = self.player.lock().unwrap();
+        if let Some(renderer) = player_lock
+            .renderer_mut()
+            .downcast_mut::<WgpuRenderBackend>()
+        {
+            let old_sample_count = renderer.msaa_sample_count();
+            let new_sample_count = if old_sample_count > 1 {
+                self.msaa_sample_count = old_sample_count;
+                1
+            } else {
+                self.msaa_sample_count
+            };
+            match renderer.set_msaa(new_sample_count) {
+                Ok(()) => log::info!("Set MSAA to {}x", renderer.msaa_sample_count()),
+                Err(e) => log::error!("Unable to change MSAA: {}", e),
+            }
+        }
+        player_lock.force_render();
+    }
+
+    fn poll_tasks(&mut self) {
+        self.executor
+            .lock()
+            .expect("active executor reference")
+            .poll_all();
+    }
+
+    fn handle_window_event(&mut self, event: WindowEvent) {
+        let player = &self.player;
+        let window = &self.window;
+        match event {
+            WindowEvent::Resized(size) => {
+                let mut player_lock = player.lock().unwrap();
+                player_lock.set_viewport_dimensions(size.width, size.height);
+                player_lock
+                    .renderer_mut()
+                    .set_viewport_dimensions(size.width, size.height);
+                window.request_redraw();
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let mut player_lock = player.lock().unwrap();
+                self.mouse_pos = position;
+                let event = ruffle_core::PlayerEvent::MouseMove {
+                    x: position.x,
+                    y: position.y,
+                };
+                player_lock.handle_event(event);
+                if player_lock.needs_render() {
+                    window.request_redraw();
+                }
+            }
+            WindowEvent::MouseInput {
+                button: MouseButton::Left,
+                state: pressed,
+                ..
+            } => {
+                let mut player_lock = player.lock().unwrap();
+                let event = if pressed == ElementState::Pressed {
+                    ruffle_core::PlayerEvent::MouseDown {
+                        x: self.mouse_pos.x,
+                        y: self.mouse_pos.y,
+                    }
+                } else {
+                    ruffle_core::PlayerEvent::MouseUp {
+                        x: self.mouse_pos.x,
+                        y: self.mouse_pos.y,
+                    }
+                };
+                player_lock.handle_event(event);
+                if player_lock.needs_render() {
+                    window.request_redraw();
+                }
+            }
+            WindowEvent::CursorLeft { .. } => {
+                let mut player_lock = player.lock().unwrap();
+                player_lock.handle_event(ruffle_core::PlayerEvent::MouseLeave);
+                if player_lock.needs_render() {
+                    window.request_redraw();
+                }
+            }
+            WindowEvent::DroppedFile(path) => self.dropped_files.push(path),
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::N),
+                        ..
+                    },
+                ..
+            } if self.modifiers.ctrl() => {
+                // Ctrl+N plays the next queued movie.
+                if let Some(path) = self.movie_queue.pop_front() {
+                    self.load_movie(&path);
+                }
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F5),
+                        ..
+                    },
+                ..
+            } if self.has_movie => {
+                // F5 saves the state of the movie, and F9 restores it.
+                match player.lock().unwrap().save_state() {
+                    Ok(state) => {
+                        log::info!("Saved state");
+                        self.quick_save = Some(state);
+                    }
+                    Err(e) => log::error!("{}", e),
+                }
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F9),
+                        ..
+                    },
+                ..
+            } if self.quick_save.is_some() => {
+                let mut player_lock = player.lock().unwrap();
+                match player_lock.load_state(self.quick_save.as_ref().unwrap()) {
+                    Ok(()) => log::info!("Restored saved state"),
+                    Err(e) => log::error!("Unable to restore saved state: {}", e),
+                }
+                window.request_redraw();
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F8),
+                        ..
+                    },
+                ..
+            } => {
+                // F8 toggles anti-aliasing.
+                self.toggle_msaa();
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F12),
+                        ..
+                    },
+                ..
+            } if self.has_movie => {
+                // F12 saves a screenshot.
+                self.save_screenshot();
+            }
+            WindowEvent::ModifiersChanged(new_modifiers) => {
+                self.modifiers = new_modifiers;
+
+                // winit can't tell us the state of the lock keys.
+                let event = ruffle_core::PlayerEvent::ModifiersChanged {
+                    modifiers: KeyModifiers {
+                        shift: new_modifiers.shift(),
+                        control: new_modifiers.ctrl(),
+                        alt: new_modifiers.alt(),
+                        caps_lock: None,
+                        num_lock: None,
+                    },
+                };
+                player.lock().unwrap().handle_event(event);
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } if self.modifiers.ctrl() && is_volume_key(key) => {
+                // Host volume controls; these are not visible to the SWF.
+                let mut player_lock = player.lock().unwrap();
+                match key {
+                    VirtualKeyCode::M => {
+                        let is_muted = player_lock.is_muted();
+                        player_lock.set_mute(!is_muted);
+                    }
+                    VirtualKeyCode::Up => {
+                        let volume = player_lock.volume();
+                        player_lock.set_volume(volume + VOLUME_STEP);
+                    }
+                    VirtualKeyCode::Down => {
+                        let volume = player_lock.volume();
+                        player_lock.set_volume(volume - VOLUME_STEP);
+                    }
+                    _ => (),
+                }
+            }
+            WindowEvent::KeyboardInput { .. } | WindowEvent::ReceivedCharacter(_) => {
+                let mut player_lock = player.lock().unwrap();
+                if let Some(event) = player_lock
+                    .input_mut()
+                    .downcast_mut::<input::WinitInputBackend>()
+                    .unwrap()
+                    .handle_event(event)
+                {
+                    player_lock.handle_event(event);
+                    if player_lock.needs_render() {
+                        window.request_redraw();
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
 }
 
 fn main() {
@@ -37,7 +603,42 @@ fn main() {
 
     let opt = Opt::from_args();
 
-    let ret = run_player(opt.input_path);
+    let ret = if opt.inspect {
+        inspect_movies(&opt.input_paths)
+    } else if let Some(num_frames) = opt.dump_frames {
+        match &opt.input_paths[..] {
+            [output_dir, movie_path] => dump_frames(
+                movie_path,
+                output_dir,
+                &DumpOptions {
+                    num_frames,
+                    skip: opt.skip,
+                    size: opt.size,
+                    scale: opt.scale,
+                    letterbox: !opt.no_letterbox,
+                    pixel_perfect: opt.pixel_perfect,
+                    trace_context: opt.trace_context,
+                    max_texture_size: opt.max_texture_size,
+                    msaa_sample_count: opt.msaa_sample_count,
+                },
+            ),
+            _ => Err("--dump-frames expects an output directory and a SWF file".into()),
+        }
+    } else {
+        run_players(
+            &opt.input_paths,
+            !opt.no_letterbox,
+            opt.pixel_perfect,
+            opt.trace_context,
+            if opt.watch {
+                Some(opt.keep_frame)
+            } else {
+                None
+            },
+            opt.max_texture_size,
+            opt.msaa_sample_count,
+        )
+    };
 
     if let Err(e) = ret {
         eprintln!("Fatal error:\n{}", e);
@@ -45,52 +646,73 @@ fn main() {
     }
 }
 
-fn run_player(input_path: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-    let movie = SwfMovie::from_path(&input_path)?;
+/// Print a JSON array with a summary of each movie, without playing them.
+fn inspect_movies(input_paths: &[PathBuf]) -> Result<(), Error> {
+    if input_paths.is_empty() {
+        return Err("No SWF files to inspect".into());
+    }
+
+    let mut summaries = Vec::with_capacity(input_paths.len());
+    for path in input_paths {
+        let movie = SwfMovie::from_path(path)
+            .map_err(|e| format!("Couldn't load {}: {}", path.display(), e))?;
+        summaries.push(inspect_movie(&movie));
+    }
+
+    let stdout = std::io::stdout();
+    serde_json::to_writer_pretty(stdout.lock(), &summaries)?;
+    println!();
+    Ok(())
+}
 
+/// Opens a window playing each of the given movies.
+///
+/// With `watch`, each movie is reloaded when its file changes, keeping its frame if `watch`
+/// holds `true`.
+fn run_players(
+    input_paths: &[PathBuf],
+    letterbox: bool,
+    pixel_perfect: bool,
+    trace_context: bool,
+    watch: Option<bool>,
+    max_texture_size: Option<u32>,
+    msaa_sample_count: Option<u32>,
+) -> Result<(), Error> {
     let event_loop: EventLoop<RuffleEvent> = EventLoop::with_user_event();
-    let window = Rc::new(
-        WindowBuilder::new()
-            .with_title(format!(
-                "Ruffle - {}",
-                input_path.file_name().unwrap_or_default().to_string_lossy()
-            ))
-            .with_inner_size(LogicalSize::new(movie.width(), movie.height()))
-            .build(&event_loop)?,
-    );
-
-    let audio: Box<dyn AudioBackend> = match audio::CpalAudioBackend::new() {
-        Ok(audio) => Box::new(audio),
-        Err(e) => {
-            log::error!("Unable to create audio device: {}", e);
-            Box::new(NullAudioBackend::new())
-        }
+    let mut descriptors = match msaa_sample_count {
+        Some(msaa_sample_count) => Descriptors::with_msaa_sample_count(msaa_sample_count)?,
+        None => Descriptors::new()?,
+    };
+    if let Some(max_texture_size) = max_texture_size {
+        descriptors.set_max_texture_size(max_texture_size);
+    }
+    let descriptors = Rc::new(descriptors);
+
+    let mut windows: HashMap<WindowId, PlayerWindow> = HashMap::new();
+    let input_paths: Vec<Option<&Path>> = if input_paths.is_empty() {
+        vec![None]
+    } else {
+        input_paths
+            .iter()
+            .map(|path| Some(path.as_path()))
+            .collect()
     };
-    let renderer = Box::new(WgpuRenderBackend::new(
-        window.as_ref(),
-        (movie.width(), movie.height()),
-    )?);
-    let (executor, chan) = GlutinAsyncExecutor::new(event_loop.create_proxy());
-    let navigator = Box::new(navigator::ExternalNavigatorBackend::with_base_path(
-        input_path
-            .parent()
-            .unwrap_or_else(|| std::path::Path::new("")),
-        chan,
-        event_loop.create_proxy(),
-    )); //TODO: actually implement this backend type
-    let input = Box::new(input::WinitInputBackend::new(window.clone()));
-    let player = Player::new(renderer, audio, navigator, input, movie)?;
-    player.lock().unwrap().set_is_playing(true); // Desktop player will auto-play.
-
-    let size = window.inner_size();
-    player
-        .lock()
-        .unwrap()
-        .set_viewport_dimensions(size.width, size.height);
-
-    let mut mouse_pos = PhysicalPosition::new(0.0, 0.0);
-    let mut time = Instant::now();
-    let mut next_frame_time = Instant::now();
+    for input_path in input_paths {
+        let mut player_window = PlayerWindow::new(
+            input_path,
+            letterbox,
+            pixel_perfect,
+            trace_context,
+            descriptors.clone(),
+            &event_loop,
+            event_loop.create_proxy(),
+        )?;
+        if let Some(keep_frame) = watch {
+            player_window.watch(input_path, keep_frame);
+        }
+        windows.insert(player_window.window.id(), player_window);
+    }
+
     loop {
         // Poll UI events
         event_loop.run(move |event, _window_target, control_flow| {
@@ -99,99 +721,54 @@ fn run_player(input_path: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
 
                 // Core loop
                 winit::event::Event::MainEventsCleared => {
-                    let new_time = Instant::now();
-                    let dt = new_time.duration_since(time).as_micros();
-                    if dt > 0 {
-                        time = new_time;
-                        let mut player_lock = player.lock().unwrap();
-                        player_lock.tick(dt as f64 / 1000.0);
-                        next_frame_time = new_time + player_lock.time_til_next_frame();
-                        if player_lock.needs_render() {
-                            window.request_redraw();
-                        }
+                    for player_window in windows.values_mut() {
+                        player_window.tick();
                     }
                 }
 
                 // Render
-                winit::event::Event::RedrawRequested(_) => player.lock().unwrap().render(),
-
-                winit::event::Event::WindowEvent { event, .. } => match event {
-                    WindowEvent::Resized(size) => {
-                        let mut player_lock = player.lock().unwrap();
-                        player_lock.set_viewport_dimensions(size.width, size.height);
-                        player_lock
-                            .renderer_mut()
-                            .set_viewport_dimensions(size.width, size.height);
-                        window.request_redraw();
+                winit::event::Event::RedrawRequested(window_id) => {
+                    if let Some(player_window) = windows.get_mut(&window_id) {
+                        player_window.render();
                     }
-                    WindowEvent::CursorMoved { position, .. } => {
-                        let mut player_lock = player.lock().unwrap();
-                        mouse_pos = position;
-                        let event = ruffle_core::PlayerEvent::MouseMove {
-                            x: position.x,
-                            y: position.y,
-                        };
-                        player_lock.handle_event(event);
-                        if player_lock.needs_render() {
-                            window.request_redraw();
-                        }
+                }
+
+                winit::event::Event::WindowEvent {
+                    window_id,
+                    event: WindowEvent::CloseRequested,
+                } => {
+                    // Shut the player down before dropping it, so that none of its tasks run
+                    // against it afterwards.
+                    if let Some(player_window) = windows.remove(&window_id) {
+                        player_window.player.lock().unwrap().shutdown();
                     }
-                    WindowEvent::MouseInput {
-                        button: MouseButton::Left,
-                        state: pressed,
-                        ..
-                    } => {
-                        let mut player_lock = player.lock().unwrap();
-                        let event = if pressed == ElementState::Pressed {
-                            ruffle_core::PlayerEvent::MouseDown {
-                                x: mouse_pos.x,
-                                y: mouse_pos.y,
-                            }
-                        } else {
-                            ruffle_core::PlayerEvent::MouseUp {
-                                x: mouse_pos.x,
-                                y: mouse_pos.y,
-                            }
-                        };
-                        player_lock.handle_event(event);
-                        if player_lock.needs_render() {
-                            window.request_redraw();
-                        }
+                    if windows.is_empty() {
+                        *control_flow = ControlFlow::Exit;
                     }
-                    WindowEvent::CursorLeft { .. } => {
-                        let mut player_lock = player.lock().unwrap();
-                        player_lock.handle_event(ruffle_core::PlayerEvent::MouseLeft);
-                        if player_lock.needs_render() {
-                            window.request_redraw();
-                        }
+                }
+
+                winit::event::Event::WindowEvent { window_id, event } => {
+                    if let Some(player_window) = windows.get_mut(&window_id) {
+                        player_window.handle_window_event(event);
                     }
-                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                    WindowEvent::KeyboardInput { .. } | WindowEvent::ReceivedCharacter(_) => {
-                        let mut player_lock = player.lock().unwrap();
-                        if let Some(event) = player_lock
-                            .input_mut()
-                            .downcast_mut::<input::WinitInputBackend>()
-                            .unwrap()
-                            .handle_event(event)
-                        {
-                            player_lock.handle_event(event);
-                            if player_lock.needs_render() {
-                                window.request_redraw();
-                            }
-                        }
+                }
+
+                winit::event::Event::UserEvent(RuffleEvent::TaskPoll(window_id)) => {
+                    if let Some(player_window) = windows.get_mut(&window_id) {
+                        player_window.poll_tasks();
                     }
-                    _ => (),
-                },
-                winit::event::Event::UserEvent(RuffleEvent::TaskPoll) => executor
-                    .lock()
-                    .expect("active executor reference")
-                    .poll_all(),
+                }
                 _ => (),
             }
 
-            // After polling events, sleep the event loop until the next event or the next frame.
+            // After polling events, sleep the event loop until the next event or the next frame
+            // of any player.
             if *control_flow != ControlFlow::Exit {
-                *control_flow = ControlFlow::WaitUntil(next_frame_time);
+                if let Some(next_frame_time) =
+                    windows.values().map(PlayerWindow::next_tick_time).min()
+                {
+                    *control_flow = ControlFlow::WaitUntil(next_frame_time);
+                }
             }
         });
     }
@@ -1,26 +1,39 @@
 //! Navigator backend for web
 
 use crate::custom_event::RuffleEvent;
+use crate::executor::GlutinAsyncExecutor;
 use ruffle_core::backend::navigator::{
-    Error, NavigationMethod, NavigatorBackend, OwnedFuture, RequestOptions,
+    file_path_from_url, is_data_url, resolve_local_path, url_from_file_path, CancellationToken,
+    DataUrl, Error, HttpStatusError, NavigationMethod, NavigatorBackend, OwnedFuture,
+    RequestOptions, SpawnHandle, SpawnedFuture,
 };
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::Sender;
+use std::sync::{Mutex, TryLockError, Weak};
+use std::thread;
 use std::time::{Duration, Instant};
 use url::Url;
 use winit::event_loop::EventLoopProxy;
+use winit::window::WindowId;
 
 /// Implementation of `NavigatorBackend` for non-web environments that can call
 /// out to a web browser.
 pub struct ExternalNavigatorBackend {
     /// Sink for tasks sent to us through `spawn_future`.
-    channel: Sender<OwnedFuture<(), Error>>,
+    channel: Sender<SpawnedFuture>,
+
+    /// The executor running our tasks.
+    executor: Weak<Mutex<GlutinAsyncExecutor>>,
 
     /// Event sink to trigger a new task poll.
     event_loop: EventLoopProxy<RuffleEvent>,
 
+    /// The window of the player we belong to.
+    window_id: WindowId,
+
     /// The base path for all relative fetches.
     relative_base_path: PathBuf,
 
@@ -31,12 +44,15 @@ pub struct ExternalNavigatorBackend {
 impl ExternalNavigatorBackend {
     #[allow(dead_code)]
     pub fn new(
-        channel: Sender<OwnedFuture<(), Error>>,
+        channel: Sender<SpawnedFuture>,
         event_loop: EventLoopProxy<RuffleEvent>,
+        window_id: WindowId,
     ) -> Self {
         Self {
             channel,
+            executor,
             event_loop,
+            window_id,
             relative_base_path: PathBuf::new(),
             start_time: Instant::now(),
         }
@@ -45,8 +61,9 @@ impl ExternalNavigatorBackend {
     /// Construct a navigator backend with fetch and async capability.
     pub fn with_base_path<P: AsRef<Path>>(
         path: P,
-        channel: Sender<OwnedFuture<(), Error>>,
+        channel: Sender<SpawnedFuture>,
         event_loop: EventLoopProxy<RuffleEvent>,
+        window_id: WindowId,
     ) -> Self {
         let mut relative_base_path = PathBuf::new();
 
@@ -54,7 +71,9 @@ impl ExternalNavigatorBackend {
 
         Self {
             channel,
+            executor,
             event_loop,
+            window_id,
             relative_base_path,
             start_time: Instant::now(),
         }
@@ -111,21 +130,84 @@ impl NavigatorBackend for ExternalNavigatorBackend {
     }
 
     fn fetch(&self, url: String, _options: RequestOptions) -> OwnedFuture<Vec<u8>, Error> {
+        if is_data_url(&url) {
+            return Box::pin(async move { Ok(DataUrl::parse(&url)?.data) });
+        }
+
         // Load from local filesystem.
         // TODO: Support network loads, honor sandbox type (local-with-filesystem, local-with-network, remote, ...)
-        let mut path = self.relative_base_path.clone();
-        path.push(url);
+        let path = resolve_local_path(&self.relative_base_path, &url);
 
-        Box::pin(async move { fs::read(path).map_err(|e| e.into()) })
+        Box::pin(async move { fs::read(path?).map_err(HttpStatusError::from_io) })
     }
 
-    fn spawn_future(&mut self, future: OwnedFuture<(), Error>) {
-        self.channel.send(future).expect("working channel send");
-
-        if self.event_loop.send_event(RuffleEvent::TaskPoll).is_err() {
+    fn spawn_future(
+        &mut self,
+        future: OwnedFuture<(), Error>,
+        token: CancellationToken,
+    ) -> SpawnHandle {
+        let token = token.child();
+        self.channel
+            .send((future, token.clone()))
+            .expect("working channel send");
+
+        if self
+            .event_loop
+            .send_event(RuffleEvent::TaskPoll(self.window_id))
+            .is_err()
+        {
             log::warn!(
                 "A task was queued on an event loop that has already ended. It will not be polled."
             );
         }
+
+        SpawnHandle::new(token)
+    }
+
+    fn drain_futures(&mut self, timeout: Duration) {
+        let executor = match self.executor.upgrade() {
+            Some(executor) => executor,
+            None => return,
+        };
+
+        // The executor may be in the middle of polling on another thread.
+        let deadline = Instant::now() + timeout;
+        loop {
+            match executor.try_lock() {
+                Ok(mut executor) => {
+                    executor.drain();
+                    return;
+                }
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => thread::yield_now(),
+                Err(_) => {
+                    log::warn!(
+                        "Executor is busy; cancelled tasks will be dropped on its next poll"
+                    );
+                    return;
+                }
+            }
+        }
+    }
+
+    fn set_base_url(&mut self, url: &str) {
+        self.relative_base_path = file_path_from_url(url)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+    }
+
+    fn resolve_relative_url<'a>(&self, url: &'a str) -> Cow<'a, str> {
+        if is_data_url(url) {
+            return url.into();
+        }
+
+        match resolve_local_path(&self.relative_base_path, url) {
+            // Movies opened from disk have `file:` URLs, so loaded ones do too.
+            Ok(path) => url_from_file_path(&path)
+                .unwrap_or_else(|| path.to_string_lossy().into_owned())
+                .into(),
+            // The fetch will fail, and there is nothing to compare it with.
+            Err(_) => url.into(),
+        }
     }
 }
@@ -1,6 +1,6 @@
 //! Task state information
 
-use ruffle_core::backend::navigator::{Error, OwnedFuture};
+use ruffle_core::backend::navigator::{CancellationToken, Error, OwnedFuture};
 use std::task::{Context, Poll};
 
 /// Indicates the state of a given task.
@@ -23,14 +23,18 @@ pub struct Task {
 
     /// The future to poll in order to progress the task.
     future: OwnedFuture<(), Error>,
+
+    /// The token that cancels the task.
+    token: CancellationToken,
 }
 
 impl Task {
     /// Box an owned future into a task structure.
-    pub fn from_future(future: OwnedFuture<(), Error>) -> Self {
+    pub fn from_future(future: OwnedFuture<(), Error>, token: CancellationToken) -> Self {
         Self {
             state: TaskState::Ready,
             future,
+            token,
         }
     }
 
@@ -50,12 +54,18 @@ impl Task {
         self.state == TaskState::Completed
     }
 
+    /// Returns `true` if the task was cancelled and should be dropped without
+    /// being polled again.
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
     /// Poll the underlying future.
     ///
     /// This wrapper function ensures that futures cannot be polled after they
-    /// have completed. Future polls will return `Ok(())`.
+    /// have completed or been cancelled. Future polls will return `Ok(())`.
     pub fn poll(&mut self, context: &mut Context) -> Poll<Result<(), Error>> {
-        if self.is_completed() {
+        if self.is_completed() || self.is_cancelled() {
             return Poll::Ready(Ok(()));
         }
 
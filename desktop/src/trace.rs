@@ -0,0 +1,29 @@
+use ruffle_core::backend::log::{LogBackend, TraceContext};
+
+/// Log backend that forwards trace output to the `log` crate, under the `avm_trace` target.
+/// Optionally, each message is prefixed with the clip and movie it came from.
+pub struct DesktopLogBackend {
+    show_context: bool,
+}
+
+impl DesktopLogBackend {
+    pub fn new(show_context: bool) -> Self {
+        Self { show_context }
+    }
+}
+
+impl LogBackend for DesktopLogBackend {
+    fn avm_trace(&mut self, message: &str) {
+        log::info!(target: "avm_trace", "{}", message);
+    }
+
+    fn avm_trace_with_context(&mut self, message: &str, context: &TraceContext) {
+        if !self.show_context {
+            return self.avm_trace(message);
+        }
+        match &context.movie_url {
+            Some(url) => log::info!(target: "avm_trace", "[{} {}] {}", url, context, message),
+            None => log::info!(target: "avm_trace", "[{}] {}", context, message),
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use ruffle_core::backend::navigator::OwnedFuture;
+use ruffle_core::backend::ui::{DisplayState, Error, MessageLevel, MouseCursor, UiBackend};
+use std::rc::Rc;
+use winit::window::{Fullscreen, Window};
+
+pub struct DesktopUiBackend {
+    window: Rc<Window>,
+    cursor_visible: bool,
+}
+
+impl DesktopUiBackend {
+    pub fn new(window: Rc<Window>) -> Self {
+        Self {
+            window,
+            cursor_visible: true,
+        }
+    }
+}
+
+impl UiBackend for DesktopUiBackend {
+    fn message(
+        &mut self,
+        level: MessageLevel,
+        message: &str,
+        choices: &[&str],
+    ) -> OwnedFuture<Option<usize>, Error> {
+        // TODO: Show a native dialog. Until then, messages are only logged, and the first
+        // choice is taken.
+        match level {
+            MessageLevel::Info => log::info!("{}", message),
+            MessageLevel::Warning => log::warn!("{}", message),
+            MessageLevel::Error => log::error!("{}", message),
+        }
+        let choice = if choices.is_empty() { None } else { Some(0) };
+        Box::pin(async move { Ok(choice) })
+    }
+
+    fn set_clipboard_content(&mut self, _content: String) {
+        log::warn!("System.setClipboard: unimplemented");
+    }
+
+    fn mouse_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    fn set_mouse_visible(&mut self, visible: bool) {
+        self.window.set_cursor_visible(visible);
+        self.cursor_visible = visible;
+    }
+
+    fn set_mouse_cursor(&mut self, cursor: MouseCursor) {
+        use winit::window::CursorIcon;
+        let icon = match cursor {
+            MouseCursor::Arrow => CursorIcon::Arrow,
+            MouseCursor::Hand => CursorIcon::Hand,
+            MouseCursor::IBeam => CursorIcon::Text,
+            MouseCursor::Grab => CursorIcon::Grab,
+        };
+        self.window.set_cursor_icon(icon);
+    }
+
+    fn display_state(&self) -> DisplayState {
+        if self.window.fullscreen().is_some() {
+            DisplayState::FullScreen
+        } else {
+            DisplayState::Normal
+        }
+    }
+
+    fn set_display_state(&mut self, state: DisplayState) -> Result<(), Error> {
+        let fullscreen = match state {
+            DisplayState::Normal => None,
+            DisplayState::FullScreen => Some(Fullscreen::Borderless(self.window.current_monitor())),
+        };
+        self.window.set_fullscreen(fullscreen);
+        Ok(())
+    }
+}
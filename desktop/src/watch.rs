@@ -0,0 +1,275 @@
+//! Reloading a movie when its file changes, for `--watch`.
+
+use ruffle_core::swf::TagCode;
+use ruffle_core::tag_utils::{decode_tags, SwfMovie};
+use ruffle_core::Player;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the file is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long a change has to stay unchanged before the file is loaded, so that a file which is
+/// still being written isn't loaded halfway.
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(500);
+
+/// The modification time and size of a file, which tell whether it has changed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct FileStamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+        })
+    }
+}
+
+/// A change to the file that hasn't been loaded yet.
+struct PendingChange {
+    stamp: FileStamp,
+
+    /// When the file was last seen changing.
+    since: Instant,
+
+    /// Whether the file has already failed to load as it is now.
+    failed: bool,
+}
+
+/// Watches the file of a movie, and loads it again once it has changed.
+///
+/// The file is polled rather than watched with OS notifications, which works the same on every
+/// platform, and for editors and compilers that replace the file instead of writing to it.
+pub struct MovieWatcher {
+    path: PathBuf,
+    loaded: Option<FileStamp>,
+    pending: Option<PendingChange>,
+    next_poll: Instant,
+}
+
+impl MovieWatcher {
+    /// Starts watching the file at the given path, which has just been loaded.
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            loaded: FileStamp::of(path),
+            pending: None,
+            next_poll: Instant::now(),
+        }
+    }
+
+    /// The time at which `poll` next checks the file.
+    pub fn next_poll_time(&self) -> Instant {
+        self.next_poll
+    }
+
+    /// Checks the file for changes, and returns the movie in it if it has changed and has been
+    /// left alone for long enough.
+    ///
+    /// A file that fails to parse is most likely still being written, so it is tried again
+    /// after the next change, or after waiting again if it doesn't change.
+    pub fn poll(&mut self, now: Instant) -> Option<SwfMovie> {
+        if now < self.next_poll {
+            return None;
+        }
+        self.next_poll = now + POLL_INTERVAL;
+
+        // The file may be missing for a moment while it is being replaced.
+        let stamp = FileStamp::of(&self.path)?;
+        if self.loaded == Some(stamp) {
+            self.pending = None;
+            return None;
+        }
+
+        match &mut self.pending {
+            Some(pending) if pending.stamp == stamp => {
+                if now.duration_since(pending.since) < DEBOUNCE_DURATION {
+                    return None;
+                }
+            }
+            _ => {
+                self.pending = Some(PendingChange {
+                    stamp,
+                    since: now,
+                    failed: false,
+                });
+                return None;
+            }
+        }
+
+        match load_complete_movie(&self.path) {
+            Ok(movie) => {
+                self.loaded = Some(stamp);
+                self.pending = None;
+                Some(movie)
+            }
+            Err(e) => {
+                let pending = self.pending.as_mut().unwrap();
+                if !pending.failed {
+                    log::warn!("Unable to reload {}, retrying: {}", self.path.display(), e);
+                }
+                pending.since = now;
+                pending.failed = true;
+                None
+            }
+        }
+    }
+}
+
+/// Loads the movie in the given file, failing if it is cut short, as it is while it is still
+/// being written.
+fn load_complete_movie(path: &Path) -> Result<SwfMovie, Box<dyn std::error::Error>> {
+    let movie = SwfMovie::from_path(path)?;
+    let mut reader =
+        ruffle_core::swf::read::Reader::new(std::io::Cursor::new(movie.data()), movie.version());
+    // Reading past the end of the file fails before reaching the End tag.
+    decode_tags(
+        &mut reader,
+        |_reader, _tag_code, _tag_len| Ok(()),
+        TagCode::End,
+    )?;
+    Ok(movie)
+}
+
+/// Replaces the movie of the player with a new version of it.
+///
+/// With `keep_frame`, the new movie is sent to the frame the old one was on, or to its last
+/// frame if it is shorter.
+pub fn reload_movie(player: &mut Player, movie: SwfMovie, keep_frame: bool) {
+    let frame = player.current_frame();
+    player.set_root_movie(Arc::new(movie));
+    player.set_is_playing(true);
+    if keep_frame && frame > 0 {
+        let frame = frame.min(player.total_frames());
+        player.goto_frame(frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ruffle_core::backend::{
+        audio::NullAudioBackend, input::NullInputBackend, log::NullLogBackend,
+        navigator::NullExecutor, navigator::NullNavigatorBackend, render::NullRenderer,
+        storage::MemoryStorageBackend, ui::NullUiBackend,
+    };
+
+    type Error = Box<dyn std::error::Error>;
+
+    /// A movie with 6 frames, and an edited version of it with the same length.
+    const LONG_MOVIE: &str = "tests/swfs/hot_reload/long.swf";
+    const EDITED_MOVIE: &str = "tests/swfs/hot_reload/edited.swf";
+
+    /// A movie with 3 frames.
+    const SHORT_MOVIE: &str = "tests/swfs/hot_reload/short.swf";
+
+    /// A fresh copy of a fixture in the temporary directory, which the test can rewrite.
+    fn watched_file(name: &str, fixture: &str) -> Result<PathBuf, Error> {
+        let path = std::env::temp_dir().join(format!("ruffle_watch_{}.swf", name));
+        std::fs::copy(fixture, &path)?;
+        Ok(path)
+    }
+
+    /// Polls the watcher once to notice a change, and again once the change has settled.
+    fn poll_after_debounce(watcher: &mut MovieWatcher, start: Instant) -> Option<SwfMovie> {
+        assert!(watcher.poll(start).is_none());
+        watcher.poll(start + DEBOUNCE_DURATION)
+    }
+
+    #[test]
+    fn changed_file_is_reloaded_after_debounce() -> Result<(), Error> {
+        let path = watched_file("changed", LONG_MOVIE)?;
+        let mut watcher = MovieWatcher::new(&path);
+        let start = Instant::now();
+        assert!(watcher.poll(start).is_none());
+
+        std::fs::copy(SHORT_MOVIE, &path)?;
+        let start = start + POLL_INTERVAL;
+        assert!(watcher.poll(start).is_none());
+        assert!(watcher.poll(start + POLL_INTERVAL).is_none());
+        let movie = watcher.poll(start + DEBOUNCE_DURATION);
+        assert_eq!(movie.expect("reloaded movie").header().num_frames, 3);
+
+        // Nothing is loaded again until the file changes again.
+        assert!(watcher.poll(start + DEBOUNCE_DURATION * 4).is_none());
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn partially_written_file_is_retried() -> Result<(), Error> {
+        let path = watched_file("partial", LONG_MOVIE)?;
+        let mut watcher = MovieWatcher::new(&path);
+
+        let data = std::fs::read(SHORT_MOVIE)?;
+        std::fs::write(&path, &data[..data.len() / 2])?;
+        let start = Instant::now();
+        assert!(poll_after_debounce(&mut watcher, start).is_none());
+
+        std::fs::write(&path, &data)?;
+        let movie = poll_after_debounce(&mut watcher, start + DEBOUNCE_DURATION * 2);
+        assert_eq!(movie.expect("reloaded movie").header().num_frames, 3);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn reload_keeps_the_frame() -> Result<(), Error> {
+        let path = watched_file("keep_frame", LONG_MOVIE)?;
+        let (_executor, channel) = NullExecutor::new();
+        let player = Player::new(
+            Box::new(NullRenderer),
+            Box::new(NullAudioBackend::new()),
+            Box::new(NullNavigatorBackend::with_base_path(
+                Path::new("tests/swfs/hot_reload"),
+                channel,
+            )),
+            Box::new(NullInputBackend::new()),
+            Box::new(NullUiBackend::new()),
+            Box::new(NullLogBackend::new()),
+            Box::new(MemoryStorageBackend::new()),
+            SwfMovie::from_path(&path)?,
+        )?;
+        let mut player = player.lock().unwrap();
+        let mut watcher = MovieWatcher::new(&path);
+        for _ in 0..2 {
+            player.run_frame();
+        }
+        assert_eq!(player.current_frame(), 2);
+
+        // An edited movie carries on from the same frame.
+        std::fs::copy(EDITED_MOVIE, &path)?;
+        let start = Instant::now();
+        let movie = poll_after_debounce(&mut watcher, start).expect("reloaded movie");
+        reload_movie(&mut player, movie, true);
+        assert_eq!(player.current_frame(), 2);
+        assert_eq!(player.total_frames(), 6);
+
+        // A shorter movie goes to its last frame instead.
+        for _ in 0..3 {
+            player.run_frame();
+        }
+        assert_eq!(player.current_frame(), 5);
+        std::fs::copy(SHORT_MOVIE, &path)?;
+        let movie = poll_after_debounce(&mut watcher, start + DEBOUNCE_DURATION * 2)
+            .expect("reloaded movie");
+        reload_movie(&mut player, movie, true);
+        assert_eq!(player.current_frame(), 3);
+        assert_eq!(player.total_frames(), 3);
+
+        // Without keeping the frame, the movie starts over.
+        reload_movie(&mut player, SwfMovie::from_path(LONG_MOVIE)?, false);
+        assert_eq!(player.current_frame(), 0);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+//! Tests saving the frames of a movie with `--dump-frames`.
+
+use ruffle_render_wgpu::Descriptors;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+type Error = Box<dyn std::error::Error>;
+
+/// A file saved by `--dump-frames`, with the size of its image and a hash of its contents.
+type DumpedFrame = (PathBuf, (u32, u32), u64);
+
+/// Dumps frames of a movie into a new directory, and returns the files that were saved.
+fn dump_frames(output_dir: &Path, args: &[&str]) -> Result<Vec<DumpedFrame>, Error> {
+    if output_dir.exists() {
+        std::fs::remove_dir_all(output_dir)?;
+    }
+    let status = Command::new(env!("CARGO_BIN_EXE_ruffle_desktop"))
+        .args(args)
+        .arg(output_dir)
+        .arg("../core/tests/swfs/avm1/clip_events/test.swf")
+        .status()?;
+    assert!(status.success());
+
+    let mut files = vec![];
+    for entry in std::fs::read_dir(output_dir)? {
+        let path = entry?.path();
+        let mut hasher = DefaultHasher::new();
+        std::fs::read(&path)?.hash(&mut hasher);
+        files.push((
+            path.strip_prefix(output_dir)?.to_path_buf(),
+            image::image_dimensions(&path)?,
+            hasher.finish(),
+        ));
+    }
+    files.sort();
+    std::fs::remove_dir_all(output_dir)?;
+    Ok(files)
+}
+
+#[test]
+fn dumped_frames_are_reproducible() -> Result<(), Error> {
+    if let Err(e) = Descriptors::new() {
+        // Machines without a GPU, such as most CI runners, can't run this test.
+        eprintln!("Skipping test: {}", e);
+        return Ok(());
+    }
+
+    let output_dir = std::env::temp_dir().join(format!("ruffle_dump_{}", std::process::id()));
+    let args = ["--dump-frames", "3", "--skip", "1", "--size", "64x48"];
+    let first_run = dump_frames(&output_dir, &args)?;
+    let names: Vec<_> = first_run.iter().map(|(name, _, _)| name.clone()).collect();
+    assert_eq!(
+        names,
+        vec![
+            PathBuf::from("00001.png"),
+            PathBuf::from("00002.png"),
+            PathBuf::from("00003.png")
+        ]
+    );
+    assert!(first_run.iter().all(|(_, size, _)| *size == (64, 48)));
+    assert_eq!(dump_frames(&output_dir, &args)?, first_run);
+
+    // The size is scaled as well.
+    let scaled = dump_frames(&output_dir, &["--dump-frames", "1", "--scale", "0.5"])?;
+    assert_eq!(scaled.len(), 1);
+    assert_eq!(scaled[0].1, (275, 200));
+
+    Ok(())
+}
+
+#[test]
+fn missing_movie_fails() -> Result<(), Error> {
+    let output_dir =
+        std::env::temp_dir().join(format!("ruffle_dump_{}_missing", std::process::id()));
+    let status = Command::new(env!("CARGO_BIN_EXE_ruffle_desktop"))
+        .args(&["--dump-frames", "1"])
+        .arg(&output_dir)
+        .arg("does_not_exist.swf")
+        .status()?;
+    assert!(!status.success());
+    assert!(!output_dir.exists());
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use ruffle_core::backend::render::{
-    swf, swf::CharacterId, BitmapHandle, BitmapInfo, Color, Letterbox, RenderBackend, ShapeHandle,
-    Transform,
+    normalize_fill_style, swf, swf::CharacterId, BitmapHandle, BitmapInfo, Color, Letterbox,
+    PixelRegion, RenderBackend, ShapeHandle, Transform,
 };
 use ruffle_core::color_transform::ColorTransform;
 use ruffle_core::shape_utils::DrawCommand;
@@ -20,8 +20,8 @@ pub struct WebCanvasRenderBackend {
     render_targets: Vec<(HtmlCanvasElement, CanvasRenderingContext2d)>,
     cur_render_target: usize,
     color_matrix: Element,
-    shapes: Vec<ShapeData>,
-    bitmaps: Vec<BitmapData>,
+    shapes: Vec<Option<ShapeData>>,
+    bitmaps: Vec<Option<BitmapData>>,
     id_to_bitmap: HashMap<CharacterId, BitmapHandle>,
     viewport_width: u32,
     viewport_height: u32,
@@ -107,6 +107,10 @@ struct BitmapData {
     width: u32,
     height: u32,
     data: String,
+
+    /// The unmultiplied RGBA pixels of bitmaps registered with `register_bitmap_raw`,
+    /// kept so that regions can be updated later.
+    pixels: Option<Vec<u8>>,
 }
 
 impl WebCanvasRenderBackend {
@@ -323,7 +327,9 @@ impl WebCanvasRenderBackend {
             && color_transform.b_add == 0.0
             && color_transform.a_add == 0.0
         {
-            self.context.set_global_alpha(color_transform.a_mult.into());
+            // Canvas ignores out-of-range alpha values, but AS allows `_alpha` beyond 0-100.
+            let alpha = f64::from(color_transform.a_mult).max(0.0).min(1.0);
+            self.context.set_global_alpha(alpha);
         } else {
             // TODO HACK: Firefox is having issues with additive alpha in color transforms (see #38).
             // Hack this away and just use multiplicative (not accurate in many cases, but won't look awful).
@@ -371,7 +377,10 @@ impl RenderBackend for WebCanvasRenderBackend {
 
         let mut bitmaps = HashMap::new();
         for (id, handle) in &self.id_to_bitmap {
-            let bitmap_data = &self.bitmaps[handle.0];
+            let bitmap_data = match &self.bitmaps[handle.0] {
+                Some(bitmap_data) => bitmap_data,
+                None => continue,
+            };
             bitmaps.insert(
                 *id,
                 (&bitmap_data.data[..], bitmap_data.width, bitmap_data.height),
@@ -386,7 +395,7 @@ impl RenderBackend for WebCanvasRenderBackend {
         )
         .unwrap_or_else(|| swf_shape_to_svg(&shape, &bitmaps, self.pixelated_property_value));
 
-        self.shapes.push(data);
+        self.shapes.push(Some(data));
 
         handle
     }
@@ -446,12 +455,13 @@ impl RenderBackend for WebCanvasRenderBackend {
         image.set_src(&jpeg_encoded);
 
         let handle = BitmapHandle(self.bitmaps.len());
-        self.bitmaps.push(BitmapData {
+        self.bitmaps.push(Some(BitmapData {
             image,
             width: metadata.width.into(),
             height: metadata.height.into(),
             data: jpeg_encoded,
-        });
+            pixels: None,
+        }));
         self.id_to_bitmap.insert(id, handle);
         BitmapInfo {
             handle,
@@ -477,12 +487,13 @@ impl RenderBackend for WebCanvasRenderBackend {
         image.set_src(&png);
 
         let handle = BitmapHandle(self.bitmaps.len());
-        self.bitmaps.push(BitmapData {
+        self.bitmaps.push(Some(BitmapData {
             image,
             width,
             height,
             data: png,
-        });
+            pixels: None,
+        }));
 
         self.id_to_bitmap.insert(id, handle);
         BitmapInfo {
@@ -506,12 +517,13 @@ impl RenderBackend for WebCanvasRenderBackend {
         image.set_src(&png);
 
         let handle = BitmapHandle(self.bitmaps.len());
-        self.bitmaps.push(BitmapData {
+        self.bitmaps.push(Some(BitmapData {
             image,
             width: swf_tag.width.into(),
             height: swf_tag.height.into(),
             data: png,
-        });
+            pixels: None,
+        }));
         self.id_to_bitmap.insert(swf_tag.id, handle);
         BitmapInfo {
             handle,
@@ -520,6 +532,80 @@ impl RenderBackend for WebCanvasRenderBackend {
         }
     }
 
+    fn register_bitmap_raw(&mut self, width: u32, height: u32, mut rgba: Vec<u8>) -> BitmapHandle {
+        ruffle_core::backend::render::unmultiply_alpha_rgba(&mut rgba[..]);
+
+        let png = Self::rgba_to_png_data_uri(&rgba[..], width, height).unwrap();
+
+        let image = HtmlImageElement::new().unwrap();
+        image.set_src(&png);
+
+        let handle = BitmapHandle(self.bitmaps.len());
+        self.bitmaps.push(Some(BitmapData {
+            image,
+            width,
+            height,
+            data: png,
+            pixels: Some(rgba),
+        }));
+        handle
+    }
+
+    fn update_bitmap_region(&mut self, bitmap: BitmapHandle, region: PixelRegion, rgba: &[u8]) {
+        let bitmap_data = match self.bitmaps.get_mut(bitmap.0) {
+            Some(Some(bitmap_data)) => bitmap_data,
+            _ => {
+                log::warn!("Attempted to update nonexistent bitmap {}", bitmap.0);
+                return;
+            }
+        };
+        let pixels = match &mut bitmap_data.pixels {
+            Some(pixels) => pixels,
+            None => {
+                log::warn!("Attempted to update a bitmap that was not registered as raw pixels");
+                return;
+            }
+        };
+
+        let mut region_pixels = rgba.to_vec();
+        ruffle_core::backend::render::unmultiply_alpha_rgba(&mut region_pixels[..]);
+
+        let row_size = region.width() as usize * 4;
+        for (row, src) in region_pixels.chunks_exact(row_size).enumerate() {
+            let start = ((region.y_min as usize + row) * bitmap_data.width as usize
+                + region.x_min as usize)
+                * 4;
+            pixels[start..start + row_size].copy_from_slice(src);
+        }
+
+        // Images can't be partially updated, so the whole bitmap is encoded again.
+        let png =
+            Self::rgba_to_png_data_uri(&pixels[..], bitmap_data.width, bitmap_data.height).unwrap();
+        bitmap_data.image.set_src(&png);
+        bitmap_data.data = png;
+    }
+
+    fn unregister_shape(&mut self, shape: ShapeHandle) {
+        if let Some(shape) = self.shapes.get_mut(shape.0) {
+            *shape = None;
+        }
+    }
+
+    fn unregister_bitmap(&mut self, bitmap: BitmapHandle) {
+        if let Some(bitmap_data) = self.bitmaps.get_mut(bitmap.0) {
+            *bitmap_data = None;
+        }
+        let ids: Vec<CharacterId> = self
+            .id_to_bitmap
+            .iter()
+            .filter(|(_, handle)| handle.0 == bitmap.0)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids {
+            self.id_to_bitmap.remove(&id);
+        }
+    }
+
     fn begin_frame(&mut self) {
         // Reset canvas transform in case it was left in a dirty state.
         self.context.reset_transform().unwrap();
@@ -539,20 +625,22 @@ impl RenderBackend for WebCanvasRenderBackend {
             .fill_rect(0.0, 0.0, width.into(), height.into());
     }
 
-    fn render_bitmap(&mut self, bitmap: BitmapHandle, transform: &Transform) {
+    fn render_bitmap(&mut self, bitmap: BitmapHandle, transform: &Transform, smoothing: bool) {
         self.set_transform(transform);
         self.set_color_filter(transform);
-        if let Some(bitmap) = self.bitmaps.get(bitmap.0) {
+        if let Some(Some(bitmap)) = self.bitmaps.get(bitmap.0) {
+            self.context.set_image_smoothing_enabled(smoothing);
             let _ = self
                 .context
                 .draw_image_with_html_image_element(&bitmap.image, 0.0, 0.0);
+            self.context.set_image_smoothing_enabled(true);
         }
         self.clear_color_filter();
     }
 
     fn render_shape(&mut self, shape: ShapeHandle, transform: &Transform) {
         self.set_transform(transform);
-        if let Some(shape) = self.shapes.get(shape.0) {
+        if let Some(Some(shape)) = self.shapes.get(shape.0) {
             for command in shape.0.iter() {
                 match command {
                     CanvasDrawCommand::Fill { path, fill_style } => {
@@ -614,28 +702,11 @@ impl RenderBackend for WebCanvasRenderBackend {
         self.context.reset_transform().unwrap();
         self.context.set_fill_style(&"black".into());
 
-        match letterbox {
-            Letterbox::None => (),
-            Letterbox::Letterbox(margin_height) => {
-                self.context
-                    .fill_rect(0.0, 0.0, self.viewport_width.into(), margin_height.into());
-                self.context.fill_rect(
-                    0.0,
-                    (self.viewport_height as f32 - margin_height).into(),
-                    self.viewport_width.into(),
-                    self.viewport_height.into(),
-                );
-            }
-            Letterbox::Pillarbox(margin_width) => {
-                self.context
-                    .fill_rect(0.0, 0.0, margin_width.into(), self.viewport_height.into());
-                self.context.fill_rect(
-                    (self.viewport_width as f32 - margin_width).into(),
-                    0.0,
-                    margin_width.into(),
-                    self.viewport_height.into(),
-                );
-            }
+        let (viewport_width, viewport_height) =
+            (self.viewport_width as f32, self.viewport_height as f32);
+        for (x, y, width, height) in letterbox.bars(viewport_width, viewport_height) {
+            self.context
+                .fill_rect(x.into(), y.into(), width.into(), height.into());
         }
     }
 
@@ -751,7 +822,7 @@ fn swf_shape_to_svg(
 
                 svg_path = svg_path.set(
                     "fill",
-                    match style {
+                    match &*normalize_fill_style(style) {
                         FillStyle::Color(Color { r, g, b, a }) => {
                             format!("rgba({},{},{},{})", r, g, b, f32::from(*a) / 255.0)
                         }
@@ -769,6 +840,7 @@ fn swf_shape_to_svg(
                             let mut svg_gradient = LinearGradient::new()
                                 .set("id", format!("f{}", num_defs))
                                 .set("gradientUnits", "userSpaceOnUse")
+                                .set("spreadMethod", svg_spread_method(gradient.spread))
                                 .set(
                                     "gradientTransform",
                                     format!(
@@ -814,6 +886,7 @@ fn swf_shape_to_svg(
                             let mut svg_gradient = RadialGradient::new()
                                 .set("id", format!("f{}", num_defs))
                                 .set("gradientUnits", "userSpaceOnUse")
+                                .set("spreadMethod", svg_spread_method(gradient.spread))
                                 .set("cx", "0")
                                 .set("cy", "0")
                                 .set("r", "0.5")
@@ -866,6 +939,7 @@ fn swf_shape_to_svg(
                                 .set("id", format!("f{}", num_defs))
                                 .set("fx", focal_point / 2.0)
                                 .set("gradientUnits", "userSpaceOnUse")
+                                .set("spreadMethod", svg_spread_method(gradient.spread))
                                 .set("cx", "0")
                                 .set("cy", "0")
                                 .set("r", "0.5")
@@ -1080,6 +1154,15 @@ fn swf_shape_to_svg(
     data
 }
 
+/// The SVG `spreadMethod` of a gradient, which says how it is drawn outside of its bounds.
+fn svg_spread_method(spread: swf::GradientSpread) -> &'static str {
+    match spread {
+        swf::GradientSpread::Pad => "pad",
+        swf::GradientSpread::Reflect => "reflect",
+        swf::GradientSpread::Repeat => "repeat",
+    }
+}
+
 /// Convert a series of `DrawCommands` to a `Path2d` shape.
 ///
 /// The path can be optionally closed by setting `is_closed` to `true`.
@@ -1151,7 +1234,7 @@ fn swf_shape_to_canvas_commands(
     for path in paths {
         match path {
             DrawPath::Fill { style, commands } => {
-                let fill_style = match style {
+                let fill_style = match &*normalize_fill_style(style) {
                     FillStyle::Color(Color { r, g, b, a }) => CanvasFillStyle::Color(CanvasColor(
                         format!("rgba({},{},{},{})", r, g, b, f32::from(*a) / 255.0),
                         *r,
@@ -5,6 +5,7 @@ use lyon::tessellation::{
     FillAttributes, FillTessellator, StrokeAttributes, StrokeTessellator, StrokeVertexConstructor,
 };
 use lyon::tessellation::{FillOptions, StrokeOptions};
+use ruffle_core::backend::render::normalize_fill_style;
 use ruffle_core::backend::render::swf::{self, FillStyle, Twips};
 use ruffle_core::shape_utils::{DrawCommand, DrawPath};
 
@@ -45,7 +46,7 @@ impl ShapeTessellator {
 
         for path in paths {
             match path {
-                DrawPath::Fill { style, commands } => match style {
+                DrawPath::Fill { style, commands } => match &*normalize_fill_style(style) {
                     FillStyle::Color(color) => {
                         let color = ((color.a as u32) << 24)
                             | ((color.b as u32) << 16)
@@ -343,15 +344,21 @@ pub struct Bitmap {
 
 #[allow(clippy::many_single_char_names)]
 fn swf_to_gl_matrix(m: swf::Matrix) -> [[f32; 3]; 3] {
-    let tx = m.translate_x.get() as f32;
-    let ty = m.translate_y.get() as f32;
-    let det = m.scale_x * m.scale_y - m.rotate_skew_1 * m.rotate_skew_0;
-    let mut a = m.scale_y / det;
-    let mut b = -m.rotate_skew_1 / det;
-    let mut c = -(tx * m.scale_y - m.rotate_skew_1 * ty) / det;
-    let mut d = -m.rotate_skew_0 / det;
-    let mut e = m.scale_x / det;
-    let mut f = (tx * m.rotate_skew_0 - m.scale_x * ty) / det;
+    // Invert the gradient matrix, so that shape coordinates can be mapped to the gradient
+    // square, which spans -16384 to 16384 twips and becomes 0.0 to 1.0 in texture space.
+    // This is done with `f64`s, as matrices with extreme scales or skews can otherwise lose all
+    // precision. Matrices that can't be inverted are rendered as solid fills instead.
+    let tx = f64::from(m.translate_x.get());
+    let ty = f64::from(m.translate_y.get());
+    let (scale_x, scale_y) = (f64::from(m.scale_x), f64::from(m.scale_y));
+    let (skew_0, skew_1) = (f64::from(m.rotate_skew_0), f64::from(m.rotate_skew_1));
+    let det = scale_x * scale_y - skew_1 * skew_0;
+    let mut a = scale_y / det;
+    let mut b = -skew_1 / det;
+    let mut c = -(tx * scale_y - skew_1 * ty) / det;
+    let mut d = -skew_0 / det;
+    let mut e = scale_x / det;
+    let mut f = (tx * skew_0 - scale_x * ty) / det;
 
     a *= 20.0 / 32768.0;
     b *= 20.0 / 32768.0;
@@ -362,6 +369,7 @@ fn swf_to_gl_matrix(m: swf::Matrix) -> [[f32; 3]; 3] {
     f /= 32768.0;
     c += 0.5;
     f += 0.5;
+    let (a, b, c, d, e, f) = (a as f32, b as f32, c as f32, d as f32, e as f32, f as f32);
     [[a, d, 0.0], [b, e, 0.0], [c, f, 1.0]]
 }
 
@@ -448,3 +456,285 @@ pub enum GradientType {
     Radial,
     Focal,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use swf::{GradientInterpolation, GradientRecord, Matrix, Rectangle, ShapeRecord, ShapeStyles};
+
+    /// A square from -100 to 100 pixels, filled with `fill_style`.
+    fn square(fill_style: FillStyle) -> swf::Shape {
+        let bounds = Rectangle {
+            x_min: Twips::from_pixels(-100.0),
+            x_max: Twips::from_pixels(100.0),
+            y_min: Twips::from_pixels(-100.0),
+            y_max: Twips::from_pixels(100.0),
+        };
+        let edge = |x: f64, y: f64| ShapeRecord::StraightEdge {
+            delta_x: Twips::from_pixels(x),
+            delta_y: Twips::from_pixels(y),
+        };
+        swf::Shape {
+            version: 4,
+            id: 1,
+            shape_bounds: bounds.clone(),
+            edge_bounds: bounds,
+            has_fill_winding_rule: false,
+            has_non_scaling_strokes: false,
+            has_scaling_strokes: false,
+            styles: ShapeStyles {
+                fill_styles: vec![fill_style],
+                line_styles: vec![],
+            },
+            shape: vec![
+                ShapeRecord::StyleChange(swf::StyleChangeData {
+                    move_to: Some((Twips::from_pixels(-100.0), Twips::from_pixels(-100.0))),
+                    fill_style_0: None,
+                    fill_style_1: Some(1),
+                    line_style: None,
+                    new_styles: None,
+                }),
+                edge(200.0, 0.0),
+                edge(0.0, 200.0),
+                edge(-200.0, 0.0),
+                edge(0.0, -200.0),
+            ],
+        }
+    }
+
+    fn gradient(matrix: Matrix, spread: GradientSpread) -> swf::Gradient {
+        swf::Gradient {
+            matrix,
+            spread,
+            interpolation: GradientInterpolation::RGB,
+            records: vec![
+                GradientRecord {
+                    ratio: 0,
+                    color: swf::Color {
+                        r: 255,
+                        g: 0,
+                        b: 0,
+                        a: 255,
+                    },
+                },
+                GradientRecord {
+                    ratio: 255,
+                    color: swf::Color {
+                        r: 0,
+                        g: 0,
+                        b: 255,
+                        a: 255,
+                    },
+                },
+            ],
+        }
+    }
+
+    /// A gradient matrix, with angles in degrees.
+    fn matrix(scale: f32, rotation: f32, shear: f32, translate: (f64, f64)) -> Matrix {
+        let (sin, cos) = rotation.to_radians().sin_cos();
+        Matrix {
+            translate_x: Twips::from_pixels(translate.0),
+            translate_y: Twips::from_pixels(translate.1),
+            scale_x: scale * cos,
+            scale_y: scale * (cos - shear * sin),
+            rotate_skew_0: scale * sin,
+            rotate_skew_1: scale * (shear * cos - sin),
+        }
+    }
+
+    /// Tessellates a square filled with `fill_style`, and returns its single draw.
+    fn tessellate(fill_style: FillStyle) -> Draw {
+        let mut mesh = ShapeTessellator::new().tessellate_shape(&square(fill_style), |_| None);
+        assert_eq!(mesh.len(), 1);
+        mesh.remove(0)
+    }
+
+    /// Maps a point of the gradient square to shape coordinates, in pixels, like Flash does.
+    fn gradient_to_shape(m: &Matrix, x: f32, y: f32) -> (f32, f32) {
+        (
+            (m.scale_x * x + m.rotate_skew_1 * y + m.translate_x.get() as f32) / 20.0,
+            (m.rotate_skew_0 * x + m.scale_y * y + m.translate_y.get() as f32) / 20.0,
+        )
+    }
+
+    /// The ratio of a gradient at a point of the shape, like the gradient shaders compute it
+    /// before applying the spread mode.
+    fn ratio_at(gradient: &Gradient, (x, y): (f32, f32)) -> f32 {
+        let m = &gradient.matrix;
+        let u = m[0][0] * x + m[1][0] * y + m[2][0];
+        let v = m[0][1] * x + m[1][1] * y + m[2][1];
+        let (x, y) = (u * 2.0 - 1.0, v * 2.0 - 1.0);
+        match gradient.gradient_type {
+            GradientType::Linear => u,
+            GradientType::Radial => (x * x + y * y).sqrt(),
+            GradientType::Focal => {
+                let f = gradient.focal_point;
+                let (dx, dy) = (f - x, -y);
+                let l = (dx * dx + dy * dy).sqrt();
+                let (dx, dy) = (dx / l, dy / l);
+                l / ((1.0 - f * f * dy * dy).sqrt() + f * dx)
+            }
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    /// Points inside the gradient square.
+    const SAMPLES: [(f32, f32); 6] = [
+        (-16384.0, 0.0),
+        (0.0, 0.0),
+        (16384.0, 0.0),
+        (8192.0, -4096.0),
+        (-4096.0, 12288.0),
+        (0.0, -16384.0),
+    ];
+
+    /// Rotations, shears and scales of the gradient square.
+    fn matrices() -> Vec<Matrix> {
+        let fit = 200.0 * 20.0 / 32768.0;
+        vec![
+            matrix(fit, 0.0, 0.0, (0.0, 0.0)),
+            matrix(fit, 30.0, 0.0, (0.0, 0.0)),
+            matrix(fit, 90.0, 0.0, (0.0, 0.0)),
+            matrix(fit, 180.0, 0.0, (0.0, 0.0)),
+            matrix(fit, -135.0, 0.0, (20.0, -30.0)),
+            matrix(fit, 0.0, 1.0, (0.0, 0.0)),
+            matrix(fit, 45.0, -2.5, (10.0, 10.0)),
+            // Stretched vertically.
+            Matrix {
+                scale_y: fit * 4.0,
+                ..matrix(fit / 2.0, 0.0, 0.0, (0.0, 0.0))
+            },
+            // The smallest and largest scales a SWF can store.
+            matrix(1.0 / 65536.0, 60.0, 0.0, (0.0, 0.0)),
+            matrix(32767.0, 60.0, 0.5, (0.0, 0.0)),
+        ]
+    }
+
+    #[test]
+    fn linear_gradients_follow_their_matrix() {
+        for m in matrices() {
+            let draw = tessellate(FillStyle::LinearGradient(gradient(
+                m.clone(),
+                GradientSpread::Pad,
+            )));
+            let gradient = match draw.draw_type {
+                DrawType::Gradient(gradient) => gradient,
+                _ => panic!("Expected a gradient"),
+            };
+            assert_eq!(gradient.gradient_type, GradientType::Linear);
+            for &(x, y) in &SAMPLES {
+                let ratio = ratio_at(&gradient, gradient_to_shape(&m, x, y));
+                assert_close(ratio, x / 32768.0 + 0.5);
+            }
+        }
+    }
+
+    #[test]
+    fn radial_gradients_follow_their_matrix() {
+        for m in matrices() {
+            let draw = tessellate(FillStyle::RadialGradient(gradient(
+                m.clone(),
+                GradientSpread::Pad,
+            )));
+            let gradient = match draw.draw_type {
+                DrawType::Gradient(gradient) => gradient,
+                _ => panic!("Expected a gradient"),
+            };
+            for &(x, y) in &SAMPLES {
+                let ratio = ratio_at(&gradient, gradient_to_shape(&m, x, y));
+                assert_close(ratio, (x * x + y * y).sqrt() / 16384.0);
+            }
+        }
+    }
+
+    #[test]
+    fn focal_gradients_start_at_their_focal_point() {
+        for m in matrices() {
+            for &focal_point in &[-0.998, -0.5, 0.0, 0.75, 0.998] {
+                let draw = tessellate(FillStyle::FocalGradient {
+                    gradient: gradient(m.clone(), GradientSpread::Reflect),
+                    focal_point,
+                });
+                let gradient = match draw.draw_type {
+                    DrawType::Gradient(gradient) => gradient,
+                    _ => panic!("Expected a gradient"),
+                };
+                assert_eq!(gradient.gradient_type, GradientType::Focal);
+                assert_eq!(gradient.repeat_mode, GradientSpread::Reflect);
+
+                // The gradient starts next to the focal point, and ends on the edge of the
+                // circle, whichever side it's on.
+                let x = focal_point * 16384.0 - focal_point.signum() * 16.0;
+                let near_focal_point = gradient_to_shape(&m, x, 0.0);
+                assert!(ratio_at(&gradient, near_focal_point) < 0.01);
+                for &(x, y) in &[(-16384.0, 0.0), (16384.0, 0.0), (0.0, 16384.0)] {
+                    assert_close(ratio_at(&gradient, gradient_to_shape(&m, x, y)), 1.0);
+                }
+
+                // Halfway between the focal point and the edge.
+                let x = (focal_point - 1.0) / 2.0 * 16384.0;
+                assert_close(ratio_at(&gradient, gradient_to_shape(&m, x, 0.0)), 0.5);
+            }
+        }
+    }
+
+    #[test]
+    fn focal_points_on_the_edge_are_clamped() {
+        let fit = 200.0 * 20.0 / 32768.0;
+        for &(focal_point, clamped) in &[(1.0, 0.998), (-1.0, -0.998)] {
+            let m = matrix(fit, 30.0, 0.0, (0.0, 0.0));
+            let draw = tessellate(FillStyle::FocalGradient {
+                gradient: gradient(m.clone(), GradientSpread::Pad),
+                focal_point,
+            });
+            let gradient = match draw.draw_type {
+                DrawType::Gradient(gradient) => gradient,
+                _ => panic!("Expected a gradient"),
+            };
+            assert_eq!(gradient.focal_point, clamped);
+
+            // Without clamping, the far side of the circle would be infinitely far away.
+            let far_side = gradient_to_shape(&m, -focal_point * 16384.0, 0.0);
+            assert_close(ratio_at(&gradient, far_side), 1.0);
+        }
+    }
+
+    #[test]
+    fn degenerate_gradients_are_filled_with_their_last_color() {
+        let degenerate = [
+            matrix(0.0, 0.0, 0.0, (0.0, 0.0)),
+            Matrix {
+                scale_y: 0.0,
+                ..matrix(1.0, 0.0, 0.0, (0.0, 0.0))
+            },
+            Matrix {
+                rotate_skew_0: 1.0,
+                rotate_skew_1: 1.0,
+                ..matrix(1.0, 0.0, 0.0, (0.0, 0.0))
+            },
+        ];
+        for m in degenerate.iter() {
+            for style in vec![
+                FillStyle::LinearGradient(gradient(m.clone(), GradientSpread::Repeat)),
+                FillStyle::RadialGradient(gradient(m.clone(), GradientSpread::Pad)),
+                FillStyle::FocalGradient {
+                    gradient: gradient(m.clone(), GradientSpread::Reflect),
+                    focal_point: 0.5,
+                },
+            ] {
+                let draw = tessellate(style);
+                assert!(matches!(draw.draw_type, DrawType::Color));
+                assert!(draw.vertices.iter().all(|v| v.color == 0xffff_0000));
+            }
+        }
+    }
+}
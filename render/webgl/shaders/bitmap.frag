@@ -9,15 +9,30 @@ uniform mat3 u_matrix;
 
 uniform sampler2D u_texture;
 
+// Repeating fills tile the bitmap; other fills extend its edge pixels.
+uniform bool u_repeating;
+
+// Half the size of a texel, in texture space.
+uniform vec2 u_half_texel;
+
 varying vec2 frag_uv;
 
 void main() {
-    vec4 color = texture2D(u_texture, frag_uv);
+    vec2 uv;
+    if( u_repeating ) {
+        // Wrap here, as WebGL 1 can't repeat textures that aren't a power of two in size.
+        uv = fract(frag_uv);
+    } else {
+        // Sample no further out than the centers of the edge texels, so that the edges never
+        // blend with anything else, regardless of the wrap mode or multisampling.
+        uv = clamp(frag_uv, u_half_texel, 1.0 - u_half_texel);
+    }
+    vec4 color = texture2D(u_texture, uv);
 
     // Unmultiply alpha before apply color transform.
     if( color.a > 0.0 ) {
         color.rgb /= color.a;
-        color = mult_color * color + add_color;
+        color = clamp(mult_color * color + add_color, 0.0, 1.0);
         color.rgb *= color.a;
     }
 
@@ -90,5 +90,7 @@ void main() {
         color = u_colors[7];
     }
 
-    gl_FragColor = mult_color * color + add_color;
+    color = clamp(mult_color * color + add_color, 0.0, 1.0);
+    // Output premultiplied alpha, like bitmaps.
+    gl_FragColor = vec4(color.rgb * color.a, color.a);
 }
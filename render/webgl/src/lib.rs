@@ -1,6 +1,6 @@
 use ruffle_core::backend::render::swf::{self, FillStyle};
 use ruffle_core::backend::render::{
-    BitmapHandle, BitmapInfo, Color, Letterbox, RenderBackend, ShapeHandle, Transform,
+    BitmapHandle, BitmapInfo, Color, Letterbox, PixelRegion, RenderBackend, ShapeHandle, Transform,
 };
 use ruffle_render_common_tess::{GradientSpread, GradientType, ShapeTessellator, Vertex};
 use ruffle_web_common::JsResult;
@@ -41,17 +41,17 @@ pub struct WebGlRenderBackend {
 
     shape_tessellator: ShapeTessellator,
 
-    textures: Vec<(swf::CharacterId, Texture)>,
-    meshes: Vec<Mesh>,
+    textures: Vec<Option<(swf::CharacterId, Texture)>>,
+    meshes: Vec<Option<Mesh>>,
 
     quad_shape: ShapeHandle,
 
     num_masks: u32,
     num_masks_active: u32,
-    write_stencil_mask: u32,
-    test_stencil_mask: u32,
-    next_stencil_mask: u32,
-    mask_stack: Vec<(u32, u32)>,
+    mask_state: MaskState,
+    /// The shapes drawn into each mask that is being written or is active, innermost last.
+    /// These are drawn again to remove the mask from the stencil buffer when it is popped.
+    masker_draws: Vec<Vec<(ShapeHandle, Transform)>>,
 
     active_program: *const ShaderProgram,
     mask_state_dirty: bool,
@@ -59,6 +59,9 @@ pub struct WebGlRenderBackend {
     mult_color: Option<[f32; 4]>,
     add_color: Option<[f32; 4]>,
 
+    /// Whether bitmaps that ask to be smoothed are, or all use nearest-neighbor sampling.
+    bitmap_smoothing: bool,
+
     viewport_width: f32,
     viewport_height: f32,
     view_matrix: [[f32; 4]; 4],
@@ -66,6 +69,25 @@ pub struct WebGlRenderBackend {
 
 impl WebGlRenderBackend {
     pub fn new(canvas: &HtmlCanvasElement) -> Result<Self, Error> {
+        Self::new_internal(canvas, None)
+    }
+
+    /// Creates a renderer that anti-aliases with the given number of samples per pixel, or
+    /// none if the count is 1.
+    ///
+    /// The count is lowered to the most the device supports. It only applies to WebGL2, as
+    /// WebGL1 leaves anti-aliasing to the browser.
+    pub fn with_msaa_sample_count(
+        canvas: &HtmlCanvasElement,
+        msaa_sample_count: u32,
+    ) -> Result<Self, Error> {
+        Self::new_internal(canvas, Some(msaa_sample_count.max(1)))
+    }
+
+    fn new_internal(
+        canvas: &HtmlCanvasElement,
+        requested_sample_count: Option<u32>,
+    ) -> Result<Self, Error> {
         // Create WebGL context.
         let options = [
             ("stencil", JsValue::TRUE),
@@ -88,18 +110,20 @@ impl WebGlRenderBackend {
 
             // Determine MSAA sample count.
             // Default to 4x MSAA on desktop, 2x on mobile/tablets.
-            let mut msaa_sample_count = if ruffle_web_common::is_mobile_or_tablet() {
-                log::info!("Running on a mobile device; defaulting to 2x MSAA");
-                2
-            } else {
-                4
+            let mut msaa_sample_count = match requested_sample_count {
+                Some(msaa_sample_count) => msaa_sample_count,
+                None if ruffle_web_common::is_mobile_or_tablet() => {
+                    log::info!("Running on a mobile device; defaulting to 2x MSAA");
+                    2
+                }
+                None => 4,
             };
 
             // Ensure that we don't exceed the max MSAA of this device.
             if let Ok(max_samples) = gl2.get_parameter(Gl2::MAX_SAMPLES) {
                 let max_samples: u32 = max_samples.as_f64().unwrap_or(0.0) as u32;
                 if max_samples > 0 && max_samples < msaa_sample_count {
-                    log::info!("Device only supports {}xMSAA", max_samples);
+                    log::warn!("Device only supports {}xMSAA", max_samples);
                     msaa_sample_count = max_samples;
                 }
             }
@@ -177,20 +201,19 @@ impl WebGlRenderBackend {
             view_matrix: [[0.0; 4]; 4],
             num_masks: 0,
             num_masks_active: 0,
-            write_stencil_mask: 0,
-            test_stencil_mask: 0,
-            next_stencil_mask: 1,
-            mask_stack: vec![],
+            mask_state: MaskState::DrawContent,
+            masker_draws: vec![],
 
             active_program: std::ptr::null(),
             mask_state_dirty: true,
             blend_func: (Gl::SRC_ALPHA, Gl::ONE_MINUS_SRC_ALPHA),
             mult_color: None,
             add_color: None,
+            bitmap_smoothing: true,
         };
 
         let quad_mesh = renderer.build_quad_mesh()?;
-        renderer.meshes.push(quad_mesh);
+        renderer.meshes.push(Some(quad_mesh));
         renderer.build_msaa_buffers()?;
         renderer.build_matrices();
 
@@ -431,6 +454,7 @@ impl WebGlRenderBackend {
         let lyon_mesh = self.shape_tessellator.tessellate_shape(shape, |id| {
             textures
                 .iter()
+                .flatten()
                 .find(|(other_id, _tex)| *other_id == id)
                 .map(|tex| (tex.1.width, tex.1.height))
         });
@@ -572,7 +596,7 @@ impl WebGlRenderBackend {
             }
         }
 
-        self.meshes.push(Mesh { draws });
+        self.meshes.push(Some(Mesh { draws }));
 
         handle
     }
@@ -603,6 +627,15 @@ impl WebGlRenderBackend {
         Ok(vao)
     }
 
+    /// Deletes a VAO.
+    fn delete_vertex_array(&self, vao: &WebGlVertexArrayObject) {
+        if let Some(gl2) = &self.gl2 {
+            gl2.delete_vertex_array(Some(vao));
+        } else {
+            self.vao_ext.delete_vertex_array_oes(Some(vao));
+        };
+    }
+
     /// Binds a VAO.
     fn bind_vertex_array(&self, vao: Option<&WebGlVertexArrayObject>) {
         if let Some(gl2) = &self.gl2 {
@@ -616,23 +649,19 @@ impl WebGlRenderBackend {
         // Set stencil state for masking, if neccessary.
         if self.mask_state_dirty {
             if self.num_masks > 0 {
+                // Everything is drawn where the stencil buffer equals the number of active masks.
                 self.gl.enable(Gl::STENCIL_TEST);
-                if self.num_masks_active < self.num_masks {
-                    self.gl.stencil_mask(self.write_stencil_mask);
-                    self.gl
-                        .stencil_func(Gl::ALWAYS, self.write_stencil_mask as i32, 0xff);
-                    self.gl.stencil_op(Gl::KEEP, Gl::KEEP, Gl::REPLACE);
-                    self.gl.color_mask(false, false, false, false);
-                } else {
-                    self.gl.stencil_mask(0xff);
-                    self.gl.stencil_func(
-                        Gl::EQUAL,
-                        self.test_stencil_mask as i32,
-                        self.test_stencil_mask,
-                    );
-                    self.gl.stencil_op(Gl::KEEP, Gl::KEEP, Gl::KEEP);
-                    self.gl.color_mask(true, true, true, true);
-                }
+                self.gl.stencil_mask(0xff);
+                self.gl
+                    .stencil_func(Gl::EQUAL, self.num_masks_active as i32, 0xff);
+                let (pass_op, write_color) = match self.mask_state {
+                    MaskState::DrawContent => (Gl::KEEP, true),
+                    MaskState::WriteMask => (Gl::INCR, false),
+                    MaskState::ClearMask => (Gl::DECR, false),
+                };
+                self.gl.stencil_op(Gl::KEEP, Gl::KEEP, pass_op);
+                self.gl
+                    .color_mask(write_color, write_color, write_color, write_color);
             } else {
                 self.gl.disable(Gl::STENCIL_TEST);
                 self.gl.color_mask(true, true, true, true);
@@ -723,14 +752,14 @@ impl RenderBackend for WebGlRenderBackend {
             .tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_MAG_FILTER, Gl::LINEAR as i32);
 
         let handle = BitmapHandle(self.textures.len());
-        self.textures.push((
+        self.textures.push(Some((
             id,
             Texture {
                 texture,
                 width: metadata.width.into(),
                 height: metadata.height.into(),
             },
-        ));
+        )));
 
         BitmapInfo {
             handle,
@@ -776,14 +805,14 @@ impl RenderBackend for WebGlRenderBackend {
             .tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_MAG_FILTER, Gl::LINEAR as i32);
 
         let handle = BitmapHandle(self.textures.len());
-        self.textures.push((
+        self.textures.push(Some((
             id,
             Texture {
                 texture,
                 width,
                 height,
             },
-        ));
+        )));
 
         BitmapInfo {
             handle,
@@ -823,14 +852,14 @@ impl RenderBackend for WebGlRenderBackend {
             .tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_MAG_FILTER, Gl::LINEAR as i32);
 
         let handle = BitmapHandle(self.textures.len());
-        self.textures.push((
+        self.textures.push(Some((
             swf_tag.id,
             Texture {
                 texture,
                 width: swf_tag.width.into(),
                 height: swf_tag.height.into(),
             },
-        ));
+        )));
 
         BitmapInfo {
             handle,
@@ -839,12 +868,91 @@ impl RenderBackend for WebGlRenderBackend {
         }
     }
 
+    fn register_bitmap_raw(&mut self, width: u32, height: u32, rgba: Vec<u8>) -> BitmapHandle {
+        let texture = self.gl.create_texture().unwrap();
+        self.gl.bind_texture(Gl::TEXTURE_2D, Some(&texture));
+        self.gl
+            .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                Gl::TEXTURE_2D,
+                0,
+                Gl::RGBA as i32,
+                width as i32,
+                height as i32,
+                0,
+                Gl::RGBA,
+                Gl::UNSIGNED_BYTE,
+                Some(&rgba),
+            )
+            .warn_on_error();
+
+        // You must set the texture parameters for non-power-of-2 textures to function in WebGL.
+        self.gl
+            .tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_WRAP_S, Gl::CLAMP_TO_EDGE as i32);
+        self.gl
+            .tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_WRAP_T, Gl::CLAMP_TO_EDGE as i32);
+        self.gl
+            .tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_MIN_FILTER, Gl::LINEAR as i32);
+        self.gl
+            .tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_MAG_FILTER, Gl::LINEAR as i32);
+
+        let handle = BitmapHandle(self.textures.len());
+        self.textures.push(Some((
+            0,
+            Texture {
+                texture,
+                width,
+                height,
+            },
+        )));
+        handle
+    }
+
+    fn update_bitmap_region(&mut self, bitmap: BitmapHandle, region: PixelRegion, rgba: &[u8]) {
+        if let Some(Some((_id, texture))) = self.textures.get(bitmap.0) {
+            self.gl.bind_texture(Gl::TEXTURE_2D, Some(&texture.texture));
+            self.gl
+                .tex_sub_image_2d_with_i32_and_i32_and_u32_and_type_and_opt_u8_array(
+                    Gl::TEXTURE_2D,
+                    0,
+                    region.x_min as i32,
+                    region.y_min as i32,
+                    region.width() as i32,
+                    region.height() as i32,
+                    Gl::RGBA,
+                    Gl::UNSIGNED_BYTE,
+                    Some(rgba),
+                )
+                .warn_on_error();
+        } else {
+            log::warn!("Attempted to update nonexistent bitmap {}", bitmap.0);
+        }
+    }
+
+    fn unregister_shape(&mut self, shape: ShapeHandle) {
+        // The quad mesh is shared by every bitmap draw and is never freed.
+        if shape.0 == self.quad_shape.0 {
+            return;
+        }
+        if let Some(mesh) = self.meshes.get_mut(shape.0).and_then(Option::take) {
+            for draw in mesh.draws {
+                self.delete_vertex_array(&draw.vao);
+                self.gl.delete_buffer(Some(&draw.vertex_buffer));
+                self.gl.delete_buffer(Some(&draw.index_buffer));
+            }
+        }
+    }
+
+    fn unregister_bitmap(&mut self, bitmap: BitmapHandle) {
+        if let Some((_id, texture)) = self.textures.get_mut(bitmap.0).and_then(Option::take) {
+            self.gl.delete_texture(Some(&texture.texture));
+        }
+    }
+
     fn begin_frame(&mut self) {
         self.num_masks = 0;
         self.num_masks_active = 0;
-        self.write_stencil_mask = 0;
-        self.test_stencil_mask = 0;
-        self.next_stencil_mask = 1;
+        self.mask_state = MaskState::DrawContent;
+        self.masker_draws.clear();
 
         self.active_program = std::ptr::null();
         self.mask_state_dirty = true;
@@ -923,9 +1031,11 @@ impl RenderBackend for WebGlRenderBackend {
             self.gl
                 .bind_texture(Gl2::TEXTURE_2D, Some(&msaa_buffers.framebuffer_texture));
             program.uniform1i(&self.gl, ShaderUniform::BitmapTexture, 0);
+            program.uniform1i(&self.gl, ShaderUniform::BitmapRepeating, 0);
+            program.uniform2f(&self.gl, ShaderUniform::BitmapHalfTexel, 0.0, 0.0);
 
             // Render the quad.
-            let quad = &self.meshes[self.quad_shape.0];
+            let quad = self.meshes[self.quad_shape.0].as_ref().unwrap();
             self.bind_vertex_array(Some(&quad.draws[0].vao));
             self.gl.draw_elements_with_i32(
                 Gl::TRIANGLES,
@@ -949,18 +1059,24 @@ impl RenderBackend for WebGlRenderBackend {
         self.gl.clear(Gl::COLOR_BUFFER_BIT | Gl::STENCIL_BUFFER_BIT);
     }
 
-    fn render_bitmap(&mut self, bitmap: BitmapHandle, transform: &Transform) {
+    fn render_bitmap(&mut self, bitmap: BitmapHandle, transform: &Transform, smoothing: bool) {
         // TODO: Might be better to make this separate code to render the bitmap
         // instead of going through render_shape. But render_shape already handles
         // masking etc.
-        if let Some((id, bitmap)) = self.textures.get(bitmap.0) {
+        if let Some(Some((id, bitmap))) = self.textures.get(bitmap.0) {
             // Adjust the quad draw to use the target bitmap.
-            let mesh = &mut self.meshes[self.quad_shape.0];
+            let mesh = self.meshes[self.quad_shape.0].as_mut().unwrap();
             let draw = &mut mesh.draws[0];
             let width = bitmap.width as f32;
             let height = bitmap.height as f32;
-            if let DrawType::Bitmap(Bitmap { id: draw_id, .. }) = &mut draw.draw_type {
+            if let DrawType::Bitmap(Bitmap {
+                id: draw_id,
+                is_smoothed,
+                ..
+            }) = &mut draw.draw_type
+            {
                 *draw_id = *id;
+                *is_smoothed = smoothing;
             }
 
             // Scale the quad to the bitmap's dimensions.
@@ -1008,19 +1124,26 @@ impl RenderBackend for WebGlRenderBackend {
         ];
 
         self.set_stencil_state();
+        if self.mask_state == MaskState::WriteMask {
+            // Bitmaps are drawn as quads, which cover the same pixels whatever their texture.
+            if let Some(draws) = self.masker_draws.last_mut() {
+                draws.push((shape, transform.clone()));
+            }
+        }
 
-        let mesh = &self.meshes[shape.0];
+        let mesh = if let Some(Some(mesh)) = self.meshes.get(shape.0) {
+            mesh
+        } else {
+            log::warn!("Attempted to render nonexistent shape {}", shape.0);
+            return;
+        };
         for draw in &mesh.draws {
             self.bind_vertex_array(Some(&draw.vao));
 
             let (program, src_blend, dst_blend) = match &draw.draw_type {
                 DrawType::Color => (&self.color_program, Gl::SRC_ALPHA, Gl::ONE_MINUS_SRC_ALPHA),
-                DrawType::Gradient(_) => (
-                    &self.gradient_program,
-                    Gl::SRC_ALPHA,
-                    Gl::ONE_MINUS_SRC_ALPHA,
-                ),
-                // Bitmaps use pre-multiplied alpha.
+                // Gradients and bitmaps use pre-multiplied alpha.
+                DrawType::Gradient(_) => (&self.gradient_program, Gl::ONE, Gl::ONE_MINUS_SRC_ALPHA),
                 DrawType::Bitmap { .. } => (&self.bitmap_program, Gl::ONE, Gl::ONE_MINUS_SRC_ALPHA),
             };
 
@@ -1090,6 +1213,7 @@ impl RenderBackend for WebGlRenderBackend {
                     let texture = &self
                         .textures
                         .iter()
+                        .flatten()
                         .find(|(id, _tex)| *id == bitmap.id)
                         .unwrap()
                         .1;
@@ -1104,9 +1228,20 @@ impl RenderBackend for WebGlRenderBackend {
                     self.gl.active_texture(Gl::TEXTURE0);
                     self.gl.bind_texture(Gl::TEXTURE_2D, Some(&texture.texture));
                     program.uniform1i(&self.gl, ShaderUniform::BitmapTexture, 0);
+                    program.uniform1i(
+                        &self.gl,
+                        ShaderUniform::BitmapRepeating,
+                        bitmap.is_repeating as i32,
+                    );
+                    program.uniform2f(
+                        &self.gl,
+                        ShaderUniform::BitmapHalfTexel,
+                        0.5 / texture.width as f32,
+                        0.5 / texture.height as f32,
+                    );
 
                     // Set texture parameters.
-                    let filter = if bitmap.is_smoothed {
+                    let filter = if bitmap.is_smoothed && self.bitmap_smoothing {
                         Gl::LINEAR as i32
                     } else {
                         Gl::NEAREST as i32
@@ -1116,8 +1251,10 @@ impl RenderBackend for WebGlRenderBackend {
                     self.gl
                         .tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_MIN_FILTER, filter);
                     // On WebGL1, you are unable to change the wrapping parameter causes non-power-of-2 textures.
+                    // The shader wraps repeating fills itself, but the sampler should repeat as
+                    // well where possible, so that smoothing blends across the seams.
                     let wrap = if self.gl2.is_some() && bitmap.is_repeating {
-                        Gl::MIRRORED_REPEAT as i32
+                        Gl::REPEAT as i32
                     } else {
                         Gl::CLAMP_TO_EDGE as i32
                     };
@@ -1139,91 +1276,93 @@ impl RenderBackend for WebGlRenderBackend {
 
         self.gl.clear_color(0.0, 0.0, 0.0, 0.0);
 
-        match letterbox {
-            Letterbox::None => (),
-            Letterbox::Letterbox(margin_height) => {
-                self.gl.enable(Gl::SCISSOR_TEST);
-                self.gl
-                    .scissor(0, 0, self.viewport_width as i32, margin_height as i32);
-                self.gl.clear(Gl::COLOR_BUFFER_BIT);
-                self.gl.scissor(
-                    0,
-                    (self.viewport_height - margin_height) as i32,
-                    self.viewport_width as i32,
-                    margin_height as i32 + 1,
-                );
-                self.gl.clear(Gl::COLOR_BUFFER_BIT);
-                self.gl.disable(Gl::SCISSOR_TEST);
-            }
-            Letterbox::Pillarbox(margin_width) => {
-                self.gl.enable(Gl::SCISSOR_TEST);
-                self.gl
-                    .scissor(0, 0, margin_width as i32, self.viewport_height as i32);
-                self.gl.clear(Gl::COLOR_BUFFER_BIT);
-                self.gl.scissor(
-                    (self.viewport_width - margin_width) as i32,
-                    0,
-                    margin_width as i32 + 1,
-                    self.viewport_height as i32,
-                );
-                self.gl.clear(Gl::COLOR_BUFFER_BIT);
-                self.gl.scissor(
-                    0,
-                    0,
-                    self.viewport_width as i32,
-                    self.viewport_height as i32,
-                );
-                self.gl.disable(Gl::SCISSOR_TEST);
-            }
+        let bars = letterbox.bars(self.viewport_width, self.viewport_height);
+        if bars.is_empty() {
+            return;
+        }
+
+        self.gl.enable(Gl::SCISSOR_TEST);
+        for (x, y, width, height) in bars {
+            // The scissor box is measured from the bottom left corner of the viewport.
+            let (left, right) = (x.round() as i32, (x + width).round() as i32);
+            let (bottom, top) = (
+                (self.viewport_height - y - height).round() as i32,
+                (self.viewport_height - y).round() as i32,
+            );
+            self.gl.scissor(left, bottom, right - left, top - bottom);
+            self.gl.clear(Gl::COLOR_BUFFER_BIT);
         }
+        self.gl.scissor(
+            0,
+            0,
+            self.viewport_width as i32,
+            self.viewport_height as i32,
+        );
+        self.gl.disable(Gl::SCISSOR_TEST);
+    }
+
+    fn set_bitmap_smoothing_enabled(&mut self, enabled: bool) {
+        self.bitmap_smoothing = enabled;
     }
 
     fn push_mask(&mut self) {
-        // Desktop draws the masker to the stencil buffer, one bit per mask.
-        // Masks-within-masks are handled as a bitmask.
-        // This does unfortunately mean we are limited in the number of masks at once (usually 8 bits).
-        if self.next_stencil_mask >= 0x100 {
-            // If we've reached the limit of masks, clear the stencil buffer and start over.
-            // But this may not be correct if there is still a mask active (mask-within-mask).
-            if self.test_stencil_mask != 0 {
-                log::warn!(
-                    "Too many masks active for stencil buffer; possibly incorrect rendering"
-                );
-            }
-            self.next_stencil_mask = 1;
-            self.gl.stencil_mask(0xff);
-            self.gl.clear_stencil(self.test_stencil_mask as i32);
-            self.gl.clear(Gl::STENCIL_BUFFER_BIT);
-            self.gl.clear_stencil(0);
+        // Masks are drawn to the stencil buffer, which counts how many masks cover each pixel.
+        // The masker increments it where all enclosing masks are active, and content is drawn
+        // where it equals the number of active masks.
+        if self.num_masks >= 0xff {
+            log::warn!("Too many nested masks for stencil buffer; possibly incorrect rendering");
         }
         self.num_masks += 1;
-        self.mask_stack
-            .push((self.write_stencil_mask, self.test_stencil_mask));
-        self.write_stencil_mask = self.next_stencil_mask;
-        self.test_stencil_mask |= self.next_stencil_mask;
-        self.next_stencil_mask <<= 1;
+        self.mask_state = MaskState::WriteMask;
+        self.masker_draws.push(Vec::new());
         self.mask_state_dirty = true;
     }
 
     fn activate_mask(&mut self) {
         self.num_masks_active += 1;
+        self.mask_state = MaskState::DrawContent;
         self.mask_state_dirty = true;
     }
 
     fn pop_mask(&mut self) {
-        if !self.mask_stack.is_empty() {
+        if let Some(draws) = self.masker_draws.pop() {
+            // Draw the masker again to decrement the stencil buffer where it was incremented,
+            // so that no trace of the mask is left for the content drawn after it.
+            self.mask_state = MaskState::ClearMask;
+            self.mask_state_dirty = true;
+            for (shape, transform) in &draws {
+                self.render_shape(*shape, transform);
+            }
+
             self.num_masks -= 1;
             self.num_masks_active -= 1;
-            let (write, test) = self.mask_stack.pop().unwrap();
-            self.write_stencil_mask = write;
-            self.test_stencil_mask = test;
+            self.mask_state = if self.num_masks_active < self.num_masks {
+                MaskState::WriteMask
+            } else {
+                MaskState::DrawContent
+            };
             self.mask_state_dirty = true;
         } else {
-            log::warn!("Mask stack underflow\n");
+            log::warn!("Mask stack underflow");
         }
     }
 }
 
+/// How draws use the stencil buffer, which holds the number of masks covering each pixel.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum MaskState {
+    /// Draws color where the stencil buffer equals the number of active masks.
+    DrawContent,
+
+    /// Increments the stencil buffer where it equals the number of active masks, without
+    /// drawing any color.
+    WriteMask,
+
+    /// Decrements the stencil buffer where it equals the number of active masks, without
+    /// drawing any color.
+    ClearMask,
+}
+
 struct Texture {
     width: u32,
     height: u32,
@@ -1288,7 +1427,7 @@ struct ShaderProgram {
 }
 
 // These should match the uniform names in the shaders.
-const NUM_UNIFORMS: usize = 12;
+const NUM_UNIFORMS: usize = 14;
 const UNIFORM_NAMES: [&str; NUM_UNIFORMS] = [
     "world_matrix",
     "view_matrix",
@@ -1302,6 +1441,8 @@ const UNIFORM_NAMES: [&str; NUM_UNIFORMS] = [
     "u_repeat_mode",
     "u_focal_point",
     "u_texture",
+    "u_repeating",
+    "u_half_texel",
 ];
 
 enum ShaderUniform {
@@ -1317,6 +1458,8 @@ enum ShaderUniform {
     GradientRepeatMode,
     GradientFocalPoint,
     BitmapTexture,
+    BitmapRepeating,
+    BitmapHalfTexel,
 }
 
 impl ShaderProgram {
@@ -1382,6 +1525,10 @@ impl ShaderProgram {
         gl.uniform1i(self.uniforms[uniform as usize].as_ref(), value);
     }
 
+    fn uniform2f(&self, gl: &Gl, uniform: ShaderUniform, x: f32, y: f32) {
+        gl.uniform2f(self.uniforms[uniform as usize].as_ref(), x, y);
+    }
+
     fn uniform4fv(&self, gl: &Gl, uniform: ShaderUniform, values: &[f32]) {
         gl.uniform4fv_with_f32_array(self.uniforms[uniform as usize].as_ref(), values);
     }
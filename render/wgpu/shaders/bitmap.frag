@@ -1,5 +1,11 @@
 #version 450
 
+layout(set = 0, binding = 1) uniform Texture {
+    mat4 u_matrix;
+    // The minimum and maximum texture coordinates to sample.
+    vec4 u_uv_bounds;
+};
+
 layout(set = 0, binding = 2) uniform Colors {
     vec4 mult_color;
     vec4 add_color;
@@ -14,13 +20,17 @@ layout(location=0) out vec4 out_color;
 
 void main() {
 
-    vec4 color = texture(sampler2D(t_color, s_color), frag_uv);
+    // Clipped fills sample no further out than the centers of the edge texels, so that the
+    // edges never blend with anything else, regardless of the wrap mode or multisampling.
+    vec2 uv = clamp(frag_uv, u_uv_bounds.xy, u_uv_bounds.zw);
+    vec4 color = texture(sampler2D(t_color, s_color), uv);
     // Unmultiply alpha before apply color transform.
     if( color.a > 0 ) {
         color.rgb /= color.a;
-        color = mult_color * color + add_color;
-        color.rgb *= color.a;
     }
+    // Transparent texels are black, so offsets still show on them, as in Flash.
+    color = clamp(mult_color * color + add_color, 0.0, 1.0);
+    color.rgb *= color.a;
 
     out_color = color;
 }
\ No newline at end of file
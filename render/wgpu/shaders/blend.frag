@@ -0,0 +1,49 @@
+#version 450
+
+// Composites a layer onto its backdrop with a blend mode that can't be expressed as a blend
+// state. Both textures have pre-multiplied alpha.
+
+// The blend modes, numbered as in `CompositeMode`.
+#define DIFFERENCE 0
+#define INVERT 1
+#define OVERLAY 2
+#define HARD_LIGHT 3
+
+layout(set = 0, binding = 2) uniform BlendOptions {
+    int blend_mode;
+};
+
+layout(set = 0, binding = 3) uniform texture2D t_layer;
+layout(set = 0, binding = 4) uniform texture2D t_backdrop;
+layout(set = 0, binding = 5) uniform sampler s_texture;
+
+layout(location=0) in vec2 frag_uv;
+
+layout(location=0) out vec4 out_color;
+
+void main() {
+    vec4 src = texture(sampler2D(t_layer, s_texture), frag_uv);
+    vec4 dst = texture(sampler2D(t_backdrop, s_texture), frag_uv);
+
+    // The modes blend unmultiplied colors.
+    vec3 cs = src.rgb / max(src.a, 0.0001);
+    vec3 cb = dst.rgb / max(dst.a, 0.0001);
+
+    vec3 multiply = 2.0 * cs * cb;
+    vec3 screen = 1.0 - 2.0 * (1.0 - cs) * (1.0 - cb);
+
+    vec3 blended;
+    if (blend_mode == DIFFERENCE) {
+        blended = abs(cs - cb);
+    } else if (blend_mode == INVERT) {
+        blended = 1.0 - cb;
+    } else if (blend_mode == OVERLAY) {
+        blended = mix(multiply, screen, step(0.5, cb));
+    } else {
+        blended = mix(multiply, screen, step(0.5, cs));
+    }
+
+    // Where only one of the layer and the backdrop is drawn, it is kept as it is.
+    vec3 color = src.rgb * (1.0 - dst.a) + dst.rgb * (1.0 - src.a) + src.a * dst.a * blended;
+    out_color = vec4(color, src.a + dst.a * (1.0 - src.a));
+}
\ No newline at end of file
@@ -5,5 +5,6 @@ layout(location=0) in vec4 frag_color;
 layout(location=0) out vec4 out_color;
 
 void main() {
-    out_color = frag_color;
+    // Output premultiplied alpha, like bitmaps and gradients.
+    out_color = vec4(frag_color.rgb * frag_color.a, frag_color.a);
 }
\ No newline at end of file
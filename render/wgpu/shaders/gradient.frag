@@ -10,10 +10,15 @@ layout(set = 0, binding = 3) uniform Gradient {
     uint u_num_colors;
     int u_repeat_mode;
     float u_focal_point;
-    vec4 u_ratios[16];
-    vec4 u_colors[16];
+    // 16 ratios, packed four to a vec4.
+    vec4 u_ratios[4];
+    // 16 colors as half floats, packed two to a uvec4.
+    uvec4 u_colors[8];
 };
 
+#define RATIO(i) u_ratios[(i) >> 2][(i) & 3]
+#define COLOR(i) vec4(unpackHalf2x16(u_colors[(i) >> 1][((i) & 1) * 2]), unpackHalf2x16(u_colors[(i) >> 1][((i) & 1) * 2 + 1]))
+
 layout(location=0) in vec2 frag_uv;
 
 layout(location=0) out vec4 out_color;
@@ -63,13 +68,15 @@ void main() {
     }
     int i = 0;
     int j = 1;
-    t = clamp(t, u_ratios[0].x, u_ratios[last].x);
-    while( t > u_ratios[j].x )
+    t = clamp(t, RATIO(0), RATIO(last));
+    while( t > RATIO(j) )
     {
         i = j;
         j++;
     }
-    float a = (t - u_ratios[i].x) / (u_ratios[j].x - u_ratios[i].x);
-    color = mix(u_colors[i], u_colors[j], a);
-    out_color = mult_color * color + add_color;
+    float a = (t - RATIO(i)) / (RATIO(j) - RATIO(i));
+    color = mix(COLOR(i), COLOR(j), a);
+    color = clamp(mult_color * color + add_color, 0.0, 1.0);
+    // Output premultiplied alpha, like bitmaps.
+    out_color = vec4(color.rgb * color.a, color.a);
 }
\ No newline at end of file
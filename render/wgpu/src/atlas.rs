@@ -0,0 +1,355 @@
+//! Packing small bitmaps into shared textures.
+
+/// The largest width and height of an atlas page.
+pub const ATLAS_PAGE_SIZE: u32 = 2048;
+
+/// The number of mip levels of an atlas page.
+///
+/// Slots are aligned to, and padded by, enough pixels that each bitmap still has a gutter of
+/// its own pixels at the smallest level, so that smoothing never blends in a neighbor.
+/// Bitmaps are rarely drawn at less than a quarter of their size, and beyond that they
+/// sample the smallest level.
+pub const ATLAS_MIP_LEVEL_COUNT: u32 = 3;
+
+/// The alignment of slots, which keeps them on whole pixels at every mip level.
+const SLOT_ALIGNMENT: u32 = 1 << (ATLAS_MIP_LEVEL_COUNT - 1);
+
+/// The pixels around each bitmap in its slot, repeating its edge pixels.
+const GUTTER: u32 = SLOT_ALIGNMENT;
+
+/// Where a bitmap is stored in an atlas page.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AtlasSlot {
+    pub page: usize,
+
+    /// The position of the bitmap in the page, inside the gutter of its slot.
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+
+    /// The size of the page.
+    page_size: u32,
+}
+
+impl AtlasSlot {
+    /// The size of the slot, including the gutter on every side.
+    pub fn padded_size(&self) -> (u32, u32) {
+        (padded(self.width), padded(self.height))
+    }
+
+    /// The position of the slot in the page, including its gutter.
+    pub fn padded_origin(&self) -> (u32, u32) {
+        (self.x - GUTTER, self.y - GUTTER)
+    }
+
+    /// Converts a texture matrix producing coordinates of the bitmap, from 0 to 1 across it,
+    /// into one producing the coordinates of the same pixels in the page.
+    pub fn to_page_matrix(&self, matrix: [[f32; 4]; 4]) -> [[f32; 4]; 4] {
+        let page_size = self.page_size as f32;
+        let (scale_x, scale_y) = (
+            self.width as f32 / page_size,
+            self.height as f32 / page_size,
+        );
+        let (offset_x, offset_y) = (self.x as f32 / page_size, self.y as f32 / page_size);
+        let [a, b, c, d] = matrix;
+        [
+            [a[0] * scale_x, a[1] * scale_y, a[2], a[3]],
+            [b[0] * scale_x, b[1] * scale_y, b[2], b[3]],
+            [
+                c[0] * scale_x + offset_x * c[2],
+                c[1] * scale_y + offset_y * c[2],
+                c[2],
+                c[3],
+            ],
+            d,
+        ]
+    }
+
+    /// Converts the minimum and maximum texture coordinates of the bitmap, from 0 to 1 across
+    /// it, into the coordinates of the same pixels in the page.
+    pub fn to_page_bounds(&self, [u_min, v_min, u_max, v_max]: [f32; 4]) -> [f32; 4] {
+        let page_size = self.page_size as f32;
+        let to_page_u = |u: f32| (self.x as f32 + u * self.width as f32) / page_size;
+        let to_page_v = |v: f32| (self.y as f32 + v * self.height as f32) / page_size;
+        [
+            to_page_u(u_min),
+            to_page_v(v_min),
+            to_page_u(u_max),
+            to_page_v(v_max),
+        ]
+    }
+}
+
+/// The size of a slot holding a bitmap of the given size.
+fn padded(size: u32) -> u32 {
+    (size + 2 * GUTTER + SLOT_ALIGNMENT - 1) / SLOT_ALIGNMENT * SLOT_ALIGNMENT
+}
+
+/// Copies premultiplied RGBA pixels into the padded image of their slot, surrounded by copies
+/// of their edge pixels.
+pub fn pad_bitmap(slot: &AtlasSlot, rgba: &[u8]) -> Vec<u8> {
+    let (padded_width, padded_height) = slot.padded_size();
+    let mut padded_rgba = Vec::with_capacity((padded_width * padded_height * 4) as usize);
+    let clamp = |i: u32, size: u32| i.saturating_sub(GUTTER).min(size - 1);
+    for y in 0..padded_height {
+        let row = clamp(y, slot.height);
+        for x in 0..padded_width {
+            let i = ((row * slot.width + clamp(x, slot.width)) * 4) as usize;
+            padded_rgba.extend_from_slice(&rgba[i..i + 4]);
+        }
+    }
+    padded_rgba
+}
+
+/// A row of slots of up to the same height, filled from left to right.
+#[derive(Debug)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    next_x: u32,
+}
+
+#[derive(Debug, Default)]
+struct Page {
+    shelves: Vec<Shelf>,
+    num_slots: usize,
+}
+
+impl Page {
+    fn allocate(&mut self, width: u32, height: u32, page_size: u32) -> Option<(u32, u32)> {
+        // Use the lowest shelf that fits without wasting more than half of its height.
+        let shelf = self.shelves.iter_mut().find(|shelf| {
+            shelf.height >= height && shelf.height / 2 < height && shelf.next_x + width <= page_size
+        });
+        let shelf = match shelf {
+            Some(shelf) => shelf,
+            None => {
+                let y = self
+                    .shelves
+                    .last()
+                    .map(|shelf| shelf.y + shelf.height)
+                    .unwrap_or(0);
+                if y + height > page_size {
+                    return None;
+                }
+                self.shelves.push(Shelf {
+                    y,
+                    height,
+                    next_x: 0,
+                });
+                self.shelves.last_mut().unwrap()
+            }
+        };
+        let position = (shelf.next_x, shelf.y);
+        shelf.next_x += width;
+        self.num_slots += 1;
+        Some(position)
+    }
+}
+
+/// Hands out slots for bitmaps in atlas pages, adding pages as they fill up.
+///
+/// Slots are packed into shelves, which suits bitmaps of similar sizes, such as the frames of a
+/// sprite sheet. The space of freed slots is only reused once every slot of its page is free.
+#[derive(Debug)]
+pub struct AtlasAllocator {
+    page_size: u32,
+    pages: Vec<Page>,
+}
+
+impl AtlasAllocator {
+    /// Creates an allocator for pages of the given size, or smaller if the maximum texture
+    /// size is smaller.
+    pub fn new(max_texture_size: u32) -> Self {
+        Self {
+            page_size: ATLAS_PAGE_SIZE.min(max_texture_size),
+            pages: Vec::new(),
+        }
+    }
+
+    /// The width and height of every page.
+    pub fn page_size(&self) -> u32 {
+        self.page_size
+    }
+
+    /// The number of pages that slots have been allocated in.
+    pub fn num_pages(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Whether a bitmap of the given size is small enough to be stored in a page.
+    ///
+    /// Only bitmaps up to a quarter of the page size are, so that larger ones don't fill up
+    /// pages on their own.
+    pub fn fits(&self, width: u32, height: u32) -> bool {
+        let max_size = self.page_size / 4;
+        width > 0 && height > 0 && width <= max_size && height <= max_size
+    }
+
+    /// Allocates a slot for a bitmap of the given size, or returns `None` if it doesn't `fit`.
+    ///
+    /// A slot that is in a new page is the first slot of that page.
+    pub fn allocate(&mut self, width: u32, height: u32) -> Option<AtlasSlot> {
+        if !self.fits(width, height) {
+            return None;
+        }
+
+        let (padded_width, padded_height) = (padded(width), padded(height));
+        let page_size = self.page_size;
+        let found = self.pages.iter_mut().enumerate().find_map(|(index, page)| {
+            page.allocate(padded_width, padded_height, page_size)
+                .map(|position| (index, position))
+        });
+        let (page, (x, y)) = match found {
+            Some(found) => found,
+            None => {
+                let mut page = Page::default();
+                let position = page.allocate(padded_width, padded_height, page_size)?;
+                self.pages.push(page);
+                (self.pages.len() - 1, position)
+            }
+        };
+
+        Some(AtlasSlot {
+            page,
+            x: x + GUTTER,
+            y: y + GUTTER,
+            width,
+            height,
+            page_size,
+        })
+    }
+
+    /// Frees a slot. Once every slot of its page is free, the whole page can be used again.
+    pub fn free(&mut self, slot: &AtlasSlot) {
+        if let Some(page) = self.pages.get_mut(slot.page) {
+            page.num_slots -= 1;
+            if page.num_slots == 0 {
+                page.shelves.clear();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slots_are_aligned_and_padded() {
+        let mut atlas = AtlasAllocator::new(8192);
+        let first = atlas.allocate(10, 7).unwrap();
+        let second = atlas.allocate(3, 5).unwrap();
+        assert_eq!((first.page, first.x, first.y), (0, GUTTER, GUTTER));
+        assert_eq!(first.padded_size(), (20, 16));
+        assert_eq!((second.page, second.x, second.y), (0, 20 + GUTTER, GUTTER));
+        assert_eq!(second.padded_origin(), (20, 0));
+        assert_eq!(second.padded_size(), (12, 16));
+    }
+
+    #[test]
+    fn large_bitmaps_are_not_packed() {
+        let mut atlas = AtlasAllocator::new(8192);
+        assert!(atlas.allocate(512, 512).is_some());
+        assert!(atlas.allocate(513, 1).is_none());
+        assert!(atlas.allocate(0, 10).is_none());
+
+        // Small maximum texture sizes make small pages.
+        let mut atlas = AtlasAllocator::new(1024);
+        assert_eq!(atlas.page_size(), 1024);
+        assert!(atlas.allocate(257, 1).is_none());
+    }
+
+    #[test]
+    fn full_pages_are_followed_by_new_ones() {
+        let mut atlas = AtlasAllocator::new(8192);
+        // 512x512 bitmaps take up 520x520 slots, 3 to a row and 3 rows to a page.
+        let slots: Vec<_> = (0..10).map(|_| atlas.allocate(512, 512).unwrap()).collect();
+        assert!(slots[..9].iter().all(|slot| slot.page == 0));
+        assert_eq!(slots[9].page, 1);
+        assert_eq!((slots[9].x, slots[9].y), (GUTTER, GUTTER));
+        assert_eq!(atlas.num_pages(), 2);
+
+        // Smaller bitmaps still fit into the space left in the first page.
+        assert_eq!(atlas.allocate(100, 100).unwrap().page, 0);
+    }
+
+    #[test]
+    fn emptied_pages_are_reused() {
+        let mut atlas = AtlasAllocator::new(8192);
+        let first = atlas.allocate(100, 100).unwrap();
+        let second = atlas.allocate(100, 100).unwrap();
+        atlas.free(&first);
+        let third = atlas.allocate(100, 100).unwrap();
+        assert_eq!(third.x, 2 * 108 + GUTTER);
+        atlas.free(&second);
+        atlas.free(&third);
+        assert_eq!(atlas.allocate(100, 100).unwrap().x, GUTTER);
+    }
+
+    #[test]
+    fn page_matrices_map_into_the_slot() {
+        let mut atlas = AtlasAllocator::new(8192);
+        atlas.allocate(100, 100).unwrap();
+        let slot = atlas.allocate(256, 512).unwrap();
+        let identity = [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        let matrix = slot.to_page_matrix(identity);
+        let transform = |(u, v): (f32, f32)| {
+            (
+                matrix[0][0] * u + matrix[1][0] * v + matrix[2][0],
+                matrix[0][1] * u + matrix[1][1] * v + matrix[2][1],
+            )
+        };
+        let page_size = ATLAS_PAGE_SIZE as f32;
+        assert_eq!(
+            transform((0.0, 0.0)),
+            (slot.x as f32 / page_size, slot.y as f32 / page_size)
+        );
+        assert_eq!(
+            transform((1.0, 1.0)),
+            (
+                (slot.x + 256) as f32 / page_size,
+                (slot.y + 512) as f32 / page_size
+            )
+        );
+    }
+
+    #[test]
+    fn gutters_repeat_the_edges() {
+        let mut atlas = AtlasAllocator::new(8192);
+        let slot = atlas.allocate(2, 1).unwrap();
+        let padded = pad_bitmap(&slot, &[1, 1, 1, 1, 2, 2, 2, 2]);
+        let (width, height) = slot.padded_size();
+        assert_eq!(padded.len(), (width * height * 4) as usize);
+        let pixel = |x: u32, y: u32| padded[((y * width + x) * 4) as usize];
+        for y in 0..height {
+            assert_eq!(pixel(0, y), 1);
+            assert_eq!(pixel(GUTTER, y), 1);
+            assert_eq!(pixel(GUTTER + 1, y), 2);
+            assert_eq!(pixel(width - 1, y), 2);
+        }
+    }
+
+    #[test]
+    fn page_bounds_stay_within_the_slot() {
+        let mut atlas = AtlasAllocator::new(8192);
+        atlas.allocate(100, 100).unwrap();
+        let slot = atlas.allocate(2, 4).unwrap();
+        let page_size = ATLAS_PAGE_SIZE as f32;
+        assert_eq!(
+            slot.to_page_bounds([0.25, 0.125, 0.75, 0.875]),
+            [
+                (slot.x as f32 + 0.5) / page_size,
+                (slot.y as f32 + 0.5) / page_size,
+                (slot.x as f32 + 1.5) / page_size,
+                (slot.y as f32 + 3.5) / page_size,
+            ]
+        );
+    }
+}
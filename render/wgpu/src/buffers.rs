@@ -0,0 +1,257 @@
+use crate::utils::create_buffer_with_data;
+use std::ops::Range;
+
+/// The size of the buffers that meshes are packed into.
+/// Meshes that don't fit get a buffer of their own.
+const BLOCK_SIZE: u64 = 1 << 20;
+
+/// Buffer copies need offsets and sizes that are multiples of 4 bytes.
+const ALIGNMENT: u64 = 4;
+
+/// A part of one of the buffers of a `BufferPool`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BufferSlice {
+    block: usize,
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// The vertex and index data of shape meshes, packed into a few large buffers.
+///
+/// Movies that redraw shapes every frame, such as with the drawing API, register and
+/// unregister meshes over and over. Sharing buffers between them avoids creating and dropping
+/// buffers for every draw, which fragments GPU memory and stalls some drivers.
+///
+/// Freed slices are only reused once `recycle` is called, so that the data of a mesh that is
+/// unregistered in the middle of a frame stays intact until the frame has been submitted.
+#[derive(Debug, Default)]
+pub struct BufferPool {
+    blocks: Vec<Option<Block>>,
+    freed: Vec<BufferSlice>,
+}
+
+#[derive(Debug)]
+struct Block {
+    buffer: wgpu::Buffer,
+    ranges: RangeAllocator,
+}
+
+impl BufferPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copies data into a free slice of the pool, recording the copy into `encoder`.
+    pub fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        data: &[u8],
+    ) -> BufferSlice {
+        let size = align(data.len() as u64);
+        let slice = self.allocate(device, size);
+
+        let mut padded = data.to_vec();
+        padded.resize(size as usize, 0);
+        let transfer_buffer = create_buffer_with_data(
+            device,
+            &padded,
+            wgpu::BufferUsage::COPY_SRC,
+            create_debug_label!("Mesh buffer transfer buffer"),
+        );
+        encoder.copy_buffer_to_buffer(&transfer_buffer, 0, self.buffer(slice), slice.offset, size);
+        slice
+    }
+
+    /// The buffer that a slice is part of.
+    pub fn buffer(&self, slice: BufferSlice) -> &wgpu::Buffer {
+        &self.blocks[slice.block]
+            .as_ref()
+            .expect("Slices are only used while their buffer is alive")
+            .buffer
+    }
+
+    /// Returns a slice to the pool. Its part of the buffer is reused after the next `recycle`.
+    pub fn free(&mut self, slice: BufferSlice) {
+        self.freed.push(slice);
+    }
+
+    /// Makes the slices freed so far available again. This must not be called while commands
+    /// that use them are still being recorded.
+    ///
+    /// Buffers that are no longer used are dropped, except for one that is kept for new meshes.
+    pub fn recycle(&mut self) {
+        for slice in self.freed.drain(..) {
+            if let Some(Some(block)) = self.blocks.get_mut(slice.block) {
+                block.ranges.free(slice.offset..slice.offset + slice.size);
+            }
+        }
+
+        let mut has_spare_block = false;
+        for block in &mut self.blocks {
+            let is_unused = match block {
+                Some(block) => block.ranges.is_unused(),
+                None => false,
+            };
+            if is_unused {
+                let is_standard_size = block.as_ref().unwrap().ranges.size == BLOCK_SIZE;
+                if has_spare_block || !is_standard_size {
+                    *block = None;
+                } else {
+                    has_spare_block = true;
+                }
+            }
+        }
+    }
+
+    /// The total size of the buffers of the pool, in bytes.
+    pub fn allocated_bytes(&self) -> u64 {
+        self.blocks
+            .iter()
+            .flatten()
+            .map(|block| block.ranges.size)
+            .sum()
+    }
+
+    fn allocate(&mut self, device: &wgpu::Device, size: u64) -> BufferSlice {
+        for (index, block) in self.blocks.iter_mut().enumerate() {
+            if let Some(block) = block {
+                if let Some(offset) = block.ranges.allocate(size) {
+                    return BufferSlice {
+                        block: index,
+                        offset,
+                        size,
+                    };
+                }
+            }
+        }
+
+        let block_size = size.max(BLOCK_SIZE);
+        let label = create_debug_label!("Mesh buffer {}", self.blocks.len());
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: label.as_deref(),
+            size: block_size,
+            usage: wgpu::BufferUsage::VERTEX
+                | wgpu::BufferUsage::INDEX
+                | wgpu::BufferUsage::COPY_DST,
+        });
+        let mut ranges = RangeAllocator::new(block_size);
+        let offset = ranges
+            .allocate(size)
+            .expect("New blocks fit the slice they are made for");
+        let block = Some(Block { buffer, ranges });
+        let index = match self.blocks.iter().position(Option::is_none) {
+            Some(index) => {
+                self.blocks[index] = block;
+                index
+            }
+            None => {
+                self.blocks.push(block);
+                self.blocks.len() - 1
+            }
+        };
+        BufferSlice {
+            block: index,
+            offset,
+            size,
+        }
+    }
+}
+
+fn align(size: u64) -> u64 {
+    (size + ALIGNMENT - 1) & !(ALIGNMENT - 1)
+}
+
+/// Keeps track of the free ranges of a buffer.
+///
+/// The free ranges are sorted and merged with their neighbours, and new ranges are taken from
+/// the first free range that fits.
+#[derive(Debug)]
+struct RangeAllocator {
+    size: u64,
+    free: Vec<Range<u64>>,
+}
+
+impl RangeAllocator {
+    fn new(size: u64) -> Self {
+        Self {
+            size,
+            free: std::iter::once(0..size).collect(),
+        }
+    }
+
+    fn allocate(&mut self, size: u64) -> Option<u64> {
+        let index = self
+            .free
+            .iter()
+            .position(|range| range.end - range.start >= size)?;
+        let range = &mut self.free[index];
+        let offset = range.start;
+        range.start += size;
+        if range.start == range.end {
+            self.free.remove(index);
+        }
+        Some(offset)
+    }
+
+    fn free(&mut self, range: Range<u64>) {
+        let index = self
+            .free
+            .iter()
+            .position(|free| free.start > range.start)
+            .unwrap_or(self.free.len());
+        self.free.insert(index, range);
+        if index + 1 < self.free.len() && self.free[index].end == self.free[index + 1].start {
+            self.free[index].end = self.free.remove(index + 1).end;
+        }
+        if index > 0 && self.free[index - 1].end == self.free[index].start {
+            self.free[index - 1].end = self.free.remove(index).end;
+        }
+    }
+
+    fn is_unused(&self) -> bool {
+        self.free.len() == 1 && self.free[0] == (0..self.size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_are_taken_from_the_first_fit() {
+        let mut ranges = RangeAllocator::new(100);
+        assert_eq!(ranges.allocate(40), Some(0));
+        assert_eq!(ranges.allocate(40), Some(40));
+        assert_eq!(ranges.allocate(40), None);
+        assert_eq!(ranges.allocate(20), Some(80));
+        assert_eq!(ranges.allocate(1), None);
+
+        ranges.free(0..40);
+        assert_eq!(ranges.allocate(30), Some(0));
+        assert_eq!(ranges.allocate(10), Some(30));
+    }
+
+    #[test]
+    fn freed_ranges_are_merged() {
+        let mut ranges = RangeAllocator::new(90);
+        for _ in 0..3 {
+            ranges.allocate(30);
+        }
+        ranges.free(0..30);
+        ranges.free(60..90);
+        assert!(!ranges.is_unused());
+        assert_eq!(ranges.allocate(60), None);
+
+        ranges.free(30..60);
+        assert!(ranges.is_unused());
+        assert_eq!(ranges.allocate(90), Some(0));
+    }
+
+    #[test]
+    fn slices_are_aligned_for_copies() {
+        assert_eq!(align(0), 0);
+        assert_eq!(align(6), 8);
+        assert_eq!(align(24), 24);
+    }
+}
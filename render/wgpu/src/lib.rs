@@ -5,57 +5,629 @@ use lyon::tessellation::{
 };
 use ruffle_core::backend::render::swf::{self, FillStyle};
 use ruffle_core::backend::render::{
-    BitmapHandle, BitmapInfo, Color, Letterbox, RenderBackend, ShapeHandle, Transform,
+    normalize_fill_style, BitmapHandle, BitmapInfo, Color, Letterbox, PixelRegion, RenderBackend,
+    RenderCapabilities, ShapeHandle, Transform,
 };
 use ruffle_core::shape_utils::DrawPath;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::convert::TryInto;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
 use swf::{CharacterId, DefineBitsLossless, Glyph, Shape};
 
 use bytemuck::{Pod, Zeroable};
 use futures::executor::block_on;
 use raw_window_handle::HasRawWindowHandle;
 
-use crate::pipelines::Pipelines;
+use crate::atlas::{pad_bitmap, AtlasAllocator, AtlasSlot, ATLAS_MIP_LEVEL_COUNT};
+use crate::buffers::BufferPool;
+use crate::pipelines::{BlendMode, CompositeMode, MaskState, Pipelines, ShapeType};
+use crate::samplers::Samplers;
 use crate::shapes::{Draw, DrawType, GradientUniforms, IncompleteDrawType, Mesh};
+use crate::target::{RenderTarget, RenderTargetFrame};
+use crate::uniforms::UniformBuffer;
 use crate::utils::{
-    build_view_matrix, create_buffer_with_data, ruffle_path_to_lyon_path, swf_bitmap_to_gl_matrix,
-    swf_to_gl_matrix,
+    build_view_matrix, create_buffer_with_data, downscale_to_fit, gradient_spread_mode,
+    mip_level_count, next_mip_level, resample_gradient, ruffle_path_to_lyon_path,
+    swf_bitmap_to_gl_matrix, swf_to_gl_matrix, MAX_GRADIENT_COLORS,
 };
 use ruffle_core::color_transform::ColorTransform;
 
 type Error = Box<dyn std::error::Error>;
 
+/// The alignment of each row of a buffer copied into a texture.
+const COPY_BYTES_PER_ROW_ALIGNMENT: u32 = 256;
+
+/// The number of samples per pixel used for anti-aliasing by default.
+const DEFAULT_MSAA_SAMPLE_COUNT: u32 = 4;
+
+/// The texture transform of a bitmap that is drawn whole from a texture of its own.
+const IDENTITY_TEXTURE_TRANSFORM: [[f32; 4]; 4] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+/// The bounds of the texture coordinates of fills that are never clamped, such as repeating
+/// bitmap fills, which wrap around instead.
+const UNCLAMPED_UV_BOUNDS: [f32; 4] = [f32::MIN, f32::MIN, f32::MAX, f32::MAX];
+
+/// The most vertices that a draw can have and still be drawn with 16-bit indices.
+const MAX_U16_VERTICES: usize = u16::MAX as usize + 1;
+
+/// The adapters to request a device from, in order of preference: the default adapter of the
+/// modern graphics APIs, then a low-power one, then the older APIs, which are the only ones
+/// with software renderers on some systems.
+const ADAPTER_FALLBACKS: [(wgpu::PowerPreference, wgpu::BackendBit); 4] = [
+    (wgpu::PowerPreference::Default, wgpu::BackendBit::PRIMARY),
+    (wgpu::PowerPreference::LowPower, wgpu::BackendBit::PRIMARY),
+    (wgpu::PowerPreference::Default, wgpu::BackendBit::SECONDARY),
+    (wgpu::PowerPreference::LowPower, wgpu::BackendBit::SECONDARY),
+];
+
 #[macro_use]
 mod utils;
 
+mod atlas;
+mod buffers;
 mod pipelines;
+mod samplers;
 mod shapes;
-
-pub struct WgpuRenderBackend {
-    window_surface: wgpu::Surface,
+mod target;
+mod uniforms;
+
+/// The GPU device, and the resources built for it that don't depend on a render target.
+///
+/// These are shared by every `WgpuRenderBackend` created from them, so that a host running
+/// several players (such as one per window) only needs a single device.
+pub struct Descriptors {
     device: wgpu::Device,
-    queue: wgpu::Queue,
-    swap_chain_desc: wgpu::SwapChainDescriptor,
-    swap_chain: wgpu::SwapChain,
+    queue: RefCell<wgpu::Queue>,
     msaa_sample_count: u32,
+    max_texture_size: u32,
+    surface_format: wgpu::TextureFormat,
     pipelines: Pipelines,
+    samplers: Samplers,
+}
+
+impl Descriptors {
+    /// Requests a graphics adapter and device, and prepares the shaders for it.
+    pub fn new() -> Result<Self, Error> {
+        Self::with_msaa_sample_count(DEFAULT_MSAA_SAMPLE_COUNT)
+    }
+
+    /// Requests a graphics adapter and device, and prepares the shaders for it to draw with the
+    /// given number of samples per pixel for anti-aliasing. A count of 1 disables anti-aliasing.
+    ///
+    /// A count that the device doesn't support is replaced with the nearest one that it does;
+    /// see `supported_sample_counts`.
+    pub fn with_msaa_sample_count(msaa_sample_count: u32) -> Result<Self, Error> {
+        Self::with_backends(
+            wgpu::BackendBit::PRIMARY | wgpu::BackendBit::SECONDARY,
+            msaa_sample_count,
+        )
+    }
+
+    /// Requests a graphics adapter and device like `with_msaa_sample_count`, only considering
+    /// adapters of the given graphics APIs.
+    ///
+    /// If there is no such adapter, the error lists the adapters that were tried and the ones
+    /// that are available.
+    pub fn with_backends(
+        backends: wgpu::BackendBit,
+        msaa_sample_count: u32,
+    ) -> Result<Self, Error> {
+        let adapter = request_adapter(backends)?;
+
+        let max_texture_size = default_max_texture_size(adapter.get_info().backend);
+        let surface_format = default_surface_format(adapter.get_info().backend);
+
+        let (device, queue) = block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            extensions: wgpu::Extensions {
+                anisotropic_filtering: false,
+            },
+            limits: wgpu::Limits::default(),
+        }));
+
+        let pipelines = Pipelines::new(&device, msaa_sample_count, surface_format)?;
+        let samplers = Samplers::new(&device);
+
+        Ok(Self {
+            device,
+            queue: RefCell::new(queue),
+            msaa_sample_count: pipelines.msaa_sample_count(),
+            max_texture_size,
+            surface_format,
+            pipelines,
+            samplers,
+        })
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    /// The number of samples per pixel used for anti-aliasing, or 1 if it is disabled.
+    pub fn msaa_sample_count(&self) -> u32 {
+        self.msaa_sample_count
+    }
+
+    /// The largest width or height of the textures created for bitmaps.
+    pub fn max_texture_size(&self) -> u32 {
+        self.max_texture_size
+    }
+
+    /// The number of render pipelines built so far.
+    /// Pipelines are only built the first time a shape needs them, so this is usually small.
+    pub fn num_pipelines_created(&self) -> usize {
+        self.pipelines.num_created()
+    }
+
+    /// The number of samplers created for drawing bitmaps.
+    /// They are shared by every bitmap, so this doesn't grow as more bitmaps are drawn.
+    pub fn num_samplers_created(&self) -> usize {
+        self.samplers.num_created()
+    }
+
+    /// Overrides the largest width or height of the textures created for bitmaps, for adapters
+    /// whose limit differs from the default guessed for their graphics API.
+    /// Larger bitmaps are downscaled to fit.
+    pub fn set_max_texture_size(&mut self, max_texture_size: u32) {
+        self.max_texture_size = max_texture_size.max(1);
+    }
+
+    /// The texture format of the windows and offscreen textures that frames are drawn into.
+    pub fn surface_format(&self) -> wgpu::TextureFormat {
+        self.surface_format
+    }
+
+    /// Overrides the texture format of the windows and offscreen textures that frames are drawn
+    /// into, such as to match the format that a window surface prefers.
+    ///
+    /// Only the 8-bit RGBA and BGRA formats are supported, with or without sRGB encoding.
+    pub fn set_surface_format(&mut self, surface_format: wgpu::TextureFormat) -> Result<(), Error> {
+        match surface_format {
+            wgpu::TextureFormat::Bgra8Unorm
+            | wgpu::TextureFormat::Bgra8UnormSrgb
+            | wgpu::TextureFormat::Rgba8Unorm
+            | wgpu::TextureFormat::Rgba8UnormSrgb => (),
+            _ => return Err(format!("Unsupported surface format {:?}", surface_format).into()),
+        }
+        self.pipelines = Pipelines::new(&self.device, self.msaa_sample_count, surface_format)?;
+        self.surface_format = surface_format;
+        Ok(())
+    }
+
+    /// Whether the GPU encodes colors written to the surface as sRGB.
+    ///
+    /// SWF colors are already sRGB, so they are converted to linear colors before drawing to
+    /// such surfaces, to come out the same as on other surfaces.
+    fn is_srgb(&self) -> bool {
+        is_srgb_format(self.surface_format)
+    }
+
+    /// Converts a SWF color into the color space that shaders output, with channels from 0 to 1.
+    fn output_color(&self, color: &Color) -> [f32; 4] {
+        let channel = |value: u8| {
+            let value = f32::from(value) / 255.0;
+            if self.is_srgb() {
+                srgb_to_linear(value)
+            } else {
+                value
+            }
+        };
+        [
+            channel(color.r),
+            channel(color.g),
+            channel(color.b),
+            f32::from(color.a) / 255.0,
+        ]
+    }
+
+    /// Converts a SWF color into the color space that shaders output, for vertex colors.
+    ///
+    /// Vertex colors have 8 bits per channel, so dark linear colors lose some precision unless
+    /// the `f32_vertex_colors` feature is enabled.
+    fn output_vertex_color(&self, color: &Color) -> VertexColor {
+        if !self.is_srgb() {
+            return vertex_color(color);
+        }
+        let [r, g, b, _] = self.output_color(color);
+        let to_byte = |value: f32| (value * 255.0).round() as u8;
+        vertex_color(&Color {
+            r: to_byte(r),
+            g: to_byte(g),
+            b: to_byte(b),
+            a: color.a,
+        })
+    }
+
+    /// Converts a color transform into the uniforms of the shaders.
+    ///
+    /// For sRGB surfaces, the shaders transform linear colors, so the multipliers and offsets are
+    /// converted too. This is only exact for transforms that don't change the color channels.
+    fn color_adjustments(&self, transform: ColorTransform) -> ColorAdjustments {
+        let adjustments = ColorAdjustments::from(transform);
+        if !self.is_srgb() {
+            return adjustments;
+        }
+        let linear = |value: f32| srgb_to_linear(value.abs()).copysign(value);
+        let [r_mult, g_mult, b_mult, a_mult] = adjustments.mult_color;
+        let [r_add, g_add, b_add, a_add] = adjustments.add_color;
+        ColorAdjustments {
+            mult_color: [linear(r_mult), linear(g_mult), linear(b_mult), a_mult],
+            add_color: [linear(r_add), linear(g_add), linear(b_add), a_add],
+        }
+    }
+}
+
+/// Requests the first adapter of `ADAPTER_FALLBACKS` that is available for the given graphics
+/// APIs.
+fn request_adapter(backends: wgpu::BackendBit) -> Result<wgpu::Adapter, Error> {
+    let mut tried = vec![];
+    for &(power_preference, fallback_backends) in &ADAPTER_FALLBACKS {
+        let fallback_backends = fallback_backends & backends;
+        if fallback_backends.is_empty() {
+            continue;
+        }
+        let adapter = block_on(wgpu::Adapter::request(
+            &wgpu::RequestAdapterOptions {
+                power_preference,
+                compatible_surface: None,
+            },
+            fallback_backends,
+        ));
+        match adapter {
+            Some(adapter) => {
+                let info = adapter.get_info();
+                log::info!("Using graphics adapter {} ({:?})", info.name, info.backend);
+                return Ok(adapter);
+            }
+            None => tried.push(format!("{:?} on {:?}", power_preference, fallback_backends)),
+        }
+    }
+
+    let available: Vec<_> = wgpu::Adapter::enumerate(wgpu::BackendBit::all())
+        .iter()
+        .map(|adapter| {
+            let info = adapter.get_info();
+            format!("{} ({:?})", info.name, info.backend)
+        })
+        .collect();
+    Err(format!(
+        "No compatible graphics adapter found for {:?}. Tried: [{}]. Available adapters: [{}]",
+        backends,
+        tried.join(", "),
+        available.join(", ")
+    )
+    .into())
+}
+
+/// The surface format used when none is given.
+fn default_surface_format(backend: wgpu::Backend) -> wgpu::TextureFormat {
+    match backend {
+        // OpenGL ES devices, such as most Android devices, often can't present BGRA surfaces.
+        wgpu::Backend::Gl => wgpu::TextureFormat::Rgba8Unorm,
+        _ => wgpu::TextureFormat::Bgra8Unorm,
+    }
+}
+
+fn is_srgb_format(format: wgpu::TextureFormat) -> bool {
+    match format {
+        wgpu::TextureFormat::Bgra8UnormSrgb | wgpu::TextureFormat::Rgba8UnormSrgb => true,
+        _ => false,
+    }
+}
+
+/// Converts an sRGB-encoded channel from 0 to 1 into linear light.
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// The numbers of samples per pixel for anti-aliasing that render targets of the given format
+/// can be created with on the given device, in increasing order. 1 means no anti-aliasing.
+///
+/// wgpu doesn't report which sample counts a device supports yet, so these are the counts that
+/// every device is required to support for the format. Integer and 32-bit float formats can't
+/// be multisampled at all.
+pub fn supported_sample_counts(_device: &wgpu::Device, format: wgpu::TextureFormat) -> Vec<u32> {
+    use wgpu::TextureFormat::*;
+    match format {
+        R8Uint | R8Sint | R16Uint | R16Sint | Rg8Uint | Rg8Sint | R32Uint | R32Sint | R32Float
+        | Rg16Uint | Rg16Sint | Rgba8Uint | Rgba8Sint | Rg32Uint | Rg32Sint | Rg32Float
+        | Rgba16Uint | Rgba16Sint | Rgba32Uint | Rgba32Sint | Rgba32Float => vec![1],
+        _ => vec![1, 4],
+    }
+}
+
+/// Picks the sample count nearest to `requested` out of the ones supported for the format,
+/// warning if it isn't the one requested.
+fn negotiate_sample_count(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    requested: u32,
+) -> Result<u32, Error> {
+    if requested == 0 {
+        return Err("The MSAA sample count must be at least 1".into());
+    }
+    let msaa_sample_count = supported_sample_counts(device, format)
+        .into_iter()
+        .min_by_key(|&count| (i64::from(count) - i64::from(requested)).abs())
+        .unwrap_or(1);
+    if msaa_sample_count != requested {
+        log::warn!(
+            "MSAA sample count {} is not supported for {:?}, using {} instead",
+            requested,
+            format,
+            msaa_sample_count
+        );
+    }
+    Ok(msaa_sample_count)
+}
+
+/// Creates the multisampled color texture that frames are drawn into before being resolved
+/// into the render target, and the depth-stencil texture used alongside it.
+fn create_frame_buffers(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    msaa_sample_count: u32,
+) -> (wgpu::TextureView, wgpu::TextureView) {
+    let extent = wgpu::Extent3d {
+        width,
+        height,
+        depth: 1,
+    };
+
+    let frame_buffer_label = create_debug_label!("Framebuffer texture");
+    let frame_buffer = device.create_texture(&wgpu::TextureDescriptor {
+        label: frame_buffer_label.as_deref(),
+        size: extent,
+        array_layer_count: 1,
+        mip_level_count: 1,
+        sample_count: msaa_sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+    });
+
+    let depth_label = create_debug_label!("Depth texture");
+    let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: depth_label.as_deref(),
+        size: extent,
+        array_layer_count: 1,
+        mip_level_count: 1,
+        sample_count: msaa_sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Depth24PlusStencil8,
+        usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+    });
+
+    (
+        frame_buffer.create_default_view(),
+        depth_texture.create_default_view(),
+    )
+}
+
+/// The texture size that adapters using the given graphics API can be expected to support.
+///
+/// wgpu doesn't report the actual limits of an adapter yet, so these are sizes supported by
+/// nearly all adapters of each API. Older OpenGL hardware is often limited to 4096.
+fn default_max_texture_size(backend: wgpu::Backend) -> u32 {
+    match backend {
+        wgpu::Backend::Dx11 | wgpu::Backend::Dx12 => 16384,
+        wgpu::Backend::Gl => 4096,
+        _ => 8192,
+    }
+}
+
+pub struct WgpuRenderBackend {
+    descriptors: Rc<Descriptors>,
+    target: RenderTarget,
+    msaa_sample_count: u32,
     frame_buffer_view: wgpu::TextureView,
     depth_texture_view: wgpu::TextureView,
-    current_frame: Option<(wgpu::SwapChainOutput, wgpu::CommandEncoder)>,
-    meshes: Vec<Mesh>,
+    current_frame: Option<(RenderTargetFrame, wgpu::CommandEncoder)>,
+    meshes: Vec<Option<Mesh>>,
+    /// The shared buffers that the vertices and indices of every mesh are stored in.
+    mesh_buffers: BufferPool,
     viewport_width: f32,
     viewport_height: f32,
     view_matrix: [[f32; 4]; 4],
-    textures: Vec<(swf::CharacterId, Texture)>,
+    textures: Vec<Option<(swf::CharacterId, Texture)>>,
+    /// Hands out the slots of small bitmaps in `atlas_pages`.
+    atlas: AtlasAllocator,
+    /// The textures that small bitmaps are packed into, so that they can share bind groups.
+    atlas_pages: Vec<wgpu::Texture>,
+    /// The bind groups of bitmaps drawn by `render_bitmap` and of atlas pages, for each
+    /// smoothing, which every draw from the same texture shares.
+    bitmap_bind_groups: HashMap<(BitmapSource, bool), wgpu::BindGroup>,
+    /// The bind group of the last draw of the frame, if the next draw can share it.
+    last_bind_group: Option<BindGroupKey>,
+    frame_stats: FrameStats,
+    last_frame_stats: FrameStats,
     num_masks: u32,
     num_masks_active: u32,
-    write_stencil_mask: u32,
-    test_stencil_mask: u32,
-    next_stencil_mask: u32,
-    mask_stack: Vec<(u32, u32)>,
+    mask_state: MaskState,
+    /// The draws of each mask that has been pushed and not popped yet, innermost last.
+    /// These are drawn again to remove the mask from the stencil buffer when it is popped.
+    masker_draws: Vec<Vec<MaskerDraw>>,
+    /// The blend mode of the innermost object being drawn.
+    /// Blend modes of nested objects aren't combined.
+    blend_mode: BlendMode,
+    /// The blend modes to restore when blend modes are popped, along with whether the blend
+    /// mode being popped began a layer.
+    blend_mode_stack: Vec<(BlendMode, bool)>,
+    /// The layers of objects with a `CompositeMode` that are being drawn, innermost last.
+    /// Draws go into the innermost layer, or into the frame if there is none.
+    layers: Vec<BlendLayer>,
+    /// Whether bitmaps that ask to be smoothed are, or all use nearest-neighbor sampling.
+    bitmap_smoothing: bool,
     quad_vbo: wgpu::Buffer,
     quad_ibo: wgpu::Buffer,
     quad_tex_transforms: wgpu::Buffer,
+    /// The transforms and color adjustments of the draws of the current frame.
+    uniforms: UniformBuffer,
+    /// The bind group of `draw_rect`, which only needs the uniforms of each rectangle.
+    rect_bind_group: wgpu::BindGroup,
+    /// Whether the next frame is drawn into `capture_target` instead of the window.
+    capture_requested: bool,
+    /// The texture that the last frame captured from a window was drawn into.
+    capture_target: Option<RenderTarget>,
+    /// Whether the graphics device was lost, after which nothing is drawn.
+    device_lost: bool,
+}
+
+/// The texture that a bitmap is sampled from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum BitmapSource {
+    /// An atlas page, shared by every bitmap in it.
+    Page(usize),
+    /// The texture of a single bitmap.
+    Bitmap(usize),
+}
+
+/// A bind group that consecutive draws can share.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum BindGroupKey {
+    Rect,
+    Bitmap(BitmapSource, bool),
+}
+
+/// Counts of the work done to draw a frame.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct FrameStats {
+    /// The number of draw calls.
+    pub draws: usize,
+
+    /// The number of draws that used a different bind group than the draw before them.
+    ///
+    /// Draws of bitmaps from the same atlas page with the same smoothing share a bind group,
+    /// while every draw of a shape, or of a bitmap with a texture of its own, counts as a
+    /// switch.
+    pub bind_group_switches: usize,
+}
+
+/// A draw made while writing a mask to the stencil buffer.
+enum MaskerDraw {
+    Shape(ShapeHandle, Transform),
+    Bitmap(BitmapHandle, Transform, bool),
+}
+
+/// The content of an object with a `CompositeMode`, which is drawn separately and then
+/// composited onto the content below it.
+///
+/// Layers cover the whole viewport and share the stencil buffer of the frame, so masks apply
+/// to their content as usual.
+struct BlendLayer {
+    composite_mode: CompositeMode,
+    /// The content of the layer, resolved if it is multisampled.
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    /// The texture that content is drawn into with MSAA, before being resolved into `texture`.
+    frame_buffer_view: Option<wgpu::TextureView>,
+}
+
+impl BlendLayer {
+    fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        msaa_sample_count: u32,
+        composite_mode: CompositeMode,
+    ) -> Self {
+        let texture_label = create_debug_label!("Layer texture");
+        let texture = create_layer_texture(
+            device,
+            texture_label,
+            format,
+            width,
+            height,
+            1,
+            wgpu::TextureUsage::OUTPUT_ATTACHMENT
+                | wgpu::TextureUsage::SAMPLED
+                | wgpu::TextureUsage::COPY_SRC,
+        );
+        let frame_buffer_view = if msaa_sample_count >= 2 {
+            let frame_buffer_label = create_debug_label!("Layer frame buffer");
+            let frame_buffer = create_layer_texture(
+                device,
+                frame_buffer_label,
+                format,
+                width,
+                height,
+                msaa_sample_count,
+                wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+            );
+            Some(frame_buffer.create_default_view())
+        } else {
+            None
+        };
+
+        Self {
+            composite_mode,
+            view: texture.create_default_view(),
+            texture,
+            frame_buffer_view,
+        }
+    }
+
+    /// Returns the color attachment to draw into the layer with, and the texture it is
+    /// resolved into with MSAA.
+    fn attachments(&self) -> (&wgpu::TextureView, Option<&wgpu::TextureView>) {
+        match &self.frame_buffer_view {
+            Some(frame_buffer_view) => (frame_buffer_view, Some(&self.view)),
+            None => (&self.view, None),
+        }
+    }
+}
+
+fn create_layer_texture(
+    device: &wgpu::Device,
+    label: Option<String>,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    sample_count: u32,
+    usage: wgpu::TextureUsage,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: label.as_deref(),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        },
+        array_layer_count: 1,
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage,
+    })
+}
+
+/// Returns the color attachment that draws go to, and the texture it is resolved into with
+/// MSAA: the innermost layer if there is one, or else the frame.
+fn draw_target<'a>(
+    frame_output: &'a RenderTargetFrame,
+    frame_buffer_view: &'a wgpu::TextureView,
+    layers: &'a [BlendLayer],
+    msaa_sample_count: u32,
+) -> (&'a wgpu::TextureView, Option<&'a wgpu::TextureView>) {
+    match layers.last() {
+        Some(layer) => layer.attachments(),
+        None if msaa_sample_count >= 2 => (frame_buffer_view, Some(frame_output.view())),
+        None => (frame_output.view(), None),
+    }
 }
 
 #[repr(C)]
@@ -72,6 +644,11 @@ unsafe impl Zeroable for Transforms {}
 #[derive(Copy, Clone, Debug)]
 struct TextureTransforms {
     u_matrix: [[f32; 4]; 4],
+    /// The texture coordinates that bitmaps are sampled within, as the minimum and maximum
+    /// coordinates. Clipped fills stop at the centers of the edge pixels of their bitmap, so
+    /// that the edges never blend with the outside of the bitmap, or with its neighbors in an
+    /// atlas page.
+    uv_bounds: [f32; 4],
 }
 
 unsafe impl Pod for TextureTransforms {}
@@ -106,113 +683,712 @@ impl From<ColorTransform> for ColorAdjustments {
 unsafe impl Pod for ColorAdjustments {}
 unsafe impl Zeroable for ColorAdjustments {}
 
+/// The color of a vertex, as stored in vertex buffers.
+///
+/// SWF colors only have 8 bits per channel, so they are packed as normalized bytes, which halves
+/// the size of each vertex. The `f32_vertex_colors` feature stores them as floats instead, for
+/// comparing the two while bisecting rendering regressions.
+#[cfg(not(feature = "f32_vertex_colors"))]
+type VertexColor = [u8; 4];
+
+#[cfg(feature = "f32_vertex_colors")]
+type VertexColor = [f32; 4];
+
+/// The vertex attribute format of `VertexColor`. Either way, shaders see a normalized `vec4`.
+#[cfg(not(feature = "f32_vertex_colors"))]
+const VERTEX_COLOR_FORMAT: wgpu::VertexFormat = wgpu::VertexFormat::Uchar4Norm;
+
+#[cfg(feature = "f32_vertex_colors")]
+const VERTEX_COLOR_FORMAT: wgpu::VertexFormat = wgpu::VertexFormat::Float4;
+
+#[cfg(not(feature = "f32_vertex_colors"))]
+fn vertex_color(color: &Color) -> VertexColor {
+    [color.r, color.g, color.b, color.a]
+}
+
+#[cfg(feature = "f32_vertex_colors")]
+fn vertex_color(color: &Color) -> VertexColor {
+    [
+        f32::from(color.r) / 255.0,
+        f32::from(color.g) / 255.0,
+        f32::from(color.b) / 255.0,
+        f32::from(color.a) / 255.0,
+    ]
+}
+
+/// The color of vertices in shapes that are colored by a gradient or bitmap instead.
+const WHITE_VERTEX_COLOR: Color = Color {
+    r: 255,
+    g: 255,
+    b: 255,
+    a: 255,
+};
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 struct GPUVertex {
     position: [f32; 2],
-    color: [f32; 4],
+    color: VertexColor,
 }
 
 unsafe impl Pod for GPUVertex {}
 unsafe impl Zeroable for GPUVertex {}
 
 impl WgpuRenderBackend {
+    /// Creates a renderer for the given window, with its own graphics device.
     pub fn new<W: HasRawWindowHandle>(window: &W, size: (u32, u32)) -> Result<Self, Error> {
-        let window_surface = wgpu::Surface::create(window);
+        Self::for_window(Rc::new(Descriptors::new()?), window, size)
+    }
 
-        let adapter = block_on(wgpu::Adapter::request(
-            &wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::Default,
-                compatible_surface: None,
-            },
-            wgpu::BackendBit::PRIMARY,
-        ))
-        .unwrap();
+    /// Creates a renderer for the given window, using a device that may be shared with other
+    /// renderers.
+    pub fn for_window<W: HasRawWindowHandle>(
+        descriptors: Rc<Descriptors>,
+        window: &W,
+        size: (u32, u32),
+    ) -> Result<Self, Error> {
+        let surface = wgpu::Surface::create(window);
+        let target = RenderTarget::swap_chain(
+            &descriptors.device,
+            surface,
+            descriptors.surface_format,
+            size.0,
+            size.1,
+        );
+        Self::new_with_target(descriptors, target, size)
+    }
 
-        let (device, queue) = block_on(adapter.request_device(&wgpu::DeviceDescriptor {
-            extensions: wgpu::Extensions {
-                anisotropic_filtering: false,
-            },
-            limits: wgpu::Limits::default(),
-        }));
+    /// Creates a renderer that draws into an offscreen texture, using a device that may be
+    /// shared with other renderers.
+    pub fn for_offscreen(descriptors: Rc<Descriptors>, size: (u32, u32)) -> Result<Self, Error> {
+        let target = RenderTarget::texture(
+            &descriptors.device,
+            descriptors.surface_format,
+            size.0,
+            size.1,
+        );
+        Self::new_with_target(descriptors, target, size)
+    }
 
-        let swap_chain_desc = wgpu::SwapChainDescriptor {
-            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
-            format: wgpu::TextureFormat::Bgra8Unorm,
-            width: size.0,
-            height: size.1,
-            present_mode: wgpu::PresentMode::Mailbox,
-        };
-        let swap_chain = device.create_swap_chain(&window_surface, &swap_chain_desc);
+    /// The number of times the uniforms of draws were copied to the GPU in the current or
+    /// last frame.
+    ///
+    /// This is one per frame, unless a frame has more draws than the uniform buffer holds.
+    pub fn num_uniform_uploads(&self) -> usize {
+        self.uniforms.num_uploads()
+    }
 
-        // TODO: Allow this to be set from command line/settings file.
-        let msaa_sample_count = 4;
+    /// The number of draws and bind group switches of the last frame.
+    pub fn last_frame_stats(&self) -> FrameStats {
+        self.last_frame_stats
+    }
 
-        let pipelines = Pipelines::new(&device, msaa_sample_count)?;
+    /// The number of atlas pages that small bitmaps have been packed into.
+    pub fn num_atlas_pages(&self) -> usize {
+        self.atlas.num_pages()
+    }
 
-        let extent = wgpu::Extent3d {
-            width: swap_chain_desc.width,
-            height: swap_chain_desc.height,
-            depth: 1,
-        };
+    /// The total size of the buffers that mesh vertices and indices are stored in, in bytes.
+    pub fn allocated_buffer_bytes(&self) -> u64 {
+        self.mesh_buffers.allocated_bytes()
+    }
+
+    /// The number of samples per pixel that frames are anti-aliased with, or 1 if they aren't.
+    pub fn msaa_sample_count(&self) -> u32 {
+        self.msaa_sample_count
+    }
 
-        let frame_buffer_label = create_debug_label!("Framebuffer texture");
-        let frame_buffer = device.create_texture(&wgpu::TextureDescriptor {
-            label: frame_buffer_label.as_deref(),
-            size: extent,
-            array_layer_count: 1,
-            mip_level_count: 1,
-            sample_count: msaa_sample_count,
-            dimension: wgpu::TextureDimension::D2,
-            format: swap_chain_desc.format,
-            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+    /// Changes the number of samples per pixel that frames are anti-aliased with, without
+    /// having to recreate the renderer or reload its shapes and bitmaps. A count of 1 disables
+    /// anti-aliasing.
+    ///
+    /// A count that isn't supported for the format of the render target is replaced with the
+    /// nearest one that is. The multisampled targets are rebuilt right away, and pipelines for
+    /// the new count are built as draws need them. This can't be called while a frame is being
+    /// drawn.
+    pub fn set_msaa(&mut self, msaa_sample_count: u32) -> Result<(), Error> {
+        if self.current_frame.is_some() {
+            return Err("The MSAA sample count can't change while a frame is being drawn".into());
+        }
+
+        let msaa_sample_count = negotiate_sample_count(
+            &self.descriptors.device,
+            self.target.format(),
+            msaa_sample_count,
+        )?;
+        if msaa_sample_count == self.msaa_sample_count {
+            return Ok(());
+        }
+
+        let (frame_buffer_view, depth_texture_view) = create_frame_buffers(
+            &self.descriptors.device,
+            self.target.format(),
+            self.viewport_width as u32,
+            self.viewport_height as u32,
+            msaa_sample_count,
+        );
+        self.frame_buffer_view = frame_buffer_view;
+        self.depth_texture_view = depth_texture_view;
+        self.msaa_sample_count = msaa_sample_count;
+        Ok(())
+    }
+
+    /// Draws the next frame into a texture that `capture_frame` can read back, for renderers
+    /// that draw into a window.
+    ///
+    /// That frame isn't presented, so the window keeps showing the frame before it. Renderers
+    /// that draw into an offscreen texture can always be read back and ignore this.
+    pub fn capture_next_frame(&mut self) {
+        self.capture_requested = true;
+    }
+
+    /// Reads back the last frame drawn into an offscreen texture, or captured from a window
+    /// with `capture_next_frame`.
+    ///
+    /// Returns `None` if no frame was captured, or if the texture couldn't be read.
+    pub fn capture_frame(&self) -> Option<image::RgbaImage> {
+        let pixels = self.capture_frame_pixels()?;
+        image::RgbaImage::from_raw(
+            self.viewport_width as u32,
+            self.viewport_height as u32,
+            pixels,
+        )
+    }
+
+    /// Reads back the last frame drawn into an offscreen texture, or captured from a window
+    /// with `capture_next_frame`, as rows of RGBA pixels without any padding between them.
+    ///
+    /// Returns `None` if no frame was captured, or if the texture couldn't be read.
+    pub fn capture_frame_pixels(&self) -> Option<Vec<u8>> {
+        let texture = match (&self.target, &self.capture_target) {
+            (RenderTarget::Texture { texture, .. }, _)
+            | (_, Some(RenderTarget::Texture { texture, .. })) => texture,
+            _ => return None,
+        };
+        let width = self.viewport_width as u32;
+        let height = self.viewport_height as u32;
+
+        // Texture-to-buffer copies require rows to start on a 256-byte boundary,
+        // so the rows are read back with padding, which is dropped afterwards.
+        let row_size = width * 4;
+        let padded_row_size = (row_size + COPY_BYTES_PER_ROW_ALIGNMENT - 1)
+            / COPY_BYTES_PER_ROW_ALIGNMENT
+            * COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer_size = wgpu::BufferAddress::from(padded_row_size * height);
+
+        let device = &self.descriptors.device;
+        let buffer_label = create_debug_label!("Frame capture buffer");
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: buffer_label.as_deref(),
+            size: buffer_size,
+            usage: wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::MAP_READ,
         });
-        let frame_buffer_view = frame_buffer.create_default_view();
-
-        let depth_label = create_debug_label!("Depth texture");
-        let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: depth_label.as_deref(),
-            size: extent,
-            array_layer_count: 1,
-            mip_level_count: 1,
-            sample_count: msaa_sample_count,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth24PlusStencil8,
-            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+        let encoder_label = create_debug_label!("Frame capture encoder");
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: encoder_label.as_deref(),
         });
+        encoder.copy_texture_to_buffer(
+            wgpu::TextureCopyView {
+                texture,
+                mip_level: 0,
+                array_layer: 0,
+                origin: wgpu::Origin3d { x: 0, y: 0, z: 0 },
+            },
+            wgpu::BufferCopyView {
+                buffer: &buffer,
+                offset: 0,
+                bytes_per_row: padded_row_size,
+                rows_per_image: 0,
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+        );
+        self.descriptors
+            .queue
+            .borrow_mut()
+            .submit(&[encoder.finish()]);
+
+        let mapping = buffer.map_read(0, buffer_size);
+        device.poll(wgpu::Maintain::Wait);
+        let mapping = match block_on(mapping) {
+            Ok(mapping) => mapping,
+            Err(_) => {
+                log::error!("Couldn't read back the frame texture");
+                return None;
+            }
+        };
+
+        let is_bgra = match self.target.format() {
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            _ => false,
+        };
+        let mut rgba = Vec::with_capacity((row_size * height) as usize);
+        for row in mapping.as_slice().chunks_exact(padded_row_size as usize) {
+            let row = &row[..row_size as usize];
+            if is_bgra {
+                for bgra in row.chunks_exact(4) {
+                    rgba.extend_from_slice(&[bgra[2], bgra[1], bgra[0], bgra[3]]);
+                }
+            } else {
+                rgba.extend_from_slice(row);
+            }
+        }
+        Some(rgba)
+    }
 
-        let depth_texture_view = depth_texture.create_default_view();
+    fn new_with_target(
+        descriptors: Rc<Descriptors>,
+        target: RenderTarget,
+        size: (u32, u32),
+    ) -> Result<Self, Error> {
+        let device = &descriptors.device;
+        let msaa_sample_count = descriptors.msaa_sample_count;
+        let (frame_buffer_view, depth_texture_view) =
+            create_frame_buffers(device, target.format(), size.0, size.1, msaa_sample_count);
+
+        let (quad_vbo, quad_ibo, quad_tex_transforms) = create_quad_buffers(device);
+
+        let uniforms = UniformBuffer::new(device);
+        let rect_bind_group_label = create_debug_label!("Rectangle bind group");
+        let rect_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &descriptors.pipelines.color.bind_layout,
+            bindings: &[
+                wgpu::Binding {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer {
+                        buffer: uniforms.buffer(),
+                        range: 0..std::mem::size_of::<Transforms>() as u64,
+                    },
+                },
+                wgpu::Binding {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Buffer {
+                        buffer: uniforms.buffer(),
+                        range: 0..std::mem::size_of::<ColorAdjustments>() as u64,
+                    },
+                },
+            ],
+            label: rect_bind_group_label.as_deref(),
+        });
 
-        let (quad_vbo, quad_ibo, quad_tex_transforms) = create_quad_buffers(&device);
+        let atlas = AtlasAllocator::new(descriptors.max_texture_size);
 
         Ok(Self {
-            window_surface,
-            device,
-            queue,
-            swap_chain_desc,
-            swap_chain,
+            descriptors,
+            target,
             msaa_sample_count,
-            pipelines,
             frame_buffer_view,
             depth_texture_view,
             current_frame: None,
             meshes: Vec::new(),
+            mesh_buffers: BufferPool::new(),
             viewport_width: size.0 as f32,
             viewport_height: size.1 as f32,
             view_matrix: build_view_matrix(size.0, size.1),
             textures: Vec::new(),
+            atlas,
+            atlas_pages: Vec::new(),
+            bitmap_bind_groups: HashMap::new(),
+            last_bind_group: None,
+            frame_stats: FrameStats::default(),
+            last_frame_stats: FrameStats::default(),
             num_masks: 0,
             num_masks_active: 0,
-            write_stencil_mask: 0,
-            test_stencil_mask: 0,
-            next_stencil_mask: 1,
-            mask_stack: Vec::new(),
+            mask_state: MaskState::DrawContent,
+            masker_draws: Vec::new(),
+            blend_mode: BlendMode::Normal,
+            blend_mode_stack: Vec::new(),
+            layers: Vec::new(),
+            bitmap_smoothing: true,
             quad_vbo,
             quad_ibo,
             quad_tex_transforms,
+            uniforms,
+            rect_bind_group,
+            capture_requested: false,
+            capture_target: None,
+            device_lost: false,
         })
     }
 
+    /// Builds the shader uniforms for a gradient fill.
+    ///
+    /// Gradients with more stops than the shader supports are resampled to fit.
+    fn gradient_uniforms(
+        &self,
+        gradient: &swf::Gradient,
+        gradient_type: i32,
+        focal_point: f32,
+    ) -> GradientUniforms {
+        let stops: Vec<_> = gradient
+            .records
+            .iter()
+            .map(|record| {
+                (
+                    f32::from(record.ratio) / 255.0,
+                    self.descriptors.output_color(&record.color),
+                )
+            })
+            .collect();
+        GradientUniforms::new(
+            gradient_type,
+            gradient_spread_mode(gradient.spread),
+            focal_point,
+            &resample_gradient(&stops, MAX_GRADIENT_COLORS),
+        )
+    }
+
+    /// Adds the transforms and color adjustments of a draw to the frame's uniforms, returning
+    /// the dynamic offsets to bind them with.
+    fn push_uniforms(
+        &mut self,
+        world_matrix: [[f32; 4]; 4],
+        colors: ColorAdjustments,
+    ) -> [wgpu::DynamicOffset; 2] {
+        if !self.uniforms.has_room_for(2) {
+            self.submit_partial_frame();
+        }
+        [
+            self.uniforms.push(&Transforms {
+                view_matrix: self.view_matrix,
+                world_matrix,
+            }),
+            self.uniforms.push(&colors),
+        ]
+    }
+
+    /// Adds the transforms, texture transforms and color adjustments of a bitmap draw to the
+    /// frame's uniforms, returning the dynamic offsets to bind them with.
+    fn push_bitmap_uniforms(
+        &mut self,
+        world_matrix: [[f32; 4]; 4],
+        texture_transforms: TextureTransforms,
+        colors: ColorAdjustments,
+    ) -> [wgpu::DynamicOffset; 3] {
+        if !self.uniforms.has_room_for(3) {
+            self.submit_partial_frame();
+        }
+        let [transforms, colors] = self.push_uniforms(world_matrix, colors);
+        let texture_transforms = self.uniforms.push(&texture_transforms);
+        [transforms, texture_transforms, colors]
+    }
+
+    /// Counts a draw in the stats of the frame, along with its bind group, which is `None` if
+    /// it can't be shared.
+    fn record_draw(&mut self, bind_group: Option<BindGroupKey>) {
+        self.frame_stats.draws += 1;
+        if bind_group.is_none() || bind_group != self.last_bind_group {
+            self.frame_stats.bind_group_switches += 1;
+        }
+        self.last_bind_group = bind_group;
+    }
+
+    /// Creates the shared bind group of a bitmap texture, unless it already exists.
+    fn prepare_bitmap_bind_group(&mut self, source: BitmapSource, is_smoothed: bool) {
+        if self.bitmap_bind_groups.contains_key(&(source, is_smoothed)) {
+            return;
+        }
+        let texture_view = match source {
+            BitmapSource::Page(page) => self.atlas_pages[page].create_default_view(),
+            BitmapSource::Bitmap(handle) => match self.textures.get(handle) {
+                Some(Some((
+                    _id,
+                    Texture {
+                        storage: TextureStorage::Own { texture, .. },
+                        ..
+                    },
+                ))) => texture.create_default_view(),
+                _ => return,
+            },
+        };
+
+        let bind_group_label = create_debug_label!("Bitmap {:?} bind group", source);
+        let bind_group = self
+            .descriptors
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.descriptors.pipelines.bitmap.bind_layout,
+                bindings: &[
+                    wgpu::Binding {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Buffer {
+                            buffer: self.uniforms.buffer(),
+                            range: 0..std::mem::size_of::<Transforms>() as u64,
+                        },
+                    },
+                    wgpu::Binding {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Buffer {
+                            buffer: self.uniforms.buffer(),
+                            range: 0..std::mem::size_of::<TextureTransforms>() as u64,
+                        },
+                    },
+                    wgpu::Binding {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Buffer {
+                            buffer: self.uniforms.buffer(),
+                            range: 0..std::mem::size_of::<ColorAdjustments>() as u64,
+                        },
+                    },
+                    wgpu::Binding {
+                        binding: 3,
+                        resource: wgpu::BindingResource::TextureView(&texture_view),
+                    },
+                    wgpu::Binding {
+                        binding: 4,
+                        resource: wgpu::BindingResource::Sampler(
+                            self.descriptors.samplers.get(is_smoothed, false),
+                        ),
+                    },
+                ],
+                label: bind_group_label.as_deref(),
+            });
+        self.bitmap_bind_groups
+            .insert((source, is_smoothed), bind_group);
+    }
+
+    /// Submits the commands recorded so far this frame, along with the uniforms they use, so
+    /// that the uniform buffer can be filled again for the rest of the frame.
+    fn submit_partial_frame(&mut self) {
+        let device = &self.descriptors.device;
+        let encoder = match &mut self.current_frame {
+            Some((_frame, encoder)) => {
+                let label = create_debug_label!("Frame encoder");
+                std::mem::replace(
+                    encoder,
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: label.as_deref(),
+                    }),
+                )
+            }
+            None => return,
+        };
+        let mut command_buffers: Vec<_> = self.uniforms.upload(device).into_iter().collect();
+        command_buffers.push(encoder.finish());
+        self.submit_frame_commands(&command_buffers);
+    }
+
+    /// Submits commands that draw the current frame.
+    ///
+    /// wgpu panics if the device was lost, such as when the graphics driver was restarted.
+    /// The panic is caught here, and the renderer stops drawing rather than taking the host
+    /// down with it.
+    fn submit_frame_commands(&mut self, command_buffers: &[wgpu::CommandBuffer]) {
+        let queue = &self.descriptors.queue;
+        let submitted = panic::catch_unwind(AssertUnwindSafe(|| {
+            queue.borrow_mut().submit(command_buffers)
+        }));
+        if submitted.is_err() {
+            log::error!("Lost the graphics device while submitting a frame");
+            self.device_lost = true;
+            self.current_frame = None;
+        }
+    }
+
+    /// Starts drawing into a new, transparent layer, which `composite_layer` blends onto the
+    /// content below it.
+    ///
+    /// Returns `false` if there is no frame being drawn, or if the content below can't be read
+    /// back to blend with. That is only the case for frames drawn straight into a window,
+    /// without MSAA.
+    fn begin_layer(&mut self, composite_mode: CompositeMode) -> bool {
+        let can_read_backdrop = self.msaa_sample_count >= 2
+            || !self.layers.is_empty()
+            || matches!(self.target, RenderTarget::Texture { .. });
+        let encoder = match &mut self.current_frame {
+            Some((_frame, encoder)) if can_read_backdrop => encoder,
+            _ => return false,
+        };
+
+        let layer = BlendLayer::new(
+            &self.descriptors.device,
+            self.target.format(),
+            self.viewport_width as u32,
+            self.viewport_height as u32,
+            self.msaa_sample_count,
+            composite_mode,
+        );
+        let (attachment, resolve_target) = layer.attachments();
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment,
+                load_op: wgpu::LoadOp::Clear,
+                store_op: wgpu::StoreOp::Store,
+                clear_color: wgpu::Color::TRANSPARENT,
+                resolve_target,
+            }],
+            depth_stencil_attachment: None,
+        });
+
+        self.layers.push(layer);
+        true
+    }
+
+    /// Blends the innermost layer onto the content below it, which draws go to afterwards.
+    fn composite_layer(&mut self) {
+        let layer = match self.layers.pop() {
+            Some(layer) => layer,
+            None => return,
+        };
+        if self.current_frame.is_none() {
+            return;
+        }
+
+        let width = self.viewport_width as u32;
+        let height = self.viewport_height as u32;
+        if !self.uniforms.has_room_for(2) {
+            self.submit_partial_frame();
+        }
+        let offsets = [
+            self.uniforms.push(&Transforms {
+                view_matrix: self.view_matrix,
+                world_matrix: [
+                    [self.viewport_width, 0.0, 0.0, 0.0],
+                    [0.0, self.viewport_height, 0.0, 0.0],
+                    [0.0, 0.0, 1.0, 0.0],
+                    [0.0, 0.0, 0.0, 1.0],
+                ],
+            }),
+            self.uniforms.push(&(layer.composite_mode as i32)),
+        ];
+        self.record_draw(None);
+
+        let (frame_output, encoder) = match &mut self.current_frame {
+            Some((frame_output, encoder)) => (frame_output, encoder),
+            None => return,
+        };
+        let device = &self.descriptors.device;
+
+        // The layer is drawn over a copy of the content below it, as a texture can't be
+        // sampled while it is being drawn into.
+        let backdrop_label = create_debug_label!("Layer backdrop texture");
+        let backdrop = create_layer_texture(
+            device,
+            backdrop_label,
+            self.target.format(),
+            width,
+            height,
+            1,
+            wgpu::TextureUsage::OUTPUT_ATTACHMENT
+                | wgpu::TextureUsage::SAMPLED
+                | wgpu::TextureUsage::COPY_DST,
+        );
+        let backdrop_view = backdrop.create_default_view();
+        let source = match (self.layers.last(), &self.target) {
+            (Some(parent), _) => Some(&parent.texture),
+            (None, _) if self.msaa_sample_count >= 2 => None,
+            (None, RenderTarget::Texture { texture, .. }) => Some(texture),
+            (None, RenderTarget::SwapChain { .. }) => return,
+        };
+        match source {
+            Some(texture) => encoder.copy_texture_to_texture(
+                wgpu::TextureCopyView {
+                    texture,
+                    mip_level: 0,
+                    array_layer: 0,
+                    origin: wgpu::Origin3d { x: 0, y: 0, z: 0 },
+                },
+                wgpu::TextureCopyView {
+                    texture: &backdrop,
+                    mip_level: 0,
+                    array_layer: 0,
+                    origin: wgpu::Origin3d { x: 0, y: 0, z: 0 },
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth: 1,
+                },
+            ),
+            None => {
+                // Window surfaces can't be copied from, but with MSAA the frame is drawn into
+                // a texture of its own, which can be resolved into the copy instead.
+                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                        attachment: &self.frame_buffer_view,
+                        load_op: wgpu::LoadOp::Load,
+                        store_op: wgpu::StoreOp::Store,
+                        clear_color: wgpu::Color::TRANSPARENT,
+                        resolve_target: Some(&backdrop_view),
+                    }],
+                    depth_stencil_attachment: None,
+                });
+            }
+        }
+
+        let pipelines = &self.descriptors.pipelines;
+        let bind_group_label = create_debug_label!("Layer composite bind group");
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &pipelines.composite.bind_layout,
+            bindings: &[
+                wgpu::Binding {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer {
+                        buffer: self.uniforms.buffer(),
+                        range: 0..std::mem::size_of::<Transforms>() as u64,
+                    },
+                },
+                wgpu::Binding {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Buffer {
+                        buffer: &self.quad_tex_transforms,
+                        range: 0..std::mem::size_of::<TextureTransforms>() as u64,
+                    },
+                },
+                wgpu::Binding {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Buffer {
+                        buffer: self.uniforms.buffer(),
+                        range: 0..std::mem::size_of::<i32>() as u64,
+                    },
+                },
+                wgpu::Binding {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&layer.view),
+                },
+                wgpu::Binding {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&backdrop_view),
+                },
+                wgpu::Binding {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(
+                        self.descriptors.samplers.get(false, false),
+                    ),
+                },
+            ],
+            label: bind_group_label.as_deref(),
+        });
+        let pipeline = pipelines.composite_pipeline_for(device, self.msaa_sample_count);
+
+        let (color_attachment, resolve_target) = draw_target(
+            frame_output,
+            &self.frame_buffer_view,
+            &self.layers,
+            self.msaa_sample_count,
+        );
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: color_attachment,
+                load_op: wgpu::LoadOp::Load,
+                store_op: wgpu::StoreOp::Store,
+                clear_color: wgpu::Color::WHITE,
+                resolve_target,
+            }],
+            depth_stencil_attachment: None,
+        });
+
+        render_pass.set_pipeline(&pipeline);
+        render_pass.set_bind_group(0, &bind_group, &offsets);
+        render_pass.set_vertex_buffer(0, &self.quad_vbo, 0, 0);
+        render_pass.set_index_buffer(&self.quad_ibo, 0, 0);
+
+        render_pass.draw_indexed(0..6, 0, 0..1);
+    }
+
     #[allow(clippy::cognitive_complexity)]
     fn register_shape_internal(&mut self, shape: &swf::Shape) -> ShapeHandle {
         let handle = ShapeHandle(self.meshes.len());
@@ -220,68 +1396,69 @@ impl WgpuRenderBackend {
 
         use lyon::tessellation::{FillOptions, StrokeOptions};
 
-        let transforms_label = create_debug_label!("Shape {} transforms ubo", shape.id);
-        let transforms_ubo = self.device.create_buffer(&wgpu::BufferDescriptor {
-            label: transforms_label.as_deref(),
-            size: std::mem::size_of::<Transforms>() as u64,
-            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
-        });
-
-        let colors_ubo = create_buffer_with_data(
-            &self.device,
-            bytemuck::cast_slice(&[ColorAdjustments {
-                mult_color: [1.0, 1.0, 1.0, 1.0],
-                add_color: [0.0, 0.0, 0.0, 0.0],
-            }]),
-            wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
-            create_debug_label!("Shape {} colors ubo", shape.id),
-        );
-
         let mut draws = Vec::new();
 
         let mut fill_tess = FillTessellator::new();
         let mut stroke_tess = StrokeTessellator::new();
-        let mut lyon_mesh: VertexBuffers<_, u16> = VertexBuffers::new();
+        let mut lyon_mesh: VertexBuffers<_, u32> = VertexBuffers::new();
+
+        let upload_label = create_debug_label!("Shape {} upload encoder", shape.id);
+        let mut upload_encoder =
+            self.descriptors
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: upload_label.as_deref(),
+                });
 
         #[allow(clippy::too_many_arguments)]
         fn flush_draw(
             shape_id: CharacterId,
             draw: IncompleteDrawType,
             draws: &mut Vec<Draw>,
-            lyon_mesh: &mut VertexBuffers<GPUVertex, u16>,
+            lyon_mesh: &mut VertexBuffers<GPUVertex, u32>,
             device: &wgpu::Device,
-            transforms_ubo: &wgpu::Buffer,
-            colors_ubo: &wgpu::Buffer,
+            uniform_buffer: &wgpu::Buffer,
             pipelines: &Pipelines,
+            samplers: &Samplers,
+            mesh_buffers: &mut BufferPool,
+            upload_encoder: &mut wgpu::CommandEncoder,
         ) {
             if lyon_mesh.vertices.is_empty() || lyon_mesh.indices.len() < 3 {
                 return;
             }
 
-            let vbo = create_buffer_with_data(
+            let vertices = mesh_buffers.upload(
                 device,
+                upload_encoder,
                 bytemuck::cast_slice(&lyon_mesh.vertices),
-                wgpu::BufferUsage::VERTEX,
-                create_debug_label!("Shape {} ({}) vbo", shape_id, draw.name()),
-            );
-
-            let ibo = create_buffer_with_data(
-                device,
-                bytemuck::cast_slice(&lyon_mesh.indices),
-                wgpu::BufferUsage::INDEX,
-                create_debug_label!("Shape {} ({}) ibo", shape_id, draw.name()),
             );
+            // Most draws fit in 16-bit indices, which take half the space. Large shapes, such
+            // as detailed maps, can tessellate into more vertices than that.
+            let (indices, index_format) = if lyon_mesh.vertices.len() <= MAX_U16_VERTICES {
+                let indices: Vec<u16> = lyon_mesh.indices.iter().map(|&i| i as u16).collect();
+                let indices =
+                    mesh_buffers.upload(device, upload_encoder, bytemuck::cast_slice(&indices));
+                (indices, wgpu::IndexFormat::Uint16)
+            } else {
+                let indices = mesh_buffers.upload(
+                    device,
+                    upload_encoder,
+                    bytemuck::cast_slice(&lyon_mesh.indices),
+                );
+                (indices, wgpu::IndexFormat::Uint32)
+            };
 
             let draw_id = draws.len();
 
             draws.push(draw.build(
                 device,
-                transforms_ubo,
-                colors_ubo,
-                vbo,
-                ibo,
+                uniform_buffer,
+                vertices,
+                indices,
                 lyon_mesh.indices.len() as u32,
+                index_format,
                 pipelines,
+                samplers,
                 shape_id,
                 draw_id,
             ));
@@ -291,14 +1468,9 @@ impl WgpuRenderBackend {
 
         for path in paths {
             match path {
-                DrawPath::Fill { style, commands } => match style {
+                DrawPath::Fill { style, commands } => match &*normalize_fill_style(style) {
                     FillStyle::Color(color) => {
-                        let color = [
-                            f32::from(color.r) / 255.0,
-                            f32::from(color.g) / 255.0,
-                            f32::from(color.b) / 255.0,
-                            f32::from(color.a) / 255.0,
-                        ];
+                        let color = self.descriptors.output_vertex_color(color);
 
                         let mut buffers_builder =
                             BuffersBuilder::new(&mut lyon_mesh, RuffleVertexCtor { color });
@@ -319,16 +1491,18 @@ impl WgpuRenderBackend {
                             IncompleteDrawType::Color,
                             &mut draws,
                             &mut lyon_mesh,
-                            &self.device,
-                            &transforms_ubo,
-                            &colors_ubo,
-                            &self.pipelines,
+                            &self.descriptors.device,
+                            self.uniforms.buffer(),
+                            &self.descriptors.pipelines,
+                            &self.descriptors.samplers,
+                            &mut self.mesh_buffers,
+                            &mut upload_encoder,
                         );
 
                         let mut buffers_builder = BuffersBuilder::new(
                             &mut lyon_mesh,
                             RuffleVertexCtor {
-                                color: [1.0, 1.0, 1.0, 1.0],
+                                color: vertex_color(&WHITE_VERTEX_COLOR),
                             },
                         );
 
@@ -342,30 +1516,7 @@ impl WgpuRenderBackend {
                             continue;
                         }
 
-                        let mut colors: [[f32; 4]; 16] = Default::default();
-                        let mut ratios: [[f32; 4]; 16] = Default::default();
-                        for (i, record) in gradient.records.iter().enumerate() {
-                            if i >= 16 {
-                                // TODO: we need to support these!
-                                break;
-                            }
-                            colors[i] = [
-                                f32::from(record.color.r) / 255.0,
-                                f32::from(record.color.g) / 255.0,
-                                f32::from(record.color.b) / 255.0,
-                                f32::from(record.color.a) / 255.0,
-                            ];
-                            ratios[i] = [f32::from(record.ratio) / 255.0, 0.0, 0.0, 0.0];
-                        }
-
-                        let uniforms = GradientUniforms {
-                            gradient_type: 0,
-                            ratios,
-                            colors,
-                            num_colors: gradient.records.len() as u32,
-                            repeat_mode: 0,
-                            focal_point: 0.0,
-                        };
+                        let uniforms = self.gradient_uniforms(gradient, 0, 0.0);
                         let matrix = swf_to_gl_matrix(gradient.matrix.clone());
 
                         flush_draw(
@@ -376,10 +1527,12 @@ impl WgpuRenderBackend {
                             },
                             &mut draws,
                             &mut lyon_mesh,
-                            &self.device,
-                            &transforms_ubo,
-                            &colors_ubo,
-                            &self.pipelines,
+                            &self.descriptors.device,
+                            self.uniforms.buffer(),
+                            &self.descriptors.pipelines,
+                            &self.descriptors.samplers,
+                            &mut self.mesh_buffers,
+                            &mut upload_encoder,
                         );
                     }
                     FillStyle::RadialGradient(gradient) => {
@@ -388,16 +1541,18 @@ impl WgpuRenderBackend {
                             IncompleteDrawType::Color,
                             &mut draws,
                             &mut lyon_mesh,
-                            &self.device,
-                            &transforms_ubo,
-                            &colors_ubo,
-                            &self.pipelines,
+                            &self.descriptors.device,
+                            self.uniforms.buffer(),
+                            &self.descriptors.pipelines,
+                            &self.descriptors.samplers,
+                            &mut self.mesh_buffers,
+                            &mut upload_encoder,
                         );
 
                         let mut buffers_builder = BuffersBuilder::new(
                             &mut lyon_mesh,
                             RuffleVertexCtor {
-                                color: [1.0, 1.0, 1.0, 1.0],
+                                color: vertex_color(&WHITE_VERTEX_COLOR),
                             },
                         );
 
@@ -411,30 +1566,7 @@ impl WgpuRenderBackend {
                             continue;
                         }
 
-                        let mut colors: [[f32; 4]; 16] = Default::default();
-                        let mut ratios: [[f32; 4]; 16] = Default::default();
-                        for (i, record) in gradient.records.iter().enumerate() {
-                            if i >= 16 {
-                                // TODO: we need to support these!
-                                break;
-                            }
-                            colors[i] = [
-                                f32::from(record.color.r) / 255.0,
-                                f32::from(record.color.g) / 255.0,
-                                f32::from(record.color.b) / 255.0,
-                                f32::from(record.color.a) / 255.0,
-                            ];
-                            ratios[i] = [f32::from(record.ratio) / 255.0, 0.0, 0.0, 0.0];
-                        }
-
-                        let uniforms = GradientUniforms {
-                            gradient_type: 1,
-                            ratios,
-                            colors,
-                            num_colors: gradient.records.len() as u32,
-                            repeat_mode: 0,
-                            focal_point: 0.0,
-                        };
+                        let uniforms = self.gradient_uniforms(gradient, 1, 0.0);
                         let matrix = swf_to_gl_matrix(gradient.matrix.clone());
 
                         flush_draw(
@@ -445,10 +1577,12 @@ impl WgpuRenderBackend {
                             },
                             &mut draws,
                             &mut lyon_mesh,
-                            &self.device,
-                            &transforms_ubo,
-                            &colors_ubo,
-                            &self.pipelines,
+                            &self.descriptors.device,
+                            self.uniforms.buffer(),
+                            &self.descriptors.pipelines,
+                            &self.descriptors.samplers,
+                            &mut self.mesh_buffers,
+                            &mut upload_encoder,
                         );
                     }
                     FillStyle::FocalGradient {
@@ -460,16 +1594,18 @@ impl WgpuRenderBackend {
                             IncompleteDrawType::Color,
                             &mut draws,
                             &mut lyon_mesh,
-                            &self.device,
-                            &transforms_ubo,
-                            &colors_ubo,
-                            &self.pipelines,
+                            &self.descriptors.device,
+                            self.uniforms.buffer(),
+                            &self.descriptors.pipelines,
+                            &self.descriptors.samplers,
+                            &mut self.mesh_buffers,
+                            &mut upload_encoder,
                         );
 
                         let mut buffers_builder = BuffersBuilder::new(
                             &mut lyon_mesh,
                             RuffleVertexCtor {
-                                color: [1.0, 1.0, 1.0, 1.0],
+                                color: vertex_color(&WHITE_VERTEX_COLOR),
                             },
                         );
 
@@ -483,30 +1619,7 @@ impl WgpuRenderBackend {
                             continue;
                         }
 
-                        let mut colors: [[f32; 4]; 16] = Default::default();
-                        let mut ratios: [[f32; 4]; 16] = Default::default();
-                        for (i, record) in gradient.records.iter().enumerate() {
-                            if i >= 16 {
-                                // TODO: we need to support these!
-                                break;
-                            }
-                            colors[i] = [
-                                f32::from(record.color.r) / 255.0,
-                                f32::from(record.color.g) / 255.0,
-                                f32::from(record.color.b) / 255.0,
-                                f32::from(record.color.a) / 255.0,
-                            ];
-                            ratios[i] = [f32::from(record.ratio) / 255.0, 0.0, 0.0, 0.0];
-                        }
-
-                        let uniforms = GradientUniforms {
-                            gradient_type: 1,
-                            ratios,
-                            colors,
-                            num_colors: gradient.records.len() as u32,
-                            repeat_mode: 0,
-                            focal_point: *focal_point,
-                        };
+                        let uniforms = self.gradient_uniforms(gradient, 2, *focal_point);
                         let matrix = swf_to_gl_matrix(gradient.matrix.clone());
 
                         flush_draw(
@@ -517,10 +1630,12 @@ impl WgpuRenderBackend {
                             },
                             &mut draws,
                             &mut lyon_mesh,
-                            &self.device,
-                            &transforms_ubo,
-                            &colors_ubo,
-                            &self.pipelines,
+                            &self.descriptors.device,
+                            self.uniforms.buffer(),
+                            &self.descriptors.pipelines,
+                            &self.descriptors.samplers,
+                            &mut self.mesh_buffers,
+                            &mut upload_encoder,
                         );
                     }
                     FillStyle::Bitmap {
@@ -534,16 +1649,18 @@ impl WgpuRenderBackend {
                             IncompleteDrawType::Color,
                             &mut draws,
                             &mut lyon_mesh,
-                            &self.device,
-                            &transforms_ubo,
-                            &colors_ubo,
-                            &self.pipelines,
+                            &self.descriptors.device,
+                            self.uniforms.buffer(),
+                            &self.descriptors.pipelines,
+                            &self.descriptors.samplers,
+                            &mut self.mesh_buffers,
+                            &mut upload_encoder,
                         );
 
                         let mut buffers_builder = BuffersBuilder::new(
                             &mut lyon_mesh,
                             RuffleVertexCtor {
-                                color: [1.0, 1.0, 1.0, 1.0],
+                                color: vertex_color(&WHITE_VERTEX_COLOR),
                             },
                         );
 
@@ -557,33 +1674,63 @@ impl WgpuRenderBackend {
                             continue;
                         }
 
-                        let texture = &self
+                        let handle = self
                             .textures
                             .iter()
-                            .find(|(other_id, _tex)| *other_id == *id)
-                            .unwrap()
-                            .1;
-                        let texture_view = texture.texture.create_default_view();
+                            .position(|texture| {
+                                matches!(texture, Some((other_id, _tex)) if *other_id == *id)
+                            })
+                            .unwrap();
+                        let texture = &self.textures[handle].as_ref().unwrap().1;
+                        let texture_transform =
+                            swf_bitmap_to_gl_matrix(matrix.clone(), texture.width, texture.height);
+                        let uv_bounds = if *is_repeating {
+                            UNCLAMPED_UV_BOUNDS
+                        } else {
+                            edge_texel_centers(texture.width, texture.height)
+                        };
+
+                        // Clipped fills are clamped within the bitmap, so they can sample an
+                        // atlas page, but repeating fills would wrap around into its neighbors.
+                        let atlas_slot = match &texture.storage {
+                            TextureStorage::Atlas { slot, .. } if !*is_repeating => Some(*slot),
+                            _ => None,
+                        };
+                        let (texture_view, texture_transforms) = match atlas_slot {
+                            Some(slot) => (
+                                self.atlas_pages[slot.page].create_default_view(),
+                                TextureTransforms {
+                                    u_matrix: slot.to_page_matrix(texture_transform),
+                                    uv_bounds: slot.to_page_bounds(uv_bounds),
+                                },
+                            ),
+                            None => (
+                                self.own_texture(handle).create_default_view(),
+                                TextureTransforms {
+                                    u_matrix: texture_transform,
+                                    uv_bounds,
+                                },
+                            ),
+                        };
 
                         flush_draw(
                             shape.id,
                             IncompleteDrawType::Bitmap {
-                                texture_transform: swf_bitmap_to_gl_matrix(
-                                    matrix.clone(),
-                                    texture.width,
-                                    texture.height,
-                                ),
+                                texture_transforms,
                                 is_smoothed: *is_smoothed,
                                 is_repeating: *is_repeating,
                                 texture_view,
                                 id: *id,
+                                atlas_page: atlas_slot.map(|slot| slot.page),
                             },
                             &mut draws,
                             &mut lyon_mesh,
-                            &self.device,
-                            &transforms_ubo,
-                            &colors_ubo,
-                            &self.pipelines,
+                            &self.descriptors.device,
+                            self.uniforms.buffer(),
+                            &self.descriptors.pipelines,
+                            &self.descriptors.samplers,
+                            &mut self.mesh_buffers,
+                            &mut upload_encoder,
                         );
                     }
                 },
@@ -592,12 +1739,7 @@ impl WgpuRenderBackend {
                     commands,
                     is_closed,
                 } => {
-                    let color = [
-                        f32::from(style.color.r) / 255.0,
-                        f32::from(style.color.g) / 255.0,
-                        f32::from(style.color.b) / 255.0,
-                        f32::from(style.color.a) / 255.0,
-                    ];
+                    let color = self.descriptors.output_vertex_color(&style.color);
 
                     let mut buffers_builder =
                         BuffersBuilder::new(&mut lyon_mesh, RuffleVertexCtor { color });
@@ -649,30 +1791,28 @@ impl WgpuRenderBackend {
             IncompleteDrawType::Color,
             &mut draws,
             &mut lyon_mesh,
-            &self.device,
-            &transforms_ubo,
-            &colors_ubo,
-            &self.pipelines,
+            &self.descriptors.device,
+            self.uniforms.buffer(),
+            &self.descriptors.pipelines,
+            &self.descriptors.samplers,
+            &mut self.mesh_buffers,
+            &mut upload_encoder,
         );
 
-        self.meshes.push(Mesh {
-            draws,
-            transforms: transforms_ubo,
-            colors_buffer: colors_ubo,
-            colors_last: ColorTransform::default(),
-            shape_id: shape.id,
-        });
+        self.descriptors
+            .queue
+            .borrow_mut()
+            .submit(&[upload_encoder.finish()]);
+
+        self.meshes.push(Some(Mesh { draws }));
 
         handle
     }
 
     fn draw_rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color) {
-        let (swap_chain_output, encoder) =
-            if let Some((swap_chain_output, encoder)) = &mut self.current_frame {
-                (swap_chain_output, encoder)
-            } else {
-                return;
-            };
+        if self.current_frame.is_none() {
+            return;
+        }
 
         let world_matrix = [
             [width, 0.0, 0.0, 0.0],
@@ -681,62 +1821,35 @@ impl WgpuRenderBackend {
             [x, y, 0.0, 1.0],
         ];
 
-        let mult_color = [
-            f32::from(color.r) / 255.0,
-            f32::from(color.g) / 255.0,
-            f32::from(color.b) / 255.0,
-            f32::from(color.a) / 255.0,
-        ];
+        let offsets = self.push_uniforms(
+            world_matrix,
+            ColorAdjustments {
+                mult_color: self.descriptors.output_color(&color),
+                add_color: [0.0, 0.0, 0.0, 0.0],
+            },
+        );
+        self.record_draw(Some(BindGroupKey::Rect));
 
-        let add_color = [0.0, 0.0, 0.0, 0.0];
+        let (frame_output, encoder) = match &mut self.current_frame {
+            Some((frame_output, encoder)) => (frame_output, encoder),
+            None => return,
+        };
 
-        let transforms_ubo = create_buffer_with_data(
-            &self.device,
-            bytemuck::cast_slice(&[Transforms {
-                view_matrix: self.view_matrix,
-                world_matrix,
-            }]),
-            wgpu::BufferUsage::UNIFORM,
-            create_debug_label!("Rectangle transfer buffer"),
+        let pipeline = self.descriptors.pipelines.pipeline_for(
+            &self.descriptors.device,
+            ShapeType::Color,
+            self.mask_state,
+            self.blend_mode,
+            wgpu::IndexFormat::Uint16,
+            self.msaa_sample_count,
         );
 
-        let colors_ubo = create_buffer_with_data(
-            &self.device,
-            bytemuck::cast_slice(&[ColorAdjustments {
-                mult_color,
-                add_color,
-            }]),
-            wgpu::BufferUsage::UNIFORM,
-            create_debug_label!("Rectangle colors transfer buffer"),
+        let (color_attachment, resolve_target) = draw_target(
+            frame_output,
+            &self.frame_buffer_view,
+            &self.layers,
+            self.msaa_sample_count,
         );
-
-        let bind_group_label = create_debug_label!("Rectangle bind group");
-        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &self.pipelines.color.bind_layout,
-            bindings: &[
-                wgpu::Binding {
-                    binding: 0,
-                    resource: wgpu::BindingResource::Buffer {
-                        buffer: &transforms_ubo,
-                        range: 0..std::mem::size_of::<Transforms>() as u64,
-                    },
-                },
-                wgpu::Binding {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Buffer {
-                        buffer: &colors_ubo,
-                        range: 0..std::mem::size_of::<ColorAdjustments>() as u64,
-                    },
-                },
-            ],
-            label: bind_group_label.as_deref(),
-        });
-
-        let (color_attachment, resolve_target) = if self.msaa_sample_count >= 2 {
-            (&self.frame_buffer_view, Some(&swap_chain_output.view))
-        } else {
-            (&swap_chain_output.view, None)
-        };
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
                 attachment: color_attachment,
@@ -756,71 +1869,362 @@ impl WgpuRenderBackend {
             }),
         });
 
-        render_pass.set_pipeline(&self.pipelines.color.pipeline_for(
-            self.num_masks,
-            self.num_masks_active,
-            self.test_stencil_mask,
-            self.write_stencil_mask,
-        ));
-        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.set_pipeline(&pipeline);
+        render_pass.set_bind_group(0, &self.rect_bind_group, &offsets);
         render_pass.set_vertex_buffer(0, &self.quad_vbo, 0, 0);
         render_pass.set_index_buffer(&self.quad_ibo, 0, 0);
 
-        if self.num_masks_active < self.num_masks {
-            render_pass.set_stencil_reference(self.write_stencil_mask);
-        } else {
-            render_pass.set_stencil_reference(self.test_stencil_mask);
-        }
+        render_pass.set_stencil_reference(self.num_masks_active);
 
         render_pass.draw_indexed(0..6, 0, 0..1);
     }
-}
 
-impl RenderBackend for WgpuRenderBackend {
-    fn set_viewport_dimensions(&mut self, width: u32, height: u32) {
-        // Avoid panics from creating 0-sized framebuffers.
-        let width = std::cmp::max(width, 1);
-        let height = std::cmp::max(height, 1);
+    /// Registers a bitmap of premultiplied RGBA pixels.
+    ///
+    /// Small mipmapped bitmaps are packed into atlas pages, so that draws of different bitmaps
+    /// can share a bind group. Others get a texture of their own.
+    fn register_texture(
+        &mut self,
+        id: CharacterId,
+        width: u32,
+        height: u32,
+        rgba: Vec<u8>,
+        mipmapped: bool,
+    ) -> BitmapHandle {
+        let handle = BitmapHandle(self.textures.len());
+        let slot = if mipmapped {
+            self.atlas.allocate(width, height)
+        } else {
+            None
+        };
+        let storage = match slot {
+            Some(slot) => {
+                self.write_atlas_slot(&slot, &rgba, handle.0);
+                TextureStorage::Atlas {
+                    slot,
+                    rgba,
+                    own: None,
+                }
+            }
+            None => {
+                let (texture, is_downscaled) =
+                    self.create_bitmap_texture(id, width, height, rgba, mipmapped, handle.0);
+                TextureStorage::Own {
+                    texture,
+                    is_downscaled,
+                }
+            }
+        };
+        self.textures.push(Some((
+            id,
+            Texture {
+                width,
+                height,
+                storage,
+            },
+        )));
+
+        handle
+    }
+
+    /// Creates a texture for a bitmap of premultiplied RGBA pixels, returning it along with
+    /// whether it had to be downscaled.
+    ///
+    /// Bitmaps larger than the maximum texture size are downscaled to fit. They are still laid
+    /// out with their original size, so they appear at the same size on stage, only blurrier.
+    ///
+    /// Mipmapped textures get a full mip chain, so that they stay smooth when scaled down.
+    /// Textures that are updated region by region can't have one, as the smaller levels
+    /// would go stale.
+    fn create_bitmap_texture(
+        &self,
+        id: CharacterId,
+        width: u32,
+        height: u32,
+        rgba: Vec<u8>,
+        mipmapped: bool,
+        handle: usize,
+    ) -> (wgpu::Texture, bool) {
+        let max_texture_size = self.descriptors.max_texture_size;
+        let (texture_width, texture_height, rgba) =
+            match downscale_to_fit(width, height, &rgba, max_texture_size) {
+                Some((texture_width, texture_height, downscaled)) => {
+                    log::warn!(
+                    "Downscaling {}x{} bitmap {} to {}x{} to fit the maximum texture size of {}",
+                    width,
+                    height,
+                    id,
+                    texture_width,
+                    texture_height,
+                    max_texture_size
+                );
+                    (texture_width, texture_height, downscaled)
+                }
+                None => (width, height, rgba),
+            };
 
-        self.swap_chain_desc.width = width;
-        self.swap_chain_desc.height = height;
-        self.swap_chain = self
+        let mip_level_count = if mipmapped {
+            mip_level_count(texture_width, texture_height)
+        } else {
+            1
+        };
+
+        let texture = self.create_texture(
+            create_debug_label!("Bitmap texture {}", id),
+            texture_width,
+            texture_height,
+            mip_level_count,
+        );
+        self.write_mip_levels(
+            &texture,
+            (0, 0),
+            (texture_width, texture_height),
+            rgba,
+            mip_level_count,
+            handle,
+        );
+
+        let is_downscaled = texture_width != width || texture_height != height;
+        (texture, is_downscaled)
+    }
+
+    /// Creates an empty texture that bitmaps can be copied into.
+    fn create_texture(
+        &self,
+        label: Option<String>,
+        width: u32,
+        height: u32,
+        mip_level_count: u32,
+    ) -> wgpu::Texture {
+        self.descriptors
             .device
-            .create_swap_chain(&self.window_surface, &self.swap_chain_desc);
+            .create_texture(&wgpu::TextureDescriptor {
+                label: label.as_deref(),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth: 1,
+                },
+                array_layer_count: 1,
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                // Sampling an sRGB texture converts its pixels to linear colors.
+                format: if self.descriptors.is_srgb() {
+                    wgpu::TextureFormat::Rgba8UnormSrgb
+                } else {
+                    wgpu::TextureFormat::Rgba8Unorm
+                },
+                usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+            })
+    }
+
+    /// Copies premultiplied RGBA pixels into a texture at the given position, along with the
+    /// given number of mip levels made from them, each at the same position scaled down.
+    fn write_mip_levels(
+        &self,
+        texture: &wgpu::Texture,
+        (x, y): (u32, u32),
+        (width, height): (u32, u32),
+        rgba: Vec<u8>,
+        mip_level_count: u32,
+        handle: usize,
+    ) {
+        let (mut level_width, mut level_height, mut level) = (width, height, rgba);
+        for mip_level in 0..mip_level_count {
+            if mip_level > 0 {
+                let (width, height, next) = next_mip_level(level_width, level_height, &level);
+                level_width = width;
+                level_height = height;
+                level = next;
+            }
+            let (level_x, level_y) = (x >> mip_level, y >> mip_level);
+            self.write_texture_region(
+                texture,
+                mip_level,
+                PixelRegion {
+                    x_min: level_x,
+                    y_min: level_y,
+                    x_max: level_x + level_width,
+                    y_max: level_y + level_height,
+                },
+                &level[..],
+                handle,
+            );
+        }
+    }
+
+    /// Copies a bitmap of premultiplied RGBA pixels into its slot in an atlas page, creating
+    /// the page if it is new.
+    fn write_atlas_slot(&mut self, slot: &AtlasSlot, rgba: &[u8], handle: usize) {
+        if slot.page == self.atlas_pages.len() {
+            let page_size = self.atlas.page_size();
+            let page = self.create_texture(
+                create_debug_label!("Atlas page {}", slot.page),
+                page_size,
+                page_size,
+                ATLAS_MIP_LEVEL_COUNT,
+            );
+            self.atlas_pages.push(page);
+        }
+        self.write_mip_levels(
+            &self.atlas_pages[slot.page],
+            slot.padded_origin(),
+            slot.padded_size(),
+            pad_bitmap(slot, rgba),
+            ATLAS_MIP_LEVEL_COUNT,
+            handle,
+        );
+    }
 
-        let label = create_debug_label!("Framebuffer texture");
-        let frame_buffer = self.device.create_texture(&wgpu::TextureDescriptor {
-            label: label.as_deref(),
-            size: wgpu::Extent3d {
+    /// The texture of a bitmap of its own, which is created from its pixels the first time it
+    /// is needed if the bitmap is in an atlas page.
+    fn own_texture(&mut self, handle: usize) -> &wgpu::Texture {
+        if let Some((
+            id,
+            Texture {
                 width,
                 height,
-                depth: 1,
+                storage:
+                    TextureStorage::Atlas {
+                        rgba, own: None, ..
+                    },
             },
-            array_layer_count: 1,
-            mip_level_count: 1,
-            sample_count: self.msaa_sample_count,
-            dimension: wgpu::TextureDimension::D2,
-            format: self.swap_chain_desc.format,
-            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
-        });
-        self.frame_buffer_view = frame_buffer.create_default_view();
+        )) = &self.textures[handle]
+        {
+            let (texture, _is_downscaled) =
+                self.create_bitmap_texture(*id, *width, *height, rgba.clone(), true, handle);
+            if let Some((
+                _id,
+                Texture {
+                    storage: TextureStorage::Atlas { own, .. },
+                    ..
+                },
+            )) = &mut self.textures[handle]
+            {
+                *own = Some(texture);
+            }
+        }
 
-        let label = create_debug_label!("Depth texture");
-        let depth_texture = self.device.create_texture(&wgpu::TextureDescriptor {
-            label: label.as_deref(),
-            size: wgpu::Extent3d {
-                width,
-                height,
+        match &self.textures[handle] {
+            Some((
+                _id,
+                Texture {
+                    storage: TextureStorage::Own { texture, .. },
+                    ..
+                },
+            ))
+            | Some((
+                _id,
+                Texture {
+                    storage:
+                        TextureStorage::Atlas {
+                            own: Some(texture), ..
+                        },
+                    ..
+                },
+            )) => texture,
+            _ => unreachable!("Bitmap {} has no texture", handle),
+        }
+    }
+
+    /// Copies premultiplied RGBA pixels into a region of a mip level of a texture.
+    fn write_texture_region(
+        &self,
+        texture: &wgpu::Texture,
+        mip_level: u32,
+        region: PixelRegion,
+        rgba: &[u8],
+        handle: usize,
+    ) {
+        if region.width() == 0 || region.height() == 0 {
+            return;
+        }
+
+        // Buffer-to-texture copies require rows to start on a 256-byte boundary,
+        // so the tightly packed rows are staged into a padded buffer.
+        let row_size = region.width() * 4;
+        let padded_row_size = (row_size + COPY_BYTES_PER_ROW_ALIGNMENT - 1)
+            / COPY_BYTES_PER_ROW_ALIGNMENT
+            * COPY_BYTES_PER_ROW_ALIGNMENT;
+        let staged;
+        let data = if padded_row_size == row_size {
+            rgba
+        } else {
+            let mut padded = vec![0; (padded_row_size * region.height()) as usize];
+            for (src, dest) in rgba
+                .chunks_exact(row_size as usize)
+                .zip(padded.chunks_exact_mut(padded_row_size as usize))
+            {
+                dest[..row_size as usize].copy_from_slice(src);
+            }
+            staged = padded;
+            &staged[..]
+        };
+
+        let buffer = create_buffer_with_data(
+            &self.descriptors.device,
+            data,
+            wgpu::BufferUsage::COPY_SRC,
+            create_debug_label!("Bitmap region transfer buffer {}", handle),
+        );
+        let encoder_label = create_debug_label!("Bitmap region encoder {}", handle);
+        let mut encoder =
+            self.descriptors
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: encoder_label.as_deref(),
+                });
+
+        encoder.copy_buffer_to_texture(
+            wgpu::BufferCopyView {
+                buffer: &buffer,
+                offset: 0,
+                bytes_per_row: padded_row_size,
+                rows_per_image: 0,
+            },
+            wgpu::TextureCopyView {
+                texture,
+                mip_level,
+                array_layer: 0,
+                origin: wgpu::Origin3d {
+                    x: region.x_min,
+                    y: region.y_min,
+                    z: 0,
+                },
+            },
+            wgpu::Extent3d {
+                width: region.width(),
+                height: region.height(),
                 depth: 1,
             },
-            array_layer_count: 1,
-            mip_level_count: 1,
-            sample_count: self.msaa_sample_count,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth24PlusStencil8,
-            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
-        });
-        self.depth_texture_view = depth_texture.create_default_view();
+        );
+        self.descriptors
+            .queue
+            .borrow_mut()
+            .submit(&[encoder.finish()]);
+    }
+}
+
+impl RenderBackend for WgpuRenderBackend {
+    fn set_viewport_dimensions(&mut self, width: u32, height: u32) {
+        // Avoid panics from creating 0-sized framebuffers.
+        let width = std::cmp::max(width, 1);
+        let height = std::cmp::max(height, 1);
+
+        self.target.resize(&self.descriptors.device, width, height);
+        // A frame captured at the old size can't be read back at the new one.
+        self.capture_target = None;
+
+        let (frame_buffer_view, depth_texture_view) = create_frame_buffers(
+            &self.descriptors.device,
+            self.target.format(),
+            width,
+            height,
+            self.msaa_sample_count,
+        );
+        self.frame_buffer_view = frame_buffer_view;
+        self.depth_texture_view = depth_texture_view;
 
         self.viewport_width = width as f32;
         self.viewport_height = height as f32;
@@ -884,57 +2288,7 @@ impl RenderBackend for WgpuRenderBackend {
             as_rgba.push(255);
         }
 
-        let texture_label = create_debug_label!("JPEG (2) texture {}", id);
-        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
-            label: texture_label.as_deref(),
-            size: extent,
-            array_layer_count: 1,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
-        });
-
-        let buffer = create_buffer_with_data(
-            &self.device,
-            &as_rgba[..],
-            wgpu::BufferUsage::COPY_SRC,
-            create_debug_label!("JPEG (2) transfer buffer {}", id),
-        );
-        let encoder_label = create_debug_label!("JPEG (2) encoder {}", id);
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: encoder_label.as_deref(),
-            });
-
-        encoder.copy_buffer_to_texture(
-            wgpu::BufferCopyView {
-                buffer: &buffer,
-                offset: 0,
-                bytes_per_row: 4 * extent.width,
-                rows_per_image: 0,
-            },
-            wgpu::TextureCopyView {
-                texture: &texture,
-                mip_level: 0,
-                array_layer: 0,
-                origin: wgpu::Origin3d::ZERO,
-            },
-            extent,
-        );
-        self.queue.submit(&[encoder.finish()]);
-
-        let handle = BitmapHandle(self.textures.len());
-        self.textures.push((
-            id,
-            Texture {
-                texture,
-                width: metadata.width.into(),
-                height: metadata.height.into(),
-            },
-        ));
+        let handle = self.register_texture(id, extent.width, extent.height, as_rgba, true);
 
         BitmapInfo {
             handle,
@@ -945,138 +2299,32 @@ impl RenderBackend for WgpuRenderBackend {
 
     fn register_bitmap_jpeg_3(
         &mut self,
-        id: u16,
-        jpeg_data: &[u8],
-        alpha_data: &[u8],
-    ) -> BitmapInfo {
-        let (width, height, rgba) =
-            ruffle_core::backend::render::define_bits_jpeg_to_rgba(jpeg_data, alpha_data)
-                .expect("Error decoding DefineBitsJPEG3");
-        let extent = wgpu::Extent3d {
-            width: width as u32,
-            height: height as u32,
-            depth: 1,
-        };
-
-        let texture_label = create_debug_label!("JPEG (3) texture {}", id);
-        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
-            label: texture_label.as_deref(),
-            size: extent,
-            array_layer_count: 1,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
-        });
-
-        let buffer = create_buffer_with_data(
-            &self.device,
-            &rgba[..],
-            wgpu::BufferUsage::COPY_SRC,
-            create_debug_label!("JPEG (3) transfer buffer {}", id),
-        );
-        let encoder_label = create_debug_label!("JPEG (3) encoder {}", id);
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: encoder_label.as_deref(),
-            });
-
-        encoder.copy_buffer_to_texture(
-            wgpu::BufferCopyView {
-                buffer: &buffer,
-                offset: 0,
-                bytes_per_row: 4 * extent.width,
-                rows_per_image: 0,
-            },
-            wgpu::TextureCopyView {
-                texture: &texture,
-                mip_level: 0,
-                array_layer: 0,
-                origin: wgpu::Origin3d::ZERO,
-            },
-            extent,
-        );
-        self.queue.submit(&[encoder.finish()]);
-
-        let handle = BitmapHandle(self.textures.len());
-        self.textures.push((
-            id,
-            Texture {
-                texture,
-                width,
-                height,
-            },
-        ));
-
-        BitmapInfo {
-            handle,
-            width: width.try_into().unwrap(),
-            height: height.try_into().unwrap(),
-        }
-    }
-
-    fn register_bitmap_png(&mut self, swf_tag: &DefineBitsLossless) -> BitmapInfo {
-        let decoded_data = ruffle_core::backend::render::define_bits_lossless_to_rgba(swf_tag)
-            .expect("Error decoding DefineBitsLossless");
-        let extent = wgpu::Extent3d {
-            width: swf_tag.width as u32,
-            height: swf_tag.height as u32,
-            depth: 1,
-        };
-
-        let texture_label = create_debug_label!("PNG texture {}", swf_tag.id);
-        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
-            label: texture_label.as_deref(),
-            size: extent,
-            array_layer_count: 1,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
-        });
-
-        let buffer = create_buffer_with_data(
-            &self.device,
-            &decoded_data[..],
-            wgpu::BufferUsage::COPY_SRC,
-            create_debug_label!("PNG transfer buffer {}", swf_tag.id),
-        );
-        let encoder_label = create_debug_label!("PNG encoder {}", swf_tag.id);
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: encoder_label.as_deref(),
-            });
-
-        encoder.copy_buffer_to_texture(
-            wgpu::BufferCopyView {
-                buffer: &buffer,
-                offset: 0,
-                bytes_per_row: 4 * extent.width,
-                rows_per_image: 0,
-            },
-            wgpu::TextureCopyView {
-                texture: &texture,
-                mip_level: 0,
-                array_layer: 0,
-                origin: wgpu::Origin3d::ZERO,
-            },
-            extent,
-        );
-        self.queue.submit(&[encoder.finish()]);
+        id: u16,
+        jpeg_data: &[u8],
+        alpha_data: &[u8],
+    ) -> BitmapInfo {
+        let (width, height, rgba) =
+            ruffle_core::backend::render::define_bits_jpeg_to_rgba(jpeg_data, alpha_data)
+                .expect("Error decoding DefineBitsJPEG3");
+        let handle = self.register_texture(id, width, height, rgba, true);
 
-        let handle = BitmapHandle(self.textures.len());
-        self.textures.push((
+        BitmapInfo {
+            handle,
+            width: width.try_into().unwrap(),
+            height: height.try_into().unwrap(),
+        }
+    }
+
+    fn register_bitmap_png(&mut self, swf_tag: &DefineBitsLossless) -> BitmapInfo {
+        let decoded_data = ruffle_core::backend::render::define_bits_lossless_to_rgba(swf_tag)
+            .expect("Error decoding DefineBitsLossless");
+        let handle = self.register_texture(
             swf_tag.id,
-            Texture {
-                texture,
-                width: swf_tag.width.into(),
-                height: swf_tag.height.into(),
-            },
-        ));
+            swf_tag.width.into(),
+            swf_tag.height.into(),
+            decoded_data,
+            true,
+        );
 
         BitmapInfo {
             handle,
@@ -1085,48 +2333,135 @@ impl RenderBackend for WgpuRenderBackend {
         }
     }
 
+    fn register_bitmap_raw(&mut self, width: u32, height: u32, rgba: Vec<u8>) -> BitmapHandle {
+        self.register_texture(0, width, height, rgba, false)
+    }
+
+    fn update_bitmap_region(&mut self, bitmap: BitmapHandle, region: PixelRegion, rgba: &[u8]) {
+        let texture = match self.textures.get(bitmap.0) {
+            Some(Some((_id, texture))) => texture,
+            _ => {
+                log::warn!("Attempted to update nonexistent bitmap {}", bitmap.0);
+                return;
+            }
+        };
+        match &texture.storage {
+            TextureStorage::Own {
+                is_downscaled: true,
+                ..
+            } => log::warn!(
+                "Can't update bitmap {}, as it was downscaled to fit the maximum texture size",
+                bitmap.0
+            ),
+            TextureStorage::Own { texture, .. } => {
+                self.write_texture_region(texture, 0, region, rgba, bitmap.0)
+            }
+            TextureStorage::Atlas { .. } => log::warn!(
+                "Can't update bitmap {}, as it is packed into an atlas page",
+                bitmap.0
+            ),
+        }
+    }
+
+    fn unregister_shape(&mut self, shape: ShapeHandle) {
+        // Dropping the mesh releases its uniform buffers and bind groups, while its vertices
+        // and indices go back to the pool once no frame can be using them.
+        if let Some(mesh) = self.meshes.get_mut(shape.0).and_then(Option::take) {
+            for draw in mesh.draws {
+                self.mesh_buffers.free(draw.vertices);
+                self.mesh_buffers.free(draw.indices);
+            }
+            if self.current_frame.is_none() {
+                self.mesh_buffers.recycle();
+            }
+        }
+    }
+
+    fn unregister_bitmap(&mut self, bitmap: BitmapHandle) {
+        let texture = match self.textures.get_mut(bitmap.0).and_then(Option::take) {
+            Some((_id, texture)) => texture,
+            None => return,
+        };
+        if let TextureStorage::Atlas { slot, .. } = &texture.storage {
+            self.atlas.free(slot);
+        }
+        self.bitmap_bind_groups
+            .retain(|(source, _is_smoothed), _bind_group| {
+                *source != BitmapSource::Bitmap(bitmap.0)
+            });
+    }
+
     fn begin_frame(&mut self) {
         assert!(self.current_frame.is_none());
-        self.current_frame = match self.swap_chain.get_next_texture() {
+        if self.device_lost {
+            return;
+        }
+        let capturing =
+            self.capture_requested && matches!(self.target, RenderTarget::SwapChain { .. });
+        self.capture_requested = false;
+        if capturing {
+            // Window surfaces can't be read back, so the frame is drawn into a texture instead.
+            // With MSAA, draws resolve into this texture just as they would into the window.
+            self.capture_target = Some(RenderTarget::texture(
+                &self.descriptors.device,
+                self.target.format(),
+                self.viewport_width as u32,
+                self.viewport_height as u32,
+            ));
+        }
+        let target = match &mut self.capture_target {
+            Some(capture_target) if capturing => capture_target,
+            _ => &mut self.target,
+        };
+        self.current_frame = match target.get_next_frame() {
             Ok(frame) => {
                 let label = create_debug_label!("Frame encoder");
                 Some((
                     frame,
-                    self.device
-                        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    self.descriptors.device.create_command_encoder(
+                        &wgpu::CommandEncoderDescriptor {
                             label: label.as_deref(),
-                        }),
+                        },
+                    ),
                 ))
             }
             Err(wgpu::TimeOut) => {
-                log::warn!("Couldn't begin new render frame: timed out whilst aquiring new swapchain output");
+                log::warn!("Couldn't begin new render frame: timed out whilst aquiring new render target texture");
                 None
             }
         };
+        self.uniforms.reset_num_uploads();
+        self.frame_stats = FrameStats::default();
+        self.last_bind_group = None;
         self.num_masks = 0;
         self.num_masks_active = 0;
-        self.write_stencil_mask = 0;
-        self.test_stencil_mask = 0;
-        self.next_stencil_mask = 1;
+        self.mask_state = MaskState::DrawContent;
+        self.masker_draws.clear();
+        self.blend_mode = BlendMode::Normal;
+        self.blend_mode_stack.clear();
+        self.layers.clear();
     }
 
     fn clear(&mut self, color: Color) {
-        if let Some((swap_chain_output, encoder)) = &mut self.current_frame {
+        if let Some((frame_output, encoder)) = &mut self.current_frame {
             let (color_attachment, resolve_target) = if self.msaa_sample_count >= 2 {
-                (&self.frame_buffer_view, Some(&swap_chain_output.view))
+                (&self.frame_buffer_view, Some(frame_output.view()))
             } else {
-                (&swap_chain_output.view, None)
+                (frame_output.view(), None)
             };
             encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
                     attachment: color_attachment,
                     load_op: wgpu::LoadOp::Clear,
                     store_op: wgpu::StoreOp::Store,
-                    clear_color: wgpu::Color {
-                        r: f64::from(color.r) / 255.0,
-                        g: f64::from(color.g) / 255.0,
-                        b: f64::from(color.b) / 255.0,
-                        a: f64::from(color.a) / 255.0,
+                    clear_color: {
+                        let [r, g, b, a] = self.descriptors.output_color(&color);
+                        wgpu::Color {
+                            r: f64::from(r),
+                            g: f64::from(g),
+                            b: f64::from(b),
+                            a: f64::from(a),
+                        }
                     },
                     resolve_target,
                 }],
@@ -1143,158 +2478,139 @@ impl RenderBackend for WgpuRenderBackend {
         }
     }
 
-    fn render_bitmap(&mut self, bitmap: BitmapHandle, transform: &Transform) {
-        if let Some((_id, texture)) = self.textures.get(bitmap.0) {
-            let (swap_chain_output, encoder) =
-                if let Some((swap_chain_output, encoder)) = &mut self.current_frame {
-                    (swap_chain_output, encoder)
-                } else {
-                    return;
-                };
+    fn render_bitmap(&mut self, bitmap: BitmapHandle, transform: &Transform, smoothing: bool) {
+        if self.mask_state == MaskState::WriteMask {
+            if let Some(draws) = self.masker_draws.last_mut() {
+                draws.push(MaskerDraw::Bitmap(bitmap, transform.clone(), smoothing));
+            }
+        }
 
-            use ruffle_core::matrix::Matrix;
-            let transform = Transform {
-                matrix: transform.matrix
-                    * Matrix {
-                        a: texture.width as f32,
-                        d: texture.height as f32,
-                        ..Default::default()
+        // Bitmaps in atlas pages share the bind group of their page, and are picked out of it
+        // by their texture transform.
+        let (width, height, source, texture_transforms) = match self.textures.get(bitmap.0) {
+            Some(Some((_id, texture))) => match &texture.storage {
+                TextureStorage::Own { .. } => (
+                    texture.width,
+                    texture.height,
+                    BitmapSource::Bitmap(bitmap.0),
+                    TextureTransforms {
+                        u_matrix: IDENTITY_TEXTURE_TRANSFORM,
+                        uv_bounds: edge_texel_centers(texture.width, texture.height),
                     },
-                ..*transform
-            };
+                ),
+                TextureStorage::Atlas { slot, .. } => (
+                    texture.width,
+                    texture.height,
+                    BitmapSource::Page(slot.page),
+                    TextureTransforms {
+                        u_matrix: slot.to_page_matrix(IDENTITY_TEXTURE_TRANSFORM),
+                        uv_bounds: slot
+                            .to_page_bounds(edge_texel_centers(texture.width, texture.height)),
+                    },
+                ),
+            },
+            _ => return,
+        };
+        if self.current_frame.is_none() {
+            return;
+        }
 
-            let world_matrix = [
-                [transform.matrix.a, transform.matrix.b, 0.0, 0.0],
-                [transform.matrix.c, transform.matrix.d, 0.0, 0.0],
-                [0.0, 0.0, 1.0, 0.0],
-                [
-                    transform.matrix.tx.to_pixels() as f32,
-                    transform.matrix.ty.to_pixels() as f32,
-                    0.0,
-                    1.0,
-                ],
-            ];
-
-            let transforms_ubo = create_buffer_with_data(
-                &self.device,
-                bytemuck::cast_slice(&[Transforms {
-                    view_matrix: self.view_matrix,
-                    world_matrix,
-                }]),
-                wgpu::BufferUsage::UNIFORM,
-                create_debug_label!("Bitmap {} transforms transfer buffer", bitmap.0),
-            );
+        use ruffle_core::matrix::Matrix;
+        let transform = Transform {
+            matrix: transform.matrix
+                * Matrix {
+                    a: width as f32,
+                    d: height as f32,
+                    ..Default::default()
+                },
+            ..*transform
+        };
 
-            let colors_ubo = create_buffer_with_data(
-                &self.device,
-                bytemuck::cast_slice(&[ColorAdjustments::from(transform.color_transform)]),
-                wgpu::BufferUsage::UNIFORM,
-                create_debug_label!("Bitmap {} colors transfer buffer", bitmap.0),
-            );
+        let world_matrix = [
+            [transform.matrix.a, transform.matrix.b, 0.0, 0.0],
+            [transform.matrix.c, transform.matrix.d, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [
+                transform.matrix.tx.to_pixels() as f32,
+                transform.matrix.ty.to_pixels() as f32,
+                0.0,
+                1.0,
+            ],
+        ];
 
-            let texture_view = texture.texture.create_default_view();
-            let sampler = self.device.create_sampler(&wgpu::SamplerDescriptor {
-                address_mode_u: wgpu::AddressMode::ClampToEdge,
-                address_mode_v: wgpu::AddressMode::ClampToEdge,
-                address_mode_w: wgpu::AddressMode::ClampToEdge,
-                mag_filter: wgpu::FilterMode::Linear,
-                min_filter: wgpu::FilterMode::Linear,
-                mipmap_filter: wgpu::FilterMode::Linear,
-                lod_min_clamp: 0.0,
-                lod_max_clamp: 100.0,
-                compare: wgpu::CompareFunction::Undefined,
-            });
+        let offsets = self.push_bitmap_uniforms(
+            world_matrix,
+            texture_transforms,
+            self.descriptors
+                .color_adjustments(transform.color_transform),
+        );
 
-            let bind_group_label = create_debug_label!("Bitmap {} bind group", bitmap.0);
-            let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: &self.pipelines.bitmap.bind_layout,
-                bindings: &[
-                    wgpu::Binding {
-                        binding: 0,
-                        resource: wgpu::BindingResource::Buffer {
-                            buffer: &transforms_ubo,
-                            range: 0..std::mem::size_of::<Transforms>() as u64,
-                        },
-                    },
-                    wgpu::Binding {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Buffer {
-                            buffer: &self.quad_tex_transforms,
-                            range: 0..std::mem::size_of::<TextureTransforms>() as u64,
-                        },
-                    },
-                    wgpu::Binding {
-                        binding: 2,
-                        resource: wgpu::BindingResource::Buffer {
-                            buffer: &colors_ubo,
-                            range: 0..std::mem::size_of::<ColorAdjustments>() as u64,
-                        },
-                    },
-                    wgpu::Binding {
-                        binding: 3,
-                        resource: wgpu::BindingResource::TextureView(&texture_view),
-                    },
-                    wgpu::Binding {
-                        binding: 4,
-                        resource: wgpu::BindingResource::Sampler(&sampler),
-                    },
-                ],
-                label: bind_group_label.as_deref(),
-            });
+        let is_smoothed = smoothing && self.bitmap_smoothing;
+        self.prepare_bitmap_bind_group(source, is_smoothed);
+        self.record_draw(Some(BindGroupKey::Bitmap(source, is_smoothed)));
+        let bind_group = match self.bitmap_bind_groups.get(&(source, is_smoothed)) {
+            Some(bind_group) => bind_group,
+            None => return,
+        };
+        let (frame_output, encoder) = match &mut self.current_frame {
+            Some((frame_output, encoder)) => (frame_output, encoder),
+            None => return,
+        };
 
-            let (color_attachment, resolve_target) = if self.msaa_sample_count >= 2 {
-                (&self.frame_buffer_view, Some(&swap_chain_output.view))
-            } else {
-                (&swap_chain_output.view, None)
-            };
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                    attachment: color_attachment,
-                    load_op: wgpu::LoadOp::Load,
-                    store_op: wgpu::StoreOp::Store,
-                    clear_color: wgpu::Color::WHITE,
-                    resolve_target,
-                }],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
-                    attachment: &self.depth_texture_view,
-                    depth_load_op: wgpu::LoadOp::Load,
-                    depth_store_op: wgpu::StoreOp::Store,
-                    stencil_load_op: wgpu::LoadOp::Load,
-                    stencil_store_op: wgpu::StoreOp::Store,
-                    clear_depth: 0.0,
-                    clear_stencil: 0,
-                }),
-            });
+        let pipeline = self.descriptors.pipelines.pipeline_for(
+            &self.descriptors.device,
+            ShapeType::Bitmap,
+            self.mask_state,
+            self.blend_mode,
+            wgpu::IndexFormat::Uint16,
+            self.msaa_sample_count,
+        );
 
-            render_pass.set_pipeline(&self.pipelines.bitmap.pipeline_for(
-                self.num_masks,
-                self.num_masks_active,
-                self.test_stencil_mask,
-                self.write_stencil_mask,
-            ));
-            render_pass.set_bind_group(0, &bind_group, &[]);
-            render_pass.set_vertex_buffer(0, &self.quad_vbo, 0, 0);
-            render_pass.set_index_buffer(&self.quad_ibo, 0, 0);
+        let (color_attachment, resolve_target) = draw_target(
+            frame_output,
+            &self.frame_buffer_view,
+            &self.layers,
+            self.msaa_sample_count,
+        );
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: color_attachment,
+                load_op: wgpu::LoadOp::Load,
+                store_op: wgpu::StoreOp::Store,
+                clear_color: wgpu::Color::WHITE,
+                resolve_target,
+            }],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                attachment: &self.depth_texture_view,
+                depth_load_op: wgpu::LoadOp::Load,
+                depth_store_op: wgpu::StoreOp::Store,
+                stencil_load_op: wgpu::LoadOp::Load,
+                stencil_store_op: wgpu::StoreOp::Store,
+                clear_depth: 0.0,
+                clear_stencil: 0,
+            }),
+        });
 
-            if self.num_masks_active < self.num_masks {
-                render_pass.set_stencil_reference(self.write_stencil_mask);
-            } else {
-                render_pass.set_stencil_reference(self.test_stencil_mask);
-            }
+        render_pass.set_pipeline(&pipeline);
+        render_pass.set_bind_group(0, bind_group, &offsets);
+        render_pass.set_vertex_buffer(0, &self.quad_vbo, 0, 0);
+        render_pass.set_index_buffer(&self.quad_ibo, 0, 0);
 
-            render_pass.draw_indexed(0..6, 0, 0..1);
-        }
+        render_pass.set_stencil_reference(self.num_masks_active);
+
+        render_pass.draw_indexed(0..6, 0, 0..1);
     }
 
     fn render_shape(&mut self, shape: ShapeHandle, transform: &Transform) {
-        let (swap_chain_output, encoder) =
-            if let Some((swap_chain_output, encoder)) = &mut self.current_frame {
-                (swap_chain_output, encoder)
-            } else {
-                return;
-            };
+        if self.mask_state == MaskState::WriteMask {
+            if let Some(draws) = self.masker_draws.last_mut() {
+                draws.push(MaskerDraw::Shape(shape, transform.clone()));
+            }
+        }
 
-        let mesh = &mut self.meshes[shape.0];
+        if self.current_frame.is_none() || !matches!(self.meshes.get(shape.0), Some(Some(_))) {
+            return;
+        }
 
         let world_matrix = [
             [transform.matrix.a, transform.matrix.b, 0.0, 0.0],
@@ -1308,48 +2624,88 @@ impl RenderBackend for WgpuRenderBackend {
             ],
         ];
 
-        if transform.color_transform != mesh.colors_last {
-            let colors_temp = create_buffer_with_data(
-                &self.device,
-                bytemuck::cast_slice(&[ColorAdjustments::from(transform.color_transform)]),
-                wgpu::BufferUsage::COPY_SRC,
-                create_debug_label!("Shape {} colors transfer buffer", mesh.shape_id),
-            );
-
-            encoder.copy_buffer_to_buffer(
-                &colors_temp,
-                0,
-                &mesh.colors_buffer,
-                0,
-                std::mem::size_of::<ColorAdjustments>() as u64,
-            );
-
-            mesh.colors_last = transform.color_transform;
+        // Bitmaps in atlas pages take their texture transforms from the uniforms of the frame,
+        // so that they can share the bind group of their page.
+        let atlas_draws: Vec<_> = match self.meshes.get(shape.0) {
+            Some(Some(mesh)) => mesh
+                .draws
+                .iter()
+                .map(|draw| match &draw.draw_type {
+                    DrawType::Bitmap { atlas, .. } => *atlas,
+                    _ => None,
+                })
+                .collect(),
+            _ => return,
+        };
+        if !self
+            .uniforms
+            .has_room_for(2 + atlas_draws.iter().flatten().count())
+        {
+            self.submit_partial_frame();
         }
-
-        let transforms_temp = create_buffer_with_data(
-            &self.device,
-            bytemuck::cast_slice(&[Transforms {
-                view_matrix: self.view_matrix,
-                world_matrix,
-            }]),
-            wgpu::BufferUsage::COPY_SRC,
-            create_debug_label!("Shape {} transforms transfer buffer", mesh.shape_id),
-        );
-
-        encoder.copy_buffer_to_buffer(
-            &transforms_temp,
-            0,
-            &mesh.transforms,
-            0,
-            std::mem::size_of::<Transforms>() as u64,
+        let [transforms_offset, colors_offset] = self.push_uniforms(
+            world_matrix,
+            self.descriptors
+                .color_adjustments(transform.color_transform),
         );
+        let shared_bind_groups: Vec<_> = atlas_draws
+            .iter()
+            .map(|atlas| match atlas {
+                Some((page, texture_transforms, is_smoothed)) => {
+                    let source = BitmapSource::Page(*page);
+                    let is_smoothed = *is_smoothed && self.bitmap_smoothing;
+                    self.prepare_bitmap_bind_group(source, is_smoothed);
+                    self.record_draw(Some(BindGroupKey::Bitmap(source, is_smoothed)));
+                    let texture_transforms_offset = self.uniforms.push(texture_transforms);
+                    Some(((source, is_smoothed), texture_transforms_offset))
+                }
+                None => {
+                    self.record_draw(None);
+                    None
+                }
+            })
+            .collect();
 
-        let (color_attachment, resolve_target) = if self.msaa_sample_count >= 2 {
-            (&self.frame_buffer_view, Some(&swap_chain_output.view))
-        } else {
-            (&swap_chain_output.view, None)
+        let (frame_output, encoder) = match &mut self.current_frame {
+            Some((frame_output, encoder)) => (frame_output, encoder),
+            None => return,
         };
+        let mesh = match self.meshes.get(shape.0) {
+            Some(Some(mesh)) => mesh,
+            _ => return,
+        };
+
+        // The pipelines have to outlive the render pass that uses them.
+        let descriptors = &self.descriptors;
+        let mask_state = self.mask_state;
+        let blend_mode = self.blend_mode;
+        let msaa_sample_count = self.msaa_sample_count;
+        let pipelines: Vec<_> = mesh
+            .draws
+            .iter()
+            .map(|draw| {
+                let shape_type = match &draw.draw_type {
+                    DrawType::Color => ShapeType::Color,
+                    DrawType::Gradient { .. } => ShapeType::Gradient,
+                    DrawType::Bitmap { .. } => ShapeType::Bitmap,
+                };
+                descriptors.pipelines.pipeline_for(
+                    &descriptors.device,
+                    shape_type,
+                    mask_state,
+                    blend_mode,
+                    draw.index_format,
+                    msaa_sample_count,
+                )
+            })
+            .collect();
+
+        let (color_attachment, resolve_target) = draw_target(
+            frame_output,
+            &self.frame_buffer_view,
+            &self.layers,
+            self.msaa_sample_count,
+        );
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
                 attachment: color_attachment,
@@ -1369,193 +2725,186 @@ impl RenderBackend for WgpuRenderBackend {
             }),
         });
 
-        for draw in &mesh.draws {
-            match &draw.draw_type {
-                DrawType::Color => {
-                    render_pass.set_pipeline(&self.pipelines.color.pipeline_for(
-                        self.num_masks,
-                        self.num_masks_active,
-                        self.test_stencil_mask,
-                        self.write_stencil_mask,
-                    ));
-                }
-                DrawType::Gradient { .. } => {
-                    render_pass.set_pipeline(&self.pipelines.gradient.pipeline_for(
-                        self.num_masks,
-                        self.num_masks_active,
-                        self.test_stencil_mask,
-                        self.write_stencil_mask,
-                    ));
-                }
-                DrawType::Bitmap { .. } => {
-                    render_pass.set_pipeline(&self.pipelines.bitmap.pipeline_for(
-                        self.num_masks,
-                        self.num_masks_active,
-                        self.test_stencil_mask,
-                        self.write_stencil_mask,
-                    ));
+        for ((draw, pipeline), shared_bind_group) in
+            mesh.draws.iter().zip(&pipelines).zip(&shared_bind_groups)
+        {
+            render_pass.set_pipeline(pipeline);
+            match (&draw.draw_type, shared_bind_group) {
+                (_, Some((key, texture_transforms_offset))) => render_pass.set_bind_group(
+                    0,
+                    &self.bitmap_bind_groups[key],
+                    &[transforms_offset, *texture_transforms_offset, colors_offset],
+                ),
+                (
+                    DrawType::Bitmap {
+                        unsmoothed_bind_group,
+                        ..
+                    },
+                    None,
+                ) => {
+                    let bind_group = match unsmoothed_bind_group {
+                        Some(bind_group) if !self.bitmap_smoothing => bind_group,
+                        _ => &draw.bind_group,
+                    };
+                    // The texture transforms are in a buffer of the draw's own.
+                    render_pass.set_bind_group(
+                        0,
+                        bind_group,
+                        &[transforms_offset, 0, colors_offset],
+                    );
                 }
+                _ => render_pass.set_bind_group(
+                    0,
+                    &draw.bind_group,
+                    &[transforms_offset, colors_offset],
+                ),
             }
+            render_pass.set_vertex_buffer(
+                0,
+                self.mesh_buffers.buffer(draw.vertices),
+                draw.vertices.offset,
+                draw.vertices.size,
+            );
+            render_pass.set_index_buffer(
+                self.mesh_buffers.buffer(draw.indices),
+                draw.indices.offset,
+                draw.indices.size,
+            );
 
-            render_pass.set_bind_group(0, &draw.bind_group, &[]);
-            render_pass.set_vertex_buffer(0, &draw.vertex_buffer, 0, 0);
-            render_pass.set_index_buffer(&draw.index_buffer, 0, 0);
-
-            if self.num_masks_active < self.num_masks {
-                render_pass.set_stencil_reference(self.write_stencil_mask);
-            } else {
-                render_pass.set_stencil_reference(self.test_stencil_mask);
-            }
+            render_pass.set_stencil_reference(self.num_masks_active);
 
             render_pass.draw_indexed(0..draw.index_count, 0, 0..1);
         }
     }
 
     fn end_frame(&mut self) {
+        self.layers.clear();
+        self.last_frame_stats = self.frame_stats;
         if let Some((_frame, encoder)) = self.current_frame.take() {
-            self.queue.submit(&[encoder.finish()]);
+            let mut command_buffers: Vec<_> = self
+                .uniforms
+                .upload(&self.descriptors.device)
+                .into_iter()
+                .collect();
+            command_buffers.push(encoder.finish());
+            self.submit_frame_commands(&command_buffers);
         }
+        self.mesh_buffers.recycle();
     }
 
     fn draw_letterbox(&mut self, letterbox: Letterbox) {
-        match letterbox {
-            Letterbox::None => {}
-            Letterbox::Letterbox(margin) => {
-                self.draw_rect(
-                    0.0,
-                    0.0,
-                    self.viewport_width,
-                    margin,
-                    Color {
-                        r: 0,
-                        g: 0,
-                        b: 0,
-                        a: 255,
-                    },
-                );
-                self.draw_rect(
-                    0.0,
-                    self.viewport_height - margin,
-                    self.viewport_width,
-                    margin,
-                    Color {
-                        r: 0,
-                        g: 0,
-                        b: 0,
-                        a: 255,
-                    },
-                );
-            }
-            Letterbox::Pillarbox(margin) => {
-                self.draw_rect(
-                    0.0,
-                    0.0,
-                    margin,
-                    self.viewport_height,
-                    Color {
-                        r: 0,
-                        g: 0,
-                        b: 0,
-                        a: 255,
-                    },
-                );
-                self.draw_rect(
-                    self.viewport_width - margin,
-                    0.0,
-                    margin,
-                    self.viewport_height,
-                    Color {
-                        r: 0,
-                        g: 0,
-                        b: 0,
-                        a: 255,
-                    },
-                );
-            }
+        for (x, y, width, height) in letterbox.bars(self.viewport_width, self.viewport_height) {
+            self.draw_rect(
+                x,
+                y,
+                width,
+                height,
+                Color {
+                    r: 0,
+                    g: 0,
+                    b: 0,
+                    a: 255,
+                },
+            );
         }
     }
 
     fn push_mask(&mut self) {
-        // Desktop draws the masker to the stencil buffer, one bit per mask.
-        // Masks-within-masks are handled as a bitmask.
-        // This does unfortunately mean we are limited in the number of masks at once (8 bits).
-        if self.next_stencil_mask >= 0x100 {
-            // If we've reached the limit of masks, clear the stencil buffer and start over.
-            // But this may not be correct if there is still a mask active (mask-within-mask).
-            if self.test_stencil_mask != 0 {
-                log::warn!(
-                    "Too many masks active for stencil buffer; possibly incorrect rendering"
-                );
-            }
-            self.next_stencil_mask = 1;
-            if let Some((swap_chain_output, encoder)) = &mut self.current_frame {
-                let (color_attachment, resolve_target) = if self.msaa_sample_count >= 2 {
-                    (&self.frame_buffer_view, Some(&swap_chain_output.view))
-                } else {
-                    (&swap_chain_output.view, None)
-                };
-                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                        attachment: color_attachment,
-                        load_op: wgpu::LoadOp::Load,
-                        store_op: wgpu::StoreOp::Store,
-                        clear_color: wgpu::Color::WHITE,
-                        resolve_target,
-                    }],
-                    depth_stencil_attachment: Some(
-                        wgpu::RenderPassDepthStencilAttachmentDescriptor {
-                            attachment: &self.depth_texture_view,
-                            depth_load_op: wgpu::LoadOp::Load,
-                            depth_store_op: wgpu::StoreOp::Store,
-                            stencil_load_op: wgpu::LoadOp::Clear,
-                            stencil_store_op: wgpu::StoreOp::Store,
-                            clear_depth: 0.0,
-                            clear_stencil: self.test_stencil_mask,
-                        },
-                    ),
-                });
-            }
+        // Masks are drawn to the stencil buffer, which counts how many masks cover each pixel.
+        // The masker increments it where all enclosing masks are active, and content is drawn
+        // where it equals the number of active masks.
+        if self.num_masks >= 0xff {
+            log::warn!("Too many nested masks for stencil buffer; possibly incorrect rendering");
         }
         self.num_masks += 1;
-        self.mask_stack
-            .push((self.write_stencil_mask, self.test_stencil_mask));
-        self.write_stencil_mask = self.next_stencil_mask;
-        self.test_stencil_mask |= self.next_stencil_mask;
-        self.next_stencil_mask <<= 1;
+        self.mask_state = MaskState::WriteMask;
+        self.masker_draws.push(Vec::new());
     }
 
     fn activate_mask(&mut self) {
         self.num_masks_active += 1;
+        self.mask_state = MaskState::DrawContent;
     }
 
     fn pop_mask(&mut self) {
-        if !self.mask_stack.is_empty() {
+        if let Some(draws) = self.masker_draws.pop() {
+            // Draw the masker again to decrement the stencil buffer where it was incremented.
+            self.mask_state = MaskState::ClearMask;
+            for draw in &draws {
+                match draw {
+                    MaskerDraw::Shape(shape, transform) => self.render_shape(*shape, transform),
+                    MaskerDraw::Bitmap(bitmap, transform, smoothing) => {
+                        self.render_bitmap(*bitmap, transform, *smoothing)
+                    }
+                }
+            }
+
             self.num_masks -= 1;
             self.num_masks_active -= 1;
-            let (write, test) = self.mask_stack.pop().unwrap();
-            self.write_stencil_mask = write;
-            self.test_stencil_mask = test;
+            self.mask_state = if self.num_masks_active < self.num_masks {
+                MaskState::WriteMask
+            } else {
+                MaskState::DrawContent
+            };
+        }
+    }
+
+    fn push_blend_mode(&mut self, blend_mode: swf::BlendMode) {
+        // Blend modes with a blend state pick the pipelines of each draw. The others draw
+        // normally into a layer, which is composited when the blend mode is popped.
+        let began_layer = match CompositeMode::from_blend_mode(blend_mode) {
+            Some(composite_mode) => self.begin_layer(composite_mode),
+            None => false,
+        };
+        self.blend_mode_stack.push((self.blend_mode, began_layer));
+        self.blend_mode = if began_layer {
+            BlendMode::Normal
+        } else {
+            blend_mode.into()
+        };
+    }
+
+    fn pop_blend_mode(&mut self) {
+        if let Some((blend_mode, began_layer)) = self.blend_mode_stack.pop() {
+            if began_layer {
+                self.composite_layer();
+            }
+            self.blend_mode = blend_mode;
+        }
+    }
+
+    fn set_bitmap_smoothing_enabled(&mut self, enabled: bool) {
+        self.bitmap_smoothing = enabled;
+    }
+
+    fn capabilities(&self) -> RenderCapabilities {
+        RenderCapabilities {
+            max_texture_size: self.descriptors.max_texture_size,
         }
     }
+
+    fn is_lost(&self) -> bool {
+        self.device_lost
+    }
 }
 
 fn create_quad_buffers(device: &wgpu::Device) -> (wgpu::Buffer, wgpu::Buffer, wgpu::Buffer) {
     let vertices = [
         GPUVertex {
             position: [0.0, 0.0],
-            color: [1.0, 1.0, 1.0, 1.0],
+            color: vertex_color(&WHITE_VERTEX_COLOR),
         },
         GPUVertex {
             position: [1.0, 0.0],
-            color: [1.0, 1.0, 1.0, 1.0],
+            color: vertex_color(&WHITE_VERTEX_COLOR),
         },
         GPUVertex {
             position: [1.0, 1.0],
-            color: [1.0, 1.0, 1.0, 1.0],
+            color: vertex_color(&WHITE_VERTEX_COLOR),
         },
         GPUVertex {
             position: [0.0, 1.0],
-            color: [1.0, 1.0, 1.0, 1.0],
+            color: vertex_color(&WHITE_VERTEX_COLOR),
         },
     ];
     let indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
@@ -1583,6 +2932,7 @@ fn create_quad_buffers(device: &wgpu::Device) -> (wgpu::Buffer, wgpu::Buffer, wg
                 [0.0, 0.0, 1.0, 0.0],
                 [0.0, 0.0, 0.0, 1.0],
             ],
+            uv_bounds: UNCLAMPED_UV_BOUNDS,
         }]),
         wgpu::BufferUsage::UNIFORM,
         create_debug_label!("Quad tex transforms"),
@@ -1593,13 +2943,47 @@ fn create_quad_buffers(device: &wgpu::Device) -> (wgpu::Buffer, wgpu::Buffer, wg
 
 #[derive(Debug)]
 struct Texture {
+    /// The size of the bitmap, which may be larger than the texture holding it.
     width: u32,
     height: u32,
-    texture: wgpu::Texture,
+    storage: TextureStorage,
+}
+
+#[derive(Debug)]
+enum TextureStorage {
+    /// A texture of its own.
+    Own {
+        texture: wgpu::Texture,
+
+        /// Whether the bitmap was shrunk to fit the maximum texture size.
+        is_downscaled: bool,
+    },
+
+    /// A slot in an atlas page.
+    ///
+    /// Repeating fills can't wrap around within a slot, so the pixels are kept to create a
+    /// texture of its own once such a fill uses the bitmap.
+    Atlas {
+        slot: AtlasSlot,
+        rgba: Vec<u8>,
+        own: Option<wgpu::Texture>,
+    },
+}
+
+/// The bounds of the texture coordinates of the centers of the edge pixels of a bitmap, from 0
+/// to 1 across it.
+fn edge_texel_centers(width: u32, height: u32) -> [f32; 4] {
+    let (half_texel_x, half_texel_y) = (0.5 / width as f32, 0.5 / height as f32);
+    [
+        half_texel_x,
+        half_texel_y,
+        1.0 - half_texel_x,
+        1.0 - half_texel_y,
+    ]
 }
 
 struct RuffleVertexCtor {
-    color: [f32; 4],
+    color: VertexColor,
 }
 
 impl FillVertexConstructor<GPUVertex> for RuffleVertexCtor {
@@ -1619,3 +3003,44 @@ impl StrokeVertexConstructor<GPUVertex> for RuffleVertexCtor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The channels that shaders read from a vertex color, from 0 to 1.
+    #[cfg(not(feature = "f32_vertex_colors"))]
+    fn shader_input([r, g, b, a]: VertexColor) -> [f32; 4] {
+        // `Uchar4Norm` divides each byte by 255.
+        let normalize = |channel: u8| f32::from(channel) / 255.0;
+        [normalize(r), normalize(g), normalize(b), normalize(a)]
+    }
+
+    #[cfg(feature = "f32_vertex_colors")]
+    fn shader_input(color: VertexColor) -> [f32; 4] {
+        color
+    }
+
+    #[test]
+    fn vertex_colors_are_read_the_same_in_either_format() {
+        for value in 0..=255 {
+            for &alpha in &[0, 128, 255] {
+                let color = Color {
+                    r: value,
+                    g: 255 - value,
+                    b: value / 2,
+                    a: alpha,
+                };
+                assert_eq!(
+                    shader_input(vertex_color(&color)),
+                    [
+                        f32::from(color.r) / 255.0,
+                        f32::from(color.g) / 255.0,
+                        f32::from(color.b) / 255.0,
+                        f32::from(color.a) / 255.0,
+                    ]
+                );
+            }
+        }
+    }
+}
@@ -1,38 +1,160 @@
-use crate::{Error, GPUVertex};
-use wgpu::vertex_attr_array;
+use crate::{negotiate_sample_count, Error, GPUVertex, VERTEX_COLOR_FORMAT};
+use ruffle_core::backend::render::swf;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
 
+/// The blending of the `Normal` blend mode. Every shader outputs premultiplied alpha, so that
+/// partly transparent draws leave the same alpha in a layer, whatever they fill with.
+const PREMULTIPLIED_ALPHA_BLEND: wgpu::BlendDescriptor = wgpu::BlendDescriptor {
+    src_factor: wgpu::BlendFactor::One,
+    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+    operation: wgpu::BlendOperation::Add,
+};
+
+/// The kinds of shape draws, which each have their own shaders and bind group layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShapeType {
+    Color,
+    Bitmap,
+    Gradient,
+}
+
+/// The blend modes that shapes can be drawn with.
+///
+/// Flash blend modes without an equivalent blend state are drawn as `Normal`, either into a
+/// layer that is then composited with a `CompositeMode`, or directly if they aren't supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    Normal,
+    Multiply,
+    Screen,
+    Add,
+    Subtract,
+    Lighten,
+    Darken,
+}
+
+impl From<swf::BlendMode> for BlendMode {
+    fn from(blend_mode: swf::BlendMode) -> Self {
+        match blend_mode {
+            swf::BlendMode::Multiply => BlendMode::Multiply,
+            swf::BlendMode::Screen => BlendMode::Screen,
+            swf::BlendMode::Add => BlendMode::Add,
+            swf::BlendMode::Subtract => BlendMode::Subtract,
+            swf::BlendMode::Lighten => BlendMode::Lighten,
+            swf::BlendMode::Darken => BlendMode::Darken,
+            _ => BlendMode::Normal,
+        }
+    }
+}
+
+/// The blend modes that need to read the colors they are drawn over, which blend states
+/// can't do.
+///
+/// Content with these modes is drawn into a layer, which is composited onto the content
+/// below it by `blend.frag` once it is complete. The values match the numbers of the modes
+/// in the shader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompositeMode {
+    Difference = 0,
+    Invert = 1,
+    Overlay = 2,
+    HardLight = 3,
+}
+
+impl CompositeMode {
+    pub fn from_blend_mode(blend_mode: swf::BlendMode) -> Option<Self> {
+        match blend_mode {
+            swf::BlendMode::Difference => Some(CompositeMode::Difference),
+            swf::BlendMode::Invert => Some(CompositeMode::Invert),
+            swf::BlendMode::Overlay => Some(CompositeMode::Overlay),
+            swf::BlendMode::HardLight => Some(CompositeMode::HardLight),
+            _ => None,
+        }
+    }
+}
+
+/// How a draw uses the stencil buffer, which holds the number of masks covering each pixel.
+///
+/// Entering a mask increments the stencil buffer where its masker is drawn, and leaving it
+/// decrements it again, so the stencil reference of every draw is the number of active masks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MaskState {
+    /// Draws color where the stencil buffer equals the reference.
+    DrawContent,
+
+    /// Increments the stencil buffer where it equals the reference, without drawing any color.
+    WriteMask,
+
+    /// Decrements the stencil buffer where it equals the reference, without drawing any color.
+    ClearMask,
+}
+
+/// Everything needed to build the pipelines for one kind of shape draw.
 #[derive(Debug)]
 pub struct ShapePipeline {
-    pub write_mask_pipelines: Vec<wgpu::RenderPipeline>,
-    pub read_mask_pipelines: Vec<wgpu::RenderPipeline>,
     pub bind_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    vertex_shader: Rc<wgpu::ShaderModule>,
+    fragment_shader: Rc<wgpu::ShaderModule>,
+    blend: wgpu::BlendDescriptor,
+}
+
+/// Identifies a pipeline by the kind of shape it draws, how it uses the stencil buffer, how
+/// it blends, the format of the indices it draws with and the number of samples per pixel of
+/// the targets it draws into.
+///
+/// Pipelines that write to or clear a mask have a `Normal` blend mode, as they don't draw any
+/// color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct PipelineKey {
+    shape_type: ShapeType,
+    mask_state: MaskState,
+    blend_mode: BlendMode,
+    index_format: wgpu::IndexFormat,
+    msaa_sample_count: u32,
 }
 
+/// The render pipelines for drawing shapes.
+///
+/// There is a pipeline for every combination of shape type, mask state, blend mode, index
+/// format and sample count. Most movies only use a few of them, so each is built the first time it is needed
+/// and cached.
 #[derive(Debug)]
 pub struct Pipelines {
     pub color: ShapePipeline,
     pub bitmap: ShapePipeline,
     pub gradient: ShapePipeline,
-}
 
-impl ShapePipeline {
-    pub fn pipeline_for(
-        &self,
-        num_masks: u32,
-        num_masks_active: u32,
-        read_mask: u32,
-        write_mask: u32,
-    ) -> &wgpu::RenderPipeline {
-        if num_masks_active < num_masks {
-            &self.write_mask_pipelines[write_mask.trailing_zeros() as usize]
-        } else {
-            &self.read_mask_pipelines[read_mask as usize]
-        }
-    }
+    /// Composites layers onto their backdrop. Its pipelines only differ in their sample count.
+    pub composite: ShapePipeline,
+    msaa_sample_count: u32,
+    format: wgpu::TextureFormat,
+
+    /// The pipelines built so far.
+    ///
+    /// `Pipelines` is shared through `Rc<Descriptors>` by every renderer and is read while
+    /// a render pass borrows other parts of the backend, so the cache is filled through a
+    /// `RefCell` rather than `&mut self`. The cost is a runtime borrow check per lookup, and
+    /// pipelines are handed out as `Rc`s so that no borrow outlives `pipeline_for`.
+    cache: RefCell<HashMap<PipelineKey, Rc<wgpu::RenderPipeline>>>,
+    composite_cache: RefCell<HashMap<u32, Rc<wgpu::RenderPipeline>>>,
+    num_created: Cell<usize>,
 }
 
 impl Pipelines {
-    pub fn new(device: &wgpu::Device, msaa_sample_count: u32) -> Result<Self, Error> {
+    /// Prepares the shaders for drawing into textures of the given format, by default with
+    /// `msaa_sample_count` samples per pixel.
+    ///
+    /// If the device doesn't support that count, the nearest supported count is used instead.
+    pub fn new(
+        device: &wgpu::Device,
+        msaa_sample_count: u32,
+        format: wgpu::TextureFormat,
+    ) -> Result<Self, Error> {
+        let msaa_sample_count = negotiate_sample_count(device, format, msaa_sample_count)?;
+
         let color_vs_bytes = include_bytes!("../shaders/color.vert.spv");
         let color_vs = device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(
             &color_vs_bytes[..],
@@ -53,18 +175,270 @@ impl Pipelines {
         let bitmap_fs = device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(
             &bitmap_fs_bytes[..],
         ))?);
+        let blend_fs_bytes = include_bytes!("../shaders/blend.frag.spv");
+        let blend_fs = device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(
+            &blend_fs_bytes[..],
+        ))?);
+
+        let texture_vs = Rc::new(texture_vs);
 
         Ok(Self {
-            color: create_color_pipelines(&device, &color_vs, &color_fs, msaa_sample_count),
-            bitmap: create_bitmap_pipeline(&device, &texture_vs, &bitmap_fs, msaa_sample_count),
-            gradient: create_gradient_pipeline(
-                &device,
-                &texture_vs,
-                &gradient_fs,
-                msaa_sample_count,
+            color: ShapePipeline::new(
+                device,
+                create_color_bind_layout(device),
+                Rc::new(color_vs),
+                Rc::new(color_fs),
+                PREMULTIPLIED_ALPHA_BLEND,
+            ),
+            bitmap: ShapePipeline::new(
+                device,
+                create_bitmap_bind_layout(device),
+                texture_vs.clone(),
+                Rc::new(bitmap_fs),
+                PREMULTIPLIED_ALPHA_BLEND,
+            ),
+            gradient: ShapePipeline::new(
+                device,
+                create_gradient_bind_layout(device),
+                texture_vs.clone(),
+                Rc::new(gradient_fs),
+                PREMULTIPLIED_ALPHA_BLEND,
             ),
+            // The composite already includes the backdrop, so it replaces it.
+            composite: ShapePipeline::new(
+                device,
+                create_composite_bind_layout(device),
+                texture_vs,
+                Rc::new(blend_fs),
+                wgpu::BlendDescriptor::REPLACE,
+            ),
+            msaa_sample_count,
+            format,
+            cache: RefCell::new(HashMap::new()),
+            composite_cache: RefCell::new(HashMap::new()),
+            num_created: Cell::new(0),
         })
     }
+
+    /// Returns the pipeline to draw a shape with the given mask state, building it if needed.
+    ///
+    /// `blend_mode` only applies to pipelines that draw content. `index_format` must match the
+    /// index buffer of the draw, and `msaa_sample_count` the targets it draws into.
+    pub fn pipeline_for(
+        &self,
+        device: &wgpu::Device,
+        shape_type: ShapeType,
+        mask_state: MaskState,
+        blend_mode: BlendMode,
+        index_format: wgpu::IndexFormat,
+        msaa_sample_count: u32,
+    ) -> Rc<wgpu::RenderPipeline> {
+        let blend_mode = match mask_state {
+            MaskState::DrawContent => blend_mode,
+            MaskState::WriteMask | MaskState::ClearMask => BlendMode::Normal,
+        };
+        let key = PipelineKey {
+            shape_type,
+            mask_state,
+            blend_mode,
+            index_format,
+            msaa_sample_count,
+        };
+
+        self.cache
+            .borrow_mut()
+            .entry(key)
+            .or_insert_with(|| {
+                self.num_created.set(self.num_created.get() + 1);
+                log::debug!("Creating render pipeline for {:?}", key);
+                let shape_pipeline = match shape_type {
+                    ShapeType::Color => &self.color,
+                    ShapeType::Bitmap => &self.bitmap,
+                    ShapeType::Gradient => &self.gradient,
+                };
+                Rc::new(shape_pipeline.create_pipeline(
+                    device,
+                    key.mask_state,
+                    key.blend_mode,
+                    key.index_format,
+                    key.msaa_sample_count,
+                    self.format,
+                ))
+            })
+            .clone()
+    }
+
+    /// Returns the pipeline to composite a layer onto targets with the given number of samples
+    /// per pixel, building it if needed.
+    pub fn composite_pipeline_for(
+        &self,
+        device: &wgpu::Device,
+        msaa_sample_count: u32,
+    ) -> Rc<wgpu::RenderPipeline> {
+        self.composite_cache
+            .borrow_mut()
+            .entry(msaa_sample_count)
+            .or_insert_with(|| {
+                self.num_created.set(self.num_created.get() + 1);
+                log::debug!(
+                    "Creating composite pipeline with {} samples per pixel",
+                    msaa_sample_count
+                );
+                Rc::new(self.composite.create_composite_pipeline(
+                    device,
+                    msaa_sample_count,
+                    self.format,
+                ))
+            })
+            .clone()
+    }
+
+    /// The number of samples per pixel that renderers draw with unless they are told otherwise.
+    pub fn msaa_sample_count(&self) -> u32 {
+        self.msaa_sample_count
+    }
+
+    /// The number of pipelines built so far.
+    pub fn num_created(&self) -> usize {
+        self.num_created.get()
+    }
+}
+
+impl ShapePipeline {
+    fn new(
+        device: &wgpu::Device,
+        bind_layout: wgpu::BindGroupLayout,
+        vertex_shader: Rc<wgpu::ShaderModule>,
+        fragment_shader: Rc<wgpu::ShaderModule>,
+        blend: wgpu::BlendDescriptor,
+    ) -> Self {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&bind_layout],
+        });
+
+        Self {
+            bind_layout,
+            pipeline_layout,
+            vertex_shader,
+            fragment_shader,
+            blend,
+        }
+    }
+
+    /// Builds a pipeline that draws, or updates the stencil buffer, where the stencil buffer
+    /// equals the stencil reference.
+    fn create_pipeline(
+        &self,
+        device: &wgpu::Device,
+        mask_state: MaskState,
+        blend_mode: BlendMode,
+        index_format: wgpu::IndexFormat,
+        msaa_sample_count: u32,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        let color_blend = match blend_mode {
+            BlendMode::Normal => self.blend.clone(),
+            BlendMode::Multiply => wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::DstColor,
+                dst_factor: wgpu::BlendFactor::Zero,
+                operation: wgpu::BlendOperation::Add,
+            },
+            BlendMode::Screen => wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcColor,
+                operation: wgpu::BlendOperation::Add,
+            },
+            BlendMode::Add => wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            BlendMode::Subtract => wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::ReverseSubtract,
+            },
+            BlendMode::Lighten => wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Max,
+            },
+            BlendMode::Darken => wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Min,
+            },
+        };
+
+        // Only comparing with the reference keeps overlapping triangles of a masker from
+        // updating the same pixel twice.
+        let (pass_op, color_write_mask) = match mask_state {
+            MaskState::DrawContent => (wgpu::StencilOperation::Keep, wgpu::ColorWrite::ALL),
+            MaskState::WriteMask => (
+                wgpu::StencilOperation::IncrementClamp,
+                wgpu::ColorWrite::empty(),
+            ),
+            MaskState::ClearMask => (
+                wgpu::StencilOperation::DecrementClamp,
+                wgpu::ColorWrite::empty(),
+            ),
+        };
+        let stencil_face = wgpu::StencilStateFaceDescriptor {
+            compare: wgpu::CompareFunction::Equal,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op,
+        };
+
+        device.create_render_pipeline(&create_pipeline_descriptor(
+            &self.vertex_shader,
+            &self.fragment_shader,
+            &self.pipeline_layout,
+            Some(wgpu::DepthStencilStateDescriptor {
+                format: wgpu::TextureFormat::Depth24PlusStencil8,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil_front: stencil_face.clone(),
+                stencil_back: stencil_face,
+                stencil_read_mask: 0xff,
+                stencil_write_mask: 0xff,
+            }),
+            &[wgpu::ColorStateDescriptor {
+                format,
+                color_blend,
+                alpha_blend: self.blend.clone(),
+                write_mask: color_write_mask,
+            }],
+            index_format,
+            msaa_sample_count,
+        ))
+    }
+
+    /// Builds a pipeline that composites a layer over the whole target.
+    ///
+    /// The layer was already clipped by any active masks when it was drawn, so the pipeline
+    /// doesn't use the stencil buffer.
+    fn create_composite_pipeline(
+        &self,
+        device: &wgpu::Device,
+        msaa_sample_count: u32,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&create_pipeline_descriptor(
+            &self.vertex_shader,
+            &self.fragment_shader,
+            &self.pipeline_layout,
+            None,
+            &[wgpu::ColorStateDescriptor {
+                format,
+                color_blend: self.blend.clone(),
+                alpha_blend: self.blend.clone(),
+                write_mask: wgpu::ColorWrite::ALL,
+            }],
+            wgpu::IndexFormat::Uint16,
+            msaa_sample_count,
+        ))
+    }
 }
 
 fn create_pipeline_descriptor<'a>(
@@ -73,6 +447,7 @@ fn create_pipeline_descriptor<'a>(
     pipeline_layout: &'a wgpu::PipelineLayout,
     depth_stencil_state: Option<wgpu::DepthStencilStateDescriptor>,
     color_states: &'a [wgpu::ColorStateDescriptor],
+    index_format: wgpu::IndexFormat,
     msaa_sample_count: u32,
 ) -> wgpu::RenderPipelineDescriptor<'a> {
     wgpu::RenderPipelineDescriptor {
@@ -99,163 +474,64 @@ fn create_pipeline_descriptor<'a>(
         sample_mask: !0,
         alpha_to_coverage_enabled: false,
         vertex_state: wgpu::VertexStateDescriptor {
-            index_format: wgpu::IndexFormat::Uint16,
+            index_format,
             vertex_buffers: &[wgpu::VertexBufferDescriptor {
                 stride: std::mem::size_of::<GPUVertex>() as u64,
                 step_mode: wgpu::InputStepMode::Vertex,
-                attributes: &vertex_attr_array![
-                    0 => Float2,
-                    1 => Float4
+                attributes: &[
+                    wgpu::VertexAttributeDescriptor {
+                        offset: 0,
+                        format: wgpu::VertexFormat::Float2,
+                        shader_location: 0,
+                    },
+                    wgpu::VertexAttributeDescriptor {
+                        offset: std::mem::size_of::<[f32; 2]>() as u64,
+                        format: VERTEX_COLOR_FORMAT,
+                        shader_location: 1,
+                    },
                 ],
             }],
         },
     }
 }
 
-fn create_color_pipelines(
-    device: &wgpu::Device,
-    vertex_shader: &wgpu::ShaderModule,
-    fragment_shader: &wgpu::ShaderModule,
-    msaa_sample_count: u32,
-) -> ShapePipeline {
+fn create_color_bind_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
     let label = create_debug_label!("Color shape bind group");
-    let bind_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         bindings: &[
             wgpu::BindGroupLayoutEntry {
                 binding: 0,
                 visibility: wgpu::ShaderStage::VERTEX,
-                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                ty: wgpu::BindingType::UniformBuffer { dynamic: true },
             },
             wgpu::BindGroupLayoutEntry {
                 binding: 1,
                 visibility: wgpu::ShaderStage::VERTEX,
-                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                ty: wgpu::BindingType::UniformBuffer { dynamic: true },
             },
         ],
         label: label.as_deref(),
-    });
-
-    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        bind_group_layouts: &[&bind_layout],
-    });
-
-    let mut write_mask_pipelines = Vec::new();
-    let mut read_mask_pipelines = Vec::new();
-
-    for i in 0..8 {
-        write_mask_pipelines.push(device.create_render_pipeline(&create_pipeline_descriptor(
-            vertex_shader,
-            fragment_shader,
-            &pipeline_layout,
-            Some(wgpu::DepthStencilStateDescriptor {
-                format: wgpu::TextureFormat::Depth24PlusStencil8,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Always,
-                stencil_front: wgpu::StencilStateFaceDescriptor {
-                    compare: wgpu::CompareFunction::Always,
-                    fail_op: wgpu::StencilOperation::Keep,
-                    depth_fail_op: wgpu::StencilOperation::Keep,
-                    pass_op: wgpu::StencilOperation::Replace,
-                },
-                stencil_back: wgpu::StencilStateFaceDescriptor {
-                    compare: wgpu::CompareFunction::Always,
-                    fail_op: wgpu::StencilOperation::Keep,
-                    depth_fail_op: wgpu::StencilOperation::Keep,
-                    pass_op: wgpu::StencilOperation::Replace,
-                },
-                stencil_read_mask: 0,
-                stencil_write_mask: 1 << i,
-            }),
-            &[wgpu::ColorStateDescriptor {
-                format: wgpu::TextureFormat::Bgra8Unorm,
-                color_blend: wgpu::BlendDescriptor {
-                    src_factor: wgpu::BlendFactor::SrcAlpha,
-                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                    operation: wgpu::BlendOperation::Add,
-                },
-                alpha_blend: wgpu::BlendDescriptor {
-                    src_factor: wgpu::BlendFactor::SrcAlpha,
-                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                    operation: wgpu::BlendOperation::Add,
-                },
-                write_mask: wgpu::ColorWrite::empty(),
-            }],
-            msaa_sample_count,
-        )));
-    }
-
-    for i in 0..256 {
-        read_mask_pipelines.push(device.create_render_pipeline(&create_pipeline_descriptor(
-            vertex_shader,
-            fragment_shader,
-            &pipeline_layout,
-            Some(wgpu::DepthStencilStateDescriptor {
-                format: wgpu::TextureFormat::Depth24PlusStencil8,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Always,
-                stencil_front: wgpu::StencilStateFaceDescriptor {
-                    compare: wgpu::CompareFunction::Equal,
-                    fail_op: wgpu::StencilOperation::Keep,
-                    depth_fail_op: wgpu::StencilOperation::Keep,
-                    pass_op: wgpu::StencilOperation::Keep,
-                },
-                stencil_back: wgpu::StencilStateFaceDescriptor {
-                    compare: wgpu::CompareFunction::Equal,
-                    fail_op: wgpu::StencilOperation::Keep,
-                    depth_fail_op: wgpu::StencilOperation::Keep,
-                    pass_op: wgpu::StencilOperation::Keep,
-                },
-                stencil_read_mask: i,
-                stencil_write_mask: 0,
-            }),
-            &[wgpu::ColorStateDescriptor {
-                format: wgpu::TextureFormat::Bgra8Unorm,
-                color_blend: wgpu::BlendDescriptor {
-                    src_factor: wgpu::BlendFactor::SrcAlpha,
-                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                    operation: wgpu::BlendOperation::Add,
-                },
-                alpha_blend: wgpu::BlendDescriptor {
-                    src_factor: wgpu::BlendFactor::SrcAlpha,
-                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                    operation: wgpu::BlendOperation::Add,
-                },
-                write_mask: wgpu::ColorWrite::ALL,
-            }],
-            msaa_sample_count,
-        )));
-    }
-
-    ShapePipeline {
-        write_mask_pipelines,
-        read_mask_pipelines,
-        bind_layout,
-    }
+    })
 }
 
-fn create_bitmap_pipeline(
-    device: &wgpu::Device,
-    vertex_shader: &wgpu::ShaderModule,
-    fragment_shader: &wgpu::ShaderModule,
-    msaa_sample_count: u32,
-) -> ShapePipeline {
+fn create_bitmap_bind_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
     let label = create_debug_label!("Bitmap shape bind group");
-    let bind_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         bindings: &[
             wgpu::BindGroupLayoutEntry {
                 binding: 0,
                 visibility: wgpu::ShaderStage::VERTEX,
-                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                ty: wgpu::BindingType::UniformBuffer { dynamic: true },
             },
             wgpu::BindGroupLayoutEntry {
                 binding: 1,
-                visibility: wgpu::ShaderStage::VERTEX,
-                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: true },
             },
             wgpu::BindGroupLayoutEntry {
                 binding: 2,
                 visibility: wgpu::ShaderStage::FRAGMENT,
-                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                ty: wgpu::BindingType::UniformBuffer { dynamic: true },
             },
             wgpu::BindGroupLayoutEntry {
                 binding: 3,
@@ -273,119 +549,17 @@ fn create_bitmap_pipeline(
             },
         ],
         label: label.as_deref(),
-    });
-
-    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        bind_group_layouts: &[&bind_layout],
-    });
-
-    let mut write_mask_pipelines = Vec::new();
-    let mut read_mask_pipelines = Vec::new();
-
-    for i in 0..8 {
-        write_mask_pipelines.push(device.create_render_pipeline(&create_pipeline_descriptor(
-            vertex_shader,
-            fragment_shader,
-            &pipeline_layout,
-            Some(wgpu::DepthStencilStateDescriptor {
-                format: wgpu::TextureFormat::Depth24PlusStencil8,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Always,
-                stencil_front: wgpu::StencilStateFaceDescriptor {
-                    compare: wgpu::CompareFunction::Always,
-                    fail_op: wgpu::StencilOperation::Keep,
-                    depth_fail_op: wgpu::StencilOperation::Keep,
-                    pass_op: wgpu::StencilOperation::Replace,
-                },
-                stencil_back: wgpu::StencilStateFaceDescriptor {
-                    compare: wgpu::CompareFunction::Always,
-                    fail_op: wgpu::StencilOperation::Keep,
-                    depth_fail_op: wgpu::StencilOperation::Keep,
-                    pass_op: wgpu::StencilOperation::Replace,
-                },
-                stencil_read_mask: 0,
-                stencil_write_mask: 1 << i,
-            }),
-            &[wgpu::ColorStateDescriptor {
-                format: wgpu::TextureFormat::Bgra8Unorm,
-                color_blend: wgpu::BlendDescriptor {
-                    src_factor: wgpu::BlendFactor::SrcAlpha,
-                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                    operation: wgpu::BlendOperation::Add,
-                },
-                alpha_blend: wgpu::BlendDescriptor {
-                    src_factor: wgpu::BlendFactor::SrcAlpha,
-                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                    operation: wgpu::BlendOperation::Add,
-                },
-                write_mask: wgpu::ColorWrite::empty(),
-            }],
-            msaa_sample_count,
-        )));
-    }
-
-    for i in 0..256 {
-        read_mask_pipelines.push(device.create_render_pipeline(&create_pipeline_descriptor(
-            vertex_shader,
-            fragment_shader,
-            &pipeline_layout,
-            Some(wgpu::DepthStencilStateDescriptor {
-                format: wgpu::TextureFormat::Depth24PlusStencil8,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Always,
-                stencil_front: wgpu::StencilStateFaceDescriptor {
-                    compare: wgpu::CompareFunction::Equal,
-                    fail_op: wgpu::StencilOperation::Keep,
-                    depth_fail_op: wgpu::StencilOperation::Keep,
-                    pass_op: wgpu::StencilOperation::Keep,
-                },
-                stencil_back: wgpu::StencilStateFaceDescriptor {
-                    compare: wgpu::CompareFunction::Equal,
-                    fail_op: wgpu::StencilOperation::Keep,
-                    depth_fail_op: wgpu::StencilOperation::Keep,
-                    pass_op: wgpu::StencilOperation::Keep,
-                },
-                stencil_read_mask: i,
-                stencil_write_mask: 0,
-            }),
-            &[wgpu::ColorStateDescriptor {
-                format: wgpu::TextureFormat::Bgra8Unorm,
-                color_blend: wgpu::BlendDescriptor {
-                    src_factor: wgpu::BlendFactor::One,
-                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                    operation: wgpu::BlendOperation::Add,
-                },
-                alpha_blend: wgpu::BlendDescriptor {
-                    src_factor: wgpu::BlendFactor::SrcAlpha,
-                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                    operation: wgpu::BlendOperation::Add,
-                },
-                write_mask: wgpu::ColorWrite::ALL,
-            }],
-            msaa_sample_count,
-        )));
-    }
-
-    ShapePipeline {
-        write_mask_pipelines,
-        read_mask_pipelines,
-        bind_layout,
-    }
+    })
 }
 
-fn create_gradient_pipeline(
-    device: &wgpu::Device,
-    vertex_shader: &wgpu::ShaderModule,
-    fragment_shader: &wgpu::ShaderModule,
-    msaa_sample_count: u32,
-) -> ShapePipeline {
+fn create_gradient_bind_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
     let label = create_debug_label!("Gradient shape bind group");
-    let bind_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         bindings: &[
             wgpu::BindGroupLayoutEntry {
                 binding: 0,
                 visibility: wgpu::ShaderStage::VERTEX,
-                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                ty: wgpu::BindingType::UniformBuffer { dynamic: true },
             },
             wgpu::BindGroupLayoutEntry {
                 binding: 1,
@@ -395,7 +569,7 @@ fn create_gradient_pipeline(
             wgpu::BindGroupLayoutEntry {
                 binding: 2,
                 visibility: wgpu::ShaderStage::FRAGMENT,
-                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                ty: wgpu::BindingType::UniformBuffer { dynamic: true },
             },
             wgpu::BindGroupLayoutEntry {
                 binding: 3,
@@ -404,102 +578,52 @@ fn create_gradient_pipeline(
             },
         ],
         label: label.as_deref(),
-    });
-
-    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        bind_group_layouts: &[&bind_layout],
-    });
-
-    let mut write_mask_pipelines = Vec::new();
-    let mut read_mask_pipelines = Vec::new();
-
-    for i in 0..8 {
-        write_mask_pipelines.push(device.create_render_pipeline(&create_pipeline_descriptor(
-            vertex_shader,
-            fragment_shader,
-            &pipeline_layout,
-            Some(wgpu::DepthStencilStateDescriptor {
-                format: wgpu::TextureFormat::Depth24PlusStencil8,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Always,
-                stencil_front: wgpu::StencilStateFaceDescriptor {
-                    compare: wgpu::CompareFunction::Always,
-                    fail_op: wgpu::StencilOperation::Keep,
-                    depth_fail_op: wgpu::StencilOperation::Keep,
-                    pass_op: wgpu::StencilOperation::Replace,
-                },
-                stencil_back: wgpu::StencilStateFaceDescriptor {
-                    compare: wgpu::CompareFunction::Always,
-                    fail_op: wgpu::StencilOperation::Keep,
-                    depth_fail_op: wgpu::StencilOperation::Keep,
-                    pass_op: wgpu::StencilOperation::Replace,
-                },
-                stencil_read_mask: 0,
-                stencil_write_mask: 1 << i,
-            }),
-            &[wgpu::ColorStateDescriptor {
-                format: wgpu::TextureFormat::Bgra8Unorm,
-                color_blend: wgpu::BlendDescriptor {
-                    src_factor: wgpu::BlendFactor::SrcAlpha,
-                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                    operation: wgpu::BlendOperation::Add,
-                },
-                alpha_blend: wgpu::BlendDescriptor {
-                    src_factor: wgpu::BlendFactor::SrcAlpha,
-                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                    operation: wgpu::BlendOperation::Add,
-                },
-                write_mask: wgpu::ColorWrite::empty(),
-            }],
-            msaa_sample_count,
-        )));
-    }
+    })
+}
 
-    for i in 0..256 {
-        read_mask_pipelines.push(device.create_render_pipeline(&create_pipeline_descriptor(
-            vertex_shader,
-            fragment_shader,
-            &pipeline_layout,
-            Some(wgpu::DepthStencilStateDescriptor {
-                format: wgpu::TextureFormat::Depth24PlusStencil8,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Always,
-                stencil_front: wgpu::StencilStateFaceDescriptor {
-                    compare: wgpu::CompareFunction::Equal,
-                    fail_op: wgpu::StencilOperation::Keep,
-                    depth_fail_op: wgpu::StencilOperation::Keep,
-                    pass_op: wgpu::StencilOperation::Keep,
-                },
-                stencil_back: wgpu::StencilStateFaceDescriptor {
-                    compare: wgpu::CompareFunction::Equal,
-                    fail_op: wgpu::StencilOperation::Keep,
-                    depth_fail_op: wgpu::StencilOperation::Keep,
-                    pass_op: wgpu::StencilOperation::Keep,
-                },
-                stencil_read_mask: i,
-                stencil_write_mask: 0,
-            }),
-            &[wgpu::ColorStateDescriptor {
-                format: wgpu::TextureFormat::Bgra8Unorm,
-                color_blend: wgpu::BlendDescriptor {
-                    src_factor: wgpu::BlendFactor::SrcAlpha,
-                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                    operation: wgpu::BlendOperation::Add,
+fn create_composite_bind_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    let label = create_debug_label!("Composite bind group");
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        bindings: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: true },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: true },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: false,
+                    component_type: wgpu::TextureComponentType::Float,
+                    dimension: wgpu::TextureViewDimension::D2,
                 },
-                alpha_blend: wgpu::BlendDescriptor {
-                    src_factor: wgpu::BlendFactor::SrcAlpha,
-                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                    operation: wgpu::BlendOperation::Add,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: false,
+                    component_type: wgpu::TextureComponentType::Float,
+                    dimension: wgpu::TextureViewDimension::D2,
                 },
-                write_mask: wgpu::ColorWrite::ALL,
-            }],
-            msaa_sample_count,
-        )));
-    }
-
-    ShapePipeline {
-        write_mask_pipelines,
-        read_mask_pipelines,
-        bind_layout,
-    }
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 5,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Sampler { comparison: false },
+            },
+        ],
+        label: label.as_deref(),
+    })
 }
@@ -0,0 +1,77 @@
+use std::cell::Cell;
+
+/// The samplers that bitmaps are drawn with.
+///
+/// Bitmap fills only differ in whether they are smoothed and whether they repeat, so the four
+/// possible samplers are created once and shared by every draw, rather than creating one for
+/// each bitmap fill of each shape.
+#[derive(Debug)]
+pub struct Samplers {
+    smoothed_clamped: wgpu::Sampler,
+    smoothed_repeating: wgpu::Sampler,
+    nearest_clamped: wgpu::Sampler,
+    nearest_repeating: wgpu::Sampler,
+    num_created: Cell<usize>,
+}
+
+impl Samplers {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let num_created = Cell::new(0);
+        let create = |is_smoothed, is_repeating| {
+            num_created.set(num_created.get() + 1);
+            create_sampler(device, is_smoothed, is_repeating)
+        };
+        Self {
+            smoothed_clamped: create(true, false),
+            smoothed_repeating: create(true, true),
+            nearest_clamped: create(false, false),
+            nearest_repeating: create(false, true),
+            num_created,
+        }
+    }
+
+    /// The sampler for a bitmap fill.
+    pub fn get(&self, is_smoothed: bool, is_repeating: bool) -> &wgpu::Sampler {
+        match (is_smoothed, is_repeating) {
+            (true, false) => &self.smoothed_clamped,
+            (true, true) => &self.smoothed_repeating,
+            (false, false) => &self.nearest_clamped,
+            (false, true) => &self.nearest_repeating,
+        }
+    }
+
+    /// The number of samplers created, which doesn't grow with the number of bitmaps drawn.
+    pub fn num_created(&self) -> usize {
+        self.num_created.get()
+    }
+}
+
+fn create_sampler(device: &wgpu::Device, is_smoothed: bool, is_repeating: bool) -> wgpu::Sampler {
+    let address_mode = if is_repeating {
+        wgpu::AddressMode::Repeat
+    } else {
+        wgpu::AddressMode::ClampToEdge
+    };
+
+    // Smoothed bitmaps blend between mip levels when scaled down, while unsmoothed ones
+    // always sample the nearest pixel of the full-size level. The level of detail is clamped
+    // to the levels that each texture actually has, so one sampler serves textures with and
+    // without mipmaps.
+    let (filter, lod_max_clamp) = if is_smoothed {
+        (wgpu::FilterMode::Linear, 100.0)
+    } else {
+        (wgpu::FilterMode::Nearest, 0.0)
+    };
+
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: address_mode,
+        address_mode_v: address_mode,
+        address_mode_w: address_mode,
+        mag_filter: filter,
+        min_filter: filter,
+        mipmap_filter: filter,
+        lod_min_clamp: 0.0,
+        lod_max_clamp,
+        compare: wgpu::CompareFunction::Undefined,
+    })
+}
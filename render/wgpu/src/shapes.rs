@@ -1,10 +1,15 @@
+use crate::buffers::BufferSlice;
 use crate::pipelines::Pipelines;
-use crate::utils::create_buffer_with_data;
-use crate::{ColorAdjustments, TextureTransforms, Transforms};
+use crate::samplers::Samplers;
+use crate::utils::{create_buffer_with_data, f32_to_f16, MAX_GRADIENT_COLORS};
+use crate::{ColorAdjustments, TextureTransforms, Transforms, UNCLAMPED_UV_BOUNDS};
 use bytemuck::{Pod, Zeroable};
 use ruffle_core::backend::audio::swf::CharacterId;
-use ruffle_core::color_transform::ColorTransform;
 
+/// The `Gradient` uniform block of the gradient shader.
+///
+/// std140 aligns every array element to 16 bytes, so the ratios are packed four to a `vec4`,
+/// and the colors are packed as half floats, two to a `uvec4`.
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct GradientUniforms {
@@ -12,9 +17,44 @@ pub struct GradientUniforms {
     pub num_colors: u32,
     pub repeat_mode: i32,
     pub focal_point: f32,
-    // TODO: pack this more efficiently. Alignment forces a float[16] to be aligned as a float4[16].
-    pub ratios: [[f32; 4]; 16],
-    pub colors: [[f32; 4]; 16],
+    pub ratios: [f32; MAX_GRADIENT_COLORS],
+    pub colors: [[u32; 4]; MAX_GRADIENT_COLORS / 2],
+}
+
+impl GradientUniforms {
+    /// Packs the stops of a gradient, as pairs of ratios from 0 to 1 and colors.
+    ///
+    /// Stops beyond `MAX_GRADIENT_COLORS` are dropped, so longer gradients must be resampled
+    /// first.
+    pub fn new(
+        gradient_type: i32,
+        repeat_mode: i32,
+        focal_point: f32,
+        stops: &[(f32, [f32; 4])],
+    ) -> Self {
+        let stops = &stops[..stops.len().min(MAX_GRADIENT_COLORS)];
+        let mut ratios = [0.0; MAX_GRADIENT_COLORS];
+        let mut colors = [[0; 4]; MAX_GRADIENT_COLORS / 2];
+        for (i, (ratio, [r, g, b, a])) in stops.iter().enumerate() {
+            ratios[i] = *ratio;
+            let pair = &mut colors[i / 2];
+            pair[(i % 2) * 2] = pack_half_floats(*r, *g);
+            pair[(i % 2) * 2 + 1] = pack_half_floats(*b, *a);
+        }
+        Self {
+            gradient_type,
+            num_colors: stops.len() as u32,
+            repeat_mode,
+            focal_point,
+            ratios,
+            colors,
+        }
+    }
+}
+
+/// Packs two floats as `unpackHalf2x16` unpacks them, with the first in the low bits.
+fn pack_half_floats(low: f32, high: f32) -> u32 {
+    u32::from(f32_to_f16(low)) | (u32::from(f32_to_f16(high)) << 16)
 }
 
 unsafe impl Pod for GradientUniforms {}
@@ -23,19 +63,17 @@ unsafe impl Zeroable for GradientUniforms {}
 #[derive(Debug)]
 pub struct Mesh {
     pub draws: Vec<Draw>,
-    pub transforms: wgpu::Buffer,
-    pub colors_buffer: wgpu::Buffer,
-    pub colors_last: ColorTransform,
-    pub shape_id: CharacterId,
 }
 
 #[derive(Debug)]
 pub struct Draw {
     pub draw_type: DrawType,
-    pub vertex_buffer: wgpu::Buffer,
-    pub index_buffer: wgpu::Buffer,
+    pub vertices: BufferSlice,
+    pub indices: BufferSlice,
     pub bind_group: wgpu::BindGroup,
     pub index_count: u32,
+    /// Indices are 16 bits, unless the draw has too many vertices for them.
+    pub index_format: wgpu::IndexFormat,
 }
 
 #[derive(Debug)]
@@ -49,6 +87,12 @@ pub enum DrawType {
         texture_transforms: wgpu::Buffer,
         texture_view: wgpu::TextureView,
         id: CharacterId,
+        /// The bind group to draw a smoothed bitmap with while bitmap smoothing is disabled.
+        unsmoothed_bind_group: Option<wgpu::BindGroup>,
+        /// The atlas page that the bitmap is in, with the transforms into the page and whether
+        /// the bitmap is smoothed. Such draws use the bind group of the page instead of their
+        /// own, and take the transforms from the uniforms of the frame.
+        atlas: Option<(usize, TextureTransforms, bool)>,
     },
 }
 
@@ -61,33 +105,27 @@ pub enum IncompleteDrawType {
         gradient: GradientUniforms,
     },
     Bitmap {
-        texture_transform: [[f32; 4]; 4],
+        texture_transforms: TextureTransforms,
         is_smoothed: bool,
         is_repeating: bool,
         texture_view: wgpu::TextureView,
         id: CharacterId,
+        atlas_page: Option<usize>,
     },
 }
 
 impl IncompleteDrawType {
-    pub fn name(&self) -> &'static str {
-        match self {
-            IncompleteDrawType::Color => "Color",
-            IncompleteDrawType::Gradient { .. } => "Gradient",
-            IncompleteDrawType::Bitmap { .. } => "Bitmap",
-        }
-    }
-
     #[allow(clippy::too_many_arguments)]
     pub fn build(
         self,
         device: &wgpu::Device,
-        transforms_ubo: &wgpu::Buffer,
-        colors_ubo: &wgpu::Buffer,
-        vertex_buffer: wgpu::Buffer,
-        index_buffer: wgpu::Buffer,
+        uniform_buffer: &wgpu::Buffer,
+        vertices: BufferSlice,
+        indices: BufferSlice,
         index_count: u32,
+        index_format: wgpu::IndexFormat,
         pipelines: &Pipelines,
+        samplers: &Samplers,
         shape_id: CharacterId,
         draw_id: usize,
     ) -> Draw {
@@ -101,14 +139,14 @@ impl IncompleteDrawType {
                         wgpu::Binding {
                             binding: 0,
                             resource: wgpu::BindingResource::Buffer {
-                                buffer: transforms_ubo,
+                                buffer: uniform_buffer,
                                 range: 0..std::mem::size_of::<Transforms>() as u64,
                             },
                         },
                         wgpu::Binding {
                             binding: 1,
                             resource: wgpu::BindingResource::Buffer {
-                                buffer: colors_ubo,
+                                buffer: uniform_buffer,
                                 range: 0..std::mem::size_of::<ColorAdjustments>() as u64,
                             },
                         },
@@ -118,10 +156,11 @@ impl IncompleteDrawType {
 
                 Draw {
                     draw_type: DrawType::Color,
-                    vertex_buffer,
-                    index_buffer,
+                    vertices,
+                    indices,
                     bind_group,
                     index_count,
+                    index_format,
                 }
             }
             IncompleteDrawType::Gradient {
@@ -130,7 +169,10 @@ impl IncompleteDrawType {
             } => {
                 let tex_transforms_ubo = create_buffer_with_data(
                     device,
-                    bytemuck::cast_slice(&[texture_transform]),
+                    bytemuck::cast_slice(&[TextureTransforms {
+                        u_matrix: texture_transform,
+                        uv_bounds: UNCLAMPED_UV_BOUNDS,
+                    }]),
                     wgpu::BufferUsage::UNIFORM,
                     create_debug_label!(
                         "Shape {} draw {} textransforms ubo transfer buffer",
@@ -158,7 +200,7 @@ impl IncompleteDrawType {
                         wgpu::Binding {
                             binding: 0,
                             resource: wgpu::BindingResource::Buffer {
-                                buffer: transforms_ubo,
+                                buffer: uniform_buffer,
                                 range: 0..std::mem::size_of::<Transforms>() as u64,
                             },
                         },
@@ -172,7 +214,7 @@ impl IncompleteDrawType {
                         wgpu::Binding {
                             binding: 2,
                             resource: wgpu::BindingResource::Buffer {
-                                buffer: colors_ubo,
+                                buffer: uniform_buffer,
                                 range: 0..std::mem::size_of::<ColorAdjustments>() as u64,
                             },
                         },
@@ -192,22 +234,24 @@ impl IncompleteDrawType {
                         texture_transforms: tex_transforms_ubo,
                         gradient: gradient_ubo,
                     },
-                    vertex_buffer,
-                    index_buffer,
+                    vertices,
+                    indices,
                     bind_group,
                     index_count,
+                    index_format,
                 }
             }
             IncompleteDrawType::Bitmap {
-                texture_transform,
+                texture_transforms,
                 is_smoothed,
                 is_repeating,
                 texture_view,
                 id,
+                atlas_page,
             } => {
                 let tex_transforms_ubo = create_buffer_with_data(
                     device,
-                    bytemuck::cast_slice(&[texture_transform]),
+                    bytemuck::cast_slice(&[texture_transforms]),
                     wgpu::BufferUsage::UNIFORM,
                     create_debug_label!(
                         "Shape {} draw {} textransforms ubo transfer buffer",
@@ -216,80 +260,103 @@ impl IncompleteDrawType {
                     ),
                 );
 
-                let address_mode = if is_repeating {
-                    wgpu::AddressMode::Repeat
-                } else {
-                    wgpu::AddressMode::ClampToEdge
-                };
-
-                let filter = if is_smoothed {
-                    wgpu::FilterMode::Linear
-                } else {
-                    wgpu::FilterMode::Nearest
-                };
-
-                let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-                    address_mode_u: address_mode,
-                    address_mode_v: address_mode,
-                    address_mode_w: address_mode,
-                    mag_filter: filter,
-                    min_filter: filter,
-                    mipmap_filter: filter,
-                    lod_min_clamp: 0.0,
-                    lod_max_clamp: 100.0,
-                    compare: wgpu::CompareFunction::Undefined,
-                });
-
-                let bind_group_label =
-                    create_debug_label!("Shape {} (bitmap) draw {} bindgroup", shape_id, draw_id);
-                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                    layout: &pipelines.bitmap.bind_layout,
-                    bindings: &[
-                        wgpu::Binding {
-                            binding: 0,
-                            resource: wgpu::BindingResource::Buffer {
-                                buffer: transforms_ubo,
-                                range: 0..std::mem::size_of::<Transforms>() as u64,
+                let create_bind_group = |is_smoothed| {
+                    let bind_group_label = create_debug_label!(
+                        "Shape {} (bitmap) draw {} bindgroup",
+                        shape_id,
+                        draw_id
+                    );
+                    device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        layout: &pipelines.bitmap.bind_layout,
+                        bindings: &[
+                            wgpu::Binding {
+                                binding: 0,
+                                resource: wgpu::BindingResource::Buffer {
+                                    buffer: uniform_buffer,
+                                    range: 0..std::mem::size_of::<Transforms>() as u64,
+                                },
                             },
-                        },
-                        wgpu::Binding {
-                            binding: 1,
-                            resource: wgpu::BindingResource::Buffer {
-                                buffer: &tex_transforms_ubo,
-                                range: 0..std::mem::size_of::<TextureTransforms>() as u64,
+                            wgpu::Binding {
+                                binding: 1,
+                                resource: wgpu::BindingResource::Buffer {
+                                    buffer: &tex_transforms_ubo,
+                                    range: 0..std::mem::size_of::<TextureTransforms>() as u64,
+                                },
                             },
-                        },
-                        wgpu::Binding {
-                            binding: 2,
-                            resource: wgpu::BindingResource::Buffer {
-                                buffer: colors_ubo,
-                                range: 0..std::mem::size_of::<ColorAdjustments>() as u64,
+                            wgpu::Binding {
+                                binding: 2,
+                                resource: wgpu::BindingResource::Buffer {
+                                    buffer: uniform_buffer,
+                                    range: 0..std::mem::size_of::<ColorAdjustments>() as u64,
+                                },
                             },
-                        },
-                        wgpu::Binding {
-                            binding: 3,
-                            resource: wgpu::BindingResource::TextureView(&texture_view),
-                        },
-                        wgpu::Binding {
-                            binding: 4,
-                            resource: wgpu::BindingResource::Sampler(&sampler),
-                        },
-                    ],
-                    label: bind_group_label.as_deref(),
-                });
+                            wgpu::Binding {
+                                binding: 3,
+                                resource: wgpu::BindingResource::TextureView(&texture_view),
+                            },
+                            wgpu::Binding {
+                                binding: 4,
+                                resource: wgpu::BindingResource::Sampler(
+                                    samplers.get(is_smoothed, is_repeating),
+                                ),
+                            },
+                        ],
+                        label: bind_group_label.as_deref(),
+                    })
+                };
+                let bind_group = create_bind_group(is_smoothed);
+                let unsmoothed_bind_group = if is_smoothed {
+                    Some(create_bind_group(false))
+                } else {
+                    None
+                };
 
                 Draw {
                     draw_type: DrawType::Bitmap {
                         texture_transforms: tex_transforms_ubo,
                         texture_view,
                         id,
+                        unsmoothed_bind_group,
+                        atlas: atlas_page.map(|page| (page, texture_transforms, is_smoothed)),
                     },
-                    vertex_buffer,
-                    index_buffer,
+                    vertices,
+                    indices,
                     bind_group,
                     index_count,
+                    index_format,
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gradient_uniforms_match_the_std140_layout() {
+        // 4 scalars, 4 vec4s of ratios and 8 uvec4s of colors.
+        assert_eq!(
+            std::mem::size_of::<GradientUniforms>(),
+            16 + 4 * 16 + 8 * 16
+        );
+    }
+
+    #[test]
+    fn gradient_colors_are_packed_in_pairs() {
+        let stops = [
+            (0.0, [1.0, 0.0, 0.0, 1.0]),
+            (0.5, [0.0, 1.0, 0.0, 0.5]),
+            (1.0, [0.0, 0.0, 1.0, 0.0]),
+        ];
+        let uniforms = GradientUniforms::new(0, 0, 0.0, &stops);
+        assert_eq!(uniforms.num_colors, 3);
+        assert_eq!(&uniforms.ratios[..4], &[0.0, 0.5, 1.0, 0.0]);
+        assert_eq!(
+            uniforms.colors[0],
+            [0x3c00, 0x3c00_0000, 0x3c00_0000, 0x3800_0000]
+        );
+        assert_eq!(uniforms.colors[1], [0, 0x3c00, 0, 0]);
+    }
+}
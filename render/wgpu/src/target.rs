@@ -0,0 +1,125 @@
+/// Where the frames drawn by a `WgpuRenderBackend` end up.
+pub enum RenderTarget {
+    /// Frames are presented to a window surface.
+    SwapChain {
+        surface: wgpu::Surface,
+        descriptor: wgpu::SwapChainDescriptor,
+        swap_chain: wgpu::SwapChain,
+    },
+
+    /// Frames are drawn into a texture that is never presented, such as when running headless.
+    Texture {
+        texture: wgpu::Texture,
+        format: wgpu::TextureFormat,
+    },
+}
+
+/// The texture that the current frame is drawn into.
+pub enum RenderTargetFrame {
+    SwapChain(wgpu::SwapChainOutput),
+    Texture(wgpu::TextureView),
+}
+
+impl RenderTarget {
+    pub fn swap_chain(
+        device: &wgpu::Device,
+        surface: wgpu::Surface,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let descriptor = wgpu::SwapChainDescriptor {
+            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+            format,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Mailbox,
+        };
+        let swap_chain = device.create_swap_chain(&surface, &descriptor);
+        RenderTarget::SwapChain {
+            surface,
+            descriptor,
+            swap_chain,
+        }
+    }
+
+    pub fn texture(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        RenderTarget::Texture {
+            texture: create_target_texture(device, format, width, height),
+            format,
+        }
+    }
+
+    /// The texture format that frames are drawn in.
+    pub fn format(&self) -> wgpu::TextureFormat {
+        match self {
+            RenderTarget::SwapChain { descriptor, .. } => descriptor.format,
+            RenderTarget::Texture { format, .. } => *format,
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        match self {
+            RenderTarget::SwapChain {
+                surface,
+                descriptor,
+                swap_chain,
+            } => {
+                descriptor.width = width;
+                descriptor.height = height;
+                *swap_chain = device.create_swap_chain(surface, descriptor);
+            }
+            RenderTarget::Texture { texture, format } => {
+                *texture = create_target_texture(device, *format, width, height);
+            }
+        }
+    }
+
+    pub fn get_next_frame(&mut self) -> Result<RenderTargetFrame, wgpu::TimeOut> {
+        match self {
+            RenderTarget::SwapChain { swap_chain, .. } => swap_chain
+                .get_next_texture()
+                .map(RenderTargetFrame::SwapChain),
+            RenderTarget::Texture { texture, .. } => {
+                Ok(RenderTargetFrame::Texture(texture.create_default_view()))
+            }
+        }
+    }
+}
+
+impl RenderTargetFrame {
+    pub fn view(&self) -> &wgpu::TextureView {
+        match self {
+            RenderTargetFrame::SwapChain(output) => &output.view,
+            RenderTargetFrame::Texture(view) => view,
+        }
+    }
+}
+
+fn create_target_texture(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+) -> wgpu::Texture {
+    let label = create_debug_label!("Render target texture");
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: label.as_deref(),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        },
+        array_layer_count: 1,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+    })
+}
@@ -0,0 +1,91 @@
+use crate::utils::create_buffer_with_data;
+use bytemuck::Pod;
+
+/// The size of the uniform buffer. Frames with more draws than fit are submitted in parts.
+const CAPACITY: u64 = 1 << 20;
+
+/// The per-draw uniforms of a frame, such as the transforms and color adjustments of each
+/// object, packed into a single buffer that is bound with dynamic offsets.
+///
+/// Uniforms are gathered on the CPU while the frame is recorded, and copied to the GPU with a
+/// single staging buffer once it is submitted, rather than with a copy for every draw.
+#[derive(Debug)]
+pub struct UniformBuffer {
+    buffer: wgpu::Buffer,
+    data: Vec<u8>,
+    num_uploads: usize,
+}
+
+impl UniformBuffer {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let label = create_debug_label!("Uniform buffer");
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: label.as_deref(),
+            size: CAPACITY,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+        Self {
+            buffer,
+            data: Vec::new(),
+            num_uploads: 0,
+        }
+    }
+
+    /// The buffer that bind groups should refer to, with a dynamic offset from `push`.
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    /// Returns `true` if `count` more values fit before the buffer has to be uploaded.
+    pub fn has_room_for(&self, count: usize) -> bool {
+        self.data.len() as u64 + count as u64 * wgpu::BIND_BUFFER_ALIGNMENT <= CAPACITY
+    }
+
+    /// Adds a value to the buffer, returning the dynamic offset to bind it with.
+    ///
+    /// The caller must make sure that there is room for it first.
+    pub fn push<T: Pod>(&mut self, value: &T) -> wgpu::DynamicOffset {
+        debug_assert!(self.has_room_for(1));
+        let offset = self.data.len();
+        self.data
+            .extend_from_slice(bytemuck::cast_slice(std::slice::from_ref(value)));
+        self.data
+            .resize(offset + wgpu::BIND_BUFFER_ALIGNMENT as usize, 0);
+        offset as wgpu::DynamicOffset
+    }
+
+    /// Copies the values pushed so far to the GPU, and starts over at the beginning of the
+    /// buffer.
+    ///
+    /// The returned commands must be submitted before the commands that use the values.
+    pub fn upload(&mut self, device: &wgpu::Device) -> Option<wgpu::CommandBuffer> {
+        if self.data.is_empty() {
+            return None;
+        }
+
+        let transfer_buffer = create_buffer_with_data(
+            device,
+            &self.data,
+            wgpu::BufferUsage::COPY_SRC,
+            create_debug_label!("Uniform transfer buffer"),
+        );
+        let label = create_debug_label!("Uniform upload encoder");
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: label.as_deref(),
+        });
+        encoder.copy_buffer_to_buffer(&transfer_buffer, 0, &self.buffer, 0, self.data.len() as u64);
+
+        self.data.clear();
+        self.num_uploads += 1;
+        Some(encoder.finish())
+    }
+
+    /// The number of uploads since the count was last reset.
+    pub fn num_uploads(&self) -> usize {
+        self.num_uploads
+    }
+
+    pub fn reset_num_uploads(&mut self) {
+        self.num_uploads = 0;
+    }
+}
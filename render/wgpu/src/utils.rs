@@ -56,15 +56,21 @@ pub fn ruffle_path_to_lyon_path(commands: Vec<DrawCommand>, is_closed: bool) ->
 
 #[allow(clippy::many_single_char_names)]
 pub fn swf_to_gl_matrix(m: swf::Matrix) -> [[f32; 4]; 4] {
-    let tx = m.translate_x.get() as f32;
-    let ty = m.translate_y.get() as f32;
-    let det = m.scale_x * m.scale_y - m.rotate_skew_1 * m.rotate_skew_0;
-    let mut a = m.scale_y / det;
-    let mut b = -m.rotate_skew_1 / det;
-    let mut c = -(tx * m.scale_y - m.rotate_skew_1 * ty) / det;
-    let mut d = -m.rotate_skew_0 / det;
-    let mut e = m.scale_x / det;
-    let mut f = (tx * m.rotate_skew_0 - m.scale_x * ty) / det;
+    // Invert the gradient matrix, so that shape coordinates can be mapped to the gradient
+    // square, which spans -16384 to 16384 twips and becomes 0.0 to 1.0 in texture space.
+    // This is done with `f64`s, as matrices with extreme scales or skews can otherwise lose all
+    // precision. Matrices that can't be inverted are rendered as solid fills instead.
+    let tx = f64::from(m.translate_x.get());
+    let ty = f64::from(m.translate_y.get());
+    let (scale_x, scale_y) = (f64::from(m.scale_x), f64::from(m.scale_y));
+    let (skew_0, skew_1) = (f64::from(m.rotate_skew_0), f64::from(m.rotate_skew_1));
+    let det = scale_x * scale_y - skew_1 * skew_0;
+    let mut a = scale_y / det;
+    let mut b = -skew_1 / det;
+    let mut c = -(tx * scale_y - skew_1 * ty) / det;
+    let mut d = -skew_0 / det;
+    let mut e = scale_x / det;
+    let mut f = (tx * skew_0 - scale_x * ty) / det;
 
     a *= 20.0 / 32768.0;
     b *= 20.0 / 32768.0;
@@ -75,6 +81,7 @@ pub fn swf_to_gl_matrix(m: swf::Matrix) -> [[f32; 4]; 4] {
     f /= 32768.0;
     c += 0.5;
     f += 0.5;
+    let (a, b, c, d, e, f) = (a as f32, b as f32, c as f32, d as f32, e as f32, f as f32);
     [
         [a, d, 0.0, 0.0],
         [b, e, 0., 0.0],
@@ -83,6 +90,99 @@ pub fn swf_to_gl_matrix(m: swf::Matrix) -> [[f32; 4]; 4] {
     ]
 }
 
+/// The most stops a gradient can have in the gradient shaders.
+pub const MAX_GRADIENT_COLORS: usize = 16;
+
+/// Reduces a gradient to at most `max_stops` stops, given as pairs of ratios and colors,
+/// by sampling its colors at evenly spaced ratios.
+///
+/// Gradients that already fit are returned unchanged. Otherwise the first and last stops are
+/// kept exactly, but sharp changes of color in between may be softened.
+pub fn resample_gradient(stops: &[(f32, [f32; 4])], max_stops: usize) -> Vec<(f32, [f32; 4])> {
+    if stops.len() <= max_stops || max_stops < 2 {
+        return stops.iter().take(max_stops).copied().collect();
+    }
+
+    let first = stops[0].0;
+    let last = stops[stops.len() - 1].0;
+    let mut next = 1;
+    (0..max_stops)
+        .map(|i| {
+            let ratio = first + (last - first) * i as f32 / (max_stops - 1) as f32;
+            while next < stops.len() - 1 && stops[next].0 < ratio {
+                next += 1;
+            }
+            let (start_ratio, start_color) = stops[next - 1];
+            let (end_ratio, end_color) = stops[next];
+            let a = if end_ratio > start_ratio {
+                ((ratio - start_ratio) / (end_ratio - start_ratio))
+                    .max(0.0)
+                    .min(1.0)
+            } else {
+                1.0
+            };
+            let mut color = [0.0; 4];
+            for (channel, (start, end)) in color.iter_mut().zip(start_color.iter().zip(&end_color))
+            {
+                *channel = start + (end - start) * a;
+            }
+            (ratio, color)
+        })
+        .collect()
+}
+
+/// Converts a float to the bits of the nearest half float, rounding ties to even.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        // Infinity, or NaN.
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        // Too large; round to infinity.
+        return sign | 0x7c00;
+    }
+
+    // Keep the top 10 bits of the mantissa, or fewer for subnormal half floats.
+    let (half, shift) = if exponent > 0 {
+        (((exponent as u32) << 10) | (mantissa >> 13), 13)
+    } else if exponent >= -10 {
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        return sign | round_shifted(mantissa >> shift, mantissa, shift) as u16;
+    } else {
+        return sign;
+    };
+    sign | round_shifted(half, mantissa, shift) as u16
+}
+
+/// Rounds `truncated`, which is `bits` shifted right by `shift`, to the nearest even value.
+fn round_shifted(truncated: u32, bits: u32, shift: u32) -> u32 {
+    let halfway = 1 << (shift - 1);
+    let remainder = bits & ((1 << shift) - 1);
+    if remainder > halfway || (remainder == halfway && truncated & 1 == 1) {
+        // Carrying into the exponent is correct, up to rounding to infinity.
+        truncated + 1
+    } else {
+        truncated
+    }
+}
+
+/// The value of the `u_repeat_mode` uniform of the gradient shader for a spread mode.
+pub fn gradient_spread_mode(spread: swf::GradientSpread) -> i32 {
+    match spread {
+        swf::GradientSpread::Pad => 0,
+        swf::GradientSpread::Repeat => 1,
+        swf::GradientSpread::Reflect => 2,
+    }
+}
+
 #[allow(clippy::many_single_char_names)]
 pub fn swf_bitmap_to_gl_matrix(
     m: swf::Matrix,
@@ -126,3 +226,220 @@ pub fn build_view_matrix(viewport_width: u32, viewport_height: u32) -> [[f32; 4]
         [-1.0, 1.0, 0.0, 1.0],
     ]
 }
+
+/// Shrinks a bitmap of premultiplied RGBA pixels so that neither side exceeds `max_size`.
+///
+/// The bitmap is reduced by the smallest whole factor that fits, with each pixel of the result
+/// averaging a square block of the original (a box filter). Returns the new width, height and
+/// pixels, or `None` if the bitmap already fits.
+pub fn downscale_to_fit(
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+    max_size: u32,
+) -> Option<(u32, u32, Vec<u8>)> {
+    if width <= max_size && height <= max_size {
+        return None;
+    }
+
+    let max_size = max_size.max(1);
+    let factor = (width.max(height) + max_size - 1) / max_size;
+    let new_width = (width + factor - 1) / factor;
+    let new_height = (height + factor - 1) / factor;
+
+    let mut downscaled = Vec::with_capacity((new_width * new_height * 4) as usize);
+    for y in 0..new_height {
+        let rows = y * factor..((y + 1) * factor).min(height);
+        for x in 0..new_width {
+            let columns = x * factor..((x + 1) * factor).min(width);
+            let mut sum = [0u32; 4];
+            for row in rows.clone() {
+                for column in columns.clone() {
+                    let i = ((row * width + column) * 4) as usize;
+                    for (total, &channel) in sum.iter_mut().zip(&rgba[i..i + 4]) {
+                        *total += u32::from(channel);
+                    }
+                }
+            }
+            // Edge blocks may be cut short by the bitmap's size.
+            let count = rows.len() as u32 * columns.len() as u32;
+            for total in &sum {
+                downscaled.push(((total + count / 2) / count) as u8);
+            }
+        }
+    }
+
+    Some((new_width, new_height, downscaled))
+}
+
+/// The number of levels in a full mipmap chain for a texture, down to 1x1.
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// Creates the next level of a mipmap chain from a level of premultiplied RGBA pixels.
+///
+/// The new level is half the size, rounded down, as the GPU expects. Each of its pixels averages
+/// a 2x2 block of the original (a box filter), with the blocks along the edges of an odd-sized
+/// level also taking in the row or column left over. Returns the new width, height and pixels.
+pub fn next_mip_level(width: u32, height: u32, rgba: &[u8]) -> (u32, u32, Vec<u8>) {
+    let new_width = (width / 2).max(1);
+    let new_height = (height / 2).max(1);
+    let block = |i: u32, new_size: u32, size: u32| {
+        let end = if i + 1 == new_size { size } else { i * 2 + 2 };
+        i * 2..end
+    };
+
+    let mut level = Vec::with_capacity((new_width * new_height * 4) as usize);
+    for y in 0..new_height {
+        let rows = block(y, new_height, height);
+        for x in 0..new_width {
+            let columns = block(x, new_width, width);
+            let mut sum = [0u32; 4];
+            for row in rows.clone() {
+                for column in columns.clone() {
+                    let i = ((row * width + column) * 4) as usize;
+                    for (total, &channel) in sum.iter_mut().zip(&rgba[i..i + 4]) {
+                        *total += u32::from(channel);
+                    }
+                }
+            }
+            let count = rows.len() as u32 * columns.len() as u32;
+            for total in &sum {
+                level.push(((total + count / 2) / count) as u8);
+            }
+        }
+    }
+
+    (new_width, new_height, level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mip_chains_go_down_to_one_pixel() {
+        assert_eq!(mip_level_count(1, 1), 1);
+        assert_eq!(mip_level_count(2, 1), 2);
+        assert_eq!(mip_level_count(1000, 10), 10);
+        assert_eq!(mip_level_count(1024, 1024), 11);
+    }
+
+    #[test]
+    fn mip_levels_average_blocks_of_pixels() {
+        #[rustfmt::skip]
+        let rgba = [
+            0, 0, 0, 0,      4, 4, 4, 4,      8, 8, 8, 8,
+            0, 0, 0, 0,      4, 4, 4, 4,      8, 8, 8, 8,
+        ];
+        // The odd column is folded into the last block.
+        assert_eq!(next_mip_level(3, 2, &rgba), (1, 1, vec![4, 4, 4, 4]));
+
+        let (width, height, level) = next_mip_level(4, 1, &[255; 16]);
+        assert_eq!((width, height), (2, 1));
+        assert_eq!(level, vec![255; 8]);
+    }
+
+    #[test]
+    fn gradients_within_the_limit_are_untouched() {
+        let stops = [(0.0, [0.0; 4]), (0.5, [1.0; 4]), (1.0, [0.0; 4])];
+        assert_eq!(
+            resample_gradient(&stops, MAX_GRADIENT_COLORS),
+            stops.to_vec()
+        );
+    }
+
+    #[test]
+    fn long_gradients_are_resampled_evenly() {
+        // A ramp from 0.2 to 0.8 with 31 stops, which stays a ramp when resampled.
+        let stops: Vec<_> = (0..31)
+            .map(|i| {
+                let ratio = 0.2 + 0.02 * i as f32;
+                (ratio, [ratio, 1.0 - ratio, 0.0, 1.0])
+            })
+            .collect();
+        let resampled = resample_gradient(&stops, 16);
+        assert_eq!(resampled.len(), 16);
+        for (i, (ratio, color)) in resampled.iter().enumerate() {
+            let expected = 0.2 + 0.04 * i as f32;
+            assert!((ratio - expected).abs() < 1e-5, "{} at {}", ratio, i);
+            assert!((color[0] - expected).abs() < 1e-5, "{:?} at {}", color, i);
+            assert!(
+                (color[1] - (1.0 - expected)).abs() < 1e-5,
+                "{:?} at {}",
+                color,
+                i
+            );
+            assert_eq!(color[3], 1.0);
+        }
+
+        // Resampling between two stops interpolates their colors.
+        let mut stops = vec![(0.0, [0.0; 4]); 17];
+        stops.push((1.0, [1.0; 4]));
+        for (i, stop) in stops.iter_mut().enumerate().take(17) {
+            stop.0 = i as f32 / 32.0;
+        }
+        let resampled = resample_gradient(&stops, 16);
+        assert_eq!(resampled[0], (0.0, [0.0; 4]));
+        assert_eq!(resampled[15], (1.0, [1.0; 4]));
+        // 0.6 is a fifth of the way from the stop at 0.5 to the last stop.
+        let (ratio, color) = resampled[9];
+        assert!((ratio - 0.6).abs() < 1e-5);
+        assert!((color[0] - 0.2).abs() < 1e-5, "{:?}", color);
+    }
+
+    #[test]
+    fn floats_are_rounded_to_half_floats() {
+        assert_eq!(f32_to_f16(0.0), 0);
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(0.5), 0x3800);
+        assert_eq!(f32_to_f16(-2.0), 0xc000);
+        assert_eq!(f32_to_f16(65504.0), 0x7bff);
+        assert_eq!(f32_to_f16(1e6), 0x7c00);
+        assert_eq!(f32_to_f16(f32::INFINITY), 0x7c00);
+        assert_eq!(f32_to_f16(f32::NAN) & 0x7e00, 0x7e00);
+        // The smallest subnormal half float, and half of it, which rounds to even.
+        assert_eq!(f32_to_f16(2f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_f16(2f32.powi(-25)), 0);
+        assert_eq!(f32_to_f16(1e-10), 0);
+        // 1 + 2^-11 is halfway between two half floats, and rounds down to the even one.
+        assert_eq!(f32_to_f16(1.0 + 2f32.powi(-11)), 0x3c00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+    }
+
+    #[test]
+    fn bitmaps_within_the_limit_are_untouched() {
+        let rgba = vec![255; 64 * 32 * 4];
+        assert!(downscale_to_fit(64, 32, &rgba, 64).is_none());
+    }
+
+    #[test]
+    fn wide_bitmap_is_box_filtered_to_fit() {
+        // Alternating opaque red and transparent columns, 10000 pixels wide.
+        let (width, height) = (10000, 4);
+        let mut rgba = Vec::with_capacity(width * height * 4);
+        for _ in 0..height {
+            for x in 0..width {
+                if x % 2 == 0 {
+                    rgba.extend_from_slice(&[255, 0, 0, 255]);
+                } else {
+                    rgba.extend_from_slice(&[0, 0, 0, 0]);
+                }
+            }
+        }
+
+        let (new_width, new_height, downscaled) =
+            downscale_to_fit(width as u32, height as u32, &rgba, 8192).unwrap();
+        assert_eq!((new_width, new_height), (5000, 2));
+        assert_eq!(downscaled.len(), 5000 * 2 * 4);
+        assert!(downscaled
+            .chunks_exact(4)
+            .all(|pixel| pixel == [128, 0, 0, 128]));
+
+        let (new_width, new_height, _) =
+            downscale_to_fit(width as u32, height as u32, &rgba, 4096).unwrap();
+        assert_eq!((new_width, new_height), (3334, 2));
+    }
+}
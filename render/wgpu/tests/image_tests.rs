@@ -0,0 +1,164 @@
+//! Tests that movies are drawn like reference images.
+//!
+//! Each test runs `tests/swfs/<name>/test.swf` at its stage size for a number of frames, and
+//! compares the last frame with `tests/swfs/<name>/expected.png`.
+
+use image::RgbaImage;
+use ruffle_core::backend::{
+    audio::NullAudioBackend, input::NullInputBackend, log::NullLogBackend,
+    navigator::NullNavigatorBackend, storage::MemoryStorageBackend, ui::NullUiBackend,
+};
+use ruffle_core::tag_utils::SwfMovie;
+use ruffle_core::Player;
+use ruffle_render_wgpu::{Descriptors, WgpuRenderBackend};
+use std::rc::Rc;
+
+type Error = Box<dyn std::error::Error>;
+
+// This macro generates test cases for a given list of SWFs using `test_swf_image`.
+macro_rules! swf_image_tests {
+    ($($(#[$attr:meta])* ($name:ident, $path:expr, $num_frames:literal, $tolerance:literal),)*) => {
+        $(
+        #[test]
+        $(#[$attr])*
+        fn $name() -> Result<(), Error> {
+            test_swf_image(
+                concat!("tests/swfs/", $path, "/test.swf"),
+                $num_frames,
+                concat!("tests/swfs/", $path, "/expected.png"),
+                $tolerance,
+            )
+        }
+        )*
+    };
+}
+
+// List of SWFs to test.
+// Format: (test_name, test_folder, number_of_frames_to_run, tolerance)
+// The test folder is relative to render/wgpu/tests/swfs.
+// Inside the folder is expected to be "test.swf" and "expected.png" with the correct last frame.
+// The tolerance is how much each color channel of a pixel may differ from the reference.
+swf_image_tests! {
+    (blend_modes, "blend_modes", 1, 2),
+    (color_transformed_alpha, "color_transformed_alpha", 1, 2),
+    (frame_changes, "frame_changes", 2, 2),
+    (mask_changes, "mask_changes", 3, 2),
+    (premultiplied_alpha, "premultiplied_alpha", 1, 2),
+    (self_intersecting_fills, "self_intersecting_fills", 1, 2),
+    (shapes_and_bitmaps, "shapes_and_bitmaps", 1, 2),
+}
+
+/// Runs an SWF for a number of frames with the wgpu renderer, and checks that the last frame
+/// matches the reference image within the given tolerance.
+///
+/// Machines without a graphics adapter skip the comparison.
+fn test_swf_image(
+    swf_path: &str,
+    num_frames: u32,
+    expected_image_path: &str,
+    tolerance: u8,
+) -> Result<(), Error> {
+    let descriptors = match Descriptors::new() {
+        Ok(descriptors) => Rc::new(descriptors),
+        Err(e) => {
+            eprintln!("Skipping test: {}", e);
+            return Ok(());
+        }
+    };
+
+    let movie = SwfMovie::from_path(swf_path)?;
+    let (width, height) = (movie.width(), movie.height());
+    let renderer = WgpuRenderBackend::for_offscreen(descriptors, (width, height))?;
+    let player = Player::new(
+        Box::new(renderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::new()),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(NullLogBackend::new()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+    let mut player = player.lock().unwrap();
+    player.set_viewport_dimensions(width, height);
+    for _ in 0..num_frames {
+        player.run_frame();
+    }
+    player.force_render();
+
+    let actual = player
+        .renderer()
+        .downcast_ref::<WgpuRenderBackend>()
+        .unwrap()
+        .capture_frame()
+        .ok_or("The frame couldn't be captured")?;
+    let expected = image::open(expected_image_path)?.to_rgba();
+
+    if let Err(message) = compare_images(&actual, &expected, tolerance) {
+        panic!(
+            "{} doesn't match {}: {}",
+            swf_path, expected_image_path, message
+        );
+    }
+    Ok(())
+}
+
+/// Compares two images, allowing each color channel of each pixel to differ by up to
+/// `tolerance`.
+///
+/// If they don't match, the error tells how many pixels differ by more than that, and the
+/// largest difference of any channel.
+fn compare_images(actual: &RgbaImage, expected: &RgbaImage, tolerance: u8) -> Result<(), String> {
+    if actual.dimensions() != expected.dimensions() {
+        return Err(format!(
+            "the image is {:?} pixels, but the reference is {:?}",
+            actual.dimensions(),
+            expected.dimensions()
+        ));
+    }
+
+    let mut max_delta = 0u8;
+    let mut num_different = 0;
+    for (actual, expected) in actual.pixels().zip(expected.pixels()) {
+        let delta = actual
+            .0
+            .iter()
+            .zip(expected.0.iter())
+            .map(|(&a, &b)| if a > b { a - b } else { b - a })
+            .max()
+            .unwrap_or(0);
+        max_delta = max_delta.max(delta);
+        if delta > tolerance {
+            num_different += 1;
+        }
+    }
+
+    if num_different > 0 {
+        Err(format!(
+            "{} of {} pixels differ by more than {}, by up to {}",
+            num_different,
+            actual.width() * actual.height(),
+            tolerance,
+            max_delta
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[test]
+fn image_differences_are_counted() {
+    let expected = RgbaImage::from_pixel(4, 4, image::Rgba([100, 100, 100, 255]));
+    let mut actual = expected.clone();
+    actual.put_pixel(0, 0, image::Rgba([102, 100, 100, 255]));
+    actual.put_pixel(1, 0, image::Rgba([100, 90, 100, 255]));
+    actual.put_pixel(2, 0, image::Rgba([100, 100, 107, 255]));
+
+    assert_eq!(compare_images(&actual, &actual, 0), Ok(()));
+    assert_eq!(
+        compare_images(&actual, &expected, 2),
+        Err("2 of 16 pixels differ by more than 2, by up to 10".to_string())
+    );
+    assert_eq!(compare_images(&actual, &expected, 10), Ok(()));
+    assert!(compare_images(&actual, &RgbaImage::new(4, 5), 255).is_err());
+}
@@ -0,0 +1,1140 @@
+//! Tests drawing into offscreen textures.
+
+use ruffle_core::backend::render::swf::{
+    BitmapFormat, BlendMode, Color, DefineBitsLossless, FillStyle, Matrix as SwfMatrix, Rectangle,
+    Shape, ShapeRecord, ShapeStyles, StyleChangeData, Twips,
+};
+use ruffle_core::backend::render::{BitmapHandle, RenderBackend, Transform};
+use ruffle_core::backend::{
+    audio::NullAudioBackend, input::NullInputBackend, log::NullLogBackend,
+    navigator::NullNavigatorBackend, storage::MemoryStorageBackend, ui::NullUiBackend,
+};
+use ruffle_core::color_transform::ColorTransform;
+use ruffle_core::matrix::Matrix;
+use ruffle_core::tag_utils::SwfMovie;
+use ruffle_core::Player;
+use ruffle_render_wgpu::{supported_sample_counts, Descriptors, WgpuRenderBackend};
+use std::io::Write;
+use std::rc::Rc;
+
+type Error = Box<dyn std::error::Error>;
+
+/// A 10x10 pixel red square.
+fn square() -> Shape {
+    rectangle(
+        10.0,
+        10.0,
+        FillStyle::Color(Color {
+            r: 255,
+            g: 0,
+            b: 0,
+            a: 255,
+        }),
+    )
+}
+
+/// A rectangle of the given size in pixels, with its top left corner at the origin.
+fn rectangle(width: f64, height: f64, fill_style: FillStyle) -> Shape {
+    let bounds = Rectangle {
+        x_min: Twips::from_pixels(0.0),
+        y_min: Twips::from_pixels(0.0),
+        x_max: Twips::from_pixels(width),
+        y_max: Twips::from_pixels(height),
+    };
+    let (width, height) = (Twips::from_pixels(width), Twips::from_pixels(height));
+    Shape {
+        version: 1,
+        id: 1,
+        shape_bounds: bounds.clone(),
+        edge_bounds: bounds,
+        has_fill_winding_rule: false,
+        has_non_scaling_strokes: false,
+        has_scaling_strokes: false,
+        styles: ShapeStyles {
+            fill_styles: vec![fill_style],
+            line_styles: vec![],
+        },
+        shape: vec![
+            ShapeRecord::StyleChange(StyleChangeData {
+                move_to: Some((Twips::new(0), Twips::new(0))),
+                fill_style_0: None,
+                fill_style_1: Some(1),
+                line_style: None,
+                new_styles: None,
+            }),
+            ShapeRecord::StraightEdge {
+                delta_x: width,
+                delta_y: Twips::new(0),
+            },
+            ShapeRecord::StraightEdge {
+                delta_x: Twips::new(0),
+                delta_y: height,
+            },
+            ShapeRecord::StraightEdge {
+                delta_x: width * -1,
+                delta_y: Twips::new(0),
+            },
+            ShapeRecord::StraightEdge {
+                delta_x: Twips::new(0),
+                delta_y: height * -1,
+            },
+        ],
+    }
+}
+
+/// Creates the graphics device, or returns `None` if there is no graphics adapter.
+fn descriptors() -> Option<Rc<Descriptors>> {
+    match Descriptors::new() {
+        Ok(descriptors) => Some(Rc::new(descriptors)),
+        Err(e) => {
+            // Machines without a GPU, such as most CI runners, can't run these tests.
+            eprintln!("Skipping test: {}", e);
+            None
+        }
+    }
+}
+
+const WHITE: Color = Color {
+    r: 255,
+    g: 255,
+    b: 255,
+    a: 255,
+};
+
+#[test]
+fn captured_frames_have_unpadded_rows() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Ok(()),
+    };
+
+    // 30 pixels make rows of 120 bytes, which are padded to 256 bytes when read back.
+    let (width, height) = (30, 20);
+    let mut renderer = WgpuRenderBackend::for_offscreen(descriptors, (width, height))?;
+    let shape = renderer.register_shape(&square());
+    renderer.begin_frame();
+    renderer.clear(WHITE);
+    renderer.render_shape(shape, &Transform::default());
+    renderer.end_frame();
+
+    let pixels = renderer
+        .capture_frame_pixels()
+        .expect("Offscreen frames can be captured");
+    assert_eq!(pixels.len(), (width * height * 4) as usize);
+    let pixel = |x: u32, y: u32| {
+        let i = ((y * width + x) * 4) as usize;
+        &pixels[i..i + 4]
+    };
+    assert_eq!(pixel(5, 5), &[255, 0, 0, 255]);
+    assert_eq!(pixel(25, 5), &[255, 255, 255, 255]);
+    assert_eq!(pixel(5, 15), &[255, 255, 255, 255]);
+    Ok(())
+}
+
+#[test]
+fn frames_without_msaa_are_drawn() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(_) => Rc::new(Descriptors::with_msaa_sample_count(1)?),
+        None => return Ok(()),
+    };
+    assert_eq!(descriptors.msaa_sample_count(), 1);
+
+    let mut renderer = WgpuRenderBackend::for_offscreen(descriptors, (32, 32))?;
+    let shape = renderer.register_shape(&square());
+    renderer.begin_frame();
+    renderer.clear(WHITE);
+    renderer.render_shape(shape, &Transform::default());
+    renderer.end_frame();
+
+    let pixels = renderer
+        .capture_frame_pixels()
+        .expect("Offscreen frames can be captured");
+    assert_eq!(&pixels[..4], &[255, 0, 0, 255]);
+    Ok(())
+}
+
+#[test]
+fn unsupported_msaa_sample_counts_are_replaced() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Ok(()),
+    };
+    let supported = supported_sample_counts(descriptors.device(), descriptors.surface_format());
+    assert!(supported.contains(&1));
+    assert!(supported.contains(&descriptors.msaa_sample_count()));
+
+    assert_eq!(
+        Descriptors::with_msaa_sample_count(3)?.msaa_sample_count(),
+        4
+    );
+    assert_eq!(
+        Descriptors::with_msaa_sample_count(64)?.msaa_sample_count(),
+        4
+    );
+    assert!(Descriptors::with_msaa_sample_count(0).is_err());
+    Ok(())
+}
+
+#[test]
+fn msaa_can_change_between_frames() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Ok(()),
+    };
+
+    let mut renderer = WgpuRenderBackend::for_offscreen(descriptors, (32, 32))?;
+    let shape = renderer.register_shape(&square());
+    for &(requested, expected) in &[(1, 1), (4, 4), (3, 4), (1, 1)] {
+        renderer.set_msaa(requested)?;
+        assert_eq!(renderer.msaa_sample_count(), expected);
+
+        renderer.begin_frame();
+        renderer.clear(WHITE);
+        renderer.render_shape(shape, &Transform::default());
+        renderer.end_frame();
+
+        let pixels = renderer
+            .capture_frame_pixels()
+            .expect("Offscreen frames can be captured");
+        assert_eq!(&pixels[..4], &[255, 0, 0, 255]);
+    }
+
+    assert!(renderer.set_msaa(0).is_err());
+    renderer.begin_frame();
+    assert!(renderer.set_msaa(4).is_err());
+    renderer.end_frame();
+    Ok(())
+}
+
+#[test]
+fn frames_are_drawn_in_srgb_and_linear_surface_formats() -> Result<(), Error> {
+    if descriptors().is_none() {
+        return Ok(());
+    }
+
+    for &format in &[
+        wgpu::TextureFormat::Bgra8Unorm,
+        wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    ] {
+        let mut descriptors = Descriptors::new()?;
+        descriptors.set_surface_format(format)?;
+        assert_eq!(descriptors.surface_format(), format);
+
+        let mut renderer = WgpuRenderBackend::for_offscreen(Rc::new(descriptors), (32, 32))?;
+        let shape = renderer.register_shape(&square());
+        renderer.begin_frame();
+        renderer.clear(WHITE);
+        renderer.render_shape(shape, &Transform::default());
+        renderer.end_frame();
+
+        let pixels = renderer
+            .capture_frame_pixels()
+            .expect("Offscreen frames can be captured");
+        assert_eq!(&pixels[..4], &[255, 0, 0, 255], "{:?}", format);
+        let last = pixels.len() - 4;
+        assert_eq!(&pixels[last..], &[255, 255, 255, 255], "{:?}", format);
+    }
+
+    let mut descriptors = Descriptors::new()?;
+    assert!(descriptors
+        .set_surface_format(wgpu::TextureFormat::R8Unorm)
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn pipelines_are_built_on_first_use() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Ok(()),
+    };
+    assert_eq!(descriptors.num_pipelines_created(), 0);
+
+    let mut renderer = WgpuRenderBackend::for_offscreen(descriptors.clone(), (32, 32))?;
+    let shape = renderer.register_shape(&square());
+    for _ in 0..2 {
+        renderer.begin_frame();
+        renderer.clear(WHITE);
+        renderer.render_shape(shape, &Transform::default());
+        renderer.end_frame();
+    }
+
+    // Only the unmasked color pipeline was needed, and it is reused across frames.
+    assert_eq!(descriptors.num_pipelines_created(), 1);
+
+    // Each blend mode needs its own pipeline, but unsupported ones are drawn normally.
+    renderer.begin_frame();
+    renderer.push_blend_mode(BlendMode::Multiply);
+    renderer.render_shape(shape, &Transform::default());
+    renderer.pop_blend_mode();
+    renderer.push_blend_mode(BlendMode::Darken);
+    renderer.render_shape(shape, &Transform::default());
+    renderer.pop_blend_mode();
+    renderer.end_frame();
+    assert_eq!(descriptors.num_pipelines_created(), 2);
+    Ok(())
+}
+
+#[test]
+fn deeply_nested_masks() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Ok(()),
+    };
+
+    // Twelve nested masks, more than the stencil buffer has bits, over a blue square.
+    // A green square drawn afterwards overlaps where the maskers were.
+    let renderer = WgpuRenderBackend::for_offscreen(descriptors, (40, 40))?;
+    let movie = SwfMovie::from_path("../../core/tests/swfs/avm1/nested_masks/test.swf")?;
+    let player = Player::new(
+        Box::new(renderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::new()),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(NullLogBackend::new()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+    let mut player = player.lock().unwrap();
+    player.run_frame();
+    player.render();
+
+    let pixels = player
+        .renderer()
+        .downcast_ref::<WgpuRenderBackend>()
+        .unwrap()
+        .capture_frame_pixels()
+        .expect("Offscreen frames can be captured");
+    let pixel = |x: u32, y: u32| {
+        let i = ((y * 40 + x) * 4) as usize;
+        &pixels[i..i + 4]
+    };
+
+    // Only where all maskers overlap is the blue square visible.
+    assert_eq!(pixel(15, 15), &[0, 0, 255, 255]);
+    assert_eq!(pixel(10, 10), &[255, 255, 255, 255]);
+    assert_eq!(pixel(15, 5), &[255, 255, 255, 255]);
+    assert_eq!(pixel(25, 25), &[255, 255, 255, 255]);
+
+    // The masks are removed once they have been drawn.
+    assert_eq!(pixel(5, 5), &[0, 255, 0, 255]);
+    Ok(())
+}
+
+#[test]
+fn culling_offscreen_clips_does_not_change_the_frame() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Ok(()),
+    };
+
+    // 10000 clips off the left edge of the stage, and a blue square on it.
+    let mut frames = vec![];
+    for &cull_offscreen_objects in &[true, false] {
+        let renderer = WgpuRenderBackend::for_offscreen(descriptors.clone(), (100, 100))?;
+        let movie = SwfMovie::from_path("../../core/tests/swfs/avm1/offscreen_clips/test.swf")?;
+        let player = Player::new(
+            Box::new(renderer),
+            Box::new(NullAudioBackend::new()),
+            Box::new(NullNavigatorBackend::new()),
+            Box::new(NullInputBackend::new()),
+            Box::new(NullUiBackend::new()),
+            Box::new(NullLogBackend::new()),
+            Box::new(MemoryStorageBackend::new()),
+            movie,
+        )?;
+        let mut player = player.lock().unwrap();
+        player.set_cull_offscreen_objects(cull_offscreen_objects);
+        player.run_frame();
+        player.render();
+
+        frames.push(
+            player
+                .renderer()
+                .downcast_ref::<WgpuRenderBackend>()
+                .unwrap()
+                .capture_frame_pixels()
+                .expect("Offscreen frames can be captured"),
+        );
+    }
+
+    let pixel = |x: u32, y: u32| {
+        let i = ((y * 100 + x) * 4) as usize;
+        &frames[0][i..i + 4]
+    };
+    assert_eq!(pixel(15, 15), &[0, 0, 255, 255]);
+    assert_eq!(pixel(5, 5), &[255, 255, 255, 255]);
+    assert!(frames[0] == frames[1]);
+    Ok(())
+}
+
+#[test]
+fn gradient_spread_modes() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Ok(()),
+    };
+
+    // Rows of pad, reflect and repeat gradients, going from black to white over their
+    // first 20 pixels.
+    let renderer = WgpuRenderBackend::for_offscreen(descriptors, (80, 60))?;
+    let movie = SwfMovie::from_path("../../core/tests/swfs/avm1/gradient_spread/test.swf")?;
+    let player = Player::new(
+        Box::new(renderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::new()),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(NullLogBackend::new()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+    let mut player = player.lock().unwrap();
+    player.run_frame();
+    player.render();
+
+    let pixels = player
+        .renderer()
+        .downcast_ref::<WgpuRenderBackend>()
+        .unwrap()
+        .capture_frame_pixels()
+        .expect("Offscreen frames can be captured");
+    let gray = |x: u32, y: u32| {
+        let i = ((y * 80 + x) * 4) as usize;
+        assert_eq!(pixels[i], pixels[i + 1]);
+        assert_eq!(pixels[i + 3], 255);
+        i32::from(pixels[i])
+    };
+    let assert_gray = |x: u32, y: u32, expected: i32| {
+        let actual = gray(x, y);
+        assert!(
+            (actual - expected).abs() <= 3,
+            "pixel ({}, {}) is {}, expected {}",
+            x,
+            y,
+            actual,
+            expected
+        );
+    };
+
+    // 27.5% of the way through the gradient.
+    for &y in &[10, 30, 50] {
+        assert_gray(5, y, 70);
+    }
+
+    // 27.5% of the way through the gradient's second repetition.
+    assert_gray(25, 10, 255);
+    assert_gray(25, 30, 185);
+    assert_gray(25, 50, 70);
+    Ok(())
+}
+
+#[test]
+fn scaled_down_smoothed_bitmaps_are_mipmapped() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Ok(()),
+    };
+
+    // A 1000x1000 checkerboard of black and white pixels.
+    let size = 1000;
+    let mut argb = Vec::with_capacity(size * size * 4);
+    for y in 0..size {
+        for x in 0..size {
+            let c = if (x + y) % 2 == 0 { 255 } else { 0 };
+            argb.extend_from_slice(&[255, c, c, c]);
+        }
+    }
+    let mut encoder = libflate::zlib::Encoder::new(vec![])?;
+    encoder.write_all(&argb)?;
+    let bitmap = DefineBitsLossless {
+        version: 2,
+        id: 1,
+        format: BitmapFormat::Rgb32,
+        width: size as u16,
+        height: size as u16,
+        num_colors: 0,
+        data: encoder.finish().into_result()?,
+    };
+
+    // The bitmap filling 100x100 pixels, smoothed on the left and unsmoothed on the right.
+    let mut renderer = WgpuRenderBackend::for_offscreen(descriptors, (200, 100))?;
+    renderer.register_bitmap_png(&bitmap);
+    let matrix = SwfMatrix {
+        scale_x: 2.0,
+        scale_y: 2.0,
+        ..Default::default()
+    };
+    let fill = |is_smoothed| {
+        rectangle(
+            100.0,
+            100.0,
+            FillStyle::Bitmap {
+                id: 1,
+                matrix: matrix.clone(),
+                is_smoothed,
+                is_repeating: false,
+            },
+        )
+    };
+    let smoothed = renderer.register_shape(&fill(true));
+    let unsmoothed = renderer.register_shape(&fill(false));
+    renderer.begin_frame();
+    renderer.clear(WHITE);
+    renderer.render_shape(smoothed, &Transform::default());
+    renderer.render_shape(
+        unsmoothed,
+        &Transform {
+            matrix: Matrix {
+                tx: Twips::from_pixels(100.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    );
+    renderer.end_frame();
+
+    let pixels = renderer
+        .capture_frame_pixels()
+        .expect("Offscreen frames can be captured");
+    for y in 0..100 {
+        for x in 0..200 {
+            let i = (y * 200 + x) * 4;
+            let gray = i32::from(pixels[i]);
+            if x < 100 {
+                // The smaller mip levels average the checkerboard to an even gray.
+                assert!((gray - 128).abs() <= 2, "pixel ({}, {}) is {}", x, y, gray);
+            } else {
+                // Every pixel is one of the checkerboard's, so it shimmers when moved.
+                assert!(gray == 0 || gray == 255, "pixel ({}, {}) is {}", x, y, gray);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn bitmap_samplers_are_shared() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Ok(()),
+    };
+    let num_samplers = descriptors.num_samplers_created();
+
+    let mut encoder = libflate::zlib::Encoder::new(vec![])?;
+    encoder.write_all(&[255, 255, 0, 0].repeat(4))?;
+    let bitmap = DefineBitsLossless {
+        version: 2,
+        id: 1,
+        format: BitmapFormat::Rgb32,
+        width: 2,
+        height: 2,
+        num_colors: 0,
+        data: encoder.finish().into_result()?,
+    };
+
+    // Hundreds of bitmap fills, in every combination of smoothing and repeating.
+    let mut renderer = WgpuRenderBackend::for_offscreen(descriptors.clone(), (32, 32))?;
+    renderer.register_bitmap_png(&bitmap);
+    let shapes: Vec<_> = (0..400)
+        .map(|i| {
+            renderer.register_shape(&rectangle(
+                32.0,
+                32.0,
+                FillStyle::Bitmap {
+                    id: 1,
+                    matrix: SwfMatrix::default(),
+                    is_smoothed: i % 2 == 0,
+                    is_repeating: i % 4 < 2,
+                },
+            ))
+        })
+        .collect();
+    renderer.begin_frame();
+    renderer.clear(WHITE);
+    for shape in shapes {
+        renderer.render_shape(shape, &Transform::default());
+    }
+    renderer.end_frame();
+
+    assert_eq!(descriptors.num_samplers_created(), num_samplers);
+    Ok(())
+}
+
+#[test]
+fn redefined_shapes_reuse_mesh_buffers() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Ok(()),
+    };
+
+    // A shape that is re-tessellated every frame, like one drawn with the drawing API,
+    // and unregistered while the frame that draws it is still being recorded.
+    let mut renderer = WgpuRenderBackend::for_offscreen(descriptors, (32, 32))?;
+    let mut allocated_bytes = None;
+    for i in 0..1000 {
+        renderer.begin_frame();
+        renderer.clear(WHITE);
+        let shape = renderer.register_shape(&rectangle(
+            10.0 + f64::from(i % 20),
+            10.0,
+            FillStyle::Color(Color {
+                r: 255,
+                g: 0,
+                b: 0,
+                a: 255,
+            }),
+        ));
+        renderer.render_shape(shape, &Transform::default());
+        renderer.unregister_shape(shape);
+        renderer.end_frame();
+
+        let bytes = renderer.allocated_buffer_bytes();
+        assert_eq!(*allocated_bytes.get_or_insert(bytes), bytes);
+    }
+
+    let frame = renderer
+        .capture_frame()
+        .expect("Offscreen frames can be captured");
+    assert_eq!(frame.get_pixel(5, 5).0, [255, 0, 0, 255]);
+    assert_eq!(frame.get_pixel(5, 20).0, [255, 255, 255, 255]);
+    Ok(())
+}
+
+#[test]
+fn draw_uniforms_are_uploaded_once_per_frame() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Ok(()),
+    };
+
+    // Many red squares on top of each other, and a last one to the right that is tinted.
+    // Each draw used to copy its transforms, and often its color adjustments, separately.
+    let mut renderer = WgpuRenderBackend::for_offscreen(descriptors, (32, 32))?;
+    let shape = renderer.register_shape(&square());
+    let tinted = |color_transform| Transform {
+        matrix: Matrix {
+            tx: Twips::from_pixels(20.0),
+            ..Default::default()
+        },
+        color_transform,
+    };
+    let draw_frame = |renderer: &mut WgpuRenderBackend, num_draws, color_transform| {
+        renderer.begin_frame();
+        renderer.clear(WHITE);
+        for _ in 1..num_draws {
+            renderer.render_shape(shape, &Transform::default());
+        }
+        renderer.render_shape(shape, &tinted(color_transform));
+        renderer.end_frame();
+        renderer
+            .capture_frame()
+            .expect("Offscreen frames can be captured")
+    };
+
+    let blue = ColorTransform {
+        r_mult: 0.0,
+        b_add: 1.0,
+        ..Default::default()
+    };
+    let frame = draw_frame(&mut renderer, 500, blue);
+    assert_eq!(renderer.num_uniform_uploads(), 1);
+    assert_eq!(frame.get_pixel(5, 5).0, [255, 0, 0, 255]);
+    assert_eq!(frame.get_pixel(25, 5).0, [0, 0, 255, 255]);
+
+    // Frames with more draws than the uniform buffer holds are submitted in parts.
+    let green = ColorTransform {
+        r_mult: 0.0,
+        g_add: 1.0,
+        ..Default::default()
+    };
+    let frame = draw_frame(&mut renderer, 3000, green);
+    assert_eq!(renderer.num_uniform_uploads(), 2);
+    assert_eq!(frame.get_pixel(5, 5).0, [255, 0, 0, 255]);
+    assert_eq!(frame.get_pixel(25, 5).0, [0, 255, 0, 255]);
+    Ok(())
+}
+
+#[test]
+fn text_in_registered_fonts_is_drawn() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Ok(()),
+    };
+
+    // A text field showing "I I" in the test font, 12 pixels high on a 200x100 stage,
+    // drawn four times larger.
+    let renderer = WgpuRenderBackend::for_offscreen(descriptors, (800, 400))?;
+    let movie = SwfMovie::from_path("../../core/tests/swfs/avm1/registered_font/test.swf")?;
+    let player = Player::new(
+        Box::new(renderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::new()),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(NullLogBackend::new()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+    let mut player = player.lock().unwrap();
+    player.set_viewport_dimensions(800, 400);
+    let font_data = std::fs::read("../../core/tests/swfs/avm1/registered_font/test.ttf")?;
+    player.register_font_from_bytes("Registered Test Font", &font_data)?;
+    player.run_frame();
+    player.render();
+
+    let pixels = player
+        .renderer()
+        .downcast_ref::<WgpuRenderBackend>()
+        .unwrap()
+        .capture_frame_pixels()
+        .expect("Offscreen frames can be captured");
+    let pixel = |x: u32, y: u32| {
+        let i = ((y * 800 + x) * 4) as usize;
+        &pixels[i..i + 4]
+    };
+
+    // Each "I" is a bar from 10% to 20% of the way across its 0.3 EM advance,
+    // and the space advances by 0.25 EM.
+    assert_eq!(pixel(7, 30), &[0, 0, 0, 255]);
+    assert_eq!(pixel(34, 30), &[0, 0, 0, 255]);
+    assert_eq!(pixel(2, 30), &[255, 255, 255, 255]);
+    assert_eq!(pixel(20, 30), &[255, 255, 255, 255]);
+    assert_eq!(pixel(7, 10), &[255, 255, 255, 255]);
+    Ok(())
+}
+
+#[test]
+fn solid_color_movies_are_captured() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Ok(()),
+    };
+
+    // A 40x30 stage with a #336699 background and nothing on it.
+    let renderer = WgpuRenderBackend::for_offscreen(descriptors, (40, 30))?;
+    let movie = SwfMovie::from_path("tests/swfs/solid_color.swf")?;
+    let player = Player::new(
+        Box::new(renderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::new()),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(NullLogBackend::new()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+    let mut player = player.lock().unwrap();
+    player.set_viewport_dimensions(40, 30);
+    player.run_frame();
+    player.render();
+
+    let image = player
+        .renderer()
+        .downcast_ref::<WgpuRenderBackend>()
+        .unwrap()
+        .capture_frame()
+        .expect("Offscreen frames can be captured");
+    assert_eq!(image.dimensions(), (40, 30));
+    for (x, y) in &[(0, 0), (39, 0), (0, 29), (39, 29), (20, 15)] {
+        assert_eq!(image.get_pixel(*x, *y).0, [0x33, 0x66, 0x99, 255]);
+    }
+    Ok(())
+}
+
+#[test]
+fn pixel_perfect_movies_are_scaled_by_whole_numbers() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Ok(()),
+    };
+
+    // A 20x10 stage filled by a smoothed 2x1 bitmap, with a red pixel and a blue pixel.
+    let renderer = WgpuRenderBackend::for_offscreen(descriptors, (50, 25))?;
+    let movie = SwfMovie::from_path("tests/swfs/pixel_art.swf")?;
+    let player = Player::new(
+        Box::new(renderer),
+        Box::new(NullAudioBackend::new()),
+        Box::new(NullNavigatorBackend::new()),
+        Box::new(NullInputBackend::new()),
+        Box::new(NullUiBackend::new()),
+        Box::new(NullLogBackend::new()),
+        Box::new(MemoryStorageBackend::new()),
+        movie,
+    )?;
+    let mut player = player.lock().unwrap();
+    player.set_pixel_perfect(true);
+    player.set_viewport_dimensions(50, 25);
+    player.run_frame();
+    player.render();
+
+    let image = player
+        .renderer()
+        .downcast_ref::<WgpuRenderBackend>()
+        .unwrap()
+        .capture_frame()
+        .expect("Offscreen frames can be captured");
+
+    // The 2.5x viewport scales the stage by 2, to 40x20 at (5, 2), with the rest covered.
+    for (x, y) in &[(0, 0), (4, 12), (45, 12), (25, 1), (25, 22), (49, 24)] {
+        assert_eq!(image.get_pixel(*x, *y).0, [0, 0, 0, 255]);
+    }
+
+    // The bitmap isn't smoothed, so its pixels meet without blending.
+    for (x, y) in &[(5, 2), (24, 12), (24, 21)] {
+        assert_eq!(image.get_pixel(*x, *y).0, [255, 0, 0, 255]);
+    }
+    for (x, y) in &[(25, 2), (25, 12), (44, 21)] {
+        assert_eq!(image.get_pixel(*x, *y).0, [0, 0, 255, 255]);
+    }
+    Ok(())
+}
+
+#[test]
+fn missing_adapters_are_reported_after_every_fallback() {
+    // No native adapter uses the browser's WebGPU API, so every fallback for it fails.
+    let error = match Descriptors::with_backends(wgpu::BackendBit::BROWSER_WEBGPU, 4) {
+        Ok(_) => panic!("Found a WebGPU adapter outside of a browser"),
+        Err(e) => e.to_string(),
+    };
+    assert!(error.contains("No compatible graphics adapter"));
+    assert!(error.contains("Default on BROWSER_WEBGPU"));
+    assert!(error.contains("LowPower on BROWSER_WEBGPU"));
+}
+
+#[test]
+fn shapes_with_more_vertices_than_16_bit_indices_are_drawn() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Ok(()),
+    };
+
+    // A 150x120 grid of 2x2 pixel squares, 4 pixels apart, has 72000 vertices.
+    let (columns, rows) = (150, 120);
+    let red = FillStyle::Color(Color {
+        r: 255,
+        g: 0,
+        b: 0,
+        a: 255,
+    });
+    let mut grid = rectangle(f64::from(columns * 4), f64::from(rows * 4), red);
+    let side = Twips::from_pixels(2.0);
+    grid.shape.clear();
+    for y in 0..rows {
+        for x in 0..columns {
+            grid.shape.extend_from_slice(&[
+                ShapeRecord::StyleChange(StyleChangeData {
+                    move_to: Some((
+                        Twips::from_pixels(f64::from(x * 4)),
+                        Twips::from_pixels(f64::from(y * 4)),
+                    )),
+                    fill_style_0: None,
+                    fill_style_1: Some(1),
+                    line_style: None,
+                    new_styles: None,
+                }),
+                ShapeRecord::StraightEdge {
+                    delta_x: side,
+                    delta_y: Twips::new(0),
+                },
+                ShapeRecord::StraightEdge {
+                    delta_x: Twips::new(0),
+                    delta_y: side,
+                },
+                ShapeRecord::StraightEdge {
+                    delta_x: side * -1,
+                    delta_y: Twips::new(0),
+                },
+                ShapeRecord::StraightEdge {
+                    delta_x: Twips::new(0),
+                    delta_y: side * -1,
+                },
+            ]);
+        }
+    }
+
+    let (width, height) = (columns * 4, rows * 4);
+    let mut renderer = WgpuRenderBackend::for_offscreen(descriptors, (width, height))?;
+    let shape = renderer.register_shape(&grid);
+    renderer.begin_frame();
+    renderer.clear(WHITE);
+    renderer.render_shape(shape, &Transform::default());
+    renderer.end_frame();
+
+    let frame = renderer
+        .capture_frame()
+        .expect("Offscreen frames can be captured");
+    // Squares addressed by indices past 65535 are drawn where they belong.
+    for (x, y) in &[
+        (1, 1),
+        (width / 2 + 1, height / 2 + 1),
+        (width - 3, height - 3),
+    ] {
+        assert_eq!(frame.get_pixel(*x, *y).0, [255, 0, 0, 255]);
+    }
+    for (x, y) in &[
+        (3, 3),
+        (width / 2 + 3, height / 2 + 3),
+        (width - 1, height - 1),
+    ] {
+        assert_eq!(frame.get_pixel(*x, *y).0, [255, 255, 255, 255]);
+    }
+    Ok(())
+}
+
+/// An opaque lossless bitmap, with the color of each pixel given by its position.
+fn lossless_bitmap(
+    id: u16,
+    width: u16,
+    height: u16,
+    color: impl Fn(u16, u16) -> [u8; 3],
+) -> Result<DefineBitsLossless, Error> {
+    let mut argb = Vec::with_capacity(usize::from(width) * usize::from(height) * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let [r, g, b] = color(x, y);
+            argb.extend_from_slice(&[255, r, g, b]);
+        }
+    }
+    let mut encoder = libflate::zlib::Encoder::new(vec![])?;
+    encoder.write_all(&argb)?;
+    Ok(DefineBitsLossless {
+        version: 2,
+        id,
+        format: BitmapFormat::Rgb32,
+        width,
+        height,
+        num_colors: 0,
+        data: encoder.finish().into_result()?,
+    })
+}
+
+#[test]
+fn small_bitmaps_share_atlas_bind_groups() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Ok(()),
+    };
+
+    // A sprite sheet of 16 small bitmaps, and 4 bitmaps too large for the atlas.
+    let color = |i: u16| [(i * 16) as u8, 255 - (i * 16) as u8, 128];
+    let mut renderer = WgpuRenderBackend::for_offscreen(descriptors, (256, 256))?;
+    let mut small = vec![];
+    for i in 0..16 {
+        let bitmap = lossless_bitmap(i + 1, 16, 16, |_x, _y| color(i))?;
+        small.push(renderer.register_bitmap_png(&bitmap).handle);
+    }
+    let mut large = vec![];
+    for i in 0..4 {
+        let bitmap = lossless_bitmap(i + 17, 640, 640, |_x, _y| color(i))?;
+        large.push(renderer.register_bitmap_png(&bitmap).handle);
+    }
+    assert_eq!(renderer.num_atlas_pages(), 1);
+
+    // Each frame draws its bitmaps in a grid of 64x64 cells.
+    let mut draw_grid = |bitmaps: &[BitmapHandle], scale: f32| {
+        renderer.begin_frame();
+        renderer.clear(WHITE);
+        for (i, bitmap) in bitmaps.iter().enumerate() {
+            let transform = Transform {
+                matrix: Matrix {
+                    a: scale,
+                    d: scale,
+                    tx: Twips::from_pixels((i % 4) as f64 * 64.0),
+                    ty: Twips::from_pixels((i / 4) as f64 * 64.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            renderer.render_bitmap(*bitmap, &transform, true);
+        }
+        renderer.end_frame();
+        let pixels = renderer
+            .capture_frame_pixels()
+            .expect("Offscreen frames can be captured");
+        (renderer.last_frame_stats(), pixels)
+    };
+
+    // Bitmaps from the same atlas page are all drawn with the same bind group.
+    let (stats, pixels) = draw_grid(&small, 1.0);
+    assert_eq!(stats.draws, 16);
+    assert_eq!(stats.bind_group_switches, 1);
+    for i in 0..16 {
+        let (x, y) = ((i % 4) * 64 + 8, (i / 4) * 64 + 8);
+        let pixel = &pixels[(y * 256 + x) * 4..][..3];
+        assert_eq!(pixel, color(i as u16), "bitmap {}", i);
+    }
+
+    // Large bitmaps have textures of their own, and switch bind groups on every draw.
+    let (stats, pixels) = draw_grid(&large, 0.05);
+    assert_eq!(stats.draws, 4);
+    assert_eq!(stats.bind_group_switches, 4);
+    for i in 0..4 {
+        let (x, y) = (i * 64 + 16, 16);
+        let pixel = &pixels[(y * 256 + x) * 4..][..3];
+        assert_eq!(pixel, color(i as u16), "bitmap {}", i);
+    }
+    Ok(())
+}
+
+#[test]
+fn repeating_fills_of_atlas_bitmaps_wrap_around() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Ok(()),
+    };
+
+    // A red and a blue pixel, small enough for the atlas, each filling 4x4 pixels.
+    let colors = [[255, 0, 0], [0, 0, 255]];
+    let bitmap = lossless_bitmap(1, 2, 1, |x, _y| colors[usize::from(x)])?;
+    let mut renderer = WgpuRenderBackend::for_offscreen(descriptors, (32, 8))?;
+    renderer.register_bitmap_png(&bitmap);
+    assert_eq!(renderer.num_atlas_pages(), 1);
+    let shape = renderer.register_shape(&rectangle(
+        32.0,
+        8.0,
+        FillStyle::Bitmap {
+            id: 1,
+            matrix: SwfMatrix {
+                scale_x: 80.0,
+                scale_y: 80.0,
+                ..Default::default()
+            },
+            is_smoothed: false,
+            is_repeating: true,
+        },
+    ));
+    renderer.begin_frame();
+    renderer.clear(WHITE);
+    renderer.render_shape(shape, &Transform::default());
+    renderer.end_frame();
+
+    let frame = renderer
+        .capture_frame()
+        .expect("Offscreen frames can be captured");
+    for x in 0..4 {
+        assert_eq!(frame.get_pixel(x * 8 + 2, 2).0, [255, 0, 0, 255]);
+        assert_eq!(frame.get_pixel(x * 8 + 6, 6).0, [0, 0, 255, 255]);
+    }
+    Ok(())
+}
+
+#[test]
+fn clipped_bitmap_fills_extend_their_edges() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Ok(()),
+    };
+
+    // A 2x2 bitmap, small enough for the atlas, filling a larger rectangle without repeating.
+    let colors = [[[255, 0, 0], [0, 255, 0]], [[0, 0, 255], [255, 255, 255]]];
+    let bitmap = lossless_bitmap(1, 2, 2, |x, y| colors[usize::from(y)][usize::from(x)])?;
+    let mut renderer = WgpuRenderBackend::for_offscreen(descriptors, (64, 64))?;
+    renderer.register_bitmap_png(&bitmap);
+    assert_eq!(renderer.num_atlas_pages(), 1);
+
+    for &scale in &[1.0, 3.0, 7.5, 16.0] {
+        for &is_smoothed in &[false, true] {
+            // The bitmap is drawn at (16, 16), scaled up by `scale`.
+            let shape = renderer.register_shape(&rectangle(
+                64.0,
+                64.0,
+                FillStyle::Bitmap {
+                    id: 1,
+                    matrix: SwfMatrix {
+                        scale_x: 20.0 * scale as f32,
+                        scale_y: 20.0 * scale as f32,
+                        translate_x: Twips::from_pixels(16.0),
+                        translate_y: Twips::from_pixels(16.0),
+                        ..Default::default()
+                    },
+                    is_smoothed,
+                    is_repeating: false,
+                },
+            ));
+            renderer.begin_frame();
+            renderer.clear(WHITE);
+            renderer.render_shape(shape, &Transform::default());
+            renderer.end_frame();
+            let frame = renderer
+                .capture_frame()
+                .expect("Offscreen frames can be captured");
+            let pixel = |x: u32, y: u32| {
+                let [r, g, b, _a] = frame.get_pixel(x, y).0;
+                [r, g, b]
+            };
+            let case = format!("scale {}, smoothed {}", scale, is_smoothed);
+
+            // Outside of the bitmap, the fill repeats the nearest edge pixel, without bleeding
+            // in the opposite edge or its neighbors in the atlas page.
+            assert_eq!(pixel(2, 2), colors[0][0], "{}", case);
+            assert_eq!(pixel(61, 2), colors[0][1], "{}", case);
+            assert_eq!(pixel(2, 61), colors[1][0], "{}", case);
+            assert_eq!(pixel(61, 61), colors[1][1], "{}", case);
+            let center = |i: usize| (16.0 + scale * (i as f64 + 0.5)) as u32;
+            assert_eq!(pixel(center(0), 2), colors[0][0], "{}", case);
+            assert_eq!(pixel(61, center(1)), colors[1][1], "{}", case);
+
+            // Hard-edged fills show each pixel of the bitmap as a solid square, while smoothed
+            // fills blend between them.
+            let between_red_and_green = pixel(16 + scale as u32 - 1, center(0));
+            if is_smoothed && scale > 1.0 {
+                assert!(
+                    between_red_and_green[0] > 64 && between_red_and_green[1] > 64,
+                    "{}: {:?}",
+                    case,
+                    between_red_and_green
+                );
+            } else {
+                assert_eq!(pixel(center(0), center(0)), colors[0][0], "{}", case);
+                assert_eq!(pixel(center(1), center(1)), colors[1][1], "{}", case);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn half_transparent_vertex_colors_are_blended() -> Result<(), Error> {
+    let descriptors = match descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Ok(()),
+    };
+
+    // Vertex colors are drawn the same with or without the `f32_vertex_colors` feature.
+    let mut renderer = WgpuRenderBackend::for_offscreen(descriptors, (16, 16))?;
+    let shape = renderer.register_shape(&rectangle(
+        16.0,
+        16.0,
+        FillStyle::Color(Color {
+            r: 51,
+            g: 153,
+            b: 204,
+            a: 128,
+        }),
+    ));
+    renderer.begin_frame();
+    renderer.clear(WHITE);
+    renderer.render_shape(shape, &Transform::default());
+    renderer.end_frame();
+
+    let frame = renderer
+        .capture_frame()
+        .expect("Offscreen frames can be captured");
+    // Half of each channel, over half of the white background.
+    let [r, g, b, a] = frame.get_pixel(8, 8).0;
+    let expected: [u8; 3] = [153, 204, 229];
+    for (actual, channel) in [r, g, b].iter().zip(expected.iter()) {
+        assert!(
+            (i16::from(*actual) - i16::from(*channel)).abs() <= 1,
+            "{:?} isn't {:?}",
+            [r, g, b],
+            expected
+        );
+    }
+    assert_eq!(a, 255);
+    Ok(())
+}
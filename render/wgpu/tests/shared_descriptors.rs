@@ -0,0 +1,87 @@
+//! Tests running several players side by side, with renderers sharing one graphics device.
+
+use ruffle_core::backend::{
+    audio::NullAudioBackend,
+    input::NullInputBackend,
+    log::LogBackend,
+    navigator::{NullExecutor, NullNavigatorBackend},
+    storage::MemoryStorageBackend,
+    ui::NullUiBackend,
+};
+use ruffle_core::tag_utils::SwfMovie;
+use ruffle_core::Player;
+use ruffle_render_wgpu::{Descriptors, WgpuRenderBackend};
+use std::path::Path;
+use std::rc::Rc;
+
+type Error = Box<dyn std::error::Error>;
+
+#[test]
+fn players_sharing_a_device_have_separate_trace_output() -> Result<(), Error> {
+    let descriptors = match Descriptors::new() {
+        Ok(descriptors) => Rc::new(descriptors),
+        Err(e) => {
+            // Machines without a GPU, such as most CI runners, can't run this test.
+            eprintln!("Skipping test: {}", e);
+            return Ok(());
+        }
+    };
+
+    let tests = [("avm1/clip_events", 4), ("avm1/execution_order1", 3)];
+    let mut instances = vec![];
+    for (path, _) in &tests {
+        let base_path = Path::new("../../core/tests/swfs").join(path);
+        let (executor, channel) = NullExecutor::new();
+        let movie = SwfMovie::from_path(base_path.join("test.swf"))?;
+        let renderer =
+            WgpuRenderBackend::for_offscreen(descriptors.clone(), (movie.width(), movie.height()))?;
+        let player = Player::new(
+            Box::new(renderer),
+            Box::new(NullAudioBackend::new()),
+            Box::new(NullNavigatorBackend::with_base_path(&base_path, channel)),
+            Box::new(NullInputBackend::new()),
+            Box::new(NullUiBackend::new()),
+            Box::new(TestLogBackend::default()),
+            Box::new(MemoryStorageBackend::new()),
+            movie,
+        )?;
+        instances.push((player, executor, base_path));
+    }
+
+    // Interleave the frames of both movies, like a host running several windows would.
+    for frame in 0..4 {
+        for ((player, executor, _), (_, num_frames)) in instances.iter_mut().zip(tests.iter()) {
+            if frame < *num_frames {
+                let mut player = player.lock().unwrap();
+                player.run_frame();
+                player.render();
+                drop(player);
+                executor.poll_all().unwrap();
+            }
+        }
+    }
+
+    for (player, executor, base_path) in instances.iter_mut() {
+        executor.block_all().unwrap();
+        let expected_output =
+            std::fs::read_to_string(base_path.join("output.txt"))?.replace("\r\n", "\n");
+        let player = player.lock().unwrap();
+        let log = player.log().downcast_ref::<TestLogBackend>().unwrap();
+        assert_eq!(log.trace_output, expected_output);
+    }
+
+    Ok(())
+}
+
+/// `TestLogBackend` captures output from AVM trace actions into a String.
+#[derive(Default)]
+struct TestLogBackend {
+    trace_output: String,
+}
+
+impl LogBackend for TestLogBackend {
+    fn avm_trace(&mut self, message: &str) {
+        self.trace_output.push_str(message);
+        self.trace_output.push('\n');
+    }
+}
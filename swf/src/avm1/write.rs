@@ -322,7 +322,9 @@ impl<W: Write> Writer<W> {
                 self.write_u8(num_actions_to_skip)?;
             }
             Action::With { ref actions } => {
-                self.write_action_header(OpCode::With, actions.len())?;
+                // The block follows the action, which only holds its length.
+                self.write_action_header(OpCode::With, 2)?;
+                self.write_u16(actions.len() as u16)?;
                 self.inner.write_all(&actions)?;
             }
             Action::Unknown { opcode, ref data } => {
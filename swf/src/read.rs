@@ -1382,15 +1382,18 @@ impl<R: Read> Reader<R> {
         Ok(zone)
     }
 
-    fn read_define_font_info(&mut self, version: u8) -> Result<Tag> {
+    pub fn read_define_font_info(&mut self, version: u8) -> Result<Tag> {
         let id = self.read_u16()?;
 
+        // Older authoring tools wrote the font name in the system code page rather than UTF-8,
+        // e.g. Shift-JIS names for Japanese fonts. Don't fail the whole tag over the name.
         let font_name_len = self.read_u8()?;
-        let mut font_name = String::with_capacity(font_name_len as usize);
+        let mut font_name = Vec::with_capacity(font_name_len as usize);
         self.input
             .by_ref()
             .take(font_name_len.into())
-            .read_to_string(&mut font_name)?;
+            .read_to_end(&mut font_name)?;
+        let font_name = String::from_utf8_lossy(&font_name).into_owned();
 
         let flags = self.read_u8()?;
         let use_wide_codes = flags & 0b1 != 0; // TODO(Herschel): Warn if false for version 2.
@@ -1664,17 +1667,19 @@ impl<R: Read> Reader<R> {
 
             0x40..=0x43 => {
                 let id = self.read_character_id()?;
+                // Bitmap smoothing only occurs in SWF version 8+.
+                let is_smoothed = self.version >= 8 && (fill_style_type & 0b10) == 0;
                 (
                     FillStyle::Bitmap {
                         id,
                         matrix: self.read_matrix()?,
-                        is_smoothed: (fill_style_type & 0b10) == 0,
+                        is_smoothed,
                         is_repeating: (fill_style_type & 0b01) == 0,
                     },
                     FillStyle::Bitmap {
                         id,
                         matrix: self.read_matrix()?,
-                        is_smoothed: (fill_style_type & 0b10) == 0,
+                        is_smoothed,
                         is_repeating: (fill_style_type & 0b01) == 0,
                     },
                 )
@@ -3194,6 +3199,38 @@ pub mod tests {
             read(&[0x43, 33, 0, 0b00_00110_0, 0b10100_000, 0b000_00000], 3),
             fill_style
         );
+
+        // Bitmaps are only smoothed from SWF version 8.
+        let read_with_version =
+            |buf: &[u8], version| Reader::new(buf, version).read_fill_style(3).unwrap();
+        let fill_style = |is_smoothed| FillStyle::Bitmap {
+            id: 20,
+            matrix: Matrix::new(),
+            is_smoothed,
+            is_repeating: true,
+        };
+        assert_eq!(read_with_version(&[0x40, 20, 0, 0], 8), fill_style(true));
+        assert_eq!(read_with_version(&[0x40, 20, 0, 0], 7), fill_style(false));
+        assert_eq!(read_with_version(&[0x42, 20, 0, 0], 8), fill_style(false));
+    }
+
+    #[test]
+    fn read_morph_bitmap_fill_style() {
+        let read = |buf: &[u8], version| {
+            Reader::new(buf, version)
+                .read_morph_fill_style(1)
+                .unwrap()
+                .0
+        };
+        let fill_style = |is_smoothed, is_repeating| FillStyle::Bitmap {
+            id: 20,
+            matrix: Matrix::new(),
+            is_smoothed,
+            is_repeating,
+        };
+        assert_eq!(read(&[0x41, 20, 0, 0, 0], 8), fill_style(true, false));
+        assert_eq!(read(&[0x43, 20, 0, 0, 0], 8), fill_style(false, false));
+        assert_eq!(read(&[0x41, 20, 0, 0, 0], 7), fill_style(false, false));
     }
 
     #[test]
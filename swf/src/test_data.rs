@@ -2756,6 +2756,15 @@ pub fn avm1_tests() -> Vec<Avm1TestData> {
             },
             vec![0x8D, 1, 0, 34],
         ),
+        (
+            5,
+            Action::With {
+                actions: &[0x96, 0x03, 0x00, 0x00, 0x61, 0x00, 0x17],
+            },
+            vec![
+                0x94, 0x02, 0x00, 0x07, 0x00, 0x96, 0x03, 0x00, 0x00, 0x61, 0x00, 0x17,
+            ],
+        ),
         (
             1,
             Action::Unknown {
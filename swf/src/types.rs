@@ -55,6 +55,11 @@ pub enum Compression {
 ///
 /// Use `Twips::from_pixels` and `Twips::to_pixels` to convert to and from
 /// pixel values.
+///
+/// Positions set from scripts are stored as twips, as in Flash Player, so a value
+/// read back is the value written, snapped towards zero to a multiple of 1/20 pixels.
+/// Converting through `Twips` everywhere a pixel value is stored keeps repeated
+/// reads and writes, such as `_x += 0.1`, rounding the same way.
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Default, PartialOrd, Ord)]
 pub struct Twips(i32);
 
@@ -75,7 +80,8 @@ impl Twips {
 
     /// Converts the number of pixels into twips.
     ///
-    /// This may be a lossy conversion; any precision less than a twip (1/20 pixels) is truncated.
+    /// This may be a lossy conversion; any precision less than a twip (1/20 pixels) is truncated
+    /// towards zero. Values outside of the range of twips saturate, and NaN becomes 0.
     pub fn from_pixels(pixels: f64) -> Self {
         Self((pixels * Self::TWIPS_PER_PIXEL) as i32)
     }
@@ -1130,3 +1136,54 @@ pub struct ProductInfo {
 
 /// `DebugId` is a UUID written to debug SWFs and used by the Flash Debugger.
 pub type DebugId = [u8; 16];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixels_are_truncated_to_twips() {
+        assert_eq!(Twips::from_pixels(1.99), Twips::new(39));
+        assert_eq!(Twips::from_pixels(-1.99), Twips::new(-39));
+        assert_eq!(Twips::from_pixels(0.049), Twips::new(0));
+        assert_eq!(Twips::from_pixels(0.05), Twips::new(1));
+        assert_eq!(Twips::from_pixels(12.345), Twips::new(246));
+    }
+
+    #[test]
+    fn twips_round_trip_through_pixels() {
+        for twips in -1000..1000 {
+            let twips = Twips::new(twips);
+            assert_eq!(Twips::from_pixels(twips.to_pixels()), twips);
+        }
+    }
+
+    #[test]
+    fn repeated_additions_snap_to_twips() {
+        // 0.7 + 0.1 is just under 0.8, so the ninth step lands on 0.75.
+        let mut x = Twips::new(0);
+        let mut positions = vec![];
+        for _ in 0..10 {
+            x = Twips::from_pixels(x.to_pixels() + 0.1);
+            positions.push(x.to_pixels());
+        }
+        assert_eq!(
+            positions,
+            [0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.75, 0.85, 0.95]
+        );
+
+        // Steps smaller than a twip never move anything.
+        let mut x = Twips::new(0);
+        for _ in 0..10 {
+            x = Twips::from_pixels(x.to_pixels() + 0.04);
+        }
+        assert_eq!(x, Twips::new(0));
+    }
+
+    #[test]
+    fn out_of_range_pixels_saturate() {
+        assert_eq!(Twips::from_pixels(1e20), Twips::new(i32::MAX));
+        assert_eq!(Twips::from_pixels(-1e20), Twips::new(i32::MIN));
+        assert_eq!(Twips::from_pixels(f64::NAN), Twips::new(0));
+    }
+}
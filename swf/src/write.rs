@@ -1349,7 +1349,8 @@ impl<W: Write> Writer<W> {
             ) if id == end_id && is_smoothed == end_is_smoothed
                 || is_repeating == end_is_repeating =>
             {
-                let fill_style_type = match (is_smoothed, is_repeating) {
+                // Lower versions use 0x40 and 0x41 type even when unsmoothed.
+                let fill_style_type = match (is_smoothed || self.version < 8, is_repeating) {
                     (true, true) => 0x40,
                     (true, false) => 0x41,
                     (false, true) => 0x42,
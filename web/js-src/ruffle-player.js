@@ -9,6 +9,8 @@ export let FLASH_MOVIE_MIMETYPE = "application/vnd.adobe.flash-movie";
 export let FLASH_ACTIVEX_CLASSID = "clsid:D27CDB6E-AE6D-11cf-96B8-444553540000";
 
 const DIMENSION_REGEX = /^\s*(\d+(\.\d+)?(%)?)/;
+const VOLUME_STORAGE_KEY = "ruffle_volume";
+const MUTED_STORAGE_KEY = "ruffle_muted";
 
 export class RufflePlayer extends HTMLElement {
     constructor(...args) {
@@ -24,8 +26,15 @@ export class RufflePlayer extends HTMLElement {
             self.play_button.addEventListener("click", self.play_button_clicked.bind(self));
         }
 
+        self.context_menu = self.shadow.getElementById("context_menu");
+        self.canvas.addEventListener("contextmenu", self.open_context_menu.bind(self));
+        window.addEventListener("click", self.hide_context_menu.bind(self));
+
         self.instance = null;
 
+        self._volume = RufflePlayer.load_stored_volume();
+        self._muted = RufflePlayer.load_stored_muted();
+
         self.Ruffle = load_ruffle();
 
         return self;
@@ -108,7 +117,7 @@ export class RufflePlayer extends HTMLElement {
 
                 if (response.ok) {
                     let data = await response.arrayBuffer();
-                    await this.play_swf_data(data);
+                    await this.play_swf_data(data, abs_url);
                     console.log("Playing " + url);
                 } else {
                     console.error("SWF load failed: " + response.status + " " + response.statusText + " for " + url);
@@ -131,7 +140,66 @@ export class RufflePlayer extends HTMLElement {
         }
     }
 
-    async play_swf_data(data) {
+    /*
+     * Shows the context menu of the object under the cursor in place of the browser's menu.
+     */
+    open_context_menu(event) {
+        if (!this.instance) {
+            return;
+        }
+        event.preventDefault();
+
+        let items = this.instance.prepare_context_menu();
+        this.context_menu.textContent = "";
+        items.forEach((item, index) => {
+            if (item.separatorBefore) {
+                this.context_menu.appendChild(document.createElement("hr"));
+            }
+
+            let element = document.createElement("li");
+            element.textContent = item.caption;
+            if (item.checked) {
+                element.classList.add("checked");
+            }
+            if (item.enabled) {
+                element.addEventListener("click", () => {
+                    this.instance.run_context_menu_callback(index);
+                });
+            } else {
+                element.classList.add("disabled");
+            }
+            this.context_menu.appendChild(element);
+        });
+
+        if (items.length > 0) {
+            let bounds = this.getBoundingClientRect();
+            this.context_menu.style.left = (event.clientX - bounds.left) + "px";
+            this.context_menu.style.top = (event.clientY - bounds.top) + "px";
+            this.context_menu.style.display = "block";
+        }
+    }
+
+    hide_context_menu() {
+        this.context_menu.style.display = "none";
+    }
+
+    /*
+     * Called when the movie exposes a function with `ExternalInterface.addCallback`.
+     * The function becomes a method of this element, so that page scripts can call it.
+     */
+    on_callback_available(name) {
+        this[name] = (...args) => {
+            if (this.instance) {
+                return this.instance.call_exposed_callback(name, args);
+            }
+        };
+    }
+
+    /*
+     * Plays a movie from its data.
+     * `url` is the location of the movie, if known, which scopes its shared objects.
+     */
+    async play_swf_data(data, url) {
         if (this.isConnected && !this.is_unused_fallback_object()) {
             console.log("Got SWF data");
 
@@ -146,7 +214,21 @@ export class RufflePlayer extends HTMLElement {
                 throw e;
             });
 
-            this.instance = Ruffle.new(this.canvas, new Uint8Array(data));
+            let config = (window.RufflePlayer && window.RufflePlayer.config) || {};
+            if (typeof config.shared_object_quota === "number") {
+                Ruffle.set_shared_object_quota(config.shared_object_quota);
+            }
+            if (typeof config.msaa_sample_count === "number") {
+                Ruffle.set_msaa_sample_count(config.msaa_sample_count);
+            }
+            await Ruffle.load_shared_objects();
+
+            this.instance = Ruffle.new(this.canvas, new Uint8Array(data), this, url);
+            this.instance.set_volume(this._volume);
+            this.instance.set_mute(this._muted);
+            if (config.pixel_perfect === true) {
+                this.instance.set_pixel_perfect(true);
+            }
             console.log("New Ruffle instance created.");
 
             if (this.play_button) {
@@ -157,6 +239,66 @@ export class RufflePlayer extends HTMLElement {
         }
     }
 
+    /*
+     * The master volume of this player, from 0 to 1.
+     * This is independent of any volume changes made by the movie,
+     * and is remembered across page loads.
+     */
+    get volume() {
+        return this._volume;
+    }
+
+    set volume(value) {
+        this._volume = Math.min(Math.max(Number(value) || 0, 0), 1);
+        if (this.instance) {
+            this.instance.set_volume(this._volume);
+        }
+        RufflePlayer.store_setting(VOLUME_STORAGE_KEY, this._volume);
+    }
+
+    /*
+     * Whether all audio from this player is muted.
+     */
+    get muted() {
+        return this._muted;
+    }
+
+    set muted(value) {
+        this._muted = !!value;
+        if (this.instance) {
+            this.instance.set_mute(this._muted);
+        }
+        RufflePlayer.store_setting(MUTED_STORAGE_KEY, this._muted);
+    }
+
+    static load_stored_volume() {
+        let volume = parseFloat(RufflePlayer.load_setting(VOLUME_STORAGE_KEY));
+        return isNaN(volume) ? 1 : Math.min(Math.max(volume, 0), 1);
+    }
+
+    static load_stored_muted() {
+        return RufflePlayer.load_setting(MUTED_STORAGE_KEY) === "true";
+    }
+
+    /*
+     * localStorage may be unavailable (e.g. disabled cookies), so settings are best-effort.
+     */
+    static load_setting(key) {
+        try {
+            return window.localStorage.getItem(key);
+        } catch (err) {
+            return null;
+        }
+    }
+
+    static store_setting(key, value) {
+        try {
+            window.localStorage.setItem(key, String(value));
+        } catch (err) {
+            console.warn(`Unable to save Ruffle setting ${key}`);
+        }
+    }
+
     /*
      * Copies attributes and children from another element to this player element.
      * Used by the polyfill elements, RuffleObject and RuffleEmbed.
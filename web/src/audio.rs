@@ -13,6 +13,9 @@ use web_sys::AudioContext;
 
 pub struct WebAudioBackend {
     context: AudioContext,
+
+    /// All sounds are routed through this node, which applies the host volume.
+    master_gain: web_sys::GainNode,
     sounds: Arena<Sound>,
     stream_data: FnvHashMap<swf::CharacterId, StreamData>,
     id_to_sound: FnvHashMap<swf::CharacterId, SoundHandle>,
@@ -111,8 +114,16 @@ impl WebAudioBackend {
         }
         log::info!("Minimum audio buffer sample rate: {}", min_sample_rate);
 
+        let master_gain = context
+            .create_gain()
+            .map_err(|_| "Unable to create master gain node")?;
+        master_gain
+            .connect_with_audio_node(&context.destination())
+            .map_err(|_| "Unable to connect master gain node")?;
+
         Ok(Self {
             context,
+            master_gain,
             sounds: Arena::new(),
             stream_data: FnvHashMap::default(),
             id_to_sound: FnvHashMap::default(),
@@ -194,7 +205,7 @@ impl WebAudioBackend {
                     }
                 };
 
-                node.connect_with_audio_node(&self.context.destination())
+                node.connect_with_audio_node(&self.master_gain)
                     .warn_on_error();
 
                 let instance = SoundInstance {
@@ -746,6 +757,10 @@ impl AudioBackend for WebAudioBackend {
         NUM_SOUNDS_LOADING.with(|n| n.get() == 0)
     }
 
+    fn set_global_volume(&mut self, volume: f32) {
+        self.master_gain.gain().set_value(volume);
+    }
+
     fn prime_audio(&mut self) {
         // Allow audio to start playing after a user gesture.
         let _ = self.context.resume();
@@ -0,0 +1,368 @@
+//! Async executor
+
+use generational_arena::{Arena, Index};
+use js_sys::Promise;
+use ruffle_core::backend::navigator::{CancellationToken, Error, OwnedFuture, SpawnedFuture};
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use wasm_bindgen::{closure::Closure, JsValue};
+
+/// State shared between the executor, its spawner, and the wakers of its tasks.
+///
+/// WebAssembly is single-threaded, so this is shared via `Rc` rather than `Arc`.
+struct Scheduler {
+    /// Whether or not we have already queued a poll of the executor.
+    waiting_for_poll: Cell<bool>,
+
+    /// The microtask callback that polls the executor.
+    poll_callback: RefCell<Option<Closure<dyn FnMut(JsValue)>>>,
+}
+
+impl Scheduler {
+    /// Queue a poll of the executor as a microtask.
+    ///
+    /// Microtasks run as soon as the current JS task completes, so futures
+    /// woken by a resolved promise continue in the same frame, without
+    /// waiting for the next animation frame or a user interaction.
+    fn schedule_poll(&self) {
+        if !self.waiting_for_poll.replace(true) {
+            if let Some(callback) = &*self.poll_callback.borrow() {
+                let _ = Promise::resolve(&JsValue::UNDEFINED).then(callback);
+            }
+        }
+    }
+}
+
+/// Wrapper type for futures in our executor.
+struct Task {
+    /// Whether the task has been woken and is ready to be polled.
+    is_ready: Rc<Cell<bool>>,
+
+    /// The future to poll in order to progress the task.
+    future: OwnedFuture<(), Error>,
+
+    /// The token that cancels the task.
+    token: CancellationToken,
+}
+
+/// Waker context for a single task.
+///
+/// All task handles are identical and interchangeable. Cloning a `TaskHandle`
+/// does not clone the underlying task.
+#[derive(Clone)]
+struct TaskHandle {
+    /// The ready flag of the task this handle refers to.
+    is_ready: Rc<Cell<bool>>,
+
+    /// The scheduler of the executor the task belongs to.
+    scheduler: Rc<Scheduler>,
+}
+
+impl TaskHandle {
+    /// Construct a new `RawWaker` for this task handle.
+    ///
+    /// This function clones the underlying task handle.
+    fn raw_waker(&self) -> RawWaker {
+        let clone = Box::new(self.clone());
+        RawWaker::new(Box::into_raw(clone) as *const (), &Self::VTABLE)
+    }
+
+    /// Construct a new waker for this task handle.
+    fn waker(&self) -> Waker {
+        unsafe { Waker::from_raw(self.raw_waker()) }
+    }
+
+    /// Mark the task as ready and make sure the executor gets polled.
+    fn wake(&self) {
+        self.is_ready.set(true);
+        self.scheduler.schedule_poll();
+    }
+
+    /// Construct a new `RawWaker` that wakes the same task.
+    ///
+    /// This function is unsafe because the pointer must have been created by
+    /// `raw_waker`.
+    unsafe fn clone_as_ptr(almost_self: *const ()) -> RawWaker {
+        (&*(almost_self as *const Self)).raw_waker()
+    }
+
+    /// Wake the given task, then drop it.
+    unsafe fn wake_as_ptr(almost_self: *const ()) {
+        Box::from_raw(almost_self as *mut Self).wake();
+    }
+
+    /// Wake the given task.
+    unsafe fn wake_by_ref_as_ptr(almost_self: *const ()) {
+        (&*(almost_self as *const Self)).wake();
+    }
+
+    /// Drop the task handle.
+    unsafe fn drop_as_ptr(almost_self: *const ()) {
+        drop(Box::from_raw(almost_self as *mut Self));
+    }
+
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(
+        Self::clone_as_ptr,
+        Self::wake_as_ptr,
+        Self::wake_by_ref_as_ptr,
+        Self::drop_as_ptr,
+    );
+}
+
+/// Hands futures to a `WebAsyncExecutor`.
+///
+/// This is given to the `WebNavigatorBackend` so that it can spawn futures.
+pub struct TaskSpawner {
+    /// The channel upon which all spawned futures will be sent.
+    channel: Sender<SpawnedFuture>,
+
+    /// The scheduler of the executor receiving the futures.
+    scheduler: Rc<Scheduler>,
+
+    /// The executor receiving the futures.
+    executor: Weak<RefCell<WebAsyncExecutor>>,
+}
+
+impl TaskSpawner {
+    /// Queue a future on the executor, and poll it as soon as possible.
+    pub fn spawn(&self, future: OwnedFuture<(), Error>, token: CancellationToken) {
+        if self.channel.send((future, token)).is_err() {
+            log::warn!("A future was spawned on an executor that has been destroyed");
+            return;
+        }
+
+        self.scheduler.schedule_poll();
+    }
+
+    /// Drop all cancelled tasks on the executor.
+    ///
+    /// If the executor is being polled right now, it will skip and drop the
+    /// cancelled tasks itself, as there's no waiting for it on a single thread.
+    pub fn drain(&self) {
+        if let Some(executor) = self.executor.upgrade() {
+            if let Ok(mut executor) = executor.try_borrow_mut() {
+                executor.drain();
+            }
+        }
+    }
+}
+
+/// An executor for futures driven by the browser's event loop.
+///
+/// The executor is polled from a microtask whenever a task is spawned or
+/// woken, and additionally on every animation frame.
+pub struct WebAsyncExecutor {
+    /// List of all spawned tasks.
+    task_queue: Arena<Task>,
+
+    /// Source of tasks sent to us by the `NavigatorBackend`.
+    channel: Receiver<SpawnedFuture>,
+
+    /// The scheduler shared with our wakers.
+    scheduler: Rc<Scheduler>,
+}
+
+impl WebAsyncExecutor {
+    /// Construct a new executor.
+    ///
+    /// This function returns the executor itself, plus the `TaskSpawner`
+    /// necessary to spawn new tasks.
+    pub fn new() -> (Rc<RefCell<Self>>, TaskSpawner) {
+        let (send, recv) = channel();
+        let scheduler = Rc::new(Scheduler {
+            waiting_for_poll: Cell::new(false),
+            poll_callback: RefCell::new(None),
+        });
+        let new_self = Rc::new(RefCell::new(Self {
+            task_queue: Arena::new(),
+            channel: recv,
+            scheduler: scheduler.clone(),
+        }));
+
+        let self_ref: Weak<RefCell<Self>> = Rc::downgrade(&new_self);
+        let callback_ref = self_ref.clone();
+        *scheduler.poll_callback.borrow_mut() = Some(Closure::wrap(Box::new(move |_| {
+            if let Some(executor) = callback_ref.upgrade() {
+                // If the executor is already being polled, it will pick up any
+                // newly woken tasks itself.
+                if let Ok(mut executor) = executor.try_borrow_mut() {
+                    executor.poll_all();
+                }
+            }
+        })
+            as Box<dyn FnMut(JsValue)>));
+
+        (
+            new_self,
+            TaskSpawner {
+                channel: send,
+                scheduler,
+                executor: self_ref,
+            },
+        )
+    }
+
+    /// Move all newly spawned futures into the task queue.
+    fn flush_channel(&mut self) {
+        while let Ok((future, token)) = self.channel.try_recv() {
+            self.task_queue.insert(Task {
+                is_ready: Rc::new(Cell::new(true)),
+                future,
+                token,
+            });
+        }
+    }
+
+    /// Poll all `Ready` futures.
+    ///
+    /// Cancelled tasks are dropped without being polled.
+    pub fn poll_all(&mut self) {
+        self.scheduler.waiting_for_poll.set(false);
+        self.flush_channel();
+
+        let mut completed_tasks: Vec<Index> = vec![];
+
+        for (index, task) in self.task_queue.iter_mut() {
+            if task.token.is_cancelled() {
+                completed_tasks.push(index);
+            } else if task.is_ready.replace(false) {
+                let handle = TaskHandle {
+                    is_ready: task.is_ready.clone(),
+                    scheduler: self.scheduler.clone(),
+                };
+                let waker = handle.waker();
+                let mut context = Context::from_waker(&waker);
+
+                match task.future.as_mut().poll(&mut context) {
+                    Poll::Pending => {}
+                    Poll::Ready(r) => {
+                        if let Err(e) = r {
+                            log::error!("Async error: {}", e);
+                        }
+
+                        completed_tasks.push(index);
+                    }
+                }
+            }
+        }
+
+        for index in completed_tasks {
+            self.task_queue.remove(index);
+        }
+    }
+
+    /// Drop all cancelled tasks, returning how many there were.
+    pub fn drain(&mut self) -> usize {
+        self.flush_channel();
+
+        let cancelled_tasks: Vec<Index> = self
+            .task_queue
+            .iter()
+            .filter(|(_, task)| task.token.is_cancelled())
+            .map(|(index, _)| index)
+            .collect();
+
+        for index in &cancelled_tasks {
+            self.task_queue.remove(*index);
+        }
+
+        cancelled_tasks.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use js_sys::Function;
+    use wasm_bindgen_futures::JsFuture;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    /// Waits for the next JS task, by which point all queued microtasks have run.
+    async fn next_task() {
+        let timeout = Promise::new(&mut |resolve: Function, _| {
+            web_sys::window()
+                .unwrap()
+                .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, 0)
+                .unwrap();
+        });
+        JsFuture::from(timeout).await.unwrap();
+    }
+
+    /// A future that sets `done` once `promise` resolves.
+    fn wait_for(promise: Promise, done: Rc<Cell<bool>>) -> OwnedFuture<(), Error> {
+        Box::pin(async move {
+            JsFuture::from(promise).await.unwrap();
+            done.set(true);
+            Ok(())
+        })
+    }
+
+    #[wasm_bindgen_test]
+    async fn spawned_futures_run_without_an_animation_frame() {
+        let (executor, spawner) = WebAsyncExecutor::new();
+        let done = Rc::new(Cell::new(false));
+        let promise = Promise::resolve(&JsValue::UNDEFINED);
+        spawner.spawn(wait_for(promise, done.clone()), CancellationToken::new());
+
+        next_task().await;
+        assert!(done.get());
+        assert_eq!(executor.borrow().task_queue.len(), 0);
+    }
+
+    #[wasm_bindgen_test]
+    async fn woken_futures_continue_once_their_promise_resolves() {
+        let (executor, spawner) = WebAsyncExecutor::new();
+        let done = Rc::new(Cell::new(false));
+        let mut resolve = None;
+        let promise = Promise::new(&mut |resolve_fn: Function, _| resolve = Some(resolve_fn));
+        spawner.spawn(wait_for(promise, done.clone()), CancellationToken::new());
+
+        next_task().await;
+        assert!(!done.get());
+        assert_eq!(executor.borrow().task_queue.len(), 1);
+
+        resolve.unwrap().call0(&JsValue::UNDEFINED).unwrap();
+        next_task().await;
+        assert!(done.get());
+        assert_eq!(executor.borrow().task_queue.len(), 0);
+    }
+
+    #[wasm_bindgen_test]
+    async fn cancelled_futures_are_never_polled() {
+        let (executor, spawner) = WebAsyncExecutor::new();
+        let done = Rc::new(Cell::new(false));
+        let token = CancellationToken::new();
+        let promise = Promise::resolve(&JsValue::UNDEFINED);
+        spawner.spawn(wait_for(promise, done.clone()), token.clone());
+        token.cancel();
+
+        next_task().await;
+        assert!(!done.get());
+        assert_eq!(executor.borrow().task_queue.len(), 0);
+    }
+
+    #[wasm_bindgen_test]
+    async fn draining_drops_cancelled_pending_futures() {
+        let (executor, spawner) = WebAsyncExecutor::new();
+        let done = Rc::new(Cell::new(false));
+        let token = CancellationToken::new();
+        let promise = Promise::new(&mut |_, _| {});
+        spawner.spawn(wait_for(promise, done.clone()), token.clone());
+        spawner.spawn(
+            wait_for(Promise::new(&mut |_, _| {}), done.clone()),
+            CancellationToken::new(),
+        );
+
+        next_task().await;
+        assert_eq!(executor.borrow().task_queue.len(), 2);
+
+        token.cancel();
+        assert_eq!(executor.borrow_mut().drain(), 1);
+        assert_eq!(executor.borrow().task_queue.len(), 1);
+        assert!(!done.get());
+    }
+}
@@ -0,0 +1,358 @@
+//! `ExternalInterface` calls between the movie and the page.
+use js_sys::{Array, Function, Object, Reflect};
+use ruffle_core::external::{ExternalInterfaceProvider, Value as ExternalValue};
+use std::collections::BTreeMap;
+use std::error::Error;
+use wasm_bindgen::{JsCast, JsValue};
+
+/// Sends `ExternalInterface.call` calls to functions of the page, and exposes the movie's
+/// callbacks on its player element.
+pub struct JavascriptInterface {
+    /// The `ruffle-player` element displaying the movie.
+    js_player: JsValue,
+}
+
+impl JavascriptInterface {
+    pub fn new(js_player: JsValue) -> Self {
+        Self { js_player }
+    }
+}
+
+impl ExternalInterfaceProvider for JavascriptInterface {
+    fn call_method(
+        &mut self,
+        name: &str,
+        args: &[ExternalValue],
+    ) -> Result<ExternalValue, Box<dyn Error>> {
+        let window = web_sys::window().ok_or("Expected window")?;
+
+        // The name may be a path such as `console.log`, in which case the function is called
+        // on the object that contains it.
+        let mut this: JsValue = window.into();
+        let mut function = this.clone();
+        for (i, part) in name.split('.').enumerate() {
+            if i > 0 {
+                this = function;
+            }
+            function = Reflect::get(&this, &part.into()).map_err(js_error)?;
+        }
+        let function = function
+            .dyn_into::<Function>()
+            .map_err(|_| format!("{} is not a function", name))?;
+
+        let js_args: Array = args.iter().map(external_to_js).collect();
+        let result = function.apply(&this, &js_args).map_err(js_error)?;
+        Ok(js_to_external(&result))
+    }
+
+    fn on_callback_available(&mut self, name: &str) {
+        // The player element defines a method of this name, which calls back into the movie.
+        let result = Reflect::get(&self.js_player, &"on_callback_available".into())
+            .and_then(|method| method.dyn_into::<Function>())
+            .and_then(|method| method.call1(&self.js_player, &name.into()));
+        if let Err(e) = result {
+            log::error!(
+                "Unable to expose ExternalInterface callback {}: {:?}",
+                name,
+                e
+            );
+        }
+    }
+}
+
+/// Convert an error thrown by JavaScript into a Rust error.
+fn js_error(error: JsValue) -> Box<dyn Error> {
+    match error.dyn_ref::<js_sys::Error>() {
+        Some(error) => String::from(error.to_string()).into(),
+        None => format!("{:?}", error).into(),
+    }
+}
+
+/// Convert a JavaScript value into a value to be sent to the movie.
+///
+/// Functions are skipped, and objects that contain themselves are sent as `null` the second
+/// time they are encountered.
+pub fn js_to_external(value: &JsValue) -> ExternalValue {
+    js_to_external_inner(value, &mut vec![])
+}
+
+fn js_to_external_inner(value: &JsValue, parents: &mut Vec<JsValue>) -> ExternalValue {
+    if value.is_undefined() {
+        return ExternalValue::Undefined;
+    } else if value.is_null() {
+        return ExternalValue::Null;
+    } else if let Some(value) = value.as_bool() {
+        return ExternalValue::Bool(value);
+    } else if let Some(number) = value.as_f64() {
+        return ExternalValue::Number(number);
+    } else if let Some(string) = value.as_string() {
+        return ExternalValue::String(string);
+    } else if !value.is_object() || value.is_instance_of::<Function>() {
+        return ExternalValue::Undefined;
+    }
+
+    if parents.iter().any(|parent| Object::is(parent, value)) {
+        return ExternalValue::Null;
+    }
+    parents.push(value.clone());
+
+    let external = if Array::is_array(value) {
+        let array: &Array = value.unchecked_ref();
+        ExternalValue::List(
+            array
+                .iter()
+                .map(|element| js_to_external_inner(&element, parents))
+                .collect(),
+        )
+    } else {
+        let mut properties = BTreeMap::new();
+        for key in Object::keys(value.unchecked_ref()).iter() {
+            let property = Reflect::get(value, &key).unwrap_or(JsValue::UNDEFINED);
+            if property.is_instance_of::<Function>() {
+                continue;
+            }
+            if let Some(key) = key.as_string() {
+                properties.insert(key, js_to_external_inner(&property, parents));
+            }
+        }
+        ExternalValue::Object(properties)
+    };
+
+    parents.pop();
+    external
+}
+
+/// Convert a value received from the movie into a JavaScript value.
+pub fn external_to_js(value: &ExternalValue) -> JsValue {
+    match value {
+        ExternalValue::Undefined => JsValue::UNDEFINED,
+        ExternalValue::Null => JsValue::NULL,
+        ExternalValue::Bool(value) => (*value).into(),
+        ExternalValue::Number(number) => (*number).into(),
+        ExternalValue::String(string) => string.into(),
+        ExternalValue::Object(properties) => {
+            let object = Object::new();
+            for (name, property) in properties {
+                let _ = Reflect::set(&object, &name.into(), &external_to_js(property));
+            }
+            object.into()
+        }
+        ExternalValue::List(values) => values.iter().map(external_to_js).collect::<Array>().into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ruffle, INSTANCES, PENDING_CALLBACKS};
+    use js_sys::{Uint8Array, JSON};
+    use wasm_bindgen_test::*;
+    use web_sys::HtmlCanvasElement;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn eval(code: &str) -> JsValue {
+        js_sys::eval(code).unwrap()
+    }
+
+    fn object(properties: Vec<(&str, ExternalValue)>) -> ExternalValue {
+        ExternalValue::Object(
+            properties
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// The object sent by the `external_interface` test movie, which contains itself.
+    fn movie_object() -> ExternalValue {
+        object(vec![
+            (
+                "list",
+                ExternalValue::List(vec![
+                    1.0.into(),
+                    "two".into(),
+                    object(vec![("three", true.into())]),
+                ]),
+            ),
+            ("nothing", ExternalValue::Null),
+            ("self", ExternalValue::Null),
+        ])
+    }
+
+    #[wasm_bindgen_test]
+    fn nested_js_values_round_trip() {
+        let js = eval("({ list: [1, 'two', { three: true }], nothing: null, text: 'four' })");
+        let external = js_to_external(&js);
+        assert_eq!(
+            external,
+            object(vec![
+                (
+                    "list",
+                    ExternalValue::List(vec![
+                        1.0.into(),
+                        "two".into(),
+                        object(vec![("three", true.into())]),
+                    ]),
+                ),
+                ("nothing", ExternalValue::Null),
+                ("text", "four".into()),
+            ])
+        );
+
+        let back = external_to_js(&external);
+        assert_eq!(
+            String::from(JSON::stringify(&back).unwrap()),
+            String::from(JSON::stringify(&js).unwrap())
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn nested_external_values_round_trip() {
+        let external = movie_object();
+        assert_eq!(js_to_external(&external_to_js(&external)), external);
+    }
+
+    #[wasm_bindgen_test]
+    fn cyclic_js_objects_are_sent_as_null() {
+        let js =
+            eval("(function () { var o = { list: [] }; o.self = o; o.list.push(o); return o; })()");
+        assert_eq!(
+            js_to_external(&js),
+            object(vec![
+                ("list", ExternalValue::List(vec![ExternalValue::Null])),
+                ("self", ExternalValue::Null),
+            ])
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn js_functions_are_skipped() {
+        let js = eval("({ a: 1, f: function () {} })");
+        assert_eq!(js_to_external(&js), object(vec![("a", 1.0.into())]));
+    }
+
+    #[wasm_bindgen_test]
+    fn call_method_calls_page_functions() {
+        eval(
+            "window.ruffleTestConcat = function (value) { \
+                return { list: value.list.concat(value.list), onWindow: this === window }; \
+            }; \
+            window.ruffleTestObject = { name: 'object', getName: function () { return this.name; } };",
+        );
+        let mut interface = JavascriptInterface::new(JsValue::UNDEFINED);
+
+        let arg = object(vec![("list", ExternalValue::List(vec![1.0.into()]))]);
+        assert_eq!(
+            interface.call_method("ruffleTestConcat", &[arg]).unwrap(),
+            object(vec![
+                ("list", ExternalValue::List(vec![1.0.into(), 1.0.into()])),
+                ("onWindow", true.into()),
+            ])
+        );
+        assert_eq!(
+            interface
+                .call_method("ruffleTestObject.getName", &[])
+                .unwrap(),
+            "object".into()
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn call_method_reports_errors() {
+        eval("window.ruffleTestThrow = function () { throw new Error('boom'); };");
+        let mut interface = JavascriptInterface::new(JsValue::UNDEFINED);
+
+        let error = interface.call_method("ruffleTestThrow", &[]).unwrap_err();
+        assert!(error.to_string().contains("boom"));
+        let error = interface.call_method("ruffleTestMissing", &[]).unwrap_err();
+        assert!(error.to_string().contains("is not a function"));
+    }
+
+    #[wasm_bindgen_test]
+    fn movie_callbacks_are_exposed_on_the_player_element() {
+        let window = web_sys::window().unwrap();
+        let canvas: HtmlCanvasElement = window
+            .document()
+            .unwrap()
+            .create_element("canvas")
+            .unwrap()
+            .dyn_into()
+            .unwrap();
+
+        // A stand-in for the `ruffle-player` element.
+        let element = eval(
+            "(function () { \
+                var element = { names: [] }; \
+                element.on_callback_available = function (name) { \
+                    this.names.push(name); \
+                    this[name] = function () { \
+                        var args = Array.prototype.slice.call(arguments); \
+                        return window.ruffleTestPlayer.call_exposed_callback(name, args); \
+                    }; \
+                }; \
+                return element; \
+            })()",
+        );
+        Reflect::set(&window, &"ruffleTestElement".into(), &element).unwrap();
+
+        // The movie calls `roundTrip`, which calls back into the movie while it is busy.
+        eval(
+            "window.roundTrip = function (value) { \
+                window.ruffleTestReceived = value; \
+                window.ruffleTestReentrant = window.ruffleTestElement.echo('during call'); \
+                return value; \
+            };",
+        );
+
+        let swf = include_bytes!("../../core/tests/swfs/avm1/external_interface/test.swf");
+        let mut ruffle =
+            Ruffle::new(canvas, Uint8Array::from(&swf[..]), element.clone(), None).unwrap();
+        Reflect::set(&window, &"ruffleTestPlayer".into(), &ruffle.clone().into()).unwrap();
+
+        INSTANCES.with(|instances| {
+            let instances = instances.borrow();
+            let instance = instances.get(ruffle.0).unwrap();
+            instance.core.lock().unwrap().run_frame();
+        });
+
+        assert_eq!(
+            js_to_external(&Reflect::get(&element, &"names".into()).unwrap()),
+            ExternalValue::List(vec!["echo".into(), "getName".into()])
+        );
+        assert_eq!(
+            js_to_external(&Reflect::get(&window, &"ruffleTestReceived".into()).unwrap()),
+            movie_object()
+        );
+
+        // The reentrant call was queued, and runs once the player is free.
+        assert!(Reflect::get(&window, &"ruffleTestReentrant".into())
+            .unwrap()
+            .is_undefined());
+        assert_eq!(PENDING_CALLBACKS.with(|pending| pending.borrow().len()), 1);
+        Ruffle::run_pending_callbacks();
+        assert!(PENDING_CALLBACKS.with(|pending| pending.borrow().is_empty()));
+
+        // Callbacks run on the object given to `addCallback`.
+        let get_name: Function = Reflect::get(&element, &"getName".into())
+            .unwrap()
+            .dyn_into()
+            .unwrap();
+        assert_eq!(
+            js_to_external(&get_name.call0(&element).unwrap()),
+            "receiver".into()
+        );
+
+        let echo: Function = Reflect::get(&element, &"echo".into())
+            .unwrap()
+            .dyn_into()
+            .unwrap();
+        let value = movie_object();
+        assert_eq!(
+            js_to_external(&echo.call1(&element, &external_to_js(&value)).unwrap()),
+            value
+        );
+
+        ruffle.destroy().unwrap();
+    }
+}
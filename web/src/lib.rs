@@ -1,18 +1,37 @@
 //! Ruffle web frontend.
 mod audio;
+mod executor;
+mod external_interface;
 mod input;
+mod locale;
 mod navigator;
-
-use crate::{audio::WebAudioBackend, input::WebInputBackend, navigator::WebNavigatorBackend};
+mod storage;
+mod trace;
+mod ui;
+
+use crate::{
+    audio::WebAudioBackend,
+    executor::WebAsyncExecutor,
+    external_interface::{external_to_js, js_to_external, JavascriptInterface},
+    input::WebInputBackend,
+    locale::WebTimeZone,
+    navigator::WebNavigatorBackend,
+    storage::WebStorageBackend,
+    trace::WebLogBackend,
+    ui::WebUiBackend,
+};
 use generational_arena::{Arena, Index};
-use js_sys::Uint8Array;
+use js_sys::{Array, Object, Promise, Reflect, Uint8Array};
 use ruffle_core::backend::render::RenderBackend;
+use ruffle_core::external::Value as ExternalValue;
 use ruffle_core::tag_utils::SwfMovie;
 use ruffle_core::PlayerEvent;
+use std::cell::Cell;
 use std::mem::drop;
 use std::sync::{Arc, Mutex};
-use std::{cell::RefCell, error::Error, num::NonZeroI32};
+use std::{cell::RefCell, error::Error, num::NonZeroI32, rc::Rc};
 use wasm_bindgen::{prelude::*, JsCast, JsValue};
+use wasm_bindgen_futures::future_to_promise;
 use web_sys::{Element, EventTarget, HtmlCanvasElement, KeyboardEvent, PointerEvent};
 
 thread_local! {
@@ -20,12 +39,28 @@ thread_local! {
     /// This gives us a clear boundary between the JS side and Rust side, avoiding
     /// issues with lifetimes and type paramters (which cannot be exported with wasm-bindgen).
     static INSTANCES: RefCell<Arena<RuffleInstance>> = RefCell::new(Arena::new());
+
+    /// Calls from the page to `ExternalInterface` callbacks that arrived while their player was
+    /// busy, e.g. because the page was itself called by the movie with `ExternalInterface.call`.
+    /// They are run once the player is free again.
+    static PENDING_CALLBACKS: RefCell<Vec<PendingCallback>> = RefCell::new(vec![]);
+
+    /// The anti-aliasing sample count set by the page, if it overrides the renderer's default.
+    static MSAA_SAMPLE_COUNT: Cell<Option<u32>> = Cell::new(None);
+}
+
+/// A queued call to an `ExternalInterface` callback.
+struct PendingCallback {
+    instance: Index,
+    name: String,
+    args: Vec<ExternalValue>,
 }
 
 type AnimationHandler = Closure<dyn FnMut(f64)>;
 
 struct RuffleInstance {
     core: Arc<Mutex<ruffle_core::Player>>,
+    executor: Rc<RefCell<WebAsyncExecutor>>,
     canvas: HtmlCanvasElement,
     canvas_width: i32,
     canvas_height: i32,
@@ -37,6 +72,7 @@ struct RuffleInstance {
     mouse_move_callback: Option<Closure<dyn FnMut(PointerEvent)>>,
     mouse_down_callback: Option<Closure<dyn FnMut(PointerEvent)>>,
     mouse_up_callback: Option<Closure<dyn FnMut(PointerEvent)>>,
+    mouse_leave_callback: Option<Closure<dyn FnMut(PointerEvent)>>,
     window_mouse_down_callback: Option<Closure<dyn FnMut(PointerEvent)>>,
     key_down_callback: Option<Closure<dyn FnMut(KeyboardEvent)>>,
     key_up_callback: Option<Closure<dyn FnMut(KeyboardEvent)>>,
@@ -52,8 +88,35 @@ pub struct Ruffle(Index);
 
 #[wasm_bindgen]
 impl Ruffle {
-    pub fn new(canvas: HtmlCanvasElement, swf_data: Uint8Array) -> Result<Ruffle, JsValue> {
-        Ruffle::new_internal(canvas, swf_data).map_err(|_| "Error creating player".into())
+    pub fn new(
+        canvas: HtmlCanvasElement,
+        swf_data: Uint8Array,
+        js_player: JsValue,
+        movie_url: Option<String>,
+    ) -> Result<Ruffle, JsValue> {
+        Ruffle::new_internal(canvas, swf_data, js_player, movie_url)
+            .map_err(|_| "Error creating player".into())
+    }
+
+    /// Loads the shared objects stored by this page.
+    /// The returned promise must resolve before players are created.
+    pub fn load_shared_objects() -> Promise {
+        future_to_promise(async {
+            storage::load().await;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Sets the size limit of all the shared objects of this page, in bytes.
+    pub fn set_shared_object_quota(quota: u32) {
+        storage::set_quota(quota as usize);
+    }
+
+    /// Sets the number of samples per pixel that players created afterwards anti-alias with,
+    /// or 1 to disable anti-aliasing. Counts the device doesn't support are lowered to the most
+    /// that it does.
+    pub fn set_msaa_sample_count(msaa_sample_count: u32) {
+        MSAA_SAMPLE_COUNT.with(|count| count.set(Some(msaa_sample_count)));
     }
 
     pub fn play(&mut self) {
@@ -66,16 +129,113 @@ impl Ruffle {
         });
     }
 
+    /// Sets the master volume of the player, ranging from 0.0 to 1.0.
+    /// This is independent of any volume set by the SWF.
+    pub fn set_volume(&mut self, volume: f32) {
+        INSTANCES.with(|instances| {
+            let mut instances = instances.borrow_mut();
+            if let Some(instance) = instances.get_mut(self.0) {
+                instance.core.lock().unwrap().set_volume(volume);
+            }
+        });
+    }
+
+    /// Sets whether the stage is only scaled by whole numbers, with bitmaps drawn without
+    /// smoothing, so that pixel art stays crisp.
+    pub fn set_pixel_perfect(&mut self, enabled: bool) {
+        INSTANCES.with(|instances| {
+            let mut instances = instances.borrow_mut();
+            if let Some(instance) = instances.get_mut(self.0) {
+                instance.core.lock().unwrap().set_pixel_perfect(enabled);
+            }
+        });
+    }
+
+    /// Mutes or unmutes all audio from the player.
+    pub fn set_mute(&mut self, is_muted: bool) {
+        INSTANCES.with(|instances| {
+            let mut instances = instances.borrow_mut();
+            if let Some(instance) = instances.get_mut(self.0) {
+                instance.core.lock().unwrap().set_mute(is_muted);
+            }
+        });
+    }
+
+    /// Builds the context menu for the object under the mouse cursor.
+    ///
+    /// Returns an array of items, each with `caption`, `enabled`, `checked` and
+    /// `separatorBefore` properties. The index of the chosen item should be
+    /// passed to `run_context_menu_callback`.
+    pub fn prepare_context_menu(&mut self) -> Array {
+        let items = Array::new();
+        INSTANCES.with(|instances| {
+            let mut instances = instances.borrow_mut();
+            if let Some(instance) = instances.get_mut(self.0) {
+                let menu = instance.core.lock().unwrap().prepare_context_menu();
+                for item in menu {
+                    let object = Object::new();
+                    let _ = Reflect::set(&object, &"caption".into(), &item.caption.into());
+                    let _ = Reflect::set(&object, &"enabled".into(), &item.enabled.into());
+                    let _ = Reflect::set(&object, &"checked".into(), &item.checked.into());
+                    let _ = Reflect::set(
+                        &object,
+                        &"separatorBefore".into(),
+                        &item.separator_before.into(),
+                    );
+                    items.push(&object);
+                }
+            }
+        });
+        items
+    }
+
+    /// Runs the action of the context menu item at the given index.
+    pub fn run_context_menu_callback(&mut self, index: usize) {
+        INSTANCES.with(|instances| {
+            let mut instances = instances.borrow_mut();
+            if let Some(instance) = instances.get_mut(self.0) {
+                instance
+                    .core
+                    .lock()
+                    .unwrap()
+                    .run_context_menu_callback(index);
+            }
+        });
+    }
+
+    /// Calls a function that the movie exposed with `ExternalInterface.addCallback`.
+    ///
+    /// If the player is busy, e.g. because the movie is calling the page, the call is queued
+    /// until the player is free, and `undefined` is returned.
+    pub fn call_exposed_callback(&self, name: &str, args: Array) -> JsValue {
+        let args: Vec<_> = args.iter().map(|arg| js_to_external(&arg)).collect();
+        match self.try_call_exposed_callback(name, &args) {
+            Some(result) => {
+                Ruffle::run_pending_callbacks();
+                external_to_js(&result)
+            }
+            None => {
+                PENDING_CALLBACKS.with(|pending| {
+                    pending.borrow_mut().push(PendingCallback {
+                        instance: self.0,
+                        name: name.to_string(),
+                        args,
+                    })
+                });
+                JsValue::UNDEFINED
+            }
+        }
+    }
+
     pub fn destroy(&mut self) -> Result<(), JsValue> {
         // Remove instance from the active list.
         if let Some(instance) = INSTANCES.with(|instances| {
             let mut instances = instances.borrow_mut();
             instances.remove(self.0)
         }) {
-            // Stop all audio playing from the instance
-            let mut player = instance.core.lock().unwrap();
-            let audio = player.audio_mut();
-            audio.stop_all_sounds();
+            // Stop all audio playing from the instance, and cancel its tasks before the
+            // executor gets to poll them again.
+            instance.core.lock().unwrap().shutdown();
 
             // Cancel the animation handler, if it's still active.
             if let Some(id) = instance.animation_handler_id {
@@ -94,6 +254,8 @@ impl Ruffle {
     fn new_internal(
         canvas: HtmlCanvasElement,
         swf_data: Uint8Array,
+        js_player: JsValue,
+        movie_url: Option<String>,
     ) -> Result<Ruffle, Box<dyn Error>> {
         console_error_panic_hook::set_once();
         let _ = console_log::init_with_level(log::Level::Trace);
@@ -101,24 +263,35 @@ impl Ruffle {
         let movie = {
             let mut data = vec![0; swf_data.length() as usize];
             swf_data.copy_to(&mut data[..]);
-            SwfMovie::from_data(&data)?
+            SwfMovie::from_data(&data, movie_url)?
         };
 
         let window = web_sys::window().ok_or_else(|| "Expected window")?;
         let renderer = create_renderer(&canvas)?;
         let audio = Box::new(WebAudioBackend::new()?);
-        let navigator = Box::new(WebNavigatorBackend::new());
-        let input = Box::new(WebInputBackend::new(&canvas));
-
-        let core = ruffle_core::Player::new(renderer, audio, navigator, input, movie)?;
+        let (executor, spawner) = WebAsyncExecutor::new();
+        let navigator = Box::new(WebNavigatorBackend::new(spawner));
+        let input = Box::new(WebInputBackend::new());
+        let ui = Box::new(WebUiBackend::new(&canvas));
+        let log = Box::new(WebLogBackend::new());
+        let storage = Box::new(WebStorageBackend::new());
+
+        let core =
+            ruffle_core::Player::new(renderer, audio, navigator, input, ui, log, storage, movie)?;
         let mut core_lock = core.lock().unwrap();
         let frame_rate = core_lock.frame_rate();
         core_lock.audio_mut().set_frame_rate(frame_rate);
+        core_lock.set_external_interface(Box::new(JavascriptInterface::new(js_player)));
+        core_lock.set_time_zone(Box::new(WebTimeZone));
+        if let Some(locale) = window.navigator().language() {
+            core_lock.set_locale(locale);
+        }
         drop(core_lock);
 
         // Create instance.
         let instance = RuffleInstance {
             core,
+            executor,
             canvas: canvas.clone(),
             canvas_width: 0, // Intiailize canvas width and height to 0 to force an initial canvas resize.
             canvas_height: 0,
@@ -129,6 +302,7 @@ impl Ruffle {
             mouse_down_callback: None,
             window_mouse_down_callback: None,
             mouse_up_callback: None,
+            mouse_leave_callback: None,
             key_down_callback: None,
             key_up_callback: None,
             timestamp: None,
@@ -190,6 +364,16 @@ impl Ruffle {
                         let mut instances = instances.borrow_mut();
                         if let Some(instance) = instances.get_mut(index) {
                             instance.has_focus = true;
+
+                            // Lock keys may have been toggled while the player didn't have focus.
+                            let modifiers =
+                                input::web_key_modifiers(|key| js_event.get_modifier_state(key));
+                            instance
+                                .core
+                                .lock()
+                                .unwrap()
+                                .handle_event(PlayerEvent::ModifiersChanged { modifiers });
+
                             if let Some(target) = js_event.current_target() {
                                 let _ = target
                                     .unchecked_ref::<Element>()
@@ -275,6 +459,32 @@ impl Ruffle {
                 instance.mouse_up_callback = Some(mouse_up_callback);
             }
 
+            // Create mouse leave handler.
+            {
+                let mouse_leave_callback = Closure::wrap(Box::new(move |_js_event: PointerEvent| {
+                    INSTANCES.with(move |instances| {
+                        let mut instances = instances.borrow_mut();
+                        if let Some(instance) = instances.get_mut(index) {
+                            instance
+                                .core
+                                .lock()
+                                .unwrap()
+                                .handle_event(PlayerEvent::MouseLeave);
+                        }
+                    });
+                })
+                    as Box<dyn FnMut(PointerEvent)>);
+                let canvas_events: &EventTarget = canvas.as_ref();
+                canvas_events
+                    .add_event_listener_with_callback(
+                        "pointerleave",
+                        mouse_leave_callback.as_ref().unchecked_ref(),
+                    )
+                    .unwrap();
+                let instance = instances.get_mut(index).unwrap();
+                instance.mouse_leave_callback = Some(mouse_leave_callback);
+            }
+
             // Create click event handler.
             // {
             //     let click_callback = Closure::wrap(Box::new(move |_| {
@@ -305,19 +515,26 @@ impl Ruffle {
                     INSTANCES.with(|instances| {
                         if let Some(instance) = instances.borrow_mut().get_mut(index) {
                             if instance.has_focus {
-                                let code = js_event.code();
+                                let modifiers = input::web_key_modifiers(|key| {
+                                    js_event.get_modifier_state(key)
+                                });
                                 instance
+                                    .core
+                                    .lock()
+                                    .unwrap()
+                                    .handle_event(PlayerEvent::ModifiersChanged { modifiers });
+
+                                let key = js_event.key();
+                                let key_code = instance
                                     .core
                                     .lock()
                                     .unwrap()
                                     .input_mut()
                                     .downcast_mut::<WebInputBackend>()
                                     .unwrap()
-                                    .keydown(code.clone());
+                                    .keydown(js_event.code(), &key);
 
-                                if let Some(codepoint) =
-                                    input::web_key_to_codepoint(&js_event.key())
-                                {
+                                if let Some(codepoint) = input::web_key_to_codepoint(&key) {
                                     instance
                                         .core
                                         .lock()
@@ -325,7 +542,7 @@ impl Ruffle {
                                         .handle_event(PlayerEvent::TextInput { codepoint });
                                 }
 
-                                if let Some(key_code) = input::web_to_ruffle_key_code(&code) {
+                                if let Some(key_code) = key_code {
                                     instance
                                         .core
                                         .lock()
@@ -356,17 +573,25 @@ impl Ruffle {
                     INSTANCES.with(|instances| {
                         if let Some(instance) = instances.borrow_mut().get_mut(index) {
                             if instance.has_focus {
-                                let code = js_event.code();
+                                let modifiers = input::web_key_modifiers(|key| {
+                                    js_event.get_modifier_state(key)
+                                });
                                 instance
+                                    .core
+                                    .lock()
+                                    .unwrap()
+                                    .handle_event(PlayerEvent::ModifiersChanged { modifiers });
+
+                                let key_code = instance
                                     .core
                                     .lock()
                                     .unwrap()
                                     .input_mut()
                                     .downcast_mut::<WebInputBackend>()
                                     .unwrap()
-                                    .keyup(code.clone());
+                                    .keyup(js_event.code());
 
-                                if let Some(key_code) = input::web_to_ruffle_key_code(&code) {
+                                if let Some(key_code) = key_code {
                                     instance
                                         .core
                                         .lock()
@@ -420,7 +645,6 @@ impl Ruffle {
 
                 let mut core_lock = instance.core.lock().unwrap();
                 core_lock.tick(dt);
-                let mut needs_render = core_lock.needs_render();
 
                 // Check for canvas resize.
                 let canvas_width = instance.canvas.client_width();
@@ -449,13 +673,16 @@ impl Ruffle {
                     core_lock
                         .renderer_mut()
                         .set_viewport_dimensions(viewport_width, viewport_height);
-
-                    // Force a re-render if we resize.
-                    needs_render = true;
                 }
 
-                if needs_render {
-                    core_lock.render();
+                // Only draws if the frame or the viewport changed.
+                core_lock.render();
+
+                // Poll any pending futures once the player is unlocked, as they may need
+                // to lock it themselves.
+                drop(core_lock);
+                if let Ok(mut executor) = instance.executor.try_borrow_mut() {
+                    executor.poll_all();
                 }
 
                 // Request next animation frame.
@@ -470,6 +697,47 @@ impl Ruffle {
                 }
             }
         });
+
+        // Callbacks from the page may have been queued while the player was running.
+        Ruffle::run_pending_callbacks();
+    }
+
+    /// Runs a callback exposed by the movie, unless its player is busy.
+    ///
+    /// Returns `None` if the callback has to wait until the player is free.
+    fn try_call_exposed_callback(
+        &self,
+        name: &str,
+        args: &[ExternalValue],
+    ) -> Option<ExternalValue> {
+        INSTANCES.with(|instances| {
+            let instances = instances.try_borrow().ok()?;
+            match instances.get(self.0) {
+                Some(instance) => {
+                    let mut core = instance.core.try_lock().ok()?;
+                    Some(core.call_internal_interface(name, args))
+                }
+                // The player was destroyed, so there is nothing to wait for.
+                None => Some(ExternalValue::Undefined),
+            }
+        })
+    }
+
+    /// Runs the callbacks that were queued while their players were busy.
+    fn run_pending_callbacks() {
+        let pending = PENDING_CALLBACKS.with(|pending| {
+            let mut pending = pending.borrow_mut();
+            std::mem::take(&mut *pending)
+        });
+        for callback in pending {
+            let ruffle = Ruffle(callback.instance);
+            if ruffle
+                .try_call_exposed_callback(&callback.name, &callback.args)
+                .is_none()
+            {
+                PENDING_CALLBACKS.with(|pending| pending.borrow_mut().push(callback));
+            }
+        }
     }
 }
 
@@ -479,7 +747,16 @@ fn create_renderer(canvas: &HtmlCanvasElement) -> Result<Box<dyn RenderBackend>,
 
     #[cfg(feature = "webgl")]
     {
-        if let Ok(renderer) = ruffle_render_webgl::WebGlRenderBackend::new(canvas) {
+        let renderer = match MSAA_SAMPLE_COUNT.with(|count| count.get()) {
+            Some(msaa_sample_count) => {
+                ruffle_render_webgl::WebGlRenderBackend::with_msaa_sample_count(
+                    canvas,
+                    msaa_sample_count,
+                )
+            }
+            None => ruffle_render_webgl::WebGlRenderBackend::new(canvas),
+        };
+        if let Ok(renderer) = renderer {
             return Ok(Box::new(renderer));
         }
     }
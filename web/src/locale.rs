@@ -0,0 +1,20 @@
+//! Time zone and locale of the browser.
+
+use js_sys::Date;
+use ruffle_core::backend::locale::TimeZoneProvider;
+use wasm_bindgen::JsValue;
+
+/// The time zone of the browser.
+pub struct WebTimeZone;
+
+impl TimeZoneProvider for WebTimeZone {
+    fn utc_offset(&self, utc_time: f64) -> i32 {
+        // `getTimezoneOffset` is how far UTC is ahead of local time, rather than behind.
+        let offset = Date::new(&JsValue::from_f64(utc_time)).get_timezone_offset();
+        if offset.is_finite() {
+            -offset as i32
+        } else {
+            -Date::new_0().get_timezone_offset() as i32
+        }
+    }
+}
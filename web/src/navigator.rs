@@ -1,28 +1,33 @@
 //! Navigator backend for web
 
+use crate::executor::TaskSpawner;
 use js_sys::{Array, ArrayBuffer, Uint8Array};
 use ruffle_core::backend::navigator::{
-    Error, NavigationMethod, NavigatorBackend, OwnedFuture, RequestOptions,
+    CancellationToken, Error, HttpStatusError, NavigationMethod, NavigatorBackend, OwnedFuture,
+    ProgressCallback, RequestOptions, SpawnHandle,
 };
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::time::Duration;
 use wasm_bindgen::JsCast;
-use wasm_bindgen_futures::{spawn_local, JsFuture};
-use web_sys::{window, Blob, BlobPropertyBag, Performance, Request, RequestInit, Response};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{window, Blob, BlobPropertyBag, Performance, Request, RequestInit, Response, Url};
 
 pub struct WebNavigatorBackend {
     performance: Performance,
     start_time: f64,
+    spawner: TaskSpawner,
 }
 
 impl WebNavigatorBackend {
-    pub fn new() -> Self {
+    pub fn new(spawner: TaskSpawner) -> Self {
         let window = web_sys::window().expect("window()");
         let performance = window.performance().expect("window.performance()");
 
         WebNavigatorBackend {
             start_time: performance.now(),
             performance,
+            spawner,
         }
     }
 }
@@ -93,6 +98,15 @@ impl NavigatorBackend for WebNavigatorBackend {
     }
 
     fn fetch(&self, url: String, options: RequestOptions) -> OwnedFuture<Vec<u8>, Error> {
+        self.fetch_with_progress(url, options, Box::new(|_, _| {}))
+    }
+
+    fn fetch_with_progress(
+        &self,
+        url: String,
+        options: RequestOptions,
+        mut progress: ProgressCallback,
+    ) -> OwnedFuture<Vec<u8>, Error> {
         Box::pin(async move {
             let mut init = RequestInit::new();
 
@@ -125,6 +139,11 @@ impl NavigatorBackend for WebNavigatorBackend {
             }
 
             let request = Request::new_with_str_and_init(&url, &init).unwrap();
+            for (name, value) in options.headers() {
+                if request.headers().set(name, value).is_err() {
+                    log::warn!("Unable to set request header {}", name);
+                }
+            }
 
             let window = web_sys::window().unwrap();
             let fetchval = JsFuture::from(window.fetch_with_request(&request)).await;
@@ -133,6 +152,19 @@ impl NavigatorBackend for WebNavigatorBackend {
             }
 
             let resp: Response = fetchval.unwrap().dyn_into().unwrap();
+            if !resp.ok() {
+                return Err(HttpStatusError(resp.status()).into());
+            }
+
+            // The body arrives in one piece, but its size is known as soon as the headers are.
+            let total = resp
+                .headers()
+                .get("Content-Length")
+                .ok()
+                .flatten()
+                .and_then(|length| length.parse().ok());
+            progress(0, total);
+
             let data: ArrayBuffer = JsFuture::from(resp.array_buffer().unwrap())
                 .await
                 .unwrap()
@@ -141,16 +173,34 @@ impl NavigatorBackend for WebNavigatorBackend {
             let jsarray = Uint8Array::new(&data);
             let mut rust_array = vec![0; jsarray.length() as usize];
             jsarray.copy_to(&mut rust_array);
+            progress(rust_array.len(), Some(rust_array.len()));
 
             Ok(rust_array)
         })
     }
 
-    fn spawn_future(&mut self, future: OwnedFuture<(), Error>) {
-        spawn_local(async move {
-            if let Err(e) = future.await {
-                log::error!("Asynchronous error occured: {}", e);
-            }
-        })
+    fn spawn_future(
+        &mut self,
+        future: OwnedFuture<(), Error>,
+        token: CancellationToken,
+    ) -> SpawnHandle {
+        let token = token.child();
+        self.spawner.spawn(future, token.clone());
+        SpawnHandle::new(token)
+    }
+
+    fn drain_futures(&mut self, _timeout: Duration) {
+        self.spawner.drain();
+    }
+
+    fn resolve_relative_url<'a>(&self, url: &'a str) -> Cow<'a, str> {
+        // Requests are made relative to the page's base URL.
+        let base = window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.base_uri().ok().flatten());
+        match base.and_then(|base| Url::new_with_base(url, &base).ok()) {
+            Some(resolved) => resolved.href().into(),
+            None => url.into(),
+        }
     }
 }
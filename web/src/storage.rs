@@ -0,0 +1,381 @@
+//! Storage backend for web
+//!
+//! Shared objects are kept in IndexedDB, or in localStorage when IndexedDB is unavailable.
+//! Both are scoped to the origin of the page. ActionScript reads and writes shared objects
+//! synchronously, so everything is loaded into a cache before the first player is created,
+//! and writes go through the cache before being persisted in the background.
+
+use js_sys::{Array, Promise, Uint8Array};
+use ruffle_core::backend::navigator::{Error, OwnedFuture};
+use ruffle_core::backend::storage::StorageBackend;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{IdbDatabase, IdbOpenDbRequest, IdbRequest, IdbTransactionMode, Storage};
+
+const DATABASE_NAME: &str = "ruffle";
+const STORE_NAME: &str = "shared_objects";
+
+/// Prefix of the keys of shared objects stored in localStorage.
+const LOCAL_STORAGE_PREFIX: &str = "ruffle_shared_object:";
+
+/// The default size limit of all the shared objects of a page, in bytes.
+/// Flash Player allows 100 KB per site without asking the user.
+pub const DEFAULT_QUOTA: usize = 100 * 1024;
+
+thread_local! {
+    /// The data of every shared object of this page.
+    static CACHE: RefCell<HashMap<String, Vec<u8>>> = RefCell::new(HashMap::new());
+
+    /// Whether `CACHE` has been loaded.
+    static LOADED: Cell<bool> = Cell::new(false);
+
+    /// The IndexedDB database, or `None` if shared objects are kept in localStorage.
+    static DATABASE: RefCell<Option<IdbDatabase>> = RefCell::new(None);
+
+    /// The size limit of all the shared objects of this page, in bytes.
+    static QUOTA: Cell<usize> = Cell::new(DEFAULT_QUOTA);
+}
+
+/// Sets the size limit of all the shared objects of this page, in bytes.
+pub fn set_quota(quota: usize) {
+    QUOTA.with(|q| q.set(quota));
+}
+
+/// Loads every stored shared object into the cache.
+/// This must complete before any player is created, and does nothing if it already did.
+pub async fn load() {
+    if LOADED.with(|loaded| loaded.get()) {
+        return;
+    }
+
+    let data = match open_database().await {
+        Ok(database) => {
+            let data = load_from_database(&database).await;
+            DATABASE.with(|db| *db.borrow_mut() = Some(database));
+            data
+        }
+        Err(e) => {
+            log::warn!(
+                "Unable to open IndexedDB, falling back to localStorage: {:?}",
+                e
+            );
+            Ok(load_from_local_storage())
+        }
+    };
+
+    let data = data.unwrap_or_else(|e| {
+        log::error!("Unable to load shared objects: {:?}", e);
+        HashMap::new()
+    });
+    CACHE.with(|cache| *cache.borrow_mut() = data);
+    LOADED.with(|loaded| loaded.set(true));
+}
+
+/// Wait for an IndexedDB request to complete.
+async fn request_result(request: &IdbRequest) -> Result<JsValue, JsValue> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        request.set_onsuccess(Some(&resolve));
+        request.set_onerror(Some(&reject));
+    });
+    JsFuture::from(promise).await?;
+    request.result()
+}
+
+async fn open_database() -> Result<IdbDatabase, JsValue> {
+    let factory = web_sys::window()
+        .ok_or_else(|| JsValue::from("Expected window"))?
+        .indexed_db()?
+        .ok_or_else(|| JsValue::from("IndexedDB is unavailable"))?;
+    let request: IdbOpenDbRequest = factory.open_with_u32(DATABASE_NAME, 1)?;
+
+    let upgrade_request = request.clone();
+    let on_upgrade_needed = Closure::once_into_js(move |_event: JsValue| {
+        if let Ok(database) = upgrade_request.result() {
+            let database: IdbDatabase = database.unchecked_into();
+            if let Err(e) = database.create_object_store(STORE_NAME) {
+                log::error!("Unable to create shared object store: {:?}", e);
+            }
+        }
+    });
+    request.set_onupgradeneeded(Some(on_upgrade_needed.unchecked_ref()));
+
+    let database = request_result(&request).await;
+    request.set_onupgradeneeded(None);
+    Ok(database?.unchecked_into())
+}
+
+async fn load_from_database(database: &IdbDatabase) -> Result<HashMap<String, Vec<u8>>, JsValue> {
+    let store = database
+        .transaction_with_str(STORE_NAME)?
+        .object_store(STORE_NAME)?;
+    // Both requests are made before waiting, so that they run in the same transaction.
+    let keys_request = store.get_all_keys()?;
+    let values_request = store.get_all()?;
+    let keys: Array = request_result(&keys_request).await?.unchecked_into();
+    let values: Array = request_result(&values_request).await?.unchecked_into();
+
+    let mut data = HashMap::new();
+    for (key, value) in keys.iter().zip(values.iter()) {
+        if let (Some(key), Ok(value)) = (key.as_string(), value.dyn_into::<Uint8Array>()) {
+            data.insert(key, value.to_vec());
+        }
+    }
+    Ok(data)
+}
+
+fn local_storage() -> Option<Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+fn load_from_local_storage() -> HashMap<String, Vec<u8>> {
+    let mut data = HashMap::new();
+    let (storage, window) = match (local_storage(), web_sys::window()) {
+        (Some(storage), Some(window)) => (storage, window),
+        _ => return data,
+    };
+
+    for i in 0..storage.length().unwrap_or(0) {
+        let key = match storage.key(i) {
+            Ok(Some(key)) if key.starts_with(LOCAL_STORAGE_PREFIX) => key,
+            _ => continue,
+        };
+        // localStorage only holds strings, so the data is stored as base64.
+        if let Some(value) = storage
+            .get_item(&key)
+            .ok()
+            .flatten()
+            .and_then(|value| window.atob(&value).ok())
+        {
+            data.insert(
+                key[LOCAL_STORAGE_PREFIX.len()..].to_string(),
+                value.chars().map(|c| c as u8).collect(),
+            );
+        }
+    }
+    data
+}
+
+fn store_in_local_storage(key: &str, data: &[u8]) -> Result<(), JsValue> {
+    let window = web_sys::window().ok_or_else(|| JsValue::from("Expected window"))?;
+    let storage = local_storage().ok_or_else(|| JsValue::from("localStorage is unavailable"))?;
+    let value = window.btoa(&data.iter().map(|&b| char::from(b)).collect::<String>())?;
+    storage.set_item(&format!("{}{}", LOCAL_STORAGE_PREFIX, key), &value)
+}
+
+fn store_in_database(
+    database: &IdbDatabase,
+    key: &str,
+    data: &[u8],
+) -> Result<IdbRequest, JsValue> {
+    database
+        .transaction_with_str_and_mode(STORE_NAME, IdbTransactionMode::Readwrite)?
+        .object_store(STORE_NAME)?
+        .put_with_key(&Uint8Array::from(data), &key.into())
+}
+
+fn remove_from_database(database: &IdbDatabase, key: &str) -> Result<IdbRequest, JsValue> {
+    database
+        .transaction_with_str_and_mode(STORE_NAME, IdbTransactionMode::Readwrite)?
+        .object_store(STORE_NAME)?
+        .delete(&key.into())
+}
+
+/// Storage backend shared by all the players of a page.
+#[derive(Default)]
+pub struct WebStorageBackend;
+
+impl WebStorageBackend {
+    pub fn new() -> Self {
+        if !LOADED.with(|loaded| loaded.get()) {
+            log::warn!("Shared objects were not loaded before creating the player");
+        }
+        WebStorageBackend
+    }
+}
+
+impl StorageBackend for WebStorageBackend {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        CACHE.with(|cache| cache.borrow().get(key).cloned())
+    }
+
+    fn put(&mut self, key: &str, data: Vec<u8>) -> OwnedFuture<(), Error> {
+        // The quota covers every shared object of the page, counting the data that would
+        // replace what is stored under `key`.
+        let size = CACHE.with(|cache| {
+            cache
+                .borrow()
+                .iter()
+                .filter(|(k, _)| k.as_str() != key)
+                .map(|(k, v)| k.len() + v.len())
+                .sum::<usize>()
+        }) + key.len()
+            + data.len();
+        let quota = QUOTA.with(|quota| quota.get());
+        if size > quota {
+            return Box::pin(async move {
+                Err(format!("{} bytes exceed the quota of {} bytes", size, quota).into())
+            });
+        }
+
+        let request = DATABASE.with(|database| match &*database.borrow() {
+            Some(database) => store_in_database(database, key, &data).map(Some),
+            None => store_in_local_storage(key, &data).map(|_| None),
+        });
+        CACHE.with(|cache| cache.borrow_mut().insert(key.to_string(), data));
+
+        Box::pin(async move {
+            let result = match request {
+                Ok(Some(request)) => request_result(&request).await.map(|_| ()),
+                Ok(None) => Ok(()),
+                Err(e) => Err(e),
+            };
+            result.map_err(|e| format!("Unable to store shared object: {:?}", e).into())
+        })
+    }
+
+    fn remove(&mut self, key: &str) {
+        CACHE.with(|cache| cache.borrow_mut().remove(key));
+        let result = DATABASE.with(|database| match &*database.borrow() {
+            Some(database) => remove_from_database(database, key).map(|_| ()),
+            None => local_storage()
+                .map(|storage| storage.remove_item(&format!("{}{}", LOCAL_STORAGE_PREFIX, key)))
+                .unwrap_or(Ok(())),
+        });
+        if let Err(e) = result {
+            log::warn!("Unable to remove shared object {}: {:?}", key, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ruffle, INSTANCES};
+    use js_sys::{Function, Reflect};
+    use ruffle_core::amf::AmfValue;
+    use std::rc::Rc;
+    use wasm_bindgen_test::*;
+    use web_sys::HtmlCanvasElement;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    /// Increments `count` in the `test` shared object, and calls `ruffleTestStatus` from
+    /// `onStatus` with the result of `flush`.
+    const SWF: &[u8] = include_bytes!("../../core/tests/swfs/avm1/shared_object/test.swf");
+
+    /// Forgets everything loaded from storage, like reloading the page does.
+    fn reload_page() {
+        CACHE.with(|cache| cache.borrow_mut().clear());
+        LOADED.with(|loaded| loaded.set(false));
+        if let Some(database) = DATABASE.with(|database| database.borrow_mut().take()) {
+            database.close();
+        }
+    }
+
+    /// Runs the first frame of the test movie, as if it was loaded from `url`.
+    fn run_movie(url: &str) -> Ruffle {
+        let canvas: HtmlCanvasElement = web_sys::window()
+            .unwrap()
+            .document()
+            .unwrap()
+            .create_element("canvas")
+            .unwrap()
+            .dyn_into()
+            .unwrap();
+        let ruffle = Ruffle::new(
+            canvas,
+            Uint8Array::from(SWF),
+            JsValue::UNDEFINED,
+            Some(url.to_string()),
+        )
+        .unwrap();
+
+        INSTANCES.with(|instances| {
+            let instances = instances.borrow();
+            let instance = instances.get(ruffle.0).unwrap();
+            instance.core.lock().unwrap().run_frame();
+        });
+        ruffle
+    }
+
+    /// Waits for the test movie to report the result of `flush`.
+    async fn flush_status() -> String {
+        let window = web_sys::window().unwrap();
+        let status = Rc::new(RefCell::new(None));
+        let callback = {
+            let status = status.clone();
+            Closure::wrap(Box::new(move |code: String| {
+                *status.borrow_mut() = Some(code);
+            }) as Box<dyn FnMut(String)>)
+        };
+        Reflect::set(&window, &"ruffleTestStatus".into(), callback.as_ref()).unwrap();
+
+        for _ in 0..100 {
+            if let Some(code) = status.borrow_mut().take() {
+                return code;
+            }
+            let timeout = Promise::new(&mut |resolve: Function, _| {
+                window
+                    .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, 10)
+                    .unwrap();
+            });
+            JsFuture::from(timeout).await.unwrap();
+        }
+        panic!("The movie never received onStatus");
+    }
+
+    /// The `count` stored in the `test` shared object under `key`.
+    fn stored_count(key: &str) -> Option<f64> {
+        let data = CACHE.with(|cache| cache.borrow().get(key).cloned())?;
+        match AmfValue::from_bytes(&data).unwrap() {
+            AmfValue::Object(properties) => {
+                properties
+                    .into_iter()
+                    .find_map(|(name, value)| match (name.as_str(), value) {
+                        ("count", AmfValue::Number(count)) => Some(count),
+                        _ => None,
+                    })
+            }
+            _ => None,
+        }
+    }
+
+    #[wasm_bindgen_test]
+    async fn shared_objects_persist_across_reloads() {
+        let key = "example.com/reload/test.swf/test";
+        load().await;
+        WebStorageBackend::new().remove(key);
+
+        for count in 1..=2 {
+            let mut ruffle = run_movie("https://example.com/reload/test.swf");
+            assert_eq!(flush_status().await, "SharedObject.Flush.Success");
+            assert_eq!(stored_count(key), Some(count as f64));
+            ruffle.destroy().unwrap();
+
+            reload_page();
+            load().await;
+            assert!(DATABASE.with(|database| database.borrow().is_some()));
+            assert_eq!(stored_count(key), Some(count as f64));
+        }
+    }
+
+    #[wasm_bindgen_test]
+    async fn flush_fails_over_quota() {
+        let key = "example.com/quota/test.swf/test";
+        load().await;
+        WebStorageBackend::new().remove(key);
+        set_quota(8);
+
+        let mut ruffle = run_movie("https://example.com/quota/test.swf");
+        assert_eq!(flush_status().await, "SharedObject.Flush.Failed");
+        assert_eq!(stored_count(key), None);
+        ruffle.destroy().unwrap();
+        set_quota(DEFAULT_QUOTA);
+
+        reload_page();
+        load().await;
+        assert_eq!(stored_count(key), None);
+    }
+}
@@ -0,0 +1,47 @@
+use ruffle_core::backend::log::{LogBackend, TraceContext};
+use web_sys::console;
+
+/// An implementation of `LogBackend` that prints traced messages to the browser console.
+///
+/// Consecutive messages from the same movie are grouped under that movie's URL, and each
+/// message is annotated with the clip and frame that traced it.
+#[derive(Default)]
+pub struct WebLogBackend {
+    /// The URL of the movie whose messages are currently being grouped, if any.
+    group: Option<Option<String>>,
+}
+
+impl WebLogBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl LogBackend for WebLogBackend {
+    fn avm_trace(&mut self, message: &str) {
+        if self.group.take().is_some() {
+            console::group_end();
+        }
+        console::log_1(&message.into());
+    }
+
+    fn avm_trace_with_context(&mut self, message: &str, context: &TraceContext) {
+        if self.group.as_ref() != Some(&context.movie_url) {
+            if self.group.is_some() {
+                console::group_end();
+            }
+            let label = context.movie_url.as_deref().unwrap_or("(unknown movie)");
+            console::group_collapsed_1(&label.into());
+            self.group = Some(context.movie_url.clone());
+        }
+        console::log_2(&message.into(), &format!("({})", context).into());
+    }
+}
+
+impl Drop for WebLogBackend {
+    fn drop(&mut self) {
+        if self.group.is_some() {
+            console::group_end();
+        }
+    }
+}
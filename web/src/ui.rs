@@ -0,0 +1,129 @@
+use ruffle_core::backend::navigator::OwnedFuture;
+use ruffle_core::backend::ui::{DisplayState, Error, MessageLevel, MouseCursor, UiBackend};
+use ruffle_web_common::JsResult;
+use web_sys::HtmlCanvasElement;
+
+/// An implementation of `UiBackend` utilizing `web_sys` bindings to browser dialogs and the
+/// Fullscreen API.
+pub struct WebUiBackend {
+    canvas: HtmlCanvasElement,
+    cursor_visible: bool,
+    cursor: MouseCursor,
+}
+
+impl WebUiBackend {
+    pub fn new(canvas: &HtmlCanvasElement) -> Self {
+        Self {
+            canvas: canvas.clone(),
+            cursor_visible: true,
+            cursor: MouseCursor::Arrow,
+        }
+    }
+
+    fn update_mouse_cursor(&self) {
+        let cursor = if self.cursor_visible {
+            match self.cursor {
+                MouseCursor::Arrow => "auto",
+                MouseCursor::Hand => "pointer",
+                MouseCursor::IBeam => "text",
+                MouseCursor::Grab => "grab",
+            }
+        } else {
+            "none"
+        };
+        self.canvas
+            .style()
+            .set_property("cursor", cursor)
+            .warn_on_error();
+    }
+}
+
+impl UiBackend for WebUiBackend {
+    fn message(
+        &mut self,
+        level: MessageLevel,
+        message: &str,
+        choices: &[&str],
+    ) -> OwnedFuture<Option<usize>, Error> {
+        match level {
+            MessageLevel::Info => log::info!("{}", message),
+            MessageLevel::Warning => log::warn!("{}", message),
+            MessageLevel::Error => log::error!("{}", message),
+        }
+
+        // Browsers only offer OK/Cancel dialogs, which can stand in for a choice of two.
+        let window = web_sys::window().expect("window()");
+        let choice = match choices {
+            [] => {
+                window.alert_with_message(message).warn_on_error();
+                None
+            }
+            [ok, cancel] => {
+                let prompt = format!(
+                    "{}\n\nPress OK to {}, or Cancel to {}.",
+                    message,
+                    ok.to_lowercase(),
+                    cancel.to_lowercase()
+                );
+                match window.confirm_with_message(&prompt) {
+                    Ok(true) => Some(0),
+                    Ok(false) => Some(1),
+                    Err(_) => None,
+                }
+            }
+            _ => {
+                log::warn!(
+                    "Can't ask the user to pick one of {} choices",
+                    choices.len()
+                );
+                None
+            }
+        };
+        Box::pin(async move { Ok(choice) })
+    }
+
+    fn set_clipboard_content(&mut self, _content: String) {
+        // TODO: The asynchronous Clipboard API is still unstable in web-sys.
+        log::warn!("System.setClipboard: unimplemented");
+    }
+
+    fn mouse_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    fn set_mouse_visible(&mut self, visible: bool) {
+        self.cursor_visible = visible;
+        self.update_mouse_cursor();
+    }
+
+    fn set_mouse_cursor(&mut self, cursor: MouseCursor) {
+        self.cursor = cursor;
+        self.update_mouse_cursor();
+    }
+
+    fn display_state(&self) -> DisplayState {
+        let is_fullscreen = web_sys::window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.fullscreen_element())
+            .is_some();
+        if is_fullscreen {
+            DisplayState::FullScreen
+        } else {
+            DisplayState::Normal
+        }
+    }
+
+    fn set_display_state(&mut self, state: DisplayState) -> Result<(), Error> {
+        let document = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or("No document")?;
+        let result = match state {
+            DisplayState::Normal => {
+                document.exit_fullscreen();
+                Ok(())
+            }
+            DisplayState::FullScreen => self.canvas.request_fullscreen(),
+        };
+        result.map_err(|_| "Fullscreen request was denied".into())
+    }
+}